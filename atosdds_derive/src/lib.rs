@@ -0,0 +1,267 @@
+//! Derive macros for [RustDDS](https://github.com/jhelovuo/RustDDS).
+//!
+//! Use these through their re-exports `rustdds::dds::traits::{DdsType, Key, Keyed}`. The
+//! generated code refers to `rustdds` and `serde`, so both must be dependencies of the
+//! crate using the derives.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+  parse_macro_input, Attribute, Data, DeriveInput, Error, Field, Fields, GenericArgument, Index,
+  Lit, Meta, NestedMeta, PathArguments, Result, Type,
+};
+
+/// Derives `Keyed` for a struct whose key consists of fields marked with `#[key]`.
+///
+/// A key struct named `<Type>Key` is generated next to the type. It has the key fields
+/// in declaration order, so that they are serialized, and thus key hashed, in that
+/// order. It derives `Clone`, `Eq`, `Ord`, `Hash` and serde traits, and implements
+/// `Key`. `#[serde(...)]` attributes of the key fields are copied to it, so that the
+/// key serializes like the key fields of the full type.
+///
+/// Each key field type must implement `Key`. Nested structs can derive it with
+/// `#[derive(Key)]`.
+#[proc_macro_derive(Keyed, attributes(key))]
+pub fn derive_keyed(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  keyed(&input)
+    .unwrap_or_else(|e| e.to_compile_error())
+    .into()
+}
+
+/// Derives `Key` for a struct or enum, which must also implement the other traits
+/// required by `Key`. The key may exceed 16 bytes if any of its fields may.
+#[proc_macro_derive(Key)]
+pub fn derive_key(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  key(&input).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+/// Derives `DdsType`. The type name is the name of the type, unless another one is
+/// given with `#[dds_type(name = "...")]`.
+#[proc_macro_derive(DdsType, attributes(dds_type))]
+pub fn derive_dds_type(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  dds_type(&input)
+    .unwrap_or_else(|e| e.to_compile_error())
+    .into()
+}
+
+fn keyed(input: &DeriveInput) -> Result<TokenStream2> {
+  no_generics(input, "Keyed")?;
+  let fields = match &input.data {
+    Data::Struct(data) => &data.fields,
+    _ => {
+      return Err(Error::new_spanned(
+        &input.ident,
+        "#[derive(Keyed)] is only supported for structs",
+      ))
+    }
+  };
+
+  let mut key_fields: Vec<(usize, &Field)> = Vec::new();
+  for (index, field) in fields.iter().enumerate() {
+    if let Some(attr) = field.attrs.iter().find(|a| a.path.is_ident("key")) {
+      if !attr.tokens.is_empty() {
+        return Err(Error::new_spanned(attr, "#[key] takes no arguments"));
+      }
+      check_key_type(&field.ty)?;
+      key_fields.push((index, field));
+    }
+  }
+  if key_fields.is_empty() {
+    return Err(Error::new_spanned(
+      &input.ident,
+      "#[derive(Keyed)] needs at least one field marked with #[key]",
+    ));
+  }
+
+  let ident = &input.ident;
+  let vis = &input.vis;
+  let key_ident = format_ident!("{}Key", ident);
+  let key_doc = format!("Key of [`{}`], generated by `#[derive(Keyed)]`.", ident);
+  let key_types: Vec<&Type> = key_fields.iter().map(|(_, f)| &f.ty).collect();
+
+  let (key_struct, get_key) = match fields {
+    Fields::Named(_) => {
+      let members = key_fields.iter().map(|(_, f)| {
+        let serde_attrs = serde_attributes(&f.attrs);
+        let field_vis = &f.vis;
+        let name = &f.ident;
+        let ty = &f.ty;
+        quote! { #(#serde_attrs)* #field_vis #name: #ty }
+      });
+      let names: Vec<_> = key_fields.iter().map(|(_, f)| &f.ident).collect();
+      (
+        quote! { #vis struct #key_ident { #(#members,)* } },
+        quote! { #key_ident { #( #names: ::std::clone::Clone::clone(&self.#names), )* } },
+      )
+    }
+    Fields::Unnamed(_) => {
+      let members = key_fields.iter().map(|(_, f)| {
+        let serde_attrs = serde_attributes(&f.attrs);
+        let field_vis = &f.vis;
+        let ty = &f.ty;
+        quote! { #(#serde_attrs)* #field_vis #ty }
+      });
+      let indices = key_fields.iter().map(|(i, _)| Index::from(*i));
+      (
+        quote! { #vis struct #key_ident ( #(#members),* ); },
+        quote! { #key_ident ( #( ::std::clone::Clone::clone(&self.#indices) ),* ) },
+      )
+    }
+    Fields::Unit => unreachable!("unit struct has no #[key] fields"),
+  };
+  let key_impl = key_impl(&key_ident, &key_types);
+
+  Ok(quote! {
+    #[doc = #key_doc]
+    #[derive(
+      ::std::clone::Clone,
+      ::std::cmp::PartialEq,
+      ::std::cmp::Eq,
+      ::std::cmp::PartialOrd,
+      ::std::cmp::Ord,
+      ::std::hash::Hash,
+      serde::Serialize,
+      serde::Deserialize,
+    )]
+    #key_struct
+
+    #key_impl
+
+    impl ::rustdds::dds::traits::Keyed for #ident {
+      type K = #key_ident;
+
+      fn get_key(&self) -> #key_ident {
+        #get_key
+      }
+    }
+  })
+}
+
+fn key(input: &DeriveInput) -> Result<TokenStream2> {
+  no_generics(input, "Key")?;
+  let types: Vec<&Type> = match &input.data {
+    Data::Struct(data) => data.fields.iter().map(|f| &f.ty).collect(),
+    Data::Enum(data) => data
+      .variants
+      .iter()
+      .flat_map(|v| v.fields.iter().map(|f| &f.ty))
+      .collect(),
+    Data::Union(_) => {
+      return Err(Error::new_spanned(
+        &input.ident,
+        "#[derive(Key)] is not supported for unions",
+      ))
+    }
+  };
+  for ty in types.iter() {
+    check_key_type(ty)?;
+  }
+  Ok(key_impl(&input.ident, &types))
+}
+
+fn dds_type(input: &DeriveInput) -> Result<TokenStream2> {
+  no_generics(input, "DdsType")?;
+  let mut type_name = input.ident.to_string();
+  for attr in input.attrs.iter().filter(|a| a.path.is_ident("dds_type")) {
+    type_name = dds_type_name(attr)?;
+  }
+
+  let ident = &input.ident;
+  Ok(quote! {
+    impl ::rustdds::dds::traits::DdsType for #ident {
+      fn type_name() -> &'static str {
+        #type_name
+      }
+    }
+  })
+}
+
+// The name of #[dds_type(name = "...")]
+fn dds_type_name(attr: &Attribute) -> Result<String> {
+  if let Meta::List(list) = attr.parse_meta()? {
+    let nested: Vec<&NestedMeta> = list.nested.iter().collect();
+    if let [NestedMeta::Meta(Meta::NameValue(name_value))] = nested.as_slice() {
+      if let (true, Lit::Str(name)) = (name_value.path.is_ident("name"), &name_value.lit) {
+        if name.value().is_empty() {
+          return Err(Error::new_spanned(name, "the type name must not be empty"));
+        }
+        return Ok(name.value());
+      }
+    }
+  }
+  Err(Error::new_spanned(
+    attr,
+    "expected #[dds_type(name = \"...\")]",
+  ))
+}
+
+fn key_impl(ident: &syn::Ident, types: &[&Type]) -> TokenStream2 {
+  quote! {
+    impl ::rustdds::dds::traits::Key for #ident {
+      fn may_exceed_16_bytes() -> bool {
+        false #( || <#types as ::rustdds::dds::traits::Key>::may_exceed_16_bytes() )*
+      }
+    }
+  }
+}
+
+fn no_generics(input: &DeriveInput, derive: &str) -> Result<()> {
+  if input.generics.params.is_empty() {
+    Ok(())
+  } else {
+    Err(Error::new_spanned(
+      &input.generics,
+      format!("#[derive({})] does not support generic types", derive),
+    ))
+  }
+}
+
+fn serde_attributes(attrs: &[Attribute]) -> Vec<&Attribute> {
+  attrs.iter().filter(|a| a.path.is_ident("serde")).collect()
+}
+
+// Rejects types that can never be keys, with a better message than the missing trait
+// implementations would give.
+fn check_key_type(ty: &Type) -> Result<()> {
+  match ty {
+    Type::Path(type_path) => {
+      if let Some(last) = type_path.path.segments.last() {
+        if last.ident == "f32" || last.ident == "f64" {
+          return Err(Error::new_spanned(
+            ty,
+            "floating point numbers cannot be keys, as they do not implement Eq, Ord or Hash",
+          ));
+        }
+        if let PathArguments::AngleBracketed(args) = &last.arguments {
+          for arg in args.args.iter() {
+            if let GenericArgument::Type(t) = arg {
+              check_key_type(t)?;
+            }
+          }
+        }
+      }
+      Ok(())
+    }
+    Type::Array(array) => check_key_type(&array.elem),
+    Type::Tuple(tuple) => tuple.elems.iter().try_for_each(check_key_type),
+    Type::Paren(paren) => check_key_type(&paren.elem),
+    Type::Group(group) => check_key_type(&group.elem),
+    Type::Reference(_)
+    | Type::Ptr(_)
+    | Type::Slice(_)
+    | Type::BareFn(_)
+    | Type::TraitObject(_)
+    | Type::ImplTrait(_)
+    | Type::Never(_) => Err(Error::new_spanned(
+      ty,
+      "key fields must be owned values, whose type implements Key",
+    )),
+    _ => Ok(()),
+  }
+}
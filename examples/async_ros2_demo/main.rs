@@ -0,0 +1,110 @@
+//! Same turtle topic as in ros2_demo, but written with async/await.
+//!
+//! The listener prints every Twist published to /turtle1/cmd_vel (e.g. by
+//! `ros2 run turtlesim turtle_teleop_key`), and the sender drives the turtle
+//! of `ros2 run turtlesim turtlesim_node` in a circle. Both run as tasks on a
+//! single `futures` executor; RustDDS needs no other runtime. The node is
+//! announced through a RosParticipant, so `ros2 node list` shows it.
+
+extern crate rustdds;
+
+use std::time::Duration;
+
+use futures::{channel::mpsc, executor::block_on, join, StreamExt};
+use log::{error, info};
+use rustdds::{
+  dds::DomainParticipant,
+  ros2::{IRosNodeControl, NodeOptions, RosContext, RosNode, RosNodeBuilder, RosParticipant},
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+};
+
+#[allow(dead_code)]
+#[path = "../ros2_demo/ros2/turtle_data.rs"]
+mod turtle_data;
+
+use turtle_data::{TurtleCmdVelTopic, Twist, Vector3};
+
+const SEND_INTERVAL: Duration = Duration::from_millis(500);
+
+fn main() {
+  env_logger::init();
+
+  let domain_participant = match DomainParticipant::new(0) {
+    Ok(dp) => dp,
+    Err(e) => {
+      error!("Cannot create DomainParticipant. {}", e);
+      return;
+    }
+  };
+  let ros_context = RosContext::new(domain_participant.clone(), true).unwrap();
+  let ros_participant = RosParticipant::new(&ros_context).unwrap();
+  let ros_node_options = NodeOptions::new(domain_participant.domain_id(), false);
+
+  // make sure topic lives long enough for ros_node
+  let turtle_cmd_vel_topic = RosNode::create_ros_topic(
+    &domain_participant,
+    &TurtleCmdVelTopic::topic_name(),
+    &TurtleCmdVelTopic::type_name(),
+    TurtleCmdVelTopic::get_qos(),
+    TurtleCmdVelTopic::topic_kind(),
+  )
+  .unwrap();
+
+  let mut ros_node = RosNodeBuilder::new()
+    .name("async_turtle")
+    .namespace("/ros2_demo")
+    .node_options(ros_node_options)
+    .ros_context(&ros_context)
+    .ros_participant(&ros_participant)
+    .build()
+    .unwrap();
+
+  let mut turtle_cmd_vel_reader = ros_node
+    .create_ros_nokey_subscriber::<Twist, CDRDeserializerAdapter<_>>(&turtle_cmd_vel_topic, None)
+    .unwrap();
+
+  let turtle_cmd_vel_writer = ros_node
+    .create_ros_nokey_publisher::<Twist, CDRSerializerAdapter<Twist>>(&turtle_cmd_vel_topic, None)
+    .unwrap();
+
+  // Plain thread acting as a timer, so that the example does not depend on any
+  // particular async runtime.
+  let (tick_sender, mut ticks) = mpsc::unbounded::<()>();
+  std::thread::spawn(move || {
+    while tick_sender.unbounded_send(()).is_ok() {
+      std::thread::sleep(SEND_INTERVAL);
+    }
+  });
+
+  let listener = async {
+    let mut samples = turtle_cmd_vel_reader.async_sample_stream();
+    while let Some(data_sample) = samples.next().await {
+      info!("Received {:?}", data_sample.value());
+    }
+    info!("Stopping listener");
+  };
+
+  let sender = async {
+    while ticks.next().await.is_some() {
+      let twist = Twist {
+        linear: Vector3 {
+          x: 1.0,
+          y: 0.0,
+          z: 0.0,
+        },
+        angular: Vector3 {
+          x: 0.0,
+          y: 0.0,
+          z: 1.0,
+        },
+      };
+      if let Err(e) = turtle_cmd_vel_writer.async_write(twist, None).await {
+        error!("Failed to write to turtle writer. {:?}", e);
+        break;
+      }
+    }
+    info!("Stopping sender");
+  };
+
+  block_on(async { join!(listener, sender) });
+}
@@ -0,0 +1,134 @@
+//! Measures how long getting large samples out of a DataReader takes, with `take`,
+//! which moves them out, and with `read` and a clone, which copies them.
+//!
+//! Usage: `cargo run --release --example large_samples [samples] [payload_bytes] [domain_id]`
+//!
+//! The defaults are 100 samples of 1 MB in domain 0. The samples are written and read
+//! in the same DomainParticipant, and only getting them out of the DataReader is timed.
+//! The time of `take` does not grow with the payload size, that of `read` and clone does.
+
+use std::{
+  thread,
+  time::{Duration as StdDuration, Instant},
+};
+
+use byteorder::LittleEndian;
+use serde::{Deserialize, Serialize};
+
+use rustdds::{
+  dds::{
+    data_types::{ReadCondition, TopicKind},
+    qos::{
+      policy::{History, Reliability},
+      QosPolicies,
+    },
+    traits::Keyed,
+    DomainParticipant,
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Sample {
+  sequence: u64,
+  payload: Vec<u8>,
+}
+
+// All samples are of the same instance, so the reader does not collect instances.
+impl Keyed for Sample {
+  type K = u32;
+
+  fn get_key(&self) -> u32 {
+    0
+  }
+}
+
+fn main() {
+  env_logger::init();
+
+  let mut args = std::env::args().skip(1);
+  let samples: usize = args.next().map_or(100, |a| a.parse().unwrap());
+  let payload_bytes: usize = args.next().map_or(1 << 20, |a| a.parse().unwrap());
+  let domain_id: u16 = args.next().map_or(0, |a| a.parse().unwrap());
+
+  let qos = QosPolicies::builder()
+    .reliability(Reliability::BestEffort)
+    .history(History::KeepAll)
+    .build();
+
+  let domain_participant = DomainParticipant::new(domain_id).unwrap();
+  let topic = domain_participant
+    .create_topic("large_samples", "Sample", &qos, TopicKind::WithKey)
+    .unwrap();
+  let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  let mut reader = subscriber
+    .create_datareader::<Sample, CDRDeserializerAdapter<Sample>>(&topic, None, None)
+    .unwrap();
+  let publisher = domain_participant.create_publisher(&qos).unwrap();
+  let writer = publisher
+    .create_datawriter::<Sample, CDRSerializerAdapter<Sample, LittleEndian>>(None, &topic, None)
+    .unwrap();
+
+  let matching_started = Instant::now();
+  while writer
+    .get_publication_matched_status()
+    .unwrap()
+    .current_count()
+    == 0
+  {
+    if matching_started.elapsed() > StdDuration::from_secs(10) {
+      println!("No DataReader was matched.");
+      return;
+    }
+    thread::sleep(StdDuration::from_millis(100));
+  }
+
+  for sequence in 0..samples as u64 {
+    let sample = Sample {
+      sequence,
+      payload: vec![(sequence % 256) as u8; payload_bytes],
+    };
+    writer.write(sample, None).unwrap();
+  }
+
+  // Reading deserializes the received samples into the DataReader, so after this
+  // the samples only need to be handed out.
+  let receiving_started = Instant::now();
+  let mut received = 0;
+  while received < samples && receiving_started.elapsed() < StdDuration::from_secs(10) {
+    received = reader.read(samples, ReadCondition::any()).unwrap().len();
+    thread::sleep(StdDuration::from_millis(10));
+  }
+  if received == 0 {
+    println!("Received no samples.");
+    return;
+  }
+
+  let started = Instant::now();
+  let copies: Vec<Sample> = reader
+    .read(samples, ReadCondition::any())
+    .unwrap()
+    .iter()
+    .filter_map(|s| s.value().as_ref().ok().map(|d| (*d).clone()))
+    .collect();
+  let read_elapsed = started.elapsed();
+
+  let started = Instant::now();
+  let taken: Vec<Sample> = reader
+    .take(samples, ReadCondition::any())
+    .unwrap()
+    .into_iter()
+    .filter_map(|s| s.into_value().ok())
+    .collect();
+  let take_elapsed = started.elapsed();
+
+  println!(
+    "Received {} of {} samples of {} bytes. Per sample, read and clone took {:?}, take \
+     took {:?}.",
+    received,
+    samples,
+    payload_bytes,
+    read_elapsed / copies.len().max(1) as u32,
+    take_elapsed / taken.len().max(1) as u32,
+  );
+}
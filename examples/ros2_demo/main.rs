@@ -6,7 +6,7 @@ use std::{io::Write, time::Duration};
 
 use rustdds::{
   dds::DomainParticipant,
-  ros2::{RosContext, RosParticipant},
+  ros2::{RosContext, RosParticipant, RosoutLogger},
 };
 use commands::ThreadControl;
 use log::{debug, error};
@@ -30,9 +30,13 @@ const ROS2_NODE_RECEIVED_TOKEN: Token = Token(1001);
 const TURTLE_CMD_VEL_RECEIVER_TOKEN: Token = Token(1002);
 const TOPIC_UPDATE_TIMER_TOKEN: Token = Token(1003);
 const NODE_INFO_TOKEN: Token = Token(1004);
+const ROS2_NODE_WRITER_STATUS_TOKEN: Token = Token(1005);
 
 fn main() {
-  log4rs::init_file("examples/ros2_demo/log4rs.yaml", Default::default()).unwrap();
+  // installed together with the rosout logger, once there is a DomainParticipant
+  let file_logger = log4rs::Logger::new(
+    log4rs::load_config_file("examples/ros2_demo/log4rs.yaml", Default::default()).unwrap(),
+  );
 
   let (command_sender, command_receiver) = mio_channel::sync_channel::<RosCommand>(10);
 
@@ -48,7 +52,7 @@ fn main() {
   .unwrap();
   stdout.flush().unwrap();
 
-  let jhandle = std::thread::spawn(move || ros2_loop(command_receiver));
+  let jhandle = std::thread::spawn(move || ros2_loop(command_receiver, file_logger));
 
   let mut main_control = MainController::new(stdout, command_sender.clone());
   main_control.start();
@@ -59,8 +63,24 @@ fn main() {
   std::thread::sleep(Duration::from_millis(10));
 }
 
-fn ros2_loop(command_receiver: mio_channel::Receiver<RosCommand>) {
-  let domain_participant = DomainParticipant::new(0);
+fn ros2_loop(command_receiver: mio_channel::Receiver<RosCommand>, file_logger: log4rs::Logger) {
+  let domain_participant = match DomainParticipant::new(0) {
+    Ok(dp) => dp,
+    Err(e) => {
+      // without a participant there is no /rosout, so only log to the file
+      log::set_max_level(file_logger.max_log_level());
+      log::set_boxed_logger(Box::new(file_logger)).unwrap_or(());
+      error!("Cannot create DomainParticipant. {}", e);
+      return;
+    }
+  };
+  // log records go to the file and, from Info up, to /rosout
+  let file_level = file_logger.max_log_level();
+  RosoutLogger::new(&domain_participant, "rustdds_ros2_demo")
+    .unwrap()
+    .chain(Box::new(file_logger), file_level)
+    .install()
+    .unwrap();
   let ros_context = RosContext::new(domain_participant.clone(), true).unwrap();
   let mut ros_participant = RosParticipant::new(&ros_context).unwrap();
 
@@ -109,6 +129,15 @@ fn ros2_loop(command_receiver: mio_channel::Receiver<RosCommand>) {
       )
       .unwrap();
 
+    poll
+      .register(
+        ros_participant.node_writer_status_listener(),
+        ROS2_NODE_WRITER_STATUS_TOKEN,
+        Ready::readable(),
+        PollOpt::edge(),
+      )
+      .unwrap();
+
     poll
       .register(
         &update_timer,
@@ -177,6 +206,8 @@ fn ros2_loop(command_receiver: mio_channel::Receiver<RosCommand>) {
             }
           }
           debug!("Finished reading nodes.");
+        } else if event.token() == ROS2_NODE_WRITER_STATUS_TOKEN {
+          ros_participant.handle_node_writer_status();
         } else if event.token() == TURTLE_CMD_VEL_RECEIVER_TOKEN {
           match &nodes_updated_sender {
             Some(s) => {
@@ -1,5 +1,5 @@
 use rustdds::{
-  dds::data_types::{DDSDuration, TopicKind},
+  dds::data_types::{Duration, TopicKind},
   dds::qos::{
     QosPolicies, policy::Deadline, policy::DestinationOrder, policy::Durability, policy::History,
     policy::LatencyBudget, policy::Lifespan, policy::Liveliness, policy::Ownership,
@@ -14,21 +14,21 @@ pub struct TurtleCmdVelTopic {}
 impl TurtleCmdVelTopic {
   const QOS: QosPolicies = QosPolicyBuilder::new()
     .durability(Durability::Volatile)
-    .deadline(Deadline(DDSDuration::DURATION_INFINITE))
+    .deadline(Deadline(Duration::DURATION_INFINITE))
     .latency_budget(LatencyBudget {
-      duration: DDSDuration::DURATION_ZERO,
+      duration: Duration::DURATION_ZERO,
     })
     .ownership(Ownership::Shared)
     .liveliness(Liveliness::Automatic {
-      lease_duration: DDSDuration::DURATION_INFINITE,
+      lease_duration: Duration::DURATION_INFINITE,
     })
     .reliability(Reliability::Reliable {
-      max_blocking_time: DDSDuration::DURATION_ZERO,
+      max_blocking_time: Duration::DURATION_ZERO,
     })
     .destination_order(DestinationOrder::ByReceptionTimestamp)
     .history(History::KeepLast { depth: 10 })
     .lifespan(Lifespan {
-      duration: DDSDuration::DURATION_INFINITE,
+      duration: Duration::DURATION_INFINITE,
     })
     .build();
 
@@ -41,7 +41,7 @@ impl TurtleCmdVelTopic {
   }
 
   pub fn type_name() -> String {
-    String::from("geometry_msgs::msg::dds_::Twist_")
+    String::from("geometry_msgs/msg/Twist")
   }
 
   pub fn get_qos() -> QosPolicies {
@@ -1,6 +1,6 @@
 use rustdds::{
-  dds::DomainParticipant, dds::traits::Entity, ros2::NodeOptions, ros2::RosContext, ros2::RosNode,
-  ros2::RosNodeBuilder, serialization::CDRDeserializerAdapter, ros2::IRosNodeControl,
+  dds::DomainParticipant, ros2::NodeOptions, ros2::RosContext, ros2::RosNode, ros2::RosNodeBuilder,
+  serialization::CDRDeserializerAdapter, ros2::IRosNodeControl,
 };
 
 use log::{info, warn};
@@ -53,8 +53,6 @@ impl TurtleListener {
         )
         .unwrap();
 
-      ros_node.add_reader(turtle_cmd_vel_reader.get_guid());
-
       ni_sender
         .send(NodeInfoCommand::Add {
           node_info: ros_node.generate_node_info(),
@@ -103,9 +101,11 @@ impl TurtleListener {
             }
           } else if event.token() == TurtleListener::TURTLE_CMD_VEL_READER_TOKEN {
             while let Ok(Some(data_sample)) = turtle_cmd_vel_reader.take_next_sample() {
-              sender
-                .send(data_sample.into_value())
-                .unwrap_or_else(|e| warn!("Failed to send received Twist. {:?}", e))
+              if let Some(twist) = data_sample.into_value() {
+                sender
+                  .send(twist)
+                  .unwrap_or_else(|e| warn!("Failed to send received Twist. {:?}", e))
+              }
             }
           }
         }
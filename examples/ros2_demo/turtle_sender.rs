@@ -1,6 +1,6 @@
 use rustdds::{
-  dds::DomainParticipant, dds::traits::Entity, ros2::NodeOptions, ros2::RosContext, ros2::RosNode,
-  ros2::RosNodeBuilder, serialization::CDRSerializerAdapter, ros2::IRosNodeControl,
+  dds::DomainParticipant, ros2::NodeOptions, ros2::RosContext, ros2::RosNode, ros2::RosNodeBuilder,
+  serialization::CDRSerializerAdapter, ros2::IRosNodeControl,
 };
 
 use log::{error, info};
@@ -53,8 +53,6 @@ impl TurtleSender {
         )
         .unwrap();
 
-      ros_node.add_writer(turtle_cmd_vel_writer.get_guid());
-
       ni_sender
         .send(NodeInfoCommand::Add {
           node_info: ros_node.generate_node_info(),
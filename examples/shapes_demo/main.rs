@@ -1,128 +1,178 @@
-extern crate rustdds;
-extern crate serde;
-extern crate mio;
-extern crate mio_extras;
-extern crate byteorder;
-extern crate termion;
-
-use rustdds::{
-  serialization::{CDRSerializerAdapter, CDRDeserializerAdapter},
-  dds::{
-    DomainParticipant, qos::QosPolicies, data_types::ReadCondition, With_Key_DataReader,
-    data_types::TopicKind,
-  },
-  dds::qos::policy::Reliability,
-  dds::data_types::DDSDuration,
-  dds::qos::policy::History,
-  dds::qos::policy::Ownership,
-  dds::qos::policy::Durability,
-  dds::qos::policy::Liveliness,
-  dds::qos::policy::DestinationOrder,
-  dds::qos::policy::ResourceLimits,
-  dds::qos::policy::Deadline,
-  dds::qos::policy::LatencyBudget,
-  dds::qos::policy::Presentation,
-  dds::qos::policy::PresentationAccessScope,
-  dds::qos::policy::Lifespan,
-  dds::traits::Keyed,
-};
+//! Publishes and subscribes the topics of the Shapes demo, which most DDS
+//! implementations ship, to check interoperability with them.
+//!
+//! Usage: `cargo run --example shapes_demo -- [options]`
+//!
+//! * `-P <shape>` publishes a `Square`, `Circle` or `Triangle`
+//! * `-S <shape>` subscribes to `Square`, `Circle` or `Triangle`
+//! * `-c <color>` the color of the published shape, `BLUE` by default
+//! * `-z <size>` the size of the published shape, 30 by default
+//! * `-r` reliable instead of best effort reliability
+//! * `-t` transient local instead of volatile durability
+//! * `-p <partition>` the partition. RustDDS does not support the Partition QoS
+//!   policy, so the demo warns and stays in the default partition.
+//! * `-d <domain_id>` the domain, 0 by default
+//!
+//! `-P` and `-S` may be given together. The published shape moves around until the
+//! demo is stopped with Ctrl-C, and it is then disposed. Received shapes are printed
+//! with their instance state, so disposed shapes and shapes whose writers are gone
+//! can be told apart.
+
 use std::{
   sync::{
-    atomic::{Ordering, AtomicBool},
+    atomic::{AtomicBool, Ordering},
     Arc,
   },
+  thread,
   time::Duration as StdDuration,
 };
-use mio::{Poll, Token, Ready, PollOpt, Events};
-use mio_extras::{timer::Timer, channel as mio_channel};
-use shapes::Square;
-use std::io::{Write, Read};
-use termion::raw::IntoRawMode;
+
 use byteorder::LittleEndian;
+use mio::{Events, Poll, PollOpt, Ready, Token};
+use mio_extras::{channel as mio_channel, timer::Timer};
+
+use rustdds::prelude::*;
+use shapes::{ShapeType, SHAPE_TOPICS, SHAPE_TYPE_NAME};
 
 mod shapes;
 
+type ShapeReader<'a> = WithKeyDataReader<'a, ShapeType, CDRDeserializerAdapter<ShapeType>>;
+
+// The area the Shapes demos draw shapes in.
+const AREA_WIDTH: i32 = 240;
+const AREA_HEIGHT: i32 = 270;
+
+// milliseconds
+const PUBLISH_INTERVAL: u64 = 100;
+
+// declaring event loop tokens for better readability
+const STOP_EVENT_LOOP_TOKEN: Token = Token(1000);
+const SHAPE_READER_TOKEN: Token = Token(1001);
+const PUBLISH_TIMER_TOKEN: Token = Token(1002);
+
+struct Options {
+  domain_id: u16,
+  publish: Option<String>,
+  subscribe: Option<String>,
+  color: String,
+  size: i32,
+  reliable: bool,
+  transient_local: bool,
+  partition: Option<String>,
+}
+
+impl Options {
+  fn from_args() -> Result<Options, String> {
+    let mut options = Options {
+      domain_id: 0,
+      publish: None,
+      subscribe: None,
+      color: String::from("BLUE"),
+      size: 30,
+      reliable: false,
+      transient_local: false,
+      partition: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+      let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+      match arg.as_str() {
+        "-P" => options.publish = Some(shape_topic(value()?)?),
+        "-S" => options.subscribe = Some(shape_topic(value()?)?),
+        "-c" => options.color = value()?.to_uppercase(),
+        "-z" => options.size = value()?.parse().map_err(|e| format!("-z: {}", e))?,
+        "-r" => options.reliable = true,
+        "-t" => options.transient_local = true,
+        "-p" => options.partition = Some(value()?),
+        "-d" => options.domain_id = value()?.parse().map_err(|e| format!("-d: {}", e))?,
+        _ => return Err(format!("Unknown option {}", arg)),
+      }
+    }
+
+    if options.publish.is_none() && options.subscribe.is_none() {
+      return Err(String::from("Give -P <shape>, -S <shape> or both"));
+    }
+    Ok(options)
+  }
+
+  fn qos(&self) -> QosPolicies {
+    let reliability = if self.reliable {
+      Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      }
+    } else {
+      Reliability::BestEffort
+    };
+    let durability = if self.transient_local {
+      Durability::TransientLocal
+    } else {
+      Durability::Volatile
+    };
+    QosPolicies::builder()
+      .reliability(reliability)
+      .durability(durability)
+      .history(History::KeepLast { depth: 1 })
+      .build()
+  }
+}
+
+fn shape_topic(name: String) -> Result<String, String> {
+  SHAPE_TOPICS
+    .iter()
+    .find(|topic| topic.eq_ignore_ascii_case(&name))
+    .map(|topic| topic.to_string())
+    .ok_or_else(|| format!("Unknown shape {}, give one of {:?}", name, SHAPE_TOPICS))
+}
+
 fn main() {
   env_logger::init();
 
-  let domain_id = std::env::args().nth(1).unwrap_or(String::from("0"));
-  let domain_id = domain_id.parse::<u16>().unwrap();
+  let options = match Options::from_args() {
+    Ok(options) => options,
+    Err(e) => {
+      println!("{}", e);
+      println!(
+        "Usage: shapes_demo [-P <shape>] [-S <shape>] [-c <color>] [-z <size>] [-r] [-t] \
+         [-p <partition>] [-d <domain_id>]"
+      );
+      return;
+    }
+  };
+  if let Some(partition) = &options.partition {
+    println!(
+      "Partitions are not supported, ignoring partition {}. Only shapes in the default \
+       partition are exchanged.",
+      partition
+    );
+  }
 
   let (stop_channel_sender, stop_channel_receiver) = mio_channel::sync_channel(10);
-  let _jhandle = std::thread::spawn(move || event_loop(stop_channel_receiver, domain_id));
+  let jhandle = thread::spawn(move || event_loop(stop_channel_receiver, options));
 
   stop_control(stop_channel_sender);
+  // The event loop disposes the published shape before it finishes.
+  jhandle.join().unwrap();
 }
 
-// milliseconds
-const KEYBOARD_CHECK_TIMEOUT: u64 = 50;
-
-// declaring event loop tokens for better readability
-const STOP_EVENT_LOOP_TOKEN: Token = Token(1000);
-const SQUARE_READER_TOKEN: Token = Token(1001);
-const KEYBOARD_CHECK_TOKEN: Token = Token(1002);
-
-fn event_loop(stop_receiver: mio_channel::Receiver<()>, domain_id: u16) {
+fn event_loop(stop_receiver: mio_channel::Receiver<()>, options: Options) {
   let poll = Poll::new().unwrap();
 
-  // adjust domain_id or participant_id if necessary to interoperability
-  let domain_participant = DomainParticipant::new(domain_id);
-
-  let pub_qos = QosPolicies::builder()
-    .reliability(Reliability::BestEffort)
-    .history(History::KeepLast { depth: 1 })
-    .ownership(Ownership::Shared)
-    .durability(Durability::Volatile)
-    .liveliness(Liveliness::Automatic {
-      lease_duration: DDSDuration::DURATION_INFINITE,
-    })
-    .destination_order(DestinationOrder::ByReceptionTimestamp)
-    .resource_limits(ResourceLimits {
-      max_instances: std::i32::MAX,
-      max_samples: std::i32::MAX,
-      max_samples_per_instance: std::i32::MAX,
-    })
-    .deadline(Deadline(DDSDuration::DURATION_INFINITE))
-    .latency_budget(LatencyBudget {
-      duration: DDSDuration::DURATION_ZERO,
-    })
-    .presentation(Presentation {
-      access_scope: PresentationAccessScope::Instance,
-      coherent_access: false,
-      ordered_access: false,
-    })
-    .lifespan(Lifespan {
-      duration: DDSDuration::DURATION_INFINITE,
-    })
-    .build();
-
-  // declare topics, subscriber, publisher, readers and writers
-  let square_topic = domain_participant
-    .create_topic("Square", "ShapeType", &pub_qos, TopicKind::WithKey)
-    .unwrap();
-  let triangle_topic = domain_participant
-    .create_topic("Triangle", "ShapeType", &pub_qos, TopicKind::WithKey)
-    .unwrap();
-
-  let square_sub = domain_participant
-    .create_subscriber(&QosPolicies::builder().build())
-    .unwrap();
-
-  // reader needs to be mutable if you want to read/take something from it
-  let mut square_reader = square_sub
-    .create_datareader::<Square, CDRDeserializerAdapter<Square>>(&square_topic, None, None)
-    .unwrap();
+  let domain_participant = DomainParticipant::new(options.domain_id).unwrap();
+  let qos = options.qos();
 
-  let square_pub = domain_participant.create_publisher(&pub_qos).unwrap();
-  let square_writer = square_pub
-    .create_datawriter::<Square, CDRSerializerAdapter<Square, LittleEndian>>(
-      None,
-      &triangle_topic,
-      None,
-    )
-    .unwrap();
+  let create_topic = |name: &str| -> Topic {
+    domain_participant
+      .create_topic(name, SHAPE_TYPE_NAME, &qos, TopicKind::WithKey)
+      .unwrap()
+  };
+  let publish_topic = options.publish.as_deref().map(create_topic);
+  let subscribe_topic = match (&options.subscribe, &publish_topic) {
+    (Some(name), Some(topic)) if options.publish.as_ref() == Some(name) => Some(topic.clone()),
+    (Some(name), _) => Some(create_topic(name)),
+    (None, _) => None,
+  };
 
-  // register readers and possible timers
   poll
     .register(
       &stop_receiver,
@@ -131,142 +181,124 @@ fn event_loop(stop_receiver: mio_channel::Receiver<()>, domain_id: u16) {
       PollOpt::edge(),
     )
     .unwrap();
-  poll
-    .register(
-      &square_reader,
-      SQUARE_READER_TOKEN,
-      Ready::readable(),
-      PollOpt::edge(),
-    )
-    .unwrap();
 
-  let stdout_org = std::io::stdout();
-  let mut areader = termion::async_stdin().bytes();
-  {
-    let mut stdout = stdout_org.lock().into_raw_mode().unwrap();
-    write!(
-      stdout,
-      "{}{} ",
-      termion::clear::All,
-      termion::cursor::Goto(1, 1)
-    )
-    .unwrap();
-    stdout.flush().unwrap();
+  let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  let publisher = domain_participant.create_publisher(&qos).unwrap();
+
+  // reader needs to be mutable if you want to read/take something from it
+  let mut reader = subscribe_topic.as_ref().map(|topic| {
+    subscriber
+      .create_datareader::<ShapeType, CDRDeserializerAdapter<ShapeType>>(topic, None, None)
+      .unwrap()
+  });
+  if let Some(reader) = &reader {
+    poll
+      .register(
+        reader,
+        SHAPE_READER_TOKEN,
+        Ready::readable(),
+        PollOpt::edge(),
+      )
+      .unwrap();
   }
 
-  let mut input_timer = Timer::default();
-  input_timer.set_timeout(StdDuration::from_millis(KEYBOARD_CHECK_TIMEOUT), ());
-  poll
-    .register(
-      &input_timer,
-      KEYBOARD_CHECK_TOKEN,
-      Ready::readable(),
-      PollOpt::edge(),
-    )
-    .unwrap();
+  let writer = publish_topic.as_ref().map(|topic| {
+    publisher
+      .create_datawriter::<ShapeType, CDRSerializerAdapter<ShapeType, LittleEndian>>(
+        None, topic, None,
+      )
+      .unwrap()
+  });
+  let mut publish_timer = Timer::default();
+  if writer.is_some() {
+    publish_timer.set_timeout(StdDuration::from_millis(PUBLISH_INTERVAL), ());
+    poll
+      .register(
+        &publish_timer,
+        PUBLISH_TIMER_TOKEN,
+        Ready::readable(),
+        PollOpt::edge(),
+      )
+      .unwrap();
+  }
+
+  let mut shape = ShapeType::new(
+    options.color.clone(),
+    AREA_WIDTH / 2,
+    AREA_HEIGHT / 2,
+    options.size,
+  );
+  let mut velocity = (3, 2);
+  let subscribed = options.subscribe.clone().unwrap_or_default();
 
-  let mut row: u16 = 0;
-  let mut square = Square::new(String::from("BLUE"), 0, 0, 30);
   loop {
-    {
-      if row > 60 {
-        let mut stdout = stdout_org.lock().into_raw_mode().unwrap();
-        row = 1;
-        write!(
-          stdout,
-          "{}{}",
-          termion::clear::All,
-          termion::cursor::Goto(1, row)
-        )
-        .unwrap();
-        stdout.flush().unwrap();
-      }
+    let mut events = Events::with_capacity(10);
+    poll.poll(&mut events, None).unwrap();
 
-      let mut events = Events::with_capacity(10);
-      poll.poll(&mut events, None).unwrap();
-
-      for event in events.iter() {
-        let mut stdout = stdout_org.lock().into_raw_mode().unwrap();
-
-        if event.token() == STOP_EVENT_LOOP_TOKEN {
-          return;
-        } else if event.token() == SQUARE_READER_TOKEN {
-          let squares = fetch_squares(&mut square_reader);
-          for square in squares.iter() {
-            write!(stdout, "{}", termion::cursor::Goto(1, row)).unwrap();
-            write!(stdout, "Item: {:?} received", square).unwrap();
-            stdout.flush().unwrap();
-            row += 1;
-          }
-        } else if event.token() == KEYBOARD_CHECK_TOKEN {
-          let mut square_moved = false;
-          let mut dispose_square = false;
-
-          while let Some(c) = areader.next() {
-            write!(stdout, "{}", termion::cursor::Goto(1, row)).unwrap();
-
-            let c = match c {
-              Ok(c) => c,
-              _ => {
-                continue;
-              }
-            };
-            match c {
-              113 => return,
-              65 => {
-                square.yadd(-1);
-                square_moved = true;
-              }
-              66 => {
-                square.yadd(1);
-                square_moved = true;
-              }
-              67 => {
-                square.xadd(1);
-                square_moved = true;
-              }
-              68 => {
-                square.xadd(-1);
-                square_moved = true;
-              }
-              100 => {
-                dispose_square = true;
-              }
-              _ => {
-                continue;
-              }
-            };
-          }
-          if square_moved {
-            match square_writer.write(square.clone(), None) {
-              Ok(_) => (),
-              Err(e) => println!("Failed to write new square. {:?}", e),
-            };
+    for event in events.iter() {
+      if event.token() == STOP_EVENT_LOOP_TOKEN {
+        if let Some(writer) = &writer {
+          println!(
+            "Disposing {} {}",
+            options.publish.as_deref().unwrap(),
+            shape.color()
+          );
+          if let Err(e) = writer.dispose(&shape.get_key(), None) {
+            println!("Failed to dispose shape. {:?}", e);
           }
-
-          if dispose_square {
-            println!("Disposing square");
-            match square_writer.dispose(square.get_key(), None) {
-              Ok(_) => (),
-              Err(e) => println!("Failed to dispose square. {:?}", e),
-            }
+        }
+        return;
+      } else if event.token() == SHAPE_READER_TOKEN {
+        if let Some(reader) = &mut reader {
+          print_shapes(&subscribed, reader);
+        }
+      } else if event.token() == PUBLISH_TIMER_TOKEN {
+        if let Some(writer) = &writer {
+          move_shape(&mut shape, &mut velocity);
+          if let Err(e) = writer.write(shape.clone(), None) {
+            println!("Failed to write shape. {:?}", e);
           }
-
-          input_timer.set_timeout(StdDuration::from_millis(KEYBOARD_CHECK_TIMEOUT), ());
         }
+        publish_timer.set_timeout(StdDuration::from_millis(PUBLISH_INTERVAL), ());
       }
     }
   }
 }
 
-fn fetch_squares(
-  reader: &mut With_Key_DataReader<Square, CDRDeserializerAdapter<Square>>,
-) -> Vec<Square> {
-  match reader.take(100, ReadCondition::any()) {
-    Ok(ds) => ds.into_iter().filter_map(|p| p.into_value().ok()).collect(),
-    Err(_) => {
-      println!("Failed to read squares");
-      vec![]
+// Moves the shape one step, bouncing it off the edges of the area.
+fn move_shape(shape: &mut ShapeType, velocity: &mut (i32, i32)) {
+  let half_size = shape.shapesize() / 2;
+  if !(half_size..=AREA_WIDTH - half_size).contains(&(shape.x() + velocity.0)) {
+    velocity.0 = -velocity.0;
+  }
+  if !(half_size..=AREA_HEIGHT - half_size).contains(&(shape.y() + velocity.1)) {
+    velocity.1 = -velocity.1;
+  }
+  shape.xadd(velocity.0);
+  shape.yadd(velocity.1);
+}
+
+fn print_shapes(topic_name: &str, reader: &mut ShapeReader<'_>) {
+  let samples = match reader.take(100, ReadCondition::any()) {
+    Ok(samples) => samples,
+    Err(e) => {
+      println!("Failed to take shapes. {:?}", e);
+      return;
+    }
+  };
+  for sample in samples {
+    let instance_state = sample.sample_info().instance_state;
+    match sample.value() {
+      Ok(shape) => println!(
+        "{:10} {:10} {:3} {:3} [{}] {:?}",
+        topic_name,
+        shape.color(),
+        shape.x(),
+        shape.y(),
+        shape.shapesize(),
+        instance_state
+      ),
+      Err(color) => println!("{:10} {:10} {:?}", topic_name, color, instance_state),
     }
   }
 }
@@ -281,7 +313,9 @@ fn stop_control(stop_sender: mio_channel::SyncSender<()>) {
   .expect("Error setting Ctrl-C handler");
 
   println!("Waiting for Ctrl-C...");
-  while running.load(Ordering::SeqCst) {}
+  while running.load(Ordering::SeqCst) {
+    thread::sleep(StdDuration::from_millis(100));
+  }
 
   match stop_sender.try_send(()) {
     Ok(_) => (),
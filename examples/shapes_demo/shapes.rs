@@ -1,17 +1,26 @@
 use rustdds::dds::traits::Keyed;
 use serde::{Serialize, Deserialize};
 
+/// The type name the Shapes demos of other DDS implementations register their
+/// topics with.
+pub const SHAPE_TYPE_NAME: &str = "ShapeType";
+
+/// The topics the Shapes demos publish and subscribe.
+pub const SHAPE_TOPICS: [&str; 3] = ["Square", "Circle", "Triangle"];
+
+/// The Shapes demo data type. The color is the key, so every color of a shape
+/// is an instance of its own.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Square {
+pub struct ShapeType {
   color: String,
   x: i32,
   y: i32,
   shapesize: i32,
 }
 
-impl Square {
-  pub fn new(color: String, x: i32, y: i32, shapesize: i32) -> Square {
-    Square {
+impl ShapeType {
+  pub fn new(color: String, x: i32, y: i32, shapesize: i32) -> ShapeType {
+    ShapeType {
       color,
       x,
       y,
@@ -19,6 +28,22 @@ impl Square {
     }
   }
 
+  pub fn color(&self) -> &str {
+    &self.color
+  }
+
+  pub fn x(&self) -> i32 {
+    self.x
+  }
+
+  pub fn y(&self) -> i32 {
+    self.y
+  }
+
+  pub fn shapesize(&self) -> i32 {
+    self.shapesize
+  }
+
   pub fn xadd(&mut self, d: i32) {
     self.x += d;
   }
@@ -28,7 +53,7 @@ impl Square {
   }
 }
 
-impl Keyed for Square {
+impl Keyed for ShapeType {
   type K = String;
 
   fn get_key(&self) -> Self::K {
@@ -39,32 +64,33 @@ impl Keyed for Square {
 #[cfg(test)]
 mod tests {
   use super::*;
-  use rustdds::{
-    submessages::RepresentationIdentifier, serialization::cdr_deserializer::CDRDeserializerAdapter,
-    dds::traits::serde_adapters::DeserializerAdapter,
-    serialization::cdr_serializer::to_little_endian_binary,
+  use byteorder::LittleEndian;
+  use rustdds::serialization::{
+    CDRDeserializerAdapter, CDRSerializerAdapter, DeserializerAdapter, RepresentationIdentifier,
+    SerializerAdapter,
   };
 
   #[test]
-  fn foobar() {
+  fn shape_type_cdr() {
     let data: [u8; 20] = [
       0x04, 0x00, 0x00, 0x00, 0x52, 0x45, 0x44, 0x00, 0x10, 0x00, 0x00, 0x00, 0xa4, 0x00, 0x00,
       0x00, 0x1e, 0x00, 0x00, 0x00,
     ];
 
-    let sq = CDRDeserializerAdapter::<Square>::from_bytes(&data, RepresentationIdentifier::CDR_LE)
-      .unwrap();
+    let shape =
+      CDRDeserializerAdapter::<ShapeType>::from_bytes(&data, RepresentationIdentifier::CDR_LE)
+        .unwrap();
 
-    let sq2 = Square {
+    let shape2 = ShapeType {
       color: String::from("RED"),
       x: 16,
       y: 164,
       shapesize: 30,
     };
 
-    assert_eq!(sq, sq2);
+    assert_eq!(shape, shape2);
 
-    let data2 = to_little_endian_binary::<Square>(&sq2).unwrap();
+    let data2 = CDRSerializerAdapter::<ShapeType, LittleEndian>::to_bytes(&shape2).unwrap();
 
     assert_eq!(data.to_vec(), data2);
   }
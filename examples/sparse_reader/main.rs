@@ -0,0 +1,137 @@
+//! Measures what getting samples out of a DataReader costs, when the application
+//! reads only 1 % of the received samples compared to reading all of them.
+//!
+//! Usage: `cargo run --release --example sparse_reader [samples] [payload_bytes] [domain_id]`
+//!
+//! The defaults are 10000 samples of 4096 bytes in domain 0. The samples are written and
+//! read in the same DomainParticipant. A DataReader deserializes a sample of an instance
+//! it already knows only when the sample is read or taken, so the sparse reader does
+//! not pay for the samples it never looks at.
+
+use std::{
+  thread,
+  time::{Duration as StdDuration, Instant},
+};
+
+use byteorder::LittleEndian;
+use serde::{Deserialize, Serialize};
+
+use rustdds::{
+  dds::{
+    data_types::{ReadCondition, TopicKind},
+    qos::{
+      policy::{History, Reliability},
+      QosPolicies,
+    },
+    traits::Keyed,
+    DomainParticipant,
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+};
+
+#[derive(Serialize, Deserialize)]
+struct Sample {
+  sequence: u64,
+  payload: Vec<u8>,
+}
+
+// All samples are of the same instance, so the reader does not collect instances.
+impl Keyed for Sample {
+  type K = u32;
+
+  fn get_key(&self) -> u32 {
+    0
+  }
+}
+
+fn main() {
+  env_logger::init();
+
+  let mut args = std::env::args().skip(1);
+  let samples: usize = args.next().map_or(10_000, |a| a.parse().unwrap());
+  let payload_bytes: usize = args.next().map_or(4096, |a| a.parse().unwrap());
+  let domain_id: u16 = args.next().map_or(0, |a| a.parse().unwrap());
+
+  let qos = QosPolicies::builder()
+    .reliability(Reliability::BestEffort)
+    .history(History::KeepAll)
+    .build();
+
+  let domain_participant = DomainParticipant::new(domain_id).unwrap();
+  let topic = domain_participant
+    .create_topic("sparse_reader", "Sample", &qos, TopicKind::WithKey)
+    .unwrap();
+  let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  // Only waits for the samples to arrive, so that the other two are not touched before
+  // they are timed.
+  let mut arrival_reader = subscriber
+    .create_datareader::<Sample, CDRDeserializerAdapter<Sample>>(&topic, None, None)
+    .unwrap();
+  let mut sparse_reader = subscriber
+    .create_datareader::<Sample, CDRDeserializerAdapter<Sample>>(&topic, None, None)
+    .unwrap();
+  let mut full_reader = subscriber
+    .create_datareader::<Sample, CDRDeserializerAdapter<Sample>>(&topic, None, None)
+    .unwrap();
+  let publisher = domain_participant.create_publisher(&qos).unwrap();
+  let writer = publisher
+    .create_datawriter::<Sample, CDRSerializerAdapter<Sample, LittleEndian>>(None, &topic, None)
+    .unwrap();
+
+  let matching_started = Instant::now();
+  while writer
+    .get_publication_matched_status()
+    .unwrap()
+    .current_count()
+    < 3
+  {
+    if matching_started.elapsed() > StdDuration::from_secs(10) {
+      println!("The DataReaders were not matched.");
+      return;
+    }
+    thread::sleep(StdDuration::from_millis(100));
+  }
+
+  for sequence in 0..samples as u64 {
+    let sample = Sample {
+      sequence,
+      payload: vec![(sequence % 256) as u8; payload_bytes],
+    };
+    writer.write(sample, None).unwrap();
+  }
+
+  let receiving_started = Instant::now();
+  let mut received = 0;
+  while received < samples && receiving_started.elapsed() < StdDuration::from_secs(10) {
+    received = arrival_reader
+      .read(samples, ReadCondition::any())
+      .unwrap()
+      .len();
+    thread::sleep(StdDuration::from_millis(10));
+  }
+  if received == 0 {
+    println!("Received no samples.");
+    return;
+  }
+
+  let sparse_count = (received / 100).max(1);
+  let started = Instant::now();
+  let sparse = sparse_reader
+    .take(sparse_count, ReadCondition::any())
+    .unwrap()
+    .len();
+  let sparse_elapsed = started.elapsed();
+
+  let started = Instant::now();
+  let full = full_reader
+    .take(samples, ReadCondition::any())
+    .unwrap()
+    .len();
+  let full_elapsed = started.elapsed();
+
+  println!(
+    "Received {} of {} samples of {} bytes. Taking {} of them took {:?}, taking all {} \
+     took {:?}.",
+    received, samples, payload_bytes, sparse, sparse_elapsed, full, full_elapsed,
+  );
+}
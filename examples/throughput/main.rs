@@ -0,0 +1,152 @@
+//! Measures how many samples per second a DataReader receives from a DataWriter of
+//! another DomainParticipant in the same process.
+//!
+//! Usage: `cargo run --release --example throughput [samples] [payload_bytes] [domain_id]`
+//!
+//! The defaults are 10000 samples of 4096 bytes in domain 0. The QoS is best effort, so
+//! samples dropped by the network are reported as lost.
+
+use std::{
+  sync::mpsc,
+  thread,
+  time::{Duration as StdDuration, Instant},
+};
+
+use byteorder::LittleEndian;
+use serde::{Deserialize, Serialize};
+
+use rustdds::{
+  dds::{
+    data_types::{Duration, TopicKind},
+    qos::{
+      policy::{History, Reliability},
+      QosPolicies,
+    },
+    traits::Keyed,
+    DomainParticipant,
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+};
+
+#[derive(Serialize, Deserialize)]
+struct Sample {
+  sequence: u64,
+  payload: Vec<u8>,
+}
+
+// All samples are of the same instance, so the reader does not collect instances.
+impl Keyed for Sample {
+  type K = u32;
+
+  fn get_key(&self) -> u32 {
+    0
+  }
+}
+
+const TOPIC_NAME: &str = "throughput";
+const TYPE_NAME: &str = "Sample";
+
+fn main() {
+  env_logger::init();
+
+  let mut args = std::env::args().skip(1);
+  let samples: u64 = args.next().map_or(10_000, |a| a.parse().unwrap());
+  let payload_bytes: usize = args.next().map_or(4096, |a| a.parse().unwrap());
+  let domain_id: u16 = args.next().map_or(0, |a| a.parse().unwrap());
+
+  let qos = QosPolicies::builder()
+    .reliability(Reliability::BestEffort)
+    .history(History::KeepAll)
+    .build();
+
+  let domain_participant = DomainParticipant::new(domain_id).unwrap();
+  let topic = domain_participant
+    .create_topic(TOPIC_NAME, TYPE_NAME, &qos, TopicKind::WithKey)
+    .unwrap();
+  let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  let mut reader = subscriber
+    .create_datareader::<Sample, CDRDeserializerAdapter<Sample>>(&topic, None, None)
+    .unwrap();
+
+  let (stop_sender, stop_receiver) = mpsc::channel();
+  let writer_qos = qos.clone();
+  let writer_thread = thread::spawn(move || {
+    write_samples(domain_id, writer_qos, samples, payload_bytes);
+    // Keep the writer's participant alive until everything has been received.
+    let _ = stop_receiver.recv();
+  });
+
+  let mut received: u64 = 0;
+  let mut first_received = None;
+  let mut last_received = Instant::now();
+  // The writer needs time to discover the reader before the first sample.
+  let mut timeout = Duration::from_secs(10);
+  while received < samples {
+    match reader.take_next_sample_timeout(timeout) {
+      Ok(Some(_sample)) => {
+        last_received = Instant::now();
+        first_received.get_or_insert(last_received);
+        received += 1;
+        timeout = Duration::from_secs(2);
+      }
+      Ok(None) => break,
+      Err(e) => {
+        println!("DataReader failed: {:?}", e);
+        break;
+      }
+    }
+  }
+  let _ = stop_sender.send(());
+  writer_thread.join().unwrap();
+
+  let elapsed = match first_received {
+    Some(first) => last_received - first,
+    None => {
+      println!("Received no samples.");
+      return;
+    }
+  };
+  let secs = elapsed.as_secs_f64().max(1e-9);
+  println!(
+    "Received {} of {} samples of {} bytes in {:?}: {:.0} samples/s, {:.1} MB/s, {} lost.",
+    received,
+    samples,
+    payload_bytes,
+    elapsed,
+    received as f64 / secs,
+    (received as f64 * payload_bytes as f64) / secs / 1e6,
+    samples - received,
+  );
+}
+
+fn write_samples(domain_id: u16, qos: QosPolicies, samples: u64, payload_bytes: usize) {
+  let domain_participant = DomainParticipant::new(domain_id).unwrap();
+  let topic = domain_participant
+    .create_topic(TOPIC_NAME, TYPE_NAME, &qos, TopicKind::WithKey)
+    .unwrap();
+  let publisher = domain_participant.create_publisher(&qos).unwrap();
+  let writer = publisher
+    .create_datawriter::<Sample, CDRSerializerAdapter<Sample, LittleEndian>>(None, &topic, None)
+    .unwrap();
+
+  let matching_started = Instant::now();
+  loop {
+    let status = writer.get_publication_matched_status().unwrap();
+    if status.current_count() > 0 {
+      break;
+    }
+    if matching_started.elapsed() > StdDuration::from_secs(10) {
+      println!("No DataReader was matched.");
+      return;
+    }
+    thread::sleep(StdDuration::from_millis(100));
+  }
+
+  for sequence in 0..samples {
+    let sample = Sample {
+      sequence,
+      payload: vec![(sequence % 256) as u8; payload_bytes],
+    };
+    writer.write(sample, None).unwrap();
+  }
+}
@@ -0,0 +1,61 @@
+//! Feeds arbitrary payloads to the deserializer adapters, as a remote writer could.
+//! Deserialization may fail, but it must not panic.
+//!
+//! Run with `cargo fuzz run from_bytes` in the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use rustdds::dds::data_types::SPDPDiscoveredParticipantData;
+use rustdds::serialization::{
+  CDRDeserializerAdapter, DeserializerAdapter, PlCdrDeserializerAdapter, Xcdr2DeserializerAdapter,
+};
+
+#[derive(Deserialize)]
+enum Color {
+  Red,
+  Green,
+  Blue,
+}
+
+#[derive(Deserialize)]
+struct Shape {
+  color: Color,
+  x: i32,
+  y: i32,
+  size: u16,
+}
+
+#[derive(Deserialize)]
+struct Message {
+  id: u64,
+  text: String,
+  letter: char,
+  flag: bool,
+  values: Vec<i32>,
+  shapes: Vec<Shape>,
+  extra: Option<f64>,
+  bytes: Vec<u8>,
+}
+
+// The first byte selects the representation identifier among the ones the adapter
+// supports, and the rest is the payload.
+fn from_bytes<D, DA>(data: &[u8])
+where
+  D: DeserializeOwned,
+  DA: DeserializerAdapter<D>,
+{
+  if let Some((selector, payload)) = data.split_first() {
+    let encodings = DA::supported_encodings();
+    let encoding = encodings[*selector as usize % encodings.len()];
+    let _ = DA::from_bytes(payload, encoding);
+  }
+}
+
+fuzz_target!(|data: &[u8]| {
+  from_bytes::<Message, CDRDeserializerAdapter<Message>>(data);
+  from_bytes::<Message, Xcdr2DeserializerAdapter<Message>>(data);
+  from_bytes::<SPDPDiscoveredParticipantData, PlCdrDeserializerAdapter<_>>(data);
+});
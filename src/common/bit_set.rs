@@ -39,9 +39,11 @@ impl<'a, C: Context> Readable<'a, C> for BitSetRef {
       return Ok(BitSetRef::new());
     }
 
-    let mut bit_vec = BitVec::with_capacity(number_of_bits as usize);
+    // A valid set has at most 256 bits, so more is not allocated up front.
+    // Reading fails at the end of the submessage, if there are not as many.
+    let mut bit_vec = BitVec::with_capacity(std::cmp::min(number_of_bits, 256) as usize);
 
-    for _ in 0..(number_of_bits / 32) + 1 {
+    for _ in 0..(u64::from(number_of_bits) + 31) / 32 {
       // read value should be directly correct
       let byte = reader.read_u32()?.reverse_bits();
       unsafe {
@@ -108,5 +110,27 @@ mod tests {
       be = [0x00, 0x00, 0x00, 0x2B,
             0x81, 0x00, 0x00, 0x00,
             0x00, 0x20, 0x00, 0x00]
+  },
+  {
+      bit_set_full_word,
+      (|| {
+          let mut set = BitSetRef::new();
+          set.insert(0);
+          set.insert(31);
+          set
+      })(),
+      le = [0x20, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x80],
+      be = [0x00, 0x00, 0x00, 0x20,
+            0x80, 0x00, 0x00, 0x01]
   });
+
+  #[test]
+  fn bit_set_huge_size_is_not_read() {
+    // numBits u32::MAX, but only one word follows
+    let bytes = [0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x00, 0x00];
+    assert!(
+      BitSetRef::read_from_buffer_with_ctx(speedy::Endianness::LittleEndian, &bytes).is_err()
+    );
+  }
 }
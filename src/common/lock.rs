@@ -0,0 +1,87 @@
+// Locking of the state a participant shares between its threads, such as DiscoveryDB
+// and DDSCache. If a thread panics while holding such a lock, the lock is poisoned,
+// and panicking on that would take down every other thread of the participant. These
+// recover instead: they continue with the state as the panicking thread left it,
+// which is at worst an update of one entity done halfway, and count the recovery.
+
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use log::error;
+
+pub(crate) trait PoisonRecovery {
+  // shown in the log when recovering
+  const NAME: &'static str;
+
+  fn count_poison_recovery(&self);
+}
+
+pub(crate) fn read_lock<T: PoisonRecovery>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+  lock
+    .read()
+    .unwrap_or_else(|poisoned| recover(lock, poisoned))
+}
+
+pub(crate) fn write_lock<T: PoisonRecovery>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+  lock
+    .write()
+    .unwrap_or_else(|poisoned| recover(lock, poisoned))
+}
+
+fn recover<T, G>(lock: &RwLock<T>, poisoned: PoisonError<G>) -> G
+where
+  T: PoisonRecovery,
+  G: std::ops::Deref<Target = T>,
+{
+  let guard = poisoned.into_inner();
+  // Later lockers get the state without a PoisonError, so each panic is counted once
+  lock.clear_poison();
+  error!(
+    "A thread panicked while holding the lock of {}. Continuing with the state it left.",
+    T::NAME
+  );
+  guard.count_poison_recovery();
+  guard
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    panic,
+    sync::atomic::{AtomicU64, Ordering},
+  };
+
+  use super::*;
+
+  #[derive(Default)]
+  struct Counted {
+    value: u32,
+    recoveries: AtomicU64,
+  }
+
+  impl PoisonRecovery for Counted {
+    const NAME: &'static str = "Counted";
+
+    fn count_poison_recovery(&self) {
+      self.recoveries.fetch_add(1, Ordering::Relaxed);
+    }
+  }
+
+  #[test]
+  fn lock_recovers_from_panic() {
+    let lock = RwLock::new(Counted::default());
+    let result = panic::catch_unwind(|| {
+      let mut counted = write_lock(&lock);
+      counted.value = 1;
+      panic!("panic while holding the lock");
+    });
+    assert!(result.is_err());
+    assert!(lock.is_poisoned());
+
+    assert_eq!(read_lock(&lock).value, 1);
+    assert!(!lock.is_poisoned());
+    write_lock(&lock).value = 2;
+    let counted = read_lock(&lock);
+    assert_eq!(counted.value, 2);
+    assert_eq!(counted.recoveries.load(Ordering::Relaxed), 1);
+  }
+}
@@ -1,4 +1,8 @@
+#[macro_use]
+pub mod trace;
+
 pub mod bit_set;
+pub mod lock;
 pub mod ranged_bit_set;
 pub mod timed_event_handler;
 pub mod validity_trait;
@@ -56,8 +56,9 @@ where
       match self.iter.next() {
         None => return None,
         Some((_, false)) => {}
+        // A value that does not fit in B ends the iteration.
         Some((i, true)) => {
-          return Some(B::from_usize(i).unwrap().checked_add(&self.base).unwrap());
+          return B::from_usize(i).and_then(|offset| offset.checked_add(&self.base));
         }
       }
     }
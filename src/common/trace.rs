@@ -0,0 +1,150 @@
+// Structured diagnostics. With the tracing feature these are spans and events of the
+// tracing crate, which can be filtered by their fields or exported to OpenTelemetry.
+// Otherwise events are log records with the fields appended to the message, and spans
+// are nothing.
+//
+// Fields have the same names everywhere:
+//   participant  GuidPrefix of our participant (span "participant")
+//   guid         GUID of our Reader or Writer (spans "reader" and "writer")
+//   source       GuidPrefix of the sender of a received message (span "rtps_message")
+//   writer       GUID of the writer of a sample, or of a matched writer
+//   reader       GUID of a matched reader
+//   seq          SequenceNumber of a sample
+//   topic        topic name
+//
+// Events on the path of every message are at trace level, so that without the
+// feature they cost a level comparison.
+
+#[cfg(feature = "tracing")]
+macro_rules! tracing_level {
+  (error) => {
+    tracing::Level::ERROR
+  };
+  (warn) => {
+    tracing::Level::WARN
+  };
+  (info) => {
+    tracing::Level::INFO
+  };
+  (debug) => {
+    tracing::Level::DEBUG
+  };
+  (trace) => {
+    tracing::Level::TRACE
+  };
+}
+
+// dds_event!(trace, "sample accepted", writer = writer_guid, seq = seq_num);
+// Field values are formatted with Debug.
+#[cfg(feature = "tracing")]
+macro_rules! dds_event {
+  ($level:ident, $message:literal $(, $field:ident = $value:expr)* $(,)?) => {
+    tracing::event!(tracing_level!($level), $($field = ?$value,)* $message)
+  };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! dds_event {
+  ($level:ident, $message:literal $(, $field:ident = $value:expr)* $(,)?) => {
+    log::$level!(concat!($message $(, " ", stringify!($field), "={:?}")*) $(, $value)*)
+  };
+}
+
+// let _span = dds_span!(trace, "reader", guid = self.get_guid());
+// The span is entered until the returned guard is dropped.
+#[cfg(feature = "tracing")]
+macro_rules! dds_span {
+  ($level:ident, $name:literal $(, $field:ident = $value:expr)* $(,)?) => {
+    tracing::span!(tracing_level!($level), $name $(, $field = ?$value)*).entered()
+  };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! dds_span {
+  ($level:ident, $name:literal $(, $field:ident = $value:expr)* $(,)?) => {{
+    $(let _ = &$value;)*
+    crate::common::trace::NoSpan
+  }};
+}
+
+// What dds_span! gives without the tracing feature
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+  use std::{
+    fmt,
+    sync::{
+      atomic::{AtomicU64, Ordering},
+      Arc, Mutex,
+    },
+  };
+
+  use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+  };
+
+  // Records the names and fields of spans and events as text
+  #[derive(Clone, Default)]
+  struct Recorder {
+    next_id: Arc<AtomicU64>,
+    records: Arc<Mutex<Vec<String>>>,
+  }
+
+  struct Fields(String);
+
+  impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+      self.0.push_str(&format!(" {}={:?}", field.name(), value));
+    }
+  }
+
+  impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+      true
+    }
+
+    fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+      let mut fields = Fields(format!("span {}", attributes.metadata().name()));
+      attributes.record(&mut fields);
+      self.records.lock().unwrap().push(fields.0);
+      span::Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+      let mut fields = Fields(format!("event {}", event.metadata().level()));
+      event.record(&mut fields);
+      self.records.lock().unwrap().push(fields.0);
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+  }
+
+  #[test]
+  fn trace_spans_and_events_have_fields() {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+      let _reader = dds_span!(trace, "reader", guid = 7);
+      dds_event!(trace, "sample accepted", writer = "w", seq = 3);
+      dds_event!(warn, "no fields");
+    });
+
+    let records = recorder.records.lock().unwrap();
+    assert_eq!(
+      *records,
+      vec![
+        "span reader guid=7".to_string(),
+        "event TRACE message=sample accepted writer=\"w\" seq=3".to_string(),
+        "event WARN message=no fields".to_string(),
+      ]
+    );
+  }
+}
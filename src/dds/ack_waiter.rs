@@ -0,0 +1,103 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// AckWaiter lets a reliable DataWriter block on its own thread until matched readers
+// have acknowledged enough of its samples. The DataWriter counts samples it has
+// written, and the event loop side Writer reports how many of them are acknowledged
+// by all matched readers. The difference is the number of samples still occupying
+// the writer history.
+#[derive(Clone)]
+pub(crate) struct AckWaiter {
+  inner: Arc<(Mutex<AckState>, Condvar)>,
+}
+
+struct AckState {
+  written: i64,
+  acked: i64,
+}
+
+impl AckWaiter {
+  pub fn new() -> AckWaiter {
+    AckWaiter {
+      inner: Arc::new((
+        Mutex::new(AckState {
+          written: 0,
+          acked: 0,
+        }),
+        Condvar::new(),
+      )),
+    }
+  }
+
+  fn lock(&self) -> MutexGuard<AckState> {
+    match self.inner.0.lock() {
+      Ok(s) => s,
+      Err(e) => panic!("AckWaiter is poisoned. {:?}", e),
+    }
+  }
+
+  // DataWriter has handed one more sample to the Writer. Returns the number of samples
+  // written so far, which is also the sequence number the Writer gives to this one.
+  pub fn add_written(&self) -> i64 {
+    let mut state = self.lock();
+    state.written += 1;
+    state.written
+  }
+
+  // Writer reports that samples 1..=acked are acknowledged by all matched readers.
+  pub fn set_acked(&self, acked: i64) {
+    let mut state = self.lock();
+    if acked != state.acked {
+      state.acked = acked;
+      self.inner.1.notify_all();
+    }
+  }
+
+  pub fn unacked(&self) -> i64 {
+    let state = self.lock();
+    state.written - state.acked
+  }
+
+  // Waits until less than max_unacked samples are unacknowledged.
+  // Returns false if that did not happen within max_wait.
+  pub fn wait_for_room(&self, max_unacked: i64, max_wait: Duration) -> bool {
+    let deadline = Instant::now() + max_wait;
+    let mut state = self.lock();
+    while state.written - state.acked >= max_unacked {
+      let now = Instant::now();
+      if now >= deadline {
+        return false;
+      }
+      state = match self.inner.1.wait_timeout(state, deadline - now) {
+        Ok((s, _timeout)) => s,
+        Err(e) => panic!("AckWaiter is poisoned. {:?}", e),
+      };
+    }
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::thread;
+
+  #[test]
+  fn ack_waiter_wait_for_room() {
+    let waiter = AckWaiter::new();
+    waiter.add_written();
+    waiter.add_written();
+    assert_eq!(waiter.unacked(), 2);
+    assert!(waiter.wait_for_room(3, Duration::from_millis(0)));
+    assert!(!waiter.wait_for_room(2, Duration::from_millis(10)));
+
+    let acker = waiter.clone();
+    let handle = thread::spawn(move || {
+      thread::sleep(Duration::from_millis(20));
+      acker.set_acked(1);
+    });
+    assert!(waiter.wait_for_room(2, Duration::from_secs(5)));
+    assert_eq!(waiter.unacked(), 1);
+    handle.join().unwrap();
+  }
+}
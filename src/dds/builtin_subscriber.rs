@@ -0,0 +1,172 @@
+use crate::{
+  discovery::{
+    discovery::Discovery,
+    data_types::{
+      spdp_participant_data::SPDPDiscoveredParticipantData,
+      topic_data::{DiscoveredReaderData, DiscoveredTopicData, DiscoveredWriterData},
+    },
+  },
+  serialization::pl_cdr_deserializer::PlCdrDeserializerAdapter,
+  structure::guid::EntityId,
+};
+
+use crate::dds::{
+  participant::DomainParticipant, pubsub::Subscriber, qos::QosPolicyBuilder, topic::*,
+  values::result::*, with_key::datareader::DataReader,
+};
+
+/// DataReader of the builtin DCPSParticipant topic
+pub type ParticipantBuiltinReader<'a> = DataReader<
+  'a,
+  SPDPDiscoveredParticipantData,
+  PlCdrDeserializerAdapter<SPDPDiscoveredParticipantData>,
+>;
+/// DataReader of the builtin DCPSPublication topic
+pub type PublicationBuiltinReader<'a> =
+  DataReader<'a, DiscoveredWriterData, PlCdrDeserializerAdapter<DiscoveredWriterData>>;
+/// DataReader of the builtin DCPSSubscription topic
+pub type SubscriptionBuiltinReader<'a> =
+  DataReader<'a, DiscoveredReaderData, PlCdrDeserializerAdapter<DiscoveredReaderData>>;
+/// DataReader of the builtin DCPSTopic topic
+pub type TopicBuiltinReader<'a> =
+  DataReader<'a, DiscoveredTopicData, PlCdrDeserializerAdapter<DiscoveredTopicData>>;
+
+/// Gives read-only access to the builtin discovery topics, i.e. the remote
+/// participants, publications, subscriptions and topics that Discovery has found.
+///
+/// The DataReaders created here are normal DataReaders, but they share the received
+/// data with Discovery. Reading or taking from them does not affect Discovery or
+/// other builtin DataReaders. They also return data received before they were created.
+///
+/// # Examples
+///
+/// ```
+/// # use rustdds::dds::DomainParticipant;
+/// use rustdds::dds::data_types::ReadCondition;
+///
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// let builtin_subscriber = domain_participant.get_builtin_subscriber().unwrap();
+/// let mut participant_reader = builtin_subscriber.participant_reader().unwrap();
+/// for sample in participant_reader.read(10, ReadCondition::any()).unwrap() {
+///   if let Ok(participant) = sample.value() {
+///     println!("{:?}", participant.participant_guid);
+///   }
+/// }
+/// ```
+pub struct BuiltinSubscriber {
+  subscriber: Subscriber,
+  participant_topic: Topic,
+  publication_topic: Topic,
+  subscription_topic: Topic,
+  topic_topic: Topic,
+}
+
+impl BuiltinSubscriber {
+  pub(crate) fn new(domain_participant: &DomainParticipant) -> Result<BuiltinSubscriber> {
+    // Same topics as Discovery uses, so that DataReaders find the same topic caches.
+    let participant_topic = domain_participant.create_topic(
+      "DCPSParticipant",
+      "SPDPDiscoveredParticipantData",
+      &Discovery::create_spdp_patricipant_qos(),
+      TopicKind::WithKey,
+    )?;
+    let publication_topic = domain_participant.create_topic(
+      "DCPSPublication",
+      "DiscoveredWriterData",
+      &Discovery::subscriber_qos(),
+      TopicKind::WithKey,
+    )?;
+    let subscription_topic = domain_participant.create_topic(
+      "DCPSSubscription",
+      "DiscoveredReaderData",
+      &Discovery::subscriber_qos(),
+      TopicKind::WithKey,
+    )?;
+    let topic_topic = domain_participant.create_topic(
+      "DCPSTopic",
+      "DiscoveredTopicData",
+      &QosPolicyBuilder::new().build(),
+      TopicKind::WithKey,
+    )?;
+
+    Ok(BuiltinSubscriber {
+      subscriber: domain_participant.create_subscriber(&Discovery::subscriber_qos())?,
+      participant_topic,
+      publication_topic,
+      subscription_topic,
+      topic_topic,
+    })
+  }
+
+  /// Creates a DataReader of discovered DomainParticipants (DCPSParticipant).
+  pub fn participant_reader(&self) -> Result<ParticipantBuiltinReader> {
+    self.subscriber.create_builtin_datareader(
+      &self.participant_topic,
+      EntityId::ENTITYID_SPDP_BUILTIN_PARTICIPANT_READER,
+    )
+  }
+
+  /// Creates a DataReader of discovered DataWriters (DCPSPublication).
+  pub fn publication_reader(&self) -> Result<PublicationBuiltinReader> {
+    self.subscriber.create_builtin_datareader(
+      &self.publication_topic,
+      EntityId::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_READER,
+    )
+  }
+
+  /// Creates a DataReader of discovered DataReaders (DCPSSubscription).
+  pub fn subscription_reader(&self) -> Result<SubscriptionBuiltinReader> {
+    self.subscriber.create_builtin_datareader(
+      &self.subscription_topic,
+      EntityId::ENTITYID_SEDP_BUILTIN_SUBSCRIPTIONS_READER,
+    )
+  }
+
+  /// Creates a DataReader of discovered Topics (DCPSTopic).
+  pub fn topic_reader(&self) -> Result<TopicBuiltinReader> {
+    self.subscriber.create_builtin_datareader(
+      &self.topic_topic,
+      EntityId::ENTITYID_SEDP_BUILTIN_TOPIC_READER,
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dds::readcondition::ReadCondition;
+  use crate::structure::entity::Entity;
+  use std::{thread, time::Duration};
+
+  #[test]
+  fn builtin_participant_reader_shares_discovery_data() {
+    let dp = DomainParticipant::new(0).unwrap();
+    let builtin_subscriber = dp.get_builtin_subscriber().unwrap();
+    let mut participant_reader = builtin_subscriber.participant_reader().unwrap();
+
+    let remote = DomainParticipant::new(0).unwrap();
+    let remote_prefix = remote.get_guid().guidPrefix;
+
+    let mut found = false;
+    for _ in 0..50 {
+      thread::sleep(Duration::from_millis(100));
+      found = participant_reader
+        .take(100, ReadCondition::any())
+        .unwrap()
+        .iter()
+        .filter_map(|s| s.value().as_ref().ok())
+        .any(|p| p.participant_guid.map(|g| g.guidPrefix) == Some(remote_prefix));
+      if found {
+        break;
+      }
+    }
+    assert!(found);
+
+    // Taking above must not have taken anything away from Discovery
+    let db = dp.discovery_db();
+    let db = db.read().unwrap();
+    assert!(db
+      .get_participants()
+      .any(|p| p.participant_guid.map(|g| g.guidPrefix) == Some(remote_prefix)));
+  }
+}
@@ -1,6 +1,8 @@
-//use log::debug;
+use log::debug;
 
-use crate::structure::{time::Timestamp, guid::GUID};
+use crate::structure::{
+  dds_cache::CacheInstant, time::Timestamp, guid::GUID, inline_qos::SampleIdentity,
+};
 
 use crate::{
   dds::traits::key::{Key, Keyed},
@@ -11,8 +13,12 @@ use crate::dds::sampleinfo::*;
 use crate::dds::qos::QosPolicies;
 use crate::dds::qos::policy;
 use crate::dds::readcondition::ReadCondition;
+use crate::messages::submessages::submessage_elements::serialized_payload::{
+  RepresentationIdentifier, SerializedPayload,
+};
 
-use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::cell::{Cell, OnceCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ops::Bound::*;
 
 //use std::num::Zero; unstable
@@ -40,21 +46,35 @@ pub(crate) fn result_ok_as_ref_err_clone<T, E: Clone>(
   }
 }
 
-// Data samples are here ordered and indexed by Timetamp, which must be a unque key.
-// RTPS Timestamp has sub-nanosecond resolution, so it could be unique, provided that the source
-// clock ticks frequently enough.
+// Data samples are here ordered and indexed by the DDSCache instant of their CacheChange,
+// which is a unique key.
 pub struct DataSampleCache<D: Keyed> {
   qos: QosPolicies,
-  datasamples: BTreeMap<Timestamp, SampleWithMetaData<D>>, // ordered storage for deserialized samples
-  pub(crate) instance_map: BTreeMap<D::K, InstanceMetaData>, // ordered storage for instances
+  datasamples: BTreeMap<CacheInstant, SampleWithMetaData<D>>, // ordered storage for deserialized samples
+  pub(crate) instance_map: BTreeMap<D::K, InstanceMetaData>,  // ordered storage for instances
   hash_to_key_map: BTreeMap<u128, D::K>,
 }
 
 pub(crate) struct InstanceMetaData {
-  instance_samples: BTreeSet<Timestamp>, // which samples belong to this instance
-  instance_state: InstanceState,         // latest known alive/not_alive state for this instance
+  instance_samples: BTreeSet<CacheInstant>, // which samples belong to this instance
+  instance_state: InstanceState,            // latest known alive/not_alive state for this instance
   latest_generation_available: NotAliveGenerationCounts, // in this instance
-  last_generation_accessed: NotAliveGenerationCounts, // in this instance
+  // in this instance. Cell, because ReadIter marks access through a shared reference.
+  last_generation_accessed: Cell<NotAliveGenerationCounts>,
+  live_writers: BTreeSet<GUID>, // writers that have written and not unregistered this instance
+  latest_source_timestamp: Option<Timestamp>, // of the samples accepted to this instance
+}
+
+impl InstanceMetaData {
+  // DDS spec 2.2.2.5.4: view_state is New if this instance has never been accessed,
+  // or if it has been reborn since it was last accessed.
+  fn view_state(&self) -> ViewState {
+    if self.latest_generation_available.total() > self.last_generation_accessed.get().total() {
+      ViewState::New
+    } else {
+      ViewState::NotNew
+    }
+  }
 }
 
 struct SampleWithMetaData<D: Keyed> {
@@ -65,10 +85,14 @@ struct SampleWithMetaData<D: Keyed> {
   writer_guid: GUID,
   // timestamps
   source_timestamp: Option<Timestamp>, // as stamped by sender
-  sample_has_been_read: bool,          // sample_state
+  reception_timestamp: Timestamp,      // of the cache instant
+  // identity of this sample, and of the sample it relates to, if the writer said so
+  sample_identity: Option<SampleIdentity>,
+  related_sample_identity: Option<SampleIdentity>,
+  sample_has_been_read: Cell<bool>, // sample_state
 
   // the data sample (or key) itself is stored here
-  sample: Result<D, D::K>,
+  sample: SampleValue<D>,
 }
 
 impl<D> SampleWithMetaData<D>
@@ -78,8 +102,53 @@ where
 {
   pub fn get_key(&self) -> D::K {
     match &self.sample {
-      Ok(d) => d.get_key(),
-      Err(k) => k.clone(),
+      SampleValue::Decoded(Ok(d)) => d.get_key(),
+      SampleValue::Decoded(Err(k)) => k.clone(),
+      SampleValue::Serialized { key, .. } => key.clone(),
+    }
+  }
+}
+
+// A data sample of an instance that the DataReader already knows is stored serialized.
+// It is deserialized only when the application accesses it, and then it stays decoded.
+// Samples that are dropped before that, e.g. by History, are never deserialized.
+//
+// ReadIter decodes samples through a shared reference as it reaches them, so the result
+// is kept in a OnceCell. None there means that the sample was rejected. ReadIter skips
+// it, and decode_samples removes it.
+enum SampleValue<D: Keyed> {
+  Decoded(Result<D, D::K>),
+  Serialized {
+    key: D::K,
+    payload: SerializedPayload,
+    encoding: RepresentationIdentifier,
+    decoded: OnceCell<Option<D>>,
+  },
+}
+
+impl<D> SampleValue<D>
+where
+  D: Keyed,
+  <D as Keyed>::K: Key,
+{
+  // Samples must be decoded with decode_samples before they are accessed.
+  fn decoded(&self) -> Result<&D, D::K> {
+    match self {
+      SampleValue::Decoded(r) => result_ok_as_ref_err_clone(r),
+      SampleValue::Serialized { decoded, .. } => match decoded.get() {
+        Some(Some(d)) => Ok(d),
+        _ => panic!("Accessed a sample that was not deserialized"),
+      },
+    }
+  }
+
+  fn into_decoded(self) -> Result<D, D::K> {
+    match self {
+      SampleValue::Decoded(r) => r,
+      SampleValue::Serialized { decoded, .. } => match decoded.into_inner() {
+        Some(Some(d)) => Ok(d),
+        _ => panic!("Accessed a sample that was not deserialized"),
+      },
     }
   }
 }
@@ -102,18 +171,149 @@ where
     &mut self,
     new_sample: Result<D, D::K>,
     writer_guid: GUID,
-    receive_timestamp: Timestamp,
+    instant: CacheInstant,
     source_timestamp: Option<Timestamp>,
   ) {
-    let instance_key = match &new_sample {
-      Ok(d) => d.get_key(),
-      Err(k) => k.clone(),
-    };
-
     let new_instance_state = match new_sample {
       Ok(_) => InstanceState::Alive,
       Err(_) => InstanceState::NotAlive_Disposed,
     };
+    self.add_sample_with_state(
+      SampleValue::Decoded(new_sample),
+      new_instance_state,
+      writer_guid,
+      instant,
+      source_timestamp,
+    )
+  }
+
+  // A data sample that knows its identity (writer GUID and sequence number), and possibly
+  // the identity of a sample it relates to. These are shown in its SampleInfo.
+  pub fn add_sample_with_identity(
+    &mut self,
+    new_sample: D,
+    instant: CacheInstant,
+    source_timestamp: Option<Timestamp>,
+    sample_identity: SampleIdentity,
+    related_sample_identity: Option<SampleIdentity>,
+  ) {
+    self.add_value_with_identity(
+      SampleValue::Decoded(Ok(new_sample)),
+      instant,
+      source_timestamp,
+      sample_identity,
+      related_sample_identity,
+    )
+  }
+
+  // Like add_sample_with_identity, but the sample of instance `key` is kept serialized
+  // until decode_samples is called for it.
+  #[allow(clippy::too_many_arguments)]
+  pub fn add_serialized_sample(
+    &mut self,
+    key: D::K,
+    payload: SerializedPayload,
+    encoding: RepresentationIdentifier,
+    instant: CacheInstant,
+    source_timestamp: Option<Timestamp>,
+    sample_identity: SampleIdentity,
+    related_sample_identity: Option<SampleIdentity>,
+  ) {
+    self.add_value_with_identity(
+      SampleValue::Serialized {
+        key,
+        payload,
+        encoding,
+        decoded: OnceCell::new(),
+      },
+      instant,
+      source_timestamp,
+      sample_identity,
+      related_sample_identity,
+    )
+  }
+
+  fn add_value_with_identity(
+    &mut self,
+    value: SampleValue<D>,
+    instant: CacheInstant,
+    source_timestamp: Option<Timestamp>,
+    sample_identity: SampleIdentity,
+    related_sample_identity: Option<SampleIdentity>,
+  ) {
+    self.add_sample_with_state(
+      value,
+      InstanceState::Alive,
+      sample_identity.writer_guid,
+      instant,
+      source_timestamp,
+    );
+    // The sample may have been dropped, e.g. by destination order.
+    if let Some(dswm) = self.datasamples.get_mut(&instant) {
+      dswm.sample_identity = Some(sample_identity);
+      dswm.related_sample_identity = related_sample_identity;
+    }
+  }
+
+  // A writer unregisters an instance. This only changes the instance state, if
+  // it was the last live writer of an Alive instance. Then the instance becomes
+  // NotAlive_NoWriters, and a sample without data is added to notify the application.
+  pub fn add_unregister(
+    &mut self,
+    instance_key: D::K,
+    writer_guid: GUID,
+    instant: CacheInstant,
+    source_timestamp: Option<Timestamp>,
+  ) {
+    let becomes_no_writers = match self.instance_map.get_mut(&instance_key) {
+      Some(imd) => {
+        imd.live_writers.remove(&writer_guid);
+        imd.live_writers.is_empty() && imd.instance_state == InstanceState::Alive
+      }
+      None => false, // unknown instance, nothing to unregister
+    };
+    if becomes_no_writers {
+      self.add_sample_with_state(
+        SampleValue::Decoded(Err(instance_key)),
+        InstanceState::NotAlive_NoWriters,
+        writer_guid,
+        instant,
+        source_timestamp,
+      )
+    }
+  }
+
+  fn add_sample_with_state(
+    &mut self,
+    new_sample: SampleValue<D>,
+    new_instance_state: InstanceState,
+    writer_guid: GUID,
+    instant: CacheInstant,
+    source_timestamp: Option<Timestamp>,
+  ) {
+    let instance_key = match &new_sample {
+      SampleValue::Decoded(Ok(d)) => d.get_key(),
+      SampleValue::Decoded(Err(k)) => k.clone(),
+      SampleValue::Serialized { key, .. } => key.clone(),
+    };
+
+    // DDS spec 2.2.3.17: With BySourceTimestamp destination order, a sample older than
+    // the ones already received for the instance must not become its latest value.
+    if self.qos.destination_order() == Some(policy::DestinationOrder::BySourceTimeStamp) {
+      let latest = self
+        .instance_map
+        .get(&instance_key)
+        .and_then(|imd| imd.latest_source_timestamp);
+      if let (Some(latest), Some(new)) = (latest, source_timestamp) {
+        if new < latest {
+          debug!(
+            "Dropping sample with source timestamp {:?} older than {:?}",
+            new, latest
+          );
+          return;
+        }
+      }
+    }
 
     // find or create metadata record
     let instance_metadata = match self.instance_map.get_mut(&instance_key) {
@@ -125,7 +325,9 @@ where
           instance_samples: BTreeSet::new(),
           instance_state: new_instance_state,
           latest_generation_available: NotAliveGenerationCounts::zero(), // this is new instance, so start from zero
-          last_generation_accessed: NotAliveGenerationCounts::sub_zero(), // never accessed
+          last_generation_accessed: Cell::new(NotAliveGenerationCounts::sub_zero()), // never accessed
+          live_writers: BTreeSet::new(),
+          latest_source_timestamp: None,
         };
         self.instance_map.insert(instance_key.clone(), imd);
         self
@@ -136,9 +338,7 @@ where
     };
 
     // update instance metadata
-    instance_metadata
-      .instance_samples
-      .insert(receive_timestamp.clone());
+    instance_metadata.instance_samples.insert(instant);
 
     match (instance_metadata.instance_state, new_instance_state) {
       (InstanceState::Alive, _) => (), // was Alive, does not change counts
@@ -164,17 +364,25 @@ where
       (InstanceState::NotAlive_NoWriters, _) => (), // you can only die once
     }
     instance_metadata.instance_state = new_instance_state;
+    instance_metadata.latest_source_timestamp =
+      std::cmp::max(instance_metadata.latest_source_timestamp, source_timestamp);
+    if new_instance_state == InstanceState::Alive {
+      instance_metadata.live_writers.insert(writer_guid);
+    }
 
     // insert new_sample to main table
     self
       .datasamples
       .insert(
-        receive_timestamp,
+        instant,
         SampleWithMetaData {
           generation_counts: instance_metadata.latest_generation_available,
           writer_guid,
           source_timestamp,
-          sample_has_been_read: false,
+          reception_timestamp: instant.timestamp(),
+          sample_identity: None,
+          related_sample_identity: None,
+          sample_has_been_read: Cell::new(false),
           sample: new_sample,
         },
       )
@@ -184,7 +392,7 @@ where
         |_already_existed| {
           panic!(
             "Tried to add duplicate datasample with the same key {:?}",
-            receive_timestamp
+            instant
           )
         },
       );
@@ -225,10 +433,66 @@ where
     // TODO: Implement other resource_limit settings than max_instances_per sample, i.e.
   }
 
+  // Deserializes the samples of `keys` that are still serialized. `decode` gets the
+  // instance key the sample was stored under, its payload, encoding and identity, and
+  // returns None if the sample is rejected. Rejected samples are removed from the cache.
+  // Returns false if any were, as `keys` then refers to samples that no longer exist.
+  pub fn decode_samples<F>(&mut self, keys: &[(CacheInstant, D::K)], mut decode: F) -> bool
+  where
+    F: FnMut(
+      &D::K,
+      &SerializedPayload,
+      RepresentationIdentifier,
+      Option<SampleIdentity>,
+    ) -> Option<D>,
+  {
+    let mut all_decoded = true;
+    for (instant, key) in keys.iter() {
+      let dswm = match self.datasamples.get(instant) {
+        Some(dswm) => dswm,
+        None => continue,
+      };
+      let accepted = match &dswm.sample {
+        SampleValue::Decoded(_) => continue,
+        SampleValue::Serialized {
+          payload,
+          encoding,
+          decoded,
+          ..
+        } => decoded
+          .get_or_init(|| decode(key, payload, *encoding, dswm.sample_identity))
+          .is_some(),
+      };
+      if !accepted {
+        self.remove_sample(instant, key);
+        all_decoded = false;
+      }
+    }
+    all_decoded
+  }
+
+  // The decoder of read_by_keys and take_by_keys, whose samples decode_samples has
+  // decoded already.
+  fn already_decoded(
+    _key: &D::K,
+    _payload: &SerializedPayload,
+    _encoding: RepresentationIdentifier,
+    _sample_identity: Option<SampleIdentity>,
+  ) -> Option<D> {
+    panic!("Accessed a sample that was not deserialized")
+  }
+
+  fn remove_sample(&mut self, instant: &CacheInstant, key: &D::K) {
+    self.datasamples.remove(instant);
+    if let Some(imd) = self.instance_map.get_mut(key) {
+      imd.instance_samples.remove(instant);
+    }
+  }
+
   // Calling select_(instance)_keys_for access does not constitute access, i.e.
   // it does not change any state of the cache.
   // Samples are marked read or viewed only when "read" or "take" methods (below) are called.
-  pub fn select_keys_for_access(&self, rc: ReadCondition) -> Vec<(Timestamp, D::K)> {
+  pub fn select_keys_for_access(&self, rc: ReadCondition) -> Vec<(CacheInstant, D::K)> {
     self
       .datasamples
       .iter()
@@ -247,7 +511,7 @@ where
     &self,
     instance: D::K,
     rc: ReadCondition,
-  ) -> Vec<(Timestamp, D::K)> {
+  ) -> Vec<(CacheInstant, D::K)> {
     match self.instance_map.get(&instance) {
       None => Vec::new(),
       Some(imd) => imd
@@ -268,6 +532,31 @@ where
     }
   }
 
+  // The newest sample of each instance that matches rc, in key order. The samples of an
+  // instance are gone through from the newest only until one matches, so the rest of its
+  // history is not looked at.
+  pub fn select_latest_per_instance_for_access(
+    &self,
+    rc: ReadCondition,
+  ) -> Vec<(CacheInstant, D::K)> {
+    self
+      .instance_map
+      .iter()
+      .filter(|(_key, imd)| Self::instance_selector(&rc, imd))
+      .filter_map(|(key, imd)| {
+        imd
+          .instance_samples
+          .iter()
+          .rev()
+          .find(|ts| match self.datasamples.get(ts) {
+            Some(ds) => self.sample_selector(&rc, imd, ds),
+            None => false,
+          })
+          .map(|ts| (*ts, key.clone()))
+      })
+      .collect()
+  }
+
   // select helper
   fn sample_selector(
     &self,
@@ -277,18 +566,22 @@ where
   ) -> bool {
     // check sample state
     (*rc.sample_state_mask() == SampleState::any()
-      || rc.sample_state_mask()
-          .contains( if d.sample_has_been_read { SampleState::Read } else {SampleState::NotRead} ) )
-    &&
+      || rc
+        .sample_state_mask()
+        .contains(if d.sample_has_been_read.get() {
+          SampleState::Read
+        } else {
+          SampleState::NotRead
+        }))
+      && Self::instance_selector(rc, imd)
+  }
+
+  // The view and instance states are the same for all samples of an instance.
+  fn instance_selector(rc: &ReadCondition, imd: &InstanceMetaData) -> bool {
     // check view state
-    (*rc.view_state_mask() == ViewState::any() 
-      ||
-      { let sample_gen = d.generation_counts.total();
-        let last_accessed = imd.last_generation_accessed.total();
-        let is_new = sample_gen > last_accessed;
-        rc.view_state_mask()
-          .contains( if is_new { ViewState::New} else { ViewState::NotNew }  )
-      }
+    (*rc.view_state_mask() == ViewState::any()
+      || rc.view_state_mask()
+          .contains( imd.view_state() )
     )
     &&
     // check instance state
@@ -302,52 +595,72 @@ where
     dswm: &SampleWithMetaData<D>,
     imd: &InstanceMetaData,
     sample_rank: usize,
-    mrs_generations: i32,
     mrsic_generations: i32,
   ) -> SampleInfo {
+    // The most recent sample of the instance always carries the latest generation counts,
+    // because the counts only increase when a new sample revives the instance.
+    let mrs_generations = imd.latest_generation_available.total();
     SampleInfo {
-      sample_state: if dswm.sample_has_been_read {
+      sample_state: if dswm.sample_has_been_read.get() {
         SampleState::Read
       } else {
         SampleState::NotRead
       },
-      view_state: if dswm.generation_counts.total() > imd.last_generation_accessed.total() {
-        ViewState::New
-      } else {
-        ViewState::NotNew
-      },
+      view_state: imd.view_state(),
       instance_state: imd.instance_state,
-      generation_counts: dswm.generation_counts.clone(),
-      sample_rank: sample_rank as i32, // how many samples follow this one
+      generation_counts: dswm.generation_counts,
+      sample_rank: sample_rank as i32, // how many samples of the same instance follow this one
       generation_rank: mrsic_generations - dswm.generation_counts.total(),
       absolute_generation_rank: mrs_generations - dswm.generation_counts.total(),
-      source_timestamp: dswm.source_timestamp.clone(),
+      source_timestamp: dswm.source_timestamp.unwrap_or(Timestamp::TIME_INVALID),
+      reception_timestamp: dswm.reception_timestamp,
       publication_handle: dswm.writer_guid,
+      sample_identity: dswm.sample_identity,
+      related_sample_identity: dswm.related_sample_identity,
     }
   }
 
-  fn record_instance_generation_viewed(
-    instance_generations: &mut HashMap<D::K, NotAliveGenerationCounts>,
-    accessed_generations: NotAliveGenerationCounts,
-    instance_key: &D::K,
-  ) {
-    instance_generations
-      .entry(instance_key.clone())
-      .and_modify(|old_gens| {
-        if accessed_generations.total() > old_gens.total() {
-          *old_gens = accessed_generations
-        }
-      })
-      .or_insert(accessed_generations);
+  // Ranks in SampleInfo are relative to the returned collection, so they must be computed
+  // from the key vector before any sample is read or removed.
+  // Returns, for each instance in the collection, the number of its samples in the collection
+  // and the generation count total of its Most Recent Sample In Collection (MRSIC).
+  fn collection_instance_ranks(
+    &self,
+    keys: &[(CacheInstant, D::K)],
+  ) -> HashMap<D::K, (usize, i32)> {
+    let mut ranks: HashMap<D::K, (usize, CacheInstant, i32)> = HashMap::new();
+    for (ts, key) in keys.iter() {
+      let gen = self.datasamples.get(ts).unwrap().generation_counts.total();
+      ranks
+        .entry(key.clone())
+        .and_modify(|(count, mrsic_ts, mrsic_gen)| {
+          *count += 1;
+          if *ts > *mrsic_ts {
+            *mrsic_ts = *ts;
+            *mrsic_gen = gen;
+          }
+        })
+        .or_insert((1, *ts, gen));
+    }
+    ranks
+      .into_iter()
+      .map(|(k, (count, _ts, gen))| (k, (count, gen)))
+      .collect()
   }
 
-  fn mark_instances_viewed(
-    &mut self,
-    instance_generations: HashMap<D::K, NotAliveGenerationCounts>,
-  ) {
-    for (inst, gen) in instance_generations.iter() {
-      if let Some(imd) = self.instance_map.get_mut(inst) {
-        imd.last_generation_accessed = *gen;
+  // Returns sample_rank and MRSIC generations for the next sample of the instance.
+  fn next_sample_rank(ranks: &mut HashMap<D::K, (usize, i32)>, key: &D::K) -> (usize, i32) {
+    let (remaining, mrsic_gen) = ranks.get_mut(key).unwrap();
+    *remaining -= 1;
+    (*remaining, *mrsic_gen)
+  }
+
+  fn mark_instances_viewed(&self, keys: &[(CacheInstant, D::K)]) {
+    for (_ts, key) in keys.iter() {
+      if let Some(imd) = self.instance_map.get(key) {
+        imd
+          .last_generation_accessed
+          .set(imd.latest_generation_available);
       } else {
         panic!("Instance disappeared!?!!1!")
       }
@@ -357,113 +670,87 @@ where
   // read methods perform actual read or take. They must be called with key vectors
   // obtained from select_*_for_access -methods above, or their subvectors.
   //
+  // The iterator versions access samples only as the iterator is consumed. They
+  // deserialize the samples still serialized with `decode`, see decode_samples, and skip
+  // the rejected ones.
   // Therea are two versions of both read and take: Return DataSample<D> (incl. metadata)
   // and "bare" versions without metadata.
-  pub fn read_by_keys(&mut self, keys: &[(Timestamp, D::K)]) -> Vec<DataSample<&D>> {
-    let len = keys.len();
-    let mut result = Vec::with_capacity(len);
-
-    if len == 0 {
-      return result;
+  pub fn read_iter<F>(&self, keys: Vec<(CacheInstant, D::K)>, decode: F) -> ReadIter<D, F>
+  where
+    F: FnMut(
+      &D::K,
+      &SerializedPayload,
+      RepresentationIdentifier,
+      Option<SampleIdentity>,
+    ) -> Option<D>,
+  {
+    ReadIter {
+      ranks: self.collection_instance_ranks(&keys),
+      cache: self,
+      keys,
+      position: 0,
+      decode,
     }
+  }
 
-    let mut instance_generations: HashMap<D::K, NotAliveGenerationCounts> = HashMap::new();
-    let mrsic_total = self
-      .instance_map
-      .get(&keys.last().unwrap().1)
-      .unwrap()
-      .latest_generation_available
-      .total();
-    let mrs_total = self
-      .datasamples
-      .iter()
-      .next_back()
-      .unwrap()
-      .1
-      .generation_counts
-      .total();
-    let mut sample_infos = VecDeque::with_capacity(len);
-    // construct SampleInfos and record read/viewed
-    for (index, (ts, key)) in keys.iter().enumerate() {
-      let dswm = self.datasamples.get_mut(ts).unwrap();
-      let imd = self.instance_map.get(key).unwrap();
-
-      let sample_info = Self::make_sample_info(dswm, imd, len - index - 1, mrs_total, mrsic_total);
-      dswm.sample_has_been_read = true; // mark as read
-      Self::record_instance_generation_viewed(
-        &mut instance_generations,
-        dswm.generation_counts,
-        key,
-      );
-      sample_infos.push_back(sample_info);
-    }
-
-    // mark instances viewed
-    self.mark_instances_viewed(instance_generations);
-
-    // We need to do SampleInfo construction and final result construction as separate passes.
-    // This is becaue SampleInfo construction needs to mark items as read and generations
-    // as viewed, i.e. needs mutable reference to data_samples.
-    // Result construction (in read, not take) needs to hand out multiple references into
-    // data_samples, therefore it needs immutable access, not mutable.
-
-    // construct results
-    for (ts, _key) in keys.iter() {
-      let sample_info = sample_infos.pop_front().unwrap();
-      let sample: &std::result::Result<D, D::K> = &self.datasamples.get(ts).unwrap().sample;
-      result.push(DataSample::new(
-        sample_info,
-        result_ok_as_ref_err_clone(sample),
-      ));
+  pub fn take_iter<F>(&mut self, keys: Vec<(CacheInstant, D::K)>, decode: F) -> TakeIter<D, F>
+  where
+    F: FnMut(
+      &D::K,
+      &SerializedPayload,
+      RepresentationIdentifier,
+      Option<SampleIdentity>,
+    ) -> Option<D>,
+  {
+    TakeIter {
+      ranks: self.collection_instance_ranks(&keys),
+      cache: self,
+      keys,
+      position: 0,
+      decode,
     }
+  }
 
+  pub fn read_by_keys(&self, keys: &[(CacheInstant, D::K)]) -> Vec<DataSample<&D>> {
+    let mut result = Vec::with_capacity(keys.len());
+    result.extend(self.read_iter(keys.to_vec(), Self::already_decoded));
     result
   }
 
-  pub fn take_by_keys(&mut self, keys: &[(Timestamp, D::K)]) -> Vec<DataSample<D>> {
-    let len = keys.len();
-    let mut result = Vec::with_capacity(len);
-
-    if len == 0 {
-      return result;
-    }
+  pub fn take_by_keys(&mut self, keys: &[(CacheInstant, D::K)]) -> Vec<DataSample<D>> {
+    let mut result = Vec::with_capacity(keys.len());
+    result.extend(self.take_iter(keys.to_vec(), Self::already_decoded));
+    result
+  }
 
-    let mut instance_generations: HashMap<D::K, NotAliveGenerationCounts> = HashMap::new();
-    let mrsic_total = self
-      .instance_map
-      .get(&keys.last().unwrap().1)
-      .unwrap()
-      .latest_generation_available
-      .total();
-    let mrs_total = self
-      .datasamples
+  // Lends the selected samples without copying them out of the cache. The samples are
+  // marked read and their instances viewed immediately. If `take` is set, the samples
+  // are removed when the loan is returned, i.e. LoanedSamples is dropped.
+  pub fn loan(&mut self, keys: Vec<(CacheInstant, D::K)>, take: bool) -> LoanedSamples<D> {
+    let mut ranks = self.collection_instance_ranks(&keys);
+    let sample_infos = keys
       .iter()
-      .next_back()
-      .unwrap()
-      .1
-      .generation_counts
-      .total();
-    // collect result
-    for (index, (ts, key)) in keys.iter().enumerate() {
-      let dswm = self.datasamples.remove(ts).unwrap();
-      let imd = self.instance_map.get(key).unwrap();
-      let sample_info = Self::make_sample_info(&dswm, imd, len - index - 1, mrs_total, mrsic_total);
-      //dwsm.sample_has_been_read = true; // no need to mark read, as the dswm is about to be destroyed
-      Self::record_instance_generation_viewed(
-        &mut instance_generations,
-        dswm.generation_counts,
-        key,
-      );
-      result.push(DataSample::new(sample_info, dswm.sample));
+      .map(|(ts, key)| {
+        let (sample_rank, mrsic_total) = Self::next_sample_rank(&mut ranks, key);
+        let dswm = self.datasamples.get(ts).unwrap();
+        let imd = self.instance_map.get(key).unwrap();
+        let sample_info = Self::make_sample_info(dswm, imd, sample_rank, mrsic_total);
+        dswm.sample_has_been_read.set(true); // mark as read
+        sample_info
+      })
+      .collect();
+    self.mark_instances_viewed(&keys);
+    LoanedSamples {
+      cache: self,
+      keys,
+      sample_infos,
+      take,
     }
-
-    self.mark_instances_viewed(instance_generations);
-    result
   }
 
   pub fn read_bare_by_keys(
-    &mut self,
-    keys: &[(Timestamp, D::K)],
+    &self,
+    keys: &[(CacheInstant, D::K)],
   ) -> Vec<std::result::Result<&D, D::K>> {
     let len = keys.len();
     let mut result = Vec::with_capacity(len);
@@ -472,36 +759,19 @@ where
       return result;
     }
 
-    let mut instance_generations: HashMap<D::K, NotAliveGenerationCounts> = HashMap::new();
-
-    // construct SampleInfos and record read/viewed
-    for (ts, key) in keys.iter() {
-      let dswm = self.datasamples.get_mut(ts).unwrap();
-      dswm.sample_has_been_read = true; // mark as read
-      Self::record_instance_generation_viewed(
-        &mut instance_generations,
-        dswm.generation_counts,
-        key,
-      );
-    }
-
-    self.mark_instances_viewed(instance_generations);
-
-    // We need to do SampleInfo construction and final result construction as separate passes.
-    // See reason in read function above.
-
-    // construct results
     for (ts, _key) in keys.iter() {
-      result.push(result_ok_as_ref_err_clone(
-        &self.datasamples.get(ts).unwrap().sample,
-      ));
+      let dswm = self.datasamples.get(ts).unwrap();
+      dswm.sample_has_been_read.set(true); // mark as read
+      result.push(dswm.sample.decoded());
     }
+
+    self.mark_instances_viewed(keys);
     result
   }
 
   pub fn take_bare_by_keys(
     &mut self,
-    keys: &[(Timestamp, D::K)],
+    keys: &[(CacheInstant, D::K)],
   ) -> Vec<std::result::Result<D, D::K>> {
     let len = keys.len();
     let mut result = Vec::with_capacity(len);
@@ -510,20 +780,16 @@ where
       return result;
     }
 
-    let mut instance_generations: HashMap<D::K, NotAliveGenerationCounts> = HashMap::new();
-
     for (ts, key) in keys.iter() {
       let dswm = self.datasamples.remove(ts).unwrap();
       //dwsm.sample_has_been_read = true; // no need to mark read, as the dswm is about to be destroyed
-      Self::record_instance_generation_viewed(
-        &mut instance_generations,
-        dswm.generation_counts,
-        key,
-      );
-      result.push(dswm.sample);
+      if let Some(imd) = self.instance_map.get_mut(key) {
+        imd.instance_samples.remove(ts);
+      }
+      result.push(dswm.sample.into_decoded());
     }
 
-    self.mark_instances_viewed(instance_generations);
+    self.mark_instances_viewed(keys);
     result
   }
 
@@ -537,6 +803,11 @@ where
     self.hash_to_key_map.get(&key_hash).map(|key| key.clone())
   }
 
+  // Makes get_key_by_hash find `key`, even if no sample of its instance is in the cache.
+  pub fn remember_key(&mut self, key: D::K) {
+    self.hash_to_key_map.insert(key.into_hash_key(), key);
+  }
+
   pub fn get_next_key(&self, key: &D::K) -> Option<D::K> {
     self
       .instance_map
@@ -550,15 +821,266 @@ where
   }
 }
 
+/// Lazily reads samples selected from a DataSampleCache.
+///
+/// Samples are deserialized and marked read as they are consumed. Instances are marked
+/// viewed when the iterator is dropped, so that all samples of an instance in the same
+/// collection report the same view_state. The sample_rank of a sample counts the later
+/// samples of its instance that are rejected when they are reached.
+pub struct ReadIter<'a, D: Keyed, F>
+where
+  <D as Keyed>::K: Key,
+{
+  cache: &'a DataSampleCache<D>,
+  keys: Vec<(CacheInstant, D::K)>,
+  ranks: HashMap<D::K, (usize, i32)>,
+  position: usize,
+  decode: F,
+}
+
+impl<'a, D, F> Iterator for ReadIter<'a, D, F>
+where
+  D: Keyed,
+  <D as Keyed>::K: Key,
+  F:
+    FnMut(&D::K, &SerializedPayload, RepresentationIdentifier, Option<SampleIdentity>) -> Option<D>,
+{
+  type Item = DataSample<&'a D>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let (ts, key) = self.keys.get(self.position)?;
+      self.position += 1;
+      let (sample_rank, mrsic_total) = DataSampleCache::<D>::next_sample_rank(&mut self.ranks, key);
+      let cache: &'a DataSampleCache<D> = self.cache;
+      let dswm = cache.datasamples.get(ts).unwrap();
+      let value = match &dswm.sample {
+        SampleValue::Decoded(r) => result_ok_as_ref_err_clone(r),
+        SampleValue::Serialized {
+          payload,
+          encoding,
+          decoded,
+          ..
+        } => {
+          let decode = &mut self.decode;
+          match decoded.get_or_init(|| decode(key, payload, *encoding, dswm.sample_identity)) {
+            Some(d) => Ok(d),
+            None => continue, // rejected
+          }
+        }
+      };
+      let imd = cache.instance_map.get(key).unwrap();
+
+      let sample_info = DataSampleCache::make_sample_info(dswm, imd, sample_rank, mrsic_total);
+      dswm.sample_has_been_read.set(true); // mark as read
+      return Some(DataSample::new(sample_info, value));
+    }
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    // samples may be rejected
+    (0, Some(self.keys.len() - self.position))
+  }
+}
+
+impl<'a, D, F> Drop for ReadIter<'a, D, F>
+where
+  D: Keyed,
+  <D as Keyed>::K: Key,
+{
+  fn drop(&mut self) {
+    self
+      .cache
+      .mark_instances_viewed(&self.keys[..self.position]);
+  }
+}
+
+/// Lazily takes samples selected from a DataSampleCache.
+///
+/// Samples are deserialized and removed from the cache only as they are consumed. As
+/// with ReadIter, the sample_rank of a sample counts the later samples of its instance
+/// that are rejected.
+pub struct TakeIter<'a, D: Keyed, F>
+where
+  <D as Keyed>::K: Key,
+{
+  cache: &'a mut DataSampleCache<D>,
+  keys: Vec<(CacheInstant, D::K)>,
+  ranks: HashMap<D::K, (usize, i32)>,
+  position: usize,
+  decode: F,
+}
+
+impl<'a, D, F> Iterator for TakeIter<'a, D, F>
+where
+  D: Keyed,
+  <D as Keyed>::K: Key,
+  F:
+    FnMut(&D::K, &SerializedPayload, RepresentationIdentifier, Option<SampleIdentity>) -> Option<D>,
+{
+  type Item = DataSample<D>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let (ts, key) = self.keys.get(self.position)?;
+      self.position += 1;
+      let (sample_rank, mrsic_total) = DataSampleCache::<D>::next_sample_rank(&mut self.ranks, key);
+      let dswm = self.cache.datasamples.remove(ts).unwrap();
+      let imd = self.cache.instance_map.get_mut(key).unwrap();
+      let sample_info = DataSampleCache::make_sample_info(&dswm, imd, sample_rank, mrsic_total);
+      // no need to mark read, as the dswm is about to be destroyed
+      imd.instance_samples.remove(ts);
+      let value = match dswm.sample {
+        SampleValue::Decoded(r) => r,
+        SampleValue::Serialized {
+          payload,
+          encoding,
+          decoded,
+          ..
+        } => match decoded.into_inner() {
+          Some(Some(d)) => Ok(d),
+          Some(None) => continue, // rejected by a ReadIter
+          None => match (self.decode)(key, &payload, encoding, dswm.sample_identity) {
+            Some(d) => Ok(d),
+            None => continue, // rejected
+          },
+        },
+      };
+      return Some(DataSample::new(sample_info, value));
+    }
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    // samples may be rejected
+    (0, Some(self.keys.len() - self.position))
+  }
+}
+
+impl<'a, D, F> Drop for TakeIter<'a, D, F>
+where
+  D: Keyed,
+  <D as Keyed>::K: Key,
+{
+  fn drop(&mut self) {
+    self
+      .cache
+      .mark_instances_viewed(&self.keys[..self.position]);
+  }
+}
+
+/// Samples lent by a DataReader, see
+/// [`read_loaned`](struct.DataReader.html#method.read_loaned) and
+/// [`take_loaned`](struct.DataReader.html#method.take_loaned).
+///
+/// The sample values are borrowed from the deserialized samples stored in the DataReader,
+/// so they are not copied. The loan holds a mutable borrow of the DataReader: no new
+/// samples are added to it while the loan is outstanding, and therefore History and
+/// ResourceLimits cannot evict or overwrite any loaned sample.
+///
+/// Taken samples are removed from the DataReader only when LoanedSamples is dropped.
+pub struct LoanedSamples<'a, D: Keyed>
+where
+  <D as Keyed>::K: Key,
+{
+  cache: &'a mut DataSampleCache<D>,
+  keys: Vec<(CacheInstant, D::K)>,
+  sample_infos: Vec<SampleInfo>,
+  take: bool,
+}
+
+impl<'a, D> LoanedSamples<'a, D>
+where
+  D: Keyed,
+  <D as Keyed>::K: Key,
+{
+  pub fn len(&self) -> usize {
+    self.keys.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.keys.is_empty()
+  }
+
+  pub fn get(&self, index: usize) -> Option<DataSample<&D>> {
+    let (ts, _key) = self.keys.get(index)?;
+    let dswm = self.cache.datasamples.get(ts).unwrap();
+    Some(DataSample::new(
+      self.sample_infos[index].clone(),
+      dswm.sample.decoded(),
+    ))
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = DataSample<&D>> {
+    (0..self.len()).filter_map(move |i| self.get(i))
+  }
+}
+
+impl<'a, D> Drop for LoanedSamples<'a, D>
+where
+  D: Keyed,
+  <D as Keyed>::K: Key,
+{
+  fn drop(&mut self) {
+    if self.take {
+      for (ts, key) in self.keys.iter() {
+        self.cache.remove_sample(ts, key);
+      }
+    }
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
   use crate::{
-    structure::{time::Timestamp},
+    structure::{dds_cache::CacheInstant, time::Timestamp},
   };
   use crate::dds::ddsdata::DDSData;
+  use crate::serialization::cdr_serializer::CDRSerializerAdapter;
   use crate::dds::traits::key::Keyed;
+  use crate::dds::qos::QosPolicyBuilder;
+  use crate::structure::duration::Duration;
   use crate::test::random_data::*;
+  use crate::serialization::{cdr_serializer::to_bytes, CDRDeserializerAdapter};
+  use crate::dds::traits::serde_adapters::DeserializerAdapter;
+  use byteorder::LittleEndian;
+  use enumflags2::BitFlags;
+  use std::cell::RefCell;
+
+  fn keep_all_cache() -> DataSampleCache<RandomData> {
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .build();
+    DataSampleCache::<RandomData>::new(qos)
+  }
+
+  // distinct, increasing timestamps
+  fn nth_timestamp(base: Timestamp, n: i64) -> Timestamp {
+    base - Duration::from_millis(1000 - n)
+  }
+
+  // distinct, increasing DDSCache instants
+  fn nth_instant(base: Timestamp, n: i64) -> CacheInstant {
+    CacheInstant::from(nth_timestamp(base, n))
+  }
+
+  fn data(a: i64) -> RandomData {
+    RandomData {
+      a,
+      b: "Fobar".to_string(),
+    }
+  }
+
+  fn sample_infos(
+    dsc: &mut DataSampleCache<RandomData>,
+    keys: &[(CacheInstant, i64)],
+  ) -> Vec<SampleInfo> {
+    dsc
+      .read_by_keys(keys)
+      .iter()
+      .map(|s| s.sample_info().clone())
+      .collect()
+  }
 
   #[test]
   fn dsc_empty_qos() {
@@ -571,20 +1093,518 @@ mod tests {
       b: "Fobar".to_string(),
     };
 
-    let org_ddsdata = DDSData::from(&data, Some(timestamp));
+    let org_ddsdata = DDSData::from::<_, CDRSerializerAdapter<_>>(&data, Some(timestamp)).unwrap();
 
     let key = data.get_key().clone();
-    datasample_cache.add_sample(Ok(data.clone()), GUID::GUID_UNKNOWN, timestamp, None);
+    let instant = CacheInstant::from(timestamp);
+    datasample_cache.add_sample(Ok(data.clone()), GUID::GUID_UNKNOWN, instant, None);
     //datasample_cache.add_datasample(datasample).unwrap();
 
-    let samples = datasample_cache.read_by_keys(&[(timestamp, key)]);
+    let samples = datasample_cache.read_by_keys(&[(instant, key)]);
     assert_eq!(samples.len(), 1);
     match &samples.get(0).unwrap().value() {
       Ok(huh) => {
-        let ddssample = DDSData::from(huh, Some(timestamp));
+        let ddssample = DDSData::from::<_, CDRSerializerAdapter<_>>(huh, Some(timestamp)).unwrap();
         assert_eq!(org_ddsdata, ddssample);
       }
       _ => (),
     }
   }
+
+  #[test]
+  fn dsc_sample_rank_per_instance() {
+    let mut dsc = keep_all_cache();
+    let base = Timestamp::now();
+    let writer = GUID::new();
+    for (n, a) in [1, 2, 1, 2, 1].iter().enumerate() {
+      dsc.add_sample(Ok(data(*a)), writer, nth_instant(base, n as i64), None);
+    }
+
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    assert_eq!(keys.len(), 5);
+    let infos = sample_infos(&mut dsc, &keys);
+    let ranks: Vec<i32> = infos.iter().map(|si| si.sample_rank).collect();
+    assert_eq!(ranks, vec![2, 1, 1, 0, 0]);
+    assert!(infos.iter().all(|si| si.view_state == ViewState::New));
+    assert!(infos.iter().all(|si| si.sample_state == SampleState::NotRead));
+    assert!(infos.iter().all(|si| si.publication_handle == writer));
+
+    // Ranks are relative to the returned collection
+    let infos = sample_infos(&mut dsc, &keys[2..]);
+    let ranks: Vec<i32> = infos.iter().map(|si| si.sample_rank).collect();
+    assert_eq!(ranks, vec![1, 0, 0]);
+    assert!(infos.iter().all(|si| si.view_state == ViewState::NotNew));
+    assert!(infos.iter().all(|si| si.sample_state == SampleState::Read));
+  }
+
+  #[test]
+  fn dsc_dispose_revive_generations() {
+    let mut dsc = keep_all_cache();
+    let base = Timestamp::now();
+    let writer = GUID::new();
+    dsc.add_sample(Ok(data(1)), writer, nth_instant(base, 0), None);
+    dsc.add_sample(Err(1), writer, nth_instant(base, 1), None);
+    dsc.add_sample(Ok(data(1)), writer, nth_instant(base, 2), None);
+    dsc.add_sample(Err(1), writer, nth_instant(base, 3), None);
+    dsc.add_sample(Ok(data(1)), writer, nth_instant(base, 4), None);
+
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+
+    // Only the oldest two samples: MRSIC is from the first generation, MRS from the third.
+    let infos = sample_infos(&mut dsc, &keys[..2]);
+    let gen_ranks: Vec<i32> = infos.iter().map(|si| si.generation_rank).collect();
+    let abs_ranks: Vec<i32> = infos.iter().map(|si| si.absolute_generation_rank).collect();
+    assert_eq!(gen_ranks, vec![0, 0]);
+    assert_eq!(abs_ranks, vec![2, 2]);
+    assert!(infos.iter().all(|si| si.instance_state == InstanceState::Alive));
+
+    let samples = dsc.take_by_keys(&keys);
+    let disposed_counts: Vec<i32> = samples
+      .iter()
+      .map(|s| s.sample_info().generation_counts.disposed_generation_count)
+      .collect();
+    let gen_ranks: Vec<i32> = samples
+      .iter()
+      .map(|s| s.sample_info().generation_rank)
+      .collect();
+    let abs_ranks: Vec<i32> = samples
+      .iter()
+      .map(|s| s.sample_info().absolute_generation_rank)
+      .collect();
+    assert_eq!(disposed_counts, vec![0, 0, 1, 1, 2]);
+    assert_eq!(gen_ranks, vec![2, 2, 1, 1, 0]);
+    assert_eq!(abs_ranks, vec![2, 2, 1, 1, 0]);
+    assert!(samples[1].value().is_err());
+    assert!(samples[4].value().is_ok());
+    assert!(dsc.select_keys_for_access(ReadCondition::any()).is_empty());
+  }
+
+  #[test]
+  fn dsc_view_state_reborn_instance() {
+    let mut dsc = keep_all_cache();
+    let base = Timestamp::now();
+    let writer = GUID::new();
+
+    dsc.add_sample(Ok(data(1)), writer, nth_instant(base, 0), None);
+    let keys = dsc.select_keys_for_access(ReadCondition::not_read());
+    assert_eq!(sample_infos(&mut dsc, &keys)[0].view_state, ViewState::New);
+
+    // second access of the same generation
+    dsc.add_sample(Ok(data(1)), writer, nth_instant(base, 1), None);
+    let keys = dsc.select_keys_for_access(ReadCondition::not_read());
+    assert_eq!(sample_infos(&mut dsc, &keys)[0].view_state, ViewState::NotNew);
+
+    // dispose does not make the instance new
+    dsc.add_sample(Err(1), writer, nth_instant(base, 2), None);
+    let keys = dsc.select_keys_for_access(ReadCondition::not_read());
+    let info = &sample_infos(&mut dsc, &keys)[0];
+    assert_eq!(info.view_state, ViewState::NotNew);
+    assert_eq!(info.instance_state, InstanceState::NotAlive_Disposed);
+
+    // rebirth does
+    dsc.add_sample(Ok(data(1)), writer, nth_instant(base, 3), None);
+    let keys = dsc.select_keys_for_access(ReadCondition::not_read());
+    let info = &sample_infos(&mut dsc, &keys)[0];
+    assert_eq!(info.view_state, ViewState::New);
+    assert_eq!(info.instance_state, InstanceState::Alive);
+
+    // all samples of the instance report the instance-wide view state
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    let infos = sample_infos(&mut dsc, &keys);
+    assert_eq!(infos.len(), 4);
+    assert!(infos.iter().all(|si| si.view_state == ViewState::NotNew));
+  }
+
+  #[test]
+  fn dsc_unregister_no_writers() {
+    let mut dsc = keep_all_cache();
+    let base = Timestamp::now();
+    let writer_a = GUID::new();
+    let writer_b = GUID::new();
+
+    dsc.add_sample(Ok(data(1)), writer_a, nth_instant(base, 0), None);
+    dsc.add_sample(Ok(data(1)), writer_b, nth_instant(base, 1), None);
+
+    // one writer remains, so instance stays alive and no sample is added
+    dsc.add_unregister(1, writer_a, nth_instant(base, 2), None);
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    assert_eq!(keys.len(), 2);
+
+    dsc.add_unregister(1, writer_b, nth_instant(base, 3), None);
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    assert_eq!(keys.len(), 3);
+    let infos = sample_infos(&mut dsc, &keys);
+    assert!(infos
+      .iter()
+      .all(|si| si.instance_state == InstanceState::NotAlive_NoWriters));
+
+    // writer comes back
+    dsc.add_sample(Ok(data(1)), writer_a, nth_instant(base, 4), None);
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    let samples = dsc.take_by_keys(&keys);
+    let last = samples.last().unwrap().sample_info();
+    assert_eq!(last.instance_state, InstanceState::Alive);
+    assert_eq!(last.view_state, ViewState::New);
+    assert_eq!(last.generation_counts.no_writers_generation_count, 1);
+    assert_eq!(last.generation_counts.disposed_generation_count, 0);
+    let abs_ranks: Vec<i32> = samples
+      .iter()
+      .map(|s| s.sample_info().absolute_generation_rank)
+      .collect();
+    assert_eq!(abs_ranks, vec![1, 1, 1, 0]);
+
+    // unregistering an unknown instance is ignored
+    dsc.add_unregister(2, writer_a, nth_instant(base, 5), None);
+    assert!(dsc.select_keys_for_access(ReadCondition::any()).is_empty());
+  }
+
+  #[test]
+  fn dsc_read_condition_state_masks() {
+    use InstanceState::*;
+    use SampleState::*;
+    use ViewState::*;
+
+    let mut dsc = keep_all_cache();
+    let base = Timestamp::now();
+    let writer_a = GUID::new();
+    let writer_b = GUID::new();
+
+    dsc.add_sample(Ok(data(1)), writer_a, nth_instant(base, 0), None);
+    dsc.add_sample(Ok(data(2)), writer_a, nth_instant(base, 1), None);
+    dsc.add_sample(Ok(data(3)), writer_b, nth_instant(base, 2), None);
+    dsc.add_sample(Ok(data(4)), writer_a, nth_instant(base, 3), None);
+    dsc.add_sample(Ok(data(5)), writer_a, nth_instant(base, 4), None);
+    dsc.add_sample(Err(5), writer_a, nth_instant(base, 5), None);
+    for key in &[1, 3, 5] {
+      let keys = dsc.select_instance_keys_for_access(*key, ReadCondition::any());
+      dsc.read_by_keys(&keys);
+    }
+    dsc.add_sample(Ok(data(1)), writer_a, nth_instant(base, 6), None);
+    dsc.add_sample(Err(2), writer_a, nth_instant(base, 7), None);
+    // writer_b is gone
+    dsc.add_unregister(3, writer_b, nth_instant(base, 8), None);
+
+    // (sample, view, instance) state of each sample, in order of instants
+    let expected_states = [
+      (Read, NotNew, Alive),
+      (NotRead, New, NotAlive_Disposed),
+      (Read, NotNew, NotAlive_NoWriters),
+      (NotRead, New, Alive),
+      (Read, NotNew, NotAlive_Disposed),
+      (Read, NotNew, NotAlive_Disposed),
+      (NotRead, NotNew, Alive),
+      (NotRead, New, NotAlive_Disposed),
+      (NotRead, NotNew, NotAlive_NoWriters),
+    ];
+
+    let instance_masks = [BitFlags::from(Alive), InstanceState::not_alive()];
+    for sample_state in &[Read, NotRead] {
+      for view_state in &[New, NotNew] {
+        for instance_mask in &instance_masks {
+          let rc = ReadCondition::any()
+            .with_sample_state(*sample_state)
+            .with_view_state(*view_state)
+            .with_instance_state(*instance_mask);
+          let selected: Vec<CacheInstant> = dsc
+            .select_keys_for_access(rc)
+            .into_iter()
+            .map(|(instant, _key)| instant)
+            .collect();
+          let expected: Vec<CacheInstant> = expected_states
+            .iter()
+            .enumerate()
+            .filter(|(_, (s, v, i))| {
+              s == sample_state && v == view_state && instance_mask.contains(*i)
+            })
+            .map(|(n, _)| nth_instant(base, n as i64))
+            .collect();
+          assert_eq!(selected, expected, "{:?}", rc);
+        }
+      }
+    }
+
+    // conditions that cannot match anything are legal
+    let nothing = ReadCondition::any().with_instance_state(BitFlags::<InstanceState>::empty());
+    assert!(dsc.select_keys_for_access(nothing).is_empty());
+
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    let states: Vec<_> = sample_infos(&mut dsc, &keys)
+      .iter()
+      .map(|si| (si.sample_state, si.view_state, si.instance_state))
+      .collect();
+    assert_eq!(states, expected_states.to_vec());
+
+    // taking not alive instances leaves the alive ones
+    let not_alive = ReadCondition::any().with_instance_state(InstanceState::not_alive());
+    let keys = dsc.select_keys_for_access(not_alive);
+    assert_eq!(dsc.take_by_keys(&keys).len(), 6);
+    let remaining: Vec<i64> = dsc
+      .select_keys_for_access(ReadCondition::any())
+      .into_iter()
+      .map(|(_instant, key)| key)
+      .collect();
+    assert_eq!(remaining, vec![1, 4, 1]);
+  }
+
+  #[test]
+  fn dsc_take_moves_samples() {
+    let mut dsc = keep_all_cache();
+    let base = Timestamp::now();
+    let sample = RandomData {
+      a: 1,
+      b: "x".repeat(1 << 20),
+    };
+    let payload_address = sample.b.as_ptr();
+    dsc.add_sample(Ok(sample), GUID::new(), nth_instant(base, 0), None);
+
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    let read = dsc.read_by_keys(&keys);
+    assert_eq!(read[0].value().as_ref().unwrap().b.as_ptr(), payload_address);
+    drop(read);
+
+    // the taken sample owns the payload the cache had, not a copy of it
+    let taken = dsc.take_by_keys(&keys).pop().unwrap();
+    assert_eq!(taken.value().as_ref().unwrap().b.as_ptr(), payload_address);
+    assert!(dsc.select_keys_for_access(ReadCondition::any()).is_empty());
+  }
+
+  #[test]
+  fn dsc_iterators_consume_lazily() {
+    let mut dsc = keep_all_cache();
+    let base = Timestamp::now();
+    let writer = GUID::new();
+    for (n, a) in [1, 2, 1, 2].iter().enumerate() {
+      dsc.add_sample(Ok(data(*a)), writer, nth_instant(base, n as i64), None);
+    }
+
+    // consume only the first sample
+    let keys = dsc.select_keys_for_access(ReadCondition::not_read());
+    let first = dsc
+      .read_iter(keys, DataSampleCache::already_decoded)
+      .next()
+      .unwrap();
+    assert_eq!(first.sample_info().sample_rank, 1);
+    assert_eq!(first.sample_info().view_state, ViewState::New);
+
+    // only the consumed sample was marked read, and only its instance viewed
+    let keys = dsc.select_keys_for_access(ReadCondition::not_read());
+    assert_eq!(keys.len(), 3);
+    let infos: Vec<SampleInfo> = dsc
+      .read_iter(keys, DataSampleCache::already_decoded)
+      .map(|s| s.sample_info().clone())
+      .collect();
+    let view_states: Vec<ViewState> = infos.iter().map(|si| si.view_state).collect();
+    assert_eq!(
+      view_states,
+      vec![ViewState::New, ViewState::NotNew, ViewState::New]
+    );
+    let ranks: Vec<i32> = infos.iter().map(|si| si.sample_rank).collect();
+    assert_eq!(ranks, vec![1, 0, 0]);
+
+    // take only two of four
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    let taken: Vec<_> = dsc
+      .take_iter(keys, DataSampleCache::already_decoded)
+      .take(2)
+      .collect();
+    assert_eq!(taken.len(), 2);
+    assert_eq!(taken[0].sample_info().sample_state, SampleState::Read);
+    let remaining = dsc.select_keys_for_access(ReadCondition::any());
+    assert_eq!(remaining.len(), 2);
+    let values: Vec<i64> = dsc
+      .take_by_keys(&remaining)
+      .into_iter()
+      .map(|s| s.value().as_ref().unwrap().a)
+      .collect();
+    assert_eq!(values, vec![1, 2]);
+  }
+
+  #[test]
+  fn dsc_loan_removes_taken_on_drop() {
+    let mut dsc = keep_all_cache();
+    let base = Timestamp::now();
+    let writer = GUID::new();
+    for (n, a) in [1, 2, 1].iter().enumerate() {
+      dsc.add_sample(Ok(data(*a)), writer, nth_instant(base, n as i64), None);
+    }
+
+    // a read loan only marks samples read
+    let keys = dsc.select_keys_for_access(ReadCondition::not_read());
+    {
+      let loan = dsc.loan(keys, false);
+      assert_eq!(loan.len(), 3);
+      let ranks: Vec<i32> = loan.iter().map(|s| s.sample_info().sample_rank).collect();
+      assert_eq!(ranks, vec![1, 0, 0]);
+      assert_eq!(loan.get(1).unwrap().value().as_ref().unwrap().a, 2);
+    }
+    assert!(dsc.select_keys_for_access(ReadCondition::not_read()).is_empty());
+    assert_eq!(dsc.select_keys_for_access(ReadCondition::any()).len(), 3);
+
+    // taken samples stay in the cache until the loan is dropped
+    let mut keys = dsc.select_keys_for_access(ReadCondition::any());
+    keys.truncate(2);
+    let loan = dsc.loan(keys, true);
+    let values: Vec<i64> = loan.iter().map(|s| s.value().as_ref().unwrap().a).collect();
+    assert_eq!(values, vec![1, 2]);
+    drop(loan);
+    let remaining = dsc.select_keys_for_access(ReadCondition::any());
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(dsc.select_instance_keys_for_access(2, ReadCondition::any()).len(), 0);
+  }
+
+  #[test]
+  fn dsc_by_source_timestamp_drops_older() {
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .destination_order(policy::DestinationOrder::BySourceTimeStamp)
+      .build();
+    let mut dsc = DataSampleCache::<RandomData>::new(qos);
+    let base = Timestamp::now();
+    let writer = GUID::new();
+    let source = |n| Some(nth_timestamp(base, n));
+
+    dsc.add_sample(Ok(data(1)), writer, nth_instant(base, 0), source(10));
+    // older than the previous sample of the same instance
+    dsc.add_sample(Ok(data(1)), writer, nth_instant(base, 1), source(5));
+    // other instances are not affected
+    dsc.add_sample(Ok(data(2)), writer, nth_instant(base, 2), source(5));
+    // without source timestamp, only receive order applies
+    dsc.add_sample(Ok(data(1)), writer, nth_instant(base, 3), None);
+
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    let infos = sample_infos(&mut dsc, &keys);
+    let source_timestamps: Vec<Timestamp> = infos.iter().map(|si| si.source_timestamp).collect();
+    assert_eq!(
+      source_timestamps,
+      vec![
+        nth_timestamp(base, 10),
+        nth_timestamp(base, 5),
+        Timestamp::TIME_INVALID
+      ]
+    );
+    // the cache instants at which the samples were received
+    let reception_timestamps: Vec<Timestamp> =
+      infos.iter().map(|si| si.reception_timestamp).collect();
+    assert_eq!(
+      reception_timestamps,
+      vec![
+        nth_instant(base, 0).timestamp(),
+        nth_instant(base, 2).timestamp(),
+        nth_instant(base, 3).timestamp()
+      ]
+    );
+  }
+
+  #[test]
+  fn dsc_serialized_samples_decoded_once() {
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepLast { depth: 1 })
+      .build();
+    let mut dsc = DataSampleCache::<RandomData>::new(qos);
+    let base = Timestamp::now();
+    let writer = GUID::new();
+    let add = |dsc: &mut DataSampleCache<RandomData>, n: i64, value: Vec<u8>| {
+      dsc.add_serialized_sample(
+        1,
+        SerializedPayload {
+          representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+          representation_options: [0, 0],
+          value: value.into(),
+        },
+        RepresentationIdentifier::CDR_LE,
+        nth_instant(base, n),
+        None,
+        SampleIdentity {
+          writer_guid: writer,
+          sequence_number: n,
+        },
+        None,
+      )
+    };
+    let serialized = |a| to_bytes::<RandomData, LittleEndian>(&data(a)).unwrap();
+    let mut decoded = Vec::new();
+    let mut decode =
+      |_key: &i64, payload: &SerializedPayload, encoding, identity: Option<SampleIdentity>| {
+        decoded.push(identity.unwrap().sequence_number);
+        CDRDeserializerAdapter::<RandomData>::from_bytes(&payload.value, encoding).ok()
+      };
+
+    // History drops the first two before anyone accesses them
+    for n in 0..3 {
+      add(&mut dsc, n, serialized(1));
+    }
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    assert!(dsc.decode_samples(&keys, &mut decode));
+    assert!(dsc.decode_samples(&keys, &mut decode));
+    assert_eq!(
+      *dsc.read_by_keys(&keys)[0].value().as_ref().unwrap(),
+      &data(1)
+    );
+
+    // a sample that fails to decode is removed
+    add(&mut dsc, 3, vec![1, 2, 3]);
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    assert!(!dsc.decode_samples(&keys, &mut decode));
+    assert!(dsc.select_keys_for_access(ReadCondition::any()).is_empty());
+    drop(decode);
+    assert_eq!(decoded, vec![2, 3]);
+  }
+
+  #[test]
+  fn dsc_iterators_decode_lazily() {
+    let mut dsc = keep_all_cache();
+    let base = Timestamp::now();
+    let writer = GUID::new();
+    let add = |dsc: &mut DataSampleCache<RandomData>, n: i64, value: Vec<u8>| {
+      dsc.add_serialized_sample(
+        1,
+        SerializedPayload {
+          representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+          representation_options: [0, 0],
+          value: value.into(),
+        },
+        RepresentationIdentifier::CDR_LE,
+        nth_instant(base, n),
+        None,
+        SampleIdentity {
+          writer_guid: writer,
+          sequence_number: n,
+        },
+        None,
+      )
+    };
+    let serialized = |a| to_bytes::<RandomData, LittleEndian>(&data(a)).unwrap();
+    let decoded = RefCell::new(Vec::new());
+    let decode =
+      |_key: &i64, payload: &SerializedPayload, encoding, identity: Option<SampleIdentity>| {
+        decoded.borrow_mut().push(identity.unwrap().sequence_number);
+        CDRDeserializerAdapter::<RandomData>::from_bytes(&payload.value, encoding).ok()
+      };
+    add(&mut dsc, 0, serialized(1));
+    add(&mut dsc, 1, vec![1, 2, 3]);
+    add(&mut dsc, 2, serialized(1));
+    add(&mut dsc, 3, serialized(1));
+
+    // reading one sample decodes only it
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    let first = dsc.read_iter(keys, decode).next().unwrap();
+    assert_eq!(*first.value().as_ref().unwrap(), &data(1));
+    assert_eq!(*decoded.borrow(), vec![0]);
+
+    // the sample that fails to decode is skipped
+    let keys = dsc.select_keys_for_access(ReadCondition::not_read());
+    assert_eq!(dsc.read_iter(keys, decode).take(1).count(), 1);
+    assert_eq!(*decoded.borrow(), vec![0, 1, 2]);
+
+    // taking decodes the samples still serialized, and only them
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    assert_eq!(keys.len(), 4);
+    let taken: Vec<i64> = dsc
+      .take_iter(keys, decode)
+      .map(|s| s.sample_info().sample_identity.unwrap().sequence_number)
+      .collect();
+    assert_eq!(taken, vec![0, 2, 3]);
+    assert_eq!(*decoded.borrow(), vec![0, 1, 2, 3]);
+    assert!(dsc.select_keys_for_access(ReadCondition::any()).is_empty());
+  }
 }
@@ -1,17 +1,20 @@
 use serde::{Serialize /*, Deserialize*/};
+use log::warn;
+use bytes::Bytes;
 
 use crate::{
-  dds::traits::key::Keyed,
+  dds::traits::{
+    key::{Key, Keyed},
+    serde_adapters::SerializerAdapter,
+  },
   structure::{
-    inline_qos::{KeyHash, StatusInfo},
+    inline_qos::{KeyHash, SampleIdentity, StatusInfo},
   },
 };
-use crate::messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier;
 use crate::messages::submessages::submessage_elements::serialized_payload::SerializedPayload;
-use crate::serialization::cdr_serializer::{to_bytes};
-use byteorder::{LittleEndian /*,BigEndian*/};
+use crate::serialization::error::Result;
 
-use crate::structure::guid::EntityId;
+use crate::structure::guid::{EntityId, GUID};
 use crate::structure::time::Timestamp;
 use crate::structure::cache_change::ChangeKind;
 
@@ -25,6 +28,10 @@ pub struct DDSData {
   value: Option<SerializedPayload>,
   // needed to identify what instance type (unique key) this change is for 9.6.3.8
   pub value_key_hash: u128,
+  // the sample, e.g. a request, that this sample relates to. Sent as inline QoS.
+  pub related_sample_identity: Option<SampleIdentity>,
+  // readers that the reader filter of the DataWriter kept this sample from
+  pub filtered_readers: Vec<GUID>,
 }
 
 impl DDSData {
@@ -36,10 +43,18 @@ impl DDSData {
       writer_id: EntityId::ENTITYID_UNKNOWN,
       value: Some(payload),
       value_key_hash: 0,
+      related_sample_identity: None,
+      filtered_readers: Vec::new(),
     }
   }
 
-  pub fn new_disposed(status_info: Option<StatusInfo>, key_hash: Option<KeyHash>) -> DDSData {
+  // Received dispose or unregister. The instance is identified by the key hash,
+  // or if that was not sent, by the serialized key.
+  pub fn new_disposed(
+    status_info: Option<StatusInfo>,
+    key_hash: Option<KeyHash>,
+    serialized_key: Option<SerializedPayload>,
+  ) -> DDSData {
     let change_kind = match status_info {
       Some(i) => i.change_kind(),
       // no change kind/status info means that it's still alive
@@ -56,71 +71,96 @@ impl DDSData {
       change_kind,
       reader_id: EntityId::ENTITYID_UNKNOWN,
       writer_id: EntityId::ENTITYID_UNKNOWN,
-      value: None,
+      value: serialized_key,
       value_key_hash: value_key_hash.value(),
+      related_sample_identity: None,
+      filtered_readers: Vec::new(),
     }
   }
 
   // TODO: Rename this method, as it gets confued with the std library "From" trait method.
-  pub fn from<D>(data: &D, source_timestamp: Option<Timestamp>) -> DDSData
+  pub fn from<D, SA>(data: &D, source_timestamp: Option<Timestamp>) -> Result<DDSData>
   where
     D: Keyed + Serialize,
+    SA: SerializerAdapter<D>,
   {
-    let value = DDSData::serialize_data(data);
+    let value = SA::to_bytes(data)?;
 
     let ts: Timestamp = match source_timestamp {
       Some(t) => t,
       None => Timestamp::now(),
     };
 
-    let serialized_payload = SerializedPayload::new(RepresentationIdentifier::CDR_LE, value);
+    let serialized_payload = SerializedPayload::new(SA::output_encoding(), value);
 
-    DDSData {
+    Ok(DDSData {
       source_timestamp: ts,
       change_kind: ChangeKind::ALIVE,
       reader_id: EntityId::ENTITYID_UNKNOWN,
       writer_id: EntityId::ENTITYID_UNKNOWN,
       value: Some(serialized_payload),
       value_key_hash: 0,
-    }
+      related_sample_identity: None,
+      filtered_readers: Vec::new(),
+    })
   }
 
-  pub fn from_dispose<D>(_key: <D as Keyed>::K, source_timestamp: Option<Timestamp>) -> DDSData
+  pub fn from_dispose<D, SA>(key: &<D as Keyed>::K, source_timestamp: Option<Timestamp>) -> DDSData
   where
-    D: Keyed,
+    D: Keyed + Serialize,
+    <D as Keyed>::K: Key,
+    SA: SerializerAdapter<D>,
   {
+    let serialized_key = match SA::key_to_bytes(key) {
+      Ok(v) => Some(SerializedPayload::new(SA::output_encoding(), v)),
+      Err(e) => {
+        warn!("Failed to serialize key of disposed instance. {:?}", e);
+        None
+      }
+    };
+    DDSData::from_serialized_dispose(serialized_key, key.into_hash_key(), source_timestamp)
+  }
+
+  // Dispose of an instance whose key has been serialized earlier, if it could be.
+  pub fn from_serialized_dispose(
+    serialized_key: Option<SerializedPayload>,
+    key_hash: u128,
+    source_timestamp: Option<Timestamp>,
+  ) -> DDSData {
+    let mut ddsdata = DDSData::without_data(ChangeKind::NOT_ALIVE_DISPOSED, source_timestamp);
+    // The serialized key is sent along with the key hash, so that also readers that do not
+    // use key hashes can tell which instance is disposed.
+    ddsdata.value = serialized_key;
+    ddsdata.value_key_hash = key_hash;
+    ddsdata
+  }
+
+  pub fn from_unregister(source_timestamp: Option<Timestamp>) -> DDSData {
+    DDSData::without_data(ChangeKind::NOT_ALIVE_UNREGISTERED, source_timestamp)
+  }
+
+  // Instance state change. The instance is identified by value_key_hash,
+  // which the caller must set.
+  fn without_data(change_kind: ChangeKind, source_timestamp: Option<Timestamp>) -> DDSData {
     let ts: Timestamp = match source_timestamp {
       Some(t) => t,
       None => Timestamp::now(),
     };
 
-    // TODO: Serialize key
-
     DDSData {
       source_timestamp: ts,
-      change_kind: ChangeKind::NOT_ALIVE_DISPOSED,
+      change_kind,
       reader_id: EntityId::ENTITYID_UNKNOWN,
       writer_id: EntityId::ENTITYID_UNKNOWN,
-      value: None, // TODO: Here we should place the serialized _key_, so that RTPS writer can send the
-      // the DATA message indicating dispose
+      value: None,
       value_key_hash: 0,
+      related_sample_identity: None,
+      filtered_readers: Vec::new(),
     }
   }
 
-  fn serialize_data<D>(data: &D) -> Vec<u8>
-  where
-    D: Keyed + Serialize,
-  {
-    //let mut cdr = CDR_serializer::<LittleEndian>::new();
-    //let mut serializer = erased_serde::Serializer::erase(&mut cdr);
-    //let value = data.serialize(&mut cdr);
-    // let value = to_little_endian_binary::<D>(&data);
-    let value = match to_bytes::<D, LittleEndian>(data) {
-      Ok(v) => v,
-      // TODO: handle error
-      _ => Vec::new(),
-    };
-    value
+  pub fn source_timestamp(&self) -> Timestamp {
+    self.source_timestamp
   }
 
   pub fn reader_id(&self) -> &EntityId {
@@ -143,10 +183,10 @@ impl DDSData {
     self.value.clone()
   }
 
-  pub fn data(&self) -> Vec<u8> {
+  pub fn data(&self) -> Bytes {
     match &self.value {
-      Some(val) => (*val).value.clone(),
-      None => Vec::new(),
+      Some(val) => val.value.clone(),
+      None => Bytes::new(),
     }
   }
 }
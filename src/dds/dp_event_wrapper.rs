@@ -3,36 +3,71 @@ use mio::{Poll, Event, Events, Token, Ready, PollOpt};
 use mio_extras::channel as mio_channel;
 extern crate chrono;
 //use chrono::Duration;
-use std::{collections::HashMap, sync::RwLockReadGuard, time::Duration};
+use std::{
+  collections::{HashMap, HashSet},
+  net::IpAddr,
+  sync::RwLockReadGuard,
+  time::Duration,
+};
 use std::{
   sync::{Arc, RwLock},
 };
 
 use crate::{
-  dds::{message_receiver::MessageReceiver, reader::Reader, writer::Writer, qos::HasQoSPolicy},
-  network::util::get_local_multicast_locators,
+  dds::{
+    message_receiver::MessageReceiver,
+    reader::{Reader, ReaderListener},
+    writer::Writer,
+    qos::{HasQoSPolicy, QosPolicyId},
+    statistics::NetworkCounters,
+  },
+  network::local_network::LocalNetwork,
   structure::builtin_endpoint::BuiltinEndpointSet,
 };
-use crate::network::udp_listener::UDPListener;
+use crate::network::{tcp::TcpTransport, udp_listener::UDPListener};
+use crate::network::capture::{no_address, PacketCapture, Transport};
 use crate::network::constant::*;
 use crate::structure::guid::{GuidPrefix, GUID, EntityId};
 use crate::structure::entity::Entity;
+use crate::structure::locator::{LocatorKind, LocatorList};
+#[cfg(feature = "shm")]
+use crate::network::shm::ShmReceiver;
+#[cfg(feature = "netlink")]
+use crate::network::interface_monitor::InterfaceMonitor;
 use crate::{
   common::timed_event_handler::{TimedEventHandler},
   discovery::discovery_db::DiscoveryDB,
-  structure::{dds_cache::DDSCache, topic_kind::TopicKind},
+  structure::{cache_change::CacheChange, dds_cache::DDSCache, topic_kind::TopicKind},
   messages::submessages::submessages::AckNack,
 };
 use crate::dds::with_key::datareader::ReaderCommand;
+use crate::common::lock::{read_lock, write_lock};
 use super::{
   qos::policy::Reliability, rtps_reader_proxy::RtpsReaderProxy, rtps_writer_proxy::RtpsWriterProxy,
   typedesc::TypeDesc,
 };
 
+// How often changes that are no longer needed are removed from DDSCache
+const CACHE_GC_PERIOD: Duration = Duration::from_secs(10);
+
 pub struct DomainInfo {
   pub domain_participant_guid: GUID,
   pub domain_id: u16,
   pub participant_id: u16,
+  // send SPDP announcements to the well-known multicast group
+  pub spdp_multicast: bool,
+  // initial peers that also receive SPDP announcements by unicast
+  pub spdp_peers: LocatorList,
+  // IP versions and interfaces of our sockets
+  pub network: LocalNetwork,
+  // hand changes of our writers to our readers in memory
+  pub intra_process_delivery: bool,
+  // what our sockets send and receive is written here, while capturing
+  pub capture: PacketCapture,
+  // how often multicast groups whose membership the OS dropped are joined again
+  pub multicast_rejoin_period: Duration,
+  // counts the groups joined again and the sockets recreated
+  pub network_counters: Arc<NetworkCounters>,
 }
 
 pub struct DPEventWrapper {
@@ -41,11 +76,20 @@ pub struct DPEventWrapper {
   ddscache: Arc<RwLock<DDSCache>>,
   discovery_db: Arc<RwLock<DiscoveryDB>>,
   udp_listeners: HashMap<Token, UDPListener>,
+  // groups the user traffic multicast listener has joined, with how many readers use each
+  reader_multicast_groups: HashMap<IpAddr, usize>,
   message_receiver: MessageReceiver,
+  // Messages from writers of other participants on this host
+  #[cfg(feature = "shm")]
+  shm_receiver: Option<ShmReceiver>,
+  // Tells when network interfaces change
+  #[cfg(feature = "netlink")]
+  interface_monitor: Option<InterfaceMonitor>,
 
   // Adding readers
   add_reader_receiver: TokenReceiverPair<Reader>,
   remove_reader_receiver: TokenReceiverPair<GUID>,
+  add_reader_listener_receiver: TokenReceiverPair<(GUID, ReaderListener)>,
   reader_timed_event_receiver: HashMap<Token, mio_channel::Receiver<TimerMessageType>>,
   // For each reader a token is added with reades guid it then can be accessed from message receiver
   reader_command_receiver_identification: HashMap<Token, GUID>,
@@ -74,6 +118,7 @@ impl DPEventWrapper {
     participant_guid_prefix: GuidPrefix,
     add_reader_receiver: TokenReceiverPair<Reader>,
     remove_reader_receiver: TokenReceiverPair<GUID>,
+    add_reader_listener_receiver: TokenReceiverPair<(GUID, ReaderListener)>,
     add_writer_receiver: TokenReceiverPair<Writer>,
     remove_writer_receiver: TokenReceiverPair<GUID>,
     stop_poll_receiver: mio_channel::Receiver<()>,
@@ -84,6 +129,7 @@ impl DPEventWrapper {
       mio_channel::sync_channel::<(GuidPrefix, AckNack)>(100);
     let mut udp_listeners = udp_listeners;
     for (token, listener) in &mut udp_listeners {
+      listener.set_capture(domain_info.capture.clone());
       poll
         .register(
           listener.mio_socket(),
@@ -111,6 +157,15 @@ impl DPEventWrapper {
         PollOpt::edge(),
       )
       .expect("Failed to register reader remover.");
+
+    poll
+      .register(
+        &add_reader_listener_receiver.receiver,
+        add_reader_listener_receiver.token,
+        Ready::readable(),
+        PollOpt::edge(),
+      )
+      .expect("Failed to register reader listener adder.");
     poll
       .register(
         &add_writer_receiver.receiver,
@@ -156,15 +211,68 @@ impl DPEventWrapper {
       )
       .expect("Failed to register reader update notification.");
 
+    #[cfg(feature = "shm")]
+    let shm_receiver = match domain_info.network.shm_namespace() {
+      Some(_) => match ShmReceiver::new(participant_guid_prefix) {
+        Ok(receiver) => {
+          poll
+            .register(
+              &mio::unix::EventedFd(&receiver.doorbell_fd()),
+              SHM_DOORBELL_TOKEN,
+              Ready::readable(),
+              PollOpt::edge(),
+            )
+            .expect("Failed to register shared memory doorbell.");
+          Some(receiver)
+        }
+        Err(e) => {
+          warn!("Cannot receive through shared memory. {:?}", e);
+          None
+        }
+      },
+      None => None,
+    };
+
+    #[cfg(feature = "netlink")]
+    let interface_monitor = match InterfaceMonitor::new() {
+      Ok(monitor) => {
+        poll
+          .register(
+            &mio::unix::EventedFd(&monitor.fd()),
+            NETWORK_INTERFACE_CHANGE_TOKEN,
+            Ready::readable(),
+            PollOpt::edge(),
+          )
+          .expect("Failed to register network interface monitor.");
+        Some(monitor)
+      }
+      Err(e) => {
+        warn!("Cannot monitor network interfaces. {:?}", e);
+        None
+      }
+    };
+
+    if let Some(tcp) = domain_info.network.tcp() {
+      tcp
+        .start(participant_guid_prefix, &poll)
+        .expect("Failed to start TCP transport.");
+    }
+
     DPEventWrapper {
       domain_info,
       poll,
       ddscache,
       discovery_db,
       udp_listeners,
+      reader_multicast_groups: HashMap::new(),
       message_receiver: MessageReceiver::new(participant_guid_prefix, acknack_sender),
+      #[cfg(feature = "shm")]
+      shm_receiver,
+      #[cfg(feature = "netlink")]
+      interface_monitor,
       add_reader_receiver,
       remove_reader_receiver,
+      add_reader_listener_receiver,
       reader_timed_event_receiver: HashMap::new(),
       reader_command_receiver_identification: HashMap::new(),
       add_writer_receiver,
@@ -178,6 +286,8 @@ impl DPEventWrapper {
   }
 
   pub fn event_loop(self) {
+    let participant = self.domain_info.domain_participant_guid.guidPrefix;
+    let _participant = dds_span!(info, "participant", participant = participant);
     let mut acknack_timer = mio_extras::timer::Timer::default();
     acknack_timer.set_timeout(Duration::from_secs(5), ());
     self
@@ -189,6 +299,28 @@ impl DPEventWrapper {
         PollOpt::edge(),
       )
       .unwrap();
+    let mut cache_gc_timer = mio_extras::timer::Timer::default();
+    cache_gc_timer.set_timeout(CACHE_GC_PERIOD, ());
+    self
+      .poll
+      .register(
+        &cache_gc_timer,
+        DPEV_CACHE_GC_TIMER_TOKEN,
+        Ready::readable(),
+        PollOpt::edge(),
+      )
+      .unwrap();
+    let mut multicast_timer = mio_extras::timer::Timer::default();
+    multicast_timer.set_timeout(self.domain_info.multicast_rejoin_period, ());
+    self
+      .poll
+      .register(
+        &multicast_timer,
+        DPEV_MULTICAST_TIMER_TOKEN,
+        Ready::readable(),
+        PollOpt::edge(),
+      )
+      .unwrap();
 
     // TODO: Use the dp to access stuff we need, e.g. historycache
     let mut ev_wrapper = self;
@@ -202,9 +334,16 @@ impl DPEventWrapper {
       for event in events.into_iter() {
         if event.token() == STOP_POLL_TOKEN {
           info!("Stopping ev_wrapper");
+          ev_wrapper.flush_writers();
           return;
         } else if DPEventWrapper::is_udp_traffic(&event) {
           ev_wrapper.handle_udp_traffic(&event);
+        } else if event.token() == SHM_DOORBELL_TOKEN {
+          ev_wrapper.handle_shm_traffic();
+        } else if event.token() == NETWORK_INTERFACE_CHANGE_TOKEN {
+          ev_wrapper.handle_interface_change();
+        } else if TcpTransport::is_tcp_token(event.token()) {
+          ev_wrapper.handle_tcp_traffic(event.token());
         } else if DPEventWrapper::is_reader_action(&event) {
           ev_wrapper.handle_reader_action(&event);
         } else if ev_wrapper.is_reader_timed_event_action(&event) {
@@ -218,32 +357,73 @@ impl DPEventWrapper {
         } else if DPEventWrapper::is_writer_acknack_action(&event) {
           ev_wrapper.handle_writer_acknack_action(&event);
         } else if DPEventWrapper::is_discovery_update_notification(&event) {
+          // Matching is redone once for all the pending notifications.
+          let mut readers_updated = false;
+          let mut writers_updated: Option<bool> = None;
+          let mut topics_updated = false;
           while let Ok(dnt) = ev_wrapper.discovery_update_notification_receiver.try_recv() {
             match dnt {
-              DiscoveryNotificationType::ReadersInfoUpdated => ev_wrapper.update_readers(),
+              DiscoveryNotificationType::ReadersInfoUpdated => readers_updated = true,
               DiscoveryNotificationType::WritersInfoUpdated {
                 needs_new_cache_change,
-              } => ev_wrapper.update_writers(needs_new_cache_change),
-              DiscoveryNotificationType::TopicsInfoUpdated => ev_wrapper.update_topics(),
+              } => {
+                writers_updated = Some(writers_updated.unwrap_or(false) || needs_new_cache_change)
+              }
+              DiscoveryNotificationType::TopicsInfoUpdated => topics_updated = true,
               DiscoveryNotificationType::AssertTopicLiveliness { writer_guid } => {
                 let writer = ev_wrapper.writers.get_mut(&writer_guid);
                 match writer {
                   Some(w) => {
-                    // Only need set heartbeat tick earlier
-                    w.handle_heartbeat_tick();
+                    w.assert_liveliness();
+                    w.send_liveliness_heartbeat();
+                    let local_readers = w.local_readers().to_vec();
+                    ev_wrapper.assert_liveliness_to_local_readers(&local_readers, writer_guid);
                   }
                   None => (),
                 };
               }
+              DiscoveryNotificationType::ParticipantLivelinessAsserted {
+                guid_prefix,
+                manual,
+              } => {
+                for reader in ev_wrapper.message_receiver.available_readers.iter_mut() {
+                  reader.participant_liveliness_asserted(guid_prefix, manual);
+                }
+              }
+              DiscoveryNotificationType::ParticipantIgnored { guid_prefix } => {
+                ev_wrapper.ignore_participant(guid_prefix)
+              }
+              DiscoveryNotificationType::QosMismatch { local, policy, .. } => {
+                ev_wrapper.report_incompatible_qos(local, policy)
+              }
             }
           }
+          if readers_updated {
+            ev_wrapper.update_readers();
+          }
+          if let Some(needs_new_cache_change) = writers_updated {
+            ev_wrapper.update_writers(needs_new_cache_change);
+          }
+          if topics_updated {
+            ev_wrapper.update_topics();
+          }
         } else if event.token() == DPEV_ACKNACK_TIMER_TOKEN {
           ev_wrapper.message_receiver.send_preemptive_acknacks();
           acknack_timer.set_timeout(Duration::from_secs(5), ());
+        } else if event.token() == DPEV_CACHE_GC_TIMER_TOKEN {
+          ev_wrapper.collect_cache_garbage();
+          cache_gc_timer.set_timeout(CACHE_GC_PERIOD, ());
+        } else if event.token() == DPEV_MULTICAST_TIMER_TOKEN {
+          ev_wrapper.restore_multicast();
+          multicast_timer.set_timeout(ev_wrapper.domain_info.multicast_rejoin_period, ());
         } else {
           info!("Unknown event");
         }
       }
+      // sending may have opened TCP connections
+      if let Some(tcp) = ev_wrapper.domain_info.network.tcp() {
+        tcp.register_connections(&ev_wrapper.poll);
+      }
     }
   }
 
@@ -255,7 +435,9 @@ impl DPEventWrapper {
   }
 
   pub fn is_reader_action(event: &Event) -> bool {
-    event.token() == ADD_READER_TOKEN || event.token() == REMOVE_READER_TOKEN
+    event.token() == ADD_READER_TOKEN
+      || event.token() == REMOVE_READER_TOKEN
+      || event.token() == ADD_READER_LISTENER_TOKEN
   }
 
   /// Writer action can be add writer remove writer or some not predefined token.
@@ -340,15 +522,157 @@ impl DPEventWrapper {
     }
   }
 
+  // Messages in the shared memory segments of our matched writers on this host
+  pub fn handle_shm_traffic(&mut self) {
+    #[cfg(feature = "shm")]
+    {
+      let messages = match self.shm_receiver.as_mut() {
+        Some(receiver) => receiver.receive(),
+        None => return,
+      };
+      for message in messages.into_iter() {
+        self
+          .domain_info
+          .capture
+          .received(Transport::Shm, &message, no_address(), no_address());
+        self.message_receiver.handle_user_msg(message);
+      }
+    }
+  }
+
+  // A network interface went up or down, or its addresses changed. The groups it lost
+  // are joined again right away, instead of at the next periodic attempt.
+  pub fn handle_interface_change(&mut self) {
+    #[cfg(feature = "netlink")]
+    {
+      let changed = match self.interface_monitor.as_mut() {
+        Some(monitor) => monitor.changed(),
+        None => false,
+      };
+      if changed {
+        self.restore_multicast();
+      }
+    }
+  }
+
+  // Joins again the multicast groups whose membership the OS has dropped, e.g. when a
+  // network interface went down and up, and binds the multicast listeners that could
+  // not be bound before. Groups still joined are left as they are.
+  fn restore_multicast(&mut self) {
+    let tokens = [
+      DISCOVERY_MUL_LISTENER_TOKEN,
+      USER_TRAFFIC_MUL_LISTENER_TOKEN,
+    ];
+    for token in tokens.iter() {
+      let rebound = !self.udp_listeners.contains_key(token);
+      if rebound && !self.bind_multicast_listener(*token) {
+        continue;
+      }
+      let mut groups = self.domain_info.network.multicast_groups();
+      if *token == USER_TRAFFIC_MUL_LISTENER_TOKEN {
+        groups.extend(self.reader_multicast_groups.keys());
+      }
+      let listener = &self.udp_listeners[token];
+      match self.domain_info.network.rejoin_multicast(listener, &groups) {
+        Ok(joined) if rebound => info!(
+          "Listening to multicast port {} again, joined {} groups.",
+          listener.port(),
+          joined
+        ),
+        Ok(0) => (),
+        Ok(joined) => {
+          info!(
+            "Joined {} lost multicast groups again on port {}.",
+            joined,
+            listener.port()
+          );
+          self
+            .domain_info
+            .network_counters
+            .multicast_rejoined(joined as u64);
+        }
+        Err(e) => debug!(
+          "Cannot join multicast groups on port {} yet. {:?}",
+          listener.port(),
+          e
+        ),
+      }
+    }
+  }
+
+  // Binds a multicast listener that could not be bound when the participant was
+  // created, e.g. because the network was down. Returns whether it succeeded.
+  fn bind_multicast_listener(&mut self, token: Token) -> bool {
+    let network = &self.domain_info.network;
+    if !network.udp_enabled() {
+      return false;
+    }
+    let domain_id = self.domain_info.domain_id;
+    let (listener_token, port) = if token == DISCOVERY_MUL_LISTENER_TOKEN {
+      (
+        DISCOVERY_SENDER_TOKEN,
+        get_spdp_well_known_multicast_port(domain_id),
+      )
+    } else {
+      (
+        USER_TRAFFIC_SENDER_TOKEN,
+        get_user_traffic_multicast_port(domain_id),
+      )
+    };
+    let host = network.multicast_bind_address().to_string();
+    let mut listener = match UDPListener::try_bind_shared(listener_token, &host, port) {
+      Ok(listener) => listener,
+      Err(e) => {
+        debug!("Cannot bind multicast port {} yet. {:?}", port, e);
+        return false;
+      }
+    };
+    if let Err(e) = network.configure_listener(&listener) {
+      warn!("Cannot configure socket of port {}. {:?}", port, e);
+    }
+    listener.set_capture(self.domain_info.capture.clone());
+    if let Err(e) = self.poll.register(
+      listener.mio_socket(),
+      token,
+      Ready::readable(),
+      PollOpt::edge(),
+    ) {
+      warn!("Cannot listen to multicast port {}. {:?}", port, e);
+      return false;
+    }
+    self.domain_info.network_counters.socket_recreated();
+    self.udp_listeners.insert(token, listener);
+    true
+  }
+
+  // Messages from our TCP connections. Accepted and reconnected connections also come
+  // here.
+  pub fn handle_tcp_traffic(&mut self, token: Token) {
+    let messages = match self.domain_info.network.tcp() {
+      Some(tcp) => tcp.handle_event(token),
+      None => return,
+    };
+    for message in messages.into_iter() {
+      self
+        .domain_info
+        .capture
+        .received(Transport::Tcp, &message, no_address(), no_address());
+      self.message_receiver.handle_user_msg(message);
+    }
+  }
+
   pub fn handle_reader_action(&mut self, event: &Event) {
     match event.token() {
       ADD_READER_TOKEN => {
         info!("add reader(s)");
+        let mut readers_added = false;
         while let Ok(mut new_reader) = self.add_reader_receiver.receiver.try_recv() {
           let (timed_action_sender, timed_action_receiver) =
             mio_channel::sync_channel::<TimerMessageType>(10);
           let time_handler: TimedEventHandler = TimedEventHandler::new(timed_action_sender.clone());
           new_reader.add_timed_event_handler(time_handler);
+          new_reader.use_local_network(&self.domain_info.network);
+          new_reader.use_capture(&self.domain_info.capture);
 
           self
             .poll
@@ -379,11 +703,52 @@ impl DPEventWrapper {
           );
           new_reader.set_requested_deadline_check_timer();
           self.message_receiver.add_reader(new_reader);
+          readers_added = true;
+        }
+        if readers_added {
+          // Match with our own writers now. Discovery may have told about the new
+          // readers before they got here.
+          self.match_readers(false);
+          self.update_writers(false);
         }
       }
       REMOVE_READER_TOKEN => {
         while let Ok(old_reader_guid) = self.remove_reader_receiver.receiver.try_recv() {
-          self.message_receiver.remove_reader(old_reader_guid);
+          let reader = match self.message_receiver.remove_reader(old_reader_guid) {
+            Some(r) => r,
+            None => continue,
+          };
+          if let Some(group) = reader.multicast_group() {
+            self.leave_reader_multicast_group(group);
+          }
+          self
+            .poll
+            .deregister(&reader.data_reader_command_receiver)
+            .unwrap_or(());
+          self
+            .reader_command_receiver_identification
+            .remove(&reader.get_reader_command_entity_token());
+          if let Some(timer) = self
+            .reader_timed_event_receiver
+            .remove(&reader.get_entity_token())
+          {
+            self.poll.deregister(&timer).unwrap_or(());
+          }
+        }
+      }
+      ADD_READER_LISTENER_TOKEN => {
+        while let Ok((reader_guid, listener)) =
+          self.add_reader_listener_receiver.receiver.try_recv()
+        {
+          match self
+            .message_receiver
+            .available_readers
+            .iter_mut()
+            .find(|r| r.get_guid() == reader_guid)
+          {
+            Some(reader) => reader.add_listener(listener),
+            None => warn!("No reader {:?} to add a listener to.", reader_guid),
+          }
         }
       }
       _ => {}
@@ -393,6 +758,7 @@ impl DPEventWrapper {
   pub fn handle_writer_action(&mut self, event: &Event) {
     match event.token() {
       ADD_WRITER_TOKEN => {
+        let mut writers_added = false;
         while let Ok(mut new_writer) = self.add_writer_receiver.receiver.try_recv() {
           &self.poll.register(
             new_writer.cache_change_receiver(),
@@ -404,6 +770,9 @@ impl DPEventWrapper {
             mio_channel::sync_channel::<TimerMessageType>(10);
           let time_handler: TimedEventHandler = TimedEventHandler::new(timed_action_sender.clone());
           new_writer.add_timed_event_handler(time_handler);
+          new_writer.use_local_network(&self.domain_info.network);
+          new_writer.use_capture(&self.domain_info.capture);
+          new_writer.use_network_counters(&self.domain_info.network_counters);
 
           self
             .poll
@@ -419,45 +788,117 @@ impl DPEventWrapper {
             timed_action_receiver,
           );
           self.writers.insert(new_writer.as_entity().guid, new_writer);
+          writers_added = true;
+        }
+        if writers_added {
+          // Match with our own readers now. Discovery may have told about the new
+          // writers before they got here.
+          self.match_readers(false);
+          self.update_writers(false);
         }
       }
       REMOVE_WRITER_TOKEN => {
         while let Ok(writer_guid) = &self.remove_writer_receiver.receiver.try_recv() {
+          // Send what the DataWriter wrote before it was deleted, e.g. its disposes.
+          if let Some(token) = self.writers.get(writer_guid).map(|w| w.get_entity_token()) {
+            self.handle_writer_commands(token);
+          }
           let writer = self.writers.remove(writer_guid);
           if let Some(w) = writer {
             &self.poll.deregister(w.cache_change_receiver());
+            if let Some(timer) = self
+              .writer_timed_event_reciever
+              .remove(&w.get_timed_event_entity_token())
+            {
+              self.poll.deregister(&timer).unwrap_or(());
+            }
           };
         }
       }
-      t => {
-        let found_writer = self
-          .writers
-          .iter_mut()
-          .find(|p| p.1.get_entity_token() == t);
+      t => self.handle_writer_commands(t),
+    }
+  }
 
-        match found_writer {
-          Some((_guid, w)) => {
-            while let Ok(cc) = w.cache_change_receiver().try_recv() {
-              match cc {
-                super::writer::WriterCommand::DDSData { data } => {
-                  w.insert_to_history_cache(data);
-                  w.send_all_unsend_messages();
-                }
-                super::writer::WriterCommand::ResetOfferedDeadlineMissedStatus {
-                  writer_guid: _,
-                } => {
-                  w.reset_offered_deadline_missed_status();
+  // Executes the commands that the DataWriter of the token has sent to its Writer.
+  fn handle_writer_commands(&mut self, t: Token) {
+    let mut local_handovers = Vec::new();
+    let found_writer = self
+      .writers
+      .iter_mut()
+      .find(|p| p.1.get_entity_token() == t);
+
+    match found_writer {
+      Some((_guid, w)) => {
+        while let Ok(cc) = w.cache_change_receiver().try_recv() {
+          match cc {
+            super::writer::WriterCommand::DDSData { data } => {
+              let filtered_readers = w.insert_to_history_cache(data);
+              w.send_or_batch_unsent();
+              let local_readers: Vec<GUID> = w
+                .local_readers()
+                .iter()
+                .filter(|r| !filtered_readers.contains(r))
+                .copied()
+                .collect();
+              if !local_readers.is_empty() {
+                if let Some(change) = w.last_change() {
+                  local_handovers.push((local_readers, change));
                 }
               }
             }
+            super::writer::WriterCommand::SetBatching { batching } => {
+              w.set_batching(batching);
+            }
+            super::writer::WriterCommand::SetRtpsConfig { config } => {
+              w.set_rtps_config(config);
+            }
+            super::writer::WriterCommand::SetFlowControl { config } => {
+              w.set_flow_control(config);
+            }
+            super::writer::WriterCommand::Flush => {
+              w.flush();
+            }
+            super::writer::WriterCommand::ResetOfferedDeadlineMissedStatus { writer_guid: _ } => {
+              w.reset_offered_deadline_missed_status();
+            }
+            super::writer::WriterCommand::ResetOfferedIncompatibleQosStatus { writer_guid: _ } => {
+              w.reset_offered_incompatible_qos_status();
+            }
+            super::writer::WriterCommand::ResetPublicationMatchedStatus { writer_guid: _ } => {
+              w.reset_publication_matched_status();
+            }
+            super::writer::WriterCommand::ResetLivelinessLostStatus { writer_guid: _ } => {
+              w.reset_liveliness_lost_status();
+            }
           }
-          None => {}
         }
+        // there is room in the command channel again
+        w.command_waker().wake();
       }
+      None => {}
+    }
+    for (readers, change) in local_handovers {
+      self.hand_over_to_local_readers(&readers, &change);
     }
   }
 
-  /// Writer timed events can be heatrbeats or cache cleaning events.
+  // Sends what the writers have written but not yet sent, e.g. the disposes of
+  // Discovery, before the event loop stops.
+  fn flush_writers(&mut self) {
+    let tokens: Vec<Token> = self
+      .writers
+      .values()
+      .map(|w| w.get_entity_token())
+      .collect();
+    for token in tokens {
+      self.handle_writer_commands(token);
+    }
+    for writer in self.writers.values_mut() {
+      writer.flush();
+    }
+  }
+
+  /// Writer timed events can be heatrbeats, batch flushes, NACK responses or flow control.
   /// events are distinguished by TimerMessageType which is send via mio channel. Channel token in
   pub fn handle_writer_timed_event(&mut self, event: &Event) {
     let reciever = self
@@ -476,19 +917,44 @@ impl DPEventWrapper {
           .iter_mut()
           .find(|p| p.1.get_timed_event_entity_token() == event.token());
         match found_writer_with_heartbeat {
+          Some((guid, w)) => {
+            if w.handle_heartbeat_tick() && !w.local_readers().is_empty() {
+              let (guid, local_readers) = (*guid, w.local_readers().to_vec());
+              self.assert_liveliness_to_local_readers(&local_readers, guid);
+            }
+          }
+          None => {}
+        }
+      } else if timer_message == TimerMessageType::writer_batch_flush {
+        let found_writer_with_batch = self
+          .writers
+          .iter_mut()
+          .find(|p| p.1.get_timed_event_entity_token() == event.token());
+        match found_writer_with_batch {
           Some((_guid, w)) => {
-            w.handle_heartbeat_tick();
+            w.flush();
           }
           None => {}
         }
-      } else if timer_message == TimerMessageType::writer_cache_cleaning {
-        let found_writer_to_clean_some_cache = self
+      } else if timer_message == TimerMessageType::writer_nack_response {
+        let found_writer_with_nacks = self
           .writers
           .iter_mut()
           .find(|p| p.1.get_timed_event_entity_token() == event.token());
-        match found_writer_to_clean_some_cache {
+        match found_writer_with_nacks {
           Some((_guid, w)) => {
-            w.handle_cache_cleaning();
+            w.handle_nack_response_timeout();
+          }
+          None => {}
+        }
+      } else if timer_message == TimerMessageType::writer_flow_control {
+        let found_writer_with_queue = self
+          .writers
+          .iter_mut()
+          .find(|p| p.1.get_timed_event_entity_token() == event.token());
+        match found_writer_with_queue {
+          Some((_guid, w)) => {
+            w.send_queued();
           }
           None => {}
         }
@@ -524,6 +990,28 @@ impl DPEventWrapper {
             }
           }
         }
+        TimerMessageType::reader_heartbeat_response => {
+          match self
+            .message_receiver
+            .available_readers
+            .iter_mut()
+            .find(|reader| reader.get_entity_token() == event.token())
+          {
+            Some(r) => r.handle_heartbeat_response_timeout(),
+            None => error!("Reader was not found with entity token"),
+          }
+        }
+        TimerMessageType::reader_liveliness_check => {
+          match self
+            .message_receiver
+            .available_readers
+            .iter_mut()
+            .find(|reader| reader.get_entity_token() == event.token())
+          {
+            Some(r) => r.handle_liveliness_check(),
+            None => error!("Reader was not found with entity token"),
+          }
+        }
         _ => {
           todo!();
         }
@@ -548,12 +1036,91 @@ impl DPEventWrapper {
     while let Ok(res) = reader.data_reader_command_receiver.try_recv() {
       message_queue.push(res);
     }
+    // (left, joined) multicast groups
+    let mut group_changes = Vec::new();
 
     for command in message_queue {
       match command {
         ReaderCommand::RESET_REQUESTED_DEADLINE_STATUS => {
           reader.reset_requested_deadline_missed_status();
         }
+        ReaderCommand::RESET_REQUESTED_INCOMPATIBLE_QOS_STATUS => {
+          reader.reset_requested_incompatible_qos_status();
+        }
+        ReaderCommand::RESET_SAMPLE_LOST_STATUS => {
+          reader.reset_sample_lost_status();
+        }
+        ReaderCommand::RESET_SUBSCRIPTION_MATCHED_STATUS => {
+          reader.reset_subscription_matched_status();
+        }
+        ReaderCommand::SET_RTPS_CONFIG(config) => {
+          reader.set_rtps_config(config);
+        }
+        ReaderCommand::SET_MULTICAST_GROUP(group) => {
+          let previous = reader.set_multicast_group(group);
+          if previous != group {
+            group_changes.push((previous, group));
+          }
+        }
+        ReaderCommand::NOTIFY_DATA_AVAILABLE => {
+          reader.notify_data_available();
+        }
+      }
+    }
+
+    for (left, joined) in group_changes {
+      if let Some(group) = left {
+        self.leave_reader_multicast_group(group);
+      }
+      if let Some(group) = joined {
+        self.join_reader_multicast_group(group);
+      }
+    }
+  }
+
+  // Readers with a multicast group receive on the user traffic multicast listener.
+  // Several readers may share a group, so it is left only when the last one leaves.
+  fn join_reader_multicast_group(&mut self, group: IpAddr) {
+    let readers = self.reader_multicast_groups.entry(group).or_insert(0);
+    *readers += 1;
+    if *readers > 1 {
+      return;
+    }
+    match self.udp_listeners.get(&USER_TRAFFIC_MUL_LISTENER_TOKEN) {
+      Some(listener) => {
+        if let Err(e) = self
+          .domain_info
+          .network
+          .join_multicast_group(listener, &group)
+        {
+          warn!("Cannot join multicast group {:?}. {:?}", group, e);
+        }
+      }
+      None => warn!(
+        "No user traffic multicast listener to join {:?} with.",
+        group
+      ),
+    }
+  }
+
+  fn leave_reader_multicast_group(&mut self, group: IpAddr) {
+    match self.reader_multicast_groups.get_mut(&group) {
+      Some(readers) if *readers > 1 => {
+        *readers -= 1;
+        return;
+      }
+      Some(_) => {
+        self.reader_multicast_groups.remove(&group);
+      }
+      None => return,
+    }
+    if let Some(listener) = self.udp_listeners.get(&USER_TRAFFIC_MUL_LISTENER_TOKEN) {
+      if let Err(e) = self
+        .domain_info
+        .network
+        .leave_multicast_group(listener, &group)
+      {
+        warn!("Cannot leave multicast group {:?}. {:?}", group, e);
       }
     }
   }
@@ -579,92 +1146,178 @@ impl DPEventWrapper {
     }
   }
 
+  /// Removes the changes no longer needed from DDSCache: first the writers remove the
+  /// changes all matched readers have acknowledged, beyond their History depth, and then
+  /// DDSCache the received changes all DataReaders have taken.
+  pub fn collect_cache_garbage(&mut self) {
+    for writer in self.writers.values_mut() {
+      writer.handle_cache_cleaning();
+    }
+    let removed = read_lock(&self.ddscache).collect_garbage();
+    debug!("DDSCache garbage collection removed {} changes", removed);
+  }
+
   pub fn update_writers(&mut self, needs_new_cache_change: bool) {
-    match self.discovery_db.read() {
-      Ok(db) => {
-        for (_, writer) in self.writers.iter_mut() {
-          if writer.get_entity_id() == EntityId::ENTITYID_SPDP_BUILTIN_PARTICIPANT_WRITER {
-            DPEventWrapper::update_spdp_participant_readers(
-              writer,
-              &db,
-              self.domain_info.domain_id,
-            );
-
-            if needs_new_cache_change {
-              for proxy in writer.readers.iter_mut() {
-                proxy.unsend_changes_set(writer.last_change_sequence_number);
-              }
+    let mut local_handovers = Vec::new();
+    // Local readers can be in DiscoveryDB before they get here.
+    let present_readers: Vec<GUID> = self
+      .message_receiver
+      .available_readers
+      .iter()
+      .map(|r| r.get_guid())
+      .collect();
+    {
+      let db = read_lock(&self.discovery_db);
+      for (_, writer) in self.writers.iter_mut() {
+        if writer.get_entity_id() == EntityId::ENTITYID_SPDP_BUILTIN_PARTICIPANT_WRITER {
+          DPEventWrapper::update_spdp_participant_readers(writer, &db, &self.domain_info);
+
+          if needs_new_cache_change {
+            for proxy in writer.readers.iter_mut() {
+              proxy.unsend_changes_set(writer.last_change_sequence_number);
             }
-          } else if writer.get_entity_id() == EntityId::ENTITYID_SEDP_BUILTIN_SUBSCRIPTIONS_WRITER {
-            DPEventWrapper::update_pubsub_readers(
-              writer,
-              &db,
-              EntityId::ENTITYID_SEDP_BUILTIN_SUBSCRIPTIONS_READER,
-              BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_SUBSCRIPTIONS_DETECTOR,
-            );
-            if needs_new_cache_change {
-              for proxy in writer.readers.iter_mut() {
-                proxy.unsend_changes_set(writer.last_change_sequence_number);
-              }
+          }
+        } else if writer.get_entity_id() == EntityId::ENTITYID_SEDP_BUILTIN_SUBSCRIPTIONS_WRITER {
+          DPEventWrapper::update_pubsub_readers(
+            writer,
+            &db,
+            EntityId::ENTITYID_SEDP_BUILTIN_SUBSCRIPTIONS_READER,
+            BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_SUBSCRIPTIONS_DETECTOR,
+          );
+          if needs_new_cache_change {
+            for proxy in writer.readers.iter_mut() {
+              proxy.unsend_changes_set(writer.last_change_sequence_number);
             }
-          } else if writer.get_entity_id() == EntityId::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_WRITER {
-            DPEventWrapper::update_pubsub_readers(
-              writer,
-              &db,
-              EntityId::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_READER,
-              BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_PUBLICATIONS_DETECTOR,
-            );
-            if needs_new_cache_change {
-              for proxy in writer.readers.iter_mut() {
-                proxy.unsend_changes_set(writer.last_change_sequence_number);
-              }
+          }
+        } else if writer.get_entity_id() == EntityId::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_WRITER {
+          DPEventWrapper::update_pubsub_readers(
+            writer,
+            &db,
+            EntityId::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_READER,
+            BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_PUBLICATIONS_DETECTOR,
+          );
+          if needs_new_cache_change {
+            for proxy in writer.readers.iter_mut() {
+              proxy.unsend_changes_set(writer.last_change_sequence_number);
             }
-          } else if writer.get_entity_id() == EntityId::ENTITYID_SEDP_BUILTIN_TOPIC_WRITER {
-            // TODO:
-          } else if writer.get_entity_id()
-            == EntityId::ENTITYID_P2P_BUILTIN_PARTICIPANT_MESSAGE_WRITER
-          {
-            DPEventWrapper::update_pubsub_readers(
-              writer,
-              &db,
-              EntityId::ENTITYID_P2P_BUILTIN_PARTICIPANT_MESSAGE_READER,
-              BuiltinEndpointSet::BUILTIN_ENDPOINT_PARTICIPANT_MESSAGE_DATA_READER,
-            );
-            if needs_new_cache_change {
-              for proxy in writer.readers.iter_mut() {
-                proxy.unsend_changes_set(writer.last_change_sequence_number);
+          }
+        } else if writer.get_entity_id() == EntityId::ENTITYID_SEDP_BUILTIN_TOPIC_WRITER {
+          DPEventWrapper::update_pubsub_readers(
+            writer,
+            &db,
+            EntityId::ENTITYID_SEDP_BUILTIN_TOPIC_READER,
+            BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_TOPICS_DETECTOR,
+          );
+          if needs_new_cache_change {
+            for proxy in writer.readers.iter_mut() {
+              proxy.unsend_changes_set(writer.last_change_sequence_number);
+            }
+          }
+        } else if writer.get_entity_id()
+          == EntityId::ENTITYID_P2P_BUILTIN_PARTICIPANT_MESSAGE_WRITER
+        {
+          DPEventWrapper::update_pubsub_readers(
+            writer,
+            &db,
+            EntityId::ENTITYID_P2P_BUILTIN_PARTICIPANT_MESSAGE_READER,
+            BuiltinEndpointSet::BUILTIN_ENDPOINT_PARTICIPANT_MESSAGE_DATA_READER,
+          );
+          if needs_new_cache_change {
+            for proxy in writer.readers.iter_mut() {
+              proxy.unsend_changes_set(writer.last_change_sequence_number);
+            }
+          }
+        } else {
+          let writer_guid = writer.get_guid();
+          let mut readers: Vec<RtpsReaderProxy> = db
+            .get_external_reader_proxies()
+            .filter(|p| match p.subscription_topic_data.topic_name().as_ref() {
+              Some(tn) => *writer.topic_name() == *tn,
+              None => false,
+            })
+            .filter(|p| db.local_writer_qos_mismatch(writer_guid, p).is_none())
+            .filter_map(|p| RtpsReaderProxy::from_discovered_reader_data(p))
+            .collect();
+          // readers of our own participant
+          let local_readers = db
+            .get_all_local_topic_readers()
+            .filter(|p| match p.subscription_topic_data.topic_name().as_ref() {
+              Some(tn) => *writer.topic_name() == *tn,
+              None => false,
+            })
+            .filter(|p| db.local_writer_qos_mismatch(writer_guid, p).is_none());
+          let reliable = match writer.get_qos().reliability {
+            Some(Reliability::Reliable { .. }) => true,
+            _ => false,
+          };
+
+          if self.domain_info.intra_process_delivery {
+            let local_readers = local_readers
+              .filter_map(|p| p.reader_proxy.remote_reader_guid)
+              .filter(|g| present_readers.contains(g))
+              .collect();
+            let added = writer.update_matched_local_readers(local_readers);
+            // The latest change is handed to new reliable readers, as it is resent
+            // to new remote ones below.
+            if reliable && !added.is_empty() {
+              if let Some(change) = writer.last_change() {
+                local_handovers.push((added, change));
               }
             }
           } else {
-            writer.readers = db
-              .get_external_reader_proxies()
-              .filter(|p| match p.subscription_topic_data.topic_name().as_ref() {
-                Some(tn) => *writer.topic_name() == *tn,
-                None => false,
-              })
-              .filter_map(|p| RtpsReaderProxy::from_discovered_reader_data(p))
-              .collect();
+            // Through the network like to any other participant, for debugging.
+            readers.extend(local_readers.filter_map(RtpsReaderProxy::from_discovered_reader_data));
+            writer.update_matched_local_readers(Vec::new());
+          }
+          writer.update_matched_readers(readers);
 
-            if let Some(Reliability::Reliable {
-              max_blocking_time: _,
-            }) = writer.get_qos().reliability
-            {
-              // reset data sending
-              for reader in writer.readers.iter_mut() {
-                reader.unsend_changes_set(writer.last_change_sequence_number);
-              }
+          if reliable {
+            // reset data sending
+            for reader in writer.readers.iter_mut() {
+              reader.unsend_changes_set(writer.last_change_sequence_number);
             }
           }
         }
       }
-      _ => panic!("DiscoveryDB is poisoned."),
+    }
+
+    if !local_handovers.is_empty() {
+      // the readers need to know the writer before taking its changes
+      self.match_readers(false);
+    }
+    for (readers, change) in local_handovers {
+      self.hand_over_to_local_readers(&readers, &change);
+    }
+  }
+
+  // Delivers a change of one of our writers to our readers, without the network.
+  fn hand_over_to_local_readers(&mut self, readers: &[GUID], change: &CacheChange) {
+    for reader in self
+      .message_receiver
+      .available_readers
+      .iter_mut()
+      .filter(|r| readers.contains(&r.get_guid()))
+    {
+      reader.handle_local_change(change);
+    }
+  }
+
+  // Local readers do not get the liveliness heartbeats of our writers from the network.
+  fn assert_liveliness_to_local_readers(&mut self, readers: &[GUID], writer_guid: GUID) {
+    for reader in self
+      .message_receiver
+      .available_readers
+      .iter_mut()
+      .filter(|r| readers.contains(&r.get_guid()))
+    {
+      reader.writer_liveliness_asserted(writer_guid);
     }
   }
 
   fn update_spdp_participant_readers(
     writer: &mut Writer,
     db: &RwLockReadGuard<DiscoveryDB>,
-    domain_id: u16,
+    domain_info: &DomainInfo,
   ) {
     let guid_prefix = writer.get_guid_prefix();
 
@@ -695,17 +1348,22 @@ impl DPEventWrapper {
       DPEventWrapper::add_reader_to_writer(writer, reader);
     }
 
-    // adding multicast reader
-    let multicast_guid = GUID::new_with_prefix_and_id(
+    // adding reader for participants not yet discovered: the multicast group and
+    // the initial peers
+    let unknown_guid = GUID::new_with_prefix_and_id(
       GuidPrefix::GUIDPREFIX_UNKNOWN,
       EntityId::ENTITYID_SPDP_BUILTIN_PARTICIPANT_READER,
     );
 
-    let mut multicast_reader = RtpsReaderProxy::new(multicast_guid);
-    multicast_reader.multicast_locator_list =
-      get_local_multicast_locators(get_spdp_well_known_multicast_port(domain_id));
+    let mut unknown_reader = RtpsReaderProxy::new(unknown_guid);
+    if domain_info.spdp_multicast {
+      unknown_reader.multicast_locator_list = domain_info
+        .network
+        .multicast_locators(get_spdp_well_known_multicast_port(domain_info.domain_id));
+    }
+    unknown_reader.unicast_locator_list = domain_info.spdp_peers.clone();
 
-    DPEventWrapper::add_reader_to_writer(writer, multicast_reader);
+    DPEventWrapper::add_reader_to_writer(writer, unknown_reader);
     debug!("SPDP Participant readers updated.");
   }
 
@@ -762,6 +1420,31 @@ impl DPEventWrapper {
     }
   }
 
+  // Stops all communication with an ignored participant. Its discovery data is
+  // already gone from DiscoveryDB, but builtin endpoints need to be cleaned here.
+  fn ignore_participant(&mut self, guid_prefix: GuidPrefix) {
+    self.message_receiver.ignore_participant(guid_prefix);
+    for reader in self.message_receiver.available_readers.iter_mut() {
+      reader.matched_writers_remove_participant(guid_prefix);
+    }
+    for (_, writer) in self.writers.iter_mut() {
+      writer.matched_readers_remove_participant(guid_prefix);
+    }
+  }
+
+  fn report_incompatible_qos(&mut self, local: GUID, policy: QosPolicyId) {
+    if let Some(writer) = self.writers.get_mut(&local) {
+      writer.report_incompatible_qos(policy);
+    } else if let Some(reader) = self
+      .message_receiver
+      .available_readers
+      .iter_mut()
+      .find(|r| r.get_guid() == local)
+    {
+      reader.report_incompatible_qos(policy);
+    }
+  }
+
   fn add_reader_to_writer(writer: &mut Writer, mut proxy: RtpsReaderProxy) {
     let reader = writer
       .readers
@@ -781,10 +1464,16 @@ impl DPEventWrapper {
   }
 
   pub fn update_readers(&mut self) {
-    let db = match self.discovery_db.read() {
-      Ok(db) => db,
-      Err(e) => panic!("DiscoveryDB is poisoned. {:?}", e),
-    };
+    self.match_readers(true);
+  }
+
+  // Matches our readers with the writers known to Discovery. Writers that no longer
+  // match are forgotten only on Discovery updates, so that adding readers or writers
+  // keeps the writers matched by other means, such as matched_writer_add.
+  fn match_readers(&mut self, forget_unmatched: bool) {
+    let db = read_lock(&self.discovery_db);
+    // writers that send to our readers through shared memory
+    let mut shm_writers = HashSet::new();
 
     for reader in self.message_receiver.available_readers.iter_mut() {
       match reader.get_entity_id() {
@@ -806,7 +1495,9 @@ impl DPEventWrapper {
             })
             .collect();
 
-          reader.retain_matched_writers(proxies.iter());
+          if forget_unmatched {
+            reader.retain_matched_writers(proxies.iter());
+          }
           for proxy in proxies.into_iter() {
             reader.add_writer_proxy(proxy);
           }
@@ -827,6 +1518,14 @@ impl DPEventWrapper {
             BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_PUBLICATIONS_ANNOUNCER,
           );
         }
+        EntityId::ENTITYID_SEDP_BUILTIN_TOPIC_READER => {
+          DPEventWrapper::update_pubsub_writers(
+            reader,
+            &db,
+            EntityId::ENTITYID_SEDP_BUILTIN_TOPIC_WRITER,
+            BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_TOPICS_ANNOUNCER,
+          );
+        }
         EntityId::ENTITYID_P2P_BUILTIN_PARTICIPANT_MESSAGE_READER => {
           DPEventWrapper::update_pubsub_writers(
             reader,
@@ -837,48 +1536,76 @@ impl DPEventWrapper {
         }
         _ => {
           let topic_name = reader.topic_name().clone();
+          let reader_guid = reader.get_guid();
+          // Writers of our own participant match like remote ones. Whether their changes
+          // come in memory or through the network is up to the writer.
           let proxies: Vec<RtpsWriterProxy> = db
             .get_external_writer_proxies()
+            .chain(db.get_all_local_topic_writers())
             .filter(|p| match p.publication_topic_data.topic_name.as_ref() {
               Some(tn) => topic_name == *tn,
               None => false,
             })
+            .filter(|p| db.local_reader_qos_mismatch(reader_guid, p).is_none())
             .filter_map(|p| RtpsWriterProxy::from_discovered_writer_data(p))
             .collect();
+          shm_writers.extend(
+            proxies
+              .iter()
+              .filter(|p| {
+                p.unicast_locator_list
+                  .iter()
+                  .any(|l| l.kind == LocatorKind::LOCATOR_KIND_SHM)
+              })
+              .map(|p| p.remote_writer_guid),
+          );
 
-          reader.retain_matched_writers(proxies.iter());
+          if forget_unmatched {
+            reader.retain_matched_writers(proxies.iter());
+          }
           for proxy in proxies.into_iter() {
             reader.add_writer_proxy(proxy);
           }
         }
       }
     }
+    drop(db);
+
+    #[cfg(feature = "shm")]
+    {
+      if let Some(receiver) = self.shm_receiver.as_mut() {
+        receiver.set_writers(&shm_writers);
+      }
+      // A writer may have written before we knew it.
+      self.handle_shm_traffic();
+    }
+    #[cfg(not(feature = "shm"))]
+    let _ = shm_writers;
   }
 
   pub fn update_topics(&mut self) {
-    match self.discovery_db.read() {
-      Ok(db) => match self.ddscache.write() {
-        Ok(mut ddsc) => {
-          for topic in db.get_all_topics() {
-            let topic_name = match &topic.topic_data.name {
-              Some(td) => td,
-              None => continue,
-            };
-            // TODO: how do you know when topic is keyed and is not
-            let topic_kind = match &topic.topic_data.key {
-              Some(_) => TopicKind::WithKey,
-              None => TopicKind::NoKey,
-            };
-            let topic_data_type = match &topic.topic_data.type_name {
-              Some(tn) => tn.clone(),
-              None => continue,
-            };
-            ddsc.add_new_topic(topic_name, topic_kind, &TypeDesc::new(topic_data_type));
-          }
-        }
-        _ => panic!("DDSCache is poisoned"),
-      },
-      _ => panic!("DiscoveryDB is poisoned"),
+    let db = read_lock(&self.discovery_db);
+    let mut ddsc = write_lock(&self.ddscache);
+    for topic in db.get_all_topics() {
+      let topic_name = match &topic.topic_data.name {
+        Some(td) => td,
+        None => continue,
+      };
+      // TODO: how do you know when topic is keyed and is not
+      let topic_kind = match &topic.topic_data.key {
+        Some(_) => TopicKind::WithKey,
+        None => TopicKind::NoKey,
+      };
+      let topic_data_type = match &topic.topic_data.type_name {
+        Some(tn) => tn.clone(),
+        None => continue,
+      };
+      ddsc.add_new_topic(
+        topic_name,
+        topic_kind,
+        &TypeDesc::new(topic_data_type),
+        &topic.topic_data.generate_qos(),
+      );
     }
   }
 }
@@ -915,6 +1642,7 @@ mod tests {
 
     let (_add_writer_sender, add_writer_receiver) = mio_channel::channel();
     let (_remove_writer_sender, remove_writer_receiver) = mio_channel::channel();
+    let (_add_reader_listener_sender, add_reader_listener_receiver) = mio_channel::channel();
 
     let (_stop_poll_sender, stop_poll_receiver) = mio_channel::channel();
 
@@ -928,6 +1656,13 @@ mod tests {
       domain_participant_guid: GUID::new(),
       domain_id: 0,
       participant_id: 0,
+      spdp_multicast: true,
+      spdp_peers: Vec::new(),
+      network: LocalNetwork::default(),
+      intra_process_delivery: true,
+      capture: PacketCapture::default(),
+      multicast_rejoin_period: Duration::from_secs(10),
+      network_counters: Arc::new(NetworkCounters::default()),
     };
 
     let dp_event_wrapper = DPEventWrapper::new(
@@ -944,6 +1679,10 @@ mod tests {
         token: REMOVE_READER_TOKEN,
         receiver: receiver_remove,
       },
+      TokenReceiverPair {
+        token: ADD_READER_LISTENER_TOKEN,
+        receiver: add_reader_listener_receiver,
+      },
       TokenReceiverPair {
         token: ADD_WRITER_TOKEN,
         receiver: add_writer_receiver,
@@ -1021,8 +1760,10 @@ mod tests {
       history: None,
       resource_limits: None,
       lifespan: None,
+      reader_data_lifecycle: None,
+      writer_data_lifecycle: None,
     };
-    let dp = DomainParticipant::new(0);
+    let dp = DomainParticipant::new(0).unwrap();
     let sub = dp.create_subscriber(&somePolicies).unwrap();
 
     let topic_1 = dp
@@ -1041,6 +1782,7 @@ mod tests {
 
     let (_add_writer_sender, add_writer_receiver) = mio_channel::channel();
     let (_remove_writer_sender, remove_writer_receiver) = mio_channel::channel();
+    let (_add_reader_listener_sender, add_reader_listener_receiver) = mio_channel::channel();
 
     let (_stop_poll_sender, stop_poll_receiver) = mio_channel::channel();
 
@@ -1054,6 +1796,13 @@ mod tests {
       domain_participant_guid: GUID::new(),
       domain_id: 0,
       participant_id: 0,
+      spdp_multicast: true,
+      spdp_peers: Vec::new(),
+      network: LocalNetwork::default(),
+      intra_process_delivery: true,
+      capture: PacketCapture::default(),
+      multicast_rejoin_period: Duration::from_secs(10),
+      network_counters: Arc::new(NetworkCounters::default()),
     };
 
     let dp_event_wrapper = DPEventWrapper::new(
@@ -1070,6 +1819,10 @@ mod tests {
         token: REMOVE_READER_TOKEN,
         receiver: receiver_remove,
       },
+      TokenReceiverPair {
+        token: ADD_READER_LISTENER_TOKEN,
+        receiver: add_reader_listener_receiver,
+      },
       TokenReceiverPair {
         token: ADD_WRITER_TOKEN,
         receiver: add_writer_receiver,
@@ -1133,6 +1886,8 @@ mod tests {
         history: None,
         resource_limits: None,
         lifespan: None,
+        reader_data_lifecycle: None,
+        writer_data_lifecycle: None,
       };
 
       let mut datareader = sub
@@ -0,0 +1,344 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::{
+  atomic::{AtomicUsize, Ordering},
+  Arc,
+};
+use std::time::{Duration, Instant};
+
+use crate::structure::{guid::GUID, locator::LocatorList, sequence_number::SequenceNumber};
+
+/// Limits the rate at which a [DataWriter](struct.WithKeyDataWriter.html) sends
+/// to the network, so that recovering a large backlog does not saturate a slow link.
+///
+/// The limit is a token bucket shared by new samples and repairs of lost ones. Repairs
+/// are sent first, but they too wait for their turn in the bucket.
+///
+/// Set with `DataWriter::set_flow_control`. Unlimited by default.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::FlowControlConfig;
+/// // 10 Mbit/s link, of which this writer may use at most a half
+/// let config = FlowControlConfig {
+///   bytes_per_second: 625_000,
+///   max_burst_bytes: 64 * 1024,
+/// };
+/// assert!(!config.is_unlimited());
+/// assert!(FlowControlConfig::default().is_unlimited());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FlowControlConfig {
+  /// Long-term average sending rate, counting each destination of a message
+  /// separately. Zero means unlimited.
+  pub bytes_per_second: u64,
+  /// How many bytes may be sent back to back after the writer has been idle. A message
+  /// larger than this is sent when the bucket is full. Zero allows one second's worth,
+  /// `bytes_per_second`.
+  pub max_burst_bytes: u64,
+}
+
+impl FlowControlConfig {
+  pub fn is_unlimited(&self) -> bool {
+    self.bytes_per_second == 0
+  }
+
+  fn burst(&self) -> f64 {
+    if self.max_burst_bytes == 0 {
+      self.bytes_per_second as f64
+    } else {
+      self.max_burst_bytes as f64
+    }
+  }
+}
+
+// Number of messages waiting in the flow controller of a Writer. The DataWriter reads
+// it to tell the application about sustained overload.
+#[derive(Clone)]
+pub(crate) struct QueueDepth {
+  inner: Arc<AtomicUsize>,
+}
+
+impl QueueDepth {
+  pub fn new() -> QueueDepth {
+    QueueDepth {
+      inner: Arc::new(AtomicUsize::new(0)),
+    }
+  }
+
+  pub fn get(&self) -> usize {
+    self.inner.load(Ordering::Relaxed)
+  }
+
+  fn set(&self, depth: usize) {
+    self.inner.store(depth, Ordering::Relaxed)
+  }
+}
+
+// What a message sent by a Writer carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Traffic {
+  // changes sent for the first time
+  Fresh,
+  // GAPs and HEARTBEATs. These go before fresh changes.
+  Repair,
+  // A change resent to one reader. Not queued again while already queued.
+  Resend {
+    reader: GUID,
+    seqnum: SequenceNumber,
+  },
+}
+
+// A serialized message and where to send it.
+pub(crate) struct Datagram {
+  pub bytes: Vec<u8>,
+  pub unicast: LocatorList,
+  pub multicast: LocatorList,
+}
+
+impl Datagram {
+  // bytes put on the network when sending to all destinations
+  fn cost(&self) -> f64 {
+    let destinations = (self.unicast.len() + self.multicast.len()).max(1);
+    (self.bytes.len() * destinations) as f64
+  }
+}
+
+// Token bucket of a rate limited Writer. Tokens are bytes, and refill at
+// bytes_per_second up to the burst size. Messages are queued and taken out when the
+// bucket has tokens for them, repairs before fresh changes.
+pub(crate) struct FlowController {
+  config: FlowControlConfig,
+  // Negative after a message larger than the burst size.
+  tokens: f64,
+  refilled_at: Instant,
+  repairs: VecDeque<(Traffic, Datagram)>,
+  fresh: VecDeque<Datagram>,
+  queued_resends: HashSet<(GUID, SequenceNumber)>,
+  depth: QueueDepth,
+}
+
+impl FlowController {
+  // Starts with a full bucket.
+  pub fn new(config: FlowControlConfig, depth: QueueDepth, now: Instant) -> FlowController {
+    depth.set(0);
+    FlowController {
+      config,
+      tokens: config.burst(),
+      refilled_at: now,
+      repairs: VecDeque::new(),
+      fresh: VecDeque::new(),
+      queued_resends: HashSet::new(),
+      depth,
+    }
+  }
+
+  pub fn set_config(&mut self, config: FlowControlConfig, now: Instant) {
+    self.refill(now);
+    self.config = config;
+    self.tokens = self.tokens.min(config.burst());
+  }
+
+  pub fn enqueue(&mut self, traffic: Traffic, datagram: Datagram) {
+    match traffic {
+      Traffic::Fresh => self.fresh.push_back(datagram),
+      Traffic::Repair => self.repairs.push_back((traffic, datagram)),
+      Traffic::Resend { reader, seqnum } => {
+        if self.queued_resends.insert((reader, seqnum)) {
+          self.repairs.push_back((traffic, datagram))
+        }
+      }
+    }
+    self.update_depth();
+  }
+
+  // Takes out the next message, if the bucket has tokens for it now.
+  pub fn next_sendable(&mut self, now: Instant) -> Option<Datagram> {
+    self.refill(now);
+    let cost = match self.repairs.front() {
+      Some((_, d)) => d.cost(),
+      None => self.fresh.front()?.cost(),
+    };
+    if self.tokens < cost && self.tokens < self.config.burst() {
+      return None;
+    }
+    self.tokens -= cost;
+    let datagram = match self.repairs.pop_front() {
+      Some((traffic, d)) => {
+        if let Traffic::Resend { reader, seqnum } = traffic {
+          self.queued_resends.remove(&(reader, seqnum));
+        }
+        d
+      }
+      None => self.fresh.pop_front()?,
+    };
+    self.update_depth();
+    Some(datagram)
+  }
+
+  // How long until the next queued message can be sent. None if nothing is queued.
+  pub fn wait_time(&self) -> Option<Duration> {
+    let cost = match self.repairs.front() {
+      Some((_, d)) => d.cost(),
+      None => self.fresh.front()?.cost(),
+    };
+    let missing = cost.min(self.config.burst()) - self.tokens;
+    if missing <= 0.0 || self.config.is_unlimited() {
+      return Some(Duration::from_secs(0));
+    }
+    Some(Duration::from_secs_f64(
+      missing / self.config.bytes_per_second as f64,
+    ))
+  }
+
+  // Everything still queued, in sending order.
+  pub fn into_queued(self) -> Vec<Datagram> {
+    self.depth.set(0);
+    self
+      .repairs
+      .into_iter()
+      .map(|(_, d)| d)
+      .chain(self.fresh.into_iter())
+      .collect()
+  }
+
+  fn refill(&mut self, now: Instant) {
+    if now > self.refilled_at {
+      let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+      self.tokens =
+        (self.tokens + elapsed * self.config.bytes_per_second as f64).min(self.config.burst());
+      self.refilled_at = now;
+    }
+  }
+
+  fn update_depth(&self) {
+    self.depth.set(self.repairs.len() + self.fresh.len());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::structure::locator::Locator;
+
+  fn datagram(len: usize) -> Datagram {
+    Datagram {
+      bytes: vec![0; len],
+      unicast: vec![Locator::from(
+        "127.0.0.1:7412".parse::<std::net::SocketAddr>().unwrap(),
+      )],
+      multicast: LocatorList::new(),
+    }
+  }
+
+  fn sendable(fc: &mut FlowController, now: Instant) -> Vec<usize> {
+    let mut lens = Vec::new();
+    while let Some(d) = fc.next_sendable(now) {
+      lens.push(d.bytes.len());
+    }
+    lens
+  }
+
+  #[test]
+  fn flow_control_token_bucket() {
+    let start = Instant::now();
+    let depth = QueueDepth::new();
+    let config = FlowControlConfig {
+      bytes_per_second: 1000,
+      max_burst_bytes: 1000,
+    };
+    let mut fc = FlowController::new(config, depth.clone(), start);
+    assert_eq!(fc.wait_time(), None);
+
+    for _ in 0..5 {
+      fc.enqueue(Traffic::Fresh, datagram(400));
+    }
+    assert_eq!(depth.get(), 5);
+
+    // a burst of two, then one per 400 ms
+    assert_eq!(sendable(&mut fc, start), vec![400, 400]);
+    assert_eq!(depth.get(), 3);
+    assert_eq!(fc.wait_time(), Some(Duration::from_millis(200)));
+    assert!(sendable(&mut fc, start + Duration::from_millis(199)).is_empty());
+    assert_eq!(
+      sendable(&mut fc, start + Duration::from_millis(200)),
+      vec![400]
+    );
+    assert_eq!(
+      sendable(&mut fc, start + Duration::from_millis(600)),
+      vec![400]
+    );
+
+    // idle time does not grow the bucket beyond the burst size
+    fc.enqueue(Traffic::Fresh, datagram(400));
+    fc.enqueue(Traffic::Fresh, datagram(400));
+    fc.enqueue(Traffic::Fresh, datagram(400));
+    assert_eq!(
+      sendable(&mut fc, start + Duration::from_secs(60)),
+      vec![400, 400]
+    );
+    assert_eq!(depth.get(), 2);
+
+    // leftovers go all at once when flow control is turned off
+    assert_eq!(fc.into_queued().len(), 2);
+    assert_eq!(depth.get(), 0);
+  }
+
+  #[test]
+  fn flow_control_oversized_message() {
+    let start = Instant::now();
+    let config = FlowControlConfig {
+      bytes_per_second: 1000,
+      max_burst_bytes: 500,
+    };
+    let mut fc = FlowController::new(config, QueueDepth::new(), start);
+    fc.enqueue(Traffic::Fresh, datagram(1500));
+    fc.enqueue(Traffic::Fresh, datagram(100));
+
+    // goes when the bucket is full, and is paid back before anything else is sent
+    assert_eq!(sendable(&mut fc, start), vec![1500]);
+    assert_eq!(fc.wait_time(), Some(Duration::from_millis(1100)));
+    assert!(sendable(&mut fc, start + Duration::from_millis(1000)).is_empty());
+    assert_eq!(
+      sendable(&mut fc, start + Duration::from_millis(1100)),
+      vec![100]
+    );
+  }
+
+  #[test]
+  fn flow_control_repairs_first() {
+    let start = Instant::now();
+    let config = FlowControlConfig {
+      bytes_per_second: 1000,
+      max_burst_bytes: 0,
+    };
+    let mut fc = FlowController::new(config, QueueDepth::new(), start);
+    let reader = GUID::GUID_UNKNOWN;
+    let resend = |seqnum| Traffic::Resend {
+      reader,
+      seqnum: SequenceNumber::from(seqnum),
+    };
+
+    fc.enqueue(Traffic::Fresh, datagram(1));
+    fc.enqueue(resend(1), datagram(2));
+    fc.enqueue(Traffic::Repair, datagram(3));
+    // already queued
+    fc.enqueue(resend(1), datagram(4));
+    fc.enqueue(resend(2), datagram(5));
+    assert_eq!(sendable(&mut fc, start), vec![2, 3, 5, 1]);
+
+    // can be queued again once sent
+    fc.enqueue(resend(1), datagram(6));
+    assert_eq!(sendable(&mut fc, start), vec![6]);
+
+    // repairs do not exceed the budget either
+    fc.enqueue(Traffic::Fresh, datagram(100));
+    for seqnum in 3..8 {
+      fc.enqueue(resend(seqnum), datagram(200));
+    }
+    assert_eq!(sendable(&mut fc, start), vec![200, 200, 200, 200]);
+    assert_eq!(
+      sendable(&mut fc, start + Duration::from_millis(300)),
+      vec![200, 100]
+    );
+  }
+}
@@ -0,0 +1,44 @@
+use std::sync::{
+  atomic::{AtomicBool, Ordering},
+  Arc, RwLock, RwLockReadGuard,
+};
+
+use crate::structure::guid::GUID;
+
+// The readers a Writer is matched with, remote and local. The Writer updates the list
+// when matching changes, and the DataWriter reads it to run its reader filter on its
+// own thread, and to check that samples fit in a UDP message when some of the readers
+// is reached over UDP.
+#[derive(Clone)]
+pub(crate) struct MatchedReaders {
+  inner: Arc<RwLock<Vec<GUID>>>,
+  over_udp: Arc<AtomicBool>,
+}
+
+impl MatchedReaders {
+  pub fn new() -> MatchedReaders {
+    MatchedReaders {
+      inner: Arc::new(RwLock::new(Vec::new())),
+      over_udp: Arc::new(AtomicBool::new(false)),
+    }
+  }
+
+  pub fn get(&self) -> RwLockReadGuard<Vec<GUID>> {
+    match self.inner.read() {
+      Ok(r) => r,
+      Err(e) => panic!("MatchedReaders is poisoned. {:?}", e),
+    }
+  }
+
+  pub fn over_udp(&self) -> bool {
+    self.over_udp.load(Ordering::Acquire)
+  }
+
+  pub fn set(&self, readers: Vec<GUID>, over_udp: bool) {
+    match self.inner.write() {
+      Ok(mut r) => *r = readers,
+      Err(e) => panic!("MatchedReaders is poisoned. {:?}", e),
+    }
+    self.over_udp.store(over_udp, Ordering::Release);
+  }
+}
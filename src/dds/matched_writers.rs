@@ -0,0 +1,32 @@
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use crate::structure::guid::GUID;
+
+// The writers a Reader is matched with, remote and local. The Reader updates the list
+// when matching changes, and the DataReader reads it to list its matched publications.
+#[derive(Clone)]
+pub(crate) struct MatchedWriters {
+  inner: Arc<RwLock<Vec<GUID>>>,
+}
+
+impl MatchedWriters {
+  pub fn new() -> MatchedWriters {
+    MatchedWriters {
+      inner: Arc::new(RwLock::new(Vec::new())),
+    }
+  }
+
+  pub fn get(&self) -> RwLockReadGuard<'_, Vec<GUID>> {
+    match self.inner.read() {
+      Ok(r) => r,
+      Err(e) => panic!("MatchedWriters is poisoned. {:?}", e),
+    }
+  }
+
+  pub fn set(&self, writers: Vec<GUID>) {
+    match self.inner.write() {
+      Ok(mut w) => *w = writers,
+      Err(e) => panic!("MatchedWriters is poisoned. {:?}", e),
+    }
+  }
+}
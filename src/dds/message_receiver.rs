@@ -1,5 +1,7 @@
 use crate::messages::protocol_version::ProtocolVersion;
 use crate::messages::vendor_id::VendorId;
+use crate::messages::submessages::submessage_elements::parameter_list::ParameterList;
+use crate::common::validity_trait::Validity;
 use crate::messages::submessages::submessages::EntitySubmessage;
 use crate::messages::submessages::submessages::*;
 use crate::structure::guid::{GuidPrefix, GUID};
@@ -22,6 +24,9 @@ use crate::{
 
 use mio_extras::channel as mio_channel;
 use log::{debug, warn};
+use bytes::Bytes;
+
+use std::collections::HashSet;
 
 const RTPS_MESSAGE_HEADER_SIZE: usize = 20;
 
@@ -31,6 +36,8 @@ pub(crate) struct MessageReceiver {
   acknack_sender: mio_channel::SyncSender<(GuidPrefix, AckNack)>,
 
   own_guid_prefix: GuidPrefix,
+  // messages from these participants are dropped
+  ignored_guid_prefixes: HashSet<GuidPrefix>,
   pub source_version: ProtocolVersion,
   pub source_vendor_id: VendorId,
   pub source_guid_prefix: GuidPrefix,
@@ -55,6 +62,7 @@ impl MessageReceiver {
       available_readers: Vec::new(),
       acknack_sender,
       own_guid_prefix: participant_guid_prefix,
+      ignored_guid_prefixes: HashSet::new(),
 
       source_version: ProtocolVersion::THIS_IMPLEMENTATION,
       source_vendor_id: VendorId::VENDOR_UNKNOWN,
@@ -113,14 +121,21 @@ impl MessageReceiver {
     }
   }
 
-  pub fn remove_reader(&mut self, old_reader_guid: GUID) {
-    if let Some(pos) = self
+  pub fn remove_reader(&mut self, old_reader_guid: GUID) -> Option<Reader> {
+    let pos = self
       .available_readers
       .iter()
-      .position(|r| r.get_guid() == old_reader_guid)
-    {
-      self.available_readers.remove(pos);
-    }
+      .position(|r| r.get_guid() == old_reader_guid)?;
+    Some(self.available_readers.remove(pos))
+  }
+
+  pub fn ignore_participant(&mut self, guid_prefix: GuidPrefix) {
+    self.ignored_guid_prefixes.insert(guid_prefix);
+  }
+
+  fn source_is_ignored(&self) -> bool {
+    let source = &self.source_guid_prefix;
+    self.ignored_guid_prefixes.contains(source)
   }
 
   pub fn get_reader(&mut self, reader_id: EntityId) -> Option<&mut Reader> {
@@ -171,14 +186,15 @@ impl MessageReceiver {
     reader.get_history_cache_sequence_start_and_end_numbers()
   }
 
-  pub fn handle_discovery_msg(&mut self, msg: Vec<u8>) {
+  pub fn handle_discovery_msg(&mut self, msg: Bytes) {
     // 9.6.2.2
     // The discovery message is just a data message. No need for the
     // messageReceiver to handle it any differently here?
     self.handle_user_msg(msg);
   }
 
-  pub fn handle_user_msg(&mut self, msg_bytes: Vec<u8>) {
+  // Received DATA payloads stay slices of msg_bytes all the way to the DDSCache.
+  pub fn handle_user_msg(&mut self, msg_bytes: Bytes) {
     self.reset();
     self.dest_guid_prefix = self.own_guid_prefix;
 
@@ -191,7 +207,22 @@ impl MessageReceiver {
       }
     };
 
-    self.source_guid_prefix = rtps_message.header.guid_prefix;
+    let header = &rtps_message.header;
+    if !header.valid() || !header.is_supported_version() {
+      debug!(
+        "Dropping a message with unsupported header {:?}",
+        rtps_message.header
+      );
+      return;
+    }
+    self.source_version = header.protocol_version;
+    self.source_vendor_id = header.vendor_id;
+    self.source_guid_prefix = header.guid_prefix;
+    let _message = dds_span!(trace, "rtps_message", source = self.source_guid_prefix);
+    if self.source_is_ignored() {
+      debug!("Dropping message from ignored participant.");
+      return;
+    }
 
     for submessage in rtps_message.submessages {
       match submessage.body {
@@ -200,15 +231,40 @@ impl MessageReceiver {
       }
       self.submessage_count += 1;
     } // submessage loop
+
+    // The readers add the changes of the message to the DDSCache, and notify their
+    // DataReaders once.
+    for reader in self.available_readers.iter_mut() {
+      reader.end_message();
+    }
   }
 
-  fn send_submessage(&mut self, submessage: EntitySubmessage) {
+  fn send_submessage(&mut self, mut submessage: EntitySubmessage) {
     if self.dest_guid_prefix != self.own_guid_prefix {
       debug!("Messages are not for this participant?");
       debug!("dest_guid_prefix: {:?}", self.dest_guid_prefix);
       debug!("participant guid: {:?}", self.own_guid_prefix);
       return; // Wrong target received
     }
+    // InfoSource may have changed the source
+    if self.source_is_ignored() {
+      return;
+    }
+    if !submessage.is_valid() {
+      warn!(
+        "Dropping an invalid submessage from {:?}: {:?}",
+        self.source_guid_prefix, submessage
+      );
+      return;
+    }
+
+    match &mut submessage {
+      EntitySubmessage::Data(data, _) => self.drop_foreign_parameters(&mut data.inline_qos),
+      EntitySubmessage::DataFrag(datafrag, _) => {
+        self.drop_foreign_parameters(&mut datafrag.inline_qos)
+      }
+      _ => (),
+    }
 
     let mr_state = self.give_message_receiver_info();
     match submessage {
@@ -220,10 +276,12 @@ impl MessageReceiver {
             .iter_mut()
             .filter(|p| p.contains_writer(data.writer_id))
           {
+            reader.begin_message();
             reader.handle_data_msg(data.clone(), mr_state.clone());
           }
         } else {
           if let Some(target_reader) = self.get_reader(data.reader_id) {
+            target_reader.begin_message();
             target_reader.handle_data_msg(data, mr_state);
           }
         }
@@ -239,6 +297,7 @@ impl MessageReceiver {
             reader.handle_heartbeat_msg(
               heartbeat.clone(),
               flags.contains(HEARTBEAT_Flags::Final),
+              flags.contains(HEARTBEAT_Flags::Liveliness),
               mr_state.clone(),
             );
           }
@@ -247,6 +306,7 @@ impl MessageReceiver {
             target_reader.handle_heartbeat_msg(
               heartbeat,
               flags.contains(HEARTBEAT_Flags::Final),
+              flags.contains(HEARTBEAT_Flags::Liveliness),
               mr_state,
             );
           }
@@ -288,14 +348,30 @@ impl MessageReceiver {
     }
   }
 
+  // Vendor specific inline QoS parameters may mean something else in messages of
+  // other vendors.
+  fn drop_foreign_parameters(&self, inline_qos: &mut Option<ParameterList>) {
+    if let Some(parameter_list) = inline_qos {
+      let vendor_id = self.source_vendor_id;
+      parameter_list
+        .parameters
+        .retain(|p| vendor_id.understands_parameter(p.parameter_id));
+    }
+  }
+
   fn handle_parsed_interpreter_submessage(&mut self, interp_subm: InterpreterSubmessage)
   // no return value, just change state of self.
   {
     match interp_subm {
+      // The timestamp applies to the submessages that follow, until the next
+      // InfoTimestamp or InfoSource, or the end of the message. The Invalidate flag
+      // means that they have no timestamp.
       InterpreterSubmessage::InfoTimestamp(ts_struct, flags) => {
-        if flags.contains(INFOTIMESTAMP_Flags::Invalidate) {
-          self.timestamp = Some(ts_struct.timestamp);
-        }
+        self.timestamp = if flags.contains(INFOTIMESTAMP_Flags::Invalidate) {
+          None
+        } else {
+          Some(ts_struct.timestamp)
+        };
       }
       InterpreterSubmessage::InfoSource(info_src, _flags) => {
         self.source_guid_prefix = info_src.guid_prefix;
@@ -350,7 +426,7 @@ impl Default for MessageReceiverState {
       source_guid_prefix: GuidPrefix::default(),
       unicast_reply_locator_list: LocatorList::default(),
       multicast_reply_locator_list: LocatorList::default(),
-      timestamp: Some(Timestamp::TIME_INVALID),
+      timestamp: None,
     }
   }
 }
@@ -376,6 +452,11 @@ mod tests {
 
   use crate::structure::topic_kind::TopicKind;
   use crate::dds::{qos::QosPolicies, typedesc::TypeDesc};
+  use crate::messages::submessages::submessage_elements::serialized_payload::SerializedPayload;
+  use crate::serialization::SubMessage;
+  use crate::structure::sequence_number::SequenceNumberSet;
+  use enumflags2::BitFlags;
+  use speedy::Endianness;
 
   #[test]
 
@@ -418,6 +499,7 @@ mod tests {
       &"test".to_string(),
       TopicKind::NoKey,
       &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
     );
     let new_reader = Reader::new(
       new_guid,
@@ -432,6 +514,7 @@ mod tests {
     //new_reader.matched_writer_add(remote_writer_guid, mr_state);
     message_receiver.add_reader(new_reader);
 
+    let udp_bits1 = Bytes::from(udp_bits1);
     message_receiver.handle_user_msg(udp_bits1.clone());
 
     assert_eq!(message_receiver.submessage_count, 4);
@@ -461,6 +544,10 @@ mod tests {
     info!("deserialized shapeType: {:?}", deserializedShapeType);
     assert_eq!(deserializedShapeType.color, "RED");
 
+    // The payload in the history cache was not copied out of the datagram.
+    let datagram = udp_bits1.as_ptr() as usize..udp_bits1.as_ptr() as usize + udp_bits1.len();
+    assert!(datagram.contains(&(a.data().as_ptr() as usize)));
+
     // now try to serialize same message
 
     let _serializedPayload = to_bytes::<ShapeType, LittleEndian>(&deserializedShapeType);
@@ -509,13 +596,311 @@ mod tests {
       mio_channel::sync_channel::<(GuidPrefix, AckNack)>(10);
     let mut message_receiver = MessageReceiver::new(guid_new.guidPrefix, acknack_sender);
 
-    message_receiver.handle_user_msg(udp_bits1);
+    message_receiver.handle_user_msg(Bytes::from(udp_bits1));
     assert_eq!(message_receiver.submessage_count, 4);
 
-    message_receiver.handle_user_msg(udp_bits2);
+    message_receiver.handle_user_msg(Bytes::from(udp_bits2));
     assert_eq!(message_receiver.submessage_count, 2);
   }
 
+  #[test]
+  fn mr_drops_messages_with_unsupported_header() {
+    // Udp packet with INFO_DST, ACKNACK of RTPS 2.3
+    let udp_bits: Vec<u8> = vec![
+      0x52, 0x54, 0x50, 0x53, 0x02, 0x03, 0x01, 0x0f, 0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00,
+      0x00, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x01, 0x0c, 0x00, 0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d,
+      0x31, 0xa2, 0x28, 0x20, 0x02, 0x08, 0x06, 0x03, 0x18, 0x00, 0x00, 0x00, 0x04, 0xc7, 0x00,
+      0x00, 0x04, 0xc2, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+      0x03, 0x00, 0x00, 0x00,
+    ];
+
+    let guid_new = GUID::new();
+    let (acknack_sender, _acknack_reciever) =
+      mio_channel::sync_channel::<(GuidPrefix, AckNack)>(10);
+    let mut message_receiver = MessageReceiver::new(guid_new.guidPrefix, acknack_sender);
+
+    for (version, supported) in &[
+      ([2u8, 1u8], true),
+      ([2, 4], true),
+      ([2, 5], true),
+      ([2, 0], false),
+      ([1, 1], false),
+      ([3, 0], false),
+    ] {
+      let mut bits = udp_bits.clone();
+      bits[4..6].copy_from_slice(version);
+      message_receiver.handle_user_msg(Bytes::from(bits));
+      let expected = if *supported { 2 } else { 0 };
+      assert_eq!(message_receiver.submessage_count, expected);
+    }
+
+    let mut not_rtps = udp_bits.clone();
+    not_rtps[..4].copy_from_slice(b"RTPX");
+    message_receiver.handle_user_msg(Bytes::from(not_rtps));
+    assert_eq!(message_receiver.submessage_count, 0);
+  }
+
+  #[test]
+  fn mr_ignores_submessages_for_other_participants() {
+    // Udp packet with INFO_DST, INFO_TS, DATA, HEARTBEAT, sent to the participant
+    // 01 03 00 0c 29 2d 31 a2 28 20 02 08
+    let udp_bits1: Vec<u8> = vec![
+      0x52, 0x54, 0x50, 0x53, 0x02, 0x03, 0x01, 0x0f, 0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00,
+      0x00, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x01, 0x0c, 0x00, 0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d,
+      0x31, 0xa2, 0x28, 0x20, 0x02, 0x08, 0x09, 0x01, 0x08, 0x00, 0x1a, 0x15, 0xf3, 0x5e, 0x00,
+      0xcc, 0xfb, 0x13, 0x15, 0x05, 0x2c, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x07,
+      0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x5b, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+      0x00, 0x04, 0x00, 0x00, 0x00, 0x52, 0x45, 0x44, 0x00, 0x69, 0x00, 0x00, 0x00, 0x17, 0x00,
+      0x00, 0x00, 0x1e, 0x00, 0x00, 0x00, 0x07, 0x01, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00,
+      0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x5b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+      0x5b, 0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00,
+    ];
+
+    // A reader with the same entity id, but in another participant.
+    let guid_prefix = GUID::new().guidPrefix;
+    let (acknack_sender, _acknack_reciever) =
+      mio_channel::sync_channel::<(GuidPrefix, AckNack)>(10);
+    let mut message_receiver = MessageReceiver::new(guid_prefix, acknack_sender);
+
+    let reader_guid =
+      GUID::new_with_prefix_and_id(guid_prefix, EntityId::createCustomEntityID([0, 0, 0], 7));
+    let (send, _rec) = mio_channel::sync_channel::<()>(100);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache.write().unwrap().add_new_topic(
+      &"test".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let reader = Reader::new(
+      reader_guid,
+      send,
+      status_sender,
+      dds_cache.clone(),
+      "test".to_string(),
+      reader_command_receiver,
+    );
+    message_receiver.add_reader(reader);
+
+    message_receiver.handle_user_msg(Bytes::from(udp_bits1));
+    assert_eq!(message_receiver.submessage_count, 4);
+    assert!(dds_cache
+      .read()
+      .unwrap()
+      .from_topic_get_all_changes("test")
+      .is_empty());
+  }
+
+  fn data_submessage(reader_id: EntityId, writer_sn: i64) -> SubMessage {
+    let data = Data {
+      reader_id,
+      writer_id: EntityId::createCustomEntityID([0, 0, 1], 2),
+      writer_sn: SequenceNumber::from(writer_sn),
+      inline_qos: None,
+      serialized_payload: Some(SerializedPayload::new(
+        RepresentationIdentifier::CDR_LE,
+        vec![1, 2, 3, 4],
+      )),
+    };
+    let flags =
+      BitFlags::<DATA_Flags>::from_endianness(Endianness::LittleEndian) | DATA_Flags::Data;
+    SubMessage {
+      header: SubmessageHeader {
+        kind: SubmessageKind::DATA,
+        flags: flags.bits(),
+        content_length: data
+          .write_to_vec_with_ctx(Endianness::LittleEndian)
+          .unwrap()
+          .len() as u16,
+      },
+      body: SubmessageBody::Entity(EntitySubmessage::Data(data, flags)),
+    }
+  }
+
+  #[test]
+  fn mr_applies_info_timestamp_to_following_data() {
+    let guid_prefix = GUID::new().guidPrefix;
+    let (acknack_sender, _acknack_reciever) =
+      mio_channel::sync_channel::<(GuidPrefix, AckNack)>(10);
+    let mut message_receiver = MessageReceiver::new(guid_prefix, acknack_sender);
+
+    let reader_id = EntityId::createCustomEntityID([0, 0, 0], 7);
+    let (send, _rec) = mio_channel::sync_channel::<()>(100);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache.write().unwrap().add_new_topic(
+      &"test".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let reader = Reader::new(
+      GUID::new_with_prefix_and_id(guid_prefix, reader_id),
+      send,
+      status_sender,
+      dds_cache.clone(),
+      "test".to_string(),
+      reader_command_receiver,
+    );
+    message_receiver.add_reader(reader);
+
+    let remote_prefix = GUID::new().guidPrefix;
+    let first_ts = Timestamp::now();
+    let second_ts = Timestamp::TIME_ZERO;
+
+    // INFO_TS applies to all DATA that follow it, the InvalidateFlag takes the
+    // timestamp away, and a later INFO_TS gives a new one.
+    let mut message = Message::new(Header::new(remote_prefix));
+    message.add_submessage(InfoTimestamp::create_submessage(
+      Some(first_ts),
+      Endianness::LittleEndian,
+    ));
+    message.add_submessage(data_submessage(reader_id, 1));
+    message.add_submessage(data_submessage(reader_id, 2));
+    message.add_submessage(InfoTimestamp::create_submessage(
+      None,
+      Endianness::LittleEndian,
+    ));
+    message.add_submessage(data_submessage(reader_id, 3));
+    message.add_submessage(InfoTimestamp::create_submessage(
+      Some(second_ts),
+      Endianness::LittleEndian,
+    ));
+    message.add_submessage(data_submessage(reader_id, 4));
+    message_receiver.handle_user_msg(Bytes::from(
+      message
+        .write_to_vec_with_ctx(Endianness::LittleEndian)
+        .unwrap(),
+    ));
+    assert_eq!(message_receiver.submessage_count, 7);
+
+    // The timestamp of the previous message does not carry over.
+    let mut message = Message::new(Header::new(remote_prefix));
+    message.add_submessage(data_submessage(reader_id, 5));
+    message_receiver.handle_user_msg(Bytes::from(
+      message
+        .write_to_vec_with_ctx(Endianness::LittleEndian)
+        .unwrap(),
+    ));
+
+    let mut timestamps: Vec<(SequenceNumber, Option<Timestamp>)> = dds_cache
+      .read()
+      .unwrap()
+      .from_topic_get_all_changes("test")
+      .into_iter()
+      .map(|(_, cc)| (cc.sequence_number, cc.source_timestamp))
+      .collect();
+    timestamps.sort_by_key(|(sn, _)| *sn);
+    assert_eq!(
+      timestamps,
+      vec![
+        (SequenceNumber::from(1), Some(first_ts)),
+        (SequenceNumber::from(2), Some(first_ts)),
+        (SequenceNumber::from(3), None),
+        (SequenceNumber::from(4), Some(second_ts)),
+        (SequenceNumber::from(5), None),
+      ]
+    );
+  }
+
+  #[test]
+  fn mr_adds_changes_of_a_message_together() {
+    let guid_prefix = GUID::new().guidPrefix;
+    let (acknack_sender, _acknack_reciever) =
+      mio_channel::sync_channel::<(GuidPrefix, AckNack)>(10);
+    let mut message_receiver = MessageReceiver::new(guid_prefix, acknack_sender);
+
+    let reader_id = EntityId::createCustomEntityID([0, 0, 0], 7);
+    let (send, rec) = mio_channel::sync_channel::<()>(100);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache.write().unwrap().add_new_topic(
+      &"test".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let reader = Reader::new(
+      GUID::new_with_prefix_and_id(guid_prefix, reader_id),
+      send,
+      status_sender,
+      dds_cache.clone(),
+      "test".to_string(),
+      reader_command_receiver,
+    );
+    message_receiver.add_reader(reader);
+
+    let mut message = Message::new(Header::new(GUID::new().guidPrefix));
+    for sn in 1..=20 {
+      message.add_submessage(data_submessage(reader_id, sn));
+    }
+    message_receiver.handle_user_msg(Bytes::from(
+      message
+        .write_to_vec_with_ctx(Endianness::LittleEndian)
+        .unwrap(),
+    ));
+
+    // All changes are there in the order of the message, and the DataReader is
+    // notified once.
+    let sequence_numbers: Vec<i64> = dds_cache
+      .read()
+      .unwrap()
+      .from_topic_get_all_changes("test")
+      .into_iter()
+      .map(|(_, cc)| i64::from(cc.sequence_number))
+      .collect();
+    assert_eq!(sequence_numbers, (1..=20).collect::<Vec<i64>>());
+    assert!(rec.try_recv().is_ok());
+    assert!(rec.try_recv().is_err());
+  }
+
+  #[test]
+  fn mr_drops_invalid_acknacks() {
+    let guid_prefix = GUID::new().guidPrefix;
+    let (acknack_sender, acknack_receiver) = mio_channel::sync_channel::<(GuidPrefix, AckNack)>(10);
+    let mut message_receiver = MessageReceiver::new(guid_prefix, acknack_sender);
+
+    let acknack = |reader_sn_state: SequenceNumberSet| {
+      AckNack {
+        reader_id: EntityId::createCustomEntityID([0, 0, 0], 7),
+        writer_id: EntityId::createCustomEntityID([0, 0, 1], 2),
+        reader_sn_state,
+        count: 1,
+      }
+      .create_submessage(BitFlags::<ACKNACK_Flags>::from_endianness(
+        Endianness::LittleEndian,
+      ))
+      .unwrap()
+    };
+    let mut beyond_max = SequenceNumberSet::new(SequenceNumber::from(std::i64::MAX));
+    beyond_max.set.insert(1);
+    let mut too_long = SequenceNumberSet::new(SequenceNumber::from(1));
+    too_long.set.insert(1000);
+
+    let mut message = Message::new(Header::new(GUID::new().guidPrefix));
+    message.add_submessage(acknack(SequenceNumberSet::new(SequenceNumber::from(0))));
+    message.add_submessage(acknack(beyond_max));
+    message.add_submessage(acknack(too_long));
+    message.add_submessage(acknack(SequenceNumberSet::new(SequenceNumber::from(1))));
+    message_receiver.handle_user_msg(Bytes::from(
+      message
+        .write_to_vec_with_ctx(Endianness::LittleEndian)
+        .unwrap(),
+    ));
+
+    assert_eq!(message_receiver.submessage_count, 4);
+    let (_, received) = acknack_receiver.try_recv().unwrap();
+    assert_eq!(received.reader_sn_state.base, SequenceNumber::from(1));
+    assert!(acknack_receiver.try_recv().is_err());
+  }
+
   #[test]
   fn mr_test_header() {
     let guid_new = GUID::new();
@@ -23,33 +23,37 @@
 //! * If you are using CDR serialization (DDS default), then use [`CDRSerializerAdapter`] and [`CDRDeserializerAdapter`]
 //!   when such adapters are required. If you need to use another serialization format, then you should find or write
 //!   a [Serde data format](https://serde.rs/data-format.html) implementation and wrap it as a (De)SerializerAdaper.
+//! * For XCDR2 (DDS-XTypes), e.g. with ROS 2 Iron or newer, use [`Xcdr2SerializerAdapter`] on the writer side.
+//!   Both [`CDRDeserializerAdapter`] and [`Xcdr2DeserializerAdapter`] read either encoding.
+//! * For debugging, [`JsonSerializerAdapter`] and [`JsonDeserializerAdapter`] exchange samples as JSON.
+//!   Only RustDDS understands this representation. They need the `json` feature.
 //! 
 //! [`DomainParticipant`]: struct.DomainParticipant.html
 //! [`Topic`]: struct.Topic.html
 //! [`Publisher`]: struct.Publisher.html
 //! [`Subscriber`]: struct.Subscriber.html
-//! [`DataReader`]: struct.With_Key_DataReader.html
-//! [`DataWriter`]: struct.With_Key_DataWriter.html
+//! [`DataReader`]: struct.WithKeyDataReader.html
+//! [`DataWriter`]: struct.WithKeyDataWriter.html
 //! [`CDRSerializerAdapter`]: ../serialization/struct.CDRSerializerAdapter.html
 //! [`CDRDeserializerAdapter`]: ../serialization/struct.CDRDeserializerAdapter.html
+//! [`Xcdr2SerializerAdapter`]: ../serialization/struct.Xcdr2SerializerAdapter.html
+//! [`Xcdr2DeserializerAdapter`]: ../serialization/struct.Xcdr2DeserializerAdapter.html
+//! [`JsonSerializerAdapter`]: ../serialization/struct.JsonSerializerAdapter.html
+//! [`JsonDeserializerAdapter`]: ../serialization/struct.JsonDeserializerAdapter.html
 //! [`Keyed`]: traits/trait.Keyed.html
 //! # Examples
 //!
+//! [`rustdds::prelude`](../prelude/index.html) imports all of the above.
+//!
 //! ```
-//! use rustdds::dds::DomainParticipant;
-//! use rustdds::dds::{No_Key_DataReader as DataReader, No_Key_DataWriter as DataWriter, no_key::DataSample};
-//! use rustdds::dds::qos::QosPolicyBuilder;
-//! use rustdds::dds::qos::policy::Reliability;
-//! use rustdds::dds::data_types::DDSDuration;
-//! use rustdds::dds::data_types::TopicKind;
-//! use rustdds::serialization::{CDRSerializerAdapter, CDRDeserializerAdapter};
+//! use rustdds::prelude::*;
 //! use serde::{Serialize, Deserialize};
 //!
 //! // DomainParticipant is always necessary
-//! let domain_participant = DomainParticipant::new(0);
+//! let domain_participant = DomainParticipant::new(0).unwrap();
 //!
 //! let qos = QosPolicyBuilder::new()
-//!   .reliability(Reliability::Reliable { max_blocking_time: DDSDuration::DURATION_ZERO })
+//!   .reliability(Reliability::Reliable { max_blocking_time: Duration::DURATION_ZERO })
 //!   .build();
 //!
 //! // DDS Subscriber, only one is necessary for each thread (slight difference to
@@ -107,9 +111,16 @@
 //! let actual_data = data_sample.value();
 //! ```
 
+use crate::serialization::{CDRDeserializerAdapter, CDRSerializerAdapter};
+
+mod ack_waiter;
+pub(crate) mod builtin_subscriber;
 mod datasample_cache;
 pub(crate) mod ddsdata;
 mod dp_event_wrapper;
+mod flow_control;
+mod matched_readers;
+mod matched_writers;
 mod message_receiver;
 mod sampleinfo;
 
@@ -119,11 +130,17 @@ pub mod no_key;
 pub mod with_key;
 
 pub(crate) mod participant;
+pub(crate) mod participant_config;
 pub(crate) mod pubsub;
+pub(crate) mod qos_profile;
 pub(crate) mod readcondition;
 pub(crate) mod reader;
+pub(crate) mod rpc;
+pub(crate) mod rtps_config;
 pub(crate) mod rtps_reader_proxy;
 pub(crate) mod rtps_writer_proxy;
+pub(crate) mod statistics;
+pub(crate) mod task_waker;
 pub(crate) mod topic;
 pub mod traits;
 pub(crate) mod typedesc;
@@ -139,22 +156,42 @@ pub mod qos;
 /// Datatypes needed for overall operability with this crate
 pub mod data_types {
   pub use crate::discovery::data_types::topic_data::{
-    DiscoveredTopicData, SubscriptionBuiltinTopicData,
+    DiscoveredReaderData, DiscoveredTopicData, DiscoveredWriterData, SubscriptionBuiltinTopicData,
   };
-  #[doc(inline)]
-  pub use crate::structure::duration::Duration as DDSDuration;
+  pub use crate::discovery::data_types::spdp_participant_data::SPDPDiscoveredParticipantData;
+  pub use crate::structure::duration::Duration;
   pub use super::readcondition::ReadCondition;
   #[doc(inline)]
   pub use super::with_key::datareader::SelectByKey;
   #[doc(inline)]
-  pub use crate::structure::time::Timestamp as DDSTimestamp;
+  pub use super::with_key::datawriter::InstanceHandle;
+  pub use crate::structure::time::Timestamp;
   pub use crate::structure::guid::*;
+  pub use crate::structure::locator::{Locator, LocatorKind};
   // TODO: move typedesc module somewhere better
   pub use crate::dds::typedesc::TypeDesc;
-  pub use crate::dds::sampleinfo::SampleInfo;
+  pub use crate::dds::sampleinfo::{InstanceState, SampleInfo, SampleState, ViewState};
+  pub use crate::structure::inline_qos::SampleIdentity;
   #[doc(inline)]
   pub use crate::structure::topic_kind::TopicKind; // AKA dds::topic::TopicKind
   pub use super::traits::key::BuiltInTopicKey;
+
+  /// Old name of [Duration](struct.Duration.html).
+  #[deprecated(note = "use rustdds::dds::data_types::Duration")]
+  pub type DDSDuration = Duration;
+  /// Old name of [Timestamp](struct.Timestamp.html).
+  #[deprecated(note = "use rustdds::dds::data_types::Timestamp")]
+  pub type DDSTimestamp = Timestamp;
+}
+
+/// Communication statuses of DataReaders and DataWriters
+pub mod statuses {
+  pub use super::values::result::{
+    DataWriterStatus, InconsistentTopicStatus, LivelinessLostStatus, OfferedDeadlineMissedStatus,
+    OfferedIncompatibleQosStatus, PublicationMatchedStatus, RequestedDeadlineMissedStatus,
+    RequestedIncompatibleQosStatus, SampleLostStatus, SampleRejectedReason, SampleRejectedStatus,
+    StatusChange, SubscriptionMatchedStatus,
+  };
 }
 
 /// DDS Error
@@ -163,18 +200,54 @@ pub mod error {
 }
 
 pub use participant::DomainParticipant;
+pub use participant_config::{
+  DomainParticipantBuilder, ParticipantConfig, MAX_DOMAIN_ID, MAX_PARTICIPANT_ID,
+};
+pub use qos_profile::{QosProfile, QosProfiles};
+pub use crate::discovery::discovery_config::{DiscoveryConfig, IpFamily, SocketConfig, TcpConfig};
+pub use crate::discovery::discovery_snapshot::{
+  DiscoverySnapshot, EndpointSnapshot, ParticipantSnapshot,
+};
+pub use crate::discovery::static_endpoint::StaticEndpoint;
+pub use crate::structure::clock::{Clock, ManualClock, SharedClock, SystemClock};
+pub use crate::structure::dds_cache::{CacheStatistics, TopicCacheStatistics};
+pub use statistics::{ParticipantStatistics, ReaderStatistics, WriterStatistics};
+pub use rtps_config::{ReaderMulticast, RtpsReaderConfig, RtpsWriterConfig, MIN_HEARTBEAT_PERIOD};
+pub use flow_control::FlowControlConfig;
+pub use crate::network::capture::CaptureConfig;
 pub use topic::Topic;
+pub use rpc::{Replier, Requester, RpcTopics};
 pub use pubsub::Subscriber;
 pub use pubsub::Publisher;
+pub use builtin_subscriber::{
+  BuiltinSubscriber, ParticipantBuiltinReader, PublicationBuiltinReader, SubscriptionBuiltinReader,
+  TopicBuiltinReader,
+};
 
 #[doc(inline)]
-pub use with_key::datawriter::DataWriter as With_Key_DataWriter;
+pub use with_key::datawriter::DataWriter as WithKeyDataWriter;
 
 #[doc(inline)]
-pub use no_key::datawriter::DataWriter as No_Key_DataWriter;
+pub use no_key::datawriter::DataWriter as NoKeyDataWriter;
 
 #[doc(inline)]
-pub use with_key::datareader::DataReader as With_Key_DataReader;
+pub use with_key::datareader::DataReader as WithKeyDataReader;
 
 #[doc(inline)]
-pub use no_key::datareader::DataReader as No_Key_DataReader;
+pub use no_key::datareader::DataReader as NoKeyDataReader;
+
+/// Old name of [WithKeyDataWriter](struct.WithKeyDataWriter.html).
+#[deprecated(note = "use rustdds::dds::WithKeyDataWriter")]
+pub type With_Key_DataWriter<'a, D, SA = CDRSerializerAdapter<D>> = WithKeyDataWriter<'a, D, SA>;
+
+/// Old name of [NoKeyDataWriter](struct.NoKeyDataWriter.html).
+#[deprecated(note = "use rustdds::dds::NoKeyDataWriter")]
+pub type No_Key_DataWriter<'a, D, SA = CDRSerializerAdapter<D>> = NoKeyDataWriter<'a, D, SA>;
+
+/// Old name of [WithKeyDataReader](struct.WithKeyDataReader.html).
+#[deprecated(note = "use rustdds::dds::WithKeyDataReader")]
+pub type With_Key_DataReader<'a, D, DA = CDRDeserializerAdapter<D>> = WithKeyDataReader<'a, D, DA>;
+
+/// Old name of [NoKeyDataReader](struct.NoKeyDataReader.html).
+#[deprecated(note = "use rustdds::dds::NoKeyDataReader")]
+pub type No_Key_DataReader<'a, D, DA = CDRDeserializerAdapter<D>> = NoKeyDataReader<'a, D, DA>;
@@ -4,13 +4,18 @@ use serde::{de::DeserializeOwned};
 use mio::{Poll, Token, Ready, PollOpt, Evented};
 
 use crate::{
+  discovery::data_types::topic_data::DiscoveredWriterData,
   structure::{
     entity::{Entity, EntityAttributes},
+    guid::GUID,
+    duration::Duration,
   },
 };
 use crate::dds::{traits::serde_adapters::*, values::result::*, qos::*, readcondition::*};
+use crate::dds::rtps_config::{ReaderMulticast, RtpsReaderConfig};
+use crate::dds::statistics::ReaderStatistics;
 
-use crate::dds::with_key::datareader as datareader_with_key;
+use crate::dds::with_key::{self, datareader as datareader_with_key};
 use crate::dds::with_key::datasample::DataSample as WithKeyDataSample;
 use crate::serialization::CDRDeserializerAdapter;
 use crate::dds::no_key::datasample::DataSample;
@@ -29,10 +34,10 @@ use super::{
 /// use rustdds::dds::DomainParticipant;
 /// use rustdds::dds::qos::QosPolicyBuilder;
 /// use rustdds::dds::data_types::TopicKind;
-/// use rustdds::dds::No_Key_DataReader as DataReader;
+/// use rustdds::dds::NoKeyDataReader as DataReader;
 /// use rustdds::serialization::CDRDeserializerAdapter;
 ///
-/// let domain_participant = DomainParticipant::new(0);
+/// let domain_participant = DomainParticipant::new(0).unwrap();
 /// let qos = QosPolicyBuilder::new().build();
 /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
 ///
@@ -79,11 +84,11 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataReader as DataReader;
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// use rustdds::dds::data_types::ReadCondition;
   ///
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -103,13 +108,12 @@ where
   ) -> Result<Vec<DataSample<&D>>> {
     let values: Vec<WithKeyDataSample<&NoKeyWrapper<D>>> =
       self.keyed_datareader.read(max_samples, read_condition)?;
-    let mut result = Vec::with_capacity(values.len());
-    for ks in values {
-      if let Some(s) = DataSample::<D>::from_with_key_ref(ks) {
-        result.push(s)
-      }
-    }
-    Ok(result)
+    Ok(
+      values
+        .into_iter()
+        .map(DataSample::<D>::from_with_key_ref)
+        .collect(),
+    )
   }
 
   /// Takes amount of sample found with `max_samples` and `read_condition` parameters.
@@ -126,11 +130,11 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataReader as DataReader;
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// # use rustdds::dds::data_types::ReadCondition;
   /// #
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -150,13 +154,65 @@ where
   ) -> Result<Vec<DataSample<D>>> {
     let values: Vec<WithKeyDataSample<NoKeyWrapper<D>>> =
       self.keyed_datareader.take(max_samples, read_condition)?;
-    let mut result = Vec::with_capacity(values.len());
-    for ks in values {
-      if let Some(s) = DataSample::<D>::from_with_key(ks) {
-        result.push(s)
-      }
-    }
-    Ok(result)
+    Ok(
+      values
+        .into_iter()
+        .map(DataSample::<D>::from_with_key)
+        .collect(),
+    )
+  }
+
+  /// Reads samples like [`read`](#method.read), but lends them instead of returning
+  /// a Vec, so that large samples are not copied.
+  /// See [`WithKeyDataReader::read_loaned`](../struct.WithKeyDataReader.html#method.read_loaned).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # // NoKey is important
+  /// # let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType {}
+  /// #
+  /// let mut data_reader = subscriber.create_datareader_no_key::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  /// if let Ok(loan) = data_reader.read_loaned(10, ReadCondition::not_read()) {
+  ///   for sample in loan.iter() {
+  ///     // Do something
+  ///   }
+  /// }; // loan is returned here
+  /// ```
+  pub fn read_loaned(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+  ) -> Result<LoanedSamples<D>> {
+    Ok(LoanedSamples::new(
+      self.keyed_datareader.read_loaned(max_samples, read_condition)?,
+    ))
+  }
+
+  /// Takes samples like [`take`](#method.take), but lends them instead of moving them out
+  /// of this DataReader. The samples are removed when the returned LoanedSamples is dropped.
+  pub fn take_loaned(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+  ) -> Result<LoanedSamples<D>> {
+    Ok(LoanedSamples::new(
+      self.keyed_datareader.take_loaned(max_samples, read_condition)?,
+    ))
   }
 
   /// Reads next unread sample
@@ -168,10 +224,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataReader as DataReader;
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// #
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -200,10 +256,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataReader as DataReader;
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// #
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -223,10 +279,165 @@ where
     Ok(ds.pop())
   }
 
+  /// Reads next unread sample, waiting for at most `timeout` if none is available yet.
+  /// Returns `Ok(None)` if nothing arrived before the timeout.
+  ///
+  /// A DataReader that has been registered to a mio Poll cannot block. Returns
+  /// `Err(PreconditionNotMet)` in that case.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::{TopicKind, Duration};
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # // NoKey is important
+  /// # let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType {}
+  /// #
+  /// let mut data_reader = subscriber.create_datareader_no_key::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  /// if let Ok(Some(data)) = data_reader.read_next_sample_timeout(Duration::from_millis(10)) {
+  ///   // Do something
+  /// }
+  /// ```
+  pub fn read_next_sample_timeout(&mut self, timeout: Duration) -> Result<Option<DataSample<&D>>> {
+    if self.keyed_datareader.wait_for_samples(timeout)? {
+      self.read_next_sample()
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Takes next unread sample, waiting for at most `timeout` if none is available yet.
+  /// Returns `Ok(None)` if nothing arrived before the timeout.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::{TopicKind, Duration};
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # // NoKey is important
+  /// # let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType {}
+  /// #
+  /// let mut data_reader = subscriber.create_datareader_no_key::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  /// if let Ok(Some(data)) = data_reader.take_next_sample_timeout(Duration::from_millis(10)) {
+  ///   // Do something
+  /// }
+  /// ```
+  pub fn take_next_sample_timeout(&mut self, timeout: Duration) -> Result<Option<DataSample<D>>> {
+    if self.keyed_datareader.wait_for_samples(timeout)? {
+      self.take_next_sample()
+    } else {
+      Ok(None)
+    }
+  }
+
   // Iterator interface
 
+  /// Produces an iterator over the samples selected by `read_condition`, including SampleInfo.
+  /// Samples are marked read only as the iterator yields them.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # // NoKey is important
+  /// # let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType {}
+  /// #
+  /// let mut data_reader = subscriber.create_datareader_no_key::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  /// for sample in data_reader.read_iter(ReadCondition::not_read()).unwrap() {
+  ///   // Do something
+  /// }
+  /// ```
+  pub fn read_iter(
+    &mut self,
+    read_condition: ReadCondition,
+  ) -> Result<impl Iterator<Item = DataSample<&D>>> {
+    Ok(
+      self
+        .keyed_datareader
+        .read_iter(read_condition)?
+        .map(DataSample::<D>::from_with_key_ref),
+    )
+  }
+
+  /// Produces an iterator that takes the samples selected by `read_condition`, including SampleInfo.
+  /// Samples are removed from the `DataReader` only as the iterator yields them.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # // NoKey is important
+  /// # let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType {}
+  /// #
+  /// let mut data_reader = subscriber.create_datareader_no_key::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  /// for sample in data_reader.take_iter(ReadCondition::not_read()).unwrap() {
+  ///   // Do something
+  /// }
+  /// ```
+  pub fn take_iter(
+    &mut self,
+    read_condition: ReadCondition,
+  ) -> Result<impl Iterator<Item = DataSample<D>> + '_> {
+    Ok(
+      self
+        .keyed_datareader
+        .take_iter(read_condition)?
+        .map(DataSample::<D>::from_with_key),
+    )
+  }
+
   /// Produces an interator over the currently available NOT_READ samples.
-  /// Yields only payload data, not SampleInfo metadata
+  /// Yields only payload data, not SampleInfo metadata, and skips samples without data
   /// This is not called `iter()` because it takes a mutable reference to self.
   ///
   /// # Examples
@@ -236,10 +447,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataReader as DataReader;
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// #
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -255,17 +466,15 @@ where
   /// }
   /// ```
   pub fn iterator(&mut self) -> Result<impl Iterator<Item = &D>> {
-    // TODO: We could come up with a more efficent implementation than wrapping a read call
     Ok(
       self
-        .read(std::usize::MAX, ReadCondition::not_read())?
-        .into_iter()
-        .map(|ds| ds.value),
+        .read_iter(ReadCondition::not_read())?
+        .filter_map(|ds| ds.value),
     )
   }
 
   /// Produces an interator over the samples filtered b ygiven condition.
-  /// Yields only payload data, not SampleInfo metadata
+  /// Yields only payload data, not SampleInfo metadata, and skips samples without data
   ///
   /// # Examples
   ///
@@ -274,10 +483,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
-  /// # use rustdds::dds::No_Key_DataReader as DataReader;
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// #
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -296,20 +505,13 @@ where
     &mut self,
     read_condition: ReadCondition,
   ) -> Result<impl Iterator<Item = &D>> {
-    // TODO: We could come up with a more efficent implementation than wrapping a read call
-    Ok(
-      self
-        .read(std::usize::MAX, read_condition)?
-        .into_iter()
-        .map(|ds| ds.value),
-    )
+    Ok(self.read_iter(read_condition)?.filter_map(|ds| ds.value))
   }
 
   /// Produces an interator over the currently available NOT_READ samples.
-  /// Yields only payload data, not SampleInfo metadata
+  /// Yields only payload data, not SampleInfo metadata, and skips samples without data
   /// Removes samples from `DataReader`.
-  /// <strong>Note!</strong> If the iterator is only partially consumed, all the samples it could have provided
-  /// are still removed from the `Datareader`.
+  /// If the iterator is only partially consumed, the remaining samples stay in the `DataReader`.
   ///
   /// # Examples
   ///
@@ -318,10 +520,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
-  /// # use rustdds::dds::No_Key_DataReader as DataReader;
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// #
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -336,20 +538,17 @@ where
   ///   // Do something
   /// }
   /// ```
-  pub fn into_iterator(&mut self) -> Result<impl Iterator<Item = D>> {
-    // TODO: We could come up with a more efficent implementation than wrapping a read call
+  pub fn into_iterator(&mut self) -> Result<impl Iterator<Item = D> + '_> {
     Ok(
       self
-        .take(std::usize::MAX, ReadCondition::not_read())?
-        .into_iter()
-        .map(|ds| ds.value),
+        .take_iter(ReadCondition::not_read())?
+        .filter_map(|ds| ds.value),
     )
   }
 
   /// Produces an interator over the samples filtered b ygiven condition.
-  /// Yields only payload data, not SampleInfo metadata
-  /// <strong>Note!</strong> If the iterator is only partially consumed, all the samples it could have provided
-  /// are still removed from the `Datareader`.
+  /// Yields only payload data, not SampleInfo metadata, and skips samples without data
+  /// If the iterator is only partially consumed, the remaining samples stay in the `DataReader`.
   ///
   /// # Examples
   ///
@@ -358,10 +557,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
-  /// # use rustdds::dds::No_Key_DataReader as DataReader;
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// #
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -379,14 +578,8 @@ where
   pub fn into_conditional_iterator(
     &mut self,
     read_condition: ReadCondition,
-  ) -> Result<impl Iterator<Item = D>> {
-    // TODO: We could come up with a more efficent implementation than wrapping a read call
-    Ok(
-      self
-        .take(std::usize::MAX, read_condition)?
-        .into_iter()
-        .map(|ds| ds.value),
-    )
+  ) -> Result<impl Iterator<Item = D> + '_> {
+    Ok(self.take_iter(read_condition)?.filter_map(|ds| ds.value))
   }
 
   /// Gets latest RequestedDeadlineMissed status
@@ -398,10 +591,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
-  /// # use rustdds::dds::No_Key_DataReader as DataReader;
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// #
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -421,8 +614,253 @@ where
   ) -> Result<Option<RequestedDeadlineMissedStatus>> {
     self.keyed_datareader.get_requested_deadline_missed_status()
   }
+
+  /// Gets latest SampleLost status
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # // NoKey is important
+  /// # let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType {}
+  /// #
+  /// let mut data_reader = subscriber.create_datareader_no_key::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  /// if let Ok(Some(status)) = data_reader.get_sample_lost_status() {
+  ///   // Do something
+  /// }
+  /// ```
+  pub fn get_sample_lost_status(&mut self) -> Result<Option<SampleLostStatus>> {
+    self.keyed_datareader.get_sample_lost_status()
+  }
+
+  /// Gets latest SampleRejected status. Samples are rejected when their payload
+  /// cannot be deserialized.
+  pub fn get_sample_rejected_status(&mut self) -> SampleRejectedStatus {
+    self.keyed_datareader.get_sample_rejected_status()
+  }
+
+  /// Counts of the RTPS traffic this DataReader has received and sent.
+  /// See [`WithKeyDataReader::get_statistics`](../struct.WithKeyDataReader.html#method.get_statistics).
+  pub fn get_statistics(&self) -> ReaderStatistics {
+    self.keyed_datareader.get_statistics()
+  }
+
+  /// Sets how quickly a reliable reader answers the heartbeats of matched writers.
+  pub fn set_rtps_config(&self, config: RtpsReaderConfig) -> Result<()> {
+    self.keyed_datareader.set_rtps_config(config)
+  }
+
+  /// See [`WithKeyDataReader::set_multicast`](../struct.WithKeyDataReader.html#method.set_multicast).
+  pub fn set_multicast(&self, multicast: ReaderMulticast) -> Result<()> {
+    self.keyed_datareader.set_multicast(multicast)
+  }
+
+  /// See [`WithKeyDataReader::set_name`](../struct.WithKeyDataReader.html#method.set_name).
+  pub fn set_name(&self, name: &str) -> Result<()> {
+    self.keyed_datareader.set_name(name)
+  }
+
+  /// Gets SubscriptionMatchedStatus
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # // NoKey is important
+  /// # let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType {}
+  /// #
+  /// let mut data_reader = subscriber.create_datareader_no_key::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  /// if let Ok(status) = data_reader.get_subscription_matched_status() {
+  ///   // Do something
+  /// }
+  /// ```
+  pub fn get_subscription_matched_status(&mut self) -> Result<SubscriptionMatchedStatus> {
+    self.keyed_datareader.get_subscription_matched_status()
+  }
+
+  /// Gets RequestedIncompatibleQosStatus
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
+  /// # use rustdds::dds::NoKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # // NoKey is important
+  /// # let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType {}
+  /// #
+  /// let mut data_reader = subscriber.create_datareader_no_key::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  /// if let Ok(status) = data_reader.get_requested_incompatible_qos_status() {
+  ///   // Do something
+  /// }
+  /// ```
+  pub fn get_requested_incompatible_qos_status(
+    &mut self,
+  ) -> Result<RequestedIncompatibleQosStatus> {
+    self
+      .keyed_datareader
+      .get_requested_incompatible_qos_status()
+  }
+
+  /// GUIDs of the DataWriters this DataReader is matched with: the remote ones and
+  /// those of our own participant that publish its topic with compatible QoS.
+  pub fn get_matched_publications(&self) -> Vec<GUID> {
+    self.keyed_datareader.get_matched_publications()
+  }
+
+  /// Discovery data of a matched DataWriter, remote or of our own participant. See
+  /// [get_matched_publications](#method.get_matched_publications).
+  pub fn get_matched_publication_data(&self, writer_guid: GUID) -> Option<DiscoveredWriterData> {
+    self
+      .keyed_datareader
+      .get_matched_publication_data(writer_guid)
+  }
 }
 
+/// Samples lent by a no_key DataReader.
+/// See [`with_key::LoanedSamples`](../with_key/struct.LoanedSamples.html).
+pub struct LoanedSamples<'a, D> {
+  keyed: with_key::LoanedSamples<'a, NoKeyWrapper<D>>,
+}
+
+impl<'a, D> LoanedSamples<'a, D> {
+  fn new(keyed: with_key::LoanedSamples<'a, NoKeyWrapper<D>>) -> LoanedSamples<'a, D> {
+    LoanedSamples { keyed }
+  }
+
+  pub fn len(&self) -> usize {
+    self.keyed.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.keyed.is_empty()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = DataSample<&D>> {
+    self.keyed.iter().map(DataSample::<D>::from_with_key_ref)
+  }
+}
+
+#[cfg(feature = "async")]
+mod async_io {
+  use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+  };
+
+  use futures_core::Stream;
+
+  use super::*;
+
+  impl<'a, D: 'static, DA> DataReader<'a, D, DA>
+  where
+    D: DeserializeOwned,
+    DA: DeserializerAdapter<D>,
+  {
+    /// Asynchronous version of [take_next_sample](#method.take_next_sample).
+    /// The returned future resolves when there is an unread sample to take.
+    ///
+    /// Requires feature `async`.
+    pub fn async_take_next(&mut self) -> TakeNextFuture<'_, 'a, D, DA> {
+      TakeNextFuture { reader: self }
+    }
+
+    /// A Stream of unread samples, taken from this DataReader as they arrive.
+    /// The stream ends if the DataReader can no longer receive data.
+    ///
+    /// Requires feature `async`.
+    pub fn async_sample_stream(&mut self) -> SampleStream<'_, 'a, D, DA> {
+      SampleStream { reader: self }
+    }
+
+    fn poll_take_next(&mut self, cx: &mut Context<'_>) -> Poll<Result<DataSample<D>>> {
+      self
+        .keyed_datareader
+        .poll_take_next(cx)
+        .map(|r| r.map(DataSample::<D>::from_with_key))
+    }
+  }
+
+  /// Future returned by [DataReader::async_take_next](struct.DataReader.html#method.async_take_next).
+  pub struct TakeNextFuture<'r, 'a, D: DeserializeOwned, DA: DeserializerAdapter<D>> {
+    reader: &'r mut DataReader<'a, D, DA>,
+  }
+
+  impl<'r, 'a, D: 'static, DA> Future for TakeNextFuture<'r, 'a, D, DA>
+  where
+    D: DeserializeOwned,
+    DA: DeserializerAdapter<D>,
+  {
+    type Output = Result<DataSample<D>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+      self.get_mut().reader.poll_take_next(cx)
+    }
+  }
+
+  /// Stream returned by [DataReader::async_sample_stream](struct.DataReader.html#method.async_sample_stream).
+  pub struct SampleStream<'r, 'a, D: DeserializeOwned, DA: DeserializerAdapter<D>> {
+    reader: &'r mut DataReader<'a, D, DA>,
+  }
+
+  impl<'r, 'a, D: 'static, DA> Stream for SampleStream<'r, 'a, D, DA>
+  where
+    D: DeserializeOwned,
+    DA: DeserializerAdapter<D>,
+  {
+    type Item = DataSample<D>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+      match self.get_mut().reader.poll_take_next(cx) {
+        Poll::Ready(Ok(sample)) => Poll::Ready(Some(sample)),
+        Poll::Ready(Err(_)) => Poll::Ready(None),
+        Poll::Pending => Poll::Pending,
+      }
+    }
+  }
+}
+
+#[cfg(feature = "async")]
+pub use async_io::{SampleStream, TakeNextFuture};
+
 // This is  not part of DDS spec. We implement mio Eventd so that the application can asynchronously
 // poll DataReader(s).
 impl<'a, D, DA> Evented for DataReader<'a, D, DA>
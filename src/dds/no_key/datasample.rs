@@ -1,7 +1,5 @@
-use crate::structure::guid::GUID;
-use crate::structure::time::Timestamp;
-
 use crate::dds::sampleinfo::*;
+use crate::structure::time::Timestamp;
 
 use crate::dds::no_key::wrappers::NoKeyWrapper;
 use crate::dds::with_key::datasample::DataSample as WithKeyDataSample;
@@ -9,72 +7,38 @@ use crate::dds::with_key::datasample::DataSample as WithKeyDataSample;
 /// DDS spec 2.2.2.5.4
 ///
 /// Note that no_key::DataSample and with_key::DataSample are two different but similar structs.
+///
+/// A NoKey topic has a single instance, which is never disposed. Its instance state is
+/// Alive, or NotAlive_NoWriters when all writers are gone. Losing the writers is told with
+/// a sample without data (valid_data = false), whose value is `None`.
 #[derive(PartialEq, Debug)]
 pub struct DataSample<D> {
   pub(crate) sample_info: SampleInfo, // TODO: Can we somehow make this lazily evaluated?
 
-  pub(crate) value: D,
+  pub(crate) value: Option<D>,
 }
 
 impl<D> DataSample<D> {
-  //TODO: At least rename this. This is not a proper constructor.
-  pub(crate) fn new_deprecated(
-    source_timestamp: Timestamp,
-    payload: D,
-    writer_guid: GUID,
-  ) -> DataSample<D> {
-    // begin dummy placeholder values
-    let sample_state = SampleState::NotRead;
-    let view_state = ViewState::New;
-    let instance_state = InstanceState::Alive;
-    let sample_rank = 0;
-    let generation_rank = 0;
-    let absolute_generation_rank = 0;
-    // end dummy placeholder values
-
-    DataSample {
-      sample_info: SampleInfo {
-        sample_state,
-        view_state,
-        instance_state,
-        generation_counts: NotAliveGenerationCounts::zero(),
-        sample_rank,
-        generation_rank,
-        absolute_generation_rank,
-        source_timestamp: Some(source_timestamp),
-        publication_handle: writer_guid,
-      },
-      value: payload,
-    }
-  }
-
-  pub(crate) fn from_with_key(keyed: WithKeyDataSample<NoKeyWrapper<D>>) -> Option<Self> {
-    match keyed.value {
-      Ok(kv) => Some(DataSample::<D> {
-        sample_info: keyed.sample_info,
-        value: kv.d,
-      }),
-      Err(_) => None,
+  pub(crate) fn from_with_key(keyed: WithKeyDataSample<NoKeyWrapper<D>>) -> Self {
+    DataSample::<D> {
+      sample_info: keyed.sample_info,
+      value: keyed.value.ok().map(|kv| kv.d),
     }
   }
 
-  pub(crate) fn from_with_key_ref(
-    keyed: WithKeyDataSample<&NoKeyWrapper<D>>,
-  ) -> Option<DataSample<&D>> {
-    match keyed.value {
-      Ok(ref kv) => Some(DataSample::<&D> {
-        sample_info: keyed.sample_info,
-        value: &kv.d,
-      }),
-      Err(_) => None,
+  pub(crate) fn from_with_key_ref(keyed: WithKeyDataSample<&NoKeyWrapper<D>>) -> DataSample<&D> {
+    DataSample::<&D> {
+      sample_info: keyed.sample_info,
+      value: keyed.value.ok().map(|kv| &kv.d),
     }
   }
 
-  pub fn value(&self) -> &D {
-    &self.value
+  /// The data, or `None` if the sample only tells that the writers are gone.
+  pub fn value(&self) -> Option<&D> {
+    self.value.as_ref()
   }
 
-  pub fn into_value(self) -> D {
+  pub fn into_value(self) -> Option<D> {
     self.value
   }
 
@@ -85,4 +49,15 @@ impl<D> DataSample<D> {
   pub fn sample_info_mut(&mut self) -> &mut SampleInfo {
     &mut self.sample_info
   }
+
+  /// When the writer wrote the sample, or TIME_INVALID if it did not tell
+  pub fn source_timestamp(&self) -> Timestamp {
+    self.sample_info.source_timestamp
+  }
+
+  /// When the sample was received. Minus `source_timestamp`, this is the latency from
+  /// the writer.
+  pub fn reception_timestamp(&self) -> Timestamp {
+    self.sample_info.reception_timestamp
+  }
 } // impl
@@ -1,29 +1,34 @@
 use std::{
+  io,
   time::{Duration},
 };
 
 use mio_extras::channel::Receiver;
+use mio::{Evented, Poll, PollOpt, Ready, Token};
 use serde::Serialize;
 
 use crate::{
   serialization::CDRSerializerAdapter, dds::values::result::StatusChange,
   structure::time::Timestamp,
 };
-use crate::structure::entity::{Entity};
+use crate::structure::{entity::Entity, guid::GUID, inline_qos::SampleIdentity};
 
 use crate::dds::pubsub::Publisher;
 use crate::dds::topic::Topic;
 use crate::dds::values::result::{
-  Result, LivelinessLostStatus, OfferedDeadlineMissedStatus, OfferedIncompatibleQosStatus,
-  PublicationMatchedStatus,
+  Result, DataWriterStatus, LivelinessLostStatus, OfferedDeadlineMissedStatus,
+  OfferedIncompatibleQosStatus, PublicationMatchedStatus,
 };
 use crate::dds::traits::dds_entity::DDSEntity;
 use crate::dds::traits::serde_adapters::SerializerAdapter;
 
 use crate::dds::qos::{HasQoSPolicy, QosPolicies};
+use crate::dds::rtps_config::RtpsWriterConfig;
+use crate::dds::flow_control::FlowControlConfig;
+use crate::dds::statistics::WriterStatistics;
 
 use crate::{
-  discovery::data_types::topic_data::SubscriptionBuiltinTopicData,
+  discovery::data_types::topic_data::DiscoveredReaderData,
   dds::with_key::datawriter as datawriter_with_key,
 };
 
@@ -38,10 +43,10 @@ use super::wrappers::{NoKeyWrapper, SAWrapper};
 /// use rustdds::dds::DomainParticipant;
 /// use rustdds::dds::qos::QosPolicyBuilder;
 /// use rustdds::dds::data_types::TopicKind;
-/// use rustdds::dds::No_Key_DataWriter as DataWriter;
+/// use rustdds::dds::NoKeyDataWriter as DataWriter;
 /// use rustdds::serialization::CDRSerializerAdapter;
 ///
-/// let domain_participant = DomainParticipant::new(0);
+/// let domain_participant = DomainParticipant::new(0).unwrap();
 /// let qos = QosPolicyBuilder::new().build();
 /// let publisher = domain_participant.create_publisher(&qos).unwrap();
 ///
@@ -78,10 +83,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -101,6 +106,24 @@ where
       .write(NoKeyWrapper::<D> { d: data }, source_timestamp)
   }
 
+  /// Writes a sample with the given source timestamp.
+  /// See [`WithKeyDataWriter::write_with_timestamp`](../struct.WithKeyDataWriter.html#method.write_with_timestamp).
+  pub fn write_with_timestamp(&self, data: D, source_timestamp: Timestamp) -> Result<()> {
+    self.write(data, Some(source_timestamp))
+  }
+
+  /// Writes a sample that relates to another sample and returns its own identity.
+  /// See [`WithKeyDataWriter::write_with_related_sample_identity`](../struct.WithKeyDataWriter.html#method.write_with_related_sample_identity).
+  pub fn write_with_related_sample_identity(
+    &self,
+    data: D,
+    related_sample_identity: Option<SampleIdentity>,
+  ) -> Result<SampleIdentity> {
+    self
+      .keyed_datawriter
+      .write_with_related_sample_identity(NoKeyWrapper::<D> { d: data }, related_sample_identity)
+  }
+
   /// Waits for all acknowledgements to finish
   ///
   /// # Examples
@@ -111,10 +134,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -131,21 +154,83 @@ where
     self.keyed_datawriter.wait_for_acknowledgments(max_wait)
   }
 
+  /// Turns on batching of samples.
+  /// See [`WithKeyDataWriter::set_batching`](../struct.WithKeyDataWriter.html#method.set_batching).
+  pub fn set_batching(
+    &self,
+    max_samples: usize,
+    max_bytes: usize,
+    max_delay: Duration,
+  ) -> Result<()> {
+    self
+      .keyed_datawriter
+      .set_batching(max_samples, max_bytes, max_delay)
+  }
+
+  /// Sets the heartbeat period and AckNack response timing of a reliable writer.
+  /// See [`WithKeyDataWriter::set_rtps_config`](../struct.WithKeyDataWriter.html#method.set_rtps_config).
+  pub fn set_rtps_config(&self, config: RtpsWriterConfig) -> Result<()> {
+    self.keyed_datawriter.set_rtps_config(config)
+  }
+
+  /// See [`WithKeyDataWriter::set_name`](../struct.WithKeyDataWriter.html#method.set_name).
+  pub fn set_name(&self, name: &str) -> Result<()> {
+    self.keyed_datawriter.set_name(name)
+  }
+
+  /// Sends the samples collected to the current batch right away.
+  pub fn flush(&self) -> Result<()> {
+    self.keyed_datawriter.flush()
+  }
+
+  /// Limits the rate at which this writer sends.
+  /// See [`WithKeyDataWriter::set_flow_control`](../struct.WithKeyDataWriter.html#method.set_flow_control).
+  pub fn set_flow_control(&self, config: FlowControlConfig) -> Result<()> {
+    self.keyed_datawriter.set_flow_control(config)
+  }
+
+  /// Number of messages waiting to be sent under flow control.
+  pub fn get_flow_control_queue_depth(&self) -> usize {
+    self.keyed_datawriter.get_flow_control_queue_depth()
+  }
+
+  /// Sends each written sample only to the matched DataReaders accepted by `filter`.
+  /// See [`WithKeyDataWriter::set_reader_filter`](../struct.WithKeyDataWriter.html#method.set_reader_filter).
+  pub fn set_reader_filter(&self, filter: Box<dyn Fn(&GUID, &D) -> bool>)
+  where
+    D: 'static,
+  {
+    self.keyed_datawriter.set_reader_filter(Box::new(
+      move |reader: &GUID, sample: &NoKeyWrapper<D>| filter(reader, &sample.d),
+    ))
+  }
+
+  /// Removes the filter set with [set_reader_filter](#method.set_reader_filter).
+  pub fn remove_reader_filter(&self) {
+    self.keyed_datawriter.remove_reader_filter()
+  }
+
+  /// Counts of the RTPS traffic this DataWriter has sent.
+  /// See [`WithKeyDataWriter::get_statistics`](../struct.WithKeyDataWriter.html#method.get_statistics).
+  pub fn get_statistics(&self) -> WriterStatistics {
+    self.keyed_datawriter.get_statistics()
+  }
+
   // status queries
-  /// Unimplemented. <b>Do not use</b>.
+  /// Gets LivelinessLostStatus, i.e. how many times a DataWriter with `ManualByTopic`
+  /// liveliness did not write or assert its liveliness within the lease duration.
   ///
   /// # Examples
   ///
-  // TODO: enable run when implemented
-  /// ```no_run
+  /// ```
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -174,10 +259,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -196,20 +281,19 @@ where
     self.keyed_datawriter.get_offered_deadline_missed_status()
   }
 
-  /// Unimplemented. <b>Do not use</b>.
+  /// Gets OfferedIncompatibleQosStatus
   ///
   /// # Examples
   ///
-  // TODO: enable run when implemented
-  /// ```no_run
+  /// ```
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -228,20 +312,19 @@ where
     self.keyed_datawriter.get_offered_incompatible_qos_status()
   }
 
-  /// Unimplemented. <b>Do not use</b>.
+  /// Gets PublicationMatchedStatus
   ///
   /// # Examples
   ///
-  // TODO: enable run when implemented
-  /// ```no_run
+  /// ```
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -269,10 +352,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -298,10 +381,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -327,10 +410,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -347,20 +430,20 @@ where
     self.keyed_datawriter.assert_liveliness()
   }
 
-  /// Unimplemented. <b>Do not use</b>.
+  /// GUIDs of the remote DataReaders discovered to subscribe the topic of
+  /// this DataWriter.
   ///
   /// # Examples
   ///
-  // TODO: enable run when implemented
-  /// ```no_run
+  /// ```
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -371,14 +454,22 @@ where
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey).unwrap();
   /// let data_writer = publisher.create_datawriter_no_key::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
   ///
-  /// for sub in data_writer.get_matched_subscriptions().iter() {
+  /// for reader_guid in data_writer.get_matched_subscriptions() {
   ///   // handle subscriptions
   /// }
   /// ```
-  pub fn get_matched_subscriptions(&self) -> Vec<SubscriptionBuiltinTopicData> {
+  pub fn get_matched_subscriptions(&self) -> Vec<GUID> {
     self.keyed_datawriter.get_matched_subscriptions()
   }
 
+  /// Discovery data of a matched DataReader, remote or of our own participant. See
+  /// [get_matched_subscriptions](#method.get_matched_subscriptions).
+  pub fn get_matched_subscription_data(&self, reader_guid: GUID) -> Option<DiscoveredReaderData> {
+    self
+      .keyed_datawriter
+      .get_matched_subscription_data(reader_guid)
+  }
+
   /// Gets mio receiver for all implemented Status changes
   ///  
   /// # Examples
@@ -388,10 +479,10 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::No_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -411,6 +502,41 @@ where
   pub fn get_status_listener(&self) -> &Receiver<StatusChange> {
     self.keyed_datawriter.get_status_listener()
   }
+
+  /// Takes the oldest status change of this DataWriter, or `None` if there is none.
+  /// On each poll event of the DataWriter, call this until it returns `None`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::statuses::DataWriterStatus;
+  /// # use rustdds::dds::NoKeyDataWriter as DataWriter;
+  /// # use rustdds::serialization::CDRSerializerAdapter;
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  ///
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType {}
+  /// #
+  /// // NoKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey).unwrap();
+  /// let data_writer = publisher.create_datawriter_no_key::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
+  ///
+  /// while let Some(status) = data_writer.get_status_change() {
+  ///   if let DataWriterStatus::AcknowledgmentsComplete = status {
+  ///     // all written samples are acknowledged
+  ///   }
+  /// }
+  /// ```
+  pub fn get_status_change(&self) -> Option<DataWriterStatus> {
+    self.keyed_datawriter.get_status_change()
+  }
 }
 
 impl<D: Serialize, SA: SerializerAdapter<D>> Entity for DataWriter<'_, D, SA> {
@@ -431,6 +557,77 @@ impl<D: Serialize, SA: SerializerAdapter<D>> HasQoSPolicy for DataWriter<'_, D,
 
 impl<D: Serialize, SA: SerializerAdapter<D>> DDSEntity for DataWriter<'_, D, SA> {}
 
+impl<D: Serialize, SA: SerializerAdapter<D>> Evented for DataWriter<'_, D, SA> {
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    self.keyed_datawriter.register(poll, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    self
+      .keyed_datawriter
+      .reregister(poll, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    self.keyed_datawriter.deregister(poll)
+  }
+}
+
+#[cfg(feature = "async")]
+mod async_io {
+  use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+  };
+
+  use super::*;
+
+  impl<'a, D, SA> DataWriter<'a, D, SA>
+  where
+    D: Serialize,
+    SA: SerializerAdapter<D>,
+  {
+    /// Asynchronous version of [write](#method.write). Resolves when the sample has been
+    /// accepted for sending.
+    ///
+    /// Requires feature `async`.
+    pub fn async_write(
+      &self,
+      data: D,
+      source_timestamp: Option<Timestamp>,
+    ) -> WriteFuture<'_, 'a, D, SA> {
+      WriteFuture {
+        inner: self
+          .keyed_datawriter
+          .async_write(NoKeyWrapper::<D> { d: data }, source_timestamp),
+      }
+    }
+  }
+
+  /// Future returned by [DataWriter::async_write](struct.DataWriter.html#method.async_write).
+  pub struct WriteFuture<'w, 'a, D: Serialize, SA: SerializerAdapter<D>> {
+    inner: datawriter_with_key::WriteFuture<'w, 'a, NoKeyWrapper<D>, SAWrapper<SA>>,
+  }
+
+  impl<'w, 'a, D: Serialize, SA: SerializerAdapter<D>> Future for WriteFuture<'w, 'a, D, SA> {
+    type Output = Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+      Pin::new(&mut self.get_mut().inner).poll(cx)
+    }
+  }
+}
+
+#[cfg(feature = "async")]
+pub use async_io::WriteFuture;
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -441,7 +638,7 @@ mod tests {
 
   #[test]
   fn dw_write_test() {
-    let domain_participant = DomainParticipant::new(0);
+    let domain_participant = DomainParticipant::new(0).unwrap();
     let qos = QosPolicies::qos_none();
     let _default_dw_qos = QosPolicies::qos_none();
     let publisher = domain_participant
@@ -477,7 +674,7 @@ mod tests {
 
   #[test]
   fn dw_wait_for_ack_test() {
-    let domain_participant = DomainParticipant::new(0);
+    let domain_participant = DomainParticipant::new(0).unwrap();
     let qos = QosPolicies::qos_none();
     let publisher = domain_participant
       .create_publisher(&qos)
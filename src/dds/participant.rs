@@ -1,39 +1,63 @@
 use mio::Token;
 use mio_extras::channel as mio_channel;
-use log::{debug, info, warn};
+use log::{debug, error, info, warn};
 
 use std::{
   thread,
   thread::JoinHandle,
   collections::HashMap,
-  time::Duration,
-  sync::{Arc, RwLock, Weak},
+  time::{Duration, Instant},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::RecvTimeoutError,
+    Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, Weak,
+  },
   ops::Deref,
-  net::Ipv4Addr,
 };
 
 use crate::{
-  discovery::data_types::topic_data::DiscoveredTopicData,
+  discovery::data_types::{
+    topic_data::DiscoveredTopicData, spdp_participant_data::SPDPDiscoveredParticipantData,
+  },
   discovery::discovery::DiscoveryCommand,
-  network::{udp_listener::UDPListener, constant::*},
+  network::{
+    udp_listener::UDPListener, constant::*, local_network::LocalNetwork,
+    util::tcp_all_connections_locator,
+  },
 };
 
 use crate::dds::{
   dp_event_wrapper::DPEventWrapper, reader::*, writer::Writer, pubsub::*, topic::*, typedesc::*,
-  qos::*, values::result::*,
+  qos::*, values::result::*, builtin_subscriber::BuiltinSubscriber,
+  participant_config::ParticipantConfig, qos_profile::QosProfile, traits::DdsType,
 };
+use crate::common::lock::{read_lock, write_lock};
+use crate::dds::statistics::{ParticipantStatistics, StatisticsRegistry};
+use crate::network::capture::{CaptureConfig, PacketCapture};
 
 use crate::{
-  discovery::{discovery::Discovery, discovery_db::DiscoveryDB},
+  discovery::{
+    discovery::Discovery, discovery_config::DiscoveryConfig, discovery_db::DiscoveryDB,
+    discovery_snapshot::DiscoverySnapshot, static_endpoint::StaticEndpoint,
+  },
   structure::{
+    clock::SharedClock,
     entity::{Entity, EntityAttributes},
-    guid::GUID,
-    dds_cache::DDSCache,
+    guid::{GuidPrefix, GUID},
+    dds_cache::{CacheStatistics, DDSCache},
+    locator::LocatorList,
   },
 };
 
 use super::dp_event_wrapper::DomainInfo;
 
+// How long shutdown waits for each background thread of a participant to stop.
+const THREAD_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+// How long creation waits for the Discovery thread to report that it is running.
+const DISCOVERY_START_TIMEOUT: Duration = Duration::from_secs(60);
+// How often find_topic looks for the topic in DiscoveryDB.
+const FIND_TOPIC_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 /// DDS DomainParticipant generally only one per domain per machine should be active
 #[derive(Clone)]
 // This is a smart pointer for DomainParticipant_Inner for easier manipulation.
@@ -47,14 +71,136 @@ unsafe impl Sync for DomainParticipant {}
 
 #[allow(clippy::new_without_default)]
 impl DomainParticipant {
+  /// Creates a DomainParticipant with the default
+  /// [ParticipantConfig](struct.ParticipantConfig.html) in the given domain.
+  ///
+  /// Fails like [from_config](#method.from_config), e.g. if `domain_id` is larger
+  /// than [MAX_DOMAIN_ID](constant.MAX_DOMAIN_ID.html) or if no participant id has
+  /// free ports on this host.
+  ///
   /// # Examples
   /// ```
   /// # use rustdds::dds::DomainParticipant;
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// ```
+  pub fn new(domain_id: u16) -> Result<DomainParticipant> {
+    DomainParticipant::from_config(ParticipantConfig::new(domain_id))
+  }
+
+  /// Creates a DomainParticipant with non-default discovery timing. Same as
+  /// [from_config](#method.from_config) with only `discovery` set.
+  ///
+  /// # Examples
+  /// ```
+  /// # use rustdds::dds::{DiscoveryConfig, DomainParticipant};
+  /// # use std::time::Duration;
+  /// let config = DiscoveryConfig {
+  ///   send_topic_info_period: Duration::from_secs(5),
+  ///   spdp_multicast: false,
+  ///   initial_peers: vec![String::from("127.0.0.1")],
+  ///   ..DiscoveryConfig::default()
+  /// };
+  /// let domain_participant = DomainParticipant::with_discovery_config(0, config).unwrap();
+  /// ```
+  pub fn with_discovery_config(
+    domain_id: u16,
+    discovery_config: DiscoveryConfig,
+  ) -> Result<DomainParticipant> {
+    DomainParticipant::from_config(ParticipantConfig {
+      domain_id,
+      discovery: discovery_config,
+      ..ParticipantConfig::default()
+    })
+  }
+
+  /// Creates a DomainParticipant with all creation-time options given in `config`.
+  /// [DomainParticipantBuilder](struct.DomainParticipantBuilder.html) collects them
+  /// one at a time.
+  ///
+  /// Returns `BadParameter` if `config` does not
+  /// [validate](struct.ParticipantConfig.html#method.validate), or if an initial peer
+  /// cannot be resolved to an address of its `ip_families`, or if one of its
+  /// `interfaces` is not found. Returns `OutOfResources` if the ports of the requested
+  /// `participant_id` are already in use on this host, or without a requested id, if
+  /// those of every id up to `max_participant_id` are. Returns `OutOfResources` or
+  /// `PreconditionNotMet` if Discovery fails to start, with the cause in the message.
+  ///
+  /// # Examples
+  /// ```
+  /// # use rustdds::dds::{DomainParticipant, ParticipantConfig};
+  /// let domain_participant = DomainParticipant::from_config(ParticipantConfig::new(0)).unwrap();
   /// ```
-  pub fn new(domain_id: u16) -> DomainParticipant {
+  pub fn from_config(config: ParticipantConfig) -> Result<DomainParticipant> {
+    config.validate()?;
+    let ParticipantConfig {
+      domain_id,
+      participant_id,
+      max_participant_id,
+      discovery: mut discovery_config,
+      name,
+      qos_profile,
+      clock,
+    } = config;
+    let name = name.unwrap_or_else(|| format!("rustdds-{}", std::process::id()));
+    if let Some(profile) = &qos_profile {
+      let peers = profile.initial_peers.iter().cloned();
+      discovery_config.initial_peers.extend(peers);
+    }
+    let mut spdp_peers = discovery_config.initial_peer_locators(domain_id)?;
+    let mut network = LocalNetwork::new(
+      &discovery_config.ip_families,
+      &discovery_config.interfaces,
+      discovery_config.socket,
+    )?;
+    network.set_max_message_size(discovery_config.max_message_size);
+    network.set_passive(discovery_config.passive);
+    if let Some(tcp) = &discovery_config.tcp {
+      network.enable_tcp(tcp)?;
+      if !tcp.udp {
+        spdp_peers.clear();
+      }
+      // participant announcements go to everyone we are connected to
+      spdp_peers.push(tcp_all_connections_locator());
+    }
+    DomainParticipant::new_with_config(
+      domain_id,
+      participant_id,
+      max_participant_id,
+      discovery_config,
+      name,
+      qos_profile,
+      spdp_peers,
+      network,
+      clock,
+    )
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn new_with_config(
+    domain_id: u16,
+    participant_id: Option<u16>,
+    max_participant_id: u16,
+    discovery_config: DiscoveryConfig,
+    name: String,
+    qos_profile: Option<QosProfile>,
+    spdp_peers: LocatorList,
+    mut network: LocalNetwork,
+    clock: SharedClock,
+  ) -> Result<DomainParticipant> {
+    network.enable_shm(discovery_config.shm_segment_size);
     let (djh_sender, djh_receiver) = mio_channel::channel();
-    let mut dpd = DomainParticipant_Disc::new(domain_id, djh_receiver);
+    let mut dpd = DomainParticipant_Disc::new(
+      domain_id,
+      participant_id,
+      max_participant_id,
+      &discovery_config,
+      spdp_peers,
+      network,
+      clock,
+      djh_receiver,
+    )?;
+    dpd.name = name;
+    dpd.qos_profile = qos_profile;
 
     let discovery_updated_sender = match dpd.discovery_updated_sender.take() {
       Some(dus) => dus,
@@ -79,23 +225,35 @@ impl DomainParticipant {
       discovery_started_sender,
       discovery_updated_sender,
       discovery_command_receiver,
-    );
+      discovery_config,
+    )?;
 
-    let discovery_handle = thread::spawn(move || Discovery::discovery_event_loop(discovery));
-    djh_sender.send(discovery_handle).unwrap_or(());
+    let discovery_thread = ParticipantThread::spawn("Discovery", move || {
+      Discovery::discovery_event_loop(discovery)
+    });
+    djh_sender.send(discovery_thread).unwrap_or(());
 
     // blocking until discovery answers
-    let discovery_started = discovery_started_receiver.recv_timeout(Duration::from_secs(60));
+    let discovery_started = discovery_started_receiver
+      .recv_timeout(DISCOVERY_START_TIMEOUT)
+      .unwrap_or_else(|e| {
+        Err(match e {
+          RecvTimeoutError::Timeout => Error::out_of_resources(format!(
+            "Discovery did not start within {:?}",
+            DISCOVERY_START_TIMEOUT
+          )),
+          RecvTimeoutError::Disconnected => {
+            Error::precondition_not_met("Discovery thread stopped before it started")
+          }
+        })
+      });
     match discovery_started {
-      Ok(ds) => match ds {
-        Ok(_) => dp,
-        Err(e) => {
-          std::mem::drop(dp);
-          // TODO: maybe return result on new?
-          panic!("Failed to start discovery. {:?}", e);
-        }
-      },
-      Err(_) => panic!("Channel error"),
+      Ok(_) => Ok(dp),
+      Err(e) => {
+        error!("Failed to start discovery. {}", e);
+        std::mem::drop(dp);
+        Err(e)
+      }
     }
   }
 
@@ -110,7 +268,7 @@ impl DomainParticipant {
   /// ```
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos);
   /// ```
@@ -129,7 +287,7 @@ impl DomainParticipant {
   /// ```
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos);
   /// ```
@@ -152,7 +310,7 @@ impl DomainParticipant {
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// use rustdds::dds::data_types::TopicKind;
   ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey);
   /// ```
@@ -168,11 +326,85 @@ impl DomainParticipant {
       .create_topic(&self.weak_clone(), name, type_desc, qos, topic_kind)
   }
 
+  /// Create DDS Topic of the data type `D`, with the type name of `D`.
+  ///
+  /// Unlike with [`create_topic`](#method.create_topic), DataReaders and DataWriters
+  /// of the topic must be of `D`, and creating them for another type fails. The type
+  /// name can be used by only one Rust type in a participant, so creating a topic of
+  /// another type with the same type name fails as well.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - Name of the topic.
+  /// * `qos` - Takes [qos policies](qos/struct.QosPolicies.html) that are distributed to DataReaders and DataWriters.
+  /// * `topic_kind` - Whether the samples of the topic have a key.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// use rustdds::dds::data_types::TopicKind;
+  /// use rustdds::dds::traits::DdsType;
+  ///
+  /// #[derive(DdsType)]
+  /// struct SomeType {
+  ///   a: i32,
+  /// }
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let topic = domain_participant
+  ///   .create_topic_for::<SomeType>("some_topic", &qos, TopicKind::NoKey)
+  ///   .unwrap();
+  /// ```
+  pub fn create_topic_for<D: DdsType>(
+    &self,
+    name: &str,
+    qos: &QosPolicies,
+    topic_kind: TopicKind,
+  ) -> Result<Topic> {
+    self
+      .dpi
+      .create_topic_for::<D>(&self.weak_clone(), name, qos, topic_kind)
+  }
+
+  /// Waits until a topic of the given name is discovered, or is already known, and
+  /// gives a Topic with its type name and QoS. DataReaders and DataWriters can then
+  /// be created without knowing the type name beforehand. The kind of the topic is
+  /// that of its known DataReaders and DataWriters, `WithKey` if there are none.
+  ///
+  /// Returns `Ok(None)` if the topic is not found within `timeout`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::dds::DomainParticipant;
+  /// use std::time::Duration;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// if let Some(topic) = domain_participant
+  ///   .find_topic("some_topic", Duration::from_millis(100))
+  ///   .unwrap()
+  /// {
+  ///   // create DataReaders of the topic
+  /// }
+  /// ```
+  pub fn find_topic(&self, name: &str, timeout: Duration) -> Result<Option<Topic>> {
+    self.dpi.find_topic(&self.weak_clone(), name, timeout)
+  }
+
+  /// Like [find_topic](#method.find_topic), but does not wait: `Ok(None)` if the
+  /// topic is not currently known.
+  pub fn lookup_topicdescription(&self, name: &str) -> Result<Option<Topic>> {
+    self.dpi.lookup_topicdescription(&self.weak_clone(), name)
+  }
+
   /// # Examples
   ///
   /// ```
   /// # use rustdds::dds::DomainParticipant;
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let domain_id = domain_participant.domain_id();
   /// ```
   pub fn domain_id(&self) -> u16 {
@@ -183,20 +415,36 @@ impl DomainParticipant {
   ///
   /// ```
   /// # use rustdds::dds::DomainParticipant;
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let participant_id = domain_participant.participant_id();
   /// ```
   pub fn participant_id(&self) -> u16 {
     self.dpi.participant_id()
   }
 
+  /// The default QoS given when the participant was created, if any. See
+  /// [ParticipantConfig](struct.ParticipantConfig.html).
+  pub fn qos_profile(&self) -> Option<&QosProfile> {
+    self.dpi.qos_profile.as_ref()
+  }
+
+  /// The name announced to other participants. See
+  /// [ParticipantConfig](struct.ParticipantConfig.html).
+  pub fn name(&self) -> &str {
+    &self.dpi.name
+  }
+
+  pub(crate) fn local_network(&self) -> LocalNetwork {
+    self.dpi.local_network()
+  }
+
   /// Gets all DiscoveredTopics from DDS network
   ///
   /// # Examples
   ///
   /// ```
   /// # use rustdds::dds::DomainParticipant;
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let discovered_topics = domain_participant.get_discovered_topics();
   /// for dtopic in discovered_topics.iter() {
   ///   // do something
@@ -206,6 +454,263 @@ impl DomainParticipant {
     self.dpi.get_discovered_topics()
   }
 
+  /// Gets the GuidPrefixes of all currently discovered remote DomainParticipants.
+  /// Ignored participants are not included.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::dds::DomainParticipant;
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// for prefix in domain_participant.get_discovered_participants() {
+  ///   let data = domain_participant.get_discovered_participant_data(prefix);
+  ///   // do something
+  /// }
+  /// ```
+  pub fn get_discovered_participants(&self) -> Vec<GuidPrefix> {
+    self.dpi.get_discovered_participants()
+  }
+
+  /// Gets a snapshot of the discovery data of a remote DomainParticipant, if it is
+  /// currently known.
+  pub fn get_discovered_participant_data(
+    &self,
+    guid_prefix: GuidPrefix,
+  ) -> Option<SPDPDiscoveredParticipantData> {
+    self.dpi.get_discovered_participant_data(guid_prefix)
+  }
+
+  /// Gets a snapshot of the discovery data of a topic by its name, if it is
+  /// currently known.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::dds::DomainParticipant;
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// if let Some(dtopic) = domain_participant.get_discovered_topic_data("some_topic") {
+  ///   // do something
+  /// }
+  /// ```
+  pub fn get_discovered_topic_data(&self, topic_name: &str) -> Option<DiscoveredTopicData> {
+    self.dpi.get_discovered_topic_data(topic_name)
+  }
+
+  /// Copies out the current discovery state: how many remote participants,
+  /// readers, writers and topics are known, and the endpoints of each remote
+  /// participant. See [DiscoverySnapshot](struct.DiscoverySnapshot.html).
+  pub fn discovery_snapshot(&self) -> DiscoverySnapshot {
+    self.dpi.discovery_snapshot()
+  }
+
+  /// Counts the changes, payload bytes and instances that the cache of this
+  /// participant holds, by topic. See [CacheStatistics](struct.CacheStatistics.html).
+  pub fn cache_statistics(&self) -> CacheStatistics {
+    self.dpi.cache_statistics()
+  }
+
+  /// Counts of the RTPS traffic of the DataWriters and DataReaders of this
+  /// participant. See [ParticipantStatistics](struct.ParticipantStatistics.html).
+  pub fn get_statistics(&self) -> ParticipantStatistics {
+    let mut statistics = self.dpi.statistics.get();
+    statistics.poisoned_locks_recovered = read_lock(&self.dpi.discovery_db).poison_recoveries()
+      + read_lock(&self.dpi.dds_cache).poison_recoveries();
+    statistics
+  }
+
+  /// Writes every RTPS message that this participant sends or receives to a pcapng
+  /// file, for Wireshark. Replaces the capture that is going on, if any. See
+  /// [CaptureConfig](struct.CaptureConfig.html).
+  ///
+  /// The file is written by a thread of its own. Messages are dropped if it falls
+  /// behind, and counted in [capture_dropped](#method.capture_dropped).
+  ///
+  /// Returns `BadParameter` if the file cannot be created.
+  ///
+  /// # Examples
+  ///
+  /// ```no_run
+  /// # use rustdds::dds::{CaptureConfig, DomainParticipant};
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// domain_participant
+  ///   .start_capture(CaptureConfig::new("rtps.pcapng"))
+  ///   .unwrap();
+  /// // ... traffic to look at ...
+  /// domain_participant.stop_capture();
+  /// ```
+  pub fn start_capture(&self, config: CaptureConfig) -> Result<()> {
+    self.dpi.capture.start(config)
+  }
+
+  /// Stops capturing. Returns when the file has all the captured messages.
+  pub fn stop_capture(&self) {
+    self.dpi.capture.stop()
+  }
+
+  /// Number of messages left out of the capture files because they were not written
+  /// fast enough.
+  pub fn capture_dropped(&self) -> u64 {
+    self.dpi.capture.dropped()
+  }
+
+  /// Deletes a Publisher of this participant. Returns `PreconditionNotMet` if it still
+  /// has DataWriters, or belongs to some other participant.
+  pub fn delete_publisher(&self, publisher: Publisher) -> Result<()> {
+    if publisher.get_participant().as_ref() != Some(self) {
+      return Err(Error::precondition_not_met(
+        "Publisher belongs to another DomainParticipant",
+      ));
+    }
+    if publisher.has_datawriters() {
+      return Err(Error::precondition_not_met(
+        "Publisher still has DataWriters",
+      ));
+    }
+    Ok(())
+  }
+
+  /// Deletes a Subscriber of this participant. Returns `PreconditionNotMet` if it still
+  /// has DataReaders, or belongs to some other participant.
+  pub fn delete_subscriber(&self, subscriber: Subscriber) -> Result<()> {
+    if subscriber.get_participant().as_ref() != Some(self) {
+      return Err(Error::precondition_not_met(
+        "Subscriber belongs to another DomainParticipant",
+      ));
+    }
+    if subscriber.has_datareaders() {
+      return Err(Error::precondition_not_met(
+        "Subscriber still has DataReaders",
+      ));
+    }
+    Ok(())
+  }
+
+  /// Deletes a Topic of this participant: releases its cached data, and disposes its
+  /// announcement so that remote participants forget it. A topic is also deleted when
+  /// its last DataWriter, DataReader and Topic are dropped, so this is needed only to
+  /// delete it while other clones of the Topic remain. Returns `PreconditionNotMet` if
+  /// some DataWriter or DataReader still uses the topic, or it belongs to some other
+  /// participant.
+  pub fn delete_topic(&self, topic: Topic) -> Result<()> {
+    if TopicDescription::get_participant(&topic).as_ref() != Some(self) {
+      return Err(Error::precondition_not_met(format!(
+        "topic {} belongs to another DomainParticipant",
+        topic.get_name()
+      )));
+    }
+    self.dpi.delete_topic(topic.get_name())
+  }
+
+  /// Deletes all DataWriters and DataReaders of this participant. Their announcements
+  /// are disposed, so remote participants see them disappear. The deleted entities
+  /// themselves return errors from then on.
+  pub fn delete_contained_entities(&self) -> Result<()> {
+    self.dpi.delete_contained_entities()
+  }
+
+  /// Stops this participant, also when clones of it still exist. Its DataWriters and
+  /// DataReaders are deleted and Discovery disposes our participant, so that remote
+  /// participants do not have to wait for our lease to expire. Then the background
+  /// threads are stopped and the sockets closed. Dropping the last clone does the same.
+  ///
+  /// Readers and writers that still exist no longer communicate, and creating new
+  /// entities returns `PreconditionNotMet`. Shutting down again does nothing.
+  ///
+  /// Returns `OutOfResources` if some background thread did not stop in time. It is
+  /// then left running.
+  ///
+  /// # Examples
+  /// ```
+  /// # use rustdds::dds::DomainParticipant;
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// domain_participant.shutdown().unwrap();
+  /// ```
+  pub fn shutdown(&self) -> Result<()> {
+    self.dpi.shutdown()
+  }
+
+  /// Ignores a remote DomainParticipant for the rest of the lifetime of this
+  /// participant. Its discovery data is dropped, matches of its readers and
+  /// writers with our local ones are removed, and any RTPS messages from it are
+  /// discarded.
+  ///
+  /// # Examples
+  /// ```
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::data_types::GuidPrefix;
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let misbehaving = GuidPrefix::new(vec![1; 12]);
+  /// domain_participant.ignore_participant(misbehaving).unwrap();
+  /// ```
+  pub fn ignore_participant(&self, guid_prefix: GuidPrefix) -> Result<()> {
+    if guid_prefix == self.get_guid().guidPrefix {
+      return Err(Error::bad_parameter(
+        "a DomainParticipant cannot ignore itself",
+      ));
+    }
+    self
+      .dpi
+      .send_discovery_command(DiscoveryCommand::IGNORE_PARTICIPANT { guid_prefix })
+  }
+
+  /// Ignores a remote DataWriter for the rest of the lifetime of this participant.
+  /// Its discovery data is dropped and it is unmatched from our local readers.
+  pub fn ignore_publication(&self, guid: GUID) -> Result<()> {
+    self
+      .dpi
+      .send_discovery_command(DiscoveryCommand::IGNORE_PUBLICATION { guid })
+  }
+
+  /// Ignores a remote DataReader for the rest of the lifetime of this participant.
+  /// Its discovery data is dropped and it is unmatched from our local writers.
+  pub fn ignore_subscription(&self, guid: GUID) -> Result<()> {
+    self
+      .dpi
+      .send_discovery_command(DiscoveryCommand::IGNORE_SUBSCRIPTION { guid })
+  }
+
+  /// Adds a remote DataWriter known in advance. It is matched with our local
+  /// DataReaders of the same topic as if it had been discovered. Returns
+  /// `BadParameter` if the endpoint is in this participant and
+  /// `PreconditionNotMet` if it is ignored.
+  pub fn add_static_remote_writer(&self, endpoint: &StaticEndpoint) -> Result<()> {
+    if endpoint.guid.guidPrefix == self.get_guid().guidPrefix {
+      return Err(Error::bad_parameter(format!(
+        "static remote writer {:?} is in this DomainParticipant",
+        endpoint.guid
+      )));
+    }
+    self.dpi.add_static_remote_writer(endpoint)
+  }
+
+  /// Adds a remote DataReader known in advance. It is matched with our local
+  /// DataWriters of the same topic as if it had been discovered. Returns
+  /// `BadParameter` if the endpoint is in this participant and
+  /// `PreconditionNotMet` if it is ignored.
+  pub fn add_static_remote_reader(&self, endpoint: &StaticEndpoint) -> Result<()> {
+    if endpoint.guid.guidPrefix == self.get_guid().guidPrefix {
+      return Err(Error::bad_parameter(format!(
+        "static remote reader {:?} is in this DomainParticipant",
+        endpoint.guid
+      )));
+    }
+    self.dpi.add_static_remote_reader(endpoint)
+  }
+
+  /// Gives access to the builtin discovery topics DCPSParticipant,
+  /// DCPSPublication, DCPSSubscription and DCPSTopic.
+  ///
+  /// # Examples
+  /// ```
+  /// # use rustdds::dds::DomainParticipant;
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let builtin_subscriber = domain_participant.get_builtin_subscriber().unwrap();
+  /// let publication_reader = builtin_subscriber.publication_reader().unwrap();
+  /// ```
+  pub fn get_builtin_subscriber(&self) -> Result<BuiltinSubscriber> {
+    BuiltinSubscriber::new(self)
+  }
+
   pub(crate) fn weak_clone(&self) -> DomainParticipantWeak {
     let dpc = self.clone();
     DomainParticipantWeak::new(dpc)
@@ -218,6 +723,28 @@ impl DomainParticipant {
   pub(crate) fn discovery_db(&self) -> Arc<RwLock<DiscoveryDB>> {
     return self.dpi.discovery_db.clone();
   }
+
+  pub(crate) fn get_add_reader_listener_sender(
+    &self,
+  ) -> mio_channel::SyncSender<(GUID, ReaderListener)> {
+    self.dpi.get_add_reader_listener_sender()
+  }
+
+  pub(crate) fn delete_local_writer(&self, guid: GUID) -> Result<()> {
+    self.dpi.delete_local_writer(guid)
+  }
+
+  pub(crate) fn statistics_registry(&self) -> &StatisticsRegistry {
+    &self.dpi.statistics
+  }
+
+  pub(crate) fn delete_local_reader(&self, guid: GUID) -> Result<()> {
+    self.dpi.delete_local_reader(guid)
+  }
+
+  pub(crate) fn release_topic_handle(&self, topic_name: &str) {
+    self.dpi.release_topic_handle(topic_name)
+  }
 }
 
 impl PartialEq for DomainParticipant {
@@ -252,14 +779,18 @@ impl DomainParticipantWeak {
   pub fn create_publisher(&self, qos: &QosPolicies) -> Result<Publisher> {
     match self.dpi.upgrade() {
       Some(dpi) => dpi.create_publisher(&self, qos),
-      None => Err(Error::OutOfResources),
+      None => Err(Error::out_of_resources(
+        "DomainParticipant has been dropped",
+      )),
     }
   }
 
   pub fn create_subscriber<'a>(&self, qos: &QosPolicies) -> Result<Subscriber> {
     match self.dpi.upgrade() {
       Some(dpi) => dpi.create_subscriber(&self, qos),
-      None => Err(Error::OutOfResources),
+      None => Err(Error::out_of_resources(
+        "DomainParticipant has been dropped",
+      )),
     }
   }
 
@@ -272,7 +803,9 @@ impl DomainParticipantWeak {
   ) -> Result<Topic> {
     match self.dpi.upgrade() {
       Some(dpi) => dpi.create_topic(&self, name, type_desc, qos, topic_kind),
-      None => Err(Error::OutOfResources),
+      None => Err(Error::out_of_resources(
+        "DomainParticipant has been dropped",
+      )),
     }
   }
 
@@ -318,18 +851,37 @@ pub(crate) struct DomainParticipant_Disc {
   discovery_updated_sender: Option<mio_channel::SyncSender<DiscoveryNotificationType>>,
   discovery_command_receiver: Option<mio_channel::Receiver<DiscoveryCommand>>,
   discovery_command_channel: mio_channel::SyncSender<DiscoveryCommand>,
-  discovery_join_handle: mio_channel::Receiver<JoinHandle<()>>,
+  discovery_join_handle: mio_channel::Receiver<ParticipantThread>,
+  shut_down: AtomicBool,
+  name: String,
+  qos_profile: Option<QosProfile>,
 }
 
 impl DomainParticipant_Disc {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     domain_id: u16,
-    discovery_join_handle: mio_channel::Receiver<JoinHandle<()>>,
-  ) -> DomainParticipant_Disc {
+    participant_id: Option<u16>,
+    max_participant_id: u16,
+    discovery_config: &DiscoveryConfig,
+    spdp_peers: LocatorList,
+    network: LocalNetwork,
+    clock: SharedClock,
+    discovery_join_handle: mio_channel::Receiver<ParticipantThread>,
+  ) -> Result<DomainParticipant_Disc> {
     let (discovery_update_notification_sender, discovery_update_notification_receiver) =
       mio_channel::sync_channel::<DiscoveryNotificationType>(100);
 
-    let dpi = DomainParticipant_Inner::new(domain_id, discovery_update_notification_receiver);
+    let dpi = DomainParticipant_Inner::new(
+      domain_id,
+      participant_id,
+      max_participant_id,
+      discovery_config,
+      spdp_peers,
+      network,
+      clock,
+      discovery_update_notification_receiver,
+    )?;
 
     let dpi_arc = Arc::new(dpi);
 
@@ -342,9 +894,53 @@ impl DomainParticipant_Disc {
       discovery_command_receiver: Some(discovery_command_receiver),
       discovery_command_channel: discovery_command_sender,
       discovery_join_handle,
+      shut_down: AtomicBool::new(false),
+      name: String::new(),
+      qos_profile: None,
     };
 
-    dpd
+    Ok(dpd)
+  }
+
+  fn send_discovery_command(&self, command: DiscoveryCommand) -> Result<()> {
+    match self.discovery_command_channel.send(command) {
+      Ok(_) => Ok(()),
+      Err(_) => Err(Error::precondition_not_met(
+        "cannot send a command to Discovery, it has stopped",
+      )),
+    }
+  }
+
+  pub fn add_static_remote_writer(&self, endpoint: &StaticEndpoint) -> Result<()> {
+    let data = endpoint.to_writer_data();
+    {
+      let mut db = write_lock(&self.discovery_db);
+      if db.is_ignored_publication(endpoint.guid) {
+        return Err(Error::precondition_not_met(format!(
+          "static remote writer {:?} is ignored",
+          endpoint.guid
+        )));
+      }
+      db.update_publication(&data);
+      db.update_topic_data_dwd(&data);
+    }
+    self.send_discovery_command(DiscoveryCommand::STATIC_ENDPOINTS_ADDED)
+  }
+
+  pub fn add_static_remote_reader(&self, endpoint: &StaticEndpoint) -> Result<()> {
+    let data = endpoint.to_reader_data();
+    {
+      let mut db = write_lock(&self.discovery_db);
+      if db.is_ignored_subscription(endpoint.guid) {
+        return Err(Error::precondition_not_met(format!(
+          "static remote reader {:?} is ignored",
+          endpoint.guid
+        )));
+      }
+      db.update_subscription(&data);
+      db.update_topic_data_drd(&data);
+    }
+    self.send_discovery_command(DiscoveryCommand::STATIC_ENDPOINTS_ADDED)
   }
 
   pub fn create_publisher(
@@ -352,7 +948,12 @@ impl DomainParticipant_Disc {
     dp: &DomainParticipantWeak,
     qos: &QosPolicies,
   ) -> Result<Publisher> {
-    self.dpi.create_publisher(&dp, qos)
+    self.check_not_shut_down()?;
+    let mut publisher = self.dpi.create_publisher(&dp, qos)?;
+    if let Some(profile) = &self.qos_profile {
+      publisher.set_default_datawriter_qos(&profile.datawriter_qos);
+    }
+    Ok(publisher)
   }
 
   pub fn create_subscriber<'a>(
@@ -360,6 +961,7 @@ impl DomainParticipant_Disc {
     dp: &DomainParticipantWeak,
     qos: &QosPolicies,
   ) -> Result<Subscriber> {
+    self.check_not_shut_down()?;
     self.dpi.create_subscriber(&dp, qos)
   }
 
@@ -371,9 +973,49 @@ impl DomainParticipant_Disc {
     qos: &QosPolicies,
     topic_kind: TopicKind,
   ) -> Result<Topic> {
+    self.check_not_shut_down()?;
     self.dpi.create_topic(&dp, name, type_desc, qos, topic_kind)
   }
 
+  pub fn create_topic_for<D: DdsType>(
+    &self,
+    dp: &DomainParticipantWeak,
+    name: &str,
+    qos: &QosPolicies,
+    topic_kind: TopicKind,
+  ) -> Result<Topic> {
+    self.check_not_shut_down()?;
+    self.dpi.create_topic_for::<D>(&dp, name, qos, topic_kind)
+  }
+
+  pub fn find_topic(
+    &self,
+    dp: &DomainParticipantWeak,
+    name: &str,
+    timeout: Duration,
+  ) -> Result<Option<Topic>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+      if let Some(topic) = self.lookup_topicdescription(dp, name)? {
+        return Ok(Some(topic));
+      }
+      let now = Instant::now();
+      if now >= deadline {
+        return Ok(None);
+      }
+      thread::sleep(FIND_TOPIC_POLL_INTERVAL.min(deadline - now));
+    }
+  }
+
+  pub fn lookup_topicdescription(
+    &self,
+    dp: &DomainParticipantWeak,
+    name: &str,
+  ) -> Result<Option<Topic>> {
+    self.check_not_shut_down()?;
+    Ok(self.dpi.lookup_topicdescription(dp, name))
+  }
+
   pub fn domain_id(&self) -> u16 {
     self.dpi.domain_id()
   }
@@ -381,41 +1023,239 @@ impl DomainParticipant_Disc {
   pub fn participant_id(&self) -> u16 {
     self.dpi.participant_id()
   }
-}
 
-impl Deref for DomainParticipant_Disc {
-  type Target = DomainParticipant_Inner;
-  fn deref(&self) -> &Self::Target {
-    &self.dpi
+  fn check_not_shut_down(&self) -> Result<()> {
+    if self.shut_down.load(Ordering::SeqCst) {
+      return Err(Error::precondition_not_met(
+        "DomainParticipant is shut down",
+      ));
+    }
+    Ok(())
   }
-}
 
-impl Drop for DomainParticipant_Disc {
-  fn drop(&mut self) {
-    debug!("Sending Discovery Stop signal.");
-    match self
-      .discovery_command_channel
-      .send(DiscoveryCommand::STOP_DISCOVERY)
-    {
-      Ok(_) => (),
-      _ => {
-        warn!("Failed to send stop signal to Discovery");
-        return;
+  // Deletes one of our DataWriters: Discovery disposes its announcement, the event loop
+  // removes its Writer, and DDSCache the topic if nothing else uses it.
+  // PreconditionNotMet if it is already deleted.
+  pub fn delete_local_writer(&self, guid: GUID) -> Result<()> {
+    let removed = write_lock(&self.discovery_db).remove_local_topic_writer(guid);
+    let topic_name = match removed {
+      Some(dwd) => dwd.publication_topic_data.topic_name,
+      None => {
+        return Err(Error::precondition_not_met(format!(
+          "DataWriter {:?} is already deleted",
+          guid
+        )))
       }
+    };
+    let disposed = self.send_discovery_command(DiscoveryCommand::REMOVE_LOCAL_WRITER { guid });
+    if self.remove_writer_sender.send(guid).is_err() {
+      warn!("Failed to remove Writer {:?} from the event loop.", guid);
     }
+    if let Some(topic_name) = topic_name {
+      self.release_topic(&topic_name, guid);
+    }
+    disposed
+  }
 
-    debug!("Waiting for Discovery join.");
-    if let Ok(handle) = self.discovery_join_handle.try_recv() {
-      handle.join().unwrap();
-      debug!("Joined Discovery.");
+  // Like delete_local_writer, but for DataReaders.
+  pub fn delete_local_reader(&self, guid: GUID) -> Result<()> {
+    let removed = write_lock(&self.discovery_db).remove_local_topic_reader(guid);
+    let topic_name = match removed {
+      Some(drd) => drd.subscription_topic_data.topic_name().clone(),
+      None => {
+        return Err(Error::precondition_not_met(format!(
+          "DataReader {:?} is already deleted",
+          guid
+        )))
+      }
+    };
+    // Builtin DataReaders are not announced, so there is nothing to dispose.
+    let disposed = if guid.entityId.is_builtin() {
+      Ok(())
+    } else {
+      self.send_discovery_command(DiscoveryCommand::REMOVE_LOCAL_READER { guid })
+    };
+    if self.sender_remove_reader.send(guid).is_err() {
+      warn!("Failed to remove Reader {:?} from the event loop.", guid);
     }
+    if let Some(topic_name) = topic_name {
+      self.release_topic(&topic_name, guid);
+    }
+    disposed
   }
-}
 
-// This is the actual working DomainParticipant.
-pub(crate) struct DomainParticipant_Inner {
-  domain_id: u16,
-  participant_id: u16,
+  fn release_topic(&self, topic_name: &String, user: GUID) {
+    if write_lock(&self.dds_cache).remove_topic_user(topic_name, user) {
+      debug!("Removed topic {} from DDSCache.", topic_name);
+      self.forget_topic(topic_name);
+    }
+  }
+
+  // The last clone of a Topic was dropped.
+  pub fn release_topic_handle(&self, topic_name: &str) {
+    if write_lock(&self.dds_cache).remove_topic_handle(topic_name) {
+      debug!("Removed topic {} from DDSCache.", topic_name);
+      self.forget_topic(topic_name);
+    }
+  }
+
+  // Stops announcing a topic that we no longer use, and disposes the announcement so
+  // that remote participants forget it too.
+  fn forget_topic(&self, topic_name: &str) {
+    let key = write_lock(&self.discovery_db).remove_local_topic(topic_name);
+    if let Some(key) = key {
+      if let Err(e) = self.send_discovery_command(DiscoveryCommand::REMOVE_LOCAL_TOPIC { key }) {
+        debug!("Topic {} is not disposed. {:?}", topic_name, e);
+      }
+    }
+  }
+
+  pub fn delete_topic(&self, topic_name: &str) -> Result<()> {
+    {
+      let mut cache = write_lock(&self.dds_cache);
+      if cache.topic_has_users(topic_name) {
+        return Err(Error::precondition_not_met(format!(
+          "topic {} is still used by some DataWriter or DataReader",
+          topic_name
+        )));
+      }
+      cache.remove_topic(&topic_name.to_string());
+    }
+    self.forget_topic(topic_name);
+    Ok(())
+  }
+
+  // The builtin endpoints of Discovery are not deleted, they go when it stops.
+  pub fn delete_contained_entities(&self) -> Result<()> {
+    let (writers, readers): (Vec<GUID>, Vec<GUID>) = {
+      let db = read_lock(&self.discovery_db);
+      (
+        db.get_all_local_topic_writers()
+          .filter_map(|w| w.writer_proxy.remote_writer_guid)
+          .filter(|guid| !guid.entityId.is_builtin())
+          .collect(),
+        db.get_all_local_topic_readers()
+          .filter_map(|r| r.reader_proxy.remote_reader_guid)
+          .filter(|guid| !guid.entityId.is_builtin())
+          .collect(),
+      )
+    };
+    let mut result = Ok(());
+    for guid in writers {
+      if let Err(e) = self.delete_local_writer(guid) {
+        result = Err(e);
+      }
+    }
+    for guid in readers {
+      if let Err(e) = self.delete_local_reader(guid) {
+        result = Err(e);
+      }
+    }
+    result
+  }
+
+  // Stops Discovery, which disposes our participant and endpoints on its way out, and
+  // then the event loop, which sends those disposes before it stops.
+  pub fn shutdown(&self) -> Result<()> {
+    if self.shut_down.swap(true, Ordering::SeqCst) {
+      return Ok(());
+    }
+    let mut all_stopped = true;
+
+    self.delete_contained_entities().unwrap_or(());
+
+    debug!("Sending Discovery Stop signal.");
+    match self
+      .discovery_command_channel
+      .send(DiscoveryCommand::STOP_DISCOVERY)
+    {
+      Ok(_) => {
+        debug!("Waiting for Discovery join.");
+        if let Ok(discovery_thread) = self.discovery_join_handle.try_recv() {
+          all_stopped &= discovery_thread.join(THREAD_STOP_TIMEOUT);
+        }
+      }
+      _ => warn!("Failed to send stop signal to Discovery"),
+    }
+
+    all_stopped &= self.dpi.shutdown();
+    if all_stopped {
+      Ok(())
+    } else {
+      Err(Error::out_of_resources(format!(
+        "threads of the DomainParticipant did not stop within {:?}",
+        THREAD_STOP_TIMEOUT
+      )))
+    }
+  }
+}
+
+impl Deref for DomainParticipant_Disc {
+  type Target = DomainParticipant_Inner;
+  fn deref(&self) -> &Self::Target {
+    &self.dpi
+  }
+}
+
+impl Drop for DomainParticipant_Disc {
+  fn drop(&mut self) {
+    self.shutdown().unwrap_or(());
+  }
+}
+
+// A background thread of a participant, which can be joined with a timeout.
+pub(crate) struct ParticipantThread {
+  name: &'static str,
+  handle: JoinHandle<()>,
+  // disconnects when the thread exits
+  finished: std::sync::mpsc::Receiver<()>,
+}
+
+impl ParticipantThread {
+  fn spawn<F>(name: &'static str, f: F) -> ParticipantThread
+  where
+    F: FnOnce() + Send + 'static,
+  {
+    let (finished_sender, finished) = std::sync::mpsc::channel::<()>();
+    let handle = thread::spawn(move || {
+      let _finished = finished_sender;
+      f()
+    });
+    ParticipantThread {
+      name,
+      handle,
+      finished,
+    }
+  }
+
+  // Returns false if the thread did not exit within the timeout. It is then left
+  // running, as is the thread itself if it is the one dropping the participant.
+  fn join(self, timeout: Duration) -> bool {
+    if self.handle.thread().id() == thread::current().id() {
+      debug!("{} is stopping its own participant.", self.name);
+      return true;
+    }
+    match self.finished.recv_timeout(timeout) {
+      Err(RecvTimeoutError::Disconnected) => {
+        match self.handle.join() {
+          Ok(_) => debug!("Joined {}.", self.name),
+          Err(_) => warn!("{} thread panicked.", self.name),
+        }
+        true
+      }
+      _ => {
+        warn!("{} did not stop within {:?}.", self.name, timeout);
+        false
+      }
+    }
+  }
+}
+
+// This is the actual working DomainParticipant.
+pub(crate) struct DomainParticipant_Inner {
+  domain_id: u16,
+  participant_id: u16,
+  network: LocalNetwork,
 
   entity_attributes: EntityAttributes,
   reader_binds: HashMap<Token, mio_channel::Receiver<(Token, Reader)>>,
@@ -423,6 +1263,8 @@ pub(crate) struct DomainParticipant_Inner {
   // Adding Readers
   sender_add_reader: mio_channel::SyncSender<Reader>,
   sender_remove_reader: mio_channel::SyncSender<GUID>,
+  // Additional DataReaders of existing Readers
+  sender_add_reader_listener: mio_channel::SyncSender<(GUID, ReaderListener)>,
 
   // Adding DataReaders
   sender_add_datareader_vec: Vec<mio_channel::SyncSender<()>>,
@@ -430,7 +1272,7 @@ pub(crate) struct DomainParticipant_Inner {
 
   // dp_event_wrapper control
   stop_poll_sender: mio_channel::Sender<()>,
-  ev_loop_handle: Option<JoinHandle<()>>,
+  ev_loop_handle: Mutex<Option<ParticipantThread>>,
 
   // Writers
   add_writer_sender: mio_channel::SyncSender<Writer>,
@@ -438,131 +1280,197 @@ pub(crate) struct DomainParticipant_Inner {
 
   dds_cache: Arc<RwLock<DDSCache>>,
   discovery_db: Arc<RwLock<DiscoveryDB>>,
+  // protocol statistics of our Writers and Readers
+  statistics: StatisticsRegistry,
+  // what our sockets send and receive, while capturing
+  capture: PacketCapture,
+  // DDS type names of create_topic_for, and the Rust types they are registered for
+  registered_types: Mutex<HashMap<String, &'static str>>,
 }
 
 impl Drop for DomainParticipant_Inner {
   fn drop(&mut self) {
-    // if send has an error simply leave as we have lost control of the ev_loop_thread anyways
-    match self.stop_poll_sender.send(()) {
-      Ok(_) => (),
-      _ => return (),
-    };
-
-    debug!("Waiting for EvLoop join");
-    // handle should always exist
-    // ignoring errors on join
-    match self.ev_loop_handle.take().unwrap().join() {
-      Ok(s) => s,
-      _ => (),
-    };
-    debug!("Joined EvLoop");
+    self.shutdown();
+    self.capture.stop();
   }
 }
 
-#[allow(clippy::new_without_default)]
-impl DomainParticipant_Inner {
-  fn new(
-    domain_id: u16,
-    discovery_update_notification_receiver: mio_channel::Receiver<DiscoveryNotificationType>,
-  ) -> DomainParticipant_Inner {
-    let mut listeners = HashMap::new();
-
-    // Creating UPD listeners for participantId 0 (change this if necessary)
-    let discovery_multicast_listener = UDPListener::try_bind(
-      DISCOVERY_SENDER_TOKEN,
-      "0.0.0.0",
-      get_spdp_well_known_multicast_port(domain_id),
-    );
-
-    match discovery_multicast_listener {
-      Some(ls) => match ls.join_multicast(&Ipv4Addr::new(239, 255, 0, 1)) {
-        Ok(_) => {
-          listeners.insert(DISCOVERY_MUL_LISTENER_TOKEN, ls);
-        }
-        _ => {
-          warn!("Cannot join multicast, possibly another instance running on this machine.");
-        }
-      },
-      None => {
-        warn!("Cannot join multicast, possibly another instance running on this machine.");
-      }
-    };
+// The SPDP and user traffic unicast sockets of one participant id. Fails if either
+// port is taken.
+fn bind_unicast_listeners(
+  domain_id: u16,
+  participant_id: u16,
+  unicast_host: &str,
+) -> Result<(UDPListener, UDPListener)> {
+  let port = get_spdp_well_known_unicast_port(domain_id, participant_id);
+  let discovery_listener = UDPListener::try_bind(DISCOVERY_SENDER_TOKEN, unicast_host, port)
+    .map_err(|e| {
+      Error::io(
+        format!(
+          "failed to bind SPDP unicast socket {}:{} of participant id {} in domain {}",
+          unicast_host, port, participant_id, domain_id
+        ),
+        e,
+      )
+    })?;
+  let port = get_user_traffic_unicast_port(domain_id, participant_id);
+  let user_traffic_listener = UDPListener::try_bind(USER_TRAFFIC_SENDER_TOKEN, unicast_host, port)
+    .map_err(|e| {
+      Error::io(
+        format!(
+          "failed to bind user traffic unicast socket {}:{} of participant id {} in domain {}",
+          unicast_host, port, participant_id, domain_id
+        ),
+        e,
+      )
+    })?;
+  Ok((discovery_listener, user_traffic_listener))
+}
 
-    let mut participant_id = 0;
+// Binds the discovery and user traffic sockets of a participant, with the given or
+// else the first participant id up to max_participant_id whose unicast ports are both
+// free. Returns the sockets by event loop token, and the participant id.
+fn create_listeners(
+  domain_id: u16,
+  requested_participant_id: Option<u16>,
+  max_participant_id: u16,
+  network: &LocalNetwork,
+) -> Result<(HashMap<Token, UDPListener>, u16)> {
+  let mut listeners = HashMap::new();
+  let multicast_host = network.multicast_bind_address().to_string();
+  let unicast_host = network.unicast_bind_address().to_string();
 
-    let mut discovery_listener = None;
+  // The multicast ports are the same for all participants of the domain, so they share
+  // them with the other participants on this host. A listener is kept even if it cannot
+  // join its groups now, e.g. when the network is down: the event loop tries again.
+  let discovery_multicast_listener = UDPListener::try_bind_shared(
+    DISCOVERY_SENDER_TOKEN,
+    &multicast_host,
+    get_spdp_well_known_multicast_port(domain_id),
+  );
 
-    while discovery_listener.is_none() {
-      discovery_listener = UDPListener::try_bind(
-        DISCOVERY_SENDER_TOKEN,
-        "0.0.0.0",
-        get_spdp_well_known_unicast_port(domain_id, participant_id),
-      );
-      if discovery_listener.is_none() {
-        participant_id += 1;
+  match discovery_multicast_listener {
+    Ok(ls) => {
+      if let Err(e) = network.join_multicast(&ls) {
+        warn!("Cannot join SPDP multicast yet. {}", e);
       }
+      listeners.insert(DISCOVERY_MUL_LISTENER_TOKEN, ls);
     }
+    Err(e) => {
+      warn!("Cannot bind SPDP multicast socket. {}", e);
+    }
+  };
 
-    info!("ParticipantId {} selected.", participant_id);
+  let user_traffic_multicast_listener = UDPListener::try_bind_shared(
+    USER_TRAFFIC_SENDER_TOKEN,
+    &multicast_host,
+    get_user_traffic_multicast_port(domain_id),
+  );
 
-    // let discovery_listener = UDPListener::new(
-    //   DISCOVERY_SENDER_TOKEN,
-    //   "0.0.0.0",
-    //   get_spdp_well_known_unicast_port(domain_id, participant_id),
-    // );
-    let discovery_listener = match discovery_listener {
-      Some(dl) => dl,
-      None => panic!("Could not find free ParticipantId"),
-    };
+  match user_traffic_multicast_listener {
+    Ok(ls) => {
+      if let Err(e) = network.join_multicast(&ls) {
+        info!("Cannot join user traffic multicast yet. {}", e);
+      }
+      listeners.insert(USER_TRAFFIC_MUL_LISTENER_TOKEN, ls);
+    }
+    Err(e) => {
+      info!("Cannot bind user traffic multicast socket. {}", e);
+    }
+  };
 
-    let user_traffic_multicast_listener = UDPListener::try_bind(
-      USER_TRAFFIC_SENDER_TOKEN,
-      "0.0.0.0",
-      get_user_traffic_multicast_port(domain_id),
-    );
+  let mut participant_id = requested_participant_id.unwrap_or(0);
 
-    match user_traffic_multicast_listener {
-      Some(ls) => match ls.join_multicast(&Ipv4Addr::new(239, 255, 0, 1)) {
-        Ok(_) => {
-          listeners.insert(USER_TRAFFIC_MUL_LISTENER_TOKEN, ls);
-        }
-        _ => {
-          info!("Cannot join multicast, possibly another instance running on this machine.");
-        }
-      },
-      None => {
-        info!("Cannot join multicast, possibly another instance running on this machine.");
+  let (discovery_listener, user_traffic_listener) = loop {
+    match bind_unicast_listeners(domain_id, participant_id, &unicast_host) {
+      Ok(listeners) => break listeners,
+      Err(e) if requested_participant_id.is_none() && participant_id < max_participant_id => {
+        debug!("Participant id {} is not available. {}", participant_id, e);
+        participant_id += 1;
       }
-    };
+      Err(Error::Io { reason, source }) if requested_participant_id.is_none() => {
+        return Err(Error::io(
+          format!(
+            "no free participant id from 0 to {} in domain {}, last {}",
+            max_participant_id, domain_id, reason
+          ),
+          source,
+        ));
+      }
+      Err(e) => return Err(e),
+    }
+  };
 
-    let user_traffic_listener = UDPListener::new(
-      USER_TRAFFIC_SENDER_TOKEN,
-      "0.0.0.0",
-      get_user_traffic_unicast_port(domain_id, participant_id),
-    );
+  info!("ParticipantId {} selected.", participant_id);
 
-    listeners.insert(DISCOVERY_LISTENER_TOKEN, discovery_listener);
+  listeners.insert(DISCOVERY_LISTENER_TOKEN, discovery_listener);
 
-    listeners.insert(USER_TRAFFIC_LISTENER_TOKEN, user_traffic_listener);
+  listeners.insert(USER_TRAFFIC_LISTENER_TOKEN, user_traffic_listener);
+
+  for listener in listeners.values() {
+    if let Err(e) = network.configure_listener(listener) {
+      warn!(
+        "Cannot configure socket of port {}. {:?}",
+        listener.port(),
+        e
+      );
+    }
+  }
+  Ok((listeners, participant_id))
+}
+
+#[allow(clippy::new_without_default)]
+impl DomainParticipant_Inner {
+  #[allow(clippy::too_many_arguments)]
+  fn new(
+    domain_id: u16,
+    requested_participant_id: Option<u16>,
+    max_participant_id: u16,
+    discovery_config: &DiscoveryConfig,
+    spdp_peers: LocatorList,
+    network: LocalNetwork,
+    clock: SharedClock,
+    discovery_update_notification_receiver: mio_channel::Receiver<DiscoveryNotificationType>,
+  ) -> Result<DomainParticipant_Inner> {
+    let (listeners, participant_id) = create_listeners(
+      domain_id,
+      requested_participant_id,
+      max_participant_id,
+      &network,
+    )?;
 
     // Adding readers
     let (sender_add_reader, receiver_add_reader) = mio_channel::sync_channel::<Reader>(100);
     let (sender_remove_reader, receiver_remove_reader) = mio_channel::sync_channel::<GUID>(10);
+    let (sender_add_reader_listener, receiver_add_reader_listener) =
+      mio_channel::sync_channel::<(GUID, ReaderListener)>(10);
 
     // Writers
     let (add_writer_sender, add_writer_receiver) = mio_channel::sync_channel::<Writer>(10);
     let (remove_writer_sender, remove_writer_receiver) = mio_channel::sync_channel::<GUID>(10);
 
     let new_guid = GUID::new();
+    let capture = PacketCapture::default();
+    let statistics = StatisticsRegistry::default();
     let domain_info = DomainInfo {
       domain_participant_guid: new_guid,
       domain_id,
       participant_id,
+      spdp_multicast: discovery_config.spdp_multicast,
+      spdp_peers,
+      network: network.clone(),
+      intra_process_delivery: discovery_config.intra_process_delivery,
+      capture: capture.clone(),
+      multicast_rejoin_period: discovery_config.multicast_rejoin_period,
+      network_counters: statistics.network(),
     };
 
-    let a_r_cache = Arc::new(RwLock::new(DDSCache::new()));
+    let a_r_cache = Arc::new(RwLock::new(DDSCache::with_clock(clock.clone())));
 
-    let discovery_db = Arc::new(RwLock::new(DiscoveryDB::new()));
+    let mut db = DiscoveryDB::with_clock(clock);
+    db.set_shm_namespace(network.shm_namespace());
+    db.set_transports(network.udp_enabled(), network.tcp().is_some());
+    let discovery_db = Arc::new(RwLock::new(db));
 
     let (stop_poll_sender, stop_poll_receiver) = mio_channel::channel::<()>();
 
@@ -580,6 +1488,10 @@ impl DomainParticipant_Inner {
         token: REMOVE_READER_TOKEN,
         receiver: receiver_remove_reader,
       },
+      TokenReceiverPair {
+        token: ADD_READER_LISTENER_TOKEN,
+        receiver: receiver_add_reader_listener,
+      },
       TokenReceiverPair {
         token: ADD_WRITER_TOKEN,
         receiver: add_writer_receiver,
@@ -592,33 +1504,62 @@ impl DomainParticipant_Inner {
       discovery_update_notification_receiver,
     );
     // Launch the background thread for DomainParticipant
-    let ev_loop_handle = thread::spawn(move || ev_wrapper.event_loop());
+    let ev_loop_handle = ParticipantThread::spawn("EvLoop", move || ev_wrapper.event_loop());
 
-    DomainParticipant_Inner {
+    Ok(DomainParticipant_Inner {
       domain_id,
       participant_id,
+      network,
       entity_attributes: EntityAttributes { guid: new_guid },
       reader_binds: HashMap::new(),
-      //ddscache: a_r_cache,
       // Adding readers
       sender_add_reader,
       sender_remove_reader,
+      sender_add_reader_listener,
       // Adding datareaders
       sender_add_datareader_vec: Vec::new(),
       sender_remove_datareader_vec: Vec::new(),
       stop_poll_sender,
-      ev_loop_handle: Some(ev_loop_handle),
+      ev_loop_handle: Mutex::new(Some(ev_loop_handle)),
       add_writer_sender,
       remove_writer_sender,
-      dds_cache: Arc::new(RwLock::new(DDSCache::new())),
+      // the same cache as the event loop, which collects its garbage
+      dds_cache: a_r_cache,
       discovery_db: discovery_db,
-    }
+      statistics,
+      capture,
+      registered_types: Mutex::new(HashMap::new()),
+    })
   }
 
   pub fn get_dds_cache(&self) -> Arc<RwLock<DDSCache>> {
     return self.dds_cache.clone();
   }
 
+  // Stops the event loop, which closes the sockets. Returns false if it did not stop
+  // in time.
+  fn shutdown(&self) -> bool {
+    // This runs in Drop, possibly while unwinding, so a poisoned lock must not panic.
+    // Taking the handle cannot have been left half done.
+    let ev_loop = self
+      .ev_loop_handle
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+      .take();
+    let ev_loop = match ev_loop {
+      Some(ev_loop) => ev_loop,
+      None => return true, // already stopped
+    };
+    // a send error means that the event loop has already exited
+    self.stop_poll_sender.send(()).unwrap_or(());
+    debug!("Waiting for EvLoop join");
+    ev_loop.join(THREAD_STOP_TIMEOUT)
+  }
+
+  pub fn cache_statistics(&self) -> CacheStatistics {
+    read_lock(&self.dds_cache).statistics()
+  }
+
   pub fn add_reader(&self, reader: Reader) {
     self.sender_add_reader.send(reader).unwrap();
   }
@@ -656,7 +1597,11 @@ impl DomainParticipant_Inner {
         dpi.get_add_writer_sender(),
         dpi.discovery_command_channel.clone(),
       ),
-      None => return Err(Error::OutOfResources),
+      None => {
+        return Err(Error::out_of_resources(
+          "DomainParticipant has been dropped",
+        ))
+      }
     };
 
     Ok(Publisher::new(
@@ -676,7 +1621,11 @@ impl DomainParticipant_Inner {
   ) -> Result<Subscriber> {
     let discovery_command = match domain_participant.dpi.upgrade() {
       Some(dpi) => dpi.discovery_command_channel.clone(),
-      None => return Err(Error::OutOfResources),
+      None => {
+        return Err(Error::out_of_resources(
+          "DomainParticipant has been dropped",
+        ))
+      }
     };
 
     Ok(Subscriber::new(
@@ -701,28 +1650,106 @@ impl DomainParticipant_Inner {
     qos: &QosPolicies,
     topic_kind: TopicKind,
   ) -> Result<Topic> {
-    let topic = Topic::new(
+    let topic = self.new_topic(
       domain_participant,
-      name.to_string(),
+      name,
       TypeDesc::new(type_desc.to_string()),
       &qos,
       topic_kind,
+      None,
     );
     Ok(topic)
 
     // TODO: refine
   }
 
+  // A topic of D, whose type name D gives. The type name is registered for D, so that
+  // it cannot be used for another type in this participant.
+  pub fn create_topic_for<D: DdsType>(
+    &self,
+    domain_participant: &DomainParticipantWeak,
+    name: &str,
+    qos: &QosPolicies,
+    topic_kind: TopicKind,
+  ) -> Result<Topic> {
+    let data_type = std::any::type_name::<D>();
+    {
+      let mut registered_types = self
+        .registered_types
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+      let registered = registered_types
+        .entry(D::type_name().to_string())
+        .or_insert(data_type);
+      if *registered != data_type {
+        return Err(Error::bad_parameter(format!(
+          "type name {} is already registered for {}, not {}",
+          D::type_name(),
+          registered,
+          data_type
+        )));
+      }
+    }
+    Ok(self.new_topic(
+      domain_participant,
+      name,
+      TypeDesc::new(D::type_name().to_string()),
+      qos,
+      topic_kind,
+      Some(data_type),
+    ))
+  }
+
+  // Topic handles are counted, so that the topic is released with the last one.
+  fn new_topic(
+    &self,
+    domain_participant: &DomainParticipantWeak,
+    name: &str,
+    type_desc: TypeDesc,
+    qos: &QosPolicies,
+    topic_kind: TopicKind,
+    data_type: Option<&'static str>,
+  ) -> Topic {
+    write_lock(&self.dds_cache).add_topic_handle(name);
+    Topic::new(
+      domain_participant,
+      name.to_string(),
+      type_desc,
+      qos,
+      topic_kind,
+      data_type,
+    )
+  }
+
   // Do not implement contentfilteredtopics or multitopics (yet)
 
-  pub fn find_topic(self, _name: &str, _timeout: Duration) -> Result<Topic> {
-    unimplemented!()
+  // A Topic of a topic known to DiscoveryDB, with its type name and QoS
+  pub fn lookup_topicdescription(
+    &self,
+    domain_participant: &DomainParticipantWeak,
+    name: &str,
+  ) -> Option<Topic> {
+    let (type_name, qos, topic_kind) = {
+      let db = self.discovery_db_read();
+      let topic_data = &db.get_topic(name)?.topic_data;
+      (
+        topic_data.type_name.clone().unwrap_or_default(),
+        topic_data.generate_qos(),
+        db.topic_kind(name).unwrap_or(TopicKind::WithKey),
+      )
+    };
+    Some(self.new_topic(
+      domain_participant,
+      name,
+      TypeDesc::new(type_name),
+      &qos,
+      topic_kind,
+      None,
+    ))
   }
 
   // get_builtin_subscriber (why would we need this?)
 
-  // ignore_* operations. TODO: Do we needa any of those?
-
   // delete_contained_entities is not needed. Data structures shoud be designed so that lifetime of all
   // created objects is within the lifetime of DomainParticipant. Then such deletion is implicit.
 
@@ -740,6 +1767,12 @@ impl DomainParticipant_Inner {
     self.sender_remove_reader.clone()
   }
 
+  pub(crate) fn get_add_reader_listener_sender(
+    &self,
+  ) -> mio_channel::SyncSender<(GUID, ReaderListener)> {
+    self.sender_add_reader_listener.clone()
+  }
+
   pub(crate) fn get_add_writer_sender(&self) -> mio_channel::SyncSender<Writer> {
     self.add_writer_sender.clone()
   }
@@ -756,14 +1789,52 @@ impl DomainParticipant_Inner {
     self.participant_id
   }
 
+  pub fn local_network(&self) -> LocalNetwork {
+    self.network.clone()
+  }
+
   pub fn get_discovered_topics(&self) -> Vec<DiscoveredTopicData> {
-    let db = match self.discovery_db.read() {
-      Ok(db) => db,
-      Err(e) => panic!("DiscoveryDB is poisoned. {:?}", e),
-    };
+    let db = read_lock(&self.discovery_db);
 
     db.get_all_topics().map(|p| p.clone()).collect()
   }
+
+  pub fn get_discovered_participants(&self) -> Vec<GuidPrefix> {
+    let own_prefix = self.get_guid().guidPrefix;
+    self
+      .discovery_db_read()
+      .get_participants()
+      .filter_map(|p| p.participant_guid)
+      .map(|g| g.guidPrefix)
+      .filter(|p| *p != own_prefix)
+      .collect()
+  }
+
+  pub fn get_discovered_participant_data(
+    &self,
+    guid_prefix: GuidPrefix,
+  ) -> Option<SPDPDiscoveredParticipantData> {
+    if guid_prefix == self.get_guid().guidPrefix {
+      return None;
+    }
+    self
+      .discovery_db_read()
+      .get_participant(guid_prefix)
+      .cloned()
+  }
+
+  pub fn get_discovered_topic_data(&self, topic_name: &str) -> Option<DiscoveredTopicData> {
+    self.discovery_db_read().get_topic(topic_name).cloned()
+  }
+
+  pub fn discovery_snapshot(&self) -> DiscoverySnapshot {
+    let own_prefix = self.get_guid().guidPrefix;
+    self.discovery_db_read().snapshot(own_prefix)
+  }
+
+  fn discovery_db_read(&self) -> RwLockReadGuard<DiscoveryDB> {
+    read_lock(&self.discovery_db)
+  }
 } // impl
 
 impl Entity for DomainParticipant {
@@ -798,7 +1869,7 @@ mod tests {
     test::random_data::RandomData,
     structure::{
       locator::{LocatorKind, Locator},
-      guid::{EntityId, GUID},
+      guid::{EntityId, GuidPrefix, GUID},
       sequence_number::{SequenceNumber, SequenceNumberSet},
     },
     messages::submessages::submessages::{
@@ -814,13 +1885,23 @@ mod tests {
   use super::DomainParticipant;
   use speedy::Endianness;
 
+  use crate::dds::{
+    readcondition::ReadCondition,
+    traits::{DdsType, Keyed, TopicDescription},
+    values::result::{Error, ReturnCode},
+  };
+  use serde::{Deserialize, Serialize};
+
   use crate::serialization::cdr_serializer::CDRSerializerAdapter;
+  use crate::serialization::cdr_deserializer::CDRDeserializerAdapter;
+  use crate::structure::entity::Entity;
   use byteorder::LittleEndian;
+  use std::{thread, time::Duration};
 
   // TODO: improve basic test when more or the structure is known
   #[test]
   fn dp_basic_domain_participant() {
-    // let _dp = DomainParticipant::new();
+    // let _dp = DomainParticipant::new().unwrap();
 
     let sender = UDPSender::new(11401);
     let data: Vec<u8> = vec![0, 1, 2, 3, 4];
@@ -832,7 +1913,7 @@ mod tests {
   }
   #[test]
   fn dp_writer_hearbeat_test() {
-    let domain_participant = DomainParticipant::new(0);
+    let domain_participant = DomainParticipant::new(0).unwrap();
     let qos = QosPolicies::qos_none();
     let _default_dw_qos = QosPolicies::qos_none();
     let publisher = domain_participant
@@ -853,7 +1934,7 @@ mod tests {
   #[test]
   fn dp_recieve_acknack_message_test() {
     // TODO SEND ACKNACK
-    let domain_participant = DomainParticipant::new(0);
+    let domain_participant = DomainParticipant::new(0).unwrap();
 
     let qos = QosPolicies::qos_none();
     let _default_dw_qos = QosPolicies::qos_none();
@@ -918,4 +1999,2099 @@ mod tests {
     let locas = vec![loca];
     _sender.send_to_locator_list(&_data, &locas);
   }
+
+  #[test]
+  fn dp_matched_status_two_participants() {
+    let qos = QosPolicies::qos_none();
+
+    // A domain of its own, so that endpoints of other tests are not matched.
+    let dp_sub = DomainParticipant::new(62).unwrap();
+    let sub_topic = dp_sub
+      .create_topic("MatchedTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let subscriber = dp_sub
+      .create_subscriber(&qos)
+      .expect("Failed to create subscriber");
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&sub_topic, None, None)
+      .expect("Failed to create datareader");
+
+    let status = data_reader.get_subscription_matched_status().unwrap();
+    assert_eq!(status.current_count(), 0);
+
+    let dp_pub = DomainParticipant::new(62).unwrap();
+    let pub_topic = dp_pub
+      .create_topic("MatchedTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let publisher = dp_pub
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &pub_topic, None,
+      )
+      .expect("Failed to create datawriter");
+    let writer_guid = data_writer.get_guid();
+
+    let mut current_count = 0;
+    for _ in 0..100 {
+      thread::sleep(Duration::from_millis(100));
+      let status = data_reader.get_subscription_matched_status().unwrap();
+      current_count = status.current_count();
+      if current_count == 1 {
+        assert_eq!(status.total_count(), 1);
+        assert_eq!(status.last_publication_handle(), Some(writer_guid));
+        break;
+      }
+    }
+    assert_eq!(current_count, 1);
+    assert!(data_reader
+      .get_matched_publications()
+      .contains(&writer_guid));
+    assert!(data_reader
+      .get_matched_publication_data(writer_guid)
+      .is_some());
+
+    drop(data_writer);
+
+    for _ in 0..100 {
+      thread::sleep(Duration::from_millis(100));
+      current_count = data_reader
+        .get_subscription_matched_status()
+        .unwrap()
+        .current_count();
+      if current_count == 0 {
+        break;
+      }
+    }
+    assert_eq!(current_count, 0);
+    assert!(data_reader.get_matched_publications().is_empty());
+  }
+
+  #[test]
+  fn dp_ignore_participant() {
+    let qos = QosPolicies::qos_none();
+
+    let dp_sub = DomainParticipant::new(0).unwrap();
+    let dp_pub = DomainParticipant::new(0).unwrap();
+    let ignored_prefix = dp_pub.get_guid().guidPrefix;
+    dp_sub.ignore_participant(ignored_prefix).unwrap();
+    assert!(matches!(
+      dp_sub.ignore_participant(dp_sub.get_guid().guidPrefix),
+      Err(Error::BadParameter { .. })
+    ));
+
+    let sub_topic = dp_sub
+      .create_topic("IgnoreTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let subscriber = dp_sub
+      .create_subscriber(&qos)
+      .expect("Failed to create subscriber");
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&sub_topic, None, None)
+      .expect("Failed to create datareader");
+
+    let pub_topic = dp_pub
+      .create_topic("IgnoreTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let publisher = dp_pub
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &pub_topic, None,
+      )
+      .expect("Failed to create datawriter");
+
+    // several SPDP and SEDP rounds
+    for i in 0..30 {
+      let data = RandomData {
+        a: i,
+        b: String::from("ignored"),
+      };
+      data_writer.write(data, None).unwrap();
+      thread::sleep(Duration::from_millis(200));
+    }
+
+    assert_eq!(
+      data_reader
+        .get_subscription_matched_status()
+        .unwrap()
+        .current_count(),
+      0
+    );
+    assert!(data_reader.get_matched_publications().is_empty());
+    assert!(data_reader
+      .take(100, ReadCondition::any())
+      .unwrap()
+      .is_empty());
+
+    let db = dp_sub.discovery_db();
+    let db = db.read().unwrap();
+    assert!(db
+      .get_participants()
+      .all(|p| p.participant_guid.map(|g| g.guidPrefix) != Some(ignored_prefix)));
+  }
+
+  #[test]
+  fn dp_new_participant_matches_before_sedp_period() {
+    use crate::discovery::discovery_config::DiscoveryConfig;
+    let qos = QosPolicies::qos_none();
+    // Periodic SEDP announcements alone would take far longer than we wait below.
+    let config = DiscoveryConfig {
+      send_readers_info_period: std::time::Duration::from_secs(60),
+      send_writers_info_period: std::time::Duration::from_secs(60),
+      ..DiscoveryConfig::default()
+    };
+
+    let dp_sub = DomainParticipant::with_discovery_config(0, config.clone()).unwrap();
+    let sub_topic = dp_sub
+      .create_topic("BurstTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let subscriber = dp_sub
+      .create_subscriber(&qos)
+      .expect("Failed to create subscriber");
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&sub_topic, None, None)
+      .expect("Failed to create datareader");
+
+    let dp_pub = DomainParticipant::with_discovery_config(0, config).unwrap();
+    let pub_topic = dp_pub
+      .create_topic("BurstTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let publisher = dp_pub
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let _data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &pub_topic, None,
+      )
+      .expect("Failed to create datawriter");
+
+    let mut current_count = 0;
+    for _ in 0..50 {
+      current_count = data_reader
+        .get_subscription_matched_status()
+        .unwrap()
+        .current_count();
+      if current_count > 0 {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(current_count, 1);
+  }
+
+  #[test]
+  fn dp_qos_mismatch_is_reported() {
+    use crate::dds::qos::{policy::Reliability, QosPolicyBuilder, QosPolicyId};
+    use crate::structure::duration::Duration as DDSDuration;
+    let reliable = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: DDSDuration::DURATION_ZERO,
+      })
+      .build();
+    let best_effort = QosPolicyBuilder::new()
+      .reliability(Reliability::BestEffort)
+      .build();
+
+    let dp_sub = DomainParticipant::new(0).unwrap();
+    let sub_topic = dp_sub
+      .create_topic("MismatchTest", "RandomData", &reliable, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let subscriber = dp_sub
+      .create_subscriber(&reliable)
+      .expect("Failed to create subscriber");
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&sub_topic, None, None)
+      .expect("Failed to create datareader");
+
+    let dp_pub = DomainParticipant::new(0).unwrap();
+    let pub_topic = dp_pub
+      .create_topic(
+        "MismatchTest",
+        "RandomData",
+        &best_effort,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+    let publisher = dp_pub
+      .create_publisher(&best_effort)
+      .expect("Failed to create publisher");
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &pub_topic, None,
+      )
+      .expect("Failed to create datawriter");
+
+    let mut reader_status = data_reader.get_requested_incompatible_qos_status().unwrap();
+    let mut writer_status = data_writer.get_offered_incompatible_qos_status().unwrap();
+    for _ in 0..100 {
+      if reader_status.count() > 0 && writer_status.count() > 0 {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+      reader_status = data_reader.get_requested_incompatible_qos_status().unwrap();
+      writer_status = data_writer.get_offered_incompatible_qos_status().unwrap();
+    }
+    assert!(reader_status.count() > 0);
+    assert_eq!(
+      reader_status.last_policy_id(),
+      Some(QosPolicyId::Reliability)
+    );
+    assert!(writer_status.count() > 0);
+    assert_eq!(
+      writer_status.last_policy_id(),
+      Some(QosPolicyId::Reliability)
+    );
+
+    // incompatible endpoints are never matched
+    assert_eq!(
+      data_reader
+        .get_subscription_matched_status()
+        .unwrap()
+        .current_count(),
+      0
+    );
+    assert_eq!(
+      data_writer
+        .get_publication_matched_status()
+        .unwrap()
+        .current_count(),
+      0
+    );
+  }
+
+  #[test]
+  fn dp_matched_endpoints_are_those_matched() {
+    use crate::dds::qos::{policy::Reliability, QosPolicyBuilder};
+    use crate::structure::duration::Duration as DDSDuration;
+    let reliable = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: DDSDuration::DURATION_ZERO,
+      })
+      .build();
+    let best_effort = QosPolicyBuilder::new()
+      .reliability(Reliability::BestEffort)
+      .build();
+
+    // A domain of its own, so that endpoints of other tests are not matched.
+    let dp_sub = DomainParticipant::new(64).unwrap();
+    let sub_topic = dp_sub
+      .create_topic(
+        "MatchedListTest",
+        "RandomData",
+        &reliable,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+    let subscriber = dp_sub
+      .create_subscriber(&reliable)
+      .expect("Failed to create subscriber");
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&sub_topic, None, None)
+      .expect("Failed to create datareader");
+    let reader_guid = data_reader.get_guid();
+    // a writer of our own participant, compatible with the reader
+    let local_publisher = dp_sub
+      .create_publisher(&reliable)
+      .expect("Failed to create publisher");
+    let local_writer = local_publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &sub_topic, None,
+      )
+      .expect("Failed to create datawriter");
+    let local_writer_guid = local_writer.get_guid();
+
+    // a remote writer that offers less than the reader requests
+    let dp_pub = DomainParticipant::new(64).unwrap();
+    let pub_topic = dp_pub
+      .create_topic(
+        "MatchedListTest",
+        "RandomData",
+        &best_effort,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+    let publisher = dp_pub
+      .create_publisher(&best_effort)
+      .expect("Failed to create publisher");
+    let remote_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &pub_topic, None,
+      )
+      .expect("Failed to create datawriter");
+    let remote_writer_guid = remote_writer.get_guid();
+
+    let mut reader_status = data_reader.get_requested_incompatible_qos_status().unwrap();
+    let mut writer_status = remote_writer.get_offered_incompatible_qos_status().unwrap();
+    for _ in 0..100 {
+      if reader_status.count() > 0
+        && writer_status.count() > 0
+        && data_reader.get_matched_publications().len() == 1
+        && local_writer.get_matched_subscriptions().len() == 1
+      {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+      reader_status = data_reader.get_requested_incompatible_qos_status().unwrap();
+      writer_status = remote_writer.get_offered_incompatible_qos_status().unwrap();
+    }
+    assert!(reader_status.count() > 0);
+    assert!(writer_status.count() > 0);
+
+    assert_eq!(
+      data_reader.get_matched_publications(),
+      vec![local_writer_guid]
+    );
+    assert!(data_reader
+      .get_matched_publication_data(local_writer_guid)
+      .is_some());
+    assert!(data_reader
+      .get_matched_publication_data(remote_writer_guid)
+      .is_none());
+    assert_eq!(
+      data_reader
+        .get_subscription_matched_status()
+        .unwrap()
+        .current_count(),
+      1
+    );
+
+    assert_eq!(local_writer.get_matched_subscriptions(), vec![reader_guid]);
+    assert!(local_writer
+      .get_matched_subscription_data(reader_guid)
+      .is_some());
+    assert!(remote_writer.get_matched_subscriptions().is_empty());
+    assert!(remote_writer
+      .get_matched_subscription_data(reader_guid)
+      .is_none());
+  }
+
+  #[test]
+  fn dp_static_discovery() {
+    use crate::discovery::{discovery_config::DiscoveryConfig, static_endpoint::StaticEndpoint};
+    use std::net::SocketAddr;
+    let qos = QosPolicies::qos_none();
+    let config = DiscoveryConfig {
+      sedp_enabled: false,
+      ..DiscoveryConfig::default()
+    };
+
+    let dp_sub = DomainParticipant::with_discovery_config(0, config.clone()).unwrap();
+    let sub_topic = dp_sub
+      .create_topic("StaticTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let subscriber = dp_sub
+      .create_subscriber(&qos)
+      .expect("Failed to create subscriber");
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&sub_topic, None, None)
+      .expect("Failed to create datareader");
+
+    let dp_pub = DomainParticipant::with_discovery_config(0, config).unwrap();
+    let pub_topic = dp_pub
+      .create_topic("StaticTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let publisher = dp_pub
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &pub_topic, None,
+      )
+      .expect("Failed to create datawriter");
+
+    // Describe each endpoint to the other participant, as a configuration would.
+    let reader_data = dp_sub
+      .discovery_db()
+      .read()
+      .unwrap()
+      .get_local_topic_readers(&sub_topic)[0]
+      .clone();
+    let writer_guid = data_writer.get_guid();
+    let writer_data = dp_pub
+      .discovery_db()
+      .read()
+      .unwrap()
+      .get_all_local_topic_writers()
+      .find(|w| w.writer_proxy.remote_writer_guid == Some(writer_guid))
+      .unwrap()
+      .clone();
+    let static_reader = StaticEndpoint {
+      guid: data_reader.get_guid(),
+      topic_name: String::from("StaticTest"),
+      type_name: String::from("RandomData"),
+      qos: qos.clone(),
+      unicast_locators: reader_data
+        .reader_proxy
+        .unicast_locator_list
+        .iter()
+        .map(|l| SocketAddr::from(*l))
+        .collect(),
+      multicast_locators: Vec::new(),
+    };
+    let static_writer = StaticEndpoint {
+      guid: writer_guid,
+      unicast_locators: writer_data
+        .writer_proxy
+        .unicast_locator_list
+        .iter()
+        .map(|l| SocketAddr::from(*l))
+        .collect(),
+      ..static_reader.clone()
+    };
+
+    // own endpoints cannot be added
+    assert!(matches!(
+      dp_pub.add_static_remote_writer(&static_writer),
+      Err(Error::BadParameter { .. })
+    ));
+
+    dp_pub.add_static_remote_reader(&static_reader).unwrap();
+    dp_sub.add_static_remote_writer(&static_writer).unwrap();
+
+    let mut reader_count = 0;
+    let mut writer_count = 0;
+    for _ in 0..50 {
+      reader_count = data_reader
+        .get_subscription_matched_status()
+        .unwrap()
+        .current_count();
+      writer_count = data_writer
+        .get_publication_matched_status()
+        .unwrap()
+        .current_count();
+      if reader_count > 0 && writer_count > 0 {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(reader_count, 1);
+    assert_eq!(writer_count, 1);
+    assert!(data_reader
+      .get_matched_publications()
+      .contains(&writer_guid));
+  }
+
+  #[test]
+  fn dp_discovered_participants_and_topics() {
+    let qos = QosPolicies::qos_none();
+
+    let dp = DomainParticipant::new(0).unwrap();
+    let remote = DomainParticipant::new(0).unwrap();
+    let remote_prefix = remote.get_guid().guidPrefix;
+
+    let topic = remote
+      .create_topic("DiscoveredTopic", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let publisher = remote
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let _data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .expect("Failed to create datawriter");
+
+    for _ in 0..50 {
+      if dp.get_discovered_participants().contains(&remote_prefix)
+        && dp.get_discovered_topic_data("DiscoveredTopic").is_some()
+      {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+
+    assert!(dp.get_discovered_participants().contains(&remote_prefix));
+    assert!(!dp
+      .get_discovered_participants()
+      .contains(&dp.get_guid().guidPrefix));
+    let data = dp
+      .get_discovered_participant_data(remote_prefix)
+      .expect("Remote participant not discovered");
+    assert_eq!(
+      data.participant_guid.map(|g| g.guidPrefix),
+      Some(remote_prefix)
+    );
+    assert!(dp
+      .get_discovered_participant_data(dp.get_guid().guidPrefix)
+      .is_none());
+
+    let topic_data = dp
+      .get_discovered_topic_data("DiscoveredTopic")
+      .expect("Remote topic not discovered");
+    assert_eq!(topic_data.get_type_name(), "RandomData");
+    assert!(dp.get_discovered_topic_data("NoSuchTopic").is_none());
+  }
+
+  #[test]
+  fn dp_ipv6_discovery() {
+    use crate::discovery::discovery_config::{DiscoveryConfig, IpFamily};
+    let config = DiscoveryConfig {
+      spdp_multicast: false,
+      initial_peers: vec![String::from("::1")],
+      ip_families: vec![IpFamily::V6],
+      ..DiscoveryConfig::default()
+    };
+    let dp_a = DomainParticipant::with_discovery_config(9, config.clone()).unwrap();
+    let dp_b = DomainParticipant::with_discovery_config(9, config).unwrap();
+    let prefix_b = dp_b.get_guid().guidPrefix;
+
+    for _ in 0..50 {
+      if dp_a.get_discovered_participants().contains(&prefix_b) {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+
+    let data = dp_a
+      .get_discovered_participant_data(prefix_b)
+      .expect("Remote participant not discovered over IPv6");
+    assert!(data
+      .metatraffic_unicast_locators
+      .iter()
+      .all(|l| l.kind == LocatorKind::LOCATOR_KIND_UDPv6));
+  }
+
+  #[test]
+  fn dp_network_interfaces() {
+    use crate::discovery::discovery_config::DiscoveryConfig;
+    let config = DiscoveryConfig {
+      interfaces: vec![String::from("no_such_interface0")],
+      ..DiscoveryConfig::default()
+    };
+    assert!(matches!(
+      DomainParticipant::with_discovery_config(0, config),
+      Err(Error::BadParameter { .. })
+    ));
+
+    let config = DiscoveryConfig {
+      interfaces: vec![String::from("127.0.0.0/8")],
+      ..DiscoveryConfig::default()
+    };
+    let dp = DomainParticipant::with_discovery_config(0, config).unwrap();
+    let locators = dp.local_network().unicast_locators(7411);
+    assert_eq!(
+      locators,
+      vec![Locator::from(
+        "127.0.0.1:7411".parse::<SocketAddr>().unwrap()
+      )]
+    );
+  }
+
+  #[test]
+  fn dp_socket_config() {
+    use crate::{
+      dds::participant_config::MAX_PARTICIPANT_ID,
+      discovery::discovery_config::{IpFamily, SocketConfig},
+      network::{constant::*, local_network::LocalNetwork},
+    };
+    let socket_config = SocketConfig {
+      receive_buffer_size: 128 * 1024,
+      ..SocketConfig::default()
+    };
+    let network = LocalNetwork::new(&[IpFamily::V4], &[], socket_config).unwrap();
+    let (listeners, _) = super::create_listeners(23, None, MAX_PARTICIPANT_ID, &network).unwrap();
+    for token in &[
+      DISCOVERY_MUL_LISTENER_TOKEN,
+      DISCOVERY_LISTENER_TOKEN,
+      USER_TRAFFIC_MUL_LISTENER_TOKEN,
+      USER_TRAFFIC_LISTENER_TOKEN,
+    ] {
+      let listener = listeners.get(token).expect("Listener not created");
+      assert!(listener.receive_buffer_size().unwrap() >= 128 * 1024);
+    }
+  }
+
+  // A participant reading its own topic, in memory or through the network
+  fn read_own_samples(intra_process_delivery: bool, topic_name: &str) {
+    use crate::discovery::discovery_config::DiscoveryConfig;
+    let config = DiscoveryConfig {
+      intra_process_delivery,
+      ..DiscoveryConfig::default()
+    };
+    let qos = QosPolicies::qos_none();
+    let dp = DomainParticipant::with_discovery_config(24, config).unwrap();
+    let topic = dp
+      .create_topic(topic_name, "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let subscriber = dp.create_subscriber(&qos).unwrap();
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None, None)
+      .expect("Failed to create datareader");
+    let publisher = dp.create_publisher(&qos).unwrap();
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .expect("Failed to create datawriter");
+
+    for _ in 0..100 {
+      if data_reader
+        .get_subscription_matched_status()
+        .unwrap()
+        .current_count()
+        == 1
+      {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(
+      data_writer
+        .get_publication_matched_status()
+        .unwrap()
+        .current_count(),
+      1
+    );
+
+    for a in 0..3 {
+      let data = RandomData {
+        a,
+        b: String::from("own"),
+      };
+      data_writer.write(data, None).unwrap();
+    }
+    let mut received = Vec::new();
+    for _ in 0..50 {
+      while let Ok(Some(sample)) = data_reader.take_next_sample() {
+        let sample_info = sample.sample_info().clone();
+        assert_eq!(sample_info.publication_handle, data_writer.get_guid());
+        received.push(sample.value().clone().unwrap().a);
+      }
+      if received.len() == 3 {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    // each sample once, in order
+    assert_eq!(received, vec![0, 1, 2]);
+  }
+
+  #[test]
+  fn dp_intra_process_delivery() {
+    read_own_samples(true, "IntraProcess");
+    read_own_samples(false, "IntraProcessNetwork");
+  }
+
+  #[test]
+  fn dp_tcp_transport() {
+    use crate::discovery::discovery_config::{DiscoveryConfig, TcpConfig};
+    let qos = QosPolicies::qos_none();
+    let tcp_only = |tcp: TcpConfig| DiscoveryConfig {
+      tcp: Some(TcpConfig { udp: false, ..tcp }),
+      ..DiscoveryConfig::default()
+    };
+    let dp_server = DomainParticipant::with_discovery_config(
+      25,
+      tcp_only(TcpConfig {
+        listen_port: Some(0),
+        ..TcpConfig::default()
+      }),
+    )
+    .unwrap();
+    let port = dp_server
+      .local_network()
+      .tcp()
+      .and_then(|tcp| tcp.listen_address())
+      .expect("Not accepting TCP connections")
+      .port();
+    // the client accepts no connections
+    let dp_client = DomainParticipant::with_discovery_config(
+      25,
+      tcp_only(TcpConfig {
+        peers: vec![format!("127.0.0.1:{}", port)],
+        ..TcpConfig::default()
+      }),
+    )
+    .unwrap();
+    assert!(dp_client.local_network().unicast_locators(7411).is_empty());
+
+    let server_topic = dp_server
+      .create_topic("TcpTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let publisher = dp_server.create_publisher(&qos).unwrap();
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None,
+        &server_topic,
+        None,
+      )
+      .expect("Failed to create datawriter");
+    let client_topic = dp_client
+      .create_topic("TcpTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let subscriber = dp_client.create_subscriber(&qos).unwrap();
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &client_topic,
+        None,
+        None,
+      )
+      .expect("Failed to create datareader");
+
+    for _ in 0..100 {
+      if data_writer
+        .get_publication_matched_status()
+        .unwrap()
+        .current_count()
+        == 1
+      {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    let data = dp_client
+      .get_discovered_participant_data(dp_server.get_guid().guidPrefix)
+      .expect("Server not discovered over TCP");
+    assert!(!data.metatraffic_unicast_locators.is_empty());
+    assert!(data
+      .metatraffic_unicast_locators
+      .iter()
+      .all(|l| l.kind == LocatorKind::LOCATOR_KIND_TCPv4));
+    assert_eq!(
+      data_writer
+        .get_publication_matched_status()
+        .unwrap()
+        .current_count(),
+      1
+    );
+
+    let sample = RandomData {
+      a: 7,
+      b: String::from("over TCP"),
+    };
+    data_writer.write(sample.clone(), None).unwrap();
+    let mut received = None;
+    for _ in 0..50 {
+      if let Ok(Some(s)) = data_reader.take_next_sample() {
+        received = s.value().clone().ok();
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(received, Some(sample));
+  }
+
+  #[test]
+  fn dp_shutdown_releases_ports() {
+    // The unicast ports of a participant are free again only after its event loop has
+    // exited, so each new participant gets the same participant id.
+    let first = DomainParticipant::new(31).unwrap();
+    let participant_id = first.participant_id();
+    first.shutdown().unwrap();
+    first.shutdown().unwrap();
+    drop(first);
+
+    for i in 0..50 {
+      let dp = DomainParticipant::new(31).unwrap();
+      assert_eq!(dp.participant_id(), participant_id);
+      // half of them are just dropped
+      if i % 2 == 0 {
+        dp.shutdown().unwrap();
+      }
+    }
+  }
+
+  #[test]
+  fn dp_shutdown_with_entities() {
+    let qos = QosPolicies::qos_none();
+    let dp = DomainParticipant::new(33).unwrap();
+    let topic = dp
+      .create_topic("ShutdownTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let publisher = dp
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .expect("Failed to create datawriter");
+    let subscriber = dp
+      .create_subscriber(&qos)
+      .expect("Failed to create subscriber");
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None, None)
+      .expect("Failed to create datareader");
+
+    dp.shutdown().unwrap();
+
+    let data = RandomData {
+      a: 1,
+      b: String::from("too late"),
+    };
+    assert!(matches!(
+      data_writer.write(data, None),
+      Err(Error::OutOfResources { .. })
+    ));
+    assert!(data_reader
+      .take(100, ReadCondition::any())
+      .unwrap()
+      .is_empty());
+    assert!(matches!(
+      publisher.create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      ),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    assert!(matches!(
+      dp.create_publisher(&qos),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    assert!(matches!(
+      dp.ignore_participant(GuidPrefix::new(vec![1; 12])),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+  }
+
+  #[test]
+  fn dp_shutdown_disposes_participant() {
+    use crate::discovery::discovery_config::DiscoveryConfig;
+    // Without the dispose, the other participant would remember us for the whole lease.
+    let config = DiscoveryConfig {
+      participant_lease_duration: Duration::from_secs(60),
+      ..DiscoveryConfig::default()
+    };
+    let dp_a = DomainParticipant::with_discovery_config(32, config.clone()).unwrap();
+    let dp_b = DomainParticipant::with_discovery_config(32, config).unwrap();
+    let prefix_b = dp_b.get_guid().guidPrefix;
+    let knows_b = || dp_a.get_discovered_participants().contains(&prefix_b);
+
+    for _ in 0..100 {
+      if knows_b() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert!(knows_b());
+
+    drop(dp_b);
+    for _ in 0..50 {
+      if !knows_b() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert!(!knows_b());
+  }
+
+  #[test]
+  fn dp_delete_datareader_disposes_it() {
+    let qos = QosPolicies::qos_none();
+    let dp_a = DomainParticipant::new(34).unwrap();
+    let dp_b = DomainParticipant::new(34).unwrap();
+    let topic_a = dp_a
+      .create_topic("DeleteTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let topic_b = dp_b
+      .create_topic("DeleteTest", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let publisher = dp_a
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic_a, None,
+      )
+      .expect("Failed to create datawriter");
+    let subscriber = dp_b
+      .create_subscriber(&qos)
+      .expect("Failed to create subscriber");
+    let data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic_b, None, None)
+      .expect("Failed to create datareader");
+    let reader_guid = data_reader.get_guid();
+    let matched = || {
+      data_writer
+        .get_matched_subscriptions()
+        .contains(&reader_guid)
+    };
+
+    for _ in 0..100 {
+      if matched() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert!(matched());
+
+    // still in use by the DataReader
+    assert!(matches!(
+      dp_b.delete_subscriber(subscriber.clone()),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    assert!(matches!(
+      dp_b.delete_topic(topic_b.clone()),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+
+    subscriber.delete_datareader(data_reader).unwrap();
+    for _ in 0..50 {
+      if !matched() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert!(!matched());
+
+    dp_b.delete_subscriber(subscriber).unwrap();
+    dp_b.delete_topic(topic_b).unwrap();
+  }
+
+  #[test]
+  fn dp_lookup_datareaders_and_datawriters() {
+    let qos = QosPolicies::qos_none();
+    let dp = DomainParticipant::new(47).unwrap();
+    let topic_a = dp
+      .create_topic("LookupA", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    let topic_b = dp
+      .create_topic("LookupB", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    let subscriber = dp.create_subscriber(&qos).unwrap();
+    let other_subscriber = dp.create_subscriber(&qos).unwrap();
+    let reader_a1 = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic_a, None, None)
+      .unwrap();
+    let reader_a2 = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic_a, None, None)
+      .unwrap();
+    let reader_b = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic_b, None, None)
+      .unwrap();
+
+    let mut guids = vec![
+      reader_a1.get_guid(),
+      reader_a2.get_guid(),
+      reader_b.get_guid(),
+    ];
+    guids.sort();
+    let readers = subscriber.get_datareaders();
+    assert_eq!(readers.iter().map(|r| r.guid).collect::<Vec<_>>(), guids);
+    let found = subscriber.lookup_datareader("LookupB").unwrap();
+    assert_eq!(found.guid, reader_b.get_guid());
+    assert_eq!(found.type_name, "RandomData");
+    assert!(subscriber.lookup_datareader("LookupC").is_none());
+    assert!(other_subscriber.lookup_datareader("LookupB").is_none());
+
+    // deleted and dropped DataReaders are not found
+    subscriber.delete_datareader(reader_b).unwrap();
+    assert!(subscriber.lookup_datareader("LookupB").is_none());
+    let a2_guid = reader_a2.get_guid();
+    drop(reader_a1);
+    assert_eq!(
+      subscriber.lookup_datareader("LookupA").unwrap().guid,
+      a2_guid
+    );
+    assert_eq!(subscriber.get_datareaders().len(), 1);
+
+    let publisher = dp.create_publisher(&qos).unwrap();
+    let writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic_a, None,
+      )
+      .unwrap();
+    let found = publisher.lookup_datawriter("LookupA").unwrap();
+    assert_eq!(found.guid, writer.get_guid());
+    assert_eq!(found.topic_name, "LookupA");
+    assert!(publisher.lookup_datawriter("LookupB").is_none());
+    publisher.delete_contained_entities().unwrap();
+    assert!(publisher.lookup_datawriter("LookupA").is_none());
+    assert!(publisher.get_datawriters().is_empty());
+  }
+
+  #[test]
+  fn dp_find_topic_of_other_participant() {
+    use crate::dds::{
+      qos::{policy::Reliability, HasQoSPolicy, QosPolicyBuilder},
+      traits::TopicDescription,
+    };
+    use crate::structure::duration::Duration as DDSDuration;
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: DDSDuration::from_millis(100),
+      })
+      .build();
+    let dp_a = DomainParticipant::new(48).unwrap();
+    let dp_b = DomainParticipant::new(48).unwrap();
+    assert!(dp_b.lookup_topicdescription("FindTopic").unwrap().is_none());
+    assert!(dp_b
+      .find_topic("FindTopic", Duration::from_millis(100))
+      .unwrap()
+      .is_none());
+
+    let topic_a = dp_a
+      .create_topic("FindTopic", "FoundType", &qos, TopicKind::NoKey)
+      .unwrap();
+    let publisher = dp_a.create_publisher(&qos).unwrap();
+    let _writer = publisher
+      .create_datawriter_no_key::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic_a, None,
+      )
+      .unwrap();
+
+    let topic_b = dp_b
+      .find_topic("FindTopic", Duration::from_secs(10))
+      .unwrap()
+      .expect("Topic was not found");
+    assert_eq!(topic_b.get_name(), "FindTopic");
+    assert_eq!(topic_b.get_type().name(), "FoundType");
+    assert_eq!(topic_b.kind(), TopicKind::NoKey);
+    assert_eq!(topic_b.get_qos().reliability, qos.reliability);
+    assert!(dp_b.lookup_topicdescription("FindTopic").unwrap().is_some());
+    assert!(dp_b
+      .lookup_topicdescription("NoSuchTopic")
+      .unwrap()
+      .is_none());
+
+    let subscriber = dp_b.create_subscriber(&qos).unwrap();
+    subscriber
+      .create_datareader_no_key::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &topic_b, None, None,
+      )
+      .unwrap();
+  }
+
+  #[test]
+  fn dp_releases_unused_topics() {
+    use crate::common::lock::read_lock;
+    use crate::discovery::discovery_config::DiscoveryConfig;
+    // Announce topics often, and do not let the periodic cleanup forget them.
+    let config = DiscoveryConfig {
+      send_topic_info_period: Duration::from_secs(1),
+      topic_cleanup_period: Duration::from_secs(60),
+      ..DiscoveryConfig::default()
+    };
+    let qos = QosPolicies::qos_none();
+    let dp_a = DomainParticipant::with_discovery_config(49, config.clone()).unwrap();
+    let dp_b = DomainParticipant::with_discovery_config(49, config).unwrap();
+    let cached = |name: &str| {
+      read_lock(&dp_a.get_dds_cache())
+        .get_topic_cache(name)
+        .is_some()
+    };
+    let announced = |name: &str| {
+      read_lock(&dp_a.discovery_db())
+        .get_local_topics()
+        .any(|t| t.topic_data.name.as_deref() == Some(name))
+    };
+
+    let topic = dp_a
+      .create_topic("ReleasedTopic", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    let publisher = dp_a.create_publisher(&qos).unwrap();
+    let writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .unwrap();
+    assert!(cached("ReleasedTopic"));
+    assert!(announced("ReleasedTopic"));
+
+    // B knows the topic from its announcement, not only from the DataWriter
+    let announcement_known = || {
+      dp_b
+        .get_discovered_topic_data("ReleasedTopic")
+        .and_then(|t| t.topic_data.key)
+        .is_some()
+    };
+    for _ in 0..100 {
+      if announcement_known() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert!(announcement_known());
+
+    // the Topic keeps the topic after its last DataWriter is gone
+    assert!(matches!(
+      dp_a.delete_topic(topic.clone()),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    drop(writer);
+    assert!(cached("ReleasedTopic"));
+    assert!(announced("ReleasedTopic"));
+    let clone = topic.clone();
+    drop(topic);
+    assert!(cached("ReleasedTopic"));
+    drop(clone);
+    assert!(!cached("ReleasedTopic"));
+    assert!(!announced("ReleasedTopic"));
+
+    // and B forgets it, before its periodic cleanup
+    for _ in 0..100 {
+      if dp_b.get_discovered_topic_data("ReleasedTopic").is_none() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert!(dp_b.get_discovered_topic_data("ReleasedTopic").is_none());
+
+    // deleting the topic releases it while other Topics of it remain
+    let topic = dp_a
+      .create_topic("DeletedTopic", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    let writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .unwrap();
+    let other = dp_a
+      .create_topic("DeletedTopic", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    drop(writer);
+    assert!(cached("DeletedTopic"));
+    dp_a.delete_topic(topic).unwrap();
+    assert!(!cached("DeletedTopic"));
+    assert!(!announced("DeletedTopic"));
+    drop(other);
+    assert!(!cached("DeletedTopic"));
+  }
+
+  #[test]
+  fn dp_from_default_config() {
+    use crate::dds::participant_config::{DomainParticipantBuilder, ParticipantConfig};
+    // new() is the default configuration, so both take the next free participant id
+    let dp_a = DomainParticipant::new(35).unwrap();
+    let dp_b = DomainParticipant::from_config(ParticipantConfig::new(35)).unwrap();
+    let dp_c = DomainParticipantBuilder::new(35).build().unwrap();
+    assert_eq!(dp_b.domain_id(), dp_a.domain_id());
+    assert_eq!(dp_b.participant_id(), dp_a.participant_id() + 1);
+    assert_eq!(dp_c.participant_id(), dp_b.participant_id() + 1);
+    assert_eq!(dp_b.local_network(), dp_a.local_network());
+
+    drop(dp_b);
+    let dp_d = DomainParticipant::new(35).unwrap();
+    assert_eq!(dp_d.participant_id(), dp_a.participant_id() + 1);
+  }
+
+  #[test]
+  fn dp_from_config_participant_id() {
+    use crate::{
+      dds::participant_config::DomainParticipantBuilder,
+      discovery::discovery_config::{DiscoveryConfig, TcpConfig},
+    };
+    let dp = DomainParticipantBuilder::new(36)
+      .participant_id(3)
+      .build()
+      .unwrap();
+    assert_eq!(dp.participant_id(), 3);
+    let in_use = DomainParticipantBuilder::new(36)
+      .participant_id(3)
+      .build()
+      .err()
+      .expect("participant id 3 is in use");
+    assert_eq!(in_use.return_code(), ReturnCode::OutOfResources);
+    assert!(in_use
+      .to_string()
+      .starts_with("failed to bind SPDP unicast socket"));
+    assert!(matches!(
+      DomainParticipantBuilder::new(300).build(),
+      Err(Error::BadParameter { .. })
+    ));
+    // neither UDP nor TCP peers to discover anyone with
+    let unreachable = DiscoveryConfig {
+      tcp: Some(TcpConfig {
+        udp: false,
+        ..TcpConfig::default()
+      }),
+      ..DiscoveryConfig::default()
+    };
+    assert!(matches!(
+      DomainParticipantBuilder::new(36)
+        .discovery_config(unreachable)
+        .build(),
+      Err(Error::BadParameter { .. })
+    ));
+  }
+
+  #[test]
+  fn dp_skips_participant_ids_with_taken_ports() {
+    use std::net::UdpSocket;
+
+    use crate::{
+      dds::participant_config::DomainParticipantBuilder,
+      network::constant::get_spdp_well_known_unicast_port,
+    };
+    // another program has the SPDP port of id 0 and the user traffic port of id 1
+    let spdp_port = get_spdp_well_known_unicast_port(46, 0);
+    let _spdp_socket = UdpSocket::bind(("0.0.0.0", spdp_port)).unwrap();
+    let user_traffic_port = get_user_traffic_unicast_port(46, 1);
+    let _user_traffic_socket = UdpSocket::bind(("0.0.0.0", user_traffic_port)).unwrap();
+
+    let taken = DomainParticipantBuilder::new(46)
+      .participant_id(0)
+      .build()
+      .err()
+      .expect("SPDP port of participant id 0 is taken");
+    assert!(matches!(taken, Error::Io { .. }));
+    assert_eq!(taken.return_code(), ReturnCode::OutOfResources);
+    assert!(taken
+      .to_string()
+      .starts_with("failed to bind SPDP unicast socket"));
+
+    let dp = DomainParticipant::new(46).unwrap();
+    assert_eq!(dp.participant_id(), 2);
+    drop(dp);
+
+    let exhausted = DomainParticipantBuilder::new(46)
+      .max_participant_id(1)
+      .build()
+      .err()
+      .expect("participant ids 0 and 1 are taken");
+    assert_eq!(exhausted.return_code(), ReturnCode::OutOfResources);
+    let message = exhausted.to_string();
+    assert!(message.starts_with("no free participant id from 0 to 1 in domain 46"));
+    assert!(message.contains("user traffic unicast socket"));
+  }
+
+  #[test]
+  fn dp_qos_profile() {
+    use crate::dds::{
+      participant_config::DomainParticipantBuilder, qos::policy::Reliability,
+      qos_profile::QosProfile,
+    };
+    let profile = QosProfile {
+      datawriter_qos: QosPolicies::builder()
+        .reliability(Reliability::BestEffort)
+        .build(),
+      initial_peers: vec![String::from("127.0.0.1")],
+      ..QosProfile::default()
+    };
+    // the peers of the profile are enough without multicast
+    let dp = DomainParticipantBuilder::new(37)
+      .spdp_multicast(false)
+      .qos_profile(profile.clone())
+      .build()
+      .unwrap();
+    assert_eq!(dp.qos_profile(), Some(&profile));
+    let publisher = dp.create_publisher(&QosPolicies::qos_none()).unwrap();
+    assert_eq!(
+      publisher.get_default_datawriter_qos(),
+      &profile.datawriter_qos
+    );
+  }
+
+  #[test]
+  fn dp_entity_names() {
+    use crate::{
+      dds::participant_config::DomainParticipantBuilder,
+      discovery::discovery_snapshot::ParticipantSnapshot,
+    };
+    let qos = QosPolicies::qos_none();
+    let dp = DomainParticipant::new(53).unwrap();
+    assert_eq!(dp.name(), format!("rustdds-{}", std::process::id()));
+    let remote = DomainParticipantBuilder::new(53)
+      .name("robot 7")
+      .build()
+      .unwrap();
+    assert_eq!(remote.name(), "robot 7");
+    let remote_prefix = remote.get_guid().guidPrefix;
+
+    let topic = remote
+      .create_topic("NamedTopic", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    let publisher = remote.create_publisher(&qos).unwrap();
+    let writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .unwrap();
+    writer.set_name("camera writer").unwrap();
+    let found = publisher.lookup_datawriter("NamedTopic").unwrap();
+    assert_eq!(found.name.as_deref(), Some("camera writer"));
+
+    // names reach the other participant in SPDP and SEDP data
+    let named_writer = |participant: &ParticipantSnapshot| {
+      participant
+        .writers
+        .iter()
+        .any(|w| w.name.as_deref() == Some("camera writer"))
+    };
+    let mut remote_snapshot = None;
+    for _ in 0..50 {
+      remote_snapshot = dp
+        .discovery_snapshot()
+        .participants
+        .into_iter()
+        .find(|p| p.guid_prefix == remote_prefix);
+      if remote_snapshot.as_ref().map_or(false, named_writer) {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    let remote_snapshot = remote_snapshot.expect("Remote participant not discovered");
+    assert_eq!(remote_snapshot.name.as_deref(), Some("robot 7"));
+    assert!(named_writer(&remote_snapshot));
+  }
+
+  #[test]
+  fn dp_manual_clock() {
+    use crate::{
+      dds::participant_config::DomainParticipantBuilder,
+      structure::clock::{Clock, ManualClock, SharedClock},
+    };
+    use std::sync::Arc;
+    let clock = Arc::new(ManualClock::new());
+    let shared = SharedClock::from(clock.clone());
+    let dp = DomainParticipantBuilder::new(38)
+      .clock(shared.clone())
+      .build()
+      .unwrap();
+    // the caches, and so also Readers, Writers and Discovery, use the given clock
+    let cache_clock = dp.get_dds_cache().read().unwrap().clock();
+    assert_eq!(cache_clock, shared);
+    assert_eq!(dp.discovery_db().read().unwrap().clock(), shared);
+    let before = cache_clock.instant();
+    clock.advance(std::time::Duration::from_secs(60));
+    assert_eq!(
+      cache_clock.instant() - before,
+      std::time::Duration::from_secs(60)
+    );
+  }
+
+  #[test]
+  fn dp_discovery_survives_panic_holding_discovery_db() {
+    use std::panic::{self, AssertUnwindSafe};
+
+    let dp = DomainParticipant::new(45).unwrap();
+    let db = dp.discovery_db();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+      let _db = db.write().unwrap();
+      panic!("panic while holding the DiscoveryDB lock");
+    }));
+    // Discovery may recover the lock already here, so only the panic is certain.
+    assert!(result.is_err());
+
+    let remote = DomainParticipant::new(45).unwrap();
+    let remote_prefix = remote.get_guid().guidPrefix;
+    for _ in 0..50 {
+      if dp.get_discovered_participants().contains(&remote_prefix) {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert!(dp.get_discovered_participants().contains(&remote_prefix));
+    assert!(!db.is_poisoned());
+    assert!(dp.get_statistics().poisoned_locks_recovered >= 1);
+  }
+
+  #[test]
+  fn dp_three_participants_in_one_process() {
+    let qos = QosPolicies::qos_none();
+    let participants: Vec<DomainParticipant> =
+      (0..3).map(|_| DomainParticipant::new(0).unwrap()).collect();
+    let mut participant_ids: Vec<u16> = participants.iter().map(|p| p.participant_id()).collect();
+    participant_ids.sort();
+    participant_ids.dedup();
+    assert_eq!(participant_ids.len(), 3);
+
+    // Each discovers the others, like participants of other processes.
+    for _ in 0..100 {
+      let all_discovered = participants.iter().all(|p| {
+        let discovered = p.get_discovered_participants();
+        participants
+          .iter()
+          .filter(|other| other.get_guid() != p.get_guid())
+          .all(|other| discovered.contains(&other.get_guid().guidPrefix))
+      });
+      if all_discovered {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+
+    let topics: Vec<_> = participants
+      .iter()
+      .map(|p| {
+        p.create_topic("InProcessTest", "RandomData", &qos, TopicKind::WithKey)
+          .unwrap()
+      })
+      .collect();
+    let publisher = participants[0].create_publisher(&qos).unwrap();
+    let writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topics[0], None,
+      )
+      .unwrap();
+    let subscribers: Vec<_> = participants[1..]
+      .iter()
+      .map(|p| p.create_subscriber(&qos).unwrap())
+      .collect();
+    let mut readers: Vec<_> = subscribers
+      .iter()
+      .zip(topics[1..].iter())
+      .map(|(s, t)| {
+        s.create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(t, None, None)
+          .unwrap()
+      })
+      .collect();
+    for _ in 0..100 {
+      let matched = writer.get_publication_matched_status().unwrap();
+      if matched.current_count() == 2 {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+
+    let sample = RandomData {
+      a: 3,
+      b: String::from("in process"),
+    };
+    writer.write(sample.clone(), None).unwrap();
+    for reader in readers.iter_mut() {
+      let mut received = None;
+      for _ in 0..50 {
+        if let Ok(Some(s)) = reader.take_next_sample() {
+          received = s.value().clone().ok();
+          break;
+        }
+        thread::sleep(Duration::from_millis(100));
+      }
+      assert_eq!(received, Some(sample.clone()));
+    }
+  }
+
+  #[test]
+  fn dp_liveliness_per_writer() {
+    use crate::dds::{
+      qos::{policy::Liveliness, QosPolicyBuilder},
+      sampleinfo::InstanceState,
+    };
+    use crate::structure::duration::Duration as DDSDuration;
+
+    let dp = DomainParticipant::new(51).unwrap();
+    let qos = QosPolicies::qos_none();
+    let lease_duration = DDSDuration::from_millis(300);
+    // DataReaders and DataWriters take their liveliness from the topic.
+    let automatic_topic = dp
+      .create_topic(
+        "AutomaticLiveliness",
+        "RandomData",
+        &QosPolicyBuilder::new()
+          .liveliness(Liveliness::Automatic { lease_duration })
+          .build(),
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let manual_topic = dp
+      .create_topic(
+        "ManualLiveliness",
+        "RandomData",
+        &QosPolicyBuilder::new()
+          .liveliness(Liveliness::ManualByTopic { lease_duration })
+          .build(),
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let subscriber = dp.create_subscriber(&qos).unwrap();
+    let mut automatic_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &automatic_topic,
+        None,
+        None,
+      )
+      .unwrap();
+    let mut manual_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &manual_topic,
+        None,
+        None,
+      )
+      .unwrap();
+    let publisher = dp.create_publisher(&qos).unwrap();
+    // Both go silent after writing once. Heartbeats keep the Automatic one alive.
+    let automatic = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None,
+        &automatic_topic,
+        None,
+      )
+      .unwrap();
+    let manual = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None,
+        &manual_topic,
+        None,
+      )
+      .unwrap();
+    for _ in 0..50 {
+      let automatic_matched = automatic_reader.get_subscription_matched_status().unwrap();
+      let manual_matched = manual_reader.get_subscription_matched_status().unwrap();
+      if automatic_matched.current_count() == 1 && manual_matched.current_count() == 1 {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+
+    automatic
+      .write(
+        RandomData {
+          a: 1,
+          b: "automatic".to_string(),
+        },
+        None,
+      )
+      .unwrap();
+    manual
+      .write(
+        RandomData {
+          a: 2,
+          b: "manual".to_string(),
+        },
+        None,
+      )
+      .unwrap();
+    thread::sleep(Duration::from_millis(1000));
+
+    let mut states: Vec<(i64, InstanceState)> = automatic_reader
+      .read(10, ReadCondition::any())
+      .unwrap()
+      .iter()
+      .chain(manual_reader.read(10, ReadCondition::any()).unwrap().iter())
+      .map(|s| (s.get_key(), s.sample_info().instance_state))
+      .collect();
+    states.sort_by_key(|(key, _)| *key);
+    assert_eq!(
+      states,
+      vec![
+        (1, InstanceState::Alive),
+        (2, InstanceState::NotAlive_NoWriters)
+      ]
+    );
+  }
+
+  #[test]
+  fn dp_no_key_reader_loses_writer_once() {
+    use crate::dds::{
+      qos::{
+        policy::{History, Liveliness},
+        QosPolicyBuilder,
+      },
+      sampleinfo::InstanceState,
+    };
+    use crate::structure::duration::Duration as DDSDuration;
+
+    let dp = DomainParticipant::new(59).unwrap();
+    // The DataWriter takes its liveliness from the topic. Keep the data sample too, so
+    // that it is not replaced by the sample without data.
+    let qos = QosPolicyBuilder::new()
+      .history(History::KeepAll)
+      .liveliness(Liveliness::ManualByTopic {
+        lease_duration: DDSDuration::from_millis(300),
+      })
+      .build();
+    let topic = dp
+      .create_topic("NoKeyLiveliness", "RandomData", &qos, TopicKind::NoKey)
+      .unwrap();
+    let subscriber = dp.create_subscriber(&qos).unwrap();
+    let mut reader = subscriber
+      .create_datareader_no_key::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &topic, None, None,
+      )
+      .unwrap();
+    let publisher = dp.create_publisher(&qos).unwrap();
+    // The writer goes silent after writing once, and loses its liveliness.
+    let writer = publisher
+      .create_datawriter_no_key::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .unwrap();
+    // Both ends must know each other, or the reader drops the only sample.
+    for _ in 0..50 {
+      if writer
+        .get_publication_matched_status()
+        .unwrap()
+        .current_count()
+        == 1
+        && reader
+          .get_subscription_matched_status()
+          .unwrap()
+          .current_count()
+          == 1
+      {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    writer
+      .write(
+        RandomData {
+          a: 1,
+          b: "once".to_string(),
+        },
+        None,
+      )
+      .unwrap();
+    thread::sleep(Duration::from_millis(1000));
+    let lost = reader
+      .read(10, ReadCondition::any())
+      .unwrap()
+      .iter()
+      .filter(|s| s.value().is_none())
+      .count();
+    assert_eq!(lost, 1);
+    // Deleting the writer that is already lost tells nothing more.
+    publisher.delete_datawriter_no_key(writer).unwrap();
+    thread::sleep(Duration::from_millis(500));
+
+    let taken: Vec<(Option<i64>, InstanceState)> = reader
+      .take(10, ReadCondition::any())
+      .unwrap()
+      .into_iter()
+      .map(|s| (s.value().map(|d| d.a), s.sample_info().instance_state))
+      .collect();
+    assert_eq!(
+      taken,
+      vec![
+        (Some(1), InstanceState::NotAlive_NoWriters),
+        (None, InstanceState::NotAlive_NoWriters)
+      ]
+    );
+  }
+
+  // Set for the writer process of dp_restarted_writer_bumps_generation, to the name
+  // of its run.
+  const RESTART_TEST_RUN: &str = "RUSTDDS_RESTART_TEST_RUN";
+
+  // The writer process of dp_restarted_writer_bumps_generation. It writes until it
+  // is killed.
+  #[test]
+  #[ignore]
+  fn dp_restarted_writer_process() {
+    use crate::dds::participant_config::DomainParticipantBuilder;
+
+    let run = match std::env::var(RESTART_TEST_RUN) {
+      Ok(run) => run,
+      Err(_) => return, // not started by the test
+    };
+    let dp = DomainParticipantBuilder::new(52)
+      .participant_id(5)
+      .build()
+      .unwrap();
+    let qos = QosPolicies::qos_none();
+    let topic = dp
+      .create_topic("Restart", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    let publisher = dp.create_publisher(&qos).unwrap();
+    let writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .unwrap();
+    loop {
+      writer
+        .write(
+          RandomData {
+            a: 1,
+            b: run.clone(),
+          },
+          None,
+        )
+        .unwrap();
+      thread::sleep(Duration::from_millis(100));
+    }
+  }
+
+  #[test]
+  fn dp_restarted_writer_bumps_generation() {
+    use std::process::{Child, Command, Stdio};
+    use crate::dds::{sampleinfo::InstanceState, with_key::datareader::DataReader};
+
+    type Reader<'a> = DataReader<'a, RandomData, CDRDeserializerAdapter<RandomData>>;
+    // (instance state, run that wrote the sample, no_writers_generation_count)
+    type Taken = Vec<(InstanceState, Option<String>, i32)>;
+
+    fn start_writer(run: &str) -> Child {
+      Command::new(std::env::current_exe().unwrap())
+        .args(&[
+          "dds::participant::tests::dp_restarted_writer_process",
+          "--exact",
+          "--ignored",
+        ])
+        .env(RESTART_TEST_RUN, run)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap()
+    }
+
+    fn take_until(reader: &mut Reader<'_>, run: &str, taken: &mut Taken) -> bool {
+      for _ in 0..150 {
+        while let Ok(Some(sample)) = reader.take_next_sample() {
+          let info = sample.sample_info();
+          taken.push((
+            info.instance_state,
+            sample.value().as_ref().ok().map(|d| d.b.clone()),
+            info.generation_counts.no_writers_generation_count,
+          ));
+        }
+        if taken.iter().any(|(_, r, _)| r.as_deref() == Some(run)) {
+          return true;
+        }
+        thread::sleep(Duration::from_millis(100));
+      }
+      false
+    }
+
+    let dp = DomainParticipant::new(52).unwrap();
+    let qos = QosPolicies::qos_none();
+    let topic = dp
+      .create_topic("Restart", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    let subscriber = dp.create_subscriber(&qos).unwrap();
+    let mut reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None, None)
+      .unwrap();
+
+    let mut taken = Taken::new();
+    let mut writer = start_writer("first");
+    let first_received = take_until(&mut reader, "first", &mut taken);
+    writer.kill().unwrap();
+    writer.wait().unwrap();
+    assert!(first_received);
+
+    // The restarted process has a new GUID, but the same ports
+    let mut writer = start_writer("second");
+    let second_received = take_until(&mut reader, "second", &mut taken);
+    writer.kill().unwrap();
+    writer.wait().unwrap();
+    assert!(second_received);
+
+    // The instance lost its writer before the restarted one wrote it again
+    let restart = taken
+      .iter()
+      .position(|(_, r, _)| r.as_deref() == Some("second"))
+      .unwrap();
+    assert_eq!(
+      taken[restart - 1],
+      (InstanceState::NotAlive_NoWriters, None, 0)
+    );
+    assert!(taken[..restart].iter().all(|(_, _, gen)| *gen == 0));
+    assert!(taken[restart..]
+      .iter()
+      .all(|(state, r, gen)| *state == InstanceState::Alive
+        && r.as_deref() == Some("second")
+        && *gen == 1));
+  }
+
+  #[test]
+  fn dp_reader_wakes_up_for_sample_burst() {
+    use mio::{Events, Poll, PollOpt, Ready, Token};
+    use std::time::Instant;
+    use crate::dds::qos::{policy::History, QosPolicyBuilder};
+
+    const SAMPLES: usize = 100_000;
+    let qos = QosPolicyBuilder::new().history(History::KeepAll).build();
+    let dp = DomainParticipant::new(54).unwrap();
+    let topic = dp
+      .create_topic("SampleBurst", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    let subscriber = dp.create_subscriber(&qos).unwrap();
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None, None)
+      .unwrap();
+    let publisher = dp.create_publisher(&qos).unwrap();
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .unwrap();
+    for _ in 0..100 {
+      if data_writer
+        .get_publication_matched_status()
+        .unwrap()
+        .current_count()
+        == 1
+      {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+
+    let poll = Poll::new().unwrap();
+    poll
+      .register(&data_reader, Token(0), Ready::readable(), PollOpt::edge())
+      .unwrap();
+
+    // As fast as the command queue of the DataWriter allows
+    let started = Instant::now();
+    for a in 0..SAMPLES as i64 {
+      let data = RandomData {
+        a,
+        b: String::new(),
+      };
+      while data_writer.write(data.clone(), None).is_err() {
+        assert!(started.elapsed() < Duration::from_secs(60));
+        thread::sleep(Duration::from_millis(1));
+      }
+    }
+
+    // Taking less than is available must not leave the rest stranded
+    let mut events = Events::with_capacity(4);
+    let mut received = 0;
+    while received < SAMPLES {
+      poll
+        .poll(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+      assert!(
+        !events.is_empty(),
+        "DataReader stopped waking up after {} samples",
+        received
+      );
+      received += data_reader.take(1000, ReadCondition::any()).unwrap().len();
+    }
+    assert_eq!(received, SAMPLES);
+  }
+
+  #[test]
+  fn dp_refuses_samples_larger_than_max_message_size() {
+    use crate::discovery::discovery_config::DiscoveryConfig;
+    // no shared memory, so that the reader is reached over UDP
+    let config = DiscoveryConfig {
+      max_message_size: 1400,
+      shm_segment_size: 0,
+      ..DiscoveryConfig::default()
+    };
+    let qos = QosPolicies::qos_none();
+    let dp_a = DomainParticipant::with_discovery_config(55, config.clone()).unwrap();
+    let dp_b = DomainParticipant::with_discovery_config(55, config).unwrap();
+    let topic_a = dp_a
+      .create_topic("MaxMessageSize", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    let topic_b = dp_b
+      .create_topic("MaxMessageSize", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    let publisher = dp_a.create_publisher(&qos).unwrap();
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic_a, None,
+      )
+      .unwrap();
+    let large = RandomData {
+      a: 1,
+      b: "x".repeat(2000),
+    };
+    // nothing is sent over UDP before a reader matches
+    assert!(data_writer.write(large.clone(), None).is_ok());
+
+    let subscriber = dp_b.create_subscriber(&qos).unwrap();
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic_b, None, None)
+      .unwrap();
+    for _ in 0..100 {
+      if data_writer
+        .get_publication_matched_status()
+        .unwrap()
+        .current_count()
+        == 1
+      {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+
+    let refused = data_writer.write(large, None).unwrap_err();
+    assert!(matches!(refused, Error::MessageTooLarge { .. }));
+    assert_eq!(refused.return_code(), ReturnCode::OutOfResources);
+
+    let fits = RandomData {
+      a: 2,
+      b: "x".repeat(1000),
+    };
+    data_writer.write(fits.clone(), None).unwrap();
+    let mut received = Vec::new();
+    for _ in 0..100 {
+      received = data_reader
+        .take(10, ReadCondition::any())
+        .unwrap()
+        .into_iter()
+        .filter_map(|s| s.into_value().ok())
+        .collect();
+      if !received.is_empty() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(received, vec![fits]);
+  }
+
+  #[derive(Serialize, Deserialize)]
+  struct OtherData {
+    a: i64,
+  }
+
+  impl Keyed for OtherData {
+    type K = i64;
+    fn get_key(&self) -> i64 {
+      self.a
+    }
+  }
+
+  impl DdsType for OtherData {
+    fn type_name() -> &'static str {
+      "test::RandomData"
+    }
+  }
+
+  #[test]
+  fn dp_topic_for_refuses_other_data_types() {
+    let qos = QosPolicies::qos_none();
+    let dp = DomainParticipant::new(0).unwrap();
+    let publisher = dp.create_publisher(&qos).unwrap();
+    let subscriber = dp.create_subscriber(&qos).unwrap();
+
+    let keyed = dp
+      .create_topic_for::<RandomData>("TypedTopic", &qos, TopicKind::WithKey)
+      .unwrap();
+    assert_eq!(keyed.get_type().name(), "test::RandomData");
+    publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &keyed, None,
+      )
+      .unwrap();
+    match subscriber
+      .create_datareader::<OtherData, CDRDeserializerAdapter<OtherData>>(&keyed, None, None)
+    {
+      Err(e) => assert_eq!(e.return_code(), ReturnCode::BadParameter),
+      Ok(_) => panic!("DataReader of another type was created"),
+    }
+
+    let no_key = dp
+      .create_topic_for::<RandomData>("TypedNoKeyTopic", &qos, TopicKind::NoKey)
+      .unwrap();
+    subscriber
+      .create_datareader_no_key::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &no_key, None, None,
+      )
+      .unwrap();
+    match publisher
+      .create_datawriter_no_key::<OtherData, CDRSerializerAdapter<OtherData, LittleEndian>>(
+        None, &no_key, None,
+      ) {
+      Err(e) => assert_eq!(e.return_code(), ReturnCode::BadParameter),
+      Ok(_) => panic!("DataWriter of another type was created"),
+    }
+
+    // the type name is taken by RandomData
+    let refused = dp
+      .create_topic_for::<OtherData>("OtherTopic", &qos, TopicKind::WithKey)
+      .unwrap_err();
+    assert_eq!(refused.return_code(), ReturnCode::BadParameter);
+
+    // topics of type names given as strings are not checked
+    let untyped = dp
+      .create_topic("UntypedTopic", "test::RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+    subscriber
+      .create_datareader::<OtherData, CDRDeserializerAdapter<OtherData>>(&untyped, None, None)
+      .unwrap();
+  }
+
+  #[test]
+  fn dp_topic_for_advertises_type_name() {
+    let qos = QosPolicies::qos_none();
+    let dp_a = DomainParticipant::new(56).unwrap();
+    let dp_b = DomainParticipant::new(56).unwrap();
+    let topic = dp_a
+      .create_topic_for::<RandomData>("AdvertisedTopic", &qos, TopicKind::WithKey)
+      .unwrap();
+    let publisher = dp_a.create_publisher(&qos).unwrap();
+    let _data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .unwrap();
+
+    for _ in 0..100 {
+      if dp_b.get_discovered_topic_data("AdvertisedTopic").is_some() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    let topic_data = dp_b
+      .get_discovered_topic_data("AdvertisedTopic")
+      .expect("Topic was not discovered");
+    assert_eq!(topic_data.get_type_name(), "test::RandomData");
+  }
+
+  #[test]
+  fn dp_passive_participant_is_not_discovered() {
+    use crate::dds::participant_config::DomainParticipantBuilder;
+    let dp_active = DomainParticipant::new(58).unwrap();
+    let dp_passive = DomainParticipantBuilder::new(58)
+      .passive(true)
+      .build()
+      .unwrap();
+
+    // The passive participant hears the multicast announcements.
+    for _ in 0..100 {
+      let discovered = dp_passive.get_discovered_participants();
+      if discovered.contains(&dp_active.get_guid().guidPrefix) {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert!(dp_passive
+      .get_discovered_participants()
+      .contains(&dp_active.get_guid().guidPrefix));
+
+    // The active participant never hears about it.
+    thread::sleep(Duration::from_secs(1));
+    assert!(!dp_active
+      .get_discovered_participants()
+      .contains(&dp_passive.get_guid().guidPrefix));
+  }
+
+  #[test]
+  fn dp_multicast_listener_is_bound_again() {
+    use std::net::UdpSocket;
+    use crate::{
+      discovery::discovery_config::DiscoveryConfig,
+      network::constant::get_spdp_well_known_multicast_port,
+    };
+    // Takes the SPDP multicast port without sharing it, so that the passive participant
+    // starts without its multicast listener, like when the network was down.
+    let blocker = UdpSocket::bind(("0.0.0.0", get_spdp_well_known_multicast_port(60))).unwrap();
+    let dp_passive = DomainParticipant::with_discovery_config(
+      60,
+      DiscoveryConfig {
+        passive: true,
+        multicast_rejoin_period: Duration::from_millis(200),
+        ..DiscoveryConfig::default()
+      },
+    )
+    .unwrap();
+    let dp_active = DomainParticipant::with_discovery_config(
+      60,
+      DiscoveryConfig {
+        send_participant_info_period: Duration::from_millis(300),
+        ..DiscoveryConfig::default()
+      },
+    )
+    .unwrap();
+    let active_prefix = dp_active.get_guid().guidPrefix;
+    let knows_active = || {
+      dp_passive
+        .get_discovered_participants()
+        .contains(&active_prefix)
+    };
+
+    // The passive participant hears only multicast announcements.
+    thread::sleep(Duration::from_secs(1));
+    assert!(!knows_active());
+    assert_eq!(dp_passive.get_statistics().sockets_recreated, 0);
+
+    drop(blocker);
+    for _ in 0..50 {
+      if knows_active() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert!(knows_active());
+    assert_eq!(dp_passive.get_statistics().sockets_recreated, 1);
+  }
 }
@@ -0,0 +1,359 @@
+use crate::{
+  dds::{
+    participant::DomainParticipant,
+    qos_profile::QosProfile,
+    values::result::{Error, Result},
+  },
+  discovery::discovery_config::{DiscoveryConfig, IpFamily},
+  structure::clock::SharedClock,
+};
+
+/// Largest domain id whose ports fit the RTPS port mapping.
+pub const MAX_DOMAIN_ID: u16 = 232;
+/// Largest participant id whose ports do not overlap those of the next domain.
+pub const MAX_PARTICIPANT_ID: u16 = 119;
+
+/// Everything needed to create a [DomainParticipant](struct.DomainParticipant.html).
+///
+/// The default is domain 0, the first free participant id and the default
+/// [DiscoveryConfig](struct.DiscoveryConfig.html), which is what
+/// `DomainParticipant::new(0)` uses.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::{DiscoveryConfig, DomainParticipant, ParticipantConfig};
+/// let config = ParticipantConfig {
+///   domain_id: 3,
+///   participant_id: Some(7),
+///   ..ParticipantConfig::default()
+/// };
+/// let domain_participant = DomainParticipant::from_config(config).unwrap();
+/// assert_eq!(domain_participant.participant_id(), 7);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParticipantConfig {
+  /// Domain to join. At most [MAX_DOMAIN_ID](constant.MAX_DOMAIN_ID.html).
+  pub domain_id: u16,
+  /// Participant id, which selects our unicast ports. None picks the first free one.
+  /// Creation fails if the given id is in use on this host. At most
+  /// [MAX_PARTICIPANT_ID](constant.MAX_PARTICIPANT_ID.html).
+  pub participant_id: Option<u16>,
+  /// Largest participant id tried when `participant_id` is None. Creation fails if the
+  /// ports of every id from 0 up to this one are in use. At most
+  /// [MAX_PARTICIPANT_ID](constant.MAX_PARTICIPANT_ID.html), which is the default.
+  pub max_participant_id: u16,
+  /// Discovery timing and peers, and the network the participant uses.
+  pub discovery: DiscoveryConfig,
+  /// Name announced to other participants, which tools such as RTI Admin Console
+  /// show instead of the GUID. None announces `rustdds-<process id>`. The name is
+  /// only informational, it does not affect matching.
+  pub name: Option<String>,
+  /// Default QoS of the participant. Its `initial_peers` are added to those of
+  /// `discovery`, and its DataWriter QoS is the default of new Publishers.
+  pub qos_profile: Option<QosProfile>,
+  /// Time source of the participant and its entities. The default is the system
+  /// clock. A [ManualClock](struct.ManualClock.html) makes leases, deadlines and
+  /// lifespans testable without waiting.
+  pub clock: SharedClock,
+}
+
+impl ParticipantConfig {
+  /// Default configuration in the given domain.
+  pub fn new(domain_id: u16) -> ParticipantConfig {
+    ParticipantConfig {
+      domain_id,
+      ..ParticipantConfig::default()
+    }
+  }
+
+  /// Checks the id ranges and `discovery`, and that the options allow discovering
+  /// anyone: without SPDP multicast some initial peer or TCP peer is needed, unless
+  /// SPDP is disabled altogether.
+  pub fn validate(&self) -> Result<()> {
+    if self.domain_id > MAX_DOMAIN_ID {
+      return Err(Error::bad_parameter(format!(
+        "domain id {} is larger than {}",
+        self.domain_id, MAX_DOMAIN_ID
+      )));
+    }
+    if let Some(participant_id) = self.participant_id {
+      if participant_id > MAX_PARTICIPANT_ID {
+        return Err(Error::bad_parameter(format!(
+          "participant id {} is larger than {}",
+          participant_id, MAX_PARTICIPANT_ID
+        )));
+      }
+    }
+    if self.max_participant_id > MAX_PARTICIPANT_ID {
+      return Err(Error::bad_parameter(format!(
+        "max participant id {} is larger than {}",
+        self.max_participant_id, MAX_PARTICIPANT_ID
+      )));
+    }
+    self.discovery.validate()?;
+
+    let discovery = &self.discovery;
+    // With UDP we always listen to SPDP multicast, even if we do not send to it.
+    let udp = discovery.tcp.as_ref().map_or(true, |tcp| tcp.udp);
+    let tcp_peers = discovery.tcp.as_ref().map_or(false, |tcp| {
+      !tcp.peers.is_empty() || tcp.listen_port.is_some()
+    });
+    if discovery.spdp_enabled && !udp && !tcp_peers {
+      return Err(Error::bad_parameter(
+        "UDP is not used and there are no TCP peers, so no participant can be discovered",
+      ));
+    }
+    Ok(())
+  }
+}
+
+impl Default for ParticipantConfig {
+  fn default() -> ParticipantConfig {
+    ParticipantConfig {
+      domain_id: 0,
+      participant_id: None,
+      max_participant_id: MAX_PARTICIPANT_ID,
+      discovery: DiscoveryConfig::default(),
+      name: None,
+      qos_profile: None,
+      clock: SharedClock::default(),
+    }
+  }
+}
+
+/// Builds a [DomainParticipant](struct.DomainParticipant.html) from a
+/// [ParticipantConfig](struct.ParticipantConfig.html). Options that are not set keep
+/// their default values.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::DomainParticipantBuilder;
+/// let domain_participant = DomainParticipantBuilder::new(0)
+///   .spdp_multicast(false)
+///   .initial_peers(vec![String::from("127.0.0.1")])
+///   .build()
+///   .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct DomainParticipantBuilder {
+  config: ParticipantConfig,
+}
+
+impl DomainParticipantBuilder {
+  pub fn new(domain_id: u16) -> DomainParticipantBuilder {
+    DomainParticipantBuilder {
+      config: ParticipantConfig::new(domain_id),
+    }
+  }
+
+  pub fn participant_id(mut self, participant_id: u16) -> DomainParticipantBuilder {
+    self.config.participant_id = Some(participant_id);
+    self
+  }
+
+  /// See `max_participant_id` of [ParticipantConfig](struct.ParticipantConfig.html).
+  pub fn max_participant_id(mut self, max_participant_id: u16) -> DomainParticipantBuilder {
+    self.config.max_participant_id = max_participant_id;
+    self
+  }
+
+  /// Replaces all discovery options set so far.
+  pub fn discovery_config(mut self, discovery: DiscoveryConfig) -> DomainParticipantBuilder {
+    self.config.discovery = discovery;
+    self
+  }
+
+  pub fn spdp_multicast(mut self, spdp_multicast: bool) -> DomainParticipantBuilder {
+    self.config.discovery.spdp_multicast = spdp_multicast;
+    self
+  }
+
+  pub fn initial_peers(mut self, initial_peers: Vec<String>) -> DomainParticipantBuilder {
+    self.config.discovery.initial_peers = initial_peers;
+    self
+  }
+
+  /// See `sedp_announce` of [DiscoveryConfig](struct.DiscoveryConfig.html).
+  pub fn sedp_announce(mut self, sedp_announce: bool) -> DomainParticipantBuilder {
+    self.config.discovery.sedp_announce = sedp_announce;
+    self
+  }
+
+  /// See `passive` of [DiscoveryConfig](struct.DiscoveryConfig.html).
+  pub fn passive(mut self, passive: bool) -> DomainParticipantBuilder {
+    self.config.discovery.passive = passive;
+    self
+  }
+
+  pub fn ip_families(mut self, ip_families: Vec<IpFamily>) -> DomainParticipantBuilder {
+    self.config.discovery.ip_families = ip_families;
+    self
+  }
+
+  pub fn interfaces(mut self, interfaces: Vec<String>) -> DomainParticipantBuilder {
+    self.config.discovery.interfaces = interfaces;
+    self
+  }
+
+  pub fn domain_tag(mut self, domain_tag: &str) -> DomainParticipantBuilder {
+    self.config.discovery.domain_tag = domain_tag.to_string();
+    self
+  }
+
+  /// See `max_message_size` of [DiscoveryConfig](struct.DiscoveryConfig.html).
+  pub fn max_message_size(mut self, max_message_size: usize) -> DomainParticipantBuilder {
+    self.config.discovery.max_message_size = max_message_size;
+    self
+  }
+
+  /// See `name` of [ParticipantConfig](struct.ParticipantConfig.html).
+  pub fn name(mut self, name: &str) -> DomainParticipantBuilder {
+    self.config.name = Some(name.to_string());
+    self
+  }
+
+  /// Default QoS of the participant, e.g. from
+  /// [QosProfiles](struct.QosProfiles.html). See `qos_profile` of
+  /// [ParticipantConfig](struct.ParticipantConfig.html).
+  pub fn qos_profile(mut self, qos_profile: QosProfile) -> DomainParticipantBuilder {
+    self.config.qos_profile = Some(qos_profile);
+    self
+  }
+
+  /// See `clock` of [ParticipantConfig](struct.ParticipantConfig.html).
+  pub fn clock(mut self, clock: SharedClock) -> DomainParticipantBuilder {
+    self.config.clock = clock;
+    self
+  }
+
+  /// The configuration built so far.
+  pub fn config(&self) -> &ParticipantConfig {
+    &self.config
+  }
+
+  /// Creates the participant. See
+  /// [DomainParticipant::from_config](struct.DomainParticipant.html#method.from_config).
+  pub fn build(self) -> Result<DomainParticipant> {
+    DomainParticipant::from_config(self.config)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::discovery::discovery_config::TcpConfig;
+
+  #[test]
+  fn participant_config_defaults() {
+    let config = ParticipantConfig::default();
+    assert_eq!(config.domain_id, 0);
+    assert_eq!(config.participant_id, None);
+    assert_eq!(config.max_participant_id, MAX_PARTICIPANT_ID);
+    assert_eq!(config.discovery, DiscoveryConfig::default());
+    assert_eq!(config.name, None);
+    assert_eq!(config.qos_profile, None);
+    assert_eq!(config.clock, SharedClock::default());
+    assert!(config.validate().is_ok());
+    assert_eq!(DomainParticipantBuilder::new(0).config(), &config);
+    assert_eq!(
+      ParticipantConfig::new(5),
+      ParticipantConfig {
+        domain_id: 5,
+        ..ParticipantConfig::default()
+      }
+    );
+  }
+
+  #[test]
+  fn participant_config_validate() {
+    assert!(ParticipantConfig::new(MAX_DOMAIN_ID).validate().is_ok());
+    assert!(matches!(
+      ParticipantConfig::new(MAX_DOMAIN_ID + 1).validate(),
+      Err(Error::BadParameter { .. })
+    ));
+
+    let builder = DomainParticipantBuilder::new(0).participant_id(MAX_PARTICIPANT_ID);
+    assert!(builder.config().validate().is_ok());
+    let builder = builder.participant_id(MAX_PARTICIPANT_ID + 1);
+    assert!(matches!(
+      builder.config().validate(),
+      Err(Error::BadParameter { .. })
+    ));
+    let builder = DomainParticipantBuilder::new(0).max_participant_id(MAX_PARTICIPANT_ID + 1);
+    assert!(matches!(
+      builder.config().validate(),
+      Err(Error::BadParameter { .. })
+    ));
+
+    // invalid discovery options
+    let builder = DomainParticipantBuilder::new(0).ip_families(vec![]);
+    assert!(matches!(
+      builder.config().validate(),
+      Err(Error::BadParameter { .. })
+    ));
+    let builder = DomainParticipantBuilder::new(0).max_message_size(1000);
+    assert!(matches!(
+      builder.config().validate(),
+      Err(Error::BadParameter { .. })
+    ));
+  }
+
+  #[test]
+  fn participant_config_reachable_peers() {
+    // nobody to announce to, but we still listen to multicast
+    let builder = DomainParticipantBuilder::new(0).spdp_multicast(false);
+    assert!(builder.config().validate().is_ok());
+    let quiet = builder.clone().sedp_announce(false).passive(true);
+    assert!(quiet.config().validate().is_ok());
+    assert!(!quiet.config().discovery.sedp_announce);
+    assert!(quiet.config().discovery.passive);
+    let peers = vec![String::from("127.0.0.1")];
+    let profile = QosProfile {
+      initial_peers: peers.clone(),
+      ..QosProfile::default()
+    };
+    assert!(builder
+      .clone()
+      .qos_profile(profile)
+      .config()
+      .validate()
+      .is_ok());
+    let builder = builder.initial_peers(peers);
+    assert!(builder.config().validate().is_ok());
+
+    let discovery = DiscoveryConfig {
+      spdp_multicast: false,
+      spdp_enabled: false,
+      ..DiscoveryConfig::default()
+    };
+    let builder = DomainParticipantBuilder::new(0).discovery_config(discovery);
+    assert!(builder.config().validate().is_ok());
+
+    // initial peers are not used without UDP
+    let tcp = TcpConfig {
+      udp: false,
+      ..TcpConfig::default()
+    };
+    let discovery = DiscoveryConfig {
+      initial_peers: vec![String::from("127.0.0.1")],
+      tcp: Some(tcp.clone()),
+      ..DiscoveryConfig::default()
+    };
+    let config = ParticipantConfig {
+      discovery: discovery.clone(),
+      ..ParticipantConfig::default()
+    };
+    assert!(matches!(config.validate(), Err(Error::BadParameter { .. })));
+    let config = ParticipantConfig {
+      discovery: DiscoveryConfig {
+        tcp: Some(TcpConfig {
+          peers: vec![String::from("127.0.0.1:7500")],
+          ..tcp
+        }),
+        ..discovery
+      },
+      ..ParticipantConfig::default()
+    };
+    assert!(config.validate().is_ok());
+  }
+}
@@ -1,9 +1,10 @@
 use mio_extras::channel as mio_channel;
-use log::error;
+use log::warn;
 
 use std::{
+  collections::HashSet,
   fmt::Debug,
-  sync::{RwLock, Arc},
+  sync::{Mutex, RwLock, Arc},
   time::Duration,
 };
 
@@ -19,7 +20,9 @@ use crate::dds::{
   participant::*,
   topic::*,
   qos::*,
-  reader::Reader,
+  matched_writers::MatchedWriters,
+  reader::{Reader, ReaderListener},
+  task_waker::TaskWaker,
   writer::Writer,
   with_key::datawriter::DataWriter as WithKeyDataWriter,
   no_key::datawriter::DataWriter as NoKeyDataWriter,
@@ -33,9 +36,11 @@ use crate::{
   discovery::{
     discovery_db::DiscoveryDB,
     data_types::topic_data::{DiscoveredWriterData},
+    discovery_snapshot::EndpointSnapshot,
   },
   structure::topic_kind::TopicKind,
 };
+use crate::common::lock::{read_lock, write_lock};
 
 use rand::Rng;
 
@@ -56,7 +61,7 @@ use super::{
 /// # use rustdds::dds::qos::QosPolicyBuilder;
 /// use rustdds::dds::Publisher;
 ///
-/// let domain_participant = DomainParticipant::new(0);
+/// let domain_participant = DomainParticipant::new(0).unwrap();
 /// let qos = QosPolicyBuilder::new().build();
 ///
 /// let publisher = domain_participant.create_publisher(&qos);
@@ -69,6 +74,8 @@ pub struct Publisher {
   default_datawriter_qos: QosPolicies, // used when creating a new DataWriter
   add_writer_sender: mio_channel::SyncSender<Writer>,
   discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
+  // DataWriters created by this Publisher or its clones, and not deleted by it since
+  datawriters: Arc<Mutex<HashSet<GUID>>>,
 }
 
 // public interface for Publisher
@@ -88,10 +95,11 @@ impl<'a> Publisher {
       default_datawriter_qos: default_dw_qos,
       add_writer_sender,
       discovery_command,
+      datawriters: Arc::new(Mutex::new(HashSet::new())),
     }
   }
 
-  /// Creates DDS [DataWriter](struct.With_Key_DataWriter.html) for Keyed topic
+  /// Creates DDS [DataWriter](struct.WithKeyDataWriter.html) for Keyed topic
   ///
   /// # Arguments
   ///
@@ -110,7 +118,7 @@ impl<'a> Publisher {
   /// use rustdds::serialization::CDRSerializerAdapter;
   /// use serde::Serialize;
   ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   ///
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
@@ -134,6 +142,21 @@ impl<'a> Publisher {
     topic: &'a Topic,
     qos: Option<QosPolicies>,
   ) -> Result<WithKeyDataWriter<'a, D, SA>>
+  where
+    D: Keyed + Serialize,
+    <D as Keyed>::K: Key,
+    SA: SerializerAdapter<D>,
+  {
+    topic.check_data_type::<D>()?;
+    self.create_datawriter_internal(entity_id, topic, qos)
+  }
+
+  fn create_datawriter_internal<D, SA>(
+    &'a self,
+    entity_id: Option<EntityId>,
+    topic: &'a Topic,
+    qos: Option<QosPolicies>,
+  ) -> Result<WithKeyDataWriter<'a, D, SA>>
   where
     D: Keyed + Serialize,
     <D as Keyed>::K: Key,
@@ -161,8 +184,10 @@ impl<'a> Publisher {
     let dp = match self.get_participant() {
       Some(dp) => dp,
       None => {
-        error!("Cannot create new DataWriter, DomainParticipant doesn't exist.");
-        return Err(Error::PreconditionNotMet);
+        return Err(Error::precondition_not_met(format!(
+          "cannot create DataWriter of topic {}, DomainParticipant has been dropped",
+          topic.get_name()
+        )))
       }
     };
 
@@ -175,11 +200,18 @@ impl<'a> Publisher {
       topic.get_qos().clone(),
       message_status_sender,
     );
+    let command_waker = new_writer.command_waker();
+    let ack_waiter = new_writer.ack_waiter();
+    let flow_control_queue_depth = new_writer.flow_control_queue_depth();
+    let statistics = new_writer.statistics();
+    let matched_readers = new_writer.matched_readers();
 
-    self
-      .add_writer_sender
-      .send(new_writer)
-      .expect("Adding new writer failed");
+    if self.add_writer_sender.send(new_writer).is_err() {
+      return Err(Error::precondition_not_met(format!(
+        "cannot create DataWriter of topic {}, DomainParticipant is shut down",
+        topic.get_name()
+      )));
+    }
 
     let matching_data_writer = WithKeyDataWriter::<D, SA>::new(
       self,
@@ -189,22 +221,40 @@ impl<'a> Publisher {
       self.discovery_command.clone(),
       dp.get_dds_cache(),
       message_status_receiver,
+      command_waker,
+      ack_waiter,
+      flow_control_queue_depth,
+      statistics.clone(),
+      matched_readers,
     );
 
     let matching_data_writer = match matching_data_writer {
       Ok(dw) => dw,
       e => return e,
     };
+    dp.statistics_registry().add_writer(guid, statistics);
 
-    match self.discovery_db.write() {
-      Ok(mut db) => {
-        let dwd = DiscoveredWriterData::new(&matching_data_writer, &topic, &dp);
+    {
+      let mut db = write_lock(&self.discovery_db);
+      let dwd = DiscoveredWriterData::new(&matching_data_writer, &topic, &dp);
 
-        db.update_local_topic_writer(dwd);
-        db.update_topic_data_p(&topic);
+      db.update_local_topic_writer(dwd);
+      db.update_topic_data_p(&topic);
+    }
+    self.lock_datawriters().insert(guid);
+    // To match with our own endpoints of the topic. The builtin ones of Discovery
+    // itself are matched by it.
+    if !entity_id.is_builtin() {
+      if let Err(e) = self
+        .discovery_command
+        .send(DiscoveryCommand::LOCAL_ENDPOINTS_CHANGED)
+      {
+        warn!(
+          "Failed to send LOCAL_ENDPOINTS_CHANGED DiscoveryCommand. {:?}",
+          e
+        );
       }
-      _ => return Err(Error::OutOfResources),
-    };
+    }
 
     Ok(matching_data_writer)
   }
@@ -227,7 +277,7 @@ impl<'a> Publisher {
   /// use rustdds::serialization::CDRSerializerAdapter;
   /// use serde::Serialize;
   ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   ///
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
@@ -248,6 +298,7 @@ impl<'a> Publisher {
     D: Serialize,
     SA: SerializerAdapter<D>,
   {
+    topic.check_data_type::<D>()?;
     let entity_id = match entity_id {
       Some(eid) => eid,
       None => {
@@ -257,21 +308,149 @@ impl<'a> Publisher {
         eid
       }
     };
-    let d =
-      self.create_datawriter::<NoKeyWrapper<D>, SAWrapper<SA>>(Some(entity_id), topic, qos)?;
+    let d = self.create_datawriter_internal::<NoKeyWrapper<D>, SAWrapper<SA>>(
+      Some(entity_id),
+      topic,
+      qos,
+    )?;
     Ok(NoKeyDataWriter::<'a, D, SA>::from_keyed(d))
   }
 
   fn add_writer(&self, writer: Writer) -> Result<()> {
     match self.add_writer_sender.send(writer) {
       Ok(_) => Ok(()),
-      _ => Err(Error::OutOfResources),
+      _ => Err(Error::out_of_resources(
+        "cannot add Writer, DomainParticipant is shut down",
+      )),
     }
   }
 
-  // delete_datawriter should not be needed. The DataWriter object itself should be deleted to accomplish this.
+  /// Deletes a DataWriter of this Publisher. Its announcement is disposed, so that
+  /// remote participants see it disappear, and the resources it used are released.
+  ///
+  /// Returns `PreconditionNotMet` if the DataWriter belongs to some other Publisher. It
+  /// is then deleted when dropped, like DataWriters are in any case.
+  pub fn delete_datawriter<D, SA>(&self, data_writer: WithKeyDataWriter<D, SA>) -> Result<()>
+  where
+    D: Keyed + Serialize,
+    SA: SerializerAdapter<D>,
+  {
+    self.remove_writer(data_writer.get_guid())
+  }
 
-  // lookup datawriter: maybe not necessary? App should remember datawriters it has created.
+  /// Deletes a DataWriter for a topic without key, see
+  /// [delete_datawriter](#method.delete_datawriter).
+  pub fn delete_datawriter_no_key<D, SA>(&self, data_writer: NoKeyDataWriter<D, SA>) -> Result<()>
+  where
+    D: Serialize,
+    SA: SerializerAdapter<D>,
+  {
+    self.remove_writer(data_writer.get_guid())
+  }
+
+  /// Deletes all DataWriters of this Publisher, see
+  /// [delete_datawriter](#method.delete_datawriter).
+  pub fn delete_contained_entities(&self) -> Result<()> {
+    let datawriters: Vec<GUID> = self.lock_datawriters().iter().copied().collect();
+    let mut result = Ok(());
+    for guid in datawriters {
+      if let Err(e) = self.remove_writer(guid) {
+        result = Err(e);
+      }
+    }
+    result
+  }
+
+  // Whether some DataWriter of this Publisher is not deleted yet.
+  pub(crate) fn has_datawriters(&self) -> bool {
+    let datawriters = self.lock_datawriters();
+    let db = read_lock(&self.discovery_db);
+    datawriters.iter().any(|g| db.is_local_topic_writer(*g))
+  }
+
+  // Deletes our DataWriter, unless the participant already has. Err(PreconditionNotMet)
+  // if it is not ours, or deleted by us already.
+  pub(crate) fn remove_writer(&self, guid: GUID) -> Result<()> {
+    if !self.lock_datawriters().remove(&guid) {
+      return Err(Error::precondition_not_met(format!(
+        "DataWriter {:?} is not of this Publisher",
+        guid
+      )));
+    }
+    match self.get_participant() {
+      Some(dp) => match dp.delete_local_writer(guid) {
+        Err(Error::PreconditionNotMet { .. }) => Ok(()),
+        r => r,
+      },
+      // everything went with the participant
+      None => Ok(()),
+    }
+  }
+
+  // DataWriters remove themselves here when dropped, so this must not panic. A set of
+  // GUIDs is consistent even if its user panicked while holding the lock.
+  fn lock_datawriters(&self) -> std::sync::MutexGuard<'_, HashSet<GUID>> {
+    self
+      .datawriters
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+  }
+
+  /// Finds a DataWriter of this Publisher by the name of its topic. If there are
+  /// several, this is the one with the smallest GUID. Returns None if there is none,
+  /// also after it has been deleted or dropped.
+  ///
+  /// A DataWriter is owned by whoever created it, and using it would require knowing
+  /// its data type and SerializerAdapter. So this only describes the DataWriter. Its
+  /// GUID identifies it, e.g. in the
+  /// [DiscoverySnapshot](struct.DiscoverySnapshot.html) of remote participants.
+  ///
+  /// # Examples
+  /// ```
+  /// # use serde::Serialize;
+  /// # use rustdds::dds::{DomainParticipant, data_types::TopicKind, qos::QosPolicyBuilder};
+  /// # use rustdds::dds::traits::Entity;
+  /// # use rustdds::serialization::CDRSerializerAdapter;
+  /// # #[derive(Serialize)]
+  /// # struct SomeType {}
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  /// let topic = domain_participant
+  ///   .create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey)
+  ///   .unwrap();
+  /// let data_writer = publisher
+  ///   .create_datawriter_no_key::<SomeType, CDRSerializerAdapter<SomeType>>(None, &topic, None)
+  ///   .unwrap();
+  ///
+  /// let found = publisher.lookup_datawriter("some_topic").unwrap();
+  /// assert_eq!(found.guid, data_writer.get_guid());
+  /// assert_eq!(found.type_name, "SomeType");
+  /// drop(data_writer);
+  /// assert!(publisher.lookup_datawriter("some_topic").is_none());
+  /// ```
+  pub fn lookup_datawriter(&self, topic_name: &str) -> Option<EndpointSnapshot> {
+    self
+      .get_datawriters()
+      .into_iter()
+      .find(|w| w.topic_name == topic_name)
+  }
+
+  /// Describes all DataWriters of this Publisher that are not deleted, ordered by
+  /// GUID. See [lookup_datawriter](#method.lookup_datawriter).
+  pub fn get_datawriters(&self) -> Vec<EndpointSnapshot> {
+    let datawriters = self.lock_datawriters();
+    let db = read_lock(&self.discovery_db);
+    let mut result: Vec<EndpointSnapshot> = datawriters
+      .iter()
+      .filter_map(|g| {
+        db.get_local_topic_writer(*g)
+          .map(|w| EndpointSnapshot::from_writer(*g, w))
+      })
+      .collect();
+    result.sort_by_key(|w| w.guid);
+    result
+  }
 
   // Suspend and resume publications are preformance optimization methods.
   // The minimal correct implementation is to do nothing. See DDS spec 2.2.2.4.1.8 and .9
@@ -314,7 +493,7 @@ impl<'a> Publisher {
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::Publisher;
   ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   ///
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
@@ -324,8 +503,6 @@ impl<'a> Publisher {
     self.domain_participant.clone().upgrade()
   }
 
-  // delete_contained_entities: We should not need this. Contained DataWriters should dispose themselves and notify publisher.
-
   /// Returns default DataWriter qos. Currently default qos is not used.
   ///
   /// # Example
@@ -335,7 +512,7 @@ impl<'a> Publisher {
   /// use rustdds::dds::qos::{QosPolicyBuilder};
   /// # use rustdds::dds::Publisher;
   ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   ///
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
@@ -354,7 +531,7 @@ impl<'a> Publisher {
   /// # use rustdds::dds::qos::{QosPolicyBuilder, policy::Durability};
   /// # use rustdds::dds::Publisher;
   ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   ///
   /// let mut publisher = domain_participant.create_publisher(&qos).unwrap();
@@ -409,7 +586,7 @@ impl Debug for Publisher {
 /// # use rustdds::dds::qos::QosPolicyBuilder;
 /// use rustdds::dds::Subscriber;
 ///
-/// let domain_participant = DomainParticipant::new(0);
+/// let domain_participant = DomainParticipant::new(0).unwrap();
 /// let qos = QosPolicyBuilder::new().build();
 ///
 /// let subscriber = domain_participant.create_subscriber(&qos);
@@ -422,6 +599,8 @@ pub struct Subscriber {
   sender_add_reader: mio_channel::SyncSender<Reader>,
   sender_remove_reader: mio_channel::SyncSender<GUID>,
   discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
+  // DataReaders created by this Subscriber or its clones, and not deleted by it since
+  datareaders: Arc<Mutex<HashSet<GUID>>>,
 }
 
 impl<'s> Subscriber {
@@ -440,6 +619,7 @@ impl<'s> Subscriber {
       sender_add_reader,
       sender_remove_reader,
       discovery_command,
+      datareaders: Arc::new(Mutex::new(HashSet::new())),
     }
   }
 
@@ -456,8 +636,8 @@ impl<'s> Subscriber {
     <D as Keyed>::K: Key,
     SA: DeserializerAdapter<D>,
   {
-    // What is the bound?
-    let (send, rec) = mio_channel::sync_channel::<()>(10);
+    // At most one notification is pending, see Reader::notify_cache_change
+    let (send, rec) = mio_channel::sync_channel::<()>(1);
     let (status_sender, status_receiver) = mio_channel::sync_channel::<StatusChange>(10);
     let (reader_command_sender, reader_command_receiver) =
       mio_channel::sync_channel::<ReaderCommand>(10);
@@ -483,13 +663,25 @@ impl<'s> Subscriber {
     let dp = match self.get_participant() {
       Some(dp) => dp,
       None => {
-        error!("DomainParticipant doesn't exist anymore.");
-        return Err(Error::PreconditionNotMet);
+        return Err(Error::precondition_not_met(format!(
+          "cannot create DataReader of topic {}, DomainParticipant has been dropped",
+          topic.get_name()
+        )))
       }
     };
 
     let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), reader_id);
 
+    // Create new topic to DDScache if one isn't present. The DataReader keeps track of
+    // its progress in it.
+    {
+      let dds_cache = dp.get_dds_cache();
+      let mut cache = write_lock(&dds_cache);
+      let topic_name = topic.get_name().to_string();
+      cache.add_new_topic(&topic_name, topic.kind(), topic.get_type(), topic.get_qos());
+      cache.add_topic_user(&topic_name, reader_guid).unwrap_or(());
+    }
+
     let new_reader = Reader::new(
       reader_guid,
       send,
@@ -505,44 +697,54 @@ impl<'s> Subscriber {
       &topic,
       rec,
       dp.get_dds_cache(),
-      self.discovery_command.clone(),
       status_receiver,
       reader_command_sender,
+      new_reader.data_waker(),
+      new_reader.statistics(),
+      new_reader.matched_writers(),
     );
 
     let matching_datareader = match matching_datareader {
       Ok(dr) => dr,
       e => return e,
     };
+    dp.statistics_registry()
+      .add_reader(reader_guid, new_reader.statistics());
 
-    match self.discovery_db.write() {
-      Ok(mut db) => {
-        db.update_local_topic_reader(&dp, &topic, &new_reader);
-        db.update_topic_data_p(&topic);
+    {
+      let mut db = write_lock(&self.discovery_db);
+      db.update_local_topic_reader(&dp, &topic, &new_reader);
+      db.update_topic_data_p(&topic);
+    }
+    // To match with our own endpoints of the topic. The builtin ones of Discovery
+    // itself are matched by it.
+    if !entity_id.is_builtin() {
+      if let Err(e) = self
+        .discovery_command
+        .send(DiscoveryCommand::LOCAL_ENDPOINTS_CHANGED)
+      {
+        warn!(
+          "Failed to send LOCAL_ENDPOINTS_CHANGED DiscoveryCommand. {:?}",
+          e
+        );
       }
-      _ => return Err(Error::OutOfResources),
-    };
-
-    // Create new topic to DDScache if one isn't present
-    match dp.get_dds_cache().write() {
-      Ok(mut rwlock) => rwlock.add_new_topic(
-        &topic.get_name().to_string(),
-        topic.kind(),
-        topic.get_type(),
-      ),
-      Err(e) => panic!(
-        "The DDSCache of domain participant {:?} is poisoned. Error: {}",
-        dp.get_guid(),
-        e
-      ),
-    };
+    }
 
     // Return the DataReader Reader pairs to where they are used
-    self
-      .sender_add_reader
-      .try_send(new_reader)
-      .expect("Could not send new Reader");
-    Ok(matching_datareader)
+    match self.sender_add_reader.try_send(new_reader) {
+      Ok(_) => {
+        self.lock_datareaders().insert(reader_guid);
+        Ok(matching_datareader)
+      }
+      Err(mio_channel::TrySendError::Full(_)) => Err(Error::out_of_resources(format!(
+        "cannot create DataReader of topic {}, too many Readers waiting to be added",
+        topic.get_name()
+      ))),
+      Err(_) => Err(Error::precondition_not_met(format!(
+        "cannot create DataReader of topic {}, DomainParticipant is shut down",
+        topic.get_name()
+      ))),
+    }
   }
 
   /// Creates DDS DataReader for keyed Topics
@@ -564,7 +766,7 @@ impl<'s> Subscriber {
   /// use rustdds::dds::data_types::TopicKind;
   /// use rustdds::dds::traits::Keyed;
   /// #
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// #
   ///
@@ -595,8 +797,12 @@ impl<'s> Subscriber {
     SA: DeserializerAdapter<D>,
   {
     if topic.kind() != TopicKind::WithKey {
-      return Err(Error::PreconditionNotMet); // TopicKind mismatch
+      return Err(Error::precondition_not_met(format!(
+        "topic {} is not WithKey",
+        topic.get_name()
+      )));
     }
+    topic.check_data_type::<D>()?;
     self.create_datareader_internal(entity_id, topic, qos)
   }
 
@@ -618,7 +824,7 @@ impl<'s> Subscriber {
   /// use rustdds::serialization::CDRDeserializerAdapter;
   /// use rustdds::dds::data_types::TopicKind;
   /// #
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// #
   ///
@@ -641,8 +847,12 @@ impl<'s> Subscriber {
     SA: DeserializerAdapter<D>,
   {
     if topic.kind() != TopicKind::NoKey {
-      return Err(Error::PreconditionNotMet); // TopicKind mismatch
+      return Err(Error::precondition_not_met(format!(
+        "topic {} is not NoKey",
+        topic.get_name()
+      )));
     }
+    topic.check_data_type::<D>()?;
 
     let entity_id = match entity_id {
       Some(eid) => eid,
@@ -662,19 +872,176 @@ impl<'s> Subscriber {
     Ok(NoKeyDataReader::<'s, D, SA>::from_keyed(d))
   }
 
-  /// Retrieves a previously created DataReader belonging to the Subscriber.
-  // TODO: Is this even possible. Whould probably need to return reference and store references on creation
-  pub(crate) fn lookup_datareader<D, SA>(
-    &self,
-    _topic_name: &str,
-  ) -> Option<WithKeyDataReader<D, SA>>
+  // Creates a DataReader that reads the same topic cache as the existing Reader
+  // builtin_reader_id, which belongs to Discovery. No new RTPS Reader is created,
+  // the existing one just notifies this DataReader as well. So taking samples here
+  // does not take them away from Discovery.
+  pub(crate) fn create_builtin_datareader<D: 'static, SA>(
+    &'s self,
+    topic: &'s Topic,
+    builtin_reader_id: EntityId,
+  ) -> Result<WithKeyDataReader<'s, D, SA>>
+  where
+    D: DeserializeOwned + Keyed,
+    <D as Keyed>::K: Key,
+    SA: DeserializerAdapter<D>,
+  {
+    let dp = match self.get_participant() {
+      Some(dp) => dp,
+      None => {
+        return Err(Error::precondition_not_met(format!(
+          "cannot create DataReader of topic {}, DomainParticipant has been dropped",
+          topic.get_name()
+        )))
+      }
+    };
+
+    let (send, rec) = mio_channel::sync_channel::<()>(1);
+    // There is no Reader of our own, so no status changes, reader commands or matched
+    // writers.
+    let (_status_sender, status_receiver) = mio_channel::sync_channel::<StatusChange>(10);
+    let (reader_command_sender, _reader_command_receiver) =
+      mio_channel::sync_channel::<ReaderCommand>(10);
+    let data_waker = TaskWaker::new();
+
+    let mut rng = rand::thread_rng();
+    let datareader_id = EntityId::createCustomEntityID([rng.gen(), rng.gen(), rng.gen()], 0xC7);
+    let builtin_reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), builtin_reader_id);
+    // the traffic is that of the Reader of Discovery
+    let statistics = dp
+      .statistics_registry()
+      .reader(builtin_reader_guid)
+      .unwrap_or_default();
+
+    let mut datareader = WithKeyDataReader::<D, SA>::new(
+      self,
+      datareader_id,
+      topic,
+      rec,
+      dp.get_dds_cache(),
+      status_receiver,
+      reader_command_sender,
+      data_waker.clone(),
+      statistics,
+      MatchedWriters::new(),
+    )?;
+    datareader.share_reader(builtin_reader_guid);
+    // Discovery data received before this DataReader was created is still relevant.
+    datareader.read_from_beginning();
+
+    let listener = ReaderListener {
+      notification_sender: send,
+      data_waker,
+    };
+    match dp
+      .get_add_reader_listener_sender()
+      .try_send((builtin_reader_guid, listener))
+    {
+      Ok(_) => Ok(datareader),
+      Err(e) => Err(Error::out_of_resources(format!(
+        "cannot add listener of builtin reader {:?}: {:?}",
+        builtin_reader_guid, e
+      ))),
+    }
+  }
+
+  /// Deletes a DataReader of this Subscriber. Its announcement is disposed, so that
+  /// remote participants see it disappear, and the resources it used are released.
+  ///
+  /// Returns `PreconditionNotMet` if the DataReader belongs to some other Subscriber.
+  /// It is then deleted when dropped, like DataReaders are in any case.
+  pub fn delete_datareader<D, SA>(&self, data_reader: WithKeyDataReader<D, SA>) -> Result<()>
   where
     D: Keyed + DeserializeOwned,
     SA: DeserializerAdapter<D>,
   {
-    todo!()
-    // TO think: Is this really necessary? Because the caller would have to know
-    // types D and SA. Sould we just trust whoever creates DataReaders to also remember them?
+    self.remove_reader(data_reader.get_guid())
+  }
+
+  /// Deletes a DataReader for a topic without key, see
+  /// [delete_datareader](#method.delete_datareader).
+  pub fn delete_datareader_no_key<D, SA>(&self, data_reader: NoKeyDataReader<D, SA>) -> Result<()>
+  where
+    D: DeserializeOwned,
+    SA: DeserializerAdapter<D>,
+  {
+    self.remove_reader(data_reader.get_guid())
+  }
+
+  /// Deletes all DataReaders of this Subscriber, see
+  /// [delete_datareader](#method.delete_datareader).
+  pub fn delete_contained_entities(&self) -> Result<()> {
+    let datareaders: Vec<GUID> = self.lock_datareaders().iter().copied().collect();
+    let mut result = Ok(());
+    for guid in datareaders {
+      if let Err(e) = self.remove_reader(guid) {
+        result = Err(e);
+      }
+    }
+    result
+  }
+
+  // Whether some DataReader of this Subscriber is not deleted yet.
+  pub(crate) fn has_datareaders(&self) -> bool {
+    let datareaders = self.lock_datareaders();
+    let db = read_lock(&self.discovery_db);
+    datareaders.iter().any(|g| db.is_local_topic_reader(*g))
+  }
+
+  // Deletes our DataReader, unless the participant already has. Err(PreconditionNotMet)
+  // if it is not ours, or deleted by us already.
+  pub(crate) fn remove_reader(&self, guid: GUID) -> Result<()> {
+    if !self.lock_datareaders().remove(&guid) {
+      return Err(Error::precondition_not_met(format!(
+        "DataReader {:?} is not of this Subscriber",
+        guid
+      )));
+    }
+    match self.get_participant() {
+      Some(dp) => match dp.delete_local_reader(guid) {
+        Err(Error::PreconditionNotMet { .. }) => Ok(()),
+        r => r,
+      },
+      // everything went with the participant
+      None => Ok(()),
+    }
+  }
+
+  // as lock_datawriters
+  fn lock_datareaders(&self) -> std::sync::MutexGuard<'_, HashSet<GUID>> {
+    self
+      .datareaders
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+  }
+
+  /// Finds a DataReader of this Subscriber by the name of its topic. If there are
+  /// several, this is the one with the smallest GUID. Returns None if there is none,
+  /// also after it has been deleted or dropped.
+  ///
+  /// Like [Publisher::lookup_datawriter](struct.Publisher.html#method.lookup_datawriter),
+  /// this only describes the DataReader, because it is owned by whoever created it.
+  pub fn lookup_datareader(&self, topic_name: &str) -> Option<EndpointSnapshot> {
+    self
+      .get_datareaders()
+      .into_iter()
+      .find(|r| r.topic_name == topic_name)
+  }
+
+  /// Describes all DataReaders of this Subscriber that are not deleted, ordered by
+  /// GUID. See [lookup_datareader](#method.lookup_datareader).
+  pub fn get_datareaders(&self) -> Vec<EndpointSnapshot> {
+    let datareaders = self.lock_datareaders();
+    let db = read_lock(&self.discovery_db);
+    let mut result: Vec<EndpointSnapshot> = datareaders
+      .iter()
+      .filter_map(|g| {
+        db.get_local_topic_reader(*g)
+          .map(|r| EndpointSnapshot::from_reader(*g, r))
+      })
+      .collect();
+    result.sort_by_key(|r| r.guid);
+    result
   }
 
   /// Returns [DomainParticipant](struct.DomainParticipant.html) if it is sill alive.
@@ -686,7 +1053,7 @@ impl<'s> Subscriber {
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::Subscriber;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   ///
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
@@ -4,7 +4,10 @@ use crate::{
   messages::submessages::submessage_elements::{
     parameter_list::ParameterList, RepresentationIdentifier,
   },
-  structure::{parameter_id::ParameterId, inline_qos::StatusInfo},
+  structure::{
+    parameter_id::ParameterId,
+    inline_qos::{SampleIdentity, StatusInfo},
+  },
 };
 
 // This is to be implemented by all DomanParticipant, Publisher, Subscriber, DataWriter, DataReader, Topic
@@ -16,7 +19,7 @@ pub trait HasQoSPolicy {
 
 /// DDS spec 2.3.3 defines this as "long" with named constants from 0 to 22.
 /// numbering is from IDL PSM, but it should be unnecessary at the Rust application interface
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum QosPolicyId {
   //Invalid  // We should represent this using Option<QosPolicyId> where needed
   //UserData,  // 1
@@ -35,7 +38,7 @@ pub enum QosPolicyId {
   ResourceLimits,
   //EntityFactory, // 15
   //WriterDataLifeCycle,
-  //ReaderDataLifeCycle, // 17
+  ReaderDataLifecycle, // 17
   //TopicData, // 18
   //GroupData,
   //TransportPriority, // 20
@@ -57,6 +60,8 @@ pub struct QosPolicyBuilder {
   history: Option<policy::History>,
   resource_limits: Option<policy::ResourceLimits>,
   lifespan: Option<policy::Lifespan>,
+  reader_data_lifecycle: Option<policy::ReaderDataLifecycle>,
+  writer_data_lifecycle: Option<policy::WriterDataLifecycle>,
 }
 
 impl QosPolicyBuilder {
@@ -74,6 +79,8 @@ impl QosPolicyBuilder {
       history: None,
       resource_limits: None,
       lifespan: None,
+      reader_data_lifecycle: None,
+      writer_data_lifecycle: None,
     }
   }
 
@@ -146,6 +153,22 @@ impl QosPolicyBuilder {
     self
   }
 
+  pub const fn reader_data_lifecycle(
+    mut self,
+    reader_data_lifecycle: policy::ReaderDataLifecycle,
+  ) -> QosPolicyBuilder {
+    self.reader_data_lifecycle = Some(reader_data_lifecycle);
+    self
+  }
+
+  pub const fn writer_data_lifecycle(
+    mut self,
+    writer_data_lifecycle: policy::WriterDataLifecycle,
+  ) -> QosPolicyBuilder {
+    self.writer_data_lifecycle = Some(writer_data_lifecycle);
+    self
+  }
+
   pub const fn build(self) -> QosPolicies {
     QosPolicies {
       durability: self.durability,
@@ -160,6 +183,8 @@ impl QosPolicyBuilder {
       history: self.history,
       resource_limits: self.resource_limits,
       lifespan: self.lifespan,
+      reader_data_lifecycle: self.reader_data_lifecycle,
+      writer_data_lifecycle: self.writer_data_lifecycle,
     }
   }
 }
@@ -180,6 +205,8 @@ pub struct QosPolicies {
   pub(crate) history: Option<policy::History>,
   pub(crate) resource_limits: Option<policy::ResourceLimits>,
   pub(crate) lifespan: Option<policy::Lifespan>,
+  pub(crate) reader_data_lifecycle: Option<policy::ReaderDataLifecycle>,
+  pub(crate) writer_data_lifecycle: Option<policy::WriterDataLifecycle>,
 }
 
 impl QosPolicies {
@@ -198,6 +225,8 @@ impl QosPolicies {
       history: None,
       resource_limits: None,
       lifespan: None,
+      reader_data_lifecycle: None,
+      writer_data_lifecycle: None,
     }
   }
 
@@ -248,6 +277,95 @@ impl QosPolicies {
   pub const fn lifespan(&self) -> Option<policy::Lifespan> {
     self.lifespan
   }
+
+  pub const fn reader_data_lifecycle(&self) -> Option<policy::ReaderDataLifecycle> {
+    self.reader_data_lifecycle
+  }
+
+  pub const fn writer_data_lifecycle(&self) -> Option<policy::WriterDataLifecycle> {
+    self.writer_data_lifecycle
+  }
+
+  /// Checks if these QoS, offered by a DataWriter, are compatible with the QoS
+  /// requested by a DataReader (DDS spec 2.2.3 "Requested vs Offered").
+  /// Returns the first incompatible policy, or None if they are compatible.
+  /// Policies that are not set on both sides are not checked.
+  pub fn compliance_failure_wrt(&self, requested: &QosPolicies) -> Option<QosPolicyId> {
+    use policy::*;
+
+    fn durability_rank(d: Durability) -> u8 {
+      match d {
+        Durability::Volatile => 0,
+        Durability::TransientLocal => 1,
+        Durability::Transient => 2,
+        Durability::Persistent => 3,
+      }
+    }
+    fn access_scope_rank(a: PresentationAccessScope) -> u8 {
+      match a {
+        PresentationAccessScope::Instance => 0,
+        PresentationAccessScope::Topic => 1,
+        PresentationAccessScope::Group => 2,
+      }
+    }
+    fn liveliness_rank_and_lease(l: Liveliness) -> (u8, crate::structure::duration::Duration) {
+      match l {
+        Liveliness::Automatic { lease_duration } => (0, lease_duration),
+        Liveliness::ManualByParticipant { lease_duration } => (1, lease_duration),
+        Liveliness::ManualByTopic { lease_duration } => (2, lease_duration),
+      }
+    }
+
+    if let (Some(o), Some(r)) = (self.durability, requested.durability) {
+      if durability_rank(o) < durability_rank(r) {
+        return Some(QosPolicyId::Durability);
+      }
+    }
+    if let (Some(o), Some(r)) = (self.presentation, requested.presentation) {
+      if access_scope_rank(o.access_scope) < access_scope_rank(r.access_scope)
+        || (r.coherent_access && !o.coherent_access)
+        || (r.ordered_access && !o.ordered_access)
+      {
+        return Some(QosPolicyId::Presentation);
+      }
+    }
+    if let (Some(o), Some(r)) = (self.deadline, requested.deadline) {
+      if o > r {
+        return Some(QosPolicyId::Deadline);
+      }
+    }
+    if let (Some(o), Some(r)) = (self.latency_budget, requested.latency_budget) {
+      if o.duration > r.duration {
+        return Some(QosPolicyId::LatencyBudget);
+      }
+    }
+    if let (Some(o), Some(r)) = (self.ownership, requested.ownership) {
+      let o_exclusive = matches!(o, Ownership::Exclusive { .. });
+      let r_exclusive = matches!(r, Ownership::Exclusive { .. });
+      if o_exclusive != r_exclusive {
+        return Some(QosPolicyId::Ownership);
+      }
+    }
+    if let (Some(o), Some(r)) = (self.liveliness, requested.liveliness) {
+      let (o_kind, o_lease) = liveliness_rank_and_lease(o);
+      let (r_kind, r_lease) = liveliness_rank_and_lease(r);
+      if o_kind < r_kind || o_lease > r_lease {
+        return Some(QosPolicyId::Liveliness);
+      }
+    }
+    if let (Some(o), Some(r)) = (self.reliability, requested.reliability) {
+      if let (Reliability::BestEffort, Reliability::Reliable { .. }) = (o, r) {
+        return Some(QosPolicyId::Reliability);
+      }
+    }
+    if let (Some(o), Some(r)) = (self.destination_order, requested.destination_order) {
+      if let (DestinationOrder::ByReceptionTimestamp, DestinationOrder::BySourceTimeStamp) = (o, r)
+      {
+        return Some(QosPolicyId::DestinationOrder);
+      }
+    }
+    None
+  }
 }
 
 // put these into a submodule to avoid repeating the word "policy" or "qospolicy"
@@ -371,6 +489,25 @@ pub mod policy {
     pub max_samples_per_instance: i32,
   }
 
+  /// DDS 2.2.3.22 READER_DATA_LIFECYCLE
+  ///
+  /// How long the changes of an instance are kept in the DDSCache after it was
+  /// unregistered by its writers or disposed. DURATION_INFINITE keeps them.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct ReaderDataLifecycle {
+    pub autopurge_nowriter_samples_delay: Duration,
+    pub autopurge_disposed_samples_delay: Duration,
+  }
+
+  /// DDS 2.2.3.21 WRITER_DATA_LIFECYCLE
+  ///
+  /// Whether a DataWriter disposes its instances when it unregisters them, and all of
+  /// them when it is dropped. DataWriters take it from the QoS of their topic.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct WriterDataLifecycle {
+    pub autodispose_unregistered_instances: bool,
+  }
+
   #[derive(Serialize, Deserialize)]
   pub(crate) struct QosData<D>
   where
@@ -423,8 +560,6 @@ pub mod policy {
     autoenable_created_entities: bool,
   }
   */
-  // WriterDataLifecycle
-  // ReaderDataLifeCycle
 
   // DurabilityService
 }
@@ -450,21 +585,35 @@ impl InlineQos {
     Ok(status_info)
   }
 
+  // KeyHash is optional. Its octets do not depend on endianness.
   pub fn key_hash(
     params: &ParameterList,
-    rep_id: RepresentationIdentifier,
-  ) -> std::result::Result<KeyHash, crate::serialization::error::Error> {
+  ) -> std::result::Result<Option<KeyHash>, crate::serialization::error::Error> {
     let key_hash = params
       .parameters
       .iter()
-      .find(|p| p.parameter_id == ParameterId::PID_KEY_HASH)
-      .clone();
-    let key_hash = match key_hash {
-      Some(p) => KeyHash::from_cdr_bytes(&p.value, rep_id)?,
-      None => KeyHash::empty(),
-    };
+      .find(|p| p.parameter_id == ParameterId::PID_KEY_HASH);
+    match key_hash {
+      Some(p) => Ok(Some(KeyHash::from_octets(&p.value)?)),
+      None => Ok(None),
+    }
+  }
 
-    Ok(key_hash)
+  // RelatedSampleIdentity is optional and may use either the standard or the
+  // vendor specific parameter id. Received parameters are stored in little endian.
+  pub fn related_sample_identity(
+    params: &ParameterList,
+  ) -> std::result::Result<Option<SampleIdentity>, crate::serialization::error::Error> {
+    let identity = params.parameters.iter().find(|p| {
+      p.parameter_id == ParameterId::PID_RELATED_SAMPLE_IDENTITY
+        || p.parameter_id == ParameterId::PID_CUSTOM_RELATED_SAMPLE_IDENTITY
+    });
+    match identity {
+      Some(p) => Ok(Some(
+        SampleIdentity::from_octets::<byteorder::LittleEndian>(&p.value)?,
+      )),
+      None => Ok(None),
+    }
   }
 }
 
@@ -473,5 +622,49 @@ impl InlineQos {
 
 // TODO: helper function to check is a QosPolices object is inconsistent (by itself)
 
-// TODO: helper function to check if two QosPolicies: Reequested and Offered are
-// compatible, according to DDS spec 2.2.3
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::structure::duration::Duration;
+
+  #[test]
+  fn qos_compliance_failure() {
+    let reliable = QosPolicyBuilder::new()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .build();
+    let best_effort = QosPolicyBuilder::new()
+      .reliability(policy::Reliability::BestEffort)
+      .build();
+    assert_eq!(reliable.compliance_failure_wrt(&best_effort), None);
+    assert_eq!(
+      best_effort.compliance_failure_wrt(&reliable),
+      Some(QosPolicyId::Reliability)
+    );
+
+    let volatile = QosPolicyBuilder::new()
+      .durability(policy::Durability::Volatile)
+      .deadline(policy::Deadline(Duration::from_secs(1)))
+      .build();
+    let transient_local = QosPolicyBuilder::new()
+      .durability(policy::Durability::TransientLocal)
+      .deadline(policy::Deadline(Duration::from_secs(2)))
+      .build();
+    assert_eq!(
+      transient_local.compliance_failure_wrt(&volatile),
+      Some(QosPolicyId::Deadline)
+    );
+    assert_eq!(
+      volatile.compliance_failure_wrt(&transient_local),
+      Some(QosPolicyId::Durability)
+    );
+
+    // unset policies are not checked
+    assert_eq!(
+      QosPolicies::qos_none().compliance_failure_wrt(&reliable),
+      None
+    );
+    assert_eq!(best_effort.compliance_failure_wrt(&volatile), None);
+  }
+}
@@ -0,0 +1,1057 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "qos_profiles")]
+use std::path::Path;
+
+#[cfg(feature = "qos_profiles")]
+use log::warn;
+
+use crate::{
+  dds::qos::{policy::*, QosPolicies, QosPolicyBuilder},
+  structure::duration::Duration,
+};
+#[cfg(feature = "qos_profiles")]
+use crate::dds::values::result::{Error, Result};
+
+/// Named set of QoS for the entities of an application, as in a DDS XML QoS profile.
+///
+/// Policies that the profile does not mention are None. `initial_peers` come from the
+/// `participant_qos` of the profile, see
+/// [DomainParticipantBuilder::qos_profile](struct.DomainParticipantBuilder.html#method.qos_profile).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QosProfile {
+  pub topic_qos: QosPolicies,
+  pub publisher_qos: QosPolicies,
+  pub subscriber_qos: QosPolicies,
+  pub datawriter_qos: QosPolicies,
+  pub datareader_qos: QosPolicies,
+  /// Hosts that receive our participant announcements, as in
+  /// [DiscoveryConfig](struct.DiscoveryConfig.html).
+  pub initial_peers: Vec<String>,
+}
+
+impl Default for QosProfile {
+  fn default() -> QosProfile {
+    QosProfile {
+      topic_qos: QosPolicyBuilder::new().build(),
+      publisher_qos: QosPolicyBuilder::new().build(),
+      subscriber_qos: QosPolicyBuilder::new().build(),
+      datawriter_qos: QosPolicyBuilder::new().build(),
+      datareader_qos: QosPolicyBuilder::new().build(),
+      initial_peers: Vec::new(),
+    }
+  }
+}
+
+/// QoS profiles read from a file in the DDS XML format used by other DDS
+/// implementations. Loading needs the `qos_profiles` feature.
+///
+/// A profile is named `library::profile` after its `qos_library`, or just `profile`
+/// if it is not in a library. Lookups also accept just the profile name. A profile
+/// with a `base_name` starts from the policies of that earlier profile.
+///
+/// The supported policies are those of [QosPolicies](qos/struct.QosPolicies.html),
+/// `ownership_strength` and the `initial_peers` of `participant_qos/discovery`.
+/// Other elements are logged and skipped. Durations may be `DURATION_INFINITY` and
+/// resource limits `LENGTH_UNLIMITED`.
+///
+/// # Examples
+/// ```
+/// # #[cfg(feature = "qos_profiles")]
+/// # {
+/// # use rustdds::dds::{QosProfiles, qos::policy::Reliability};
+/// let xml = r#"
+///   <dds>
+///     <qos_library name="fleet">
+///       <qos_profile name="telemetry">
+///         <datareader_qos>
+///           <reliability><kind>BEST_EFFORT_RELIABILITY_QOS</kind></reliability>
+///           <history><kind>KEEP_LAST_HISTORY_QOS</kind><depth>5</depth></history>
+///         </datareader_qos>
+///       </qos_profile>
+///     </qos_library>
+///   </dds>"#;
+/// let profiles = QosProfiles::from_xml(xml).unwrap();
+/// let qos = profiles.datareader_qos("telemetry").unwrap();
+/// assert_eq!(qos.reliability(), Some(Reliability::BestEffort));
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct QosProfiles {
+  profiles: BTreeMap<String, QosProfile>,
+  default_profile: Option<String>,
+}
+
+impl QosProfiles {
+  pub fn new() -> QosProfiles {
+    QosProfiles::default()
+  }
+
+  /// Reads profiles from an XML file. Returns `BadParameter` if the file cannot be
+  /// read or is not a valid profile file.
+  #[cfg(feature = "qos_profiles")]
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<QosProfiles> {
+    match std::fs::read_to_string(path.as_ref()) {
+      Ok(xml) => QosProfiles::from_xml(&xml),
+      Err(e) => Err(Error::bad_parameter(format!(
+        "cannot read QoS profiles {}: {}",
+        path.as_ref().display(),
+        e
+      ))),
+    }
+  }
+
+  /// Reads profiles from XML text. See [load](#method.load).
+  #[cfg(feature = "qos_profiles")]
+  pub fn from_xml(xml: &str) -> Result<QosProfiles> {
+    xml_reader::read_profiles(xml)
+  }
+
+  /// Writes the profiles as XML that [from_xml](#method.from_xml) reads back.
+  pub fn to_xml(&self) -> String {
+    xml_writer::write_profiles(self)
+  }
+
+  /// Adds or replaces a profile.
+  pub fn insert(&mut self, name: &str, profile: QosProfile) {
+    self.profiles.insert(name.to_string(), profile);
+  }
+
+  /// Marks the profile used by [default_profile](#method.default_profile).
+  pub fn set_default_profile(&mut self, name: &str) {
+    self.default_profile = Some(name.to_string());
+  }
+
+  /// Names of all profiles, with their library.
+  pub fn names(&self) -> impl Iterator<Item = &String> {
+    self.profiles.keys()
+  }
+
+  pub fn profile(&self, name: &str) -> Option<&QosProfile> {
+    self.profiles.get(self.full_name(name)?)
+  }
+
+  /// The profile marked with `is_default_qos="true"`.
+  pub fn default_profile(&self) -> Option<&QosProfile> {
+    self.profile(self.default_profile.as_ref()?)
+  }
+
+  pub fn topic_qos(&self, name: &str) -> Option<QosPolicies> {
+    self.profile(name).map(|p| p.topic_qos.clone())
+  }
+
+  pub fn publisher_qos(&self, name: &str) -> Option<QosPolicies> {
+    self.profile(name).map(|p| p.publisher_qos.clone())
+  }
+
+  pub fn subscriber_qos(&self, name: &str) -> Option<QosPolicies> {
+    self.profile(name).map(|p| p.subscriber_qos.clone())
+  }
+
+  pub fn datawriter_qos(&self, name: &str) -> Option<QosPolicies> {
+    self.profile(name).map(|p| p.datawriter_qos.clone())
+  }
+
+  pub fn datareader_qos(&self, name: &str) -> Option<QosPolicies> {
+    self.profile(name).map(|p| p.datareader_qos.clone())
+  }
+
+  // The full name of a profile given with or without its library.
+  fn full_name<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+    if self.profiles.contains_key(name) {
+      return Some(name);
+    }
+    self
+      .profiles
+      .keys()
+      .find(|k| k.rsplit("::").next() == Some(name))
+      .map(|k| k.as_str())
+  }
+}
+
+// Names of the policy kinds in DDS XML.
+const DURATION_INFINITY: &str = "DURATION_INFINITY";
+const LENGTH_UNLIMITED: &str = "LENGTH_UNLIMITED";
+
+fn durability_name(d: Durability) -> &'static str {
+  match d {
+    Durability::Volatile => "VOLATILE_DURABILITY_QOS",
+    Durability::TransientLocal => "TRANSIENT_LOCAL_DURABILITY_QOS",
+    Durability::Transient => "TRANSIENT_DURABILITY_QOS",
+    Durability::Persistent => "PERSISTENT_DURABILITY_QOS",
+  }
+}
+
+fn access_scope_name(a: PresentationAccessScope) -> &'static str {
+  match a {
+    PresentationAccessScope::Instance => "INSTANCE_PRESENTATION_QOS",
+    PresentationAccessScope::Topic => "TOPIC_PRESENTATION_QOS",
+    PresentationAccessScope::Group => "GROUP_PRESENTATION_QOS",
+  }
+}
+
+fn ownership_name(o: Ownership) -> &'static str {
+  match o {
+    Ownership::Shared => "SHARED_OWNERSHIP_QOS",
+    Ownership::Exclusive { .. } => "EXCLUSIVE_OWNERSHIP_QOS",
+  }
+}
+
+fn liveliness_name(l: Liveliness) -> &'static str {
+  match l {
+    Liveliness::Automatic { .. } => "AUTOMATIC_LIVELINESS_QOS",
+    Liveliness::ManualByParticipant { .. } => "MANUAL_BY_PARTICIPANT_LIVELINESS_QOS",
+    Liveliness::ManualByTopic { .. } => "MANUAL_BY_TOPIC_LIVELINESS_QOS",
+  }
+}
+
+fn reliability_name(r: Reliability) -> &'static str {
+  match r {
+    Reliability::BestEffort => "BEST_EFFORT_RELIABILITY_QOS",
+    Reliability::Reliable { .. } => "RELIABLE_RELIABILITY_QOS",
+  }
+}
+
+fn destination_order_name(d: DestinationOrder) -> &'static str {
+  match d {
+    DestinationOrder::ByReceptionTimestamp => "BY_RECEPTION_TIMESTAMP_DESTINATIONORDER_QOS",
+    DestinationOrder::BySourceTimeStamp => "BY_SOURCE_TIMESTAMP_DESTINATIONORDER_QOS",
+  }
+}
+
+fn history_name(h: History) -> &'static str {
+  match h {
+    History::KeepLast { .. } => "KEEP_LAST_HISTORY_QOS",
+    History::KeepAll => "KEEP_ALL_HISTORY_QOS",
+  }
+}
+
+mod xml_writer {
+  use std::fmt::Write;
+
+  use super::*;
+
+  pub(super) fn write_profiles(profiles: &QosProfiles) -> String {
+    let mut xml = String::from("<dds>\n");
+    // profiles of the same library are next to each other in the map
+    let mut library: Option<&str> = None;
+    for (full_name, profile) in profiles.profiles.iter() {
+      let (lib, name) = match full_name.rfind("::") {
+        Some(i) => (Some(&full_name[..i]), &full_name[i + 2..]),
+        None => (None, full_name.as_str()),
+      };
+      if lib != library {
+        if library.is_some() {
+          xml.push_str("  </qos_library>\n");
+        }
+        if let Some(lib) = lib {
+          writeln!(xml, "  <qos_library name=\"{}\">", escape(lib)).unwrap();
+        }
+        library = lib;
+      }
+      let indent = if library.is_some() { "    " } else { "  " };
+      let default = if profiles.default_profile.as_ref() == Some(full_name) {
+        " is_default_qos=\"true\""
+      } else {
+        ""
+      };
+      writeln!(
+        xml,
+        "{}<qos_profile name=\"{}\"{}>",
+        indent,
+        escape(name),
+        default
+      )
+      .unwrap();
+      write_profile(&mut xml, profile, &format!("{}  ", indent));
+      writeln!(xml, "{}</qos_profile>", indent).unwrap();
+    }
+    if library.is_some() {
+      xml.push_str("  </qos_library>\n");
+    }
+    xml.push_str("</dds>\n");
+    xml
+  }
+
+  fn write_profile(xml: &mut String, profile: &QosProfile, indent: &str) {
+    if !profile.initial_peers.is_empty() {
+      writeln!(xml, "{}<participant_qos>", indent).unwrap();
+      writeln!(xml, "{}  <discovery>", indent).unwrap();
+      writeln!(xml, "{}    <initial_peers>", indent).unwrap();
+      for peer in profile.initial_peers.iter() {
+        writeln!(xml, "{}      <element>{}</element>", indent, escape(peer)).unwrap();
+      }
+      writeln!(xml, "{}    </initial_peers>", indent).unwrap();
+      writeln!(xml, "{}  </discovery>", indent).unwrap();
+      writeln!(xml, "{}</participant_qos>", indent).unwrap();
+    }
+    let entities = [
+      ("topic_qos", &profile.topic_qos),
+      ("publisher_qos", &profile.publisher_qos),
+      ("subscriber_qos", &profile.subscriber_qos),
+      ("datawriter_qos", &profile.datawriter_qos),
+      ("datareader_qos", &profile.datareader_qos),
+    ];
+    for (tag, qos) in entities.iter() {
+      let mut policies = String::new();
+      write_policies(&mut policies, qos);
+      if policies.is_empty() {
+        continue;
+      }
+      writeln!(xml, "{}<{}>", indent, tag).unwrap();
+      for line in policies.lines() {
+        writeln!(xml, "{}  {}", indent, line).unwrap();
+      }
+      writeln!(xml, "{}</{}>", indent, tag).unwrap();
+    }
+  }
+
+  fn write_policies(xml: &mut String, qos: &QosPolicies) {
+    if let Some(d) = qos.durability {
+      policy(xml, "durability", &[kind(durability_name(d))]);
+    }
+    if let Some(p) = qos.presentation {
+      policy(
+        xml,
+        "presentation",
+        &[
+          value("access_scope", access_scope_name(p.access_scope)),
+          value("coherent_access", &p.coherent_access.to_string()),
+          value("ordered_access", &p.ordered_access.to_string()),
+        ],
+      );
+    }
+    if let Some(Deadline(period)) = qos.deadline {
+      policy(xml, "deadline", &[duration("period", period)]);
+    }
+    if let Some(l) = qos.latency_budget {
+      policy(xml, "latency_budget", &[duration("duration", l.duration)]);
+    }
+    if let Some(o) = qos.ownership {
+      policy(xml, "ownership", &[kind(ownership_name(o))]);
+      if let Ownership::Exclusive { strength } = o {
+        policy(
+          xml,
+          "ownership_strength",
+          &[value("value", &strength.to_string())],
+        );
+      }
+    }
+    if let Some(l) = qos.liveliness {
+      let lease_duration = match l {
+        Liveliness::Automatic { lease_duration }
+        | Liveliness::ManualByParticipant { lease_duration }
+        | Liveliness::ManualByTopic { lease_duration } => lease_duration,
+      };
+      policy(
+        xml,
+        "liveliness",
+        &[
+          kind(liveliness_name(l)),
+          duration("lease_duration", lease_duration),
+        ],
+      );
+    }
+    if let Some(t) = qos.time_based_filter {
+      policy(
+        xml,
+        "time_based_filter",
+        &[duration("minimum_separation", t.minimum_separation)],
+      );
+    }
+    if let Some(r) = qos.reliability {
+      let mut elements = vec![kind(reliability_name(r))];
+      if let Reliability::Reliable { max_blocking_time } = r {
+        elements.push(duration("max_blocking_time", max_blocking_time));
+      }
+      policy(xml, "reliability", &elements);
+    }
+    if let Some(d) = qos.destination_order {
+      policy(xml, "destination_order", &[kind(destination_order_name(d))]);
+    }
+    if let Some(h) = qos.history {
+      let mut elements = vec![kind(history_name(h))];
+      if let History::KeepLast { depth } = h {
+        elements.push(value("depth", &depth.to_string()));
+      }
+      policy(xml, "history", &elements);
+    }
+    if let Some(r) = qos.resource_limits {
+      policy(
+        xml,
+        "resource_limits",
+        &[
+          value("max_samples", &length(r.max_samples)),
+          value("max_instances", &length(r.max_instances)),
+          value(
+            "max_samples_per_instance",
+            &length(r.max_samples_per_instance),
+          ),
+        ],
+      );
+    }
+    if let Some(l) = qos.lifespan {
+      policy(xml, "lifespan", &[duration("duration", l.duration)]);
+    }
+    if let Some(r) = qos.reader_data_lifecycle {
+      policy(
+        xml,
+        "reader_data_lifecycle",
+        &[
+          duration(
+            "autopurge_nowriter_samples_delay",
+            r.autopurge_nowriter_samples_delay,
+          ),
+          duration(
+            "autopurge_disposed_samples_delay",
+            r.autopurge_disposed_samples_delay,
+          ),
+        ],
+      );
+    }
+    if let Some(w) = qos.writer_data_lifecycle {
+      policy(
+        xml,
+        "writer_data_lifecycle",
+        &[value(
+          "autodispose_unregistered_instances",
+          &w.autodispose_unregistered_instances.to_string(),
+        )],
+      );
+    }
+  }
+
+  fn policy(xml: &mut String, tag: &str, elements: &[String]) {
+    writeln!(xml, "<{}>", tag).unwrap();
+    for e in elements {
+      writeln!(xml, "  {}", e).unwrap();
+    }
+    writeln!(xml, "</{}>", tag).unwrap();
+  }
+
+  fn kind(name: &str) -> String {
+    value("kind", name)
+  }
+
+  fn value(tag: &str, value: &str) -> String {
+    format!("<{}>{}</{}>", tag, value, tag)
+  }
+
+  fn length(l: i32) -> String {
+    if l < 0 {
+      LENGTH_UNLIMITED.to_string()
+    } else {
+      l.to_string()
+    }
+  }
+
+  fn duration(tag: &str, d: Duration) -> String {
+    if d == Duration::DURATION_INFINITE {
+      return format!(
+        "<{}><sec>{}</sec><nanosec>{}</nanosec></{}>",
+        tag, DURATION_INFINITY, DURATION_INFINITY, tag
+      );
+    }
+    // Rounded, so that reading the nanoseconds back gives the same fraction.
+    let ticks = d.to_ticks();
+    let mut sec = ticks >> 32;
+    let mut nanosec = ((ticks & 0xFFFF_FFFF) * 1_000_000_000 + (1 << 31)) >> 32;
+    if nanosec == 1_000_000_000 {
+      sec += 1;
+      nanosec = 0;
+    }
+    format!(
+      "<{}><sec>{}</sec><nanosec>{}</nanosec></{}>",
+      tag, sec, nanosec, tag
+    )
+  }
+
+  fn escape(text: &str) -> String {
+    text
+      .replace('&', "&amp;")
+      .replace('<', "&lt;")
+      .replace('>', "&gt;")
+      .replace('"', "&quot;")
+  }
+}
+
+#[cfg(feature = "qos_profiles")]
+mod xml_reader {
+  use roxmltree::{Document, Node};
+
+  use super::*;
+
+  pub(super) fn read_profiles(xml: &str) -> Result<QosProfiles> {
+    let document = match Document::parse(xml) {
+      Ok(d) => d,
+      Err(e) => {
+        return Err(Error::bad_parameter(format!(
+          "QoS profiles are not valid XML: {}",
+          e
+        )))
+      }
+    };
+    let root = document.root_element();
+    if root.tag_name().name() != "dds" {
+      return Err(Error::bad_parameter(format!(
+        "QoS profiles root element is <{}>, not <dds>",
+        root.tag_name().name()
+      )));
+    }
+
+    let mut profiles = QosProfiles::new();
+    for node in elements(root) {
+      match node.tag_name().name() {
+        "qos_library" => {
+          let library = name_of(node)?;
+          for profile in elements(node) {
+            if profile.tag_name().name() == "qos_profile" {
+              read_profile(&mut profiles, profile, Some(library))?;
+            } else {
+              skip(profile);
+            }
+          }
+        }
+        "qos_profile" => read_profile(&mut profiles, node, None)?,
+        _ => skip(node),
+      }
+    }
+    Ok(profiles)
+  }
+
+  fn read_profile(profiles: &mut QosProfiles, node: Node, library: Option<&str>) -> Result<()> {
+    let name = name_of(node)?;
+    let full_name = match library {
+      Some(library) => format!("{}::{}", library, name),
+      None => name.to_string(),
+    };
+    let mut profile = match node.attribute("base_name") {
+      Some(base) => match profiles.profile(base) {
+        Some(p) => p.clone(),
+        None => {
+          return Err(Error::bad_parameter(format!(
+            "base profile {:?} of QoS profile {:?} is not defined before it",
+            base, full_name
+          )))
+        }
+      },
+      None => QosProfile::default(),
+    };
+
+    for entity in elements(node) {
+      match entity.tag_name().name() {
+        "participant_qos" => read_participant(&mut profile, entity),
+        "topic_qos" => read_policies(&mut profile.topic_qos, entity)?,
+        "publisher_qos" => read_policies(&mut profile.publisher_qos, entity)?,
+        "subscriber_qos" => read_policies(&mut profile.subscriber_qos, entity)?,
+        "datawriter_qos" => read_policies(&mut profile.datawriter_qos, entity)?,
+        "datareader_qos" => read_policies(&mut profile.datareader_qos, entity)?,
+        _ => skip(entity),
+      }
+    }
+
+    if node.attribute("is_default_qos") == Some("true") {
+      profiles.set_default_profile(&full_name);
+    }
+    profiles.insert(&full_name, profile);
+    Ok(())
+  }
+
+  fn read_participant(profile: &mut QosProfile, node: Node) {
+    for setting in elements(node) {
+      if setting.tag_name().name() != "discovery" {
+        skip(setting);
+        continue;
+      }
+      for discovery in elements(setting) {
+        if discovery.tag_name().name() != "initial_peers" {
+          skip(discovery);
+          continue;
+        }
+        profile.initial_peers = Vec::new();
+        for peer in elements(discovery) {
+          profile.initial_peers.push(text(peer).to_string());
+        }
+      }
+    }
+  }
+
+  fn read_policies(qos: &mut QosPolicies, node: Node) -> Result<()> {
+    let mut ownership_strength = None;
+    for p in elements(node) {
+      match p.tag_name().name() {
+        "durability" => {
+          qos.durability = Some(match child_text(p, "kind") {
+            None | Some("VOLATILE_DURABILITY_QOS") => Durability::Volatile,
+            Some("TRANSIENT_LOCAL_DURABILITY_QOS") => Durability::TransientLocal,
+            Some("TRANSIENT_DURABILITY_QOS") => Durability::Transient,
+            Some("PERSISTENT_DURABILITY_QOS") => Durability::Persistent,
+            Some(other) => return invalid(p, other),
+          })
+        }
+        "presentation" => {
+          let access_scope = match child_text(p, "access_scope") {
+            None | Some("INSTANCE_PRESENTATION_QOS") => PresentationAccessScope::Instance,
+            Some("TOPIC_PRESENTATION_QOS") => PresentationAccessScope::Topic,
+            Some("GROUP_PRESENTATION_QOS") => PresentationAccessScope::Group,
+            Some(other) => return invalid(p, other),
+          };
+          qos.presentation = Some(Presentation {
+            access_scope,
+            coherent_access: child_bool(p, "coherent_access")?.unwrap_or(false),
+            ordered_access: child_bool(p, "ordered_access")?.unwrap_or(false),
+          })
+        }
+        "deadline" => {
+          qos.deadline = Some(Deadline(
+            child_duration(p, "period")?.unwrap_or(Duration::DURATION_INFINITE),
+          ))
+        }
+        "latency_budget" => {
+          qos.latency_budget = Some(LatencyBudget {
+            duration: child_duration(p, "duration")?.unwrap_or(Duration::DURATION_ZERO),
+          })
+        }
+        "ownership" => {
+          qos.ownership = Some(match child_text(p, "kind") {
+            None | Some("SHARED_OWNERSHIP_QOS") => Ownership::Shared,
+            Some("EXCLUSIVE_OWNERSHIP_QOS") => Ownership::Exclusive {
+              // from the base profile, if any
+              strength: match qos.ownership {
+                Some(Ownership::Exclusive { strength }) => strength,
+                _ => 0,
+              },
+            },
+            Some(other) => return invalid(p, other),
+          })
+        }
+        "ownership_strength" => ownership_strength = Some(child_int(p, "value")?.unwrap_or(0)),
+        "liveliness" => {
+          let lease_duration =
+            child_duration(p, "lease_duration")?.unwrap_or(Duration::DURATION_INFINITE);
+          qos.liveliness = Some(match child_text(p, "kind") {
+            None | Some("AUTOMATIC_LIVELINESS_QOS") => Liveliness::Automatic { lease_duration },
+            Some("MANUAL_BY_PARTICIPANT_LIVELINESS_QOS") => {
+              Liveliness::ManualByParticipant { lease_duration }
+            }
+            Some("MANUAL_BY_TOPIC_LIVELINESS_QOS") => Liveliness::ManualByTopic { lease_duration },
+            Some(other) => return invalid(p, other),
+          })
+        }
+        "time_based_filter" => {
+          qos.time_based_filter = Some(TimeBasedFilter {
+            minimum_separation: child_duration(p, "minimum_separation")?
+              .unwrap_or(Duration::DURATION_ZERO),
+          })
+        }
+        "reliability" => {
+          qos.reliability = Some(match child_text(p, "kind") {
+            Some("BEST_EFFORT_RELIABILITY_QOS") => Reliability::BestEffort,
+            None | Some("RELIABLE_RELIABILITY_QOS") => Reliability::Reliable {
+              max_blocking_time: child_duration(p, "max_blocking_time")?
+                .unwrap_or_else(|| Duration::from_millis(100)),
+            },
+            Some(other) => return invalid(p, other),
+          })
+        }
+        "destination_order" => {
+          qos.destination_order = Some(match child_text(p, "kind") {
+            None | Some("BY_RECEPTION_TIMESTAMP_DESTINATIONORDER_QOS") => {
+              DestinationOrder::ByReceptionTimestamp
+            }
+            Some("BY_SOURCE_TIMESTAMP_DESTINATIONORDER_QOS") => DestinationOrder::BySourceTimeStamp,
+            Some(other) => return invalid(p, other),
+          })
+        }
+        "history" => {
+          qos.history = Some(match child_text(p, "kind") {
+            None | Some("KEEP_LAST_HISTORY_QOS") => History::KeepLast {
+              depth: child_int(p, "depth")?.unwrap_or(1),
+            },
+            Some("KEEP_ALL_HISTORY_QOS") => History::KeepAll,
+            Some(other) => return invalid(p, other),
+          })
+        }
+        "resource_limits" => {
+          qos.resource_limits = Some(ResourceLimits {
+            max_samples: child_int(p, "max_samples")?.unwrap_or(-1),
+            max_instances: child_int(p, "max_instances")?.unwrap_or(-1),
+            max_samples_per_instance: child_int(p, "max_samples_per_instance")?.unwrap_or(-1),
+          })
+        }
+        "lifespan" => {
+          qos.lifespan = Some(Lifespan {
+            duration: child_duration(p, "duration")?.unwrap_or(Duration::DURATION_INFINITE),
+          })
+        }
+        "reader_data_lifecycle" => {
+          qos.reader_data_lifecycle = Some(ReaderDataLifecycle {
+            autopurge_nowriter_samples_delay: child_duration(
+              p,
+              "autopurge_nowriter_samples_delay",
+            )?
+            .unwrap_or(Duration::DURATION_INFINITE),
+            autopurge_disposed_samples_delay: child_duration(
+              p,
+              "autopurge_disposed_samples_delay",
+            )?
+            .unwrap_or(Duration::DURATION_INFINITE),
+          })
+        }
+        "writer_data_lifecycle" => {
+          qos.writer_data_lifecycle = Some(WriterDataLifecycle {
+            autodispose_unregistered_instances: child_bool(
+              p,
+              "autodispose_unregistered_instances",
+            )?
+            .unwrap_or(true),
+          })
+        }
+        _ => skip(p),
+      }
+    }
+    // The strength is a policy of its own in XML, but part of Ownership here.
+    if let (Some(s), Some(Ownership::Exclusive { strength })) =
+      (ownership_strength, qos.ownership.as_mut())
+    {
+      *strength = s;
+    }
+    Ok(())
+  }
+
+  fn elements<'a, 'input>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(|n| n.is_element())
+  }
+
+  fn skip(node: Node) {
+    warn!(
+      "Unsupported element <{}> in QoS profiles at {:?}, skipped.",
+      node.tag_name().name(),
+      node.document().text_pos_at(node.range().start)
+    );
+  }
+
+  fn invalid<T>(node: Node, value: &str) -> Result<T> {
+    Err(Error::bad_parameter(format!(
+      "invalid value {:?} of <{}> in QoS profiles at {}",
+      value,
+      node.tag_name().name(),
+      node.document().text_pos_at(node.range().start)
+    )))
+  }
+
+  fn name_of<'a>(node: Node<'a, '_>) -> Result<&'a str> {
+    match node.attribute("name") {
+      Some(name) => Ok(name),
+      None => Err(Error::bad_parameter(format!(
+        "<{}> in QoS profiles has no name",
+        node.tag_name().name()
+      ))),
+    }
+  }
+
+  fn text<'a>(node: Node<'a, '_>) -> &'a str {
+    node.text().unwrap_or("").trim()
+  }
+
+  fn child_text<'a>(node: Node<'a, '_>, tag: &str) -> Option<&'a str> {
+    elements(node)
+      .find(|n| n.tag_name().name() == tag)
+      .map(text)
+  }
+
+  fn child_bool(node: Node, tag: &str) -> Result<Option<bool>> {
+    match child_text(node, tag) {
+      None => Ok(None),
+      Some("true") | Some("1") | Some("BOOLEAN_TRUE") => Ok(Some(true)),
+      Some("false") | Some("0") | Some("BOOLEAN_FALSE") => Ok(Some(false)),
+      Some(other) => invalid(node, other),
+    }
+  }
+
+  fn child_int(node: Node, tag: &str) -> Result<Option<i32>> {
+    match child_text(node, tag) {
+      None => Ok(None),
+      Some(LENGTH_UNLIMITED) => Ok(Some(-1)),
+      Some(t) => match t.parse() {
+        Ok(i) => Ok(Some(i)),
+        Err(_) => invalid(node, t),
+      },
+    }
+  }
+
+  fn child_duration(node: Node, tag: &str) -> Result<Option<Duration>> {
+    let duration = match elements(node).find(|n| n.tag_name().name() == tag) {
+      Some(d) => d,
+      None => return Ok(None),
+    };
+    let sec = match child_text(duration, "sec") {
+      None => 0,
+      Some(DURATION_INFINITY) | Some("DURATION_INFINITE_SEC") => {
+        return Ok(Some(Duration::DURATION_INFINITE))
+      }
+      Some(t) => match t.parse::<i32>() {
+        Ok(s) => s,
+        Err(_) => return invalid(duration, t),
+      },
+    };
+    let nanosec = match child_text(duration, "nanosec") {
+      None => 0,
+      Some(DURATION_INFINITY) | Some("DURATION_INFINITE_NSEC") => {
+        return Ok(Some(Duration::DURATION_INFINITE))
+      }
+      Some(t) => match t.parse::<i64>() {
+        Ok(n) if (0..1_000_000_000).contains(&n) => n,
+        _ => return invalid(duration, t),
+      },
+    };
+    Ok(Some(Duration::from_ticks(
+      ((sec as i64) << 32) + (nanosec << 32) / 1_000_000_000,
+    )))
+  }
+}
+
+#[cfg(all(test, feature = "qos_profiles"))]
+mod tests {
+  use super::*;
+  use crate::dds::values::result::Error;
+
+  // Every policy set, and some values that are not their defaults.
+  fn all_policies() -> QosPolicies {
+    QosPolicyBuilder::new()
+      .durability(Durability::TransientLocal)
+      .presentation(Presentation {
+        access_scope: PresentationAccessScope::Topic,
+        coherent_access: true,
+        ordered_access: false,
+      })
+      .deadline(Deadline(Duration::from_millis(1500)))
+      .latency_budget(LatencyBudget {
+        duration: Duration::from_millis(3),
+      })
+      .ownership(Ownership::Exclusive { strength: 7 })
+      .liveliness(Liveliness::ManualByTopic {
+        lease_duration: Duration::from_secs(4),
+      })
+      .time_based_filter(TimeBasedFilter {
+        minimum_separation: Duration::from_std(std::time::Duration::from_nanos(123_456_789)),
+      })
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_millis(999),
+      })
+      .destination_order(DestinationOrder::BySourceTimeStamp)
+      .history(History::KeepLast { depth: 12 })
+      .resource_limits(ResourceLimits {
+        max_samples: 100,
+        max_instances: -1,
+        max_samples_per_instance: 10,
+      })
+      .lifespan(Lifespan {
+        duration: Duration::DURATION_INFINITE,
+      })
+      .reader_data_lifecycle(ReaderDataLifecycle {
+        autopurge_nowriter_samples_delay: Duration::from_secs(30),
+        autopurge_disposed_samples_delay: Duration::DURATION_INFINITE,
+      })
+      .writer_data_lifecycle(WriterDataLifecycle {
+        autodispose_unregistered_instances: false,
+      })
+      .build()
+  }
+
+  fn round_trip(profiles: &QosProfiles) -> QosProfiles {
+    QosProfiles::from_xml(&profiles.to_xml()).unwrap()
+  }
+
+  #[test]
+  fn qos_profiles_round_trip() {
+    let mut profiles = QosProfiles::new();
+    profiles.insert(
+      "fleet::telemetry",
+      QosProfile {
+        topic_qos: all_policies(),
+        datareader_qos: all_policies(),
+        datawriter_qos: all_policies(),
+        publisher_qos: QosPolicyBuilder::new()
+          .presentation(Presentation {
+            access_scope: PresentationAccessScope::Group,
+            coherent_access: false,
+            ordered_access: true,
+          })
+          .build(),
+        subscriber_qos: QosPolicyBuilder::new().build(),
+        initial_peers: vec![String::from("10.0.0.5"), String::from("host&co:7400")],
+      },
+    );
+    profiles.insert("fleet::empty", QosProfile::default());
+    profiles.insert(
+      "standalone",
+      QosProfile {
+        datawriter_qos: all_policies(),
+        ..QosProfile::default()
+      },
+    );
+    profiles.set_default_profile("fleet::telemetry");
+
+    let read = round_trip(&profiles);
+    assert_eq!(read, profiles);
+    assert_eq!(read.default_profile(), profiles.profile("telemetry"));
+  }
+
+  #[test]
+  fn qos_profiles_round_trip_every_variant() {
+    let lease_duration = Duration::from_millis(250);
+    let variants = vec![
+      QosPolicyBuilder::new()
+        .durability(Durability::Volatile)
+        .ownership(Ownership::Shared)
+        .liveliness(Liveliness::Automatic { lease_duration })
+        .reliability(Reliability::BestEffort)
+        .destination_order(DestinationOrder::ByReceptionTimestamp)
+        .history(History::KeepAll)
+        .build(),
+      QosPolicyBuilder::new()
+        .durability(Durability::Transient)
+        .presentation(Presentation {
+          access_scope: PresentationAccessScope::Instance,
+          coherent_access: false,
+          ordered_access: true,
+        })
+        .liveliness(Liveliness::ManualByParticipant { lease_duration })
+        .build(),
+      QosPolicyBuilder::new()
+        .durability(Durability::Persistent)
+        .ownership(Ownership::Exclusive { strength: -3 })
+        .build(),
+    ];
+    let mut profiles = QosProfiles::new();
+    for (i, qos) in variants.into_iter().enumerate() {
+      profiles.insert(
+        &format!("variants::v{}", i),
+        QosProfile {
+          datareader_qos: qos,
+          ..QosProfile::default()
+        },
+      );
+    }
+    assert_eq!(round_trip(&profiles), profiles);
+  }
+
+  #[test]
+  fn qos_profiles_durations_round_trip() {
+    let mut profiles = QosProfiles::new();
+    for (i, nanos) in [0, 1, 3, 999_999_999, 1_000_000_001, 86_400_000_000_123]
+      .iter()
+      .enumerate()
+    {
+      let duration = Duration::from_std(std::time::Duration::from_nanos(*nanos));
+      profiles.insert(
+        &format!("d{}", i),
+        QosProfile {
+          topic_qos: QosPolicyBuilder::new()
+            .latency_budget(LatencyBudget { duration })
+            .build(),
+          ..QosProfile::default()
+        },
+      );
+    }
+    assert_eq!(round_trip(&profiles), profiles);
+  }
+
+  #[test]
+  fn qos_profiles_read_dds_xml() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+      <dds xmlns="http://www.omg.org/dds/">
+        <qos_library name="lib">
+          <qos_profile name="base">
+            <participant_qos>
+              <discovery>
+                <initial_peers><element>127.0.0.1</element></initial_peers>
+                <multicast_receive_addresses/>
+              </discovery>
+              <participant_name><name>unknown</name></participant_name>
+            </participant_qos>
+            <datawriter_qos>
+              <reliability>
+                <kind>RELIABLE_RELIABILITY_QOS</kind>
+                <max_blocking_time><sec>DURATION_INFINITY</sec><nanosec>0</nanosec></max_blocking_time>
+              </reliability>
+              <ownership><kind>EXCLUSIVE_OWNERSHIP_QOS</kind></ownership>
+              <ownership_strength><value>5</value></ownership_strength>
+              <writer_data_lifecycle><autodispose_unregistered_instances>false</autodispose_unregistered_instances></writer_data_lifecycle>
+            </datawriter_qos>
+          </qos_profile>
+          <qos_profile name="derived" base_name="lib::base" is_default_qos="true">
+            <datawriter_qos>
+              <ownership_strength><value>9</value></ownership_strength>
+              <history><kind>KEEP_LAST_HISTORY_QOS</kind></history>
+              <resource_limits><max_samples>LENGTH_UNLIMITED</max_samples><max_instances>4</max_instances></resource_limits>
+            </datawriter_qos>
+          </qos_profile>
+        </qos_library>
+      </dds>"#;
+    let profiles = QosProfiles::from_xml(xml).unwrap();
+    assert_eq!(
+      profiles.names().collect::<Vec<_>>(),
+      vec!["lib::base", "lib::derived"]
+    );
+
+    let base = profiles.datawriter_qos("base").unwrap();
+    assert_eq!(
+      base.reliability(),
+      Some(Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_INFINITE
+      })
+    );
+    assert_eq!(base.ownership(), Some(Ownership::Exclusive { strength: 5 }));
+    assert_eq!(
+      base.writer_data_lifecycle(),
+      Some(WriterDataLifecycle {
+        autodispose_unregistered_instances: false
+      })
+    );
+    assert_eq!(base.history(), None);
+
+    let derived = profiles.default_profile().unwrap();
+    assert_eq!(derived.initial_peers, vec![String::from("127.0.0.1")]);
+    let qos = &derived.datawriter_qos;
+    assert_eq!(qos.reliability(), base.reliability());
+    assert_eq!(qos.ownership(), Some(Ownership::Exclusive { strength: 9 }));
+    assert_eq!(qos.history(), Some(History::KeepLast { depth: 1 }));
+    assert_eq!(
+      qos.resource_limits(),
+      Some(ResourceLimits {
+        max_samples: -1,
+        max_instances: 4,
+        max_samples_per_instance: -1,
+      })
+    );
+    assert!(profiles.datareader_qos("nonexistent").is_none());
+  }
+
+  #[test]
+  fn qos_profiles_invalid() {
+    let invalid = [
+      "<dds><qos_profile name=\"p\"></dds>",
+      "<profiles/>",
+      "<dds><qos_profile/></dds>",
+      "<dds><qos_profile name=\"p\" base_name=\"q\"/></dds>",
+      "<dds><qos_profile name=\"p\"><topic_qos><durability><kind>SOMETIMES</kind>\
+       </durability></topic_qos></qos_profile></dds>",
+      "<dds><qos_profile name=\"p\"><topic_qos><history><depth>many</depth>\
+       </history></topic_qos></qos_profile></dds>",
+      "<dds><qos_profile name=\"p\"><topic_qos><deadline><period><nanosec>1000000000\
+       </nanosec></period></deadline></topic_qos></qos_profile></dds>",
+    ];
+    for xml in invalid.iter() {
+      assert!(
+        matches!(QosProfiles::from_xml(xml), Err(Error::BadParameter { .. })),
+        "{}",
+        xml
+      );
+    }
+    assert!(matches!(
+      QosProfiles::load("/nonexistent/qos.xml"),
+      Err(Error::BadParameter { .. })
+    ));
+  }
+}
@@ -33,6 +33,42 @@ impl ReadCondition {
     }
   }
 
+  /// Condition reads samples whose sample, view and instance states are all in the
+  /// given sets. A condition that no sample can match, e.g. with an empty set, is
+  /// allowed and selects nothing.
+  pub fn new(
+    sample_state_mask: BitFlags<SampleState>,
+    view_state_mask: BitFlags<ViewState>,
+    instance_state_mask: BitFlags<InstanceState>,
+  ) -> ReadCondition {
+    ReadCondition {
+      sample_state_mask,
+      view_state_mask,
+      instance_state_mask,
+    }
+  }
+
+  /// Replaces the accepted sample states, e.g. `SampleState::Read`.
+  pub fn with_sample_state(mut self, mask: impl Into<BitFlags<SampleState>>) -> ReadCondition {
+    self.sample_state_mask = mask.into();
+    self
+  }
+
+  /// Replaces the accepted view states, e.g. `ViewState::New`.
+  pub fn with_view_state(mut self, mask: impl Into<BitFlags<ViewState>>) -> ReadCondition {
+    self.view_state_mask = mask.into();
+    self
+  }
+
+  /// Replaces the accepted instance states. E.g.
+  /// `ReadCondition::any().with_instance_state(InstanceState::not_alive())` reads only
+  /// samples of instances that are disposed or have no writers, including the
+  /// notifications of that. Instance and view state are those of the whole instance.
+  pub fn with_instance_state(mut self, mask: impl Into<BitFlags<InstanceState>>) -> ReadCondition {
+    self.instance_state_mask = mask.into();
+    self
+  }
+
   pub fn sample_state_mask(&self) -> &BitFlags<SampleState> {
     &self.sample_state_mask
   }
@@ -7,20 +7,28 @@ use crate::structure::endpoint::{Endpoint, EndpointAttributes};
 use crate::messages::submessages::submessages::*;
 
 use crate::dds::ddsdata::DDSData;
+use crate::dds::matched_writers::MatchedWriters;
+use crate::dds::rtps_config::RtpsReaderConfig;
 use crate::dds::rtps_writer_proxy::RtpsWriterProxy;
+use crate::dds::statistics::ReaderCounters;
+use crate::dds::task_waker::TaskWaker;
 use crate::structure::entity::EntityAttributes;
-use crate::structure::guid::{GUID, EntityId};
+use crate::structure::guid::{GUID, EntityId, GuidPrefix};
 use crate::structure::sequence_number::{SequenceNumber, SequenceNumberSet};
 use crate::structure::locator::LocatorList;
-use crate::structure::{duration::Duration, time::Timestamp};
+use crate::structure::{
+  clock::SharedClock, dds_cache::CacheInstant, duration::Duration, time::Timestamp,
+};
 
 use std::{
   collections::hash_map::DefaultHasher,
   hash::Hasher,
+  net::IpAddr,
   slice::Iter,
   sync::{Arc, RwLock},
 };
 use crate::structure::dds_cache::{DDSCache};
+use crate::common::lock::read_lock;
 //use std::time::Instant;
 
 use mio::Token;
@@ -28,15 +36,16 @@ use mio_extras::channel as mio_channel;
 use log::{debug, info, warn};
 use std::fmt;
 
-use std::collections::{HashSet, HashMap};
-use std::time::Duration as StdDuration;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration as StdDuration, Instant};
 use enumflags2::BitFlags;
 
 use crate::structure::cache_change::CacheChange;
 use crate::dds::message_receiver::MessageReceiverState;
-use crate::dds::qos::{QosPolicies, HasQoSPolicy};
+use crate::dds::qos::{QosPolicies, QosPolicyId, HasQoSPolicy};
 use crate::dds::values::result::Result as DDSResult;
-use crate::network::udp_sender::UDPSender;
+use crate::network::{local_network::LocalNetwork, tcp::TcpTransport, udp_sender::UDPSender};
+use crate::network::capture::PacketCapture;
 
 use crate::serialization::message::Message;
 use crate::messages::header::Header;
@@ -47,21 +56,39 @@ use speedy::{Writable, Endianness};
 use chrono::Duration as chronoDuration;
 
 use super::{
-  qos::{QosPolicyBuilder},
-  values::result::{RequestedDeadlineMissedStatus, StatusChange},
+  qos::{
+    QosPolicyBuilder,
+    policy::{Liveliness, Reliability},
+  },
+  values::result::{
+    RequestedDeadlineMissedStatus, RequestedIncompatibleQosStatus, SampleLostStatus, StatusChange,
+    SubscriptionMatchedStatus,
+  },
   with_key::datareader::ReaderCommand,
 };
 
 use super::qos::InlineQos;
 
 
+// An additional DataReader reading the same topic from DDSCache as the DataReader
+// that owns the Reader. Used for application readers of the builtin topics, so that
+// they do not take samples from the Discovery readers.
+pub(crate) struct ReaderListener {
+  pub notification_sender: mio_channel::SyncSender<()>,
+  pub data_waker: TaskWaker,
+}
+
 pub(crate) struct Reader {
   // Should the instant be sent?
   notification_sender: mio_channel::SyncSender<()>,
+  // wakes async tasks waiting on the DataReader
+  data_waker: TaskWaker,
+  listeners: Vec<ReaderListener>,
   status_sender: mio_channel::SyncSender<StatusChange>,
 
   dds_cache: Arc<RwLock<DDSCache>>,
-  seqnum_instant_map: HashMap<SequenceNumber, Timestamp>,
+  clock: SharedClock,
+  seqnum_instant_map: HashMap<SequenceNumber, CacheInstant>,
   topic_name: String,
   qos_policy: QosPolicies,
 
@@ -70,16 +97,44 @@ pub(crate) struct Reader {
 
   heartbeat_response_delay: StdDuration,
   heartbeat_supression_duration: StdDuration,
+  // when each writer's heartbeat was last answered, for heartbeat suppression
+  last_heartbeat_response: HashMap<GUID, Instant>,
+  // AckNacks waiting for heartbeat_response_delay, at most one per writer
+  pending_acknacks: HashMap<GUID, (AckNack, MessageReceiverState)>,
 
   sent_ack_nack_count: i32,
   received_hearbeat_count: i32,
 
   matched_writers: HashMap<GUID, RtpsWriterProxy>,
+  // the GUIDs of matched_writers, shared with the DataReader
+  matched_writers_of_datareader: MatchedWriters,
+  // reliably received changes waiting for the changes before them, per writer
+  pending_changes: HashMap<GUID, BTreeMap<SequenceNumber, (Data, Option<Timestamp>)>>,
+  // An RTPS message is being handled. Its changes wait in received_changes to be added
+  // to the DDSCache together, and notifying the DataReader waits for the end of it.
+  in_message: bool,
+  received_changes: Vec<(CacheInstant, CacheChange)>,
+  notification_deferred: bool,
 
   requested_deadline_missed_status: RequestedDeadlineMissedStatus,
+  requested_incompatible_qos_status: RequestedIncompatibleQosStatus,
+  sample_lost_status: SampleLostStatus,
+  subscription_matched_status: SubscriptionMatchedStatus,
 
   timed_event_handler: Option<TimedEventHandler>,
+  // a reader_liveliness_check timer is running
+  liveliness_check_scheduled: bool,
   pub(crate) data_reader_command_receiver: mio_channel::Receiver<ReaderCommand>,
+  // group that the user traffic multicast socket has joined for this reader
+  multicast_group: Option<IpAddr>,
+  // Connections of the participant, if it uses TCP
+  tcp: Option<TcpTransport>,
+  // Traffic capture of the participant
+  capture: PacketCapture,
+  // The participant is passive: we receive, but send no AckNacks.
+  passive: bool,
+  // protocol statistics, shared with the DataReader and the participant
+  statistics: Arc<ReaderCounters>,
 } // placeholder
 
 impl Reader {
@@ -91,10 +146,14 @@ impl Reader {
     topic_name: String,
     data_reader_command_receiver: mio_channel::Receiver<ReaderCommand>, //qos_policy: QosPolicies, add later to constructor
   ) -> Reader {
+    let clock = read_lock(&dds_cache).clock();
     Reader {
       notification_sender,
+      data_waker: TaskWaker::new(),
+      listeners: Vec::new(),
       status_sender,
       dds_cache,
+      clock,
       topic_name,
       qos_policy: QosPolicyBuilder::new().build(),
 
@@ -102,14 +161,30 @@ impl Reader {
       entity_attributes: EntityAttributes { guid },
       enpoint_attributes: EndpointAttributes::default(),
 
-      heartbeat_response_delay: StdDuration::new(0, 500_000_000), // 0,5sec
-      heartbeat_supression_duration: StdDuration::new(0, 0),
+      heartbeat_response_delay: RtpsReaderConfig::default().heartbeat_response_delay,
+      heartbeat_supression_duration: RtpsReaderConfig::default().heartbeat_suppression_duration,
+      last_heartbeat_response: HashMap::new(),
+      pending_acknacks: HashMap::new(),
       sent_ack_nack_count: 0,
       received_hearbeat_count: 0,
       matched_writers: HashMap::new(),
+      matched_writers_of_datareader: MatchedWriters::new(),
+      pending_changes: HashMap::new(),
+      in_message: false,
+      received_changes: Vec::new(),
+      notification_deferred: false,
       requested_deadline_missed_status: RequestedDeadlineMissedStatus::new(),
+      requested_incompatible_qos_status: RequestedIncompatibleQosStatus::new(),
+      sample_lost_status: SampleLostStatus::new(),
+      subscription_matched_status: SubscriptionMatchedStatus::new(),
       timed_event_handler: None,
+      liveliness_check_scheduled: false,
       data_reader_command_receiver,
+      multicast_group: None,
+      tcp: None,
+      capture: PacketCapture::default(),
+      passive: false,
+      statistics: Arc::new(ReaderCounters::default()),
     }
   }
   // TODO: check if it's necessary to implement different handlers for discovery
@@ -117,7 +192,7 @@ impl Reader {
 
   // TODO Used for test/debugging purposes
   pub fn get_history_cache_change_data(&self, sequence_number: SequenceNumber) -> Option<DDSData> {
-    let dds_cache = self.dds_cache.read().unwrap();
+    let dds_cache = read_lock(&self.dds_cache);
     let cc = dds_cache.from_topic_get_change(
       &self.topic_name,
       &self.seqnum_instant_map.get(&sequence_number).unwrap(),
@@ -131,6 +206,29 @@ impl Reader {
     }
   }
 
+  pub(crate) fn data_waker(&self) -> TaskWaker {
+    self.data_waker.clone()
+  }
+
+  pub(crate) fn statistics(&self) -> Arc<ReaderCounters> {
+    self.statistics.clone()
+  }
+
+  pub(crate) fn matched_writers(&self) -> MatchedWriters {
+    self.matched_writers_of_datareader.clone()
+  }
+
+  // Lets the DataReader know the current matched writers, remote and local.
+  fn update_matched_writers_of_datareader(&self) {
+    self
+      .matched_writers_of_datareader
+      .set(self.matched_writers.keys().copied().collect());
+  }
+
+  pub(crate) fn add_listener(&mut self, listener: ReaderListener) {
+    self.listeners.push(listener);
+  }
+
   /// To know when token represents a reader we should look entity attribute kind
   pub fn get_entity_token(&self) -> Token {
     let id = self.as_entity().as_usize();
@@ -149,6 +247,50 @@ impl Reader {
     self.timed_event_handler = Some(time_handler);
   }
 
+  pub fn set_rtps_config(&mut self, config: RtpsReaderConfig) {
+    self.heartbeat_response_delay = config.heartbeat_response_delay;
+    self.heartbeat_supression_duration = config.heartbeat_suppression_duration;
+    if self.heartbeat_response_delay == StdDuration::from_secs(0) {
+      self.handle_heartbeat_response_timeout();
+    }
+  }
+
+  pub fn multicast_group(&self) -> Option<IpAddr> {
+    self.multicast_group
+  }
+
+  /// Returns the previous group.
+  pub fn set_multicast_group(&mut self, group: Option<IpAddr>) -> Option<IpAddr> {
+    std::mem::replace(&mut self.multicast_group, group)
+  }
+
+  // Sends AckNacks also over the TCP connections of the participant, if it has them,
+  // and none at all if the participant is passive.
+  pub fn use_local_network(&mut self, network: &LocalNetwork) {
+    self.tcp = network.tcp().cloned();
+    self.passive = network.passive();
+  }
+
+  // Writes our AckNacks also to the traffic capture of the participant.
+  pub fn use_capture(&mut self, capture: &PacketCapture) {
+    self.capture = capture.clone();
+  }
+
+  /// Sends the AckNacks delayed by heartbeat_response_delay.
+  pub fn handle_heartbeat_response_timeout(&mut self) {
+    let guid_prefix = self.entity_attributes.guid.guidPrefix;
+    for (_, (acknack, mr_state)) in self.pending_acknacks.drain() {
+      Reader::send_acknack(
+        guid_prefix,
+        acknack,
+        mr_state,
+        self.tcp.as_ref(),
+        &self.capture,
+      );
+      self.statistics.acknack_sent();
+    }
+  }
+
   pub fn set_requested_deadline_check_timer(&mut self) {
     if let Some(deadline) = self.qos_policy.deadline {
       debug!(
@@ -177,6 +319,60 @@ impl Reader {
     self.requested_deadline_missed_status.reset_change();
   }
 
+  pub fn reset_requested_incompatible_qos_status(&mut self) {
+    self.requested_incompatible_qos_status.reset_change();
+  }
+
+  pub fn reset_sample_lost_status(&mut self) {
+    self.sample_lost_status.reset_change();
+  }
+
+  pub fn reset_subscription_matched_status(&mut self) {
+    self.subscription_matched_status.reset_change();
+  }
+
+  fn is_reliable(&self) -> bool {
+    match self.qos_policy.reliability {
+      Some(Reliability::Reliable { .. }) => true,
+      _ => false,
+    }
+  }
+
+  // Counts the lost sequence number ranges into SampleLostStatus and notifies
+  // the DataReader.
+  fn report_lost_changes(
+    &mut self,
+    writer_guid: GUID,
+    lost_changes: Vec<(SequenceNumber, SequenceNumber)>,
+  ) {
+    if lost_changes.is_empty() {
+      return;
+    }
+    for (first, last) in lost_changes.iter() {
+      debug!(
+        "Reader {:?} lost samples {:?} ..= {:?} from writer {:?}",
+        self.get_guid(),
+        first,
+        last,
+        writer_guid
+      );
+      let count = i64::from(*last - *first) + 1;
+      self.statistics.samples_lost(count as u64);
+      self
+        .sample_lost_status
+        .increase_by(count.min(std::i32::MAX as i64) as i32);
+    }
+    self.send_status_change(StatusChange::SampleLostStatus(self.sample_lost_status));
+  }
+
+  // Discovery found a writer of our topic that does not offer the QoS we request.
+  pub fn report_incompatible_qos(&mut self, policy: QosPolicyId) {
+    self.requested_incompatible_qos_status.increase(policy);
+    self.send_status_change(StatusChange::RequestedIncompatibleQosStatus(
+      self.requested_incompatible_qos_status,
+    ));
+  }
+
   pub fn send_status_change(&self, change: StatusChange) {
     match self.status_sender.try_send(change.clone()) {
       Ok(()) => info!(
@@ -211,13 +407,13 @@ impl Reader {
         return changes;
       }
       Some(deadline) => {
+        let insta_now = self.clock.now();
         for (_g, writer_proxy) in self.matched_writers.iter_mut() {
           //let last_instant = wP.changes.values().max_by(|x,y|x.cmp(y));
           let last_instant = writer_proxy.changes.values().max_by(|x, y| x.cmp(y));
           match last_instant {
             Some(instant) => {
-              let insta_now = Timestamp::now();
-              let perioid = insta_now.duration_since(*instant);
+              let perioid = insta_now.duration_since(instant.timestamp());
               // if time singe last received message is greater than deadline increase status and return notification.
               debug!("Comparing deadlines: {:?} - {:?}", perioid, deadline);
               if perioid > deadline.0 {
@@ -279,16 +475,13 @@ impl Reader {
   // Used for test/debugging purposes
   pub fn get_history_cache_change(&self, sequence_number: SequenceNumber) -> Option<CacheChange> {
     debug!("{:?}", sequence_number);
-    let dds_cache = self.dds_cache.read().unwrap();
+    let dds_cache = read_lock(&self.dds_cache);
     let cc = dds_cache.from_topic_get_change(
       &self.topic_name,
       &self.seqnum_instant_map.get(&sequence_number).unwrap(),
     );
     debug!("history cache !!!! {:?}", cc);
-    match cc {
-      Some(cc) => Some(cc.clone()),
-      None => None,
-    }
+    cc
   }
 
   // TODO Used for test/debugging purposes
@@ -304,9 +497,155 @@ impl Reader {
     match old_proxy {
       Some(op) => op.update_contents(proxy),
       None => {
-        self.matched_writers.insert(proxy.remote_writer_guid, proxy);
+        let writer_guid = proxy.remote_writer_guid;
+        dds_event!(
+          debug,
+          "writer matched",
+          guid = self.get_guid(),
+          writer = writer_guid
+        );
+        let mut proxy = proxy;
+        proxy.last_life_sign = self.clock.now();
+        self.matched_writers.insert(writer_guid, proxy);
+        self.update_matched_writers_of_datareader();
+        self.subscription_matched_status.increase(writer_guid);
+        self.send_status_change(StatusChange::SubscriptionMatchedStatus(
+          self.subscription_matched_status,
+        ));
+      }
+    };
+    self.schedule_liveliness_check();
+  }
+
+  /// The writer showed it is alive, by data or a liveliness heartbeat.
+  pub fn writer_liveliness_asserted(&mut self, writer_guid: GUID) {
+    let now = self.clock.now();
+    let revived = match self.matched_writers.get_mut(&writer_guid) {
+      Some(wp) => {
+        wp.last_life_sign = now;
+        !std::mem::replace(&mut wp.alive, true)
       }
+      None => return,
+    };
+    if revived {
+      dds_event!(
+        debug,
+        "writer liveliness regained",
+        guid = self.get_guid(),
+        writer = writer_guid
+      );
+      self.schedule_liveliness_check();
+    }
+  }
+
+  /// A participant message asserted the liveliness of the writers of a participant.
+  /// Automatic writers are asserted by any participant message, ManualByParticipant
+  /// writers only by the manual ones, and ManualByTopic writers by neither.
+  pub fn participant_liveliness_asserted(&mut self, guid_prefix: GuidPrefix, manual: bool) {
+    let asserted: Vec<GUID> = self
+      .matched_writers
+      .values()
+      .filter(|wp| wp.remote_writer_guid.guidPrefix == guid_prefix)
+      .filter(|wp| match wp.liveliness {
+        Some(Liveliness::Automatic { .. }) => true,
+        Some(Liveliness::ManualByParticipant { .. }) => manual,
+        _ => false,
+      })
+      .map(|wp| wp.remote_writer_guid)
+      .collect();
+    for writer_guid in asserted {
+      self.writer_liveliness_asserted(writer_guid);
+    }
+  }
+
+  // Sets a timer to the earliest lease expiry of the writers still alive.
+  fn schedule_liveliness_check(&mut self) {
+    if self.liveliness_check_scheduled {
+      return;
+    }
+    let now = self.clock.now();
+    let next_expiry = self
+      .matched_writers
+      .values()
+      .filter(|wp| wp.alive)
+      .filter_map(|wp| {
+        let lease = chronoDuration::from(wp.liveliness_lease()?);
+        Some(lease - chronoDuration::from(now.duration_since(wp.last_life_sign)))
+      })
+      .min();
+    let next_expiry = match next_expiry {
+      Some(e) => std::cmp::max(e, chronoDuration::milliseconds(1)),
+      None => return,
+    };
+    if let Some(teh) = self.timed_event_handler.as_mut() {
+      teh.set_timeout(&next_expiry, TimerMessageType::reader_liveliness_check);
+      self.liveliness_check_scheduled = true;
+    }
+  }
+
+  /// Marks the writers whose lease has expired not alive, and reports their
+  /// instances as having lost the writer.
+  pub fn handle_liveliness_check(&mut self) {
+    self.liveliness_check_scheduled = false;
+    let now = self.clock.now();
+    let lost: Vec<GUID> = self
+      .matched_writers
+      .values_mut()
+      .filter(|wp| wp.alive)
+      .filter_map(|wp| {
+        let lease = wp.liveliness_lease()?;
+        if now.duration_since(wp.last_life_sign) >= lease {
+          wp.alive = false;
+          Some(wp.remote_writer_guid)
+        } else {
+          None
+        }
+      })
+      .collect();
+    for writer_guid in lost {
+      self.writer_lost(writer_guid);
+    }
+    self.schedule_liveliness_check();
+  }
+
+  // The DataReader takes the writer out of the live writers of its instances, as if
+  // the writer had unregistered them.
+  fn writer_lost(&mut self, writer_guid: GUID) {
+    dds_event!(
+      debug,
+      "writer liveliness lost",
+      guid = self.get_guid(),
+      writer = writer_guid
+    );
+    let instance_keys = match self.matched_writers.get(&writer_guid) {
+      Some(wp) => wp.instance_keys.clone(),
+      None => return,
     };
+    if instance_keys.is_empty() {
+      return;
+    }
+    let now = self.clock.now();
+    let cache = read_lock(&self.dds_cache);
+    for key in instance_keys {
+      let mut cache_change = CacheChange::new(
+        ChangeKind::NOT_ALIVE_UNREGISTERED,
+        writer_guid,
+        SequenceNumber::default(),
+        None,
+      );
+      cache_change.key = key;
+      cache_change.received_by = Some(self.get_guid());
+      let instant = CacheInstant::at(now);
+      if let Err(e) = cache.to_topic_add_change(&self.topic_name, &instant, cache_change) {
+        warn!(
+          "Reader {:?} could not unregister an instance of a lost writer: {}",
+          self.get_guid(),
+          e
+        );
+      }
+    }
+    drop(cache);
+    self.notify_cache_change();
   }
 
   pub fn contains_writer(&self, entity_id: EntityId) -> bool {
@@ -335,11 +674,53 @@ impl Reader {
 
   pub fn retain_matched_writers(&mut self, retvals: Iter<RtpsWriterProxy>) {
     let rt: Vec<GUID> = retvals.map(|p| p.remote_writer_guid).collect();
-    self.matched_writers.retain(|guid, _| rt.contains(guid));
+    let removed: Vec<GUID> = self
+      .matched_writers
+      .keys()
+      .filter(|guid| !rt.contains(guid))
+      .cloned()
+      .collect();
+    for guid in removed {
+      self.matched_writer_remove(guid);
+    }
   }
 
+  /// Forgets the writer, with the changes still waiting for the ones missing before
+  /// them. The instances of the writer lose it, as when its liveliness is lost, unless
+  /// that has happened already.
   pub fn matched_writer_remove(&mut self, remote_writer_guid: GUID) -> Option<RtpsWriterProxy> {
-    self.matched_writers.remove(&remote_writer_guid)
+    if let Some(true) = self.matched_writers.get(&remote_writer_guid).map(|wp| wp.alive) {
+      self.writer_lost(remote_writer_guid);
+    }
+    let removed = self.matched_writers.remove(&remote_writer_guid);
+    self.pending_changes.remove(&remote_writer_guid);
+    if removed.is_some() {
+      self.update_matched_writers_of_datareader();
+      self.subscription_matched_status.decrease(remote_writer_guid);
+      self.send_status_change(StatusChange::SubscriptionMatchedStatus(
+        self.subscription_matched_status,
+      ));
+      dds_event!(
+        debug,
+        "writer unmatched",
+        guid = self.get_guid(),
+        writer = remote_writer_guid
+      );
+    }
+    removed
+  }
+
+  // Removes all matched writers of the given participant.
+  pub fn matched_writers_remove_participant(&mut self, guid_prefix: GuidPrefix) {
+    let removed: Vec<GUID> = self
+      .matched_writers
+      .keys()
+      .filter(|guid| guid.guidPrefix == guid_prefix)
+      .cloned()
+      .collect();
+    for guid in removed {
+      self.matched_writer_remove(guid);
+    }
   }
 
   fn matched_writer_lookup(&mut self, remote_writer_guid: GUID) -> Option<&mut RtpsWriterProxy> {
@@ -348,66 +729,206 @@ impl Reader {
 
   // handles regular data message and updates history cache
   pub fn handle_data_msg(&mut self, data: Data, mr_state: MessageReceiverState) {
+    let _reader = dds_span!(trace, "reader", guid = self.get_guid());
     let duration = match mr_state.timestamp {
-      Some(ts) => Timestamp::now().duration_since(ts),
+      Some(ts) => self.clock.now().duration_since(ts),
       None => Duration::DURATION_ZERO,
     };
 
+    let writer_guid = GUID::new_with_prefix_and_id(mr_state.source_guid_prefix, data.writer_id);
+    let seq_num = data.writer_sn;
+    self.writer_liveliness_asserted(writer_guid);
+
     // checking lifespan for silent dropping of message
     match self.get_qos().lifespan {
       Some(ls) => {
         if ls.duration < duration {
+          dds_event!(
+            trace,
+            "sample dropped",
+            writer = writer_guid,
+            seq = seq_num,
+            reason = "lifespan"
+          );
+          self.statistics.sample_rejected();
           return;
         }
       }
       None => (),
     }
 
-    let writer_guid = GUID::new_with_prefix_and_id(mr_state.source_guid_prefix, data.writer_id);
-    let seq_num = data.writer_sn;
+    if i64::from(seq_num) < 1 {
+      return; // invalid (section 8.3.4.1)
+    }
 
-    let instant = Timestamp::now();
+    let instant = CacheInstant::at(self.clock.now());
 
     // Really should be checked from qosPolicy?
     // Added in order to test stateless actions.
     // TODO
     let statefull = self.matched_writers.contains_key(&writer_guid);
+    // Reliable readers get missing changes repaired, so they deliver changes in
+    // order, and consider missing ones lost only once the writer heartbeats them
+    // out of its cache.
+    let reliable = self.is_reliable();
 
     let mut no_writers = false;
+    let mut lost_changes = Vec::new();
 
     if statefull {
-      if let Some(writer_proxy) = self.matched_writer_lookup(writer_guid) {
-        if writer_proxy.contains_change(seq_num) {
-          // change already present
+      if let Some(writer_proxy) = self.matched_writers.get_mut(&writer_guid) {
+        // The same DATA may come both by unicast and multicast, or be resent.
+        if !writer_proxy.accept_change(seq_num, reliable) {
+          dds_event!(
+            trace,
+            "sample dropped",
+            writer = writer_guid,
+            seq = seq_num,
+            reason = "duplicate"
+          );
+          self.statistics.duplicate_dropped();
+          return;
+        }
+        if reliable {
+          dds_event!(
+            trace,
+            "sample accepted",
+            writer = writer_guid,
+            seq = seq_num
+          );
+          self.statistics.data_received();
+          self
+            .pending_changes
+            .entry(writer_guid)
+            .or_insert_with(BTreeMap::new)
+            .insert(seq_num, (data, mr_state.timestamp));
+          self.deliver_pending_changes(writer_guid);
           return;
         }
+        // Best effort changes are delivered in arrival order.
+        lost_changes = writer_proxy.lost_changes_up_to(seq_num);
         // Add the change and get the instant
         writer_proxy.received_changes_add(seq_num, instant);
       } else {
         no_writers = true;
       }
     }
+    dds_event!(
+      trace,
+      "sample accepted",
+      writer = writer_guid,
+      seq = seq_num
+    );
+    self.statistics.data_received();
+    self.report_lost_changes(writer_guid, lost_changes);
 
-    self.make_cache_change(data, instant, writer_guid, no_writers);
+    self.make_cache_change(data, instant, writer_guid, no_writers, mr_state.timestamp);
     // Add to own track-keeping datastructure
     self.seqnum_instant_map.insert(seq_num, instant);
 
     self.notify_cache_change();
   }
 
+  // Takes a change of a writer in our own participant, handed over in memory. It
+  // cannot be lost or reordered on the way, so the only bookkeeping is to record it as
+  // received. The payload bytes are shared with the writer's copy.
+  pub fn handle_local_change(&mut self, change: &CacheChange) {
+    let writer_guid = change.writer_guid;
+    let instant = CacheInstant::at(self.clock.now());
+    match self.matched_writer_lookup(writer_guid) {
+      Some(writer_proxy) => {
+        // Changes written before we matched are never handed over, so there is nothing
+        // to wait for.
+        if !writer_proxy.accept_change(change.sequence_number, false) {
+          return;
+        }
+        writer_proxy.received_changes_add(change.sequence_number, instant);
+        writer_proxy.instance_changed(change.key, change.kind);
+      }
+      None => return,
+    }
+    self.writer_liveliness_asserted(writer_guid);
+
+    let mut cache_change = change.clone();
+    cache_change.received_by = Some(self.get_guid());
+    let added =
+      read_lock(&self.dds_cache).to_topic_add_change(&self.topic_name, &instant, cache_change);
+    if let Err(e) = added {
+      warn!(
+        "Reader {:?} could not add a local change: {}",
+        self.get_guid(),
+        e
+      );
+      return;
+    }
+    self
+      .seqnum_instant_map
+      .insert(change.sequence_number, instant);
+
+    self.notify_cache_change();
+  }
+
+  // Hands the reliably received changes of the writer to the DDSCache, in order,
+  // as far as no changes before them are missing.
+  fn deliver_pending_changes(&mut self, writer_guid: GUID) {
+    let received_before = match self.matched_writers.get(&writer_guid) {
+      Some(wp) => wp.received_before(),
+      None => return,
+    };
+    let deliverable = match self.pending_changes.get_mut(&writer_guid) {
+      Some(pending) => {
+        let still_pending = pending.split_off(&received_before);
+        std::mem::replace(pending, still_pending)
+      }
+      None => return,
+    };
+    if deliverable.is_empty() {
+      return;
+    }
+
+    for (seq_num, (data, source_timestamp)) in deliverable {
+      let instant = CacheInstant::at(self.clock.now());
+      if let Some(writer_proxy) = self.matched_writer_lookup(writer_guid) {
+        writer_proxy.received_changes_add(seq_num, instant);
+      }
+      self.make_cache_change(data, instant, writer_guid, false, source_timestamp);
+      self.seqnum_instant_map.insert(seq_num, instant);
+    }
+    self.notify_cache_change();
+  }
+
   pub fn handle_heartbeat_msg(
     &mut self,
     heartbeat: Heartbeat,
     final_flag_set: bool,
+    liveliness_flag_set: bool,
     mr_state: MessageReceiverState,
   ) -> bool {
+    let _reader = dds_span!(trace, "reader", guid = self.get_guid());
     let writer_guid =
       GUID::new_with_prefix_and_id(mr_state.source_guid_prefix, heartbeat.writer_id);
+    dds_event!(
+      trace,
+      "heartbeat received",
+      writer = writer_guid,
+      first = heartbeat.first_sn,
+      last = heartbeat.last_sn,
+      count = heartbeat.count,
+    );
 
     // Added in order to test stateless actions. TODO
     if !self.matched_writers.contains_key(&writer_guid) {
       return false;
     }
+    // Any heartbeat shows that an Automatic writer is alive. Manual writers assert
+    // their liveliness with the liveliness flag.
+    let automatic = match self.matched_writers.get(&writer_guid) {
+      Some(wp) => matches!(wp.liveliness, Some(Liveliness::Automatic { .. })),
+      None => false,
+    };
+    if liveliness_flag_set || automatic {
+      self.writer_liveliness_asserted(writer_guid);
+    }
 
     let writer_proxy = match self.matched_writer_lookup(writer_guid) {
       Some(wp) => wp,
@@ -422,29 +943,53 @@ impl Reader {
     }
     writer_proxy.received_heartbeat_count = heartbeat.count;
 
+    // Changes before first_sn that we never got are no longer available from
+    // the writer.
+    let lost_changes = writer_proxy.lost_changes_up_to(heartbeat.first_sn);
+
     // remove fragmented changes until first_sn.
     let removed_instances = writer_proxy.irrelevant_changes_up_to(heartbeat.first_sn);
+    self.report_lost_changes(writer_guid, lost_changes);
 
-    // Remove instances from DDSHistoryCache
-    let mut cache = match self.dds_cache.write() {
-      Ok(rwlock) => rwlock,
-      // TODO: Should we panic here? Are we allowed to continue with poisoned DDSCache?
-      Err(e) => panic!("The DDSCache of is poisoned. Error: {}", e),
-    };
+    // Remove instances from DDSHistoryCache. They may have been received in this message.
+    self.add_received_changes();
+    let cache = read_lock(&self.dds_cache);
     for instant in removed_instances.iter() {
-      match cache.from_topic_remove_change(&self.topic_name, instant) {
-        Some(_) => (),
-        None => warn!("WriterProxy told to remove an instant which was not present"),
+      // Garbage collection may have removed it already.
+      if let Err(e) = cache.from_topic_remove_change(&self.topic_name, instant) {
+        debug!(
+          "WriterProxy told to remove a change which was not present: {}",
+          e
+        );
       }
     }
     drop(cache);
+    // no longer waiting for the changes before first_sn
+    self.deliver_pending_changes(writer_guid);
 
+    // A passive reader does not answer, so it cannot get missing changes repaired.
+    if self.passive {
+      return false;
+    }
     let writer_proxy = match self.matched_writer_lookup(writer_guid) {
       Some(wp) => wp,
       None => return false, // Matching writer not found
     };
     // See if ack_nack is needed.
     if writer_proxy.changes_are_missing(heartbeat.first_sn, heartbeat.last_sn) || !final_flag_set {
+      if let Some(last_response) = self.last_heartbeat_response.get(&writer_guid) {
+        let since_response = self
+          .clock
+          .instant()
+          .saturating_duration_since(*last_response);
+        if since_response < self.heartbeat_supression_duration {
+          return false;
+        }
+      }
+      let writer_proxy = match self.matched_writer_lookup(writer_guid) {
+        Some(wp) => wp,
+        None => return false,
+      };
       let missing_seqnums =
         writer_proxy.get_missing_sequence_numbers(heartbeat.first_sn, heartbeat.last_sn);
       let seqnum_base = missing_seqnums.iter().min();
@@ -465,16 +1010,50 @@ impl Reader {
       };
 
       self.sent_ack_nack_count += 1;
+      self
+        .last_heartbeat_response
+        .insert(writer_guid, self.clock.instant());
       // The acknack can be sent now or later. The rest of the RTPS message
       // needs to be constructed. p. 48
 
-      self.send_acknack(response_ack_nack, mr_state);
+      if self.heartbeat_response_delay == StdDuration::from_secs(0) {
+        let guid_prefix = self.entity_attributes.guid.guidPrefix;
+        Reader::send_acknack(
+          guid_prefix,
+          response_ack_nack,
+          mr_state,
+          self.tcp.as_ref(),
+          &self.capture,
+        );
+        self.statistics.acknack_sent();
+      } else {
+        // A newer AckNack replaces the pending one of the same writer.
+        let timer_running = !self.pending_acknacks.is_empty();
+        self
+          .pending_acknacks
+          .insert(writer_guid, (response_ack_nack, mr_state));
+        if !timer_running {
+          let delay = chronoDuration::from_std(self.heartbeat_response_delay);
+          let timer_set = match (delay, self.timed_event_handler.as_mut()) {
+            (Ok(delay), Some(teh)) => {
+              teh.set_timeout(&delay, TimerMessageType::reader_heartbeat_response);
+              true
+            }
+            _ => false,
+          };
+          if !timer_set {
+            warn!("Unable to delay AckNack, sending it now.");
+            self.handle_heartbeat_response_timeout();
+          }
+        }
+      }
       return true;
     }
     false
   }
 
   pub fn handle_gap_msg(&mut self, gap: Gap, mr_state: MessageReceiverState) {
+    let _reader = dds_span!(trace, "reader", guid = self.get_guid());
     // ATM all things related to groups is ignored. TODO?
 
     let writer_guid = GUID::new_with_prefix_and_id(mr_state.source_guid_prefix, gap.writer_id);
@@ -485,46 +1064,42 @@ impl Reader {
       None => return, // Matching writer not found
     };
 
-    // Sequencenumber set in the gap is invalid: (section 8.3.5.5)
-    // Set checked to be not empty. Unwraps won't panic
-    if gap.gap_list.set.len() != 0 {
-      if i64::from(gap.gap_start) < 1i64
-        || (gap.gap_list.set.iter().max().unwrap() - gap.gap_list.set.iter().min().unwrap()) >= 256
-      {
-        return;
-      }
-    } else {
+    // Invalid Gap (section 8.3.7.4.3)
+    if !gap.is_valid() {
       return;
-    };
+    }
     // Irrelevant sequence numbers communicated in the Gap message are
-    // composed of two groups:
-    let mut irrelevant_changes_set = HashSet::new();
-
     //   1. All sequence numbers in the range gapStart <= sequence_number < gapList.base
-    for seq_num_i64 in i64::from(gap.gap_start)..i64::from(gap.gap_list.base) {
-      irrelevant_changes_set.insert(SequenceNumber::from(seq_num_i64));
-    }
     //   2. All the sequence numbers that appear explicitly listed in the gapList.
-    for seq_num in &mut gap.gap_list.set.into_iter() {
-      irrelevant_changes_set.insert(SequenceNumber::from(seq_num as i64));
-    }
+    // The writer will never send them, so they count as received, not lost.
+    // The range can be long, so it is not gone through one by one.
+    let range_start = gap.gap_start;
+    let range_end = gap.gap_list.base;
+    let listed_changes: Vec<SequenceNumber> = gap.gap_list.into_iter().collect();
 
     // Remove from writerProxy and DDSHistoryCache
-    let mut removed_instances = Vec::new();
-    for seq_num in &irrelevant_changes_set {
+    let mut removed_instances = writer_proxy.set_irrelevant_range(range_start, range_end);
+    for seq_num in &listed_changes {
       match writer_proxy.set_irrelevant_change(*seq_num) {
         Some(i) => removed_instances.push(i),
         None => (),
       };
     }
-    let mut cache = match self.dds_cache.write() {
-      Ok(rwlock) => rwlock,
-      // TODO: Should we panic here? Are we allowed to continue with poisoned DDSCache?
-      Err(e) => panic!("The DDSCache of is poisoned. Error: {}", e),
-    };
+    self.add_received_changes();
+    let cache = read_lock(&self.dds_cache);
     for instant in &removed_instances {
-      cache.from_topic_remove_change(&self.topic_name, instant);
+      if let Err(e) = cache.from_topic_remove_change(&self.topic_name, instant) {
+        warn!("GAP: could not remove an irrelevant change: {}", e);
+      }
+    }
+    drop(cache);
+
+    if let Some(pending) = self.pending_changes.get_mut(&writer_guid) {
+      pending.retain(|seq_num, _| {
+        (*seq_num < range_start || range_end <= *seq_num) && !listed_changes.contains(seq_num)
+      });
     }
+    self.deliver_pending_changes(writer_guid);
 
     // Is this needed?
     // self.notify_cache_change();
@@ -548,22 +1123,21 @@ impl Reader {
   fn make_cache_change(
     &mut self,
     data: Data,
-    instant: Timestamp,
+    instant: CacheInstant,
     writer_guid: GUID,
     no_writers: bool,
+    source_timestamp: Option<Timestamp>,
   ) {
-    let representation_identifier = match &data.serialized_payload {
-      Some(sp) => sp.representation_identifier(),
-      None => RepresentationIdentifier::CDR_LE,
-    };
-
+    // Inline QoS is not part of the payload, so its encoding does not follow the
+    // representation of the payload. StatusInfo is octets, which read the same in any
+    // byte order.
     let status_info = match &data.inline_qos {
-      Some(iqos) => InlineQos::status_info(iqos, representation_identifier).ok(),
+      Some(iqos) => InlineQos::status_info(iqos, RepresentationIdentifier::CDR_LE).ok(),
       None => None,
     };
 
     let key_hash = match &data.inline_qos {
-      Some(iqos) => InlineQos::key_hash(iqos, representation_identifier).ok(),
+      Some(iqos) => InlineQos::key_hash(iqos).ok().flatten(),
       None => None,
     };
 
@@ -586,31 +1160,125 @@ impl Reader {
     }
 
     let mut ddsdata = if change_kind != ChangeKind::ALIVE {
-      DDSData::new_disposed(status_info, key_hash)
+      // The key hash identifies the instance. Only without it the DataReader needs to
+      // deserialize the key that a dispose or unregister may carry as payload.
+      let serialized_key = if key_hash.is_none() && status_info.is_some() {
+        data.serialized_payload
+      } else {
+        None
+      };
+      DDSData::new_disposed(status_info, key_hash, serialized_key)
     } else {
-      match data.serialized_payload {
+      let mut ddsdata = match data.serialized_payload {
         Some(pl) => DDSData::new(pl),
-        None => return,
+        None => {
+          self.statistics.sample_rejected();
+          return;
+        }
+      };
+      // keyed writers send the key hash, which indexes the change by instance in DDSCache
+      if let Some(key_hash) = key_hash {
+        ddsdata.value_key_hash = key_hash.value();
       }
+      ddsdata
     };
 
+    ddsdata.related_sample_identity = match &data.inline_qos {
+      Some(iqos) => InlineQos::related_sample_identity(iqos).ok().flatten(),
+      None => None,
+    };
     ddsdata.set_reader_id(data.reader_id);
     ddsdata.set_writer_id(data.writer_id);
-    let cache_change = CacheChange::new(change_kind, writer_guid, data.writer_sn, Some(ddsdata));
-    let mut cache = match self.dds_cache.write() {
-      Ok(rwlock) => rwlock,
-      // TODO: Should we panic here? Are we allowed to continue with poisoned DDSCache?
-      Err(e) => panic!("The DDSCache of is poisoned. Error: {}", e),
-    };
-    cache.to_topic_add_change(&self.topic_name, &instant, cache_change);
+    let mut cache_change =
+      CacheChange::new(change_kind, writer_guid, data.writer_sn, Some(ddsdata));
+    // as given by the latest InfoTimestamp from the writer
+    cache_change.source_timestamp = source_timestamp;
+    cache_change.received_by = Some(self.get_guid());
+    if let Some(writer_proxy) = self.matched_writers.get_mut(&writer_guid) {
+      writer_proxy.instance_changed(cache_change.key, cache_change.kind);
+    }
+    if self.in_message {
+      self.received_changes.push((instant, cache_change));
+      return;
+    }
+    let cache = read_lock(&self.dds_cache);
+    if let Err(e) = cache.to_topic_add_change(&self.topic_name, &instant, cache_change) {
+      warn!(
+        "Reader {:?} dropped a received change: {}",
+        self.get_guid(),
+        e
+      );
+      self.statistics.sample_rejected();
+    }
+  }
+
+  // The MessageReceiver calls this before giving the first DATA of an RTPS message to
+  // this reader, and end_message after the whole message. A message may carry many
+  // changes, and this way they take one lock of the DDSCache and one notification.
+  pub fn begin_message(&mut self) {
+    self.in_message = true;
+  }
+
+  pub fn end_message(&mut self) {
+    if !self.in_message {
+      return;
+    }
+    self.in_message = false;
+    self.add_received_changes();
+    if std::mem::replace(&mut self.notification_deferred, false) {
+      self.notify_cache_change();
+    }
+  }
+
+  // Adds the changes received so far from the current message to the DDSCache, in the
+  // order they were received.
+  fn add_received_changes(&mut self) {
+    if self.received_changes.is_empty() {
+      return;
+    }
+    let changes = std::mem::replace(&mut self.received_changes, Vec::new());
+    let count = changes.len();
+    let added = read_lock(&self.dds_cache).to_topic_add_changes(&self.topic_name, changes);
+    if let Err(e) = added {
+      warn!(
+        "Reader {:?} dropped {} received changes: {}",
+        self.get_guid(),
+        count,
+        e
+      );
+      self.statistics.samples_rejected(count as u64);
+    }
+  }
+
+  // Notifies the DataReader again, when it left samples unread
+  pub fn notify_data_available(&mut self) {
+    self.notify_cache_change();
   }
 
   // notifies DataReaders (or any listeners that history cache has changed for this reader)
-  // likely use of mio channel
-  fn notify_cache_change(&self) {
+  // The channels have room for one notification, so a burst of changes causes one
+  // wakeup, and later ones are coalesced to it.
+  fn notify_cache_change(&mut self) {
+    if self.in_message {
+      self.notification_deferred = true;
+      return;
+    }
+    // listeners are dropped when their DataReader is gone
+    self
+      .listeners
+      .retain(|l| match l.notification_sender.try_send(()) {
+        Ok(()) | Err(mio_channel::TrySendError::Full(_)) => {
+          l.data_waker.wake();
+          true
+        }
+        Err(mio_channel::TrySendError::Disconnected(_)) => false,
+        Err(mio_channel::TrySendError::Io(_)) => true,
+      });
+
     match self.notification_sender.try_send(()) {
-      Ok(()) => (),
-      Err(mio_channel::TrySendError::Full(_)) => (), // This is harmless. There is a notification in already.
+      Ok(()) => self.data_waker.wake(),
+      // There is a notification pending already, so this change is coalesced to it.
+      Err(mio_channel::TrySendError::Full(_)) => self.data_waker.wake(),
       Err(mio_channel::TrySendError::Disconnected(_)) => {
         // If we get here, our DataReader has died. The Reader should now dispose itself.
         // TODO: Implement Reader disposal.
@@ -621,9 +1289,23 @@ impl Reader {
     }
   }
 
-  fn send_acknack(&self, acknack: AckNack, mr_state: MessageReceiverState) {
+  fn send_acknack(
+    guid_prefix: GuidPrefix,
+    acknack: AckNack,
+    mr_state: MessageReceiverState,
+    tcp: Option<&TcpTransport>,
+    capture: &PacketCapture,
+  ) {
+    dds_event!(
+      trace,
+      "acknack sent",
+      writer = GUID::new_with_prefix_and_id(mr_state.source_guid_prefix, acknack.writer_id),
+      base = acknack.reader_sn_state.base,
+      count = acknack.count,
+    );
     // Should it be saved as an attribute?
-    let sender = UDPSender::new_with_random_port();
+    let mut sender = UDPSender::new_with_random_port();
+    sender.set_capture(capture.clone());
     // TODO: How to determine which flags should be one? Both on atm
     let flags = BitFlags::<ACKNACK_Flags>::from_flag(ACKNACK_Flags::Endianness)
       | BitFlags::<ACKNACK_Flags>::from_flag(ACKNACK_Flags::Final);
@@ -635,7 +1317,7 @@ impl Reader {
       protocol_id: ProtocolId::default(),
       protocol_version: ProtocolVersion::THIS_IMPLEMENTATION,
       vendor_id: VendorId::THIS_IMPLEMENTATION,
-      guid_prefix: self.entity_attributes.guid.guidPrefix,
+      guid_prefix,
     });
 
     let info_dst = InfoDestination {
@@ -658,10 +1340,18 @@ impl Reader {
       .write_to_vec_with_ctx(Endianness::LittleEndian)
       .unwrap();
     sender.send_to_locator_list(&bytes, &mr_state.unicast_reply_locator_list);
+    if let Some(tcp) = tcp {
+      tcp.send(&bytes, &mr_state.unicast_reply_locator_list);
+      capture.sent_over_tcp(&bytes, &mr_state.unicast_reply_locator_list);
+    }
   }
 
   pub fn send_preemptive_acknacks(&mut self) {
-    let sender = UDPSender::new_with_random_port();
+    if self.passive {
+      return;
+    }
+    let mut sender = UDPSender::new_with_random_port();
+    sender.set_capture(self.capture.clone());
 
     let flags = BitFlags::<ACKNACK_Flags>::from_flag(ACKNACK_Flags::Endianness)
       | BitFlags::<ACKNACK_Flags>::from_flag(ACKNACK_Flags::Final);
@@ -708,6 +1398,13 @@ impl Reader {
         .write_to_vec_with_ctx(Endianness::LittleEndian)
         .unwrap();
       sender.send_to_locator_list(&bytes, &writer_proxy.unicast_locator_list);
+      if let Some(tcp) = &self.tcp {
+        tcp.send(&bytes, &writer_proxy.unicast_locator_list);
+        self
+          .capture
+          .sent_over_tcp(&bytes, &writer_proxy.unicast_locator_list);
+      }
+      self.statistics.acknack_sent();
     }
   }
 
@@ -754,6 +1451,13 @@ impl Endpoint for Reader {
   }
 }*/
 
+impl Drop for Reader {
+  fn drop(&mut self) {
+    // async tasks waiting for data would otherwise wait forever
+    self.data_waker.close();
+  }
+}
+
 impl fmt::Debug for Reader {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     f.debug_struct("Reader")
@@ -795,6 +1499,7 @@ mod tests {
       &"test".to_string(),
       TopicKind::NoKey,
       &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
     );
     let mut reader = Reader::new(
       guid,
@@ -843,6 +1548,7 @@ mod tests {
       &"test".to_string(),
       TopicKind::NoKey,
       &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
     );
     let mut new_reader = Reader::new(
       new_guid,
@@ -884,7 +1590,7 @@ mod tests {
     let ddsdata = DDSData::new(d.serialized_payload.unwrap());
     let cc_built_here = CacheChange::new(ChangeKind::ALIVE, writer_guid, d_seqnum, Some(ddsdata));
 
-    assert_eq!(cc_from_chache.unwrap(), &cc_built_here);
+    assert_eq!(cc_from_chache.unwrap(), cc_built_here);
   }
 
   #[test]
@@ -901,6 +1607,7 @@ mod tests {
       &"test".to_string(),
       TopicKind::NoKey,
       &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
     );
     let mut new_reader = Reader::new(
       new_guid,
@@ -938,7 +1645,7 @@ mod tests {
       last_sn: SequenceNumber::from(0),
       count: 1,
     };
-    assert!(!new_reader.handle_heartbeat_msg(hb_new, true, mr_state.clone())); // should be false, no ack
+    assert!(!new_reader.handle_heartbeat_msg(hb_new, true, false, mr_state.clone())); // should be false, no ack
 
     let hb_one = Heartbeat {
       reader_id: new_reader.get_entity_id(),
@@ -947,7 +1654,7 @@ mod tests {
       last_sn: SequenceNumber::from(1),
       count: 2,
     };
-    assert!(new_reader.handle_heartbeat_msg(hb_one, false, mr_state.clone())); // Should send an ack_nack
+    assert!(new_reader.handle_heartbeat_msg(hb_one, false, false, mr_state.clone())); // Should send an ack_nack
 
     // After ack_nack, will receive the following change
     let change = CacheChange::new(
@@ -956,11 +1663,12 @@ mod tests {
       SequenceNumber::from(1),
       Some(d.clone()),
     );
-    new_reader.dds_cache.write().unwrap().to_topic_add_change(
-      &new_reader.topic_name,
-      &Timestamp::now(),
-      change.clone(),
-    );
+    new_reader
+      .dds_cache
+      .write()
+      .unwrap()
+      .to_topic_add_change(&new_reader.topic_name, &CacheInstant::now(), change.clone())
+      .unwrap();
     changes.push(change);
 
     // Duplicate
@@ -971,7 +1679,7 @@ mod tests {
       last_sn: SequenceNumber::from(1),
       count: 2,
     };
-    assert!(!new_reader.handle_heartbeat_msg(hb_one2, false, mr_state.clone())); // No acknack
+    assert!(!new_reader.handle_heartbeat_msg(hb_one2, false, false, mr_state.clone())); // No acknack
 
     let hb_3_1 = Heartbeat {
       reader_id: new_reader.get_entity_id(),
@@ -980,7 +1688,7 @@ mod tests {
       last_sn: SequenceNumber::from(3),  // writer has written 3 samples
       count: 3,
     };
-    assert!(new_reader.handle_heartbeat_msg(hb_3_1, false, mr_state.clone())); // Should send an ack_nack
+    assert!(new_reader.handle_heartbeat_msg(hb_3_1, false, false, mr_state.clone())); // Should send an ack_nack
 
     // After ack_nack, will receive the following changes
     let change = CacheChange::new(
@@ -989,11 +1697,12 @@ mod tests {
       SequenceNumber::from(2),
       Some(d.clone()),
     );
-    new_reader.dds_cache.write().unwrap().to_topic_add_change(
-      &new_reader.topic_name,
-      &Timestamp::now(),
-      change.clone(),
-    );
+    new_reader
+      .dds_cache
+      .write()
+      .unwrap()
+      .to_topic_add_change(&new_reader.topic_name, &CacheInstant::now(), change.clone())
+      .unwrap();
     changes.push(change);
 
     let change = CacheChange::new(
@@ -1002,11 +1711,12 @@ mod tests {
       SequenceNumber::from(3),
       Some(d),
     );
-    new_reader.dds_cache.write().unwrap().to_topic_add_change(
-      &new_reader.topic_name,
-      &Timestamp::now(),
-      change.clone(),
-    );
+    new_reader
+      .dds_cache
+      .write()
+      .unwrap()
+      .to_topic_add_change(&new_reader.topic_name, &CacheInstant::now(), change.clone())
+      .unwrap();
     changes.push(change);
 
     let hb_none = Heartbeat {
@@ -1016,7 +1726,7 @@ mod tests {
       last_sn: SequenceNumber::from(3),  // writer has written 3 samples
       count: 4,
     };
-    assert!(new_reader.handle_heartbeat_msg(hb_none, false, mr_state)); // Should sen acknack
+    assert!(new_reader.handle_heartbeat_msg(hb_none, false, false, mr_state)); // Should sen acknack
 
     assert_eq!(new_reader.sent_ack_nack_count, 3);
   }
@@ -1034,6 +1744,7 @@ mod tests {
       &"test".to_string(),
       TopicKind::NoKey,
       &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
     );
     let mut reader = Reader::new(
       new_guid,
@@ -1065,7 +1776,8 @@ mod tests {
     d.writer_id = writer_id;
     let mut changes = Vec::new();
 
-    for i in 0..n {
+    // sequence numbers start from 1
+    for i in 1..n {
       d.writer_sn = SequenceNumber::from(i);
       reader.handle_data_msg(d.clone(), mr_state.clone());
       changes.push(
@@ -1078,8 +1790,8 @@ mod tests {
 
     // make sequence numbers 1-3 and 5 7 irrelevant
     let mut gap_list = SequenceNumberSet::new(SequenceNumber::from(4));
-    gap_list.insert(SequenceNumber::from(5 + 4)); // TODO! Why do you need to add base!?
-    gap_list.insert(SequenceNumber::from(7 + 4));
+    gap_list.insert(SequenceNumber::from(5));
+    gap_list.insert(SequenceNumber::from(7));
 
     let gap = Gap {
       reader_id: reader.get_entity_id(),
@@ -1091,10 +1803,6 @@ mod tests {
     // Cache changee muutetaan tutkiin datan kirjoittajaa.
     reader.handle_gap_msg(gap, mr_state);
 
-    assert_eq!(
-      reader.get_history_cache_change(SequenceNumber::from(0)),
-      Some(changes[0].clone())
-    );
     assert_eq!(
       reader.get_history_cache_change(SequenceNumber::from(1)),
       None
@@ -1109,7 +1817,7 @@ mod tests {
     );
     assert_eq!(
       reader.get_history_cache_change(SequenceNumber::from(4)),
-      Some(changes[4].clone())
+      Some(changes[3].clone())
     );
     assert_eq!(
       reader.get_history_cache_change(SequenceNumber::from(5)),
@@ -1117,7 +1825,7 @@ mod tests {
     );
     assert_eq!(
       reader.get_history_cache_change(SequenceNumber::from(6)),
-      Some(changes[6].clone())
+      Some(changes[5].clone())
     );
     assert_eq!(
       reader.get_history_cache_change(SequenceNumber::from(7)),
@@ -1125,11 +1833,664 @@ mod tests {
     );
     assert_eq!(
       reader.get_history_cache_change(SequenceNumber::from(8)),
-      Some(changes[8].clone())
+      Some(changes[7].clone())
     );
     assert_eq!(
       reader.get_history_cache_change(SequenceNumber::from(9)),
-      Some(changes[9].clone())
+      Some(changes[8].clone())
+    );
+  }
+
+  #[test]
+  fn rtpsreader_handles_extreme_sequence_numbers() {
+    let new_guid = GUID::new();
+    let (send, _rec) = mio_channel::sync_channel::<()>(100);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_command_sender, reader_command_receiver) =
+      mio_channel::sync_channel::<ReaderCommand>(10);
+
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache.write().unwrap().add_new_topic(
+      &"test".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
     );
+    let mut reader = Reader::new(
+      new_guid,
+      send,
+      status_sender,
+      dds_cache,
+      "test".to_string(),
+      reader_command_receiver,
+    );
+
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let writer_id = writer_guid.entityId;
+
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+
+    reader.matched_writer_add(
+      writer_guid.clone(),
+      EntityId::ENTITYID_UNKNOWN,
+      mr_state.unicast_reply_locator_list.clone(),
+      mr_state.multicast_reply_locator_list.clone(),
+    );
+    let max = SequenceNumber::from(std::i64::MAX);
+
+    // Valid, but the AckNack can ask for 256 of the changes only.
+    let heartbeat = Heartbeat {
+      reader_id: reader.get_entity_id(),
+      writer_id,
+      first_sn: SequenceNumber::from(1),
+      last_sn: max,
+      count: 1,
+    };
+    assert!(reader.handle_heartbeat_msg(heartbeat, false, false, mr_state.clone()));
+    let writer_proxy = reader.matched_writer_lookup(writer_guid).unwrap();
+    assert_eq!(
+      writer_proxy
+        .get_missing_sequence_numbers(SequenceNumber::from(1), max)
+        .len(),
+      256
+    );
+
+    // All but the last possible change are irrelevant.
+    let gap = Gap {
+      reader_id: reader.get_entity_id(),
+      writer_id,
+      gap_start: SequenceNumber::from(1),
+      gap_list: SequenceNumberSet::new(max),
+    };
+    reader.handle_gap_msg(gap, mr_state.clone());
+    let writer_proxy = reader.matched_writer_lookup(writer_guid).unwrap();
+    assert_eq!(writer_proxy.received_before(), max);
+    assert_eq!(
+      writer_proxy.get_missing_sequence_numbers(SequenceNumber::from(1), max),
+      vec![max]
+    );
+
+    let mut d = Data::default();
+    d.writer_id = writer_id;
+    d.writer_sn = max;
+    reader.handle_data_msg(d, mr_state.clone());
+    assert!(reader.get_history_cache_change(max).is_some());
+
+    let heartbeat = Heartbeat {
+      reader_id: reader.get_entity_id(),
+      writer_id,
+      first_sn: max,
+      last_sn: max,
+      count: 2,
+    };
+    assert!(!reader.handle_heartbeat_msg(heartbeat, true, false, mr_state));
+  }
+
+  #[test]
+  fn rtpsreader_sample_lost() {
+    let new_guid = GUID::new();
+    let (send, _rec) = mio_channel::sync_channel::<()>(100);
+    let (status_sender, status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_command_sender, reader_command_receiver) =
+      mio_channel::sync_channel::<ReaderCommand>(10);
+
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache.write().unwrap().add_new_topic(
+      &"test".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let mut reader = Reader::new(
+      new_guid,
+      send,
+      status_sender,
+      dds_cache,
+      "test".to_string(),
+      reader_command_receiver,
+    );
+
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let writer_id = writer_guid.entityId;
+
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+
+    reader.matched_writer_add(
+      writer_guid.clone(),
+      EntityId::ENTITYID_UNKNOWN,
+      mr_state.unicast_reply_locator_list.clone(),
+      mr_state.multicast_reply_locator_list.clone(),
+    );
+
+    match status_reciever.try_recv() {
+      Ok(StatusChange::SubscriptionMatchedStatus(status)) => {
+        assert_eq!(status.current_count(), 1)
+      }
+      other => panic!("Expected SubscriptionMatchedStatus, got {:?}", other),
+    }
+
+    let mut d = Data::default();
+    d.writer_id = writer_id;
+    for i in 1..3 {
+      d.writer_sn = SequenceNumber::from(i);
+      reader.handle_data_msg(d.clone(), mr_state.clone());
+    }
+    assert!(status_reciever.try_recv().is_err());
+
+    // sequence number 3 is irrelevant, so it is not lost
+    let gap = Gap {
+      reader_id: reader.get_entity_id(),
+      writer_id,
+      gap_start: SequenceNumber::from(3),
+      gap_list: SequenceNumberSet::new(SequenceNumber::from(4)),
+    };
+    reader.handle_gap_msg(gap, mr_state.clone());
+
+    // 4 is lost
+    d.writer_sn = SequenceNumber::from(5);
+    reader.handle_data_msg(d.clone(), mr_state.clone());
+    match status_reciever.try_recv() {
+      Ok(StatusChange::SampleLostStatus(status)) => assert_eq!(status.count(), 1),
+      other => panic!("Expected SampleLostStatus, got {:?}", other),
+    }
+
+    // 6 and 7 are lost
+    d.writer_sn = SequenceNumber::from(8);
+    reader.handle_data_msg(d.clone(), mr_state.clone());
+    match status_reciever.try_recv() {
+      Ok(StatusChange::SampleLostStatus(status)) => {
+        assert_eq!(status.count(), 3);
+        assert_eq!(status.count_change(), 3);
+      }
+      other => panic!("Expected SampleLostStatus, got {:?}", other),
+    }
+
+    // late arrival does not count as anything new
+    d.writer_sn = SequenceNumber::from(7);
+    reader.handle_data_msg(d, mr_state);
+    assert!(status_reciever.try_recv().is_err());
+  }
+
+  // A reader of topic "test" matched with the writer, and the cache it delivers to
+  fn reader_matched_with(
+    writer_guid: GUID,
+    reliable: bool,
+  ) -> (
+    Reader,
+    Arc<RwLock<DDSCache>>,
+    mio_channel::Receiver<StatusChange>,
+  ) {
+    let (send, _rec) = mio_channel::sync_channel::<()>(100);
+    let (status_sender, status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_command_sender, reader_command_receiver) =
+      mio_channel::sync_channel::<ReaderCommand>(10);
+
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache.write().unwrap().add_new_topic(
+      &"test".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let mut reader = Reader::new(
+      GUID::new(),
+      send,
+      status_sender,
+      dds_cache.clone(),
+      "test".to_string(),
+      reader_command_receiver,
+    );
+    if reliable {
+      let qos = QosPolicyBuilder::new()
+        .reliability(Reliability::Reliable {
+          max_blocking_time: Duration::from_secs(1),
+        })
+        .build();
+      reader.set_qos(&qos).unwrap();
+    }
+    reader.matched_writer_add(
+      writer_guid,
+      EntityId::ENTITYID_UNKNOWN,
+      LocatorList::new(),
+      LocatorList::new(),
+    );
+    // the SubscriptionMatchedStatus
+    assert!(status_reciever.try_recv().is_ok());
+    (reader, dds_cache, status_reciever)
+  }
+
+  // (writer, sequence number) of the cached changes, in delivery order
+  fn delivered(dds_cache: &Arc<RwLock<DDSCache>>) -> Vec<(GUID, i64)> {
+    dds_cache
+      .read()
+      .unwrap()
+      .from_topic_get_all_changes("test")
+      .iter()
+      .map(|(_, cc)| (cc.writer_guid, i64::from(cc.sequence_number)))
+      .collect()
+  }
+
+  fn feed(reader: &mut Reader, writer_guid: GUID, seqnums: &[i64]) {
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+    let mut d = Data::default();
+    d.reader_id = reader.get_entity_id();
+    d.writer_id = writer_guid.entityId;
+    for &sn in seqnums {
+      d.writer_sn = SequenceNumber::from(sn);
+      reader.handle_data_msg(d.clone(), mr_state.clone());
+    }
+  }
+
+  #[test]
+  fn rtpsreader_best_effort_duplicates_and_reordering() {
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let (mut reader, dds_cache, _status_reciever) = reader_matched_with(writer_guid, false);
+
+    // e.g. received by both unicast and multicast
+    feed(&mut reader, writer_guid, &[1, 1, 2, 1, 2]);
+    assert_eq!(
+      delivered(&dds_cache),
+      vec![(writer_guid, 1), (writer_guid, 2)]
+    );
+
+    // delivered in arrival order, but only once
+    feed(&mut reader, writer_guid, &[5, 3, 5, 4, 3]);
+    let seqnums: Vec<i64> = delivered(&dds_cache).iter().map(|&(_, sn)| sn).collect();
+    assert_eq!(seqnums, vec![1, 2, 5, 3, 4]);
+
+    // Far ahead: the window moves, and the changes left behind are not waited for.
+    feed(&mut reader, writer_guid, &[1000, 6, 1000]);
+    let seqnums: Vec<i64> = delivered(&dds_cache).iter().map(|&(_, sn)| sn).collect();
+    assert_eq!(seqnums, vec![1, 2, 5, 3, 4, 1000]);
+  }
+
+  #[test]
+  fn rtpsreader_reliable_delivers_in_order() {
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let writer_id = writer_guid.entityId;
+    let (mut reader, dds_cache, status_reciever) = reader_matched_with(writer_guid, true);
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+
+    // waiting for 1
+    feed(&mut reader, writer_guid, &[3, 2, 3]);
+    assert!(delivered(&dds_cache).is_empty());
+    // Still missing, and asked for again. The ones waiting are not.
+    let hb = Heartbeat {
+      reader_id: reader.get_entity_id(),
+      writer_id,
+      first_sn: SequenceNumber::from(1),
+      last_sn: SequenceNumber::from(3),
+      count: 1,
+    };
+    assert!(reader.handle_heartbeat_msg(hb, true, false, mr_state.clone()));
+    let writer_proxy = reader.matched_writer_lookup(writer_guid).unwrap();
+    assert_eq!(
+      writer_proxy.get_missing_sequence_numbers(SequenceNumber::from(1), SequenceNumber::from(3)),
+      vec![SequenceNumber::from(1)]
+    );
+
+    feed(&mut reader, writer_guid, &[1, 2]);
+    assert_eq!(
+      delivered(&dds_cache),
+      vec![(writer_guid, 1), (writer_guid, 2), (writer_guid, 3)]
+    );
+
+    // 6 waits until the GAP tells that 4 will not come.
+    feed(&mut reader, writer_guid, &[6, 5]);
+    assert_eq!(delivered(&dds_cache).len(), 3);
+    let gap = Gap {
+      reader_id: reader.get_entity_id(),
+      writer_id,
+      gap_start: SequenceNumber::from(4),
+      gap_list: SequenceNumberSet::new(SequenceNumber::from(5)),
+    };
+    reader.handle_gap_msg(gap, mr_state.clone());
+    let seqnums: Vec<i64> = delivered(&dds_cache).iter().map(|&(_, sn)| sn).collect();
+    assert_eq!(seqnums, vec![1, 2, 3, 5, 6]);
+
+    // 7 is no longer available from the writer, so 8 does not wait for it.
+    feed(&mut reader, writer_guid, &[8, 6]);
+    let hb = Heartbeat {
+      reader_id: reader.get_entity_id(),
+      writer_id,
+      first_sn: SequenceNumber::from(8),
+      last_sn: SequenceNumber::from(8),
+      count: 2,
+    };
+    reader.handle_heartbeat_msg(hb, true, false, mr_state);
+    assert_eq!(delivered(&dds_cache).last(), Some(&(writer_guid, 8)));
+
+    // only 7 was lost
+    let mut lost = 0;
+    while let Ok(status) = status_reciever.try_recv() {
+      if let StatusChange::SampleLostStatus(status) = status {
+        lost = status.count();
+      }
+    }
+    assert_eq!(lost, 1);
+  }
+
+  #[test]
+  fn rtpsreader_writer_restart() {
+    let old_writer = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    // The restarted writer has a new GUID, and starts again from 1.
+    let new_writer = GUID {
+      guidPrefix: GuidPrefix::new(vec![2; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let (mut reader, dds_cache, _status_reciever) = reader_matched_with(old_writer, true);
+    feed(&mut reader, old_writer, &[1, 2, 3]);
+
+    reader.matched_writer_add(
+      new_writer,
+      EntityId::ENTITYID_UNKNOWN,
+      LocatorList::new(),
+      LocatorList::new(),
+    );
+    feed(&mut reader, new_writer, &[2, 1, 2]);
+    // late duplicates of the old writer
+    feed(&mut reader, old_writer, &[2, 3]);
+    assert_eq!(
+      delivered(&dds_cache),
+      vec![
+        (old_writer, 1),
+        (old_writer, 2),
+        (old_writer, 3),
+        (new_writer, 1),
+        (new_writer, 2)
+      ]
+    );
+
+    // Forgetting the old writer unregisters its instance, and does not affect the new one.
+    reader.matched_writer_remove(old_writer);
+    feed(&mut reader, new_writer, &[3]);
+    assert_eq!(delivered(&dds_cache).len(), 7);
+    assert!(delivered(&dds_cache).contains(&(new_writer, 3)));
+    let cache = dds_cache.read().unwrap();
+    assert!(cache
+      .from_topic_get_all_changes("test")
+      .iter()
+      .any(|(_, cc)| cc.writer_guid == old_writer && cc.kind == ChangeKind::NOT_ALIVE_UNREGISTERED));
+  }
+  #[test]
+  fn rtpsreader_local_change() {
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 2),
+    };
+    let (mut reader, dds_cache, _status_reciever) = reader_matched_with(writer_guid, true);
+    let change = |sn: i64| {
+      CacheChange::new(
+        ChangeKind::ALIVE,
+        writer_guid,
+        SequenceNumber::from(sn),
+        Some(DDSData::new(SerializedPayload::new(
+          RepresentationIdentifier::CDR_LE,
+          vec![1, 2, 3, 4],
+        ))),
+      )
+    };
+
+    reader.handle_local_change(&change(1));
+    reader.handle_local_change(&change(1));
+    reader.handle_local_change(&change(2));
+    assert_eq!(
+      delivered(&dds_cache),
+      vec![(writer_guid, 1), (writer_guid, 2)]
+    );
+    let cache = dds_cache.read().unwrap();
+    for (_, cc) in cache.from_topic_get_all_changes("test") {
+      assert_eq!(cc.received_by, Some(reader.get_guid()));
+      assert!(cc.source_timestamp.is_some());
+    }
+    drop(cache);
+
+    // not from a matched writer
+    let mut other = change(3);
+    other.writer_guid.entityId = EntityId::createCustomEntityID([2; 3], 2);
+    reader.handle_local_change(&other);
+    assert_eq!(delivered(&dds_cache).len(), 2);
+  }
+
+  #[test]
+  fn rtpsreader_dispose_after_topic_removed() {
+    use crate::messages::submessages::submessage_elements::{
+      parameter::Parameter, parameter_list::ParameterList,
+    };
+
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 3),
+    };
+    let (mut reader, dds_cache, _status_reciever) = reader_matched_with(writer_guid, false);
+    feed(&mut reader, writer_guid, &[1]);
+    assert_eq!(delivered(&dds_cache), vec![(writer_guid, 1)]);
+
+    // The topic is deleted while the dispose is on its way.
+    dds_cache.write().unwrap().remove_topic(&"test".to_string());
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+    let mut param_list = ParameterList::new();
+    param_list
+      .parameters
+      .push(Parameter::create_pid_status_info_parameter(
+        true, false, false,
+      ));
+    let mut dispose = Data::default();
+    dispose.reader_id = reader.get_entity_id();
+    dispose.writer_id = writer_guid.entityId;
+    dispose.writer_sn = SequenceNumber::from(2);
+    dispose.inline_qos = Some(param_list);
+    reader.handle_data_msg(dispose, mr_state);
+    reader.handle_local_change(&CacheChange::new(
+      ChangeKind::NOT_ALIVE_DISPOSED,
+      writer_guid,
+      SequenceNumber::from(3),
+      None,
+    ));
+
+    // Nothing was stored, and the DDSCache is still usable.
+    assert!(!dds_cache.is_poisoned());
+    assert!(delivered(&dds_cache).is_empty());
+    dds_cache.write().unwrap().add_new_topic(
+      &"test".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    feed(&mut reader, writer_guid, &[4]);
+    assert_eq!(delivered(&dds_cache), vec![(writer_guid, 4)]);
+  }
+
+  #[test]
+  fn rtpsreader_deadline_and_lifespan_follow_clock() {
+    use crate::dds::qos::policy::{Deadline, Lifespan};
+    use crate::structure::clock::{Clock, ManualClock};
+
+    let clock = Arc::new(ManualClock::new());
+    let (send, _rec) = mio_channel::sync_channel::<()>(100);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_command_sender, reader_command_receiver) =
+      mio_channel::sync_channel::<ReaderCommand>(10);
+    let dds_cache = Arc::new(RwLock::new(DDSCache::with_clock(SharedClock::from(
+      clock.clone(),
+    ))));
+    dds_cache.write().unwrap().add_new_topic(
+      &"test".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let mut reader = Reader::new(
+      GUID::new(),
+      send,
+      status_sender,
+      dds_cache.clone(),
+      "test".to_string(),
+      reader_command_receiver,
+    );
+    let qos = QosPolicyBuilder::new()
+      .deadline(Deadline(Duration::from_secs(1)))
+      .lifespan(Lifespan {
+        duration: Duration::from_secs(2),
+      })
+      .build();
+    reader.set_qos(&qos).unwrap();
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    reader.matched_writer_add(
+      writer_guid,
+      EntityId::ENTITYID_UNKNOWN,
+      LocatorList::new(),
+      LocatorList::new(),
+    );
+
+    // nothing received yet
+    assert_eq!(reader.calculate_if_requested_deadline_is_missed().len(), 1);
+
+    feed(&mut reader, writer_guid, &[1]);
+    clock.advance(StdDuration::from_millis(1000));
+    assert!(reader
+      .calculate_if_requested_deadline_is_missed()
+      .is_empty());
+    clock.advance(StdDuration::from_millis(1));
+    let changes = reader.calculate_if_requested_deadline_is_missed();
+    assert!(matches!(
+      changes.as_slice(),
+      [StatusChange::RequestedDeadlineMissedStatus(status)] if status.count() == 2
+    ));
+
+    // Samples older than their lifespan are dropped on arrival.
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+    mr_state.timestamp = Some(clock.now());
+    let mut d = Data::default();
+    d.reader_id = reader.get_entity_id();
+    d.writer_id = writer_guid.entityId;
+    d.writer_sn = SequenceNumber::from(2);
+    clock.advance(StdDuration::from_secs(2));
+    reader.handle_data_msg(d.clone(), mr_state.clone());
+    assert_eq!(delivered(&dds_cache), vec![(writer_guid, 1), (writer_guid, 2)]);
+    d.writer_sn = SequenceNumber::from(3);
+    clock.advance(StdDuration::from_millis(1));
+    reader.handle_data_msg(d, mr_state);
+    assert_eq!(delivered(&dds_cache), vec![(writer_guid, 1), (writer_guid, 2)]);
+  }
+
+  #[test]
+  fn rtpsreader_writer_liveliness_lease() {
+    use crate::structure::clock::{Clock, ManualClock};
+
+    let clock = Arc::new(ManualClock::new());
+    let (send, _rec) = mio_channel::sync_channel::<()>(100);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_command_sender, reader_command_receiver) =
+      mio_channel::sync_channel::<ReaderCommand>(10);
+    let dds_cache = Arc::new(RwLock::new(DDSCache::with_clock(SharedClock::from(
+      clock.clone(),
+    ))));
+    dds_cache.write().unwrap().add_new_topic(
+      &"test".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("testi".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let mut reader = Reader::new(
+      GUID::new(),
+      send,
+      status_sender,
+      dds_cache.clone(),
+      "test".to_string(),
+      reader_command_receiver,
+    );
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let mut proxy = RtpsWriterProxy::new(
+      writer_guid,
+      LocatorList::new(),
+      LocatorList::new(),
+      EntityId::ENTITYID_UNKNOWN,
+    );
+    proxy.liveliness = Some(Liveliness::Automatic {
+      lease_duration: Duration::from_secs(1),
+    });
+    reader.add_writer_proxy(proxy);
+    let unregistered = |dds_cache: &Arc<RwLock<DDSCache>>| {
+      dds_cache
+        .read()
+        .unwrap()
+        .from_topic_get_all_changes("test")
+        .iter()
+        .filter(|(_, cc)| cc.kind == ChangeKind::NOT_ALIVE_UNREGISTERED)
+        .count()
+    };
+
+    feed(&mut reader, writer_guid, &[1]);
+    clock.advance(StdDuration::from_millis(999));
+    reader.handle_liveliness_check();
+    assert_eq!(unregistered(&dds_cache), 0);
+
+    // any heartbeat shows that an Automatic writer is alive
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+    let hb = Heartbeat {
+      reader_id: reader.get_entity_id(),
+      writer_id: writer_guid.entityId,
+      first_sn: SequenceNumber::from(1),
+      last_sn: SequenceNumber::from(1),
+      count: 1,
+    };
+    reader.handle_heartbeat_msg(hb, true, false, mr_state);
+    clock.advance(StdDuration::from_millis(999));
+    reader.handle_liveliness_check();
+    assert_eq!(unregistered(&dds_cache), 0);
+
+    // The instances of the writer lose it once, when the lease expires.
+    clock.advance(StdDuration::from_millis(1));
+    reader.handle_liveliness_check();
+    assert_eq!(unregistered(&dds_cache), 1);
+    assert!(!reader.matched_writers[&writer_guid].alive);
+    clock.advance(StdDuration::from_secs(2));
+    reader.handle_liveliness_check();
+    assert_eq!(unregistered(&dds_cache), 1);
+
+    // ManualByParticipant writers are not asserted by automatic participant messages
+    let mut proxy = RtpsWriterProxy::new(
+      writer_guid,
+      LocatorList::new(),
+      LocatorList::new(),
+      EntityId::ENTITYID_UNKNOWN,
+    );
+    proxy.liveliness = Some(Liveliness::ManualByParticipant {
+      lease_duration: Duration::from_secs(1),
+    });
+    reader.add_writer_proxy(proxy);
+    reader.participant_liveliness_asserted(writer_guid.guidPrefix, false);
+    assert!(!reader.matched_writers[&writer_guid].alive);
+    reader.participant_liveliness_asserted(writer_guid.guidPrefix, true);
+    assert!(reader.matched_writers[&writer_guid].alive);
   }
 }
@@ -0,0 +1,544 @@
+//! Request/reply (RPC) over DDS: a request topic and a reply topic per service.
+//!
+//! A [Requester](struct.Requester.html) writes requests. Each request is
+//! identified by its sample identity, i.e. the GUID of the request writer and
+//! the sequence number of the sample. A [Replier](struct.Replier.html) sends
+//! the identity of the request back in the related sample identity inline QoS
+//! of its reply, which is how the Requester matches replies to requests.
+//!
+//! Fragmented (large) samples do not carry inline QoS, so requests and replies
+//! must fit in a single DATA submessage.
+
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  time::Instant,
+};
+
+use log::{debug, warn};
+use mio::Evented;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+  dds::{
+    no_key::{datareader::DataReader, datasample::DataSample, datawriter::DataWriter},
+    pubsub::{Publisher, Subscriber},
+    qos::QosPolicies,
+    topic::{Topic, TopicKind},
+    values::result::{Error, Result},
+    DomainParticipant,
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+  structure::{duration::Duration, entity::Entity, guid::GUID, inline_qos::SampleIdentity},
+};
+
+/// Request and reply topics of a service, named `<service>_Request` and
+/// `<service>_Reply`. Keep alive longer than the Requesters and Repliers using
+/// them.
+pub struct RpcTopics {
+  request_topic: Topic,
+  reply_topic: Topic,
+}
+
+impl RpcTopics {
+  /// # Arguments
+  ///
+  /// * `domain_participant` - [DomainParticipant](struct.DomainParticipant.html)
+  /// * `service_name` - Name of the service, e.g. `Calculator`
+  /// * `request_type` - Type name of the requests
+  /// * `reply_type` - Type name of the replies
+  /// * `qos` - Quality of Service parameters for both topics, usually reliable
+  pub fn new(
+    domain_participant: &DomainParticipant,
+    service_name: &str,
+    request_type: &str,
+    reply_type: &str,
+    qos: &QosPolicies,
+  ) -> Result<RpcTopics> {
+    Ok(RpcTopics {
+      request_topic: domain_participant.create_topic(
+        &format!("{}_Request", service_name),
+        request_type,
+        qos,
+        TopicKind::NoKey,
+      )?,
+      reply_topic: domain_participant.create_topic(
+        &format!("{}_Reply", service_name),
+        reply_type,
+        qos,
+        TopicKind::NoKey,
+      )?,
+    })
+  }
+
+  pub fn request_topic(&self) -> &Topic {
+    &self.request_topic
+  }
+
+  pub fn reply_topic(&self) -> &Topic {
+    &self.reply_topic
+  }
+}
+
+/// Sends requests and receives the replies to them. Any number of requests
+/// may be waiting for a reply at the same time. If several Repliers answer the
+/// same request, the first reply wins and the others are discarded.
+pub struct Requester<'a, Req, Rep>
+where
+  Req: Serialize,
+  Rep: DeserializeOwned + 'static,
+{
+  request_writer: DataWriter<'a, Req, CDRSerializerAdapter<Req>>,
+  reply_reader: DataReader<'a, Rep, CDRDeserializerAdapter<Rep>>,
+  // requests without a reply yet
+  pending_requests: BTreeSet<SampleIdentity>,
+  // replies that arrived while waiting for another one
+  received_replies: BTreeMap<SampleIdentity, Rep>,
+}
+
+impl<'a, Req, Rep> Requester<'a, Req, Rep>
+where
+  Req: Serialize,
+  Rep: DeserializeOwned + 'static,
+{
+  /// `qos` is used for both the request writer and the reply reader. If
+  /// `None`, the QoS of the topics is used.
+  pub fn new(
+    publisher: &'a Publisher,
+    subscriber: &'a Subscriber,
+    topics: &'a RpcTopics,
+    qos: Option<QosPolicies>,
+  ) -> Result<Requester<'a, Req, Rep>> {
+    Ok(Requester {
+      request_writer: publisher.create_datawriter_no_key(
+        None,
+        topics.request_topic(),
+        qos.clone(),
+      )?,
+      reply_reader: subscriber.create_datareader_no_key(topics.reply_topic(), None, qos)?,
+      pending_requests: BTreeSet::new(),
+      received_replies: BTreeMap::new(),
+    })
+  }
+
+  /// Sends a request without waiting for the reply. The returned id is the
+  /// sample identity of the request.
+  pub fn send_request(&mut self, request: Req) -> Result<SampleIdentity> {
+    let id = self
+      .request_writer
+      .write_with_related_sample_identity(request, None)?;
+    self.pending_requests.insert(id);
+    Ok(id)
+  }
+
+  /// Takes the next reply to a request of this Requester, if any.
+  pub fn take_reply(&mut self) -> Result<Option<(SampleIdentity, Rep)>> {
+    if let Some(id) = self.received_replies.keys().next().copied() {
+      return Ok(self.received_replies.remove(&id).map(|reply| (id, reply)));
+    }
+    while let Some(sample) = self.reply_reader.take_next_sample()? {
+      if let Some(reply) = self.pending_reply(sample) {
+        return Ok(Some(reply));
+      }
+    }
+    Ok(None)
+  }
+
+  /// Waits for at most `timeout` for a reply to any request of this Requester.
+  pub fn receive_reply(&mut self, timeout: Duration) -> Result<Option<(SampleIdentity, Rep)>> {
+    let deadline = Instant::now() + timeout.to_std();
+    loop {
+      if let Some(reply) = self.take_reply()? {
+        return Ok(Some(reply));
+      }
+      let now = Instant::now();
+      if now >= deadline {
+        return Ok(None);
+      }
+      let remaining = Duration::from_std(deadline - now);
+      if let Some(sample) = self.reply_reader.take_next_sample_timeout(remaining)? {
+        if let Some(reply) = self.pending_reply(sample) {
+          return Ok(Some(reply));
+        }
+      }
+    }
+  }
+
+  /// Waits for at most `timeout` for the reply to request `id`. Replies to
+  /// other requests that arrive meanwhile are kept for
+  /// [take_reply](#method.take_reply).
+  pub fn receive_reply_for(
+    &mut self,
+    id: SampleIdentity,
+    timeout: Duration,
+  ) -> Result<Option<Rep>> {
+    if let Some(reply) = self.received_replies.remove(&id) {
+      return Ok(Some(reply));
+    }
+    if !self.pending_requests.contains(&id) {
+      return Err(Error::precondition_not_met(format!(
+        "request {:?} is not waiting for a reply",
+        id
+      )));
+    }
+    let deadline = Instant::now() + timeout.to_std();
+    loop {
+      let now = Instant::now();
+      if now >= deadline {
+        debug!("Request {:?} timed out.", id);
+        return Ok(None);
+      }
+      let remaining = Duration::from_std(deadline - now);
+      if let Some(sample) = self.reply_reader.take_next_sample_timeout(remaining)? {
+        match self.pending_reply(sample) {
+          Some((reply_id, reply)) if reply_id == id => return Ok(Some(reply)),
+          Some((reply_id, reply)) => {
+            self.received_replies.insert(reply_id, reply);
+          }
+          None => (),
+        }
+      }
+    }
+  }
+
+  /// Stops waiting for the reply to request `id`. A reply arriving later is
+  /// discarded.
+  pub fn cancel_request(&mut self, id: SampleIdentity) {
+    self.pending_requests.remove(&id);
+    self.received_replies.remove(&id);
+  }
+
+  /// Number of requests without a reply
+  pub fn pending_requests(&self) -> usize {
+    self.pending_requests.len()
+  }
+
+  pub fn request_writer_guid(&self) -> GUID {
+    self.request_writer.get_guid()
+  }
+
+  pub fn reply_reader_guid(&self) -> GUID {
+    self.reply_reader.get_guid()
+  }
+
+  // Replies to other Requesters arrive on the same topic, as do further
+  // replies to requests that already got one.
+  fn pending_reply(&mut self, sample: DataSample<Rep>) -> Option<(SampleIdentity, Rep)> {
+    let related_sample_identity = sample.sample_info().related_sample_identity;
+    let publication_handle = sample.sample_info().publication_handle;
+    let reply = sample.into_value()?;
+    match related_sample_identity {
+      Some(id) if self.pending_requests.remove(&id) => Some((id, reply)),
+      Some(id) => {
+        debug!("Discarding reply to {:?}, which is not pending.", id);
+        None
+      }
+      None => {
+        warn!(
+          "Discarding reply without related sample identity from {:?}",
+          publication_handle
+        );
+        None
+      }
+    }
+  }
+}
+
+impl<'a, Req, Rep> Evented for Requester<'a, Req, Rep>
+where
+  Req: Serialize,
+  Rep: DeserializeOwned + 'static,
+{
+  fn register(
+    &self,
+    poll: &mio::Poll,
+    token: mio::Token,
+    interest: mio::Ready,
+    opts: mio::PollOpt,
+  ) -> std::io::Result<()> {
+    poll.register(&self.reply_reader, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &mio::Poll,
+    token: mio::Token,
+    interest: mio::Ready,
+    opts: mio::PollOpt,
+  ) -> std::io::Result<()> {
+    poll.reregister(&self.reply_reader, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &mio::Poll) -> std::io::Result<()> {
+    poll.deregister(&self.reply_reader)
+  }
+}
+
+/// Receives requests and sends replies to them.
+pub struct Replier<'a, Req, Rep>
+where
+  Req: DeserializeOwned + 'static,
+  Rep: Serialize,
+{
+  request_reader: DataReader<'a, Req, CDRDeserializerAdapter<Req>>,
+  reply_writer: DataWriter<'a, Rep, CDRSerializerAdapter<Rep>>,
+}
+
+impl<'a, Req, Rep> Replier<'a, Req, Rep>
+where
+  Req: DeserializeOwned + 'static,
+  Rep: Serialize,
+{
+  /// `qos` is used for both the request reader and the reply writer. If
+  /// `None`, the QoS of the topics is used.
+  pub fn new(
+    publisher: &'a Publisher,
+    subscriber: &'a Subscriber,
+    topics: &'a RpcTopics,
+    qos: Option<QosPolicies>,
+  ) -> Result<Replier<'a, Req, Rep>> {
+    Ok(Replier {
+      request_reader: subscriber.create_datareader_no_key(
+        topics.request_topic(),
+        None,
+        qos.clone(),
+      )?,
+      reply_writer: publisher.create_datawriter_no_key(None, topics.reply_topic(), qos)?,
+    })
+  }
+
+  /// Takes the next request, if any. Answer it with
+  /// [send_reply](#method.send_reply) using the returned id.
+  pub fn take_request(&mut self) -> Result<Option<(SampleIdentity, Req)>> {
+    while let Some(sample) = self.request_reader.take_next_sample()? {
+      if let Some(request) = Self::identified_request(sample) {
+        return Ok(Some(request));
+      }
+    }
+    Ok(None)
+  }
+
+  /// Waits for at most `timeout` for the next request.
+  pub fn receive_request(&mut self, timeout: Duration) -> Result<Option<(SampleIdentity, Req)>> {
+    let deadline = Instant::now() + timeout.to_std();
+    loop {
+      if let Some(request) = self.take_request()? {
+        return Ok(Some(request));
+      }
+      let now = Instant::now();
+      if now >= deadline {
+        return Ok(None);
+      }
+      let remaining = Duration::from_std(deadline - now);
+      if let Some(sample) = self.request_reader.take_next_sample_timeout(remaining)? {
+        if let Some(request) = Self::identified_request(sample) {
+          return Ok(Some(request));
+        }
+      }
+    }
+  }
+
+  /// Sends the reply to request `id`.
+  pub fn send_reply(&self, id: SampleIdentity, reply: Rep) -> Result<()> {
+    self
+      .reply_writer
+      .write_with_related_sample_identity(reply, Some(id))
+      .map(|_| ())
+  }
+
+  pub fn request_reader_guid(&self) -> GUID {
+    self.request_reader.get_guid()
+  }
+
+  pub fn reply_writer_guid(&self) -> GUID {
+    self.reply_writer.get_guid()
+  }
+
+  fn identified_request(sample: DataSample<Req>) -> Option<(SampleIdentity, Req)> {
+    let sample_identity = sample.sample_info().sample_identity;
+    let request = sample.into_value()?;
+    match sample_identity {
+      Some(id) => Some((id, request)),
+      None => {
+        warn!("Discarding request without sample identity.");
+        None
+      }
+    }
+  }
+}
+
+impl<'a, Req, Rep> Evented for Replier<'a, Req, Rep>
+where
+  Req: DeserializeOwned + 'static,
+  Rep: Serialize,
+{
+  fn register(
+    &self,
+    poll: &mio::Poll,
+    token: mio::Token,
+    interest: mio::Ready,
+    opts: mio::PollOpt,
+  ) -> std::io::Result<()> {
+    poll.register(&self.request_reader, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &mio::Poll,
+    token: mio::Token,
+    interest: mio::Ready,
+    opts: mio::PollOpt,
+  ) -> std::io::Result<()> {
+    poll.reregister(&self.request_reader, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &mio::Poll) -> std::io::Result<()> {
+    poll.deregister(&self.request_reader)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{thread, time::Duration as StdDuration};
+
+  use serde::Deserialize;
+
+  use super::*;
+  use crate::dds::{
+    qos::{
+      policy::{History, Reliability},
+      QosPolicyBuilder,
+    },
+    traits::TopicDescription,
+  };
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct AddRequest {
+    a: i64,
+    b: i64,
+  }
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct AddReply {
+    sum: i64,
+    replier: i32,
+  }
+
+  #[test]
+  fn rpc_first_reply_wins() {
+    // Keep all requests and replies, as they are sent before they are taken.
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_millis(100),
+      })
+      .history(History::KeepAll)
+      .build();
+    let dp_client = DomainParticipant::new(41).unwrap();
+    let dp_server = DomainParticipant::new(41).unwrap();
+    let client_topics =
+      RpcTopics::new(&dp_client, "Adder", "AddRequest", "AddReply", &qos).unwrap();
+    let server_topics =
+      RpcTopics::new(&dp_server, "Adder", "AddRequest", "AddReply", &qos).unwrap();
+    assert_eq!(
+      TopicDescription::get_name(client_topics.request_topic()),
+      "Adder_Request"
+    );
+    assert_eq!(
+      TopicDescription::get_name(client_topics.reply_topic()),
+      "Adder_Reply"
+    );
+
+    let client_publisher = dp_client.create_publisher(&qos).unwrap();
+    let client_subscriber = dp_client.create_subscriber(&qos).unwrap();
+    let server_publisher = dp_server.create_publisher(&qos).unwrap();
+    let server_subscriber = dp_server.create_subscriber(&qos).unwrap();
+
+    let mut requester = Requester::<AddRequest, AddReply>::new(
+      &client_publisher,
+      &client_subscriber,
+      &client_topics,
+      None,
+    )
+    .unwrap();
+    let mut repliers: Vec<Replier<AddRequest, AddReply>> = (0..2)
+      .map(|_| Replier::new(&server_publisher, &server_subscriber, &server_topics, None).unwrap())
+      .collect();
+
+    // both Repliers and the Requester must have been discovered, because the
+    // topics are volatile
+    let matched = |requester: &Requester<AddRequest, AddReply>,
+                   repliers: &[Replier<AddRequest, AddReply>]| {
+      let readers = requester.request_writer.get_matched_subscriptions();
+      let writers = requester.reply_reader.get_matched_publications();
+      repliers.iter().all(|r| {
+        readers.contains(&r.request_reader_guid()) && writers.contains(&r.reply_writer_guid())
+      })
+    };
+    for _ in 0..100 {
+      if matched(&requester, &repliers) {
+        break;
+      }
+      thread::sleep(StdDuration::from_millis(100));
+    }
+    assert!(matched(&requester, &repliers));
+
+    let ids: Vec<SampleIdentity> = (0..3)
+      .map(|i| requester.send_request(AddRequest { a: i, b: 10 }).unwrap())
+      .collect();
+    assert_eq!(requester.pending_requests(), 3);
+    assert!(ids
+      .iter()
+      .all(|id| id.writer_guid == requester.request_writer_guid()));
+
+    // every Replier answers every request it gets
+    let mut answered = 0;
+    for _ in 0..100 {
+      for (n, replier) in repliers.iter_mut().enumerate() {
+        while let Some((id, request)) = replier.take_request().unwrap() {
+          assert!(ids.contains(&id));
+          replier
+            .send_reply(
+              id,
+              AddReply {
+                sum: request.a + request.b,
+                replier: n as i32,
+              },
+            )
+            .unwrap();
+          answered += 1;
+        }
+      }
+      if answered == 6 {
+        break;
+      }
+      thread::sleep(StdDuration::from_millis(50));
+    }
+    assert_eq!(answered, 6);
+
+    // one reply per request, the last request waited for by id
+    let last = requester
+      .receive_reply_for(ids[2], Duration::from_secs(5))
+      .unwrap()
+      .unwrap();
+    assert_eq!(last.sum, 12);
+    let mut replies = BTreeMap::new();
+    while let Some((id, reply)) = requester.receive_reply(Duration::from_secs(5)).unwrap() {
+      assert!(replies.insert(id, reply.sum).is_none());
+      if replies.len() == 2 {
+        break;
+      }
+    }
+    assert_eq!(replies.get(&ids[0]), Some(&10));
+    assert_eq!(replies.get(&ids[1]), Some(&11));
+    assert_eq!(requester.pending_requests(), 0);
+
+    // the replies of the slower Replier are discarded
+    assert!(requester
+      .receive_reply(Duration::from_millis(500))
+      .unwrap()
+      .is_none());
+    assert!(matches!(
+      requester.receive_reply_for(ids[0], Duration::from_millis(10)),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+  }
+}
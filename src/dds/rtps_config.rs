@@ -0,0 +1,132 @@
+use std::{net::IpAddr, time::Duration};
+
+use crate::dds::values::result::{Error, Result};
+
+/// Shortest accepted heartbeat period. Shorter periods would keep the event loop busy
+/// resending unacknowledged changes.
+pub const MIN_HEARTBEAT_PERIOD: Duration = Duration::from_millis(10);
+
+/// RTPS protocol tuning of a reliable
+/// [DataWriter](struct.WithKeyDataWriter.html). See RTPS spec 8.4.7.1.
+///
+/// Set with `DataWriter::set_rtps_config`. Best effort writers ignore these.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::RtpsWriterConfig;
+/// # use std::time::Duration;
+/// let config = RtpsWriterConfig {
+///   heartbeat_period: Duration::from_millis(100),
+///   ..RtpsWriterConfig::default()
+/// };
+/// assert!(config.validate().is_ok());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtpsWriterConfig {
+  /// How often the writer announces its changes with a HEARTBEAT, and resends the
+  /// changes not yet acknowledged. This bounds the time to recover from a lost sample
+  /// when no AckNack gets through either. With Liveliness `ManualByTopic` the writer
+  /// also heartbeats at least three times per lease duration. At least
+  /// [`MIN_HEARTBEAT_PERIOD`](constant.MIN_HEARTBEAT_PERIOD.html).
+  pub heartbeat_period: Duration,
+  /// How long the writer waits before resending changes requested by an AckNack. This
+  /// collects the requests of several readers to fewer repairs. Zero repairs
+  /// immediately.
+  pub nack_response_delay: Duration,
+  /// Requests for changes written less than this ago are ignored, as the change is
+  /// probably still on its way.
+  pub nack_suppression_duration: Duration,
+}
+
+impl Default for RtpsWriterConfig {
+  fn default() -> RtpsWriterConfig {
+    RtpsWriterConfig {
+      heartbeat_period: Duration::from_secs(1),
+      nack_response_delay: Duration::from_millis(200),
+      nack_suppression_duration: Duration::from_millis(0),
+    }
+  }
+}
+
+impl RtpsWriterConfig {
+  /// Checks that the heartbeat period is at least
+  /// [`MIN_HEARTBEAT_PERIOD`](constant.MIN_HEARTBEAT_PERIOD.html).
+  pub fn validate(&self) -> Result<()> {
+    if self.heartbeat_period < MIN_HEARTBEAT_PERIOD {
+      return Err(Error::bad_parameter(format!(
+        "heartbeat period {:?} is shorter than {:?}",
+        self.heartbeat_period, MIN_HEARTBEAT_PERIOD
+      )));
+    }
+    Ok(())
+  }
+}
+
+/// RTPS protocol tuning of a reliable
+/// [DataReader](struct.WithKeyDataReader.html). See RTPS spec 8.4.10.1.
+///
+/// Set with `DataReader::set_rtps_config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RtpsReaderConfig {
+  /// How long the reader waits before answering a HEARTBEAT with an AckNack. Zero
+  /// answers immediately, which gets lost samples repaired fastest.
+  pub heartbeat_response_delay: Duration,
+  /// HEARTBEATs of a writer arriving less than this after the previous answered one
+  /// are not answered.
+  pub heartbeat_suppression_duration: Duration,
+}
+
+/// Multicast group a [DataReader](struct.WithKeyDataReader.html) receives its
+/// samples on. A writer matched with several readers in the same group sends each
+/// sample once to the group, instead of once to each reader. Repairs of lost samples
+/// still go to each reader by unicast.
+///
+/// Set with `DataReader::set_multicast`. Unicast only by default.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::ReaderMulticast;
+/// let explicit = ReaderMulticast::Group("239.255.10.1".parse().unwrap());
+/// assert_ne!(explicit, ReaderMulticast::Auto);
+/// assert_eq!(ReaderMulticast::default(), ReaderMulticast::Off);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReaderMulticast {
+  /// Samples come by unicast only.
+  Off,
+  /// A group derived from the domain and topic name, so that all readers of the topic
+  /// choosing `Auto` share it.
+  Auto,
+  /// The given multicast address. It has to be of an IP version the participant uses.
+  Group(IpAddr),
+}
+
+impl Default for ReaderMulticast {
+  fn default() -> ReaderMulticast {
+    ReaderMulticast::Off
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rtps_config_validation() {
+    assert!(RtpsWriterConfig::default().validate().is_ok());
+    let too_fast = RtpsWriterConfig {
+      heartbeat_period: Duration::from_millis(1),
+      ..RtpsWriterConfig::default()
+    };
+    assert!(matches!(
+      too_fast.validate(),
+      Err(Error::BadParameter { .. })
+    ));
+    let minimum = RtpsWriterConfig {
+      heartbeat_period: MIN_HEARTBEAT_PERIOD,
+      nack_response_delay: Duration::from_millis(0),
+      ..RtpsWriterConfig::default()
+    };
+    assert!(minimum.validate().is_ok());
+  }
+}
@@ -3,8 +3,7 @@ use log::{debug, warn};
 use crate::{
   network::constant::get_user_traffic_multicast_port,
   network::constant::get_user_traffic_unicast_port,
-  network::util::get_local_multicast_locators,
-  network::util::get_local_unicast_socket_address,
+  network::local_network::LocalNetwork,
   structure::{
     entity::Entity,
     guid::{EntityId, GUID},
@@ -21,7 +20,7 @@ use std::{
   net::{SocketAddr, Ipv4Addr},
 };
 
-use super::reader::Reader;
+use super::{qos::policy::Reliability, reader::Reader};
 
 #[derive(Debug, PartialEq, Clone)]
 ///ReaderProxy class represents the information an RTPS StatefulWriter maintains on each matched RTPS Reader
@@ -41,6 +40,8 @@ pub(crate) struct RtpsReaderProxy {
   pub expects_in_line_qos: bool,
   /// Specifies whether the remote Reader is responsive to the Writer
   pub is_active: bool,
+  /// Reliable Readers acknowledge the changes they receive, best effort ones do not.
+  pub is_reliable: bool,
 
   // keeps list of changes where response has been received
   acked_changes: HashSet<SequenceNumber>,
@@ -51,7 +52,13 @@ pub(crate) struct RtpsReaderProxy {
   // this keeps sequence number of reader recieved (acknack recieved) messages
   largest_acked_change: Option<SequenceNumber>,
 
+  // reader has acknowledged all changes before this one (largest AckNack base so far)
+  all_acked_before: SequenceNumber,
+
   unsent_changes: HashSet<SequenceNumber>,
+
+  // changes the DataWriter reader filter kept from this reader, which it gets GAPs for
+  irrelevant_changes: HashSet<SequenceNumber>,
 }
 
 impl RtpsReaderProxy {
@@ -64,19 +71,40 @@ impl RtpsReaderProxy {
       //changes_for_reader : writer.history_cache.clone(),
       expects_in_line_qos: false,
       is_active: true,
+      is_reliable: true,
       acked_changes: HashSet::new(),
       requested_changes: HashSet::new(),
       unsent_changes: HashSet::new(),
+      irrelevant_changes: HashSet::new(),
       largest_acked_change: None,
+      all_acked_before: SequenceNumber::default(),
     }
   }
 
-  pub fn from_reader(reader: &Reader, domain_id: u16, participant_id: u16) -> RtpsReaderProxy {
-    let unicast_locator_list =
-      get_local_unicast_socket_address(get_user_traffic_unicast_port(domain_id, participant_id));
-
-    let multicast_locator_list =
-      get_local_multicast_locators(get_user_traffic_multicast_port(domain_id));
+  pub fn from_reader(
+    reader: &Reader,
+    domain_id: u16,
+    participant_id: u16,
+    network: &LocalNetwork,
+  ) -> RtpsReaderProxy {
+    // Writers on this host use the shared memory locator, if we have one.
+    let mut unicast_locator_list: LocatorList =
+      network.shm_locator(reader.get_guid()).into_iter().collect();
+    unicast_locator_list
+      .extend(network.unicast_locators(get_user_traffic_unicast_port(domain_id, participant_id)));
+    unicast_locator_list.extend(network.tcp_locators(reader.get_guid().guidPrefix));
+
+    // User data comes by unicast, unless the reader has a multicast group.
+    let multicast_locator_list = reader
+      .multicast_group()
+      .iter()
+      .map(|group| {
+        Locator::from(SocketAddr::new(
+          *group,
+          get_user_traffic_multicast_port(domain_id),
+        ))
+      })
+      .collect();
 
     RtpsReaderProxy {
       remote_reader_guid: reader.get_guid(),
@@ -85,10 +113,13 @@ impl RtpsReaderProxy {
       multicast_locator_list,
       expects_in_line_qos: false,
       is_active: true,
+      is_reliable: true,
       acked_changes: HashSet::new(),
       requested_changes: HashSet::new(),
       unsent_changes: HashSet::new(),
+      irrelevant_changes: HashSet::new(),
       largest_acked_change: None,
+      all_acked_before: SequenceNumber::default(),
     }
   }
 
@@ -107,6 +138,11 @@ impl RtpsReaderProxy {
       Some(v) => v.clone(),
       None => false,
     };
+    // Readers are best effort by default.
+    let is_reliable = matches!(
+      discovered_reader_data.subscription_topic_data.reliability(),
+      Some(Reliability::Reliable { .. })
+    );
 
     Some(RtpsReaderProxy {
       remote_reader_guid: remote_reader_guid.clone(),
@@ -121,10 +157,13 @@ impl RtpsReaderProxy {
         .clone(),
       expects_in_line_qos: expects_inline_qos,
       is_active: true,
+      is_reliable,
       acked_changes: HashSet::new(),
       requested_changes: HashSet::new(),
       unsent_changes: HashSet::new(),
+      irrelevant_changes: HashSet::new(),
       largest_acked_change: None,
+      all_acked_before: SequenceNumber::default(),
     })
   }
 
@@ -133,6 +172,7 @@ impl RtpsReaderProxy {
       self.unicast_locator_list = updated.unicast_locator_list.clone();
       self.multicast_locator_list = updated.multicast_locator_list.clone();
       self.expects_in_line_qos = updated.expects_in_line_qos.clone();
+      self.is_reliable = updated.is_reliable;
     }
   }
 
@@ -152,10 +192,13 @@ impl RtpsReaderProxy {
       expects_in_line_qos: false,
 
       is_active: true,
+      is_reliable: true,
       acked_changes: HashSet::new(),
       requested_changes: HashSet::new(),
       unsent_changes: HashSet::new(),
+      irrelevant_changes: HashSet::new(),
       largest_acked_change: None,
+      all_acked_before: SequenceNumber::default(),
     }
   }
 
@@ -167,7 +210,7 @@ impl RtpsReaderProxy {
     }
   }
 
-  fn can_send_unsend(&self) -> bool {
+  pub fn can_send_unsend(&self) -> bool {
     if self.unsent_changes().len() > 0 {
       return true;
     }
@@ -276,6 +319,42 @@ impl RtpsReaderProxy {
     self.largest_acked_change = Some(sequence_number);
   }
 
+  /// AckNack base tells that the reader has received all changes before it.
+  pub fn acked_before_set(&mut self, base: SequenceNumber) {
+    if base > self.all_acked_before {
+      self.all_acked_before = base;
+      if !self.irrelevant_changes.is_empty() {
+        self.irrelevant_changes.retain(|&sn| sn >= base);
+      }
+    }
+  }
+
+  /// Largest sequence number such that it and all changes before it are acknowledged.
+  pub fn all_acked_up_to(&self) -> SequenceNumber {
+    self.all_acked_before.sub(SequenceNumber::from(1))
+  }
+
+  /// Keeps acknowledgement progress when the proxy is replaced with an updated one.
+  pub fn copy_all_acked_before(&mut self, other: &RtpsReaderProxy) {
+    self.acked_before_set(other.all_acked_before);
+  }
+
+  /// The change is not for this reader. It gets a GAP instead of the DATA.
+  pub fn set_irrelevant_change(&mut self, sequence_number: SequenceNumber) {
+    self.irrelevant_changes.insert(sequence_number);
+  }
+
+  pub fn change_is_relevant(&self, sequence_number: SequenceNumber) -> bool {
+    !self.irrelevant_changes.contains(&sequence_number)
+  }
+
+  /// Keeps the irrelevant changes when the proxy is replaced with an updated one.
+  pub fn copy_irrelevant_changes(&mut self, other: &RtpsReaderProxy) {
+    self
+      .irrelevant_changes
+      .extend(other.irrelevant_changes.iter().copied());
+  }
+
   pub fn sequence_is_acked(&self, sequence_number: &SequenceNumber) -> bool {
     if self.largest_acked_change.is_none() {
       return false;
@@ -5,11 +5,17 @@ use crate::structure::guid::{EntityId, GUID};
 use crate::{
   discovery::data_types::topic_data::DiscoveredWriterData,
   structure::sequence_number::{SequenceNumber},
-  structure::time::Timestamp,
+  structure::dds_cache::CacheInstant,
 };
-use std::collections::HashMap;
+use crate::dds::qos::policy::Liveliness;
+use crate::structure::{cache_change::ChangeKind, duration::Duration, time::Timestamp};
+use std::collections::{BTreeSet, HashMap};
 //use std::time::Instant;
 
+/// How far ahead of the first not received change we keep track of received
+/// changes. As many as an AckNack can ask for.
+const OUT_OF_ORDER_WINDOW: i64 = 256;
+
 #[derive(Debug)]
 pub struct RtpsWriterProxy {
   /// Identifies the remote matched Writer
@@ -28,11 +34,40 @@ pub struct RtpsWriterProxy {
 
   /// List of sequence_numbers received from the matched RTPS Writer
   // TODO: When should they be removed from here?
-  pub changes: HashMap<SequenceNumber, Timestamp>,
+  pub changes: HashMap<SequenceNumber, CacheInstant>,
 
   pub received_heartbeat_count: i32,
 
   pub sent_ack_nack_count: i32,
+
+  /// Sequence numbers announced irrelevant by GAP, but not yet accounted for
+  /// in sample lost detection.
+  gapped_changes: BTreeSet<SequenceNumber>,
+
+  /// All sequence numbers below this have been accounted for as either
+  /// received, irrelevant or lost. None until we first hear from the writer.
+  lost_accounted_up_to: Option<SequenceNumber>,
+
+  /// All sequence numbers below this have been received, or will never be.
+  received_before: SequenceNumber,
+
+  /// Sequence numbers above `received_before` that have been received, or will
+  /// never be.
+  received_out_of_order: BTreeSet<SequenceNumber>,
+
+  /// Liveliness offered by the Writer, if known from Discovery
+  pub liveliness: Option<Liveliness>,
+
+  /// When the Writer last showed it is alive, by data, a liveliness heartbeat or
+  /// a participant message, as its liveliness kind allows.
+  pub last_life_sign: Timestamp,
+
+  /// False once the lease of the Writer has expired without a life sign
+  pub alive: bool,
+
+  /// Key hashes of the instances received from the Writer, and not disposed by it
+  /// since. They lose this writer when it loses its liveliness.
+  pub instance_keys: BTreeSet<u128>,
 }
 
 impl RtpsWriterProxy {
@@ -50,6 +85,14 @@ impl RtpsWriterProxy {
       changes: HashMap::new(),
       received_heartbeat_count: 0,
       sent_ack_nack_count: 0,
+      gapped_changes: BTreeSet::new(),
+      lost_accounted_up_to: None,
+      received_before: SequenceNumber::from(1),
+      received_out_of_order: BTreeSet::new(),
+      liveliness: None,
+      last_life_sign: Timestamp::TIME_INVALID,
+      alive: true,
+      instance_keys: BTreeSet::new(),
     }
   }
 
@@ -57,6 +100,31 @@ impl RtpsWriterProxy {
     self.unicast_locator_list = other.unicast_locator_list;
     self.multicast_locator_list = other.multicast_locator_list;
     self.remote_group_entity_id = other.remote_group_entity_id;
+    self.liveliness = other.liveliness;
+  }
+
+  // A disposed instance stays disposed when the Writer is lost, so the Writer is not
+  // taken out of it.
+  pub fn instance_changed(&mut self, key: u128, kind: ChangeKind) {
+    if kind == ChangeKind::NOT_ALIVE_DISPOSED {
+      self.instance_keys.remove(&key);
+    } else {
+      self.instance_keys.insert(key);
+    }
+  }
+
+  /// The lease of the Writer liveliness, if it is finite.
+  pub fn liveliness_lease(&self) -> Option<Duration> {
+    let lease_duration = match self.liveliness? {
+      Liveliness::Automatic { lease_duration }
+      | Liveliness::ManualByParticipant { lease_duration }
+      | Liveliness::ManualByTopic { lease_duration } => lease_duration,
+    };
+    if lease_duration < Duration::DURATION_INFINITE {
+      Some(lease_duration)
+    } else {
+      None
+    }
   }
 
   pub fn get_missing_sequence_numbers(
@@ -64,20 +132,26 @@ impl RtpsWriterProxy {
     hb_first_sn: SequenceNumber,
     hb_last_sn: SequenceNumber,
   ) -> Vec<SequenceNumber> {
-    // taking all available seqnums in range
-    let seqnums: Vec<SequenceNumber> = self
-      .changes
-      .iter()
-      .map(|(&sq, _)| sq)
-      .filter(|&sq| sq >= hb_first_sn && sq <= hb_last_sn)
-      .collect();
-
-    let mut missing_seqnums = Vec::new();
-    for sq in i64::from(hb_first_sn)..(i64::from(hb_last_sn) + 1) {
+    // Gapped changes will never come, so they are not missing either.
+    let first = std::cmp::max(hb_first_sn, self.received_before);
+    let mut missing_seqnums: Vec<SequenceNumber> = Vec::new();
+    // An AckNack can ask for the 256 sequence numbers from the first missing one, so
+    // the rest of the range of the Heartbeat, which may be huge, is not looked at.
+    let mut sq = i64::from(first);
+    while sq <= i64::from(hb_last_sn) {
       let msq = SequenceNumber::from(sq);
-      if !seqnums.contains(&msq) {
-        missing_seqnums.push(msq)
+      if !self.already_received(msq) {
+        match missing_seqnums.first() {
+          Some(&base) if msq >= base.saturating_add(SequenceNumber::from(OUT_OF_ORDER_WINDOW)) => {
+            break
+          }
+          _ => missing_seqnums.push(msq),
+        }
       }
+      sq = match sq.checked_add(1) {
+        Some(next) => next,
+        None => break,
+      };
     }
 
     missing_seqnums
@@ -91,21 +165,76 @@ impl RtpsWriterProxy {
     if hb_last_sn == SequenceNumber::from(0) {
       return false;
     }
-    let range_length = i64::from(hb_last_sn.sub(hb_first_sn)) as usize + 1;
-    let seq_count = self
-      .changes
-      .iter()
-      .filter(|(&sq, _)| sq >= hb_first_sn && sq <= hb_last_sn)
-      .count();
-
-    seq_count < range_length
+    !self
+      .get_missing_sequence_numbers(hb_first_sn, hb_last_sn)
+      .is_empty()
   }
 
   pub fn contains_change(&self, seqnum: SequenceNumber) -> bool {
     self.changes.contains_key(&seqnum)
   }
 
-  pub fn received_changes_add(&mut self, seq_num: SequenceNumber, instant: Timestamp) {
+  /// Whether the change has been received already, or will never be.
+  pub fn already_received(&self, seq_num: SequenceNumber) -> bool {
+    seq_num < self.received_before || self.received_out_of_order.contains(&seq_num)
+  }
+
+  /// All changes before this have been received, or will never be.
+  pub fn received_before(&self) -> SequenceNumber {
+    self.received_before
+  }
+
+  /// Records the arrival of a change. Returns false if it should be dropped,
+  /// because it is a duplicate, or too far ahead of the changes still missing to
+  /// keep track of. Readers that do not wait for the missing changes move the
+  /// window forward instead.
+  pub fn accept_change(&mut self, seq_num: SequenceNumber, wait_for_missing: bool) -> bool {
+    if self.already_received(seq_num) {
+      return false;
+    }
+    if seq_num
+      >= self
+        .received_before
+        .saturating_add(SequenceNumber::from(OUT_OF_ORDER_WINDOW))
+    {
+      if wait_for_missing {
+        // it will be sent again once the missing changes are repaired
+        return false;
+      }
+      self.skip_to(seq_num - SequenceNumber::from(OUT_OF_ORDER_WINDOW - 1));
+    }
+    self.mark_received(seq_num);
+    true
+  }
+
+  fn mark_received(&mut self, seq_num: SequenceNumber) {
+    if self.already_received(seq_num) {
+      return;
+    }
+    self.received_out_of_order.insert(seq_num);
+    self.advance_received_before();
+  }
+
+  // Gives up on the changes before seq_num.
+  fn skip_to(&mut self, seq_num: SequenceNumber) {
+    if seq_num <= self.received_before {
+      return;
+    }
+    self.received_before = seq_num;
+    self.received_out_of_order = self.received_out_of_order.split_off(&seq_num);
+    self.advance_received_before();
+  }
+
+  fn advance_received_before(&mut self) {
+    // The largest sequence number stays out of order, as there is none after it.
+    while self.received_before < SequenceNumber::from(std::i64::MAX)
+      && self.received_out_of_order.remove(&self.received_before)
+    {
+      self.received_before = self.received_before + SequenceNumber::from(1);
+    }
+  }
+
+  pub fn received_changes_add(&mut self, seq_num: SequenceNumber, instant: CacheInstant) {
     self.changes.insert(seq_num, instant);
   }
 
@@ -123,11 +252,112 @@ impl RtpsWriterProxy {
     None
   }
 
-  pub fn set_irrelevant_change(&mut self, seq_num: SequenceNumber) -> Option<Timestamp> {
+  pub fn set_irrelevant_change(&mut self, seq_num: SequenceNumber) -> Option<CacheInstant> {
+    match self.lost_accounted_up_to {
+      Some(accounted) if seq_num < accounted => (),
+      _ => {
+        self.gapped_changes.insert(seq_num);
+      }
+    }
+    self.mark_received(seq_num);
     self.changes.remove(&seq_num)
   }
 
-  pub fn irrelevant_changes_up_to(&mut self, smallest_seqnum: SequenceNumber) -> Vec<Timestamp> {
+  /// Marks the sequence numbers from `start` to before `end` irrelevant, as the range
+  /// of a GAP does, and returns the instants of the changes removed by that. The range
+  /// may be huge, so sequence numbers are stored one by one only as far as received
+  /// changes are tracked.
+  pub fn set_irrelevant_range(
+    &mut self,
+    start: SequenceNumber,
+    end: SequenceNumber,
+  ) -> Vec<CacheInstant> {
+    if start >= end {
+      return Vec::new();
+    }
+    let window = SequenceNumber::from(OUT_OF_ORDER_WINDOW);
+
+    match self.lost_accounted_up_to {
+      // The whole range after what is accounted for is irrelevant, not lost.
+      Some(accounted) if start <= accounted => {
+        if end > accounted {
+          self.lost_accounted_up_to = Some(end);
+          self.gapped_changes = self.gapped_changes.split_off(&end);
+        }
+      }
+      _ => {
+        let window_end = std::cmp::min(end, start.saturating_add(window));
+        for sq in i64::from(start)..i64::from(window_end) {
+          self.gapped_changes.insert(SequenceNumber::from(sq));
+        }
+      }
+    }
+
+    if start <= self.received_before {
+      self.skip_to(end);
+    } else {
+      let window_end = std::cmp::min(end, self.received_before.saturating_add(window));
+      for sq in i64::from(start)..i64::from(window_end) {
+        self.received_out_of_order.insert(SequenceNumber::from(sq));
+      }
+    }
+
+    let removed: Vec<SequenceNumber> = self
+      .changes
+      .keys()
+      .filter(|&&sq| start <= sq && sq < end)
+      .cloned()
+      .collect();
+    removed
+      .iter()
+      .filter_map(|sq| self.changes.remove(sq))
+      .collect()
+  }
+
+  /// Marks all sequence numbers below `up_to` as accounted for, and returns
+  /// the (inclusive) ranges of those that were neither received nor announced
+  /// irrelevant by a GAP, i.e. lost.
+  pub fn lost_changes_up_to(
+    &mut self,
+    up_to: SequenceNumber,
+  ) -> Vec<(SequenceNumber, SequenceNumber)> {
+    let mut lost = Vec::new();
+    match self.lost_accounted_up_to {
+      // Nothing can be known to be lost before the first change we hear of.
+      None => (),
+      Some(start) if up_to <= start => return lost,
+      Some(start) => {
+        let mut known: Vec<SequenceNumber> = self
+          .changes
+          .keys()
+          .filter(|&&sq| sq >= start && sq < up_to)
+          .cloned()
+          .collect();
+        known.extend(self.gapped_changes.range(start..up_to).cloned());
+        // received, but still waiting for the changes before them
+        known.extend(self.received_out_of_order.range(start..up_to).cloned());
+        known.sort();
+        known.dedup();
+
+        let mut next = start;
+        for sq in known {
+          if sq > next {
+            lost.push((next, sq - SequenceNumber::from(1)));
+          }
+          next = sq.saturating_add(SequenceNumber::from(1));
+        }
+        if up_to > next {
+          lost.push((next, up_to - SequenceNumber::from(1)));
+        }
+      }
+    }
+    self.lost_accounted_up_to = Some(up_to);
+    self.gapped_changes = self.gapped_changes.split_off(&up_to);
+    lost
+  }
+
+  pub fn irrelevant_changes_up_to(&mut self, smallest_seqnum: SequenceNumber) -> Vec<CacheInstant> {
+    self.skip_to(smallest_seqnum);
     let mut remove = Vec::new();
     for (&seqnum, _) in self.changes.iter() {
       if seqnum < smallest_seqnum {
@@ -171,6 +401,14 @@ impl RtpsWriterProxy {
       changes: HashMap::new(),
       received_heartbeat_count: 0,
       sent_ack_nack_count: 0,
+      gapped_changes: BTreeSet::new(),
+      lost_accounted_up_to: None,
+      received_before: SequenceNumber::from(1),
+      received_out_of_order: BTreeSet::new(),
+      liveliness: discovered_writer_data.publication_topic_data.liveliness,
+      last_life_sign: Timestamp::TIME_INVALID,
+      alive: true,
+      instance_keys: BTreeSet::new(),
     })
   }
 }
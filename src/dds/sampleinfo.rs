@@ -1,7 +1,7 @@
 use enumflags2::BitFlags;
 
 use crate::{structure::guid::GUID};
-use crate::structure::time::Timestamp;
+use crate::structure::{inline_qos::SampleIdentity, time::Timestamp};
 
 //use std::num::Zero; // unstable
 
@@ -123,11 +123,22 @@ pub struct SampleInfo {
   //(MRS.disposed_generation_count + MRS.no_writers_generation_count)
   //- (S.disposed_generation_count + S.no_writers_generation_count)
   pub absolute_generation_rank: i32,
-  pub source_timestamp: Option<Timestamp>,
+  // the time the writer stamped on the sample with InfoTimestamp, or TIME_INVALID if
+  // it sent none
+  pub source_timestamp: Timestamp,
+  // the time the sample was received and entered the cache
+  pub reception_timestamp: Timestamp,
 
   // the publication_handle that identifies locally the DataWriter that modified
   // the instance (wrote the sample)
   pub publication_handle: GUID,
+
+  // Writer GUID and sequence number of the sample. Used to relate replies to requests.
+  // None for samples that only change the instance state.
+  pub sample_identity: Option<SampleIdentity>,
+  // The sample that the writer said this sample relates to, e.g. the request this
+  // sample is a reply to.
+  pub related_sample_identity: Option<SampleIdentity>,
 }
 
 #[allow(clippy::new_without_default)]
@@ -141,8 +152,11 @@ impl SampleInfo {
       sample_rank: 0,
       generation_rank: 0,
       absolute_generation_rank: 0,
-      source_timestamp: None,
+      source_timestamp: Timestamp::TIME_INVALID,
+      reception_timestamp: Timestamp::TIME_INVALID,
       publication_handle: GUID::GUID_UNKNOWN,
+      sample_identity: None,
+      related_sample_identity: None,
     }
   }
 
@@ -0,0 +1,447 @@
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex, MutexGuard,
+  },
+};
+
+use crate::{
+  messages::submessages::submessage::EntitySubmessage,
+  serialization::{message::Message, submessage::SubmessageBody},
+  structure::guid::GUID,
+};
+
+/// Counts of the RTPS traffic a DataWriter has sent to remote readers. Changes
+/// handed to DataReaders of the same participant in memory are not counted.
+/// The counts only grow. This is a copy, it does not change when the counters
+/// do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WriterStatistics {
+  /// DATA submessages sent for the first time, one for each reader or multicast
+  /// group the change was sent to
+  pub data_sent: u64,
+  /// HEARTBEAT submessages sent
+  pub heartbeats_sent: u64,
+  /// GAP submessages sent
+  pub gaps_sent: u64,
+  /// RTPS messages sent. A message sent to several locators is counted once. With
+  /// batching, one message carries several DATA.
+  pub messages_sent: u64,
+  /// Bytes of the RTPS messages sent. A message sent to several locators is
+  /// counted once.
+  pub bytes_sent: u64,
+  /// DATA submessages sent again, because the reader asked for them or had not
+  /// acknowledged them
+  pub retransmissions: u64,
+}
+
+impl WriterStatistics {
+  fn add(&mut self, other: &WriterStatistics) {
+    self.data_sent += other.data_sent;
+    self.heartbeats_sent += other.heartbeats_sent;
+    self.gaps_sent += other.gaps_sent;
+    self.messages_sent += other.messages_sent;
+    self.bytes_sent += other.bytes_sent;
+    self.retransmissions += other.retransmissions;
+  }
+}
+
+/// Counts of the RTPS traffic a DataReader has received from remote writers.
+/// Changes handed over in memory by DataWriters of the same participant are not
+/// counted. The counts only grow. This is a copy, it does not change when the
+/// counters do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReaderStatistics {
+  /// DATA submessages that brought a change not received before
+  pub data_received: u64,
+  /// DATA submessages that brought a change already received, e.g. a resent one
+  pub duplicates_dropped: u64,
+  /// ACKNACK submessages sent
+  pub acknacks_sent: u64,
+  /// Changes that will never be received, as in
+  /// [SampleLostStatus](error/struct.SampleLostStatus.html)
+  pub samples_lost: u64,
+  /// Received changes that were dropped: expired by their lifespan, without the
+  /// payload they should have, or not taken by the cache
+  pub samples_rejected: u64,
+  /// Samples the DataReader could not deserialize. The last one is told by
+  /// [SampleRejectedStatus](error/struct.SampleRejectedStatus.html). Unlike the
+  /// other counts, these include samples of writers in the same participant.
+  pub deserialization_failures: u64,
+}
+
+impl ReaderStatistics {
+  fn add(&mut self, other: &ReaderStatistics) {
+    self.data_received += other.data_received;
+    self.duplicates_dropped += other.duplicates_dropped;
+    self.acknacks_sent += other.acknacks_sent;
+    self.samples_lost += other.samples_lost;
+    self.samples_rejected += other.samples_rejected;
+    self.deserialization_failures += other.deserialization_failures;
+  }
+}
+
+/// Protocol statistics of the DataWriters and DataReaders of a
+/// [DomainParticipant](struct.DomainParticipant.html), including the ones of
+/// Discovery. The totals include also the counts of the deleted ones, so they
+/// only grow. This is a copy, it does not change when the counters do.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::DomainParticipant;
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// let statistics = domain_participant.get_statistics();
+/// println!(
+///   "{} DATA sent in {} bytes, {} resent",
+///   statistics.writer_total.data_sent,
+///   statistics.writer_total.bytes_sent,
+///   statistics.writer_total.retransmissions
+/// );
+/// for (guid, reader) in statistics.readers.iter() {
+///   println!("{:?}: {} samples lost", guid, reader.samples_lost);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ParticipantStatistics {
+  /// Statistics of each existing DataWriter, by GUID
+  pub writers: HashMap<GUID, WriterStatistics>,
+  /// Statistics of each existing DataReader, by GUID
+  pub readers: HashMap<GUID, ReaderStatistics>,
+  /// Sums of the statistics of all DataWriters ever created
+  pub writer_total: WriterStatistics,
+  /// Sums of the statistics of all DataReaders ever created
+  pub reader_total: ReaderStatistics,
+  /// Times a thread panicked while holding the lock of the discovery database or
+  /// the cache of the participant. The participant continues with what the thread
+  /// left there.
+  pub poisoned_locks_recovered: u64,
+  /// Multicast groups joined again after the operating system had dropped our
+  /// membership, e.g. when a network interface went down and up
+  pub multicast_rejoins: u64,
+  /// Sockets created again: multicast listening sockets that could not be bound
+  /// before, and sending sockets of DataWriters replaced after the network was
+  /// unreachable
+  pub sockets_recreated: u64,
+}
+
+// Counters of a Writer, updated on the event loop thread and read by its DataWriter
+// and the participant. Relaxed ordering is enough, as each counter is independent.
+#[derive(Default)]
+pub(crate) struct WriterCounters {
+  data_sent: AtomicU64,
+  heartbeats_sent: AtomicU64,
+  gaps_sent: AtomicU64,
+  messages_sent: AtomicU64,
+  bytes_sent: AtomicU64,
+  retransmissions: AtomicU64,
+}
+
+impl WriterCounters {
+  // Counts a message serialized to bytes octets. Its DATA are retransmissions if it is
+  // resent.
+  pub fn message_sent(&self, message: &Message, bytes: usize, resent: bool) {
+    let (mut data, mut heartbeats, mut gaps) = (0, 0, 0);
+    for submessage in message.submessages.iter() {
+      match &submessage.body {
+        SubmessageBody::Entity(EntitySubmessage::Data(..)) => data += 1,
+        SubmessageBody::Entity(EntitySubmessage::Heartbeat(..)) => heartbeats += 1,
+        SubmessageBody::Entity(EntitySubmessage::Gap(..)) => gaps += 1,
+        _ => (),
+      }
+    }
+    if resent {
+      self.retransmissions.fetch_add(data, Ordering::Relaxed);
+    } else {
+      self.data_sent.fetch_add(data, Ordering::Relaxed);
+    }
+    self
+      .heartbeats_sent
+      .fetch_add(heartbeats, Ordering::Relaxed);
+    self.gaps_sent.fetch_add(gaps, Ordering::Relaxed);
+    self.messages_sent.fetch_add(1, Ordering::Relaxed);
+    self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+  }
+
+  pub fn get(&self) -> WriterStatistics {
+    WriterStatistics {
+      data_sent: self.data_sent.load(Ordering::Relaxed),
+      heartbeats_sent: self.heartbeats_sent.load(Ordering::Relaxed),
+      gaps_sent: self.gaps_sent.load(Ordering::Relaxed),
+      messages_sent: self.messages_sent.load(Ordering::Relaxed),
+      bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+      retransmissions: self.retransmissions.load(Ordering::Relaxed),
+    }
+  }
+}
+
+// Counters of a Reader, like WriterCounters
+#[derive(Default)]
+pub(crate) struct ReaderCounters {
+  data_received: AtomicU64,
+  duplicates_dropped: AtomicU64,
+  acknacks_sent: AtomicU64,
+  samples_lost: AtomicU64,
+  samples_rejected: AtomicU64,
+  deserialization_failures: AtomicU64,
+}
+
+impl ReaderCounters {
+  pub fn data_received(&self) {
+    self.data_received.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn duplicate_dropped(&self) {
+    self.duplicates_dropped.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn acknack_sent(&self) {
+    self.acknacks_sent.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn samples_lost(&self, count: u64) {
+    self.samples_lost.fetch_add(count, Ordering::Relaxed);
+  }
+
+  pub fn sample_rejected(&self) {
+    self.samples_rejected(1);
+  }
+
+  pub fn samples_rejected(&self, count: u64) {
+    self.samples_rejected.fetch_add(count, Ordering::Relaxed);
+  }
+
+  pub fn deserialization_failed(&self) {
+    self
+      .deserialization_failures
+      .fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn get(&self) -> ReaderStatistics {
+    ReaderStatistics {
+      data_received: self.data_received.load(Ordering::Relaxed),
+      duplicates_dropped: self.duplicates_dropped.load(Ordering::Relaxed),
+      acknacks_sent: self.acknacks_sent.load(Ordering::Relaxed),
+      samples_lost: self.samples_lost.load(Ordering::Relaxed),
+      samples_rejected: self.samples_rejected.load(Ordering::Relaxed),
+      deserialization_failures: self.deserialization_failures.load(Ordering::Relaxed),
+    }
+  }
+}
+
+// Counters of the network of a participant, updated on the event loop thread
+#[derive(Default)]
+pub(crate) struct NetworkCounters {
+  multicast_rejoins: AtomicU64,
+  sockets_recreated: AtomicU64,
+}
+
+impl NetworkCounters {
+  pub fn multicast_rejoined(&self, groups: u64) {
+    self.multicast_rejoins.fetch_add(groups, Ordering::Relaxed);
+  }
+
+  pub fn socket_recreated(&self) {
+    self.sockets_recreated.fetch_add(1, Ordering::Relaxed);
+  }
+}
+
+// The counters of the Writers and Readers of a participant. The registry holds one
+// reference to each. When it holds the only one, both the RTPS entity and its
+// DDS entity are gone, and the final counts are kept only in the totals.
+#[derive(Default)]
+pub(crate) struct StatisticsRegistry {
+  inner: Mutex<Registry>,
+  network: Arc<NetworkCounters>,
+}
+
+#[derive(Default)]
+struct Registry {
+  writers: HashMap<GUID, Arc<WriterCounters>>,
+  readers: HashMap<GUID, Arc<ReaderCounters>>,
+  deleted_writers: WriterStatistics,
+  deleted_readers: ReaderStatistics,
+}
+
+impl StatisticsRegistry {
+  fn lock(&self) -> MutexGuard<Registry> {
+    match self.inner.lock() {
+      Ok(r) => r,
+      Err(e) => panic!("StatisticsRegistry is poisoned. {:?}", e),
+    }
+  }
+
+  pub fn add_writer(&self, guid: GUID, counters: Arc<WriterCounters>) {
+    self.lock().writers.insert(guid, counters);
+  }
+
+  pub fn add_reader(&self, guid: GUID, counters: Arc<ReaderCounters>) {
+    self.lock().readers.insert(guid, counters);
+  }
+
+  pub fn reader(&self, guid: GUID) -> Option<Arc<ReaderCounters>> {
+    self.lock().readers.get(&guid).cloned()
+  }
+
+  pub fn network(&self) -> Arc<NetworkCounters> {
+    self.network.clone()
+  }
+
+  pub fn get(&self) -> ParticipantStatistics {
+    let mut registry = self.lock();
+    let Registry {
+      writers,
+      readers,
+      deleted_writers,
+      deleted_readers,
+    } = &mut *registry;
+    writers.retain(|_, counters| {
+      let deleted = Arc::strong_count(counters) == 1;
+      if deleted {
+        deleted_writers.add(&counters.get());
+      }
+      !deleted
+    });
+    readers.retain(|_, counters| {
+      let deleted = Arc::strong_count(counters) == 1;
+      if deleted {
+        deleted_readers.add(&counters.get());
+      }
+      !deleted
+    });
+
+    let mut statistics = ParticipantStatistics {
+      writer_total: *deleted_writers,
+      reader_total: *deleted_readers,
+      multicast_rejoins: self.network.multicast_rejoins.load(Ordering::Relaxed),
+      sockets_recreated: self.network.sockets_recreated.load(Ordering::Relaxed),
+      ..ParticipantStatistics::default()
+    };
+    for (guid, counters) in writers.iter() {
+      let writer = counters.get();
+      statistics.writer_total.add(&writer);
+      statistics.writers.insert(*guid, writer);
+    }
+    for (guid, counters) in readers.iter() {
+      let reader = counters.get();
+      statistics.reader_total.add(&reader);
+      statistics.readers.insert(*guid, reader);
+    }
+    statistics
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{thread, time::Duration as StdDuration};
+
+  use byteorder::LittleEndian;
+  use serde::{Deserialize, Serialize};
+
+  use super::*;
+  use crate::{
+    dds::{
+      participant::DomainParticipant,
+      qos::{
+        policy::{History, Reliability},
+        QosPolicyBuilder,
+      },
+      topic::TopicKind,
+    },
+    serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+    structure::{duration::Duration, entity::Entity},
+  };
+
+  #[derive(Serialize, Deserialize)]
+  struct Counted {
+    n: u32,
+  }
+
+  #[test]
+  fn statistics_count_sent_and_received_data() {
+    // All samples are written before they are taken.
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_millis(100),
+      })
+      .history(History::KeepAll)
+      .build();
+    let dp_a = DomainParticipant::new(43).unwrap();
+    let dp_b = DomainParticipant::new(43).unwrap();
+    let topic_a = dp_a
+      .create_topic("StatisticsTest", "Counted", &qos, TopicKind::NoKey)
+      .unwrap();
+    let topic_b = dp_b
+      .create_topic("StatisticsTest", "Counted", &qos, TopicKind::NoKey)
+      .unwrap();
+    let publisher = dp_a.create_publisher(&qos).unwrap();
+    let subscriber = dp_b.create_subscriber(&qos).unwrap();
+    let writer = publisher
+      .create_datawriter_no_key::<Counted, CDRSerializerAdapter<Counted, LittleEndian>>(
+        None, &topic_a, None,
+      )
+      .unwrap();
+    let mut reader = subscriber
+      .create_datareader_no_key::<Counted, CDRDeserializerAdapter<Counted>>(&topic_b, None, None)
+      .unwrap();
+
+    let reader_guid = reader.get_guid();
+    for _ in 0..100 {
+      if writer.get_matched_subscriptions().contains(&reader_guid) {
+        break;
+      }
+      thread::sleep(StdDuration::from_millis(100));
+    }
+    assert!(writer.get_matched_subscriptions().contains(&reader_guid));
+    assert_eq!(writer.get_statistics().data_sent, 0);
+    assert_eq!(reader.get_statistics().data_received, 0);
+
+    const N: u32 = 10;
+    for n in 0..N {
+      writer.write(Counted { n }, None).unwrap();
+    }
+    let mut received = 0;
+    for _ in 0..100 {
+      while reader.take_next_sample().unwrap().is_some() {
+        received += 1;
+      }
+      if received == N {
+        break;
+      }
+      thread::sleep(StdDuration::from_millis(50));
+    }
+    assert_eq!(received, N);
+
+    let written = writer.get_statistics();
+    assert_eq!(written.data_sent, u64::from(N));
+    assert!(written.bytes_sent > 0);
+    assert_eq!(reader.get_statistics().data_received, u64::from(N));
+
+    let sent_by_a = dp_a.get_statistics();
+    assert_eq!(sent_by_a.writers.get(&writer.get_guid()), Some(&written));
+    // Discovery writers count too
+    assert!(sent_by_a.writer_total.data_sent > written.data_sent);
+    let received_by_b = dp_b.get_statistics();
+    assert_eq!(
+      received_by_b
+        .readers
+        .get(&reader_guid)
+        .map(|r| r.data_received),
+      Some(u64::from(N))
+    );
+
+    // the counts of deleted DataWriters stay in the totals
+    let writer_guid = writer.get_guid();
+    let total_before = dp_a.get_statistics().writer_total;
+    publisher.delete_datawriter_no_key(writer).unwrap();
+    let deleted = || !dp_a.get_statistics().writers.contains_key(&writer_guid);
+    for _ in 0..50 {
+      if deleted() {
+        break;
+      }
+      thread::sleep(StdDuration::from_millis(100));
+    }
+    assert!(deleted());
+    assert!(dp_a.get_statistics().writer_total.data_sent >= total_before.data_sent);
+  }
+}
@@ -0,0 +1,69 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::Waker;
+
+// TaskWaker connects an async task waiting on a DataReader or DataWriter to the
+// event loop thread. The task registers its Waker here before checking the
+// corresponding channel, and the event loop wakes it after it has sent a notification
+// (Reader) or consumed commands (Writer). This way async users do not need any
+// other runtime than their own, and the mio event loop stays as it is.
+#[derive(Clone)]
+pub(crate) struct TaskWaker {
+  inner: Arc<Mutex<TaskWakerState>>,
+}
+
+struct TaskWakerState {
+  waker: Option<Waker>,
+  // The event loop side is gone, so waiting is pointless.
+  closed: bool,
+}
+
+impl TaskWaker {
+  pub fn new() -> TaskWaker {
+    TaskWaker {
+      inner: Arc::new(Mutex::new(TaskWakerState {
+        waker: None,
+        closed: false,
+      })),
+    }
+  }
+
+  fn lock(&self) -> MutexGuard<TaskWakerState> {
+    match self.inner.lock() {
+      Ok(s) => s,
+      Err(e) => panic!("TaskWaker is poisoned. {:?}", e),
+    }
+  }
+
+  // Stores the waker of the current task, replacing any previous one.
+  // Returns false if the event loop side is closed, and nobody will wake the task.
+  pub fn register(&self, waker: &Waker) -> bool {
+    let mut state = self.lock();
+    if state.closed {
+      return false;
+    }
+    match &state.waker {
+      Some(w) if w.will_wake(waker) => (),
+      _ => state.waker = Some(waker.clone()),
+    }
+    true
+  }
+
+  pub fn wake(&self) {
+    let waker = self.lock().waker.take();
+    // wake outside of the lock
+    if let Some(w) = waker {
+      w.wake()
+    }
+  }
+
+  pub fn close(&self) {
+    let waker = {
+      let mut state = self.lock();
+      state.closed = true;
+      state.waker.take()
+    };
+    if let Some(w) = waker {
+      w.wake()
+    }
+  }
+}
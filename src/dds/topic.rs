@@ -1,7 +1,8 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
 use crate::{
   dds::{participant::*, typedesc::*, qos::*, values::result::*, traits::dds_entity::DDSEntity},
+  structure::{entity::Entity, guid::GuidPrefix},
 };
 
 pub use crate::structure::topic_kind::TopicKind;
@@ -23,7 +24,7 @@ pub trait TopicDescription {
 /// use rustdds::dds::Topic;
 /// use rustdds::dds::data_types::TopicKind;
 ///
-/// let domain_participant = DomainParticipant::new(0);
+/// let domain_participant = DomainParticipant::new(0).unwrap();
 /// let qos = QosPolicyBuilder::new().build();
 /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey);
 /// ```
@@ -34,27 +35,58 @@ pub struct Topic {
   my_typedesc: TypeDesc,
   my_qos_policies: QosPolicies,
   topic_kind: TopicKind, // WITH_KEY or NO_KEY
+  // The Rust type of topics created with create_topic_for. DataReaders and DataWriters
+  // of other types are refused.
+  data_type: Option<&'static str>,
+  _handle: Arc<TopicHandle>,
+}
+
+// Keeps the topic in its participant, and releases it when the last clone of the Topic
+// is dropped. The participant counts the handles of each topic.
+struct TopicHandle {
+  domain_participant: DomainParticipantWeak,
+  topic_name: String,
+}
+
+impl Drop for TopicHandle {
+  fn drop(&mut self) {
+    if let Some(dp) = self.domain_participant.clone().upgrade() {
+      dp.release_topic_handle(&self.topic_name);
+    }
+  }
 }
 
 impl Topic {
   // visibility pub(crate), because only DomainParticipant should be able to
-  // create new Topic objects from an application point of view.
+  // create new Topic objects from an application point of view. The participant must
+  // have counted the new handle.
   pub(crate) fn new(
     my_domainparticipant: &DomainParticipantWeak,
     my_name: String,
     my_typedesc: TypeDesc,
     my_qos_policies: &QosPolicies,
     topic_kind: TopicKind,
+    data_type: Option<&'static str>,
   ) -> Topic {
+    let handle = TopicHandle {
+      domain_participant: my_domainparticipant.clone(),
+      topic_name: my_name.clone(),
+    };
     Topic {
       my_domainparticipant: my_domainparticipant.clone(),
       my_name,
       my_typedesc,
       my_qos_policies: my_qos_policies.clone(),
       topic_kind,
+      data_type,
+      _handle: Arc::new(handle),
     }
   }
 
+  pub(crate) fn participant_guid_prefix(&self) -> GuidPrefix {
+    self.my_domainparticipant.get_guid_prefix()
+  }
+
   fn get_participant(&self) -> Option<DomainParticipant> {
     self.my_domainparticipant.clone().upgrade()
   }
@@ -77,7 +109,7 @@ impl Topic {
   /// # use rustdds::dds::Topic;
   /// use rustdds::dds::data_types::TopicKind;
   ///
-  /// # let domain_participant = DomainParticipant::new(0);
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
   /// # let qos = QosPolicyBuilder::new().build();
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// assert_eq!(topic.kind(), TopicKind::WithKey);
@@ -86,6 +118,21 @@ impl Topic {
     self.topic_kind
   }
 
+  // Checks that DataReaders and DataWriters of D can be created on this topic
+  pub(crate) fn check_data_type<D>(&self) -> Result<()> {
+    match self.data_type {
+      Some(data_type) if data_type != std::any::type_name::<D>() => {
+        Err(Error::bad_parameter(format!(
+          "topic {} is of type {}, not {}",
+          self.get_name(),
+          data_type,
+          std::any::type_name::<D>()
+        )))
+      }
+      _ => Ok(()),
+    }
+  }
+
   // DDS spec 2.2.2.3.2 Topic Class
   // specifies only method get_inconsistent_topic_status
   // TODO: implement
@@ -0,0 +1,32 @@
+/// A data type that knows the DDS type name it is published under.
+///
+/// Topics created with
+/// [`DomainParticipant::create_topic_for`](../struct.DomainParticipant.html#method.create_topic_for)
+/// take their type name from here, and only accept DataReaders and DataWriters of the
+/// same type.
+///
+/// # Deriving
+/// The derived type name is the name of the type. `#[dds_type(name = "...")]` gives
+/// another one, e.g. to match the type name of an IDL type of another DDS
+/// implementation.
+/// ```
+/// use rustdds::dds::traits::DdsType;
+///
+/// #[derive(DdsType)]
+/// struct Temperature {
+///   celsius: f32,
+/// }
+///
+/// #[derive(DdsType)]
+/// #[dds_type(name = "sensors::msg::Humidity")]
+/// struct Humidity {
+///   percent: f32,
+/// }
+///
+/// assert_eq!(Temperature::type_name(), "Temperature");
+/// assert_eq!(Humidity::type_name(), "sensors::msg::Humidity");
+/// ```
+pub trait DdsType {
+  /// The type name of topics of this type, as advertised in discovery.
+  fn type_name() -> &'static str;
+}
@@ -2,7 +2,7 @@
 // See e.g. Figure 2.3 in "2.2.1.2.2 Overall Conceptual Model"
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
-use byteorder::{LittleEndian};
+use byteorder::{BigEndian};
 use rand::Rng;
 use serde::{Serialize, Deserialize, de::DeserializeOwned};
 
@@ -18,6 +18,49 @@ use crate::serialization::cdr_serializer::to_bytes;
 /// implement [`Key`]. Otherwise, `K` can be chosen to suit the application. It is advisable that `K`
 /// is something that can be cloned with reasonable effort.
 ///
+/// # Deriving
+/// `Keyed` can be derived by marking the key fields with `#[key]`. This generates a key
+/// struct `<Type>Key` containing the key fields in declaration order. Key fields must
+/// implement [`Key`], which nested structs can derive.
+/// ```
+/// use rustdds::dds::traits::{Key, Keyed};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Key)]
+/// pub struct Region {
+///   pub zone: u16,
+///   pub area: String,
+/// }
+///
+/// #[derive(Serialize, Deserialize, Keyed)]
+/// pub struct Sensor {
+///   #[key]
+///   pub region: Region,
+///   #[key]
+///   pub id: u32,
+///   pub reading: f64,
+/// }
+///
+/// let sensor = Sensor {
+///   region: Region { zone: 1, area: String::from("hall") },
+///   id: 7,
+///   reading: 21.5,
+/// };
+/// let key: SensorKey = sensor.get_key();
+/// assert_eq!(key.id, 7);
+/// ```
+/// Types that cannot be keys are rejected:
+/// ```compile_fail
+/// use rustdds::dds::traits::Keyed;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, Keyed)]
+/// pub struct Measurement {
+///   #[key]
+///   pub value: f64,
+/// }
+/// ```
+///
 /// [`Key`]: trait.Key.html
 
 pub trait Keyed {
@@ -54,14 +97,27 @@ pub trait Keyed {
 pub trait Key:
   Eq + PartialEq + PartialOrd + Ord + Hash + Clone + Serialize + DeserializeOwned
 {
-  // no methods required
+  /// Tells if the serialized key may be longer than 16 bytes, even if a particular value is not.
+  /// Then the key hash is always an MD5 digest, as RTPS decides by the maximum size of the key type.
+  /// The default is `true`, which is right for keys containing strings or sequences. Override
+  /// this to return `false` for fixed-size keys of at most 16 bytes, whose key hash is then the
+  /// serialized key itself. Derived implementations decide by the key fields.
+  fn may_exceed_16_bytes() -> bool {
+    true
+  }
+
+  /// Computes the RTPS KeyHash (RTPS spec 9.6.3.8). The key is serialized to big-endian CDR,
+  /// which is the key hash as such, zero-padded to 16 bytes, if it fits. Otherwise the key hash
+  /// is the MD5 digest of the serialization.
+  ///
+  /// The 16 bytes of the key hash are represented as a little-endian `u128`.
   fn into_hash_key(&self) -> u128 {
-    let cdr_bytes = match to_bytes::<Self, LittleEndian>(&self) {
+    let cdr_bytes = match to_bytes::<Self, BigEndian>(&self) {
       Ok(b) => b,
       _ => Vec::new(),
     };
 
-    let digest = if cdr_bytes.len() > 16 {
+    let digest = if Self::may_exceed_16_bytes() || cdr_bytes.len() > 16 {
       md5::compute(&cdr_bytes).to_vec()
     } else {
       cdr_bytes
@@ -91,21 +147,19 @@ impl<D: Keyed> Keyed for &D {
   }
 }
 
-// TODO: might want to implement this for each primitive?
-impl Key for bool {}
-impl Key for char {}
-impl Key for i8 {}
-impl Key for i16 {}
-impl Key for i32 {}
-impl Key for i64 {}
-impl Key for i128 {}
-impl Key for isize {}
-impl Key for u8 {}
-impl Key for u16 {}
-impl Key for u32 {}
-impl Key for u64 {}
-impl Key for u128 {}
-impl Key for usize {}
+macro_rules! fixed_size_keys {
+  ($($t:ty),*) => {
+    $(
+      impl Key for $t {
+        fn may_exceed_16_bytes() -> bool {
+          false
+        }
+      }
+    )*
+  };
+}
+
+fixed_size_keys!(bool, char, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
 
 impl Key for String {}
 
@@ -128,3 +182,107 @@ impl BuiltInTopicKey {
     BuiltInTopicKey { value: [0, 0, 0] }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{
+    dds::traits::{Key, Keyed},
+    structure::guid::GUID,
+  };
+
+  #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Key)]
+  struct Position {
+    x: i16,
+    y: i16,
+  }
+
+  #[derive(Serialize, Deserialize, Keyed)]
+  struct Vehicle {
+    #[key]
+    fleet: u8,
+    speed: f32,
+    #[key]
+    position: Position,
+  }
+
+  #[derive(Serialize, Deserialize, Keyed)]
+  struct Named {
+    #[key]
+    name: String,
+    #[key]
+    index: u32,
+  }
+
+  #[derive(Serialize, Deserialize, Keyed)]
+  struct Reading(u64, #[key] i32);
+
+  #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+  struct Label(String);
+
+  impl Key for Label {}
+
+  #[test]
+  fn derived_compound_key() {
+    let vehicle = Vehicle {
+      fleet: 3,
+      speed: 12.5,
+      position: Position { x: 1, y: -1 },
+    };
+    let key = vehicle.get_key();
+    assert_eq!(key.fleet, 3);
+    assert_eq!(key.position, Position { x: 1, y: -1 });
+    assert_eq!(Reading(9, -5).get_key().0, -5);
+
+    // Key fields are serialized in declaration order, skipping the rest. The nested
+    // struct starts at a 4 byte boundary.
+    assert_eq!(
+      to_bytes::<VehicleKey, BigEndian>(&key).unwrap(),
+      vec![0x03, 0x00, 0x00, 0x00, 0x00, 0x01, 0xff, 0xff]
+    );
+    let mut expected = [0u8; 16];
+    expected[..8].copy_from_slice(&[0x03, 0x00, 0x00, 0x00, 0x00, 0x01, 0xff, 0xff]);
+    assert_eq!(key.into_hash_key().to_le_bytes(), expected);
+  }
+
+  #[test]
+  fn derived_key_may_exceed_16_bytes() {
+    assert!(!Position::may_exceed_16_bytes());
+    assert!(!VehicleKey::may_exceed_16_bytes());
+    assert!(NamedKey::may_exceed_16_bytes());
+
+    let key = Named {
+      name: String::from("a"),
+      index: 1,
+    }
+    .get_key();
+    let cdr = to_bytes::<NamedKey, BigEndian>(&key).unwrap();
+    assert_eq!(key.into_hash_key().to_le_bytes(), md5::compute(&cdr).0);
+  }
+
+  #[test]
+  fn key_hash_of_short_key_is_padded_big_endian() {
+    let mut expected = [0u8; 16];
+    expected[..4].copy_from_slice(&[0x00, 0x00, 0x01, 0x02]);
+    assert_eq!(0x0102i32.into_hash_key().to_le_bytes(), expected);
+    assert_eq!(0i32.into_hash_key(), 0);
+  }
+
+  #[test]
+  fn key_hash_of_implemented_key_is_md5_by_default() {
+    assert!(Label::may_exceed_16_bytes());
+    assert!(!GUID::may_exceed_16_bytes());
+    let label = Label(String::from("a"));
+    let cdr = to_bytes::<Label, BigEndian>(&label).unwrap();
+    assert_eq!(label.into_hash_key().to_le_bytes(), md5::compute(&cdr).0);
+  }
+
+  #[test]
+  fn key_hash_of_string_is_md5() {
+    // CDR string: length including terminating nul, characters, nul
+    let cdr: Vec<u8> = vec![0x00, 0x00, 0x00, 0x04, b'a', b'b', b'c', 0x00];
+    let expected = md5::compute(&cdr).0;
+    assert_eq!("abc".to_string().into_hash_key().to_le_bytes(), expected);
+  }
+}
@@ -1,12 +1,15 @@
 //! All DDS related traits for functionality such as getting DDS [EntityId](../data_types/struct.EntityId.html)
 
 pub(crate) mod dds_entity;
+pub(crate) mod dds_type;
 pub(crate) mod key;
 pub mod serde_adapters;
 
 pub use dds_entity::DDSEntity;
+pub use dds_type::DdsType;
 pub use crate::structure::entity::Entity;
 
 pub use key::{Key, Keyed};
+pub use atosdds_derive::{DdsType, Key, Keyed};
 
 pub use super::topic::TopicDescription;
@@ -3,7 +3,7 @@ use std::io;
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 
-use crate::serialization::error::Result;
+use crate::serialization::error::{Error, Result};
 
 use crate::messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier;
 
@@ -15,6 +15,22 @@ where
 {
   fn supported_encodings() -> &'static [RepresentationIdentifier]; // Which data encodings can this deserializer read?
   fn from_bytes<'de>(input_bytes: &'de [u8], encoding: RepresentationIdentifier) -> Result<D>;
+
+  /// Deserializes a key of `D`. Writers send a serialized key instead of data e.g. when
+  /// disposing an instance. The default implementation does not support this, and
+  /// such instances can then be identified only by key hash.
+  fn key_from_bytes<'de, K>(
+    _input_bytes: &'de [u8],
+    encoding: RepresentationIdentifier,
+  ) -> Result<K>
+  where
+    K: DeserializeOwned,
+  {
+    Err(Error::Message(format!(
+      "Key deserialization from representation {} not supported.",
+      u16::from(encoding)
+    )))
+  }
 }
 
 pub trait SerializerAdapter<D>
@@ -23,4 +39,23 @@ where
 {
   fn output_encoding() -> RepresentationIdentifier;
   fn to_writer<W: io::Write>(writer: W, value: &D) -> Result<()>;
+
+  fn to_bytes(value: &D) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    Self::to_writer(&mut buffer, value)?;
+    Ok(buffer)
+  }
+
+  /// Serializes a key of `D` in the `output_encoding`. Writers send a serialized key
+  /// e.g. when disposing an instance. The default implementation does not support this,
+  /// and such instances can then be identified only by key hash.
+  fn key_to_bytes<K>(_value: &K) -> Result<Vec<u8>>
+  where
+    K: Serialize,
+  {
+    Err(Error::Message(format!(
+      "Key serialization to representation {} not supported.",
+      u16::from(Self::output_encoding())
+    )))
+  }
 }
@@ -1,24 +1,124 @@
-use std::result;
+use std::{io, result};
+
+use crate::structure::{guid::GUID, inline_qos::SampleIdentity};
+use crate::dds::qos::QosPolicyId;
+use crate::serialization::error::Error as SerializationError;
 
 // This is a specialized Result, similar to std::io::Result
 pub type Result<T> = result::Result<T, Error>;
 
-/// This roughly corresponds to "Return codes" in DDS spec 2.2.1.1 Format and Conventions
-#[derive(Debug)]
-pub enum Error {
-  // OK is not included. It is not an error. Ok/Error shoudl be distinguished with the Result type.
-  // Error, // unspecified, please do not use these
+/// "Return codes" in DDS spec 2.2.1.1 Format and Conventions, except OK,
+/// TIMEOUT and NO_DATA, which are not errors in Rust.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ReturnCode {
   BadParameter,
   Unsupported,
-  // AlreadyDeleted, // we should use Rust type system to avoid these, so no need for run-time error.
   OutOfResources,
   NotEnabled,
-  ImmutablePolicy, // can we check this statically?
+  ImmutablePolicy,
   InconsistentPolicy,
   PreconditionNotMet,
-  //Timeout,  // this is normal operation and should be encoded as Option<> or Result<>
   IllegalOperation,
-  //NoData,  // this should be encoded as Option<SomeData>, not an error code
+}
+
+/// Error of a DDS operation. The message tells what failed, e.g. the entity,
+/// topic or address, and [`return_code`](#method.return_code) classifies it
+/// as the DDS specification does.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+  #[error("{reason}")]
+  BadParameter { reason: String },
+  #[error("{reason}")]
+  Unsupported { reason: String },
+  #[error("{reason}")]
+  OutOfResources { reason: String },
+  #[error("{reason}")]
+  NotEnabled { reason: String },
+  #[error("{reason}")]
+  ImmutablePolicy { reason: String },
+  #[error("{reason}")]
+  InconsistentPolicy { reason: String },
+  #[error("{reason}")]
+  PreconditionNotMet { reason: String },
+  #[error("{reason}")]
+  IllegalOperation { reason: String },
+  /// An operating system call, such as binding a socket, failed.
+  #[error("{reason}: {source}")]
+  Io { reason: String, source: io::Error },
+  /// A sample or key could not be serialized.
+  #[error("{reason}: {source}")]
+  Serialization {
+    reason: String,
+    source: SerializationError,
+  },
+  /// A sample does not fit in the largest message the participant sends over UDP,
+  /// `max_message_size` of its DiscoveryConfig.
+  #[error("{reason}")]
+  MessageTooLarge { reason: String },
+}
+
+impl Error {
+  /// The DDS return code of this error. Io and MessageTooLarge errors are
+  /// `OutOfResources`, and Serialization errors `BadParameter`.
+  pub fn return_code(&self) -> ReturnCode {
+    match self {
+      Error::BadParameter { .. } => ReturnCode::BadParameter,
+      Error::Unsupported { .. } => ReturnCode::Unsupported,
+      Error::OutOfResources { .. } => ReturnCode::OutOfResources,
+      Error::NotEnabled { .. } => ReturnCode::NotEnabled,
+      Error::ImmutablePolicy { .. } => ReturnCode::ImmutablePolicy,
+      Error::InconsistentPolicy { .. } => ReturnCode::InconsistentPolicy,
+      Error::PreconditionNotMet { .. } => ReturnCode::PreconditionNotMet,
+      Error::IllegalOperation { .. } => ReturnCode::IllegalOperation,
+      Error::Io { .. } => ReturnCode::OutOfResources,
+      Error::Serialization { .. } => ReturnCode::BadParameter,
+      Error::MessageTooLarge { .. } => ReturnCode::OutOfResources,
+    }
+  }
+
+  pub(crate) fn bad_parameter<S: Into<String>>(reason: S) -> Error {
+    Error::BadParameter {
+      reason: reason.into(),
+    }
+  }
+
+  pub(crate) fn unsupported<S: Into<String>>(reason: S) -> Error {
+    Error::Unsupported {
+      reason: reason.into(),
+    }
+  }
+
+  pub(crate) fn out_of_resources<S: Into<String>>(reason: S) -> Error {
+    Error::OutOfResources {
+      reason: reason.into(),
+    }
+  }
+
+  pub(crate) fn message_too_large<S: Into<String>>(reason: S) -> Error {
+    Error::MessageTooLarge {
+      reason: reason.into(),
+    }
+  }
+
+  pub(crate) fn precondition_not_met<S: Into<String>>(reason: S) -> Error {
+    Error::PreconditionNotMet {
+      reason: reason.into(),
+    }
+  }
+
+  pub(crate) fn io<S: Into<String>>(reason: S, source: io::Error) -> Error {
+    Error::Io {
+      reason: reason.into(),
+      source,
+    }
+  }
+
+  pub(crate) fn serialization<S: Into<String>>(reason: S, source: SerializationError) -> Error {
+    Error::Serialization {
+      reason: reason.into(),
+      source,
+    }
+  }
 }
 
 /// Helper to contain same count actions across statuses
@@ -57,6 +157,16 @@ impl CountWithChange {
     self.count_change += 1;
   }
 
+  pub fn decrease(&mut self) {
+    self.count -= 1;
+    self.count_change -= 1;
+  }
+
+  pub fn increase_by(&mut self, amount: i32) {
+    self.count += amount;
+    self.count_change += amount;
+  }
+
   pub fn reset_count(&mut self) {
     self.count_change = 0;
   }
@@ -80,11 +190,18 @@ impl InconsistentTopicStatus {
 }
 
 /// DDS SampleLostStatus
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct SampleLostStatus {
   total: CountWithChange,
 }
 
 impl SampleLostStatus {
+  pub(crate) fn new() -> SampleLostStatus {
+    SampleLostStatus {
+      total: CountWithChange::new(),
+    }
+  }
+
   /// Total cumulative count of all samples lost across of instances of data published under the Topic.
   pub fn count(&self) -> i32 {
     self.total.count()
@@ -94,24 +211,45 @@ impl SampleLostStatus {
   pub fn count_change(&self) -> i32 {
     self.total.count_change()
   }
+
+  pub(crate) fn increase_by(&mut self, amount: i32) {
+    self.total.increase_by(amount);
+  }
+
+  pub(crate) fn reset_change(&mut self) {
+    self.total.reset_count();
+  }
 }
 
 /// Reason for sample rejection
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SampleRejectedReason {
   InstancesLimit,
   SamplesLimit,
   SamplesPerInstanceLimit,
+  /// The serialized payload could not be deserialized. This is not a DDS
+  /// specification reason.
+  DeserializationError,
 }
 
 /// DDS SampleRejectedStatus
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct SampleRejectedStatus {
   total: CountWithChange,
   last_reason: Option<SampleRejectedReason>, // None == NOT_REJECTED
-                                             // missing: last_instance_handle: instance key indicating last rejected instance
+  // missing: last_instance_handle: instance key indicating last rejected instance
+  last_sample: Option<SampleIdentity>,
 }
 
 impl SampleRejectedStatus {
+  pub(crate) fn new() -> SampleRejectedStatus {
+    SampleRejectedStatus {
+      total: CountWithChange::new(),
+      last_reason: None,
+      last_sample: None,
+    }
+  }
+
   /// Total cumulative count of samples rejected by the DataReader.
   pub fn count(&self) -> i32 {
     self.total.count()
@@ -126,6 +264,22 @@ impl SampleRejectedStatus {
   pub fn sample_rejected_reason(&self) -> Option<SampleRejectedReason> {
     self.last_reason
   }
+
+  /// Writer GUID and sequence number of the last sample rejected, if they are known.
+  /// This is not in the DDS specification.
+  pub fn last_rejected_sample(&self) -> Option<SampleIdentity> {
+    self.last_sample
+  }
+
+  pub(crate) fn increase(&mut self, reason: SampleRejectedReason, sample: Option<SampleIdentity>) {
+    self.total.increase();
+    self.last_reason = Some(reason);
+    self.last_sample = sample;
+  }
+
+  pub(crate) fn reset_change(&mut self) {
+    self.total.reset_count();
+  }
 }
 
 /// All possible status changes
@@ -138,6 +292,26 @@ pub enum StatusChange {
   RequestedIncompatibleQosStatus(RequestedIncompatibleQosStatus),
   PublicationMatchedStatus(PublicationMatchedStatus),
   SubscriptionMatchedStatus(SubscriptionMatchedStatus),
+  SampleLostStatus(SampleLostStatus),
+  /// All samples written so far are acknowledged by all matched reliable DataReaders.
+  AcknowledgmentsComplete,
+}
+
+/// Status changes of a DataWriter, in the order they happened. See
+/// `DataWriter::get_status_change`.
+#[derive(Debug, Clone)]
+pub enum DataWriterStatus {
+  /// A DataReader was matched or unmatched.
+  PublicationMatched(PublicationMatchedStatus),
+  /// The DataWriter did not write within its offered deadline.
+  OfferedDeadlineMissed(OfferedDeadlineMissedStatus),
+  /// A DataReader requests QoS that the DataWriter does not offer.
+  OfferedIncompatibleQos(OfferedIncompatibleQosStatus),
+  /// The DataWriter with `ManualByTopic` liveliness did not write or assert its
+  /// liveliness within the lease duration.
+  LivelinessLost(LivelinessLostStatus),
+  /// All samples written so far are acknowledged by all matched reliable DataReaders.
+  AcknowledgmentsComplete,
 }
 
 /// DDS LivelinessLostStatus
@@ -147,6 +321,20 @@ pub struct LivelinessLostStatus {
 }
 
 impl LivelinessLostStatus {
+  pub(crate) fn new() -> LivelinessLostStatus {
+    LivelinessLostStatus {
+      total: CountWithChange::new(),
+    }
+  }
+
+  pub(crate) fn increase(&mut self) {
+    self.total.increase();
+  }
+
+  pub(crate) fn reset_change(&mut self) {
+    self.total.reset_count();
+  }
+
   /// Total cumulative number of times that a previously-alive DataWriter became not alive due to a failure to actively signal its liveliness within its offered liveliness period.
   /// This count does not change when an already not alive DataWriter simply remains not alive for another liveliness period.
   pub fn count(&self) -> i32 {
@@ -199,14 +387,21 @@ impl OfferedDeadlineMissedStatus {
 }
 
 /// DDS OfferedIncompatibleQosStatus
-#[derive(Debug, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub struct OfferedIncompatibleQosStatus {
   total: CountWithChange,
-  //TODO: last_policy_id: QosPolicyId_t
+  last_policy_id: Option<QosPolicyId>,
   //TODO: policies: QosPolicyCountSeq
 }
 
 impl OfferedIncompatibleQosStatus {
+  pub(crate) fn new() -> OfferedIncompatibleQosStatus {
+    OfferedIncompatibleQosStatus {
+      total: CountWithChange::new(),
+      last_policy_id: None,
+    }
+  }
+
   /// Total cumulative number of times the concerned DataWriter discovered a
   /// DataReader for the same Topic with a requested QoS that is incompatible
   /// with that offered by the DataWriter.
@@ -217,6 +412,21 @@ impl OfferedIncompatibleQosStatus {
   pub fn count_change(&self) -> i32 {
     self.total.count_change()
   }
+
+  /// The policy found incompatible the last time an incompatible DataReader
+  /// was discovered, or None if that has not happened.
+  pub fn last_policy_id(&self) -> Option<QosPolicyId> {
+    self.last_policy_id
+  }
+
+  pub(crate) fn increase(&mut self, policy: QosPolicyId) {
+    self.total.increase();
+    self.last_policy_id = Some(policy);
+  }
+
+  pub(crate) fn reset_change(&mut self) {
+    self.total.reset_count();
+  }
 }
 
 /// DDS RequestedDeadlineMissedStatus
@@ -265,14 +475,21 @@ impl RequestedDeadlineMissedStatus {
 }
 
 /// DDS RequestedIncompatibleQosStatus
-#[derive(Debug, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub struct RequestedIncompatibleQosStatus {
   total: CountWithChange,
-  //TODO: last_policy_id: QosPolicyId_t
+  last_policy_id: Option<QosPolicyId>,
   //TODO: policies: QosPolicyCountSeq
 }
 
 impl RequestedIncompatibleQosStatus {
+  pub(crate) fn new() -> RequestedIncompatibleQosStatus {
+    RequestedIncompatibleQosStatus {
+      total: CountWithChange::new(),
+      last_policy_id: None,
+    }
+  }
+
   /// Total cumulative number of times the concerned DataReader discovered a
   /// DataWriter for the same Topic with an offered QoS that was incompatible
   /// with that requested by the DataReader.
@@ -285,17 +502,56 @@ impl RequestedIncompatibleQosStatus {
   pub fn count_change(&self) -> i32 {
     self.total.count_change()
   }
+
+  /// The policy found incompatible the last time an incompatible DataWriter
+  /// was discovered, or None if that has not happened.
+  pub fn last_policy_id(&self) -> Option<QosPolicyId> {
+    self.last_policy_id
+  }
+
+  pub(crate) fn increase(&mut self, policy: QosPolicyId) {
+    self.total.increase();
+    self.last_policy_id = Some(policy);
+  }
+
+  pub(crate) fn reset_change(&mut self) {
+    self.total.reset_count();
+  }
 }
 
 /// DDS PublicationMatchedStatus
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct PublicationMatchedStatus {
   total: CountWithChange,
   current: CountWithChange,
-  // Missing: reference to last instance key
+  last_subscription_handle: Option<GUID>,
 }
 
 impl PublicationMatchedStatus {
+  pub(crate) fn new() -> PublicationMatchedStatus {
+    PublicationMatchedStatus {
+      total: CountWithChange::new(),
+      current: CountWithChange::new(),
+      last_subscription_handle: None,
+    }
+  }
+
+  pub(crate) fn increase(&mut self, reader: GUID) {
+    self.total.increase();
+    self.current.increase();
+    self.last_subscription_handle = Some(reader);
+  }
+
+  pub(crate) fn decrease(&mut self, reader: GUID) {
+    self.current.decrease();
+    self.last_subscription_handle = Some(reader);
+  }
+
+  pub(crate) fn reset_change(&mut self) {
+    self.total.reset_count();
+    self.current.reset_count();
+  }
+
   /// Total cumulative count the concerned DataWriter discovered a “match” with
   /// a DataReader. That is, it found a DataReader for the same Topic with a
   /// requested QoS that is compatible with that offered by the DataWriter.
@@ -319,17 +575,46 @@ impl PublicationMatchedStatus {
   pub fn current_count_change(&self) -> i32 {
     self.current.count_change()
   }
+
+  /// GUID of the last DataReader that matched or unmatched the DataWriter.
+  pub fn last_subscription_handle(&self) -> Option<GUID> {
+    self.last_subscription_handle
+  }
 }
 
 /// DDS SubscriptionMatchedStatus
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct SubscriptionMatchedStatus {
   total: CountWithChange,
   current: CountWithChange,
-  // Missing: reference to last instance key
+  last_publication_handle: Option<GUID>,
 }
 
 impl SubscriptionMatchedStatus {
+  pub(crate) fn new() -> SubscriptionMatchedStatus {
+    SubscriptionMatchedStatus {
+      total: CountWithChange::new(),
+      current: CountWithChange::new(),
+      last_publication_handle: None,
+    }
+  }
+
+  pub(crate) fn increase(&mut self, writer: GUID) {
+    self.total.increase();
+    self.current.increase();
+    self.last_publication_handle = Some(writer);
+  }
+
+  pub(crate) fn decrease(&mut self, writer: GUID) {
+    self.current.decrease();
+    self.last_publication_handle = Some(writer);
+  }
+
+  pub(crate) fn reset_change(&mut self) {
+    self.total.reset_count();
+    self.current.reset_count();
+  }
+
   /// Total cumulative count the concerned DataReader discovered a “match”
   /// with a DataWriter. That is, it found a DataWriter for the same Topic with
   /// a requested QoS that is compatible with that offered by the DataReader.
@@ -353,4 +638,9 @@ impl SubscriptionMatchedStatus {
   pub fn current_count_change(&self) -> i32 {
     self.current.count_change()
   }
+
+  /// GUID of the last DataWriter that matched or unmatched the DataReader.
+  pub fn last_publication_handle(&self) -> Option<GUID> {
+    self.last_publication_handle
+  }
 }
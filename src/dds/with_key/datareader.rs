@@ -1,23 +1,30 @@
-use std::{fs::File, io};
-use std::sync::{Arc, RwLock};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock, mpsc::TryRecvError};
 use std::marker::PhantomData;
+use std::time::Instant;
 
 use itertools::Itertools;
-use io::Write;
 use serde::de::DeserializeOwned;
 use mio_extras::channel as mio_channel;
-use log::{error, info, warn};
-use mio::{Evented, Poll, PollOpt, Ready, Token};
+use log::{debug, info, log_enabled, warn, Level};
+use mio::{Evented, Events, Poll, PollOpt, Ready, Token};
 
 use crate::{
   serialization::CDRDeserializerAdapter,
-  discovery::discovery::DiscoveryCommand,
+  discovery::{discovery_config::IpFamily, data_types::topic_data::DiscoveredWriterData},
+  network::{constant::get_user_traffic_multicast_port, util::topic_multicast_group},
   structure::{
     entity::{Entity, EntityAttributes},
     guid::{GUID, EntityId},
+    locator::Locator,
+    clock::SharedClock,
     time::Timestamp,
-    dds_cache::DDSCache,
+    duration::Duration,
+    dds_cache::{CacheInstant, DDSCache},
     cache_change::{CacheChange, ChangeKind},
+    inline_qos::SampleIdentity,
+    topic_kind::TopicKind,
   },
 };
 use crate::dds::{
@@ -27,15 +34,22 @@ use crate::dds::{
   qos::*,
   with_key::datasample::*,
   sampleinfo::*,
-  datasample_cache::DataSampleCache,
+  datasample_cache::{DataSampleCache, LoanedSamples},
+  matched_writers::MatchedWriters,
+  rtps_config::{ReaderMulticast, RtpsReaderConfig},
+  statistics::{ReaderCounters, ReaderStatistics},
+  task_waker::TaskWaker,
   pubsub::Subscriber,
   topic::Topic,
   readcondition::*,
 };
 
-use crate::messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier;
+use crate::messages::submessages::submessage_elements::serialized_payload::{
+  RepresentationIdentifier, SerializedPayload,
+};
+use crate::common::lock::{read_lock, write_lock};
 
-/// Parameter for reading [Readers](../struct.With_Key_DataReader.html) data with key or with next from current key.
+/// Parameter for reading [Readers](../struct.WithKeyDataReader.html) data with key or with next from current key.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SelectByKey {
   This,
@@ -45,8 +59,18 @@ pub enum SelectByKey {
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum ReaderCommand {
   RESET_REQUESTED_DEADLINE_STATUS,
+  RESET_REQUESTED_INCOMPATIBLE_QOS_STATUS,
+  RESET_SAMPLE_LOST_STATUS,
+  RESET_SUBSCRIPTION_MATCHED_STATUS,
+  SET_RTPS_CONFIG(RtpsReaderConfig),
+  SET_MULTICAST_GROUP(Option<IpAddr>),
+  // Notify the DataReader again, as it left samples in its cache
+  NOTIFY_DATA_AVAILABLE,
 }
 
+// Token for the private Poll used by blocking reads. This is the only registration in that Poll.
+const BLOCKING_READ_TOKEN: Token = Token(0);
+
 struct CurrentStatusChanges {
   pub livelinessLost: Option<LivelinessLostStatus>,
   pub offeredDeadlineMissed: Option<OfferedDeadlineMissedStatus>,
@@ -55,6 +79,7 @@ struct CurrentStatusChanges {
   pub requestedIncompatibleQos: Option<RequestedIncompatibleQosStatus>,
   pub publicationMatched: Option<PublicationMatchedStatus>,
   pub subscriptionMatched: Option<SubscriptionMatchedStatus>,
+  pub sampleLost: Option<SampleLostStatus>,
 }
 
 impl CurrentStatusChanges {
@@ -67,6 +92,7 @@ impl CurrentStatusChanges {
       requestedIncompatibleQos: None,
       publicationMatched: None,
       subscriptionMatched: None,
+      sampleLost: None,
     }
   }
 }
@@ -81,10 +107,10 @@ impl CurrentStatusChanges {
 /// use rustdds::dds::qos::QosPolicyBuilder;
 /// use rustdds::dds::data_types::TopicKind;
 /// use rustdds::dds::traits::Keyed;
-/// use rustdds::dds::With_Key_DataReader as DataReader;
+/// use rustdds::dds::WithKeyDataReader as DataReader;
 /// use rustdds::serialization::CDRDeserializerAdapter;
 ///
-/// let domain_participant = DomainParticipant::new(0);
+/// let domain_participant = DomainParticipant::new(0).unwrap();
 /// let qos = QosPolicyBuilder::new().build();
 /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
 ///
@@ -112,17 +138,29 @@ pub struct DataReader<
   qos_policy: QosPolicies,
   entity_attributes: EntityAttributes,
   pub(crate) notification_receiver: mio_channel::Receiver<()>,
+  // Created on first blocking read. Owns the registration of notification_receiver.
+  blocking_poll: Option<Poll>,
 
   dds_cache: Arc<RwLock<DDSCache>>,
+  clock: SharedClock,
 
   datasample_cache: DataSampleCache<D>,
-  latest_instant: Timestamp,
+  latest_instant: CacheInstant,
+  // The Reader whose received changes are taken. Our own, unless we share that of
+  // Discovery.
+  reader_guid: GUID,
   deserializer_type: PhantomData<DA>, // This is to provide use for DA
 
-  discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
   pub(crate) status_receiver: mio_channel::Receiver<StatusChange>,
   current_status: CurrentStatusChanges,
+  // Samples are deserialized here, so the rejections are counted here and not
+  // in the Reader.
+  sample_rejected_status: SampleRejectedStatus,
   pub(crate) reader_command: mio_channel::SyncSender<ReaderCommand>,
+  data_waker: TaskWaker,
+  statistics: Arc<ReaderCounters>,
+  // writers of the Reader, for get_matched_publications
+  matched_writers: MatchedWriters,
 }
 
 impl<'a, D, DA> Drop for DataReader<'a, D, DA>
@@ -131,16 +169,15 @@ where
   DA: DeserializerAdapter<D>,
 {
   fn drop(&mut self) {
-    match self
-      .discovery_command
-      .send(DiscoveryCommand::REMOVE_LOCAL_READER {
-        guid: self.get_guid(),
-      }) {
-      Ok(_) => {}
-      Err(e) => error!(
-        "Failed to send REMOVE_LOCAL_READER DiscoveryCommand. {:?}",
-        e
-      ),
+    if self.my_subscriber.remove_reader(self.get_guid()).is_ok() {
+      return;
+    }
+    // Deleted explicitly already, or sharing the Reader of another DataReader, so
+    // only its progress is left.
+    if let Err(e) = read_lock(&self.dds_cache)
+      .remove_data_reader(&self.my_topic.get_name().to_string(), self.get_guid())
+    {
+      debug!("DataReader was not in DDSCache: {}", e);
     }
   }
 }
@@ -158,41 +195,76 @@ where
     // Each notification sent to this channel must be try_recv'd
     notification_receiver: mio_channel::Receiver<()>,
     dds_cache: Arc<RwLock<DDSCache>>,
-    discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
     status_receiver: mio_channel::Receiver<StatusChange>,
     reader_command: mio_channel::SyncSender<ReaderCommand>,
+    data_waker: TaskWaker,
+    statistics: Arc<ReaderCounters>,
+    matched_writers: MatchedWriters,
   ) -> Result<Self> {
     let dp = match subscriber.get_participant() {
       Some(dp) => dp,
       None => {
-        error!("Cannot create new DataReader, DomainParticipant doesn't exist.");
-        return Err(Error::PreconditionNotMet);
+        return Err(Error::precondition_not_met(format!(
+          "cannot create DataReader of topic {}, DomainParticipant has been dropped",
+          topic.get_name()
+        )))
       }
     };
 
-    let entity_attributes = EntityAttributes::new(GUID::new_with_prefix_and_id(
-      dp.get_guid_prefix().clone(),
-      my_id,
-    ));
+    let guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix().clone(), my_id);
+    let entity_attributes = EntityAttributes::new(guid);
+
+    let clock = read_lock(&dds_cache).clock();
 
-    Ok(Self {
+    let data_reader = Self {
       my_subscriber: subscriber,
       my_topic: topic,
       qos_policy: topic.get_qos().clone(),
       entity_attributes,
       notification_receiver,
+      blocking_poll: None,
       dds_cache,
       datasample_cache: DataSampleCache::new(topic.get_qos().clone()),
       // The reader is created before the datareader, hence initializing the
       // latest_instant to now should be fine. There should be no smaller instants
       // added by the reader.
-      latest_instant: Timestamp::now(),
+      latest_instant: CacheInstant::at(clock.now()),
+      clock,
+      reader_guid: guid,
       deserializer_type: PhantomData,
-      discovery_command,
       status_receiver,
       current_status: CurrentStatusChanges::new(),
+      sample_rejected_status: SampleRejectedStatus::new(),
       reader_command,
-    })
+      data_waker,
+      statistics,
+      matched_writers,
+    };
+    data_reader.set_cache_progress();
+    Ok(data_reader)
+  }
+
+  // Makes the next read also return everything that is already in DDSCache.
+  pub(crate) fn read_from_beginning(&mut self) {
+    self.latest_instant = CacheInstant::from(Timestamp::TIME_ZERO);
+    self.set_cache_progress();
+  }
+
+  // Makes this DataReader take the changes that the Reader `reader_guid` received.
+  pub(crate) fn share_reader(&mut self, reader_guid: GUID) {
+    self.reader_guid = reader_guid;
+  }
+
+  // Tells DDSCache garbage collection which changes this DataReader has taken.
+  fn set_cache_progress(&self) {
+    let cache = read_lock(&self.dds_cache);
+    if let Err(e) = cache.set_data_reader_progress(
+      &self.my_topic.get_name().to_string(),
+      self.get_guid(),
+      self.latest_instant,
+    ) {
+      warn!("DataReader progress not recorded: {}", e);
+    }
   }
 
   /// Reads amount of samples found with `max_samples` and `read_condition` parameters.
@@ -211,10 +283,10 @@ where
   /// # use rustdds::dds::data_types::TopicKind;
   /// use rustdds::dds::data_types::ReadCondition;
   /// # use rustdds::dds::traits::Keyed;
-  /// # use rustdds::dds::With_Key_DataReader as DataReader;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -245,15 +317,14 @@ where
     max_samples: usize,
     read_condition: ReadCondition,
   ) -> Result<Vec<DataSample<&D>>> {
+    self.clear_notifications();
     self.fill_local_datasample_cache();
 
-    let mut selected = self.datasample_cache.select_keys_for_access(read_condition);
-    selected.truncate(max_samples);
+    let selected = self.select_and_decode(max_samples, |dsc| {
+      dsc.select_keys_for_access(read_condition)
+    });
 
     let result = self.datasample_cache.read_by_keys(&selected);
-    // clearing receiver buffer
-    while let Ok(_) = self.notification_receiver.try_recv() {}
-
     Ok(result)
   }
 
@@ -273,10 +344,10 @@ where
   /// # use rustdds::dds::data_types::TopicKind;
   /// use rustdds::dds::data_types::ReadCondition;
   /// # use rustdds::dds::traits::Keyed;
-  /// # use rustdds::dds::With_Key_DataReader as DataReader;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -307,19 +378,99 @@ where
     max_samples: usize,
     read_condition: ReadCondition,
   ) -> Result<Vec<DataSample<D>>> {
+    self.clear_notifications();
     self.fill_local_datasample_cache();
 
-    let mut selected = self.datasample_cache.select_keys_for_access(read_condition);
-    selected.truncate(max_samples);
+    let selected = self.select_and_decode(max_samples, |dsc| {
+      dsc.select_keys_for_access(read_condition)
+    });
 
     let result = self.datasample_cache.take_by_keys(&selected);
 
-    // clearing receiver buffer
-    while let Ok(_) = self.notification_receiver.try_recv() {}
-
     Ok(result)
   }
 
+  /// Reads samples like [`read`](#method.read), but lends them instead of returning
+  /// a Vec. The values are borrowed from the deserialized samples kept in this DataReader,
+  /// so large samples are not copied.
+  ///
+  /// The DataReader cannot be used while the loan is outstanding. Therefore no new samples
+  /// are received into it, and History or ResourceLimits cannot evict the loaned samples.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn get_key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  ///
+  /// // Wait for data to arrive...
+  ///
+  /// if let Ok(loan) = data_reader.read_loaned(10, ReadCondition::not_read()) {
+  ///   for sample in loan.iter() {
+  ///     // do something
+  ///   }
+  /// }; // loan is returned here
+  /// ```
+  pub fn read_loaned(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+  ) -> Result<LoanedSamples<D>> {
+    self.loan(max_samples, read_condition, false)
+  }
+
+  /// Takes samples like [`take`](#method.take), but lends them instead of moving them
+  /// out of this DataReader. See [`read_loaned`](#method.read_loaned).
+  ///
+  /// The loaned samples are removed from the DataReader when the returned LoanedSamples
+  /// is dropped.
+  pub fn take_loaned(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+  ) -> Result<LoanedSamples<D>> {
+    self.loan(max_samples, read_condition, true)
+  }
+
+  fn loan(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+    take: bool,
+  ) -> Result<LoanedSamples<D>> {
+    self.clear_notifications();
+    self.fill_local_datasample_cache();
+
+    let selected = self.select_and_decode(max_samples, |dsc| {
+      dsc.select_keys_for_access(read_condition)
+    });
+
+    Ok(self.datasample_cache.loan(selected, take))
+  }
+
   /// Reads next unread sample
   ///
   /// # Examples
@@ -330,10 +481,10 @@ where
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
   /// # use rustdds::dds::traits::Keyed;
-  /// # use rustdds::dds::With_Key_DataReader as DataReader;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -372,10 +523,10 @@ where
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
   /// # use rustdds::dds::traits::Keyed;
-  /// # use rustdds::dds::With_Key_DataReader as DataReader;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -404,11 +555,12 @@ where
     Ok(ds.pop())
   }
 
-  // Iterator interface
-
-  /// Produces an interator over the currently available NOT_READ samples.
-  /// Yields only payload data, not SampleInfo metadata
-  /// This is not called `iter()` because it takes a mutable reference to self.
+  /// Reads next unread sample, waiting for at most `timeout` if none is available yet.
+  /// Returns `Ok(None)` if nothing arrived before the timeout.
+  ///
+  /// The calling thread blocks on the notification channel of this DataReader, so
+  /// a DataReader that has been registered to a mio Poll cannot block, and vice versa. Returns `Err(PreconditionNotMet)` in that case, and also
+  /// if the underlying RTPS Reader has been removed.
   ///
   /// # Examples
   ///
@@ -416,12 +568,12 @@ where
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
-  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::data_types::{TopicKind, Duration};
   /// # use rustdds::dds::traits::Keyed;
-  /// # use rustdds::dds::With_Key_DataReader as DataReader;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -439,24 +591,22 @@ where
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
   ///
-  /// // Wait for data to arrive...
-  ///
-  /// for data in data_reader.iterator() {
+  /// if let Ok(Some(data)) = data_reader.read_next_sample_timeout(Duration::from_millis(10)) {
   ///   // do something
   /// }
   /// ```
-  pub fn iterator(&mut self) -> Result<impl Iterator<Item = std::result::Result<&D, D::K>>> {
-    // TODO: We could come up with a more efficent implementation than wrapping a read call
-    Ok(
-      self
-        .read(std::usize::MAX, ReadCondition::not_read())?
-        .into_iter()
-        .map(|ds| ds.value),
-    )
+  pub fn read_next_sample_timeout(&mut self, timeout: Duration) -> Result<Option<DataSample<&D>>> {
+    if self.wait_for_samples(timeout)? {
+      self.read_next_sample()
+    } else {
+      Ok(None)
+    }
   }
 
-  /// Produces an interator over the samples filtered b ygiven condition.
-  /// Yields only payload data, not SampleInfo metadata
+  /// Takes next unread sample, waiting for at most `timeout` if none is available yet.
+  /// Returns `Ok(None)` if nothing arrived before the timeout.
+  ///
+  /// See [read_next_sample_timeout](#method.read_next_sample_timeout) for restrictions.
   ///
   /// # Examples
   ///
@@ -464,13 +614,12 @@ where
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
-  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::data_types::{TopicKind, Duration};
   /// # use rustdds::dds::traits::Keyed;
-  /// # use rustdds::dds::With_Key_DataReader as DataReader;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
-  /// use rustdds::dds::data_types::ReadCondition;
-  ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -488,30 +637,126 @@ where
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
   ///
-  /// // Wait for data to arrive...
-  ///
-  /// for data in data_reader.conditional_iterator(ReadCondition::any()) {
+  /// if let Ok(Some(data)) = data_reader.take_next_sample_timeout(Duration::from_millis(10)) {
   ///   // do something
   /// }
   /// ```
-  pub fn conditional_iterator(
-    &mut self,
-    read_condition: ReadCondition,
-  ) -> Result<impl Iterator<Item = std::result::Result<&D, D::K>>> {
-    // TODO: We could come up with a more efficent implementation than wrapping a read call
-    Ok(
-      self
-        .read(std::usize::MAX, read_condition)?
-        .into_iter()
-        .map(|ds| ds.value),
-    )
+  pub fn take_next_sample_timeout(&mut self, timeout: Duration) -> Result<Option<DataSample<D>>> {
+    if self.wait_for_samples(timeout)? {
+      self.take_next_sample()
+    } else {
+      Ok(None)
+    }
   }
 
-  /// Produces an interator over the currently available NOT_READ samples.
-  /// Yields only payload data, not SampleInfo metadata
-  /// Removes samples from `DataReader`.
-  /// <strong>Note!</strong> If the iterator is only partially consumed, all the samples it could have provided
-  /// are still removed from the `Datareader`.
+  // Blocks until there is a NOT_READ sample in the local cache or timeout expires.
+  // Returns true if there is a sample available.
+  pub(crate) fn wait_for_samples(&mut self, timeout: Duration) -> Result<bool> {
+    let deadline = Instant::now() + timeout.to_std();
+    let mut events = Events::with_capacity(4);
+    loop {
+      // Notifications must be cleared before looking into the cache. Then any data
+      // arriving after the check leaves a notification pending, and the poll below
+      // returns immediately.
+      let mut disconnected = false;
+      loop {
+        match self.notification_receiver.try_recv() {
+          Ok(()) => (),
+          Err(TryRecvError::Empty) => break,
+          Err(TryRecvError::Disconnected) => {
+            disconnected = true;
+            break;
+          }
+        }
+      }
+
+      self.fill_local_datasample_cache();
+      if !self
+        .datasample_cache
+        .select_keys_for_access(ReadCondition::not_read())
+        .is_empty()
+      {
+        return Ok(true);
+      }
+      if disconnected {
+        return Err(self.removed_error());
+      }
+
+      let now = Instant::now();
+      if now >= deadline {
+        return Ok(false);
+      }
+      if let Err(e) = self.blocking_poll()?.poll(&mut events, Some(deadline - now)) {
+        return Err(Error::io(
+          format!(
+            "cannot wait for samples of DataReader {:?}",
+            self.get_guid()
+          ),
+          e,
+        ));
+      }
+    }
+  }
+
+  fn blocking_poll(&mut self) -> Result<&Poll> {
+    if self.blocking_poll.is_none() {
+      let poll = match Poll::new() {
+        Ok(p) => p,
+        Err(e) => return Err(Error::io("cannot create Poll for blocking read", e)),
+      };
+      if let Err(e) = poll.register(
+        &self.notification_receiver,
+        BLOCKING_READ_TOKEN,
+        Ready::readable(),
+        PollOpt::level(),
+      ) {
+        return Err(Error::precondition_not_met(format!(
+          "cannot block on DataReader {:?}, it is already registered to a Poll: {}",
+          self.get_guid(),
+          e
+        )));
+      }
+      self.blocking_poll = Some(poll);
+    }
+    match &self.blocking_poll {
+      Some(poll) => Ok(poll),
+      // not reachable, it was just set
+      None => Err(Error::precondition_not_met("no Poll for blocking read")),
+    }
+  }
+
+  // Error of a DataReader whose RTPS Reader is no longer in the event loop
+  fn removed_error(&self) -> Error {
+    Error::precondition_not_met(format!(
+      "RTPS Reader of DataReader {:?} of topic {} has been removed",
+      self.get_guid(),
+      self.my_topic.get_name()
+    ))
+  }
+
+  // Error of a command that could not be handed to the RTPS Reader
+  fn command_error(&self, action: &str, e: mio_channel::TrySendError<ReaderCommand>) -> Error {
+    let reason = format!(
+      "cannot {} DataReader {:?} of topic {}",
+      action,
+      self.get_guid(),
+      self.my_topic.get_name()
+    );
+    match e {
+      mio_channel::TrySendError::Io(e) => Error::io(reason, e),
+      mio_channel::TrySendError::Full(_) => {
+        Error::out_of_resources(format!("{}, its command queue is full", reason))
+      }
+      mio_channel::TrySendError::Disconnected(_) => self.removed_error(),
+    }
+  }
+
+  // Iterator interface
+
+  /// Produces an iterator over the samples selected by `read_condition`, including SampleInfo.
+  /// Samples are not copied out of the `DataReader`, and are marked read only as
+  /// the iterator yields them. SampleInfo ranks are relative to all the samples
+  /// selected when the iterator was created.
   ///
   /// # Examples
   ///
@@ -519,12 +764,12 @@ where
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
-  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
   /// # use rustdds::dds::traits::Keyed;
-  /// # use rustdds::dds::With_Key_DataReader as DataReader;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -544,24 +789,41 @@ where
   ///
   /// // Wait for data to arrive...
   ///
-  /// for data in data_reader.into_iterator() {
+  /// for sample in data_reader.read_iter(ReadCondition::not_read()).unwrap() {
+  ///   let info = sample.sample_info();
   ///   // do something
   /// }
   /// ```
-  pub fn into_iterator(&mut self) -> Result<impl Iterator<Item = std::result::Result<D, D::K>>> {
-    // TODO: We could come up with a more efficent implementation than wrapping a read call
+  pub fn read_iter(
+    &mut self,
+    read_condition: ReadCondition,
+  ) -> Result<impl Iterator<Item = DataSample<&D>> + '_> {
+    self.clear_notifications();
+    self.fill_local_datasample_cache();
+
+    // Samples are deserialized only as the iterator reaches them, so the ones that fail
+    // to deserialize are skipped instead of selecting again.
+    let selected = self.datasample_cache.select_keys_for_access(read_condition);
+    let rejected_status = &mut self.sample_rejected_status;
+    let statistics = &self.statistics;
     Ok(
       self
-        .take(std::usize::MAX, ReadCondition::not_read())?
-        .into_iter()
-        .map(|ds| ds.value),
+        .datasample_cache
+        .read_iter(selected, move |key, payload, encoding, sample_identity| {
+          decode_sample::<D, DA>(
+            rejected_status,
+            statistics,
+            key,
+            payload,
+            encoding,
+            sample_identity,
+          )
+        }),
     )
   }
 
-  /// Produces an interator over the samples filtered b ygiven condition.
-  /// Yields only payload data, not SampleInfo metadata
-  /// <strong>Note!</strong> If the iterator is only partially consumed, all the samples it could have provided
-  /// are still removed from the `Datareader`.
+  /// Produces an iterator that takes the samples selected by `read_condition`, including SampleInfo.
+  /// Samples are removed from the `DataReader` only as the iterator yields them.
   ///
   /// # Examples
   ///
@@ -569,13 +831,12 @@ where
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
-  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::data_types::{TopicKind, ReadCondition};
   /// # use rustdds::dds::traits::Keyed;
-  /// # use rustdds::dds::With_Key_DataReader as DataReader;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
-  /// use rustdds::dds::data_types::ReadCondition;
-  ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -595,155 +856,41 @@ where
   ///
   /// // Wait for data to arrive...
   ///
-  /// for data in data_reader.into_conditional_iterator(ReadCondition::not_read()) {
+  /// for sample in data_reader.take_iter(ReadCondition::not_read()).unwrap() {
+  ///   let info = sample.sample_info();
   ///   // do something
   /// }
   /// ```
-  pub fn into_conditional_iterator(
+  pub fn take_iter(
     &mut self,
     read_condition: ReadCondition,
-  ) -> Result<impl Iterator<Item = std::result::Result<D, D::K>>> {
-    // TODO: We could come up with a more efficent implementation than wrapping a read call
+  ) -> Result<impl Iterator<Item = DataSample<D>> + '_> {
+    self.clear_notifications();
+    self.fill_local_datasample_cache();
+
+    // as in read_iter
+    let selected = self.datasample_cache.select_keys_for_access(read_condition);
+    let rejected_status = &mut self.sample_rejected_status;
+    let statistics = &self.statistics;
     Ok(
       self
-        .take(std::usize::MAX, read_condition)?
-        .into_iter()
-        .map(|ds| ds.value),
-    )
-  }
-
-  // Gets all unseen cache_changes from the TopicCache. Deserializes
-  // the serialized payload and stores the DataSamples (the actual data and the
-  // samplestate) to local container, datasample_cache.
-  fn fill_local_datasample_cache(&mut self) {
-    let dds_cache = match self.dds_cache.read() {
-      Ok(rwlock) => rwlock,
-      // TODO: Should we panic here? Are we allowed to continue with poisoned DDSCache?
-      Err(e) => panic!(
-        "The DDSCache of domain participant is poisoned. Error: {}",
-        e
-      ),
-    };
-
-    let cache_changes = dds_cache.from_topic_get_changes_in_range(
-      &self.my_topic.get_name().to_string(),
-      &self.latest_instant,
-      &Timestamp::now(),
-    );
-
-    let cache_changes: Vec<(&Timestamp, &CacheChange)> = cache_changes
-      .into_iter()
-      .sorted_by(|(a, _), (b, _)| Ord::cmp(a, b))
-      .filter(|(_, cc)| cc.writer_guid.guidPrefix != self.get_guid_prefix())
-      .collect();
-
-    match cache_changes.last() {
-      Some((last_instant, _)) => self.latest_instant = **last_instant,
-      // No new changes available
-      None => return,
-    };
-
-    for (
-      instant,
-      CacheChange {
-        kind,
-        writer_guid,
-        sequence_number: _,
-        data_value: payload_opt,
-        key: key_hash,
-      },
-    ) in cache_changes
-    {
-      match kind {
-        ChangeKind::NOT_ALIVE_UNREGISTERED => (), // presumably causes no local cache update?
-
-        ChangeKind::NOT_ALIVE_DISPOSED => {
-          /* TODO: Instance to be disposed could be specified by serialized payload also, not only key_hash? */
-          match self.datasample_cache.get_key_by_hash(*key_hash) {
-            Some(key) => self
-              .datasample_cache
-              .add_sample(Err(key), *writer_guid, *instant, None),
-            /* TODO: How to get source timestamps other then None ?? */
-            None => warn!("Tried to dispose with unkonwn key hash: {:x?}", key_hash),
-          }
-        }
-        ChangeKind::ALIVE => {
-          match payload_opt {
-            Some(serialized_payload) => {
-              // what is our data serialization format (representation identifier) ?
-              let rep_id = match RepresentationIdentifier::try_from_u16(
-                serialized_payload.representation_identifier,
-              ) {
-                Ok(r) => r,
-                // cannot use .or_else() because need to "continue" the for-loop
-                Err(other_rep_id) => {
-                  if let Some(ri) = DA::supported_encodings()
-                    .iter()
-                    .find(|r| **r as u16 == other_rep_id)
-                  {
-                    *ri // no worries, our DeserializerAdapter recognizes this representation
-                  } else {
-                    warn!("Datareader: Unknown representation id {:?}.", other_rep_id);
-                    continue; // skip this sample, as we cannot decode it
-                  }
-                }
-              };
-
-              // deserialize
-              let payload = match DA::from_bytes(&serialized_payload.value, rep_id) {
-                Ok(p) => p,
-                // cannot use .or_else() because need to "continue" the for-loop
-                Err(e) => {
-                  error!("Failed to deserialize bytes \n{}", e);
-                  // TODO: Wrap this in a debug conditional. We cannot go writing
-                  // to the file system unless requested by user!
-                  File::create("error_bin.bin")
-                    .unwrap()
-                    .write_all(&serialized_payload.value)
-                    .unwrap();
-                  continue;
-                }
-              };
-              // insert to local cache
-              self
-                .datasample_cache
-                .add_sample(Ok(payload), *writer_guid, *instant, None)
-              /* TODO: How to get source timestamps other then None ?? */
-            }
-            None => warn!("Got CacheChange kind=ALIVE , but no serialized payload!"),
-          }
-        }
-      }
-    }
-  }
-
-  fn infer_key(
-    &self,
-    instance_key: Option<<D as Keyed>::K>,
-    this_or_next: SelectByKey,
-  ) -> Option<<D as Keyed>::K> {
-    match instance_key {
-      Some(k) => match this_or_next {
-        SelectByKey::This => Some(k),
-        SelectByKey::Next => self.datasample_cache.get_next_key(&k),
-      },
-      None => self
         .datasample_cache
-        .instance_map
-        .keys()
-        .next()
-        .map(|k| k.clone()),
-    }
+        .take_iter(selected, move |key, payload, encoding, sample_identity| {
+          decode_sample::<D, DA>(
+            rejected_status,
+            statistics,
+            key,
+            payload,
+            encoding,
+            sample_identity,
+          )
+        }),
+    )
   }
 
-  /// Works similarly to read(), but will return only samples from a specific instance.
-  /// The instance is specified by an optional key. In case the key is not specified, the smallest
-  /// (in key order) instance is selected.
-  /// If a key is specified, then the parameter this_or_next specifies whether to access the instance
-  /// with specified key or the following one, in key order.
-  ///
-  /// This should cover DDS DataReader methods read_instance, read_next_instance,
-  /// read_next_instance_w_condition.
+  /// Produces an interator over the currently available NOT_READ samples.
+  /// Yields only payload data, not SampleInfo metadata
+  /// This is not called `iter()` because it takes a mutable reference to self.
   ///
   /// # Examples
   ///
@@ -753,11 +900,10 @@ where
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
   /// # use rustdds::dds::traits::Keyed;
-  /// # use rustdds::dds::With_Key_DataReader as DataReader;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
-  /// use rustdds::dds::data_types::{ReadCondition,SelectByKey};
-  ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -777,46 +923,16 @@ where
   ///
   /// // Wait for data to arrive...
   ///
-  /// if let Ok(datas) = data_reader.read_instance(10, ReadCondition::any(), Some(3), SelectByKey::This) {
-  ///   for data in datas.iter() {
-  ///     // do something
-  ///   }
+  /// for data in data_reader.iterator() {
+  ///   // do something
   /// }
   /// ```
-  pub fn read_instance(
-    &mut self,
-    max_samples: usize,
-    read_condition: ReadCondition,
-    // Select only samples from instance specified by key. In case of None, select the
-    // "smallest" instance as specified by the key type Ord trait.
-    instance_key: Option<<D as Keyed>::K>,
-    // This = Select instance specified by key.
-    // Next = select next instance in the order specified by Ord on keys.
-    this_or_next: SelectByKey,
-  ) -> Result<Vec<DataSample<&D>>> {
-    self.fill_local_datasample_cache();
-
-    let key = match self.infer_key(instance_key, this_or_next) {
-      Some(k) => k,
-      None => return Ok(Vec::new()),
-    };
-
-    let mut selected = self
-      .datasample_cache
-      .select_instance_keys_for_access(key, read_condition);
-    selected.truncate(max_samples);
-
-    let result = self.datasample_cache.read_by_keys(&selected);
-
-    // clearing receiver buffer
-    while let Ok(_) = self.notification_receiver.try_recv() {}
-
-    Ok(result)
+  pub fn iterator(&mut self) -> Result<impl Iterator<Item = std::result::Result<&D, D::K>>> {
+    Ok(self.read_iter(ReadCondition::not_read())?.map(|ds| ds.value))
   }
 
-  /// Similar to read_instance, but will return owned datasamples
-  /// This should cover DDS DataReader methods take_instance, take_next_instance,
-  /// take_next_instance_w_condition.
+  /// Produces an interator over the samples filtered b ygiven condition.
+  /// Yields only payload data, not SampleInfo metadata
   ///
   /// # Examples
   ///
@@ -826,11 +942,11 @@ where
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
   /// # use rustdds::dds::traits::Keyed;
-  /// # use rustdds::dds::With_Key_DataReader as DataReader;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
-  /// use rustdds::dds::data_types::{ReadCondition,SelectByKey};
+  /// use rustdds::dds::data_types::ReadCondition;
   ///
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
@@ -850,181 +966,66 @@ where
   ///
   /// // Wait for data to arrive...
   ///
-  /// if let Ok(datas) = data_reader.take_instance(10, ReadCondition::any(), Some(3), SelectByKey::Next) {
-  ///   for data in datas.iter() {
-  ///     // do something
-  ///   }
+  /// for data in data_reader.conditional_iterator(ReadCondition::any()) {
+  ///   // do something
   /// }
   /// ```
-  pub fn take_instance(
+  pub fn conditional_iterator(
     &mut self,
-    max_samples: usize,
     read_condition: ReadCondition,
-    // Select only samples from instance specified by key. In case of None, select the
-    // "smallest" instance as specified by the key type Ord trait.
-    instance_key: Option<<D as Keyed>::K>,
-    // This = Select instance specified by key.
-    // Next = select next instance in the order specified by Ord on keys.
-    this_or_next: SelectByKey,
-  ) -> Result<Vec<DataSample<D>>> {
-    self.fill_local_datasample_cache();
-
-    let key = match self.infer_key(instance_key, this_or_next) {
-      Some(k) => k,
-      None => return Ok(Vec::new()),
-    };
-
-    let mut selected = self
-      .datasample_cache
-      .select_instance_keys_for_access(key, read_condition);
-    selected.truncate(max_samples);
-
-    let result = self.datasample_cache.take_by_keys(&selected);
-
-    // clearing receiver buffer
-    while let Ok(_) = self.notification_receiver.try_recv() {}
-
-    Ok(result)
-  }
-
-  // status queries
-
-  fn reset_local_requested_deadline_status_change(&mut self) {
-    info!(
-      "reset_local_requested_deadline_status_change current {:?}",
-      self.current_status.requestedDeadlineMissed
-    );
-    match self.current_status.requestedDeadlineMissed {
-      Some(_s) => {
-        self
-          .current_status
-          .requestedDeadlineMissed
-          .as_mut()
-          .unwrap()
-          .reset_change();
-      }
-      None => {}
-    }
-  }
-
-  fn fetch_readers_current_status(&mut self) -> Result<()> {
-    //self.TEST_FUNCTION_get_requested_deadline_missed_status();
-    let mut received_requested_deadline_status_change: bool = false;
-    loop {
-      let received_status = self.status_receiver.try_recv();
-      match received_status {
-        Ok(s) => match s {
-          StatusChange::LivelinessLostStatus(status) => {
-            self.current_status.livelinessLost = Some(status);
-          }
-          StatusChange::OfferedDeadlineMissedStatus(status) => {
-            self.current_status.offeredDeadlineMissed = Some(status);
-          }
-          StatusChange::OfferedIncompatibleQosStatus(status) => {
-            self.current_status.offeredIncompatibleQos = Some(status);
-          }
-          StatusChange::RequestedDeadlineMissedStatus(status) => {
-            self.current_status.requestedDeadlineMissed = Some(status);
-            received_requested_deadline_status_change = true;
-          }
-          StatusChange::RequestedIncompatibleQosStatus(status) => {
-            self.current_status.requestedIncompatibleQos = Some(status);
-          }
-          StatusChange::PublicationMatchedStatus(status) => {
-            self.current_status.publicationMatched = Some(status);
-          }
-          StatusChange::SubscriptionMatchedStatus(status) => {
-            self.current_status.subscriptionMatched = Some(status);
-          }
-        },
-        Err(e) => {
-          match e {
-            std::sync::mpsc::TryRecvError::Empty => {
-              break;
-            }
-            std::sync::mpsc::TryRecvError::Disconnected => {
-              error!(
-                " Reader disconnected, could not fetch status change: {:?}",
-                e
-              );
-              // return disconnect status!!!
-              return Err(Error::OutOfResources);
-            }
-          }
-        }
-      }
-    }
-    // after looping set command to reset requested deadeline status if needed. Also reset local status.
-    match received_requested_deadline_status_change {
-      true => {
-        self
-          .current_status
-          .requestedDeadlineMissed
-          .unwrap()
-          .reset_change();
-        match self
-          .reader_command
-          .try_send(ReaderCommand::RESET_REQUESTED_DEADLINE_STATUS)
-        {
-          Ok(()) => {
-            return Ok(());
-          }
-          Err(e) => {
-            error!("Unable to send RESET_REQUESTED_DEADLINE_STATUS: {:?}", e);
-            return Err(Error::OutOfResources);
-          }
-        }
-      }
-      false => {
-        return Ok(());
-      }
-    }
-  }
-  /*
-  pub fn register_status_change_notificator(&self, poll: & Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()>{
-    todo!();
-    // let res = poll.register(&self.status_receiver, token, interest, opts);
-
-    //self.TEST_FUNCTION_get_requested_deadline_missed_status();
-    //info!("register datareader status reciever to poll with token {:?}", token );
-    //return res;
-  }
-  */
-
-  // Helper functions
-
-  fn matches_conditions(rcondition: &ReadCondition, dsample: &DataSample<D>) -> bool {
-    if !rcondition
-      .sample_state_mask()
-      .contains(dsample.sample_info().sample_state)
-    {
-      return false;
-    }
-    if !rcondition
-      .view_state_mask()
-      .contains(dsample.sample_info().view_state)
-    {
-      return false;
-    }
-    if !rcondition
-      .instance_state_mask()
-      .contains(dsample.sample_info().instance_state)
-    {
-      return false;
-    }
-    true
+  ) -> Result<impl Iterator<Item = std::result::Result<&D, D::K>>> {
+    Ok(self.read_iter(read_condition)?.map(|ds| ds.value))
   }
 
-  fn change_kind_to_instance_state(c_k: &ChangeKind) -> InstanceState {
-    match c_k {
-      ChangeKind::ALIVE => InstanceState::Alive,
-      ChangeKind::NOT_ALIVE_DISPOSED => InstanceState::NotAlive_Disposed,
-      // TODO check this..?
-      ChangeKind::NOT_ALIVE_UNREGISTERED => InstanceState::NotAlive_NoWriters,
-    }
+  /// Produces an interator over the currently available NOT_READ samples.
+  /// Yields only payload data, not SampleInfo metadata
+  /// Removes samples from `DataReader`.
+  /// If the iterator is only partially consumed, the remaining samples stay in the `DataReader`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn get_key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  ///
+  /// // Wait for data to arrive...
+  ///
+  /// for data in data_reader.into_iterator() {
+  ///   // do something
+  /// }
+  /// ```
+  pub fn into_iterator(
+    &mut self,
+  ) -> Result<impl Iterator<Item = std::result::Result<D, D::K>> + '_> {
+    Ok(self.take_iter(ReadCondition::not_read())?.map(|ds| ds.value))
   }
 
-  /// Gets RequestedDeadlineMissedStatus
+  /// Produces an interator over the samples filtered b ygiven condition.
+  /// Yields only payload data, not SampleInfo metadata
+  /// If the iterator is only partially consumed, the remaining samples stay in the `DataReader`.
   ///
   /// # Examples
   ///
@@ -1034,13 +1035,12 @@ where
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
   /// # use rustdds::dds::traits::Keyed;
-  /// # use rustdds::dds::With_Key_DataReader as DataReader;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
   /// # use rustdds::serialization::CDRDeserializerAdapter;
-  /// use rustdds::dds::qos::policy::Deadline;
-  /// use rustdds::dds::data_types::DDSDuration;
+  /// use rustdds::dds::data_types::ReadCondition;
   ///
-  /// let domain_participant = DomainParticipant::new(0);
-  /// let qos = QosPolicyBuilder::new().deadline(Deadline(DDSDuration::from_millis(1))).build();
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
   /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
   /// #
   /// # #[derive(Serialize, Deserialize)]
@@ -1057,165 +1057,2058 @@ where
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
   ///
-  /// // Wait for some deadline to be missed...
-  /// if let Ok(Some(rqdl)) = data_reader.get_requested_deadline_missed_status() {
+  /// // Wait for data to arrive...
+  ///
+  /// for data in data_reader.into_conditional_iterator(ReadCondition::not_read()) {
   ///   // do something
   /// }
-  ///
   /// ```
-  pub fn get_requested_deadline_missed_status(
+  pub fn into_conditional_iterator(
     &mut self,
-  ) -> Result<Option<RequestedDeadlineMissedStatus>> {
-    self.fetch_readers_current_status()?;
-    let value_before_reset = self.current_status.requestedDeadlineMissed.clone();
-    self.reset_local_requested_deadline_status_change();
-    return Ok(value_before_reset);
+    read_condition: ReadCondition,
+  ) -> Result<impl Iterator<Item = std::result::Result<D, D::K>> + '_> {
+    Ok(self.take_iter(read_condition)?.map(|ds| ds.value))
   }
-} // impl
 
-/*
-impl<'a, D: 'static, SA> IDataReader<D, SA> for DataReader<'a, D, SA>
-where
-  D: DeserializeOwned + Keyed,
-  <D as Keyed>::K: Key,
-  SA: DeserializerAdapter<D>,
-{
-  fn read(
-    &mut self,
-    max_samples: usize,
-    read_condition: ReadCondition,
-  ) -> Result<Vec<&dyn IDataSample<D>>> {
-    let samples = self.read_as_obj(max_samples, read_condition);
-    match samples {
-      Ok(d) => Ok(d.into_iter().map(|p| p.as_idata_sample()).collect()),
-      Err(e) => Err(e),
+  // Finds the instance of a dispose or unregister. If the writer sent a serialized key, it is
+  // because the key hash was missing. Otherwise the key hash identifies the instance.
+  // A NoKey topic has only one instance, whatever key hash the writer sent.
+  fn instance_key(
+    &self,
+    key_hash: u128,
+    serialized_key: &Option<SerializedPayload>,
+  ) -> Option<D::K> {
+    if self.my_topic.kind() == TopicKind::NoKey {
+      return self.known_instance(key_hash);
+    }
+    match serialized_key {
+      Some(sk) => {
+        let rep_id = RepresentationIdentifier::try_from_u16(sk.representation_identifier).ok()?;
+        match DA::key_from_bytes::<D::K>(&sk.value, rep_id) {
+          Ok(key) => Some(key),
+          Err(e) => {
+            warn!("Failed to deserialize key. {:?}", e);
+            None
+          }
+        }
+      }
+      None => self.datasample_cache.get_key_by_hash(key_hash),
     }
   }
 
-  fn take(
-    &mut self,
-    max_samples: usize,
-    read_condition: ReadCondition,
-  ) -> Result<Vec<Box<dyn IDataSample<D>>>> {
-    let samples = self.take_as_obj(max_samples, read_condition);
-    match samples {
-      Ok(d) => Ok(d.into_iter().map(|p| p.into_idata_sample()).collect()),
-      Err(e) => Err(e),
+  // The instance of a data sample, if it is known without deserializing the sample.
+  // A NoKey topic has only one instance. On a WithKey topic the key hash identifies
+  // the instance, if the writer sent one and the instance has been seen before.
+  fn known_instance(&self, key_hash: u128) -> Option<D::K> {
+    match self.my_topic.kind() {
+      TopicKind::NoKey => self.datasample_cache.instance_map.keys().next().cloned(),
+      TopicKind::WithKey if key_hash != 0 => self.datasample_cache.get_key_by_hash(key_hash),
+      TopicKind::WithKey => None,
     }
   }
 
-  fn read_next_sample(&mut self) -> Result<Option<&dyn IDataSample<D>>> {
-    let mut ds =
-      <DataReader<D, SA> as IDataReader<D, SA>>::read(self, 1, ReadCondition::not_read())?;
-    let val = match ds.pop() {
-      Some(v) => Some(v.as_idata_sample()),
-      None => None,
-    };
-    Ok(val)
+  // Tries to deserialize only the key of a sample whose data could not be deserialized.
+  // With CDR, this works if the key fields come first in the data, so that only the
+  // rest of it is corrupt. The key is accepted only if it matches the key hash the
+  // writer sent.
+  fn recover_key(
+    &self,
+    key_hash: u128,
+    payload: &SerializedPayload,
+    encoding: RepresentationIdentifier,
+  ) -> Option<D::K> {
+    if key_hash == 0 {
+      return None;
+    }
+    let key = DA::key_from_bytes::<D::K>(&payload.value, encoding).ok()?;
+    if key.into_hash_key() == key_hash {
+      debug!("Recovered the key of a sample that failed to deserialize");
+      Some(key)
+    } else {
+      None
+    }
   }
 
-  fn take_next_sample(&mut self) -> Result<Option<Box<dyn IDataSample<D>>>> {
-    let ds = self.take_next_sample()?;
-    Ok(ds.into_iter().map(|p| p.into_idata_sample()).find(|_| true))
+  // Selects at most max_samples samples from the local cache with `select`, and
+  // deserializes the ones still serialized. Samples that fail to deserialize are rejected
+  // and removed from the cache, and then the selection is made again without them.
+  fn select_and_decode<F>(&mut self, max_samples: usize, select: F) -> Vec<(CacheInstant, D::K)>
+  where
+    F: Fn(&DataSampleCache<D>) -> Vec<(CacheInstant, D::K)>,
+  {
+    loop {
+      let mut selected = select(&self.datasample_cache);
+      let left_behind = selected.len() > max_samples;
+      selected.truncate(max_samples);
+      let rejected_status = &mut self.sample_rejected_status;
+      let statistics = &self.statistics;
+      let all_decoded = self.datasample_cache.decode_samples(
+        &selected,
+        |key, payload, encoding, sample_identity| {
+          decode_sample::<D, DA>(
+            rejected_status,
+            statistics,
+            key,
+            payload,
+            encoding,
+            sample_identity,
+          )
+        },
+      );
+      if all_decoded {
+        if left_behind {
+          self.notify_again();
+        }
+        return selected;
+      }
+    }
   }
 
-  fn get_requested_deadline_missed_status(&mut self) -> Result<Option<RequestedDeadlineMissedStatus>> {
-    self.fetch_readers_current_status()?;
-    let value_before_reset = self.current_status.requestedDeadlineMissed.clone();
-    self.reset_local_requested_deadline_status_change();
-    return Ok(value_before_reset);
+  // The Reader sends at most one notification at a time. They are cleared before
+  // looking into DDSCache, so that changes arriving after that leave a notification
+  // pending, and the DataReader wakes up for them.
+  fn clear_notifications(&self) {
+    while let Ok(_) = self.notification_receiver.try_recv() {}
+  }
 
+  // A read or take limited by max_samples left selected samples in the cache. The
+  // notification was cleared already, so the Reader is asked for another one.
+  fn notify_again(&self) {
+    match self
+      .reader_command
+      .try_send(ReaderCommand::NOTIFY_DATA_AVAILABLE)
+    {
+      // Full means that the Reader has commands to handle, and it notifies after them.
+      Ok(()) | Err(mio_channel::TrySendError::Full(_)) => (),
+      Err(e) => debug!(
+        "Cannot notify DataReader {:?} again: {:?}",
+        self.get_guid(),
+        e
+      ),
+    }
   }
-}
-*/
 
-// This is  not part of DDS spec. We implement mio Eventd so that the application can asynchronously
-// poll DataReader(s).
-impl<'a, D, DA> Evented for DataReader<'a, D, DA>
-where
-  D: Keyed + DeserializeOwned,
-  DA: DeserializerAdapter<D>,
-{
-  // We just delegate all the operations to notification_receiver, since it already implements Evented
-  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
-    self
-      .notification_receiver
-      .register(poll, token, interest, opts)
+  // Gets all unseen cache_changes from the TopicCache, and stores the DataSamples (the
+  // actual data and the samplestate) to local container, datasample_cache. Data samples
+  // are deserialized here only if their instance is not known yet, see
+  // select_and_decode.
+  fn fill_local_datasample_cache(&mut self) {
+    let topic_cache_lock = read_lock(&self.dds_cache).get_topic_cache(self.my_topic.get_name());
+    let topic_cache_lock = match topic_cache_lock {
+      Some(lock) => lock,
+      // The topic is not in DDSCache, so there is nothing to take.
+      None => return,
+    };
+    // Only our topic is locked while the changes are deserialized.
+    let topic_cache = read_lock(&topic_cache_lock);
+
+    let cache_changes =
+      topic_cache.get_changes_in_range(&self.latest_instant, &CacheInstant::at(self.clock.now()));
+
+    let cache_changes: Vec<(&CacheInstant, &CacheChange)> = cache_changes
+      .into_iter()
+      .sorted_by(|(a, _), (b, _)| Ord::cmp(a, b))
+      // Changes of our own writers are in the same TopicCache, and so are the copies
+      // other readers of the topic received. Only the copies delivered to this reader
+      // are taken.
+      .filter(|(_, cc)| match cc.received_by {
+        Some(reader_guid) => reader_guid == self.reader_guid,
+        None => cc.writer_guid.guidPrefix != self.get_guid_prefix(),
+      })
+      .collect();
+
+    match cache_changes.last() {
+      Some((last_instant, _)) => self.latest_instant = **last_instant,
+      // No new changes available
+      None => return,
+    };
+
+    for (
+      instant,
+      CacheChange {
+        kind,
+        writer_guid,
+        sequence_number,
+        data_value: payload_opt,
+        key: key_hash,
+        source_timestamp,
+        related_sample_identity,
+        ..
+      },
+    ) in cache_changes
+    {
+      match kind {
+        ChangeKind::NOT_ALIVE_UNREGISTERED => match self.instance_key(*key_hash, payload_opt) {
+          Some(key) => {
+            self
+              .datasample_cache
+              .add_unregister(key, *writer_guid, *instant, *source_timestamp)
+          }
+          None => debug!("Unregister with unknown key hash: {:x?}", key_hash),
+        },
+
+        // The only instance of a NoKey topic cannot be disposed.
+        ChangeKind::NOT_ALIVE_DISPOSED if self.my_topic.kind() == TopicKind::NoKey => {
+          debug!("Ignored dispose from {:?} on a NoKey topic", writer_guid)
+        }
+        ChangeKind::NOT_ALIVE_DISPOSED => match self.instance_key(*key_hash, payload_opt) {
+          Some(key) => {
+            self
+              .datasample_cache
+              .add_sample(Err(key), *writer_guid, *instant, *source_timestamp)
+          }
+          None => warn!("Tried to dispose with unkonwn key hash: {:x?}", key_hash),
+        },
+        ChangeKind::ALIVE => {
+          match payload_opt {
+            Some(serialized_payload) => {
+              let sample_identity = SampleIdentity {
+                writer_guid: *writer_guid,
+                sequence_number: i64::from(*sequence_number),
+              };
+              // what is our data serialization format (representation identifier) ?
+              // Only the DeserializerAdapter knows which ones it can decode.
+              let rep_id = match DA::supported_encodings()
+                .iter()
+                .find(|r| **r as u16 == serialized_payload.representation_identifier)
+              {
+                Some(r) => *r,
+                // cannot use .or_else() because need to "continue" the for-loop
+                None => {
+                  reject_undecodable(
+                    &mut self.sample_rejected_status,
+                    &self.statistics,
+                    Some(sample_identity),
+                    serialized_payload,
+                    &format!(
+                      "unsupported representation id {:?}",
+                      serialized_payload.representation_identifier
+                    ),
+                  );
+                  continue; // skip this sample, as we cannot decode it
+                }
+              };
+
+              // A sample of a known instance is deserialized only if it is accessed.
+              if let Some(key) = self.known_instance(*key_hash) {
+                self.datasample_cache.add_serialized_sample(
+                  key,
+                  serialized_payload.clone(),
+                  rep_id,
+                  *instant,
+                  *source_timestamp,
+                  sample_identity,
+                  *related_sample_identity,
+                );
+                continue;
+              }
+
+              // Otherwise it is deserialized now to find out its instance.
+              let payload = match DA::from_bytes(&serialized_payload.value, rep_id) {
+                Ok(p) => p,
+                // cannot use .or_else() because need to "continue" the for-loop
+                Err(e) => {
+                  reject_undecodable(
+                    &mut self.sample_rejected_status,
+                    &self.statistics,
+                    Some(sample_identity),
+                    serialized_payload,
+                    &e,
+                  );
+                  // A later dispose or unregister by key hash can still find the
+                  // instance, if its key can be recovered.
+                  if let Some(key) = self.recover_key(*key_hash, serialized_payload, rep_id) {
+                    self.datasample_cache.remember_key(key);
+                  }
+                  continue;
+                }
+              };
+              // insert to local cache
+              self.datasample_cache.add_sample_with_identity(
+                payload,
+                *instant,
+                *source_timestamp,
+                sample_identity,
+                *related_sample_identity,
+              )
+            }
+            None => warn!("Got CacheChange kind=ALIVE , but no serialized payload!"),
+          }
+        }
+      }
+    }
+    drop(topic_cache);
+    self.set_cache_progress();
   }
 
-  fn reregister(
+  fn infer_key(
     &self,
-    poll: &Poll,
-    token: Token,
-    interest: Ready,
-    opts: PollOpt,
-  ) -> io::Result<()> {
-    self
-      .notification_receiver
-      .reregister(poll, token, interest, opts)
+    instance_key: Option<<D as Keyed>::K>,
+    this_or_next: SelectByKey,
+  ) -> Option<<D as Keyed>::K> {
+    match instance_key {
+      Some(k) => match this_or_next {
+        SelectByKey::This => Some(k),
+        SelectByKey::Next => self.datasample_cache.get_next_key(&k),
+      },
+      None => self
+        .datasample_cache
+        .instance_map
+        .keys()
+        .next()
+        .map(|k| k.clone()),
+    }
   }
 
-  fn deregister(&self, poll: &Poll) -> io::Result<()> {
-    self.notification_receiver.deregister(poll)
-  }
-}
+  /// Works similarly to read(), but will return only samples from a specific instance.
+  /// The instance is specified by an optional key. In case the key is not specified, the smallest
+  /// (in key order) instance is selected.
+  /// If a key is specified, then the parameter this_or_next specifies whether to access the instance
+  /// with specified key or the following one, in key order.
+  ///
+  /// This should cover DDS DataReader methods read_instance, read_next_instance,
+  /// read_next_instance_w_condition.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// use rustdds::dds::data_types::{ReadCondition,SelectByKey};
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn get_key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  ///
+  /// // Wait for data to arrive...
+  ///
+  /// if let Ok(datas) = data_reader.read_instance(10, ReadCondition::any(), Some(3), SelectByKey::This) {
+  ///   for data in datas.iter() {
+  ///     // do something
+  ///   }
+  /// }
+  /// ```
+  pub fn read_instance(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+    // Select only samples from instance specified by key. In case of None, select the
+    // "smallest" instance as specified by the key type Ord trait.
+    instance_key: Option<<D as Keyed>::K>,
+    // This = Select instance specified by key.
+    // Next = select next instance in the order specified by Ord on keys.
+    this_or_next: SelectByKey,
+  ) -> Result<Vec<DataSample<&D>>> {
+    self.clear_notifications();
+    self.fill_local_datasample_cache();
 
-impl<D, DA> HasQoSPolicy for DataReader<'_, D, DA>
-where
-  D: Keyed + DeserializeOwned,
-  DA: DeserializerAdapter<D>,
-{
-  fn set_qos(&mut self, policy: &QosPolicies) -> Result<()> {
-    // TODO: check liveliness of qos_policy
-    self.qos_policy = policy.clone();
-    Ok(())
+    let key = match self.infer_key(instance_key, this_or_next) {
+      Some(k) => k,
+      None => return Ok(Vec::new()),
+    };
+
+    let selected = self.select_and_decode(max_samples, |dsc| {
+      dsc.select_instance_keys_for_access(key.clone(), read_condition)
+    });
+
+    let result = self.datasample_cache.read_by_keys(&selected);
+
+    Ok(result)
   }
 
-  fn get_qos(&self) -> &QosPolicies {
-    &self.qos_policy
-  }
-}
+  /// Similar to read_instance, but will return owned datasamples
+  /// This should cover DDS DataReader methods take_instance, take_next_instance,
+  /// take_next_instance_w_condition.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// use rustdds::dds::data_types::{ReadCondition,SelectByKey};
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn get_key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  ///
+  /// // Wait for data to arrive...
+  ///
+  /// if let Ok(datas) = data_reader.take_instance(10, ReadCondition::any(), Some(3), SelectByKey::Next) {
+  ///   for data in datas.iter() {
+  ///     // do something
+  ///   }
+  /// }
+  /// ```
+  pub fn take_instance(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+    // Select only samples from instance specified by key. In case of None, select the
+    // "smallest" instance as specified by the key type Ord trait.
+    instance_key: Option<<D as Keyed>::K>,
+    // This = Select instance specified by key.
+    // Next = select next instance in the order specified by Ord on keys.
+    this_or_next: SelectByKey,
+  ) -> Result<Vec<DataSample<D>>> {
+    self.clear_notifications();
+    self.fill_local_datasample_cache();
+
+    let key = match self.infer_key(instance_key, this_or_next) {
+      Some(k) => k,
+      None => return Ok(Vec::new()),
+    };
+
+    let selected = self.select_and_decode(max_samples, |dsc| {
+      dsc.select_instance_keys_for_access(key.clone(), read_condition)
+    });
+
+    let result = self.datasample_cache.take_by_keys(&selected);
+
+    Ok(result)
+  }
+
+  /// Reads the newest sample of each instance that matches `read_condition`, in key
+  /// order. The samples are returned whether they have been read before or not, unless
+  /// `read_condition` says otherwise, and its instance state mask tells if disposed
+  /// instances and instances without writers are included.
+  ///
+  /// Only the newest samples of each instance are looked at, so this stays fast with a
+  /// long history, e.g. when a monitoring application periodically shows the latest value
+  /// of each instance.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// use rustdds::dds::data_types::{InstanceState, ReadCondition};
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn get_key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  ///
+  /// // The latest value of each instance that is still alive
+  /// let alive = ReadCondition::any().with_instance_state(InstanceState::Alive);
+  /// if let Ok(latest) = data_reader.read_latest_per_instance(alive) {
+  ///   for data in latest.iter() {
+  ///     // do something
+  ///   }
+  /// }
+  /// ```
+  pub fn read_latest_per_instance(
+    &mut self,
+    read_condition: ReadCondition,
+  ) -> Result<Vec<DataSample<&D>>> {
+    self.clear_notifications();
+    self.fill_local_datasample_cache();
+
+    let selected = self.select_and_decode(usize::MAX, |dsc| {
+      dsc.select_latest_per_instance_for_access(read_condition)
+    });
+
+    Ok(self.datasample_cache.read_by_keys(&selected))
+  }
+
+  // status queries
+
+  fn reset_local_requested_deadline_status_change(&mut self) {
+    info!(
+      "reset_local_requested_deadline_status_change current {:?}",
+      self.current_status.requestedDeadlineMissed
+    );
+    match self.current_status.requestedDeadlineMissed {
+      Some(_s) => {
+        self
+          .current_status
+          .requestedDeadlineMissed
+          .as_mut()
+          .unwrap()
+          .reset_change();
+      }
+      None => {}
+    }
+  }
+
+  fn fetch_readers_current_status(&mut self) -> Result<()> {
+    //self.TEST_FUNCTION_get_requested_deadline_missed_status();
+    let mut received_requested_deadline_status_change: bool = false;
+    loop {
+      let received_status = self.status_receiver.try_recv();
+      match received_status {
+        Ok(s) => match s {
+          StatusChange::LivelinessLostStatus(status) => {
+            self.current_status.livelinessLost = Some(status);
+          }
+          StatusChange::OfferedDeadlineMissedStatus(status) => {
+            self.current_status.offeredDeadlineMissed = Some(status);
+          }
+          StatusChange::OfferedIncompatibleQosStatus(status) => {
+            self.current_status.offeredIncompatibleQos = Some(status);
+          }
+          StatusChange::RequestedDeadlineMissedStatus(status) => {
+            self.current_status.requestedDeadlineMissed = Some(status);
+            received_requested_deadline_status_change = true;
+          }
+          StatusChange::RequestedIncompatibleQosStatus(status) => {
+            self.current_status.requestedIncompatibleQos = Some(status);
+          }
+          StatusChange::PublicationMatchedStatus(status) => {
+            self.current_status.publicationMatched = Some(status);
+          }
+          StatusChange::SubscriptionMatchedStatus(status) => {
+            self.current_status.subscriptionMatched = Some(status);
+          }
+          StatusChange::SampleLostStatus(status) => {
+            self.current_status.sampleLost = Some(status);
+          }
+          // only sent to DataWriters
+          StatusChange::AcknowledgmentsComplete => (),
+        },
+        Err(e) => {
+          match e {
+            std::sync::mpsc::TryRecvError::Empty => {
+              break;
+            }
+            std::sync::mpsc::TryRecvError::Disconnected => {
+              // return disconnect status!!!
+              return Err(self.removed_error());
+            }
+          }
+        }
+      }
+    }
+    // after looping set command to reset requested deadeline status if needed. Also reset local status.
+    match received_requested_deadline_status_change {
+      true => {
+        self
+          .current_status
+          .requestedDeadlineMissed
+          .unwrap()
+          .reset_change();
+        match self
+          .reader_command
+          .try_send(ReaderCommand::RESET_REQUESTED_DEADLINE_STATUS)
+        {
+          Ok(()) => {
+            return Ok(());
+          }
+          Err(e) => {
+            return Err(self.command_error("reset RequestedDeadlineMissedStatus of", e));
+          }
+        }
+      }
+      false => {
+        return Ok(());
+      }
+    }
+  }
+  /*
+  pub fn register_status_change_notificator(&self, poll: & Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()>{
+    todo!();
+    // let res = poll.register(&self.status_receiver, token, interest, opts);
+
+    //self.TEST_FUNCTION_get_requested_deadline_missed_status();
+    //info!("register datareader status reciever to poll with token {:?}", token );
+    //return res;
+  }
+  */
+
+  // Helper functions
+
+  fn matches_conditions(rcondition: &ReadCondition, dsample: &DataSample<D>) -> bool {
+    if !rcondition
+      .sample_state_mask()
+      .contains(dsample.sample_info().sample_state)
+    {
+      return false;
+    }
+    if !rcondition
+      .view_state_mask()
+      .contains(dsample.sample_info().view_state)
+    {
+      return false;
+    }
+    if !rcondition
+      .instance_state_mask()
+      .contains(dsample.sample_info().instance_state)
+    {
+      return false;
+    }
+    true
+  }
+
+  fn change_kind_to_instance_state(c_k: &ChangeKind) -> InstanceState {
+    match c_k {
+      ChangeKind::ALIVE => InstanceState::Alive,
+      ChangeKind::NOT_ALIVE_DISPOSED => InstanceState::NotAlive_Disposed,
+      // TODO check this..?
+      ChangeKind::NOT_ALIVE_UNREGISTERED => InstanceState::NotAlive_NoWriters,
+    }
+  }
+
+  /// Gets RequestedDeadlineMissedStatus
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// use rustdds::dds::qos::policy::Deadline;
+  /// use rustdds::dds::data_types::Duration;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().deadline(Deadline(Duration::from_millis(1))).build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn get_key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  ///
+  /// // Wait for some deadline to be missed...
+  /// if let Ok(Some(rqdl)) = data_reader.get_requested_deadline_missed_status() {
+  ///   // do something
+  /// }
+  ///
+  /// ```
+  pub fn get_requested_deadline_missed_status(
+    &mut self,
+  ) -> Result<Option<RequestedDeadlineMissedStatus>> {
+    self.fetch_readers_current_status()?;
+    let value_before_reset = self.current_status.requestedDeadlineMissed.clone();
+    self.reset_local_requested_deadline_status_change();
+    return Ok(value_before_reset);
+  }
+
+  /// Gets SampleLostStatus, i.e. how many samples have been detected as lost
+  /// from the sequence numbers of the matched writers. Sequence numbers a
+  /// writer has announced irrelevant with GAP are not counted.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn get_key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  ///
+  /// if let Ok(Some(sls)) = data_reader.get_sample_lost_status() {
+  ///   println!("Lost {} samples", sls.count());
+  /// }
+  /// ```
+  pub fn get_sample_lost_status(&mut self) -> Result<Option<SampleLostStatus>> {
+    self.fetch_readers_current_status()?;
+    let value_before_reset = self.current_status.sampleLost;
+    if let Some(s) = self.current_status.sampleLost.as_mut() {
+      s.reset_change();
+      match self
+        .reader_command
+        .try_send(ReaderCommand::RESET_SAMPLE_LOST_STATUS)
+      {
+        Ok(()) => (),
+        Err(e) => return Err(self.command_error("reset SampleLostStatus of", e)),
+      }
+    }
+    Ok(value_before_reset)
+  }
+
+  /// Gets SampleRejectedStatus. RustDDS rejects samples whose payload cannot be
+  /// deserialized, with reason
+  /// [`DeserializationError`](SampleRejectedReason::DeserializationError).
+  /// Samples of instances already known to this DataReader are deserialized only
+  /// when they are read or taken, so their rejections are counted only then.
+  /// The other samples read or taken at the same time are still returned.
+  ///
+  /// The writer and sequence number of the rejected sample are logged as a
+  /// warning, and with debug logging also its payload in hex.
+  pub fn get_sample_rejected_status(&mut self) -> SampleRejectedStatus {
+    self.fill_local_datasample_cache();
+    let value_before_reset = self.sample_rejected_status;
+    self.sample_rejected_status.reset_change();
+    value_before_reset
+  }
+
+  /// Counts of the RTPS traffic this DataReader has received and sent. See
+  /// [ReaderStatistics](../struct.ReaderStatistics.html).
+  pub fn get_statistics(&self) -> ReaderStatistics {
+    self.statistics.get()
+  }
+
+  /// Sets how quickly a reliable reader answers the heartbeats of matched writers.
+  /// Answering sooner gets lost samples repaired sooner.
+  pub fn set_rtps_config(&self, config: RtpsReaderConfig) -> Result<()> {
+    match self
+      .reader_command
+      .try_send(ReaderCommand::SET_RTPS_CONFIG(config))
+    {
+      Ok(()) => Ok(()),
+      Err(e) => Err(self.command_error("set RTPS config of", e)),
+    }
+  }
+
+  /// Makes matched DataWriters send to a multicast group instead of to this reader
+  /// alone. This pays off when many readers of the topic share the group. Writers
+  /// learn of the group with the next discovery update, and until then send as before.
+  ///
+  /// Fails with `BadParameter` if the group is not a multicast address of an IP
+  /// version the participant uses.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::{DomainParticipant, ReaderMulticast};
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn get_key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  /// let topic = domain_participant.create_topic("video", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  /// data_reader.set_multicast(ReaderMulticast::Auto).unwrap();
+  /// ```
+  pub fn set_multicast(&self, multicast: ReaderMulticast) -> Result<()> {
+    let dp = match self.my_subscriber.get_participant() {
+      Some(dp) => dp,
+      None => {
+        return Err(Error::precondition_not_met(format!(
+          "cannot set multicast of DataReader {:?}, DomainParticipant has been dropped",
+          self.get_guid()
+        )))
+      }
+    };
+    let network = dp.local_network();
+    let group = match multicast {
+      ReaderMulticast::Off => None,
+      ReaderMulticast::Auto => Some(topic_multicast_group(
+        dp.domain_id(),
+        self.my_topic.get_name(),
+        network.ip_families()[0],
+      )),
+      ReaderMulticast::Group(group) => {
+        if !group.is_multicast() || !network.ip_families().contains(&IpFamily::of(&group)) {
+          return Err(Error::bad_parameter(format!(
+            "{} is not a multicast group of {:?}",
+            group,
+            network.ip_families()
+          )));
+        }
+        Some(group)
+      }
+    };
+
+    if let Err(e) = self
+      .reader_command
+      .try_send(ReaderCommand::SET_MULTICAST_GROUP(group))
+    {
+      return Err(self.command_error("set multicast group of", e));
+    }
+
+    let port = get_user_traffic_multicast_port(dp.domain_id());
+    let locators = group
+      .iter()
+      .map(|g| Locator::from(SocketAddr::new(*g, port)))
+      .collect();
+    write_lock(&dp.discovery_db())
+      .update_local_reader_multicast_locators(self.get_guid(), locators);
+    Ok(())
+  }
+
+  /// Names this DataReader for people, e.g. in tools such as RTI Admin Console,
+  /// which otherwise show only its GUID. Remote participants learn of the name with
+  /// the next discovery update. The name does not affect matching.
+  pub fn set_name(&self, name: &str) -> Result<()> {
+    let dp = match self.my_subscriber.get_participant() {
+      Some(dp) => dp,
+      None => {
+        return Err(Error::precondition_not_met(format!(
+          "cannot set name of DataReader {:?}, DomainParticipant has been dropped",
+          self.get_guid()
+        )))
+      }
+    };
+    write_lock(&dp.discovery_db()).update_local_reader_name(self.get_guid(), name);
+    Ok(())
+  }
+
+  /// Gets SubscriptionMatchedStatus, i.e. how many remote DataWriters are
+  /// currently matched to this DataReader.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn get_key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  ///
+  /// if let Ok(sms) = data_reader.get_subscription_matched_status() {
+  ///   if sms.current_count() > 0 {
+  ///     // there are writers to receive data from
+  ///   }
+  /// }
+  /// ```
+  pub fn get_subscription_matched_status(&mut self) -> Result<SubscriptionMatchedStatus> {
+    self.fetch_readers_current_status()?;
+    let value_before_reset = match self.current_status.subscriptionMatched {
+      Some(s) => s,
+      None => return Ok(SubscriptionMatchedStatus::new()),
+    };
+    if let Some(s) = self.current_status.subscriptionMatched.as_mut() {
+      s.reset_change();
+    }
+    match self
+      .reader_command
+      .try_send(ReaderCommand::RESET_SUBSCRIPTION_MATCHED_STATUS)
+    {
+      Ok(()) => Ok(value_before_reset),
+      Err(e) => Err(self.command_error("reset SubscriptionMatchedStatus of", e)),
+    }
+  }
+
+  /// Gets RequestedIncompatibleQosStatus, i.e. how many times a remote DataWriter
+  /// of the same topic was discovered, but could not be matched because it does not
+  /// offer the QoS this DataReader requests.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn get_key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  ///
+  /// if let Ok(status) = data_reader.get_requested_incompatible_qos_status() {
+  ///   if let Some(policy) = status.last_policy_id() {
+  ///     println!("A writer does not offer {:?}", policy);
+  ///   }
+  /// }
+  /// ```
+  pub fn get_requested_incompatible_qos_status(
+    &mut self,
+  ) -> Result<RequestedIncompatibleQosStatus> {
+    self.fetch_readers_current_status()?;
+    let value_before_reset = match self.current_status.requestedIncompatibleQos {
+      Some(s) => s,
+      None => return Ok(RequestedIncompatibleQosStatus::new()),
+    };
+    if let Some(s) = self.current_status.requestedIncompatibleQos.as_mut() {
+      s.reset_change();
+    }
+    match self
+      .reader_command
+      .try_send(ReaderCommand::RESET_REQUESTED_INCOMPATIBLE_QOS_STATUS)
+    {
+      Ok(()) => Ok(value_before_reset),
+      Err(e) => Err(self.command_error("reset RequestedIncompatibleQosStatus of", e)),
+    }
+  }
+
+  /// GUIDs of the DataWriters this DataReader is matched with: the remote ones and
+  /// those of our own participant that publish its topic with compatible QoS.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::dds::WithKeyDataReader as DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn get_key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None, None).unwrap();
+  ///
+  /// for writer_guid in data_reader.get_matched_publications() {
+  ///   if let Some(data) = data_reader.get_matched_publication_data(writer_guid) {
+  ///     // do something
+  ///   }
+  /// }
+  /// ```
+  pub fn get_matched_publications(&self) -> Vec<GUID> {
+    self.matched_writers.get().clone()
+  }
+
+  /// Discovery data of a matched DataWriter, remote or of our own participant. See
+  /// [get_matched_publications](#method.get_matched_publications).
+  pub fn get_matched_publication_data(&self, writer_guid: GUID) -> Option<DiscoveredWriterData> {
+    if !self.matched_writers.get().contains(&writer_guid) {
+      return None;
+    }
+    let dp = self.my_subscriber.get_participant()?;
+    let discovery_db = dp.discovery_db();
+    let db = read_lock(&discovery_db);
+    db.get_external_writer(writer_guid)
+      .or_else(|| db.get_local_topic_writer(writer_guid))
+      .cloned()
+  }
+} // impl
+
+/*
+impl<'a, D: 'static, SA> IDataReader<D, SA> for DataReader<'a, D, SA>
+where
+  D: DeserializeOwned + Keyed,
+  <D as Keyed>::K: Key,
+  SA: DeserializerAdapter<D>,
+{
+  fn read(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+  ) -> Result<Vec<&dyn IDataSample<D>>> {
+    let samples = self.read_as_obj(max_samples, read_condition);
+    match samples {
+      Ok(d) => Ok(d.into_iter().map(|p| p.as_idata_sample()).collect()),
+      Err(e) => Err(e),
+    }
+  }
+
+  fn take(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+  ) -> Result<Vec<Box<dyn IDataSample<D>>>> {
+    let samples = self.take_as_obj(max_samples, read_condition);
+    match samples {
+      Ok(d) => Ok(d.into_iter().map(|p| p.into_idata_sample()).collect()),
+      Err(e) => Err(e),
+    }
+  }
+
+  fn read_next_sample(&mut self) -> Result<Option<&dyn IDataSample<D>>> {
+    let mut ds =
+      <DataReader<D, SA> as IDataReader<D, SA>>::read(self, 1, ReadCondition::not_read())?;
+    let val = match ds.pop() {
+      Some(v) => Some(v.as_idata_sample()),
+      None => None,
+    };
+    Ok(val)
+  }
+
+  fn take_next_sample(&mut self) -> Result<Option<Box<dyn IDataSample<D>>>> {
+    let ds = self.take_next_sample()?;
+    Ok(ds.into_iter().map(|p| p.into_idata_sample()).find(|_| true))
+  }
+
+  fn get_requested_deadline_missed_status(&mut self) -> Result<Option<RequestedDeadlineMissedStatus>> {
+    self.fetch_readers_current_status()?;
+    let value_before_reset = self.current_status.requestedDeadlineMissed.clone();
+    self.reset_local_requested_deadline_status_change();
+    return Ok(value_before_reset);
+
+  }
+}
+*/
+
+// This is  not part of DDS spec. We implement mio Eventd so that the application can asynchronously
+// poll DataReader(s).
+// A burst of samples makes the DataReader readable once. A read or take that leaves
+// samples behind because of max_samples makes it readable again, so edge triggered
+// polling does not strand samples.
+impl<'a, D, DA> Evented for DataReader<'a, D, DA>
+where
+  D: Keyed + DeserializeOwned,
+  DA: DeserializerAdapter<D>,
+{
+  // We just delegate all the operations to notification_receiver, since it already implements Evented
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    self
+      .notification_receiver
+      .register(poll, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    self
+      .notification_receiver
+      .reregister(poll, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    self.notification_receiver.deregister(poll)
+  }
+}
+
+impl<D, DA> HasQoSPolicy for DataReader<'_, D, DA>
+where
+  D: Keyed + DeserializeOwned,
+  DA: DeserializerAdapter<D>,
+{
+  fn set_qos(&mut self, policy: &QosPolicies) -> Result<()> {
+    // TODO: check liveliness of qos_policy
+    self.qos_policy = policy.clone();
+    Ok(())
+  }
+
+  fn get_qos(&self) -> &QosPolicies {
+    &self.qos_policy
+  }
+}
+
+impl<'a, D, DA> Entity for DataReader<'a, D, DA>
+where
+  D: Keyed + DeserializeOwned,
+  DA: DeserializerAdapter<D>,
+{
+  fn as_entity(&self) -> &EntityAttributes {
+    &self.entity_attributes
+  }
+}
+
+// Counts a sample that could not be deserialized, and tells who sent it. With debug
+// logging, the payload is also dumped in hex.
+fn reject_undecodable(
+  rejected_status: &mut SampleRejectedStatus,
+  statistics: &ReaderCounters,
+  sample_identity: Option<SampleIdentity>,
+  payload: &SerializedPayload,
+  reason: &dyn std::fmt::Display,
+) {
+  warn!(
+    "Rejected sample {:?}: failed to deserialize {} bytes: {}",
+    sample_identity,
+    payload.value.len(),
+    reason
+  );
+  if log_enabled!(Level::Debug) {
+    let hex: Vec<String> = payload.value.iter().map(|b| format!("{:02x}", b)).collect();
+    debug!(
+      "Payload of rejected sample {:?}: {}",
+      sample_identity,
+      hex.join(" ")
+    );
+  }
+  rejected_status.increase(SampleRejectedReason::DeserializationError, sample_identity);
+  statistics.deserialization_failed();
+}
+
+// Deserializes a sample that was stored serialized under the instance `key`. A sample
+// that deserializes to another key is rejected like one that does not deserialize.
+fn decode_sample<D, DA>(
+  rejected_status: &mut SampleRejectedStatus,
+  statistics: &ReaderCounters,
+  key: &D::K,
+  payload: &SerializedPayload,
+  encoding: RepresentationIdentifier,
+  sample_identity: Option<SampleIdentity>,
+) -> Option<D>
+where
+  D: DeserializeOwned + Keyed,
+  <D as Keyed>::K: Key,
+  DA: DeserializerAdapter<D>,
+{
+  match DA::from_bytes(&payload.value, encoding) {
+    Ok(d) if d.get_key() == *key => Some(d),
+    Ok(_) => {
+      reject_undecodable(
+        rejected_status,
+        statistics,
+        sample_identity,
+        payload,
+        &"its key does not match its key hash",
+      );
+      None
+    }
+    Err(e) => {
+      reject_undecodable(rejected_status, statistics, sample_identity, payload, &e);
+      None
+    }
+  }
+}
+
+#[cfg(feature = "async")]
+mod async_io {
+  use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+  };
+
+  use futures_core::Stream;
+
+  use super::*;
+
+  impl<'a, D: 'static, DA> DataReader<'a, D, DA>
+  where
+    D: DeserializeOwned + Keyed,
+    <D as Keyed>::K: Key,
+    DA: DeserializerAdapter<D>,
+  {
+    /// Asynchronous version of [take_next_sample](#method.take_next_sample).
+    /// The returned future resolves when there is an unread sample to take.
+    ///
+    /// The task is woken up by the DomainParticipant event loop thread, so any
+    /// executor will do. Requires feature `async`.
+    pub fn async_take_next(&mut self) -> TakeNextFuture<'_, 'a, D, DA> {
+      TakeNextFuture { reader: self }
+    }
+
+    /// A Stream of unread samples, taken from this DataReader as they arrive.
+    /// The stream ends if the DataReader can no longer receive data.
+    ///
+    /// Requires feature `async`.
+    pub fn async_sample_stream(&mut self) -> SampleStream<'_, 'a, D, DA> {
+      SampleStream { reader: self }
+    }
+
+    pub(crate) fn poll_take_next(&mut self, cx: &mut Context<'_>) -> Poll<Result<DataSample<D>>> {
+      // Register before looking for data. Then any data arriving after the check
+      // wakes us up.
+      let registered = self.data_waker.register(cx.waker());
+      match self.take_next_sample() {
+        Ok(Some(sample)) => Poll::Ready(Ok(sample)),
+        Ok(None) if registered => Poll::Pending,
+        Ok(None) => Poll::Ready(Err(self.removed_error())),
+        Err(e) => Poll::Ready(Err(e)),
+      }
+    }
+  }
+
+  /// Future returned by [DataReader::async_take_next](struct.DataReader.html#method.async_take_next).
+  pub struct TakeNextFuture<'r, 'a, D, DA>
+  where
+    D: Keyed + DeserializeOwned,
+    DA: DeserializerAdapter<D>,
+  {
+    reader: &'r mut DataReader<'a, D, DA>,
+  }
+
+  impl<'r, 'a, D: 'static, DA> Future for TakeNextFuture<'r, 'a, D, DA>
+  where
+    D: DeserializeOwned + Keyed,
+    <D as Keyed>::K: Key,
+    DA: DeserializerAdapter<D>,
+  {
+    type Output = Result<DataSample<D>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+      self.get_mut().reader.poll_take_next(cx)
+    }
+  }
+
+  /// Stream returned by [DataReader::async_sample_stream](struct.DataReader.html#method.async_sample_stream).
+  pub struct SampleStream<'r, 'a, D, DA>
+  where
+    D: Keyed + DeserializeOwned,
+    DA: DeserializerAdapter<D>,
+  {
+    reader: &'r mut DataReader<'a, D, DA>,
+  }
+
+  impl<'r, 'a, D: 'static, DA> Stream for SampleStream<'r, 'a, D, DA>
+  where
+    D: DeserializeOwned + Keyed,
+    <D as Keyed>::K: Key,
+    DA: DeserializerAdapter<D>,
+  {
+    type Item = DataSample<D>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+      match self.get_mut().reader.poll_take_next(cx) {
+        Poll::Ready(Ok(sample)) => Poll::Ready(Some(sample)),
+        Poll::Ready(Err(_)) => Poll::Ready(None),
+        Poll::Pending => Poll::Pending,
+      }
+    }
+  }
+}
+
+#[cfg(feature = "async")]
+pub use async_io::{SampleStream, TakeNextFuture};
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dds::{participant::DomainParticipant, topic::TopicKind};
+  use crate::test::random_data::*;
+  use crate::dds::traits::key::Keyed;
+  use mio_extras::channel as mio_channel;
+  use log::info;
+  use crate::dds::reader::Reader;
+  use crate::messages::submessages::data::Data;
+  use crate::dds::message_receiver::*;
+  use crate::structure::guid::GuidPrefix;
+  use crate::structure::sequence_number::SequenceNumber;
+  use crate::serialization::{cdr_deserializer::CDRDeserializerAdapter, cdr_serializer::to_bytes};
+  use byteorder::LittleEndian;
+  use crate::messages::submessages::submessage_elements::serialized_payload::SerializedPayload;
+  use std::{
+    thread,
+    time::{self},
+  };
+  use mio::{Events};
+  #[test]
+  fn dr_get_samples_from_ddschache() {
+    let dp = DomainParticipant::new(0).unwrap();
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll);
+
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic("dr", "drtest?", &qos, TopicKind::WithKey)
+      .unwrap();
+
+    let (send, _rec) = mio_channel::sync_channel::<()>(10);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
+
+    let reader_id = EntityId::default();
+    let datareader_id = EntityId::default();
+    let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), reader_id);
+
+    let mut new_reader = Reader::new(
+      reader_guid,
+      send,
+      status_sender,
+      dp.get_dds_cache(),
+      topic.get_name().to_string(),
+      reader_command_receiver,
+    );
+
+    let mut matching_datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &topic,
+        Some(datareader_id),
+        None,
+      )
+      .unwrap();
+
+    let random_data = RandomData {
+      a: 1,
+      b: "somedata".to_string(),
+    };
+    let data_key = random_data.get_key();
+
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+
+    new_reader.matched_writer_add(
+      writer_guid.clone(),
+      EntityId::ENTITYID_UNKNOWN,
+      mr_state.unicast_reply_locator_list.clone(),
+      mr_state.multicast_reply_locator_list.clone(),
+    );
+
+    let mut data = Data::default();
+    data.reader_id = EntityId::createCustomEntityID([1, 2, 3], 111);
+    data.writer_id = writer_guid.entityId;
+    data.writer_sn = SequenceNumber::from(1);
+
+    data.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, LittleEndian>(&random_data)
+        .unwrap()
+        .into(),
+    });
+    new_reader.handle_data_msg(data, mr_state.clone());
+
+    matching_datareader.fill_local_datasample_cache();
+    let deserialized_random_data = matching_datareader.read(1, ReadCondition::any()).unwrap()[0]
+      .value()
+      .unwrap()
+      .clone();
+
+    assert_eq!(deserialized_random_data, random_data);
+
+    // Test getting of next samples.
+    let random_data2 = RandomData {
+      a: 1,
+      b: "somedata number 2".to_string(),
+    };
+    let mut data2 = Data::default();
+    data2.reader_id = EntityId::createCustomEntityID([1, 2, 3], 111);
+    data2.writer_id = writer_guid.entityId;
+    data2.writer_sn = SequenceNumber::from(2);
+
+    data2.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, LittleEndian>(&random_data2)
+        .unwrap()
+        .into(),
+    });
+
+    let random_data3 = RandomData {
+      a: 1,
+      b: "third somedata".to_string(),
+    };
+    let mut data3 = Data::default();
+    data3.reader_id = EntityId::createCustomEntityID([1, 2, 3], 111);
+    data3.writer_id = writer_guid.entityId;
+    data3.writer_sn = SequenceNumber::from(3);
+
+    data3.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, LittleEndian>(&random_data3)
+        .unwrap()
+        .into(),
+    });
+
+    new_reader.handle_data_msg(data2, mr_state.clone());
+    new_reader.handle_data_msg(data3, mr_state);
+
+    matching_datareader.fill_local_datasample_cache();
+    let random_data_vec = matching_datareader
+      .read_instance(100, ReadCondition::any(), Some(data_key), SelectByKey::This)
+      .unwrap();
+    assert_eq!(random_data_vec.len(), 3);
+  }
+
+  #[test]
+  fn dr_source_and_reception_timestamps() {
+    let dp = DomainParticipant::new(0).unwrap();
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll);
+
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic("dr timestamps", "RandomData", &qos, TopicKind::WithKey)
+      .unwrap();
+
+    let (send, _rec) = mio_channel::sync_channel::<()>(10);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
+    let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), EntityId::default());
+    let mut new_reader = Reader::new(
+      reader_guid,
+      send,
+      status_sender,
+      dp.get_dds_cache(),
+      topic.get_name().to_string(),
+      reader_command_receiver,
+    );
+    let mut datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &topic,
+        Some(EntityId::default()),
+        None,
+      )
+      .unwrap();
+
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    new_reader.matched_writer_add(writer_guid, EntityId::ENTITYID_UNKNOWN, vec![], vec![]);
+    let data = |a: i64, sn: i64| {
+      let mut data = Data::default();
+      data.writer_id = writer_guid.entityId;
+      data.writer_sn = SequenceNumber::from(sn);
+      data.serialized_payload = Some(SerializedPayload {
+        representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+        representation_options: [0, 0],
+        value: to_bytes::<RandomData, LittleEndian>(&RandomData {
+          a,
+          b: "timestamps".to_string(),
+        })
+        .unwrap()
+        .into(),
+      });
+      data
+    };
+
+    // with an InfoTimestamp, and without one
+    let source_timestamp =
+      Timestamp::now() - crate::structure::duration::Duration::from_millis(100);
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+    mr_state.timestamp = Some(source_timestamp);
+    let before = Timestamp::now();
+    new_reader.handle_data_msg(data(1, 1), mr_state.clone());
+    mr_state.timestamp = None;
+    new_reader.handle_data_msg(data(2, 2), mr_state);
+    let after = Timestamp::now();
+
+    datareader.fill_local_datasample_cache();
+    let samples = datareader.read(10, ReadCondition::any()).unwrap();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0].source_timestamp(), source_timestamp);
+    assert_eq!(samples[1].source_timestamp(), Timestamp::TIME_INVALID);
+    for sample in samples {
+      assert!(before <= sample.reception_timestamp());
+      assert!(sample.reception_timestamp() <= after);
+    }
+  }
+
+  #[test]
+  fn dr_read_and_take() {
+    let dp = DomainParticipant::new(0).unwrap();
+
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll); // Just for testing
+
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic("dr read", "read fn test?", &qos, TopicKind::WithKey)
+      .unwrap();
+
+    let (send, _rec) = mio_channel::sync_channel::<()>(10);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
+
+    let default_id = EntityId::default();
+    let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), default_id);
+
+    let mut reader = Reader::new(
+      reader_guid,
+      send,
+      status_sender,
+      dp.get_dds_cache(),
+      topic.get_name().to_string(),
+      reader_command_receiver,
+    );
+
+    let mut datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &topic,
+        Some(default_id),
+        None,
+      )
+      .unwrap();
+
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+    reader.matched_writer_add(
+      writer_guid.clone(),
+      EntityId::ENTITYID_UNKNOWN,
+      mr_state.unicast_reply_locator_list.clone(),
+      mr_state.multicast_reply_locator_list.clone(),
+    );
+
+    // Reader and datareader ready, test with data
+    let test_data = RandomData {
+      a: 10,
+      b: ":DDD".to_string(),
+    };
+
+    let test_data2 = RandomData {
+      a: 11,
+      b: ":)))".to_string(),
+    };
+
+    let mut data_msg = Data::default();
+    data_msg.reader_id = reader.get_entity_id();
+    data_msg.writer_id = writer_guid.entityId;
+    data_msg.writer_sn = SequenceNumber::from(1);
+
+    data_msg.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, LittleEndian>(&test_data)
+        .unwrap()
+        .into(),
+    });
+
+    let mut data_msg2 = Data::default();
+    data_msg2.reader_id = reader.get_entity_id();
+    data_msg2.writer_id = writer_guid.entityId;
+    data_msg2.writer_sn = SequenceNumber::from(2);
+
+    data_msg2.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, LittleEndian>(&test_data2)
+        .unwrap()
+        .into(),
+    });
+    reader.handle_data_msg(data_msg, mr_state.clone());
+    reader.handle_data_msg(data_msg2, mr_state.clone());
+
+    // Read the same sample two times.
+    {
+      let result_vec = datareader.read(100, ReadCondition::any()).unwrap();
+      let d = result_vec[0].value().unwrap();
+      assert_eq!(&test_data, d);
+    }
+    {
+      let result_vec2 = datareader.read(100, ReadCondition::any()).unwrap();
+      let d2 = result_vec2[1].value().unwrap();
+      assert_eq!(&test_data2, d2);
+    }
+    {
+      let result_vec3 = datareader.read(100, ReadCondition::any()).unwrap();
+      let d3 = result_vec3[0].value().unwrap();
+      assert_eq!(&test_data, d3);
+    }
+
+    // Take
+    let mut result_vec = datareader.take(100, ReadCondition::any()).unwrap();
+    let result_vec2 = datareader.take(100, ReadCondition::any());
+
+    let d2 = result_vec.pop().unwrap();
+    let d2 = d2.value().as_ref().unwrap().clone();
+    let d1 = result_vec.pop().unwrap();
+    let d1 = d1.value().as_ref().unwrap().clone();
+    assert_eq!(test_data2, d2);
+    assert_eq!(test_data, d1);
+    assert!(result_vec2.is_ok());
+    assert_eq!(result_vec2.unwrap().len(), 0);
+
+    //datareader.
+
+    // Read and take tests with instant
+
+    let data_key1 = RandomData {
+      a: 1,
+      b: ":D".to_string(),
+    };
+    let data_key2_1 = RandomData {
+      a: 2,
+      b: ":(".to_string(),
+    };
+    let data_key2_2 = RandomData {
+      a: 2,
+      b: ":)".to_string(),
+    };
+    let data_key2_3 = RandomData {
+      a: 2,
+      b: "xD".to_string(),
+    };
+
+    let key1 = data_key1.get_key();
+    let key2 = data_key2_1.get_key();
+
+    assert!(data_key2_1.get_key() == data_key2_2.get_key());
+    assert!(data_key2_3.get_key() == key2);
+
+    let mut data_msg = Data::default();
+    data_msg.reader_id = reader.get_entity_id();
+    data_msg.writer_id = writer_guid.entityId;
+    data_msg.writer_sn = SequenceNumber::from(3);
+
+    data_msg.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, LittleEndian>(&data_key1)
+        .unwrap()
+        .into(),
+    });
+    let mut data_msg2 = Data::default();
+    data_msg2.reader_id = reader.get_entity_id();
+    data_msg2.writer_id = writer_guid.entityId;
+    data_msg2.writer_sn = SequenceNumber::from(4);
+
+    data_msg2.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, LittleEndian>(&data_key2_1)
+        .unwrap()
+        .into(),
+    });
+    let mut data_msg3 = Data::default();
+    data_msg3.reader_id = reader.get_entity_id();
+    data_msg3.writer_id = writer_guid.entityId;
+    data_msg3.writer_sn = SequenceNumber::from(5);
+
+    data_msg3.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, LittleEndian>(&data_key2_2)
+        .unwrap()
+        .into(),
+    });
+    let mut data_msg4 = Data::default();
+    data_msg4.reader_id = reader.get_entity_id();
+    data_msg4.writer_id = writer_guid.entityId;
+    data_msg4.writer_sn = SequenceNumber::from(6);
+
+    data_msg4.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, LittleEndian>(&data_key2_3)
+        .unwrap()
+        .into(),
+    });
+    reader.handle_data_msg(data_msg, mr_state.clone());
+    reader.handle_data_msg(data_msg2, mr_state.clone());
+    reader.handle_data_msg(data_msg3, mr_state.clone());
+    reader.handle_data_msg(data_msg4, mr_state.clone());
+
+    info!("calling read with key 1 and this");
+    let results =
+      datareader.read_instance(100, ReadCondition::any(), Some(key1), SelectByKey::This);
+    assert_eq!(data_key1, results.unwrap()[0].value().unwrap().clone());
+
+    info!("calling read with None and this");
+    // Takes the samllest key, 1 in this case.
+    let results = datareader.read_instance(100, ReadCondition::any(), None, SelectByKey::This);
+    assert_eq!(data_key1, results.unwrap()[0].value().unwrap().clone());
+
+    info!("calling read with key 1 and next");
+    let results =
+      datareader.read_instance(100, ReadCondition::any(), Some(key1), SelectByKey::Next);
+    assert_eq!(results.as_ref().unwrap().len(), 3);
+    assert_eq!(data_key2_2, results.unwrap()[1].value().unwrap().clone());
+
+    info!("calling take with key 2 and this");
+    let results =
+      datareader.take_instance(100, ReadCondition::any(), Some(key2), SelectByKey::This);
+    assert_eq!(results.as_ref().unwrap().len(), 3);
+    let mut vec = results.unwrap();
+    let d3 = vec.pop().unwrap();
+    let d3 = d3.into_value().unwrap();
+    let d2 = vec.pop().unwrap();
+    let d2 = d2.into_value().unwrap();
+    let d1 = vec.pop().unwrap();
+    let d1 = d1.into_value().unwrap();
+    assert_eq!(data_key2_3, d3);
+    assert_eq!(data_key2_2, d2);
+    assert_eq!(data_key2_1, d1);
+
+    info!("calling take with key 2 and this");
+    let results =
+      datareader.take_instance(100, ReadCondition::any(), Some(key2), SelectByKey::This);
+    assert!(results.is_ok());
+    assert!(results.unwrap().is_empty());
+  }
+
+  #[test]
+  fn dr_wake_up() {
+    let dp = DomainParticipant::new(0).unwrap();
+
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll); // Just for testing
+
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic("wakeup", "Wake up!", &qos, TopicKind::WithKey)
+      .unwrap();
+
+    let (send, rec) = mio_channel::sync_channel::<()>(10);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
+
+    let default_id = EntityId::default();
+    let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), default_id);
+
+    let mut reader = Reader::new(
+      reader_guid,
+      send,
+      status_sender,
+      dp.get_dds_cache(),
+      topic.get_name().to_string(),
+      reader_command_receiver,
+    );
+
+    let mut datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &topic,
+        Some(default_id),
+        None,
+      )
+      .unwrap();
+    datareader.notification_receiver = rec;
+
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+    reader.matched_writer_add(
+      writer_guid.clone(),
+      EntityId::ENTITYID_UNKNOWN,
+      mr_state.unicast_reply_locator_list.clone(),
+      mr_state.multicast_reply_locator_list.clone(),
+    );
+
+    let test_data1 = RandomData {
+      a: 1,
+      b: "Testing 1".to_string(),
+    };
+
+    let test_data2 = RandomData {
+      a: 2,
+      b: "Testing 2".to_string(),
+    };
+
+    let test_data3 = RandomData {
+      a: 2,
+      b: "Testing 3".to_string(),
+    };
+
+    let mut data_msg = Data::default();
+    data_msg.reader_id = reader.get_entity_id();
+    data_msg.writer_id = writer_guid.entityId;
+    data_msg.writer_sn = SequenceNumber::from(1);
+
+    data_msg.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, byteorder::LittleEndian>(&test_data1)
+        .unwrap()
+        .into(),
+    });
+
+    let mut data_msg2 = Data::default();
+    data_msg2.reader_id = reader.get_entity_id();
+    data_msg2.writer_id = writer_guid.entityId;
+    data_msg2.writer_sn = SequenceNumber::from(2);
+
+    data_msg2.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, byteorder::LittleEndian>(&test_data2)
+        .unwrap()
+        .into(),
+    });
+
+    let mut data_msg3 = Data::default();
+    data_msg3.reader_id = reader.get_entity_id();
+    data_msg3.writer_id = writer_guid.entityId;
+    data_msg3.writer_sn = SequenceNumber::from(3);
+
+    data_msg3.serialized_payload = Some(SerializedPayload {
+      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+      representation_options: [0, 0],
+      value: to_bytes::<RandomData, byteorder::LittleEndian>(&test_data3)
+        .unwrap()
+        .into(),
+    });
+
+    let handle = std::thread::spawn(move || {
+      reader.handle_data_msg(data_msg, mr_state.clone());
+      thread::sleep(time::Duration::from_millis(100));
+      info!("I'll send the second now..");
+      reader.handle_data_msg(data_msg2, mr_state.clone());
+      thread::sleep(time::Duration::from_millis(100));
+      info!("I'll send the third now..");
+      reader.handle_data_msg(data_msg3, mr_state.clone());
+    });
+
+    let poll = Poll::new().unwrap();
+    poll
+      .register(&datareader, Token(100), Ready::readable(), PollOpt::edge())
+      .unwrap();
+
+    let mut count_to_stop = 0;
+    'l: loop {
+      let mut events = Events::with_capacity(1024);
+      info!("Going to poll");
+      poll.poll(&mut events, None).unwrap();
+
+      for event in events.into_iter() {
+        info!("Handling events");
+        if event.token() == Token(100) {
+          let data = datareader.take(100, ReadCondition::any());
+          let len = data.as_ref().unwrap().len();
+          info!("There were {} samples available.", len);
+          info!("Their strings:");
+          for d in data.unwrap().into_iter() {
+            // Remove one notification for each data
+            info!("{}", d.value().as_ref().unwrap().b);
+          }
+          count_to_stop += len;
+        }
+        if count_to_stop >= 3 {
+          info!("I'll stop now with count {}", count_to_stop);
+          break 'l;
+        }
+      } // for
+    } // loop
+
+    handle.join().unwrap();
+    assert_eq!(count_to_stop, 3);
+  }
+
+  #[test]
+  fn dr_take_next_sample_timeout() {
+    let dp = DomainParticipant::new(0).unwrap();
+
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll);
+
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic("timeout", "Timeout", &qos, TopicKind::WithKey)
+      .unwrap();
+
+    let (send, rec) = mio_channel::sync_channel::<()>(10);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
+
+    let default_id = EntityId::default();
+    let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), default_id);
+
+    let mut reader = Reader::new(
+      reader_guid,
+      send,
+      status_sender,
+      dp.get_dds_cache(),
+      topic.get_name().to_string(),
+      reader_command_receiver,
+    );
+
+    let mut datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &topic,
+        Some(default_id),
+        None,
+      )
+      .unwrap();
+    datareader.notification_receiver = rec;
+
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+    reader.matched_writer_add(
+      writer_guid.clone(),
+      EntityId::ENTITYID_UNKNOWN,
+      mr_state.unicast_reply_locator_list.clone(),
+      mr_state.multicast_reply_locator_list.clone(),
+    );
+
+    let make_data_msg = |a: i64, sn: i64| {
+      let mut data_msg = Data::default();
+      data_msg.reader_id = reader.get_entity_id();
+      data_msg.writer_id = writer_guid.entityId;
+      data_msg.writer_sn = SequenceNumber::from(sn);
+      data_msg.serialized_payload = Some(SerializedPayload {
+        representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+        representation_options: [0, 0],
+        value: to_bytes::<RandomData, byteorder::LittleEndian>(&RandomData {
+          a,
+          b: "Timeout".to_string(),
+        })
+        .unwrap()
+        .into(),
+      });
+      data_msg
+    };
+    let data_msg1 = make_data_msg(1, 1);
+    let data_msg2 = make_data_msg(2, 2);
+
+    // nothing available
+    let start = time::Instant::now();
+    let res = datareader.take_next_sample_timeout(Duration::from_millis(50));
+    assert!(matches!(res, Ok(None)));
+    assert!(start.elapsed() >= time::Duration::from_millis(50));
 
-impl<'a, D, DA> Entity for DataReader<'a, D, DA>
-where
-  D: Keyed + DeserializeOwned,
-  DA: DeserializerAdapter<D>,
-{
-  fn as_entity(&self) -> &EntityAttributes {
-    &self.entity_attributes
+    let (sent_second, second_sent) = std::sync::mpsc::channel();
+    let handle = std::thread::spawn(move || {
+      thread::sleep(time::Duration::from_millis(100));
+      reader.handle_data_msg(data_msg1, mr_state.clone());
+      reader.handle_data_msg(data_msg2, mr_state.clone());
+      sent_second.send(()).unwrap();
+      // dropping the Reader disconnects the notification channel
+    });
+
+    // wakes up on arrival
+    let start = time::Instant::now();
+    let sample = datareader
+      .take_next_sample_timeout(Duration::from_secs(5))
+      .unwrap()
+      .unwrap();
+    assert!(start.elapsed() < time::Duration::from_secs(5));
+    assert_eq!(sample.value().as_ref().unwrap().a, 1);
+
+    // data already arrived before the call returns immediately
+    second_sent.recv().unwrap();
+    handle.join().unwrap();
+    let start = time::Instant::now();
+    let sample = datareader
+      .take_next_sample_timeout(Duration::from_secs(5))
+      .unwrap()
+      .unwrap();
+    assert!(start.elapsed() < time::Duration::from_secs(5));
+    assert_eq!(sample.value().as_ref().unwrap().a, 2);
+
+    // Reader is gone, so there is nothing to wait for
+    let res = datareader.take_next_sample_timeout(Duration::from_secs(5));
+    assert!(matches!(res, Err(Error::PreconditionNotMet { .. })));
   }
-}
 
-#[cfg(test)]
-mod tests {
-  use super::*;
-  use crate::dds::{participant::DomainParticipant, topic::TopicKind};
-  use crate::test::random_data::*;
-  use crate::dds::traits::key::Keyed;
-  use mio_extras::channel as mio_channel;
-  use log::info;
-  use crate::dds::reader::Reader;
-  use crate::messages::submessages::data::Data;
-  use crate::dds::message_receiver::*;
-  use crate::structure::guid::GuidPrefix;
-  use crate::structure::sequence_number::SequenceNumber;
-  use crate::serialization::{cdr_deserializer::CDRDeserializerAdapter, cdr_serializer::to_bytes};
-  use byteorder::LittleEndian;
-  use crate::messages::submessages::submessage_elements::serialized_payload::SerializedPayload;
-  use std::{
-    thread,
-    time::{self},
-  };
-  use mio::{Events};
   #[test]
-  fn dr_get_samples_from_ddschache() {
-    let dp = DomainParticipant::new(0);
+  fn dr_rejects_malformed_samples() {
+    let dp = DomainParticipant::new(0).unwrap();
     let mut qos = QosPolicies::qos_none();
     qos.history = Some(policy::History::KeepAll);
 
     let sub = dp.create_subscriber(&qos).unwrap();
     let topic = dp
-      .create_topic("dr", "drtest?", &qos, TopicKind::WithKey)
+      .create_topic("dr malformed", "malformed?", &qos, TopicKind::WithKey)
       .unwrap();
 
     let (send, _rec) = mio_channel::sync_channel::<()>(10);
@@ -1223,11 +3116,9 @@ mod tests {
     let (_reader_commander, reader_command_receiver) =
       mio_extras::channel::sync_channel::<ReaderCommand>(100);
 
-    let reader_id = EntityId::default();
-    let datareader_id = EntityId::default();
-    let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), reader_id);
-
-    let mut new_reader = Reader::new(
+    let default_id = EntityId::default();
+    let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), default_id);
+    let mut reader = Reader::new(
       reader_guid,
       send,
       status_sender,
@@ -1236,105 +3127,174 @@ mod tests {
       reader_command_receiver,
     );
 
-    let mut matching_datareader = sub
+    let mut datareader = sub
       .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
         &topic,
-        Some(datareader_id),
+        Some(default_id),
         None,
       )
       .unwrap();
 
-    let random_data = RandomData {
-      a: 1,
-      b: "somedata".to_string(),
-    };
-    let data_key = random_data.get_key();
-
     let writer_guid = GUID {
       guidPrefix: GuidPrefix::new(vec![1; 12]),
       entityId: EntityId::createCustomEntityID([1; 3], 1),
     };
     let mut mr_state = MessageReceiverState::default();
     mr_state.source_guid_prefix = writer_guid.guidPrefix;
-
-    new_reader.matched_writer_add(
+    reader.matched_writer_add(
       writer_guid.clone(),
       EntityId::ENTITYID_UNKNOWN,
       mr_state.unicast_reply_locator_list.clone(),
       mr_state.multicast_reply_locator_list.clone(),
     );
 
-    let mut data = Data::default();
-    data.reader_id = EntityId::createCustomEntityID([1, 2, 3], 111);
-    data.writer_id = writer_guid.entityId;
-    data.writer_sn = SequenceNumber::from(0);
+    let good_bytes = to_bytes::<RandomData, LittleEndian>(&RandomData {
+      a: 1,
+      b: "malformed".to_string(),
+    })
+    .unwrap();
+    let make_data_msg = |value: Vec<u8>, sn: i64| {
+      let mut data_msg = Data::default();
+      data_msg.reader_id = reader.get_entity_id();
+      data_msg.writer_id = writer_guid.entityId;
+      data_msg.writer_sn = SequenceNumber::from(sn);
+      data_msg.serialized_payload = Some(SerializedPayload {
+        representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+        representation_options: [0, 0],
+        value: value.into(),
+      });
+      data_msg
+    };
+    // truncated in the middle of the string length
+    let truncated = make_data_msg(good_bytes[..10].to_vec(), 1);
+    let good = make_data_msg(good_bytes.clone(), 2);
+    reader.handle_data_msg(truncated, mr_state.clone());
+    reader.handle_data_msg(good, mr_state);
+
+    let status = datareader.get_sample_rejected_status();
+    assert_eq!(status.count(), 1);
+    assert_eq!(status.count_change(), 1);
+    assert_eq!(
+      status.sample_rejected_reason(),
+      Some(SampleRejectedReason::DeserializationError)
+    );
+    assert_eq!(
+      status.last_rejected_sample(),
+      Some(SampleIdentity {
+        writer_guid,
+        sequence_number: 1
+      })
+    );
+    assert_eq!(datareader.get_statistics().deserialization_failures, 1);
+    let status = datareader.get_sample_rejected_status();
+    assert_eq!(status.count(), 1);
+    assert_eq!(status.count_change(), 0);
+
+    // the well-formed sample is still delivered
+    let samples = datareader.take(10, ReadCondition::any()).unwrap();
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].value().as_ref().unwrap().a, 1);
+  }
 
-    data.serialized_payload = Some(SerializedPayload {
-      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
-      representation_options: [0, 0],
-      value: to_bytes::<RandomData, LittleEndian>(&random_data).unwrap(),
-    });
-    new_reader.handle_data_msg(data, mr_state.clone());
+  #[test]
+  fn dr_rejects_unsupported_representations() {
+    let dp = DomainParticipant::new(0).unwrap();
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll);
 
-    matching_datareader.fill_local_datasample_cache();
-    let deserialized_random_data = matching_datareader.read(1, ReadCondition::any()).unwrap()[0]
-      .value()
-      .unwrap()
-      .clone();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic("dr representations", "json?", &qos, TopicKind::WithKey)
+      .unwrap();
 
-    assert_eq!(deserialized_random_data, random_data);
+    let (send, _rec) = mio_channel::sync_channel::<()>(10);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
 
-    // Test getting of next samples.
-    let random_data2 = RandomData {
-      a: 1,
-      b: "somedata number 2".to_string(),
-    };
-    let mut data2 = Data::default();
-    data2.reader_id = EntityId::createCustomEntityID([1, 2, 3], 111);
-    data2.writer_id = writer_guid.entityId;
-    data2.writer_sn = SequenceNumber::from(1);
+    let default_id = EntityId::default();
+    let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), default_id);
+    let mut reader = Reader::new(
+      reader_guid,
+      send,
+      status_sender,
+      dp.get_dds_cache(),
+      topic.get_name().to_string(),
+      reader_command_receiver,
+    );
 
-    data2.serialized_payload = Some(SerializedPayload {
-      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
-      representation_options: [0, 0],
-      value: to_bytes::<RandomData, LittleEndian>(&random_data2).unwrap(),
-    });
+    let mut datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &topic,
+        Some(default_id),
+        None,
+      )
+      .unwrap();
 
-    let random_data3 = RandomData {
-      a: 1,
-      b: "third somedata".to_string(),
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
     };
-    let mut data3 = Data::default();
-    data3.reader_id = EntityId::createCustomEntityID([1, 2, 3], 111);
-    data3.writer_id = writer_guid.entityId;
-    data3.writer_sn = SequenceNumber::from(2);
-
-    data3.serialized_payload = Some(SerializedPayload {
-      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
-      representation_options: [0, 0],
-      value: to_bytes::<RandomData, LittleEndian>(&random_data3).unwrap(),
-    });
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+    reader.matched_writer_add(
+      writer_guid.clone(),
+      EntityId::ENTITYID_UNKNOWN,
+      mr_state.unicast_reply_locator_list.clone(),
+      mr_state.multicast_reply_locator_list.clone(),
+    );
 
-    new_reader.handle_data_msg(data2, mr_state.clone());
-    new_reader.handle_data_msg(data3, mr_state);
+    let make_data_msg = |representation_identifier: u16, value: Vec<u8>, sn: i64| {
+      let mut data_msg = Data::default();
+      data_msg.reader_id = reader.get_entity_id();
+      data_msg.writer_id = writer_guid.entityId;
+      data_msg.writer_sn = SequenceNumber::from(sn);
+      data_msg.serialized_payload = Some(SerializedPayload {
+        representation_identifier,
+        representation_options: [0, 0],
+        value: value.into(),
+      });
+      data_msg
+    };
+    let cdr_data = RandomData {
+      a: 2,
+      b: "cdr".to_string(),
+    };
+    let json_bytes = br#"{"a":1,"b":"json"}"#.to_vec();
+    let cdr_bytes = to_bytes::<RandomData, LittleEndian>(&cdr_data).unwrap();
+    let json = make_data_msg(RepresentationIdentifier::JSON as u16, json_bytes, 1);
+    let unknown = make_data_msg(0x4242, cdr_bytes.clone(), 2);
+    let cdr = make_data_msg(RepresentationIdentifier::CDR_LE as u16, cdr_bytes, 3);
+    reader.handle_data_msg(json, mr_state.clone());
+    reader.handle_data_msg(unknown, mr_state.clone());
+    reader.handle_data_msg(cdr, mr_state);
+
+    let status = datareader.get_sample_rejected_status();
+    assert_eq!(status.count(), 2);
+    assert_eq!(
+      status.sample_rejected_reason(),
+      Some(SampleRejectedReason::DeserializationError)
+    );
 
-    matching_datareader.fill_local_datasample_cache();
-    let random_data_vec = matching_datareader
-      .read_instance(100, ReadCondition::any(), Some(data_key), SelectByKey::This)
-      .unwrap();
-    assert_eq!(random_data_vec.len(), 3);
+    let samples = datareader.take(10, ReadCondition::any()).unwrap();
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].value().as_ref().unwrap(), &cdr_data);
   }
 
   #[test]
-  fn dr_read_and_take() {
-    let dp = DomainParticipant::new(0);
+  fn dr_deserializes_known_instances_when_accessed() {
+    use crate::messages::submessages::submessage_elements::{
+      parameter::Parameter, parameter_list::ParameterList,
+    };
+    use crate::structure::{inline_qos::KeyHash, parameter_id::ParameterId};
 
+    let dp = DomainParticipant::new(0).unwrap();
     let mut qos = QosPolicies::qos_none();
-    qos.history = Some(policy::History::KeepAll); // Just for testing
+    qos.history = Some(policy::History::KeepAll);
 
     let sub = dp.create_subscriber(&qos).unwrap();
     let topic = dp
-      .create_topic("dr read", "read fn test?", &qos, TopicKind::WithKey)
+      .create_topic("dr on demand", "on demand?", &qos, TopicKind::WithKey)
       .unwrap();
 
     let (send, _rec) = mio_channel::sync_channel::<()>(10);
@@ -1344,7 +3304,6 @@ mod tests {
 
     let default_id = EntityId::default();
     let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), default_id);
-
     let mut reader = Reader::new(
       reader_guid,
       send,
@@ -1375,201 +3334,309 @@ mod tests {
       mr_state.multicast_reply_locator_list.clone(),
     );
 
-    // Reader and datareader ready, test with data
-    let test_data = RandomData {
-      a: 10,
-      b: ":DDD".to_string(),
+    let sample = RandomData {
+      a: 1,
+      b: "on demand".to_string(),
+    };
+    let good_bytes = to_bytes::<RandomData, LittleEndian>(&sample).unwrap();
+    // the key hash tells the instance without deserializing the sample
+    let make_data_msg = |value: Vec<u8>, sn: i64| {
+      let mut inline_qos = ParameterList::new();
+      inline_qos.parameters.push(Parameter {
+        parameter_id: ParameterId::PID_KEY_HASH,
+        value: KeyHash::from(sample.get_key().into_hash_key())
+          .to_octets()
+          .to_vec(),
+      });
+      let mut data_msg = Data::default();
+      data_msg.reader_id = reader.get_entity_id();
+      data_msg.writer_id = writer_guid.entityId;
+      data_msg.writer_sn = SequenceNumber::from(sn);
+      data_msg.inline_qos = Some(inline_qos);
+      data_msg.serialized_payload = Some(SerializedPayload {
+        representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+        representation_options: [0, 0],
+        value: value.into(),
+      });
+      data_msg
     };
+    let first = make_data_msg(good_bytes.clone(), 1);
+    let truncated = make_data_msg(good_bytes[..10].to_vec(), 2);
+    let third = make_data_msg(good_bytes, 3);
+    reader.handle_data_msg(first, mr_state.clone());
+    reader.handle_data_msg(truncated, mr_state.clone());
+    reader.handle_data_msg(third, mr_state);
+
+    // The instance is known from the first sample, so the others are not deserialized
+    // until they are accessed.
+    assert_eq!(datareader.get_sample_rejected_status().count(), 0);
+
+    let samples = datareader.take(10, ReadCondition::any()).unwrap();
+    let sequence_numbers: Vec<i64> = samples
+      .iter()
+      .map(|s| s.sample_info().sample_identity.unwrap().sequence_number)
+      .collect();
+    assert_eq!(sequence_numbers, vec![1, 3]);
+    assert!(samples
+      .iter()
+      .all(|s| s.value().as_ref().unwrap() == &sample));
+    let status = datareader.get_sample_rejected_status();
+    assert_eq!(status.count(), 1);
+    assert_eq!(
+      status.sample_rejected_reason(),
+      Some(SampleRejectedReason::DeserializationError)
+    );
+  }
 
-    let test_data2 = RandomData {
-      a: 11,
-      b: ":)))".to_string(),
+  #[test]
+  fn dr_recovers_key_of_malformed_sample() {
+    use crate::messages::submessages::submessage_elements::{
+      parameter::Parameter, parameter_list::ParameterList,
     };
+    use crate::structure::{inline_qos::KeyHash, parameter_id::ParameterId};
 
-    let mut data_msg = Data::default();
-    data_msg.reader_id = reader.get_entity_id();
-    data_msg.writer_id = writer_guid.entityId;
-    data_msg.writer_sn = SequenceNumber::from(0);
+    let dp = DomainParticipant::new(0).unwrap();
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll);
 
-    data_msg.serialized_payload = Some(SerializedPayload {
-      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
-      representation_options: [0, 0],
-      value: to_bytes::<RandomData, LittleEndian>(&test_data).unwrap(),
-    });
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic("dr recovered key", "recovered?", &qos, TopicKind::WithKey)
+      .unwrap();
 
-    let mut data_msg2 = Data::default();
-    data_msg2.reader_id = reader.get_entity_id();
-    data_msg2.writer_id = writer_guid.entityId;
-    data_msg2.writer_sn = SequenceNumber::from(1);
+    let (send, _rec) = mio_channel::sync_channel::<()>(10);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
 
-    data_msg2.serialized_payload = Some(SerializedPayload {
+    let default_id = EntityId::default();
+    let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), default_id);
+    let mut reader = Reader::new(
+      reader_guid,
+      send,
+      status_sender,
+      dp.get_dds_cache(),
+      topic.get_name().to_string(),
+      reader_command_receiver,
+    );
+
+    let mut datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &topic,
+        Some(default_id),
+        None,
+      )
+      .unwrap();
+
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+    reader.matched_writer_add(
+      writer_guid.clone(),
+      EntityId::ENTITYID_UNKNOWN,
+      mr_state.unicast_reply_locator_list.clone(),
+      mr_state.multicast_reply_locator_list.clone(),
+    );
+
+    let sample = RandomData {
+      a: 7,
+      b: "recovered".to_string(),
+    };
+    let key_hash = Parameter {
+      parameter_id: ParameterId::PID_KEY_HASH,
+      value: KeyHash::from(sample.get_key().into_hash_key())
+        .to_octets()
+        .to_vec(),
+    };
+    // The key comes first in the data, and survives the truncation.
+    let good_bytes = to_bytes::<RandomData, LittleEndian>(&sample).unwrap();
+    let mut truncated = Data::default();
+    truncated.reader_id = reader.get_entity_id();
+    truncated.writer_id = writer_guid.entityId;
+    truncated.writer_sn = SequenceNumber::from(1);
+    truncated.inline_qos = Some(ParameterList {
+      parameters: vec![key_hash.clone()],
+    });
+    truncated.serialized_payload = Some(SerializedPayload {
       representation_identifier: RepresentationIdentifier::CDR_LE as u16,
       representation_options: [0, 0],
-      value: to_bytes::<RandomData, LittleEndian>(&test_data2).unwrap(),
+      value: good_bytes[..10].to_vec().into(),
     });
-    reader.handle_data_msg(data_msg, mr_state.clone());
-    reader.handle_data_msg(data_msg2, mr_state.clone());
-
-    // Read the same sample two times.
-    {
-      let result_vec = datareader.read(100, ReadCondition::any()).unwrap();
-      let d = result_vec[0].value().unwrap();
-      assert_eq!(&test_data, d);
-    }
-    {
-      let result_vec2 = datareader.read(100, ReadCondition::any()).unwrap();
-      let d2 = result_vec2[1].value().unwrap();
-      assert_eq!(&test_data2, d2);
-    }
-    {
-      let result_vec3 = datareader.read(100, ReadCondition::any()).unwrap();
-      let d3 = result_vec3[0].value().unwrap();
-      assert_eq!(&test_data, d3);
-    }
+    // The dispose tells the instance only with the key hash.
+    let mut dispose = Data::default();
+    dispose.reader_id = reader.get_entity_id();
+    dispose.writer_id = writer_guid.entityId;
+    dispose.writer_sn = SequenceNumber::from(2);
+    dispose.inline_qos = Some(ParameterList {
+      parameters: vec![
+        key_hash,
+        Parameter::create_pid_status_info_parameter(true, false, false),
+      ],
+    });
+    reader.handle_data_msg(truncated, mr_state.clone());
+    reader.handle_data_msg(dispose, mr_state);
+
+    let samples = datareader.take(10, ReadCondition::any()).unwrap();
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].value(), &Err(7));
+    assert_eq!(
+      samples[0].sample_info().instance_state,
+      InstanceState::NotAlive_Disposed
+    );
+    assert_eq!(datareader.get_sample_rejected_status().count(), 1);
+    assert_eq!(datareader.get_statistics().deserialization_failures, 1);
+  }
 
-    // Take
-    let mut result_vec = datareader.take(100, ReadCondition::any()).unwrap();
-    let result_vec2 = datareader.take(100, ReadCondition::any());
+  #[test]
+  fn dr_disposes_instance_of_serialized_key() {
+    use serde::{Deserialize, Serialize};
+    use crate::messages::submessages::submessage_elements::{
+      parameter::Parameter, parameter_list::ParameterList,
+    };
+    use crate::structure::{inline_qos::KeyHash, parameter_id::ParameterId};
 
-    let d2 = result_vec.pop().unwrap();
-    let d2 = d2.value().as_ref().unwrap().clone();
-    let d1 = result_vec.pop().unwrap();
-    let d1 = d1.value().as_ref().unwrap().clone();
-    assert_eq!(test_data2, d2);
-    assert_eq!(test_data, d1);
-    assert!(result_vec2.is_ok());
-    assert_eq!(result_vec2.unwrap().len(), 0);
+    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+    struct Label(String);
 
-    //datareader.
+    // By default, as for strings, the key hash is an MD5 digest even of a short key.
+    impl Key for Label {}
 
-    // Read and take tests with instant
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Labeled {
+      label: Label,
+      value: i32,
+    }
 
-    let data_key1 = RandomData {
-      a: 1,
-      b: ":D".to_string(),
-    };
-    let data_key2_1 = RandomData {
-      a: 2,
-      b: ":(".to_string(),
-    };
-    let data_key2_2 = RandomData {
-      a: 2,
-      b: ":)".to_string(),
-    };
-    let data_key2_3 = RandomData {
-      a: 2,
-      b: "xD".to_string(),
-    };
+    impl Keyed for Labeled {
+      type K = Label;
+      fn get_key(&self) -> Label {
+        self.label.clone()
+      }
+    }
 
-    let key1 = data_key1.get_key();
-    let key2 = data_key2_1.get_key();
+    let dp = DomainParticipant::new(0).unwrap();
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll);
 
-    assert!(data_key2_1.get_key() == data_key2_2.get_key());
-    assert!(data_key2_3.get_key() == key2);
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic("dr serialized key", "Labeled", &qos, TopicKind::WithKey)
+      .unwrap();
 
-    let mut data_msg = Data::default();
-    data_msg.reader_id = reader.get_entity_id();
-    data_msg.writer_id = writer_guid.entityId;
-    data_msg.writer_sn = SequenceNumber::from(2);
+    let (send, _rec) = mio_channel::sync_channel::<()>(10);
+    let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
+    let (_reader_commander, reader_command_receiver) =
+      mio_extras::channel::sync_channel::<ReaderCommand>(100);
 
-    data_msg.serialized_payload = Some(SerializedPayload {
-      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
-      representation_options: [0, 0],
-      value: to_bytes::<RandomData, LittleEndian>(&data_key1).unwrap(),
-    });
-    let mut data_msg2 = Data::default();
-    data_msg2.reader_id = reader.get_entity_id();
-    data_msg2.writer_id = writer_guid.entityId;
-    data_msg2.writer_sn = SequenceNumber::from(3);
+    let default_id = EntityId::default();
+    let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), default_id);
+    let mut reader = Reader::new(
+      reader_guid,
+      send,
+      status_sender,
+      dp.get_dds_cache(),
+      topic.get_name().to_string(),
+      reader_command_receiver,
+    );
 
-    data_msg2.serialized_payload = Some(SerializedPayload {
-      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
-      representation_options: [0, 0],
-      value: to_bytes::<RandomData, LittleEndian>(&data_key2_1).unwrap(),
-    });
-    let mut data_msg3 = Data::default();
-    data_msg3.reader_id = reader.get_entity_id();
-    data_msg3.writer_id = writer_guid.entityId;
-    data_msg3.writer_sn = SequenceNumber::from(4);
+    let mut datareader = sub
+      .create_datareader::<Labeled, CDRDeserializerAdapter<Labeled>>(&topic, Some(default_id), None)
+      .unwrap();
 
-    data_msg3.serialized_payload = Some(SerializedPayload {
+    let writer_guid = GUID {
+      guidPrefix: GuidPrefix::new(vec![1; 12]),
+      entityId: EntityId::createCustomEntityID([1; 3], 1),
+    };
+    let mut mr_state = MessageReceiverState::default();
+    mr_state.source_guid_prefix = writer_guid.guidPrefix;
+    reader.matched_writer_add(
+      writer_guid.clone(),
+      EntityId::ENTITYID_UNKNOWN,
+      mr_state.unicast_reply_locator_list.clone(),
+      mr_state.multicast_reply_locator_list.clone(),
+    );
+
+    let sample = Labeled {
+      label: Label(String::from("a")),
+      value: 1,
+    };
+    let mut alive = Data::default();
+    alive.reader_id = reader.get_entity_id();
+    alive.writer_id = writer_guid.entityId;
+    alive.writer_sn = SequenceNumber::from(1);
+    alive.inline_qos = Some(ParameterList {
+      parameters: vec![Parameter {
+        parameter_id: ParameterId::PID_KEY_HASH,
+        value: KeyHash::from(sample.get_key().into_hash_key())
+          .to_octets()
+          .to_vec(),
+      }],
+    });
+    alive.serialized_payload = Some(SerializedPayload {
       representation_identifier: RepresentationIdentifier::CDR_LE as u16,
       representation_options: [0, 0],
-      value: to_bytes::<RandomData, LittleEndian>(&data_key2_2).unwrap(),
+      value: to_bytes::<Labeled, LittleEndian>(&sample).unwrap().into(),
     });
-    let mut data_msg4 = Data::default();
-    data_msg4.reader_id = reader.get_entity_id();
-    data_msg4.writer_id = writer_guid.entityId;
-    data_msg4.writer_sn = SequenceNumber::from(5);
-
-    data_msg4.serialized_payload = Some(SerializedPayload {
+    // The dispose tells the instance only with the serialized key, as writers
+    // that do not send key hashes do.
+    let mut dispose = Data::default();
+    dispose.reader_id = reader.get_entity_id();
+    dispose.writer_id = writer_guid.entityId;
+    dispose.writer_sn = SequenceNumber::from(2);
+    dispose.inline_qos = Some(ParameterList {
+      parameters: vec![Parameter::create_pid_status_info_parameter(
+        true, false, false,
+      )],
+    });
+    dispose.serialized_payload = Some(SerializedPayload {
       representation_identifier: RepresentationIdentifier::CDR_LE as u16,
       representation_options: [0, 0],
-      value: to_bytes::<RandomData, LittleEndian>(&data_key2_3).unwrap(),
+      value: to_bytes::<Label, LittleEndian>(&sample.label)
+        .unwrap()
+        .into(),
     });
-    reader.handle_data_msg(data_msg, mr_state.clone());
-    reader.handle_data_msg(data_msg2, mr_state.clone());
-    reader.handle_data_msg(data_msg3, mr_state.clone());
-    reader.handle_data_msg(data_msg4, mr_state.clone());
-
-    info!("calling read with key 1 and this");
-    let results =
-      datareader.read_instance(100, ReadCondition::any(), Some(key1), SelectByKey::This);
-    assert_eq!(data_key1, results.unwrap()[0].value().unwrap().clone());
-
-    info!("calling read with None and this");
-    // Takes the samllest key, 1 in this case.
-    let results = datareader.read_instance(100, ReadCondition::any(), None, SelectByKey::This);
-    assert_eq!(data_key1, results.unwrap()[0].value().unwrap().clone());
-
-    info!("calling read with key 1 and next");
-    let results =
-      datareader.read_instance(100, ReadCondition::any(), Some(key1), SelectByKey::Next);
-    assert_eq!(results.as_ref().unwrap().len(), 3);
-    assert_eq!(data_key2_2, results.unwrap()[1].value().unwrap().clone());
-
-    info!("calling take with key 2 and this");
-    let results =
-      datareader.take_instance(100, ReadCondition::any(), Some(key2), SelectByKey::This);
-    assert_eq!(results.as_ref().unwrap().len(), 3);
-    let mut vec = results.unwrap();
-    let d3 = vec.pop().unwrap();
-    let d3 = d3.into_value().unwrap();
-    let d2 = vec.pop().unwrap();
-    let d2 = d2.into_value().unwrap();
-    let d1 = vec.pop().unwrap();
-    let d1 = d1.into_value().unwrap();
-    assert_eq!(data_key2_3, d3);
-    assert_eq!(data_key2_2, d2);
-    assert_eq!(data_key2_1, d1);
-
-    info!("calling take with key 2 and this");
-    let results =
-      datareader.take_instance(100, ReadCondition::any(), Some(key2), SelectByKey::This);
-    assert!(results.is_ok());
-    assert!(results.unwrap().is_empty());
+    reader.handle_data_msg(alive, mr_state.clone());
+    reader.handle_data_msg(dispose, mr_state);
+
+    let samples = datareader.take(10, ReadCondition::any()).unwrap();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0].value(), &Ok(sample.clone()));
+    assert_eq!(samples[1].value(), &Err(sample.label));
+    assert!(samples
+      .iter()
+      .all(|s| s.sample_info().instance_state == InstanceState::NotAlive_Disposed));
+    assert_eq!(datareader.get_sample_rejected_status().count(), 0);
   }
 
   #[test]
-  fn dr_wake_up() {
-    let dp = DomainParticipant::new(0);
+  fn dr_read_latest_per_instance() {
+    use crate::messages::submessages::submessage_elements::{
+      parameter::Parameter, parameter_list::ParameterList,
+    };
+    use crate::structure::{inline_qos::KeyHash, parameter_id::ParameterId};
+
+    const INSTANCES: i64 = 100;
+    const ROUNDS: i64 = 3;
 
+    let dp = DomainParticipant::new(0).unwrap();
     let mut qos = QosPolicies::qos_none();
-    qos.history = Some(policy::History::KeepAll); // Just for testing
+    qos.history = Some(policy::History::KeepAll);
 
     let sub = dp.create_subscriber(&qos).unwrap();
     let topic = dp
-      .create_topic("wakeup", "Wake up!", &qos, TopicKind::WithKey)
+      .create_topic("dr latest", "latest?", &qos, TopicKind::WithKey)
       .unwrap();
 
-    let (send, rec) = mio_channel::sync_channel::<()>(10);
+    let (send, _rec) = mio_channel::sync_channel::<()>(10);
     let (status_sender, _status_reciever) = mio_extras::channel::sync_channel::<StatusChange>(100);
     let (_reader_commander, reader_command_receiver) =
       mio_extras::channel::sync_channel::<ReaderCommand>(100);
 
     let default_id = EntityId::default();
     let reader_guid = GUID::new_with_prefix_and_id(dp.get_guid_prefix(), default_id);
-
     let mut reader = Reader::new(
       reader_guid,
       send,
@@ -1586,7 +3653,6 @@ mod tests {
         None,
       )
       .unwrap();
-    datareader.notification_receiver = rec;
 
     let writer_guid = GUID {
       guidPrefix: GuidPrefix::new(vec![1; 12]),
@@ -1601,96 +3667,167 @@ mod tests {
       mr_state.multicast_reply_locator_list.clone(),
     );
 
-    let test_data1 = RandomData {
-      a: 1,
-      b: "Testing 1".to_string(),
+    let reader_id = reader.get_entity_id();
+    let new_data_msg = |sn: i64| {
+      let mut data_msg = Data::default();
+      data_msg.reader_id = reader_id;
+      data_msg.writer_id = writer_guid.entityId;
+      data_msg.writer_sn = SequenceNumber::from(sn);
+      data_msg
     };
+    // Each round writes every instance once, so the writes of the instances interleave.
+    let mut sn = 0;
+    for round in 0..ROUNDS {
+      for a in 0..INSTANCES {
+        sn += 1;
+        let sample = RandomData {
+          a,
+          b: round.to_string(),
+        };
+        let mut data_msg = new_data_msg(sn);
+        data_msg.serialized_payload = Some(SerializedPayload {
+          representation_identifier: RepresentationIdentifier::CDR_LE as u16,
+          representation_options: [0, 0],
+          value: to_bytes::<RandomData, LittleEndian>(&sample)
+            .unwrap()
+            .into(),
+        });
+        reader.handle_data_msg(data_msg, mr_state.clone());
+      }
+    }
+    // The first ten instances are disposed.
+    for a in 0..10i64 {
+      sn += 1;
+      let mut dispose = new_data_msg(sn);
+      dispose.inline_qos = Some(ParameterList {
+        parameters: vec![
+          Parameter {
+            parameter_id: ParameterId::PID_KEY_HASH,
+            value: KeyHash::from(a.into_hash_key()).to_octets().to_vec(),
+          },
+          Parameter::create_pid_status_info_parameter(true, false, false),
+        ],
+      });
+      reader.handle_data_msg(dispose, mr_state.clone());
+    }
 
-    let test_data2 = RandomData {
-      a: 2,
-      b: "Testing 2".to_string(),
-    };
+    // A dispose has no sample identity, but it is the newest sample of its instance.
+    let latest: Vec<(i64, Option<i64>)> = datareader
+      .read_latest_per_instance(ReadCondition::any())
+      .unwrap()
+      .iter()
+      .map(|s| {
+        (
+          s.get_key(),
+          s.sample_info().sample_identity.map(|i| i.sequence_number),
+        )
+      })
+      .collect();
+    let expected: Vec<(i64, Option<i64>)> = (0..INSTANCES)
+      .map(|a| match a {
+        0..=9 => (a, None),
+        _ => (a, Some((ROUNDS - 1) * INSTANCES + a + 1)),
+      })
+      .collect();
+    assert_eq!(latest, expected);
+
+    // Only the newest samples were read, the rest are still not read.
+    let alive = ReadCondition::any().with_instance_state(InstanceState::Alive);
+    let latest = datareader.read_latest_per_instance(alive).unwrap();
+    assert_eq!(latest.len(), (INSTANCES - 10) as usize);
+    assert!(latest
+      .iter()
+      .all(|s| s.value().as_ref().unwrap().b == (ROUNDS - 1).to_string()));
+    let not_read = datareader.read(1000, ReadCondition::not_read()).unwrap();
+    assert_eq!(not_read.len(), ((ROUNDS - 1) * INSTANCES + 10) as usize);
+  }
 
-    let test_data3 = RandomData {
-      a: 2,
-      b: "Testing 3".to_string(),
+  #[cfg(feature = "async")]
+  #[test]
+  fn dr_async_take_next_and_write() {
+    use std::{
+      future::Future,
+      pin::Pin,
+      sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+      },
+      task::{Context, Poll},
     };
 
-    let mut data_msg = Data::default();
-    data_msg.reader_id = reader.get_entity_id();
-    data_msg.writer_id = writer_guid.entityId;
-    data_msg.writer_sn = SequenceNumber::from(0);
-
-    data_msg.serialized_payload = Some(SerializedPayload {
-      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
-      representation_options: [0, 0],
-      value: to_bytes::<RandomData, byteorder::LittleEndian>(&test_data1).unwrap(),
-    });
-
-    let mut data_msg2 = Data::default();
-    data_msg2.reader_id = reader.get_entity_id();
-    data_msg2.writer_id = writer_guid.entityId;
-    data_msg2.writer_sn = SequenceNumber::from(1);
-
-    data_msg2.serialized_payload = Some(SerializedPayload {
-      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
-      representation_options: [0, 0],
-      value: to_bytes::<RandomData, byteorder::LittleEndian>(&test_data2).unwrap(),
-    });
-
-    let mut data_msg3 = Data::default();
-    data_msg3.reader_id = reader.get_entity_id();
-    data_msg3.writer_id = writer_guid.entityId;
-    data_msg3.writer_sn = SequenceNumber::from(2);
+    use futures::{
+      executor::block_on,
+      task::{waker_ref, ArcWake},
+      Stream,
+    };
 
-    data_msg3.serialized_payload = Some(SerializedPayload {
-      representation_identifier: RepresentationIdentifier::CDR_LE as u16,
-      representation_options: [0, 0],
-      value: to_bytes::<RandomData, byteorder::LittleEndian>(&test_data3).unwrap(),
-    });
+    use crate::serialization::cdr_serializer::CDRSerializerAdapter;
 
-    let handle = std::thread::spawn(move || {
-      reader.handle_data_msg(data_msg, mr_state.clone());
-      thread::sleep(time::Duration::from_millis(100));
-      info!("I'll send the second now..");
-      reader.handle_data_msg(data_msg2, mr_state.clone());
-      thread::sleep(time::Duration::from_millis(100));
-      info!("I'll send the third now..");
-      reader.handle_data_msg(data_msg3, mr_state.clone());
-    });
+    // Remembers that the task was woken up.
+    struct Woken(AtomicBool);
+    impl ArcWake for Woken {
+      fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.store(true, Ordering::SeqCst);
+      }
+    }
+    let woken = Arc::new(Woken(AtomicBool::new(false)));
+    let waker = waker_ref(&woken);
+    let mut cx = Context::from_waker(&waker);
+    // The event loop thread wakes the task when a sample arrives.
+    let wait_for_wake = || {
+      for _ in 0..100 {
+        if woken.0.swap(false, Ordering::SeqCst) {
+          return true;
+        }
+        thread::sleep(time::Duration::from_millis(50));
+      }
+      false
+    };
 
-    let poll = Poll::new().unwrap();
-    poll
-      .register(&datareader, Token(100), Ready::readable(), PollOpt::edge())
+    let dp = DomainParticipant::new(0).unwrap();
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll);
+    let topic = dp
+      .create_topic("dr async", "async?", &qos, TopicKind::WithKey)
+      .unwrap();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let publisher = dp.create_publisher(&qos).unwrap();
+    let mut datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None, None)
+      .unwrap();
+    let datawriter = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
       .unwrap();
 
-    let mut count_to_stop = 0;
-    'l: loop {
-      let mut events = Events::with_capacity(1024);
-      info!("Going to poll");
-      poll.poll(&mut events, None).unwrap();
-
-      for event in events.into_iter() {
-        info!("Handling events");
-        if event.token() == Token(100) {
-          let data = datareader.take(100, ReadCondition::any());
-          let len = data.as_ref().unwrap().len();
-          info!("There were {} samples available.", len);
-          info!("Their strings:");
-          for d in data.unwrap().into_iter() {
-            // Remove one notification for each data
-            info!("{}", d.value().as_ref().unwrap().b);
-          }
-          count_to_stop += len;
-        }
-        if count_to_stop >= 3 {
-          info!("I'll stop now with count {}", count_to_stop);
-          break 'l;
-        }
-      } // for
-    } // loop
+    let first = RandomData {
+      a: 1,
+      b: "first".to_string(),
+    };
+    {
+      let mut take_next = datareader.async_take_next();
+      assert!(Pin::new(&mut take_next).poll(&mut cx).is_pending());
+
+      block_on(datawriter.async_write(first.clone(), None)).unwrap();
+      assert!(wait_for_wake());
+      match Pin::new(&mut take_next).poll(&mut cx) {
+        Poll::Ready(Ok(sample)) => assert_eq!(sample.value(), &Ok(first)),
+        other => panic!("Expected the first sample, got {:?}", other.map(|_| ())),
+      }
+    }
 
-    handle.join().unwrap();
-    assert_eq!(count_to_stop, 3);
+    let second = RandomData {
+      a: 2,
+      b: "second".to_string(),
+    };
+    let mut stream = datareader.async_sample_stream();
+    assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+    block_on(datawriter.async_write(second.clone(), None)).unwrap();
+    assert!(wait_for_wake());
+    match Pin::new(&mut stream).poll_next(&mut cx) {
+      Poll::Ready(Some(sample)) => assert_eq!(sample.value(), &Ok(second)),
+      other => panic!("Expected the second sample, got {:?}", other.map(|_| ())),
+    }
   }
 }
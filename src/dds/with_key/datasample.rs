@@ -1,6 +1,6 @@
-use crate::{dds::traits::key::*, structure::guid::GUID};
-use crate::structure::time::Timestamp;
+use crate::dds::traits::key::*;
 use crate::dds::sampleinfo::*;
+use crate::structure::time::Timestamp;
 
 //use super::{interfaces::{IDataSample, IDataSampleConvert, IKeyedDataSample, IKeyedDataSampleConvert}, no_key::wrappers::NoKeyWrapper};
 
@@ -30,65 +30,6 @@ where
     DataSample { sample_info, value }
   }
 
-  pub(crate) fn new_deprecated(
-    source_timestamp: Timestamp,
-    payload: D,
-    writer_guid: GUID,
-  ) -> DataSample<D> {
-    // begin dummy placeholder values
-    let sample_state = SampleState::NotRead;
-    let view_state = ViewState::New;
-    let instance_state = InstanceState::Alive;
-    let sample_rank = 0;
-    let generation_rank = 0;
-    let absolute_generation_rank = 0;
-    // end dummy placeholder values
-
-    DataSample {
-      sample_info: SampleInfo {
-        sample_state,
-        view_state,
-        instance_state,
-        generation_counts: NotAliveGenerationCounts::zero(),
-        sample_rank,
-        generation_rank,
-        absolute_generation_rank,
-        source_timestamp: Some(source_timestamp),
-        publication_handle: writer_guid,
-      },
-      value: Ok(payload),
-    }
-  }
-
-  pub(crate) fn new_disposed<K>(source_timestamp: Timestamp, key: D::K, writer_guid: GUID) -> DataSample<D>
-  where
-    <D as Keyed>::K: Key,
-  {
-    // begin dummy placeholder values
-    let sample_state = SampleState::NotRead;
-    let view_state = ViewState::New;
-    let instance_state = InstanceState::NotAlive_Disposed;
-    let sample_rank = 0;
-    let generation_rank = 0;
-    let absolute_generation_rank = 0;
-    // end dummy placeholder values
-
-    DataSample {
-      sample_info: SampleInfo {
-        sample_state,
-        view_state,
-        instance_state,
-        generation_counts: NotAliveGenerationCounts::zero(),
-        sample_rank,
-        generation_rank,
-        absolute_generation_rank,
-        source_timestamp: Some(source_timestamp),
-        publication_handle: writer_guid,
-      },
-      value: Err(key),
-    }
-  } // fn
-
   // convenience shorthand to get the key directly, without digging out the "value"
   pub fn get_key(&self) -> D::K
   where
@@ -115,4 +56,15 @@ where
   pub fn sample_info_mut(&mut self) -> &mut SampleInfo {
     &mut self.sample_info
   }
+
+  /// When the writer wrote the sample, or TIME_INVALID if it did not tell
+  pub fn source_timestamp(&self) -> Timestamp {
+    self.sample_info.source_timestamp
+  }
+
+  /// When the sample was received. Minus `source_timestamp`, this is the latency from
+  /// the writer.
+  pub fn reception_timestamp(&self) -> Timestamp {
+    self.sample_info.reception_timestamp
+  }
 } // impl
@@ -1,29 +1,36 @@
 use std::{
+  cell::{Cell, RefCell},
+  collections::{BTreeMap, BTreeSet, VecDeque},
+  io,
   marker::PhantomData,
   sync::{Arc, RwLock},
   time::Duration,
 };
 use mio_extras::channel::{self as mio_channel, Receiver};
+use mio::{Evented, Poll, PollOpt, Ready, Token};
 
 use serde::Serialize;
-use log::{error, warn};
+use log::{debug, error};
 
 use crate::{
   discovery::discovery::DiscoveryCommand, serialization::CDRSerializerAdapter,
   dds::qos::policy::Liveliness, structure::time::Timestamp,
 };
+use crate::messages::submessages::submessage_elements::serialized_payload::SerializedPayload;
 use crate::structure::entity::{Entity, EntityAttributes};
 use crate::structure::{
+  clock::SharedClock,
   dds_cache::DDSCache,
   guid::{GUID, EntityId},
-  topic_kind::TopicKind,
+  inline_qos::SampleIdentity,
 };
 
+use crate::common::lock::{read_lock, write_lock};
 use crate::dds::pubsub::Publisher;
 use crate::dds::topic::Topic;
 use crate::dds::values::result::{
-  Result, Error, LivelinessLostStatus, OfferedDeadlineMissedStatus, OfferedIncompatibleQosStatus,
-  PublicationMatchedStatus,
+  Result, Error, DataWriterStatus, LivelinessLostStatus, OfferedDeadlineMissedStatus,
+  OfferedIncompatibleQosStatus, PublicationMatchedStatus,
 };
 use crate::dds::traits::dds_entity::DDSEntity;
 use crate::dds::traits::key::*;
@@ -31,15 +38,32 @@ use crate::dds::traits::TopicDescription;
 
 use crate::dds::qos::{
   HasQoSPolicy, QosPolicies,
-  policy::{Reliability},
+  policy::{DestinationOrder, History, Reliability},
 };
 use crate::dds::traits::serde_adapters::SerializerAdapter;
-use crate::dds::with_key::datasample::DataSample;
-use crate::{discovery::data_types::topic_data::SubscriptionBuiltinTopicData, dds::ddsdata::DDSData};
+use crate::{discovery::data_types::topic_data::DiscoveredReaderData, dds::ddsdata::DDSData};
 use super::super::{
-  datasample_cache::DataSampleCache, values::result::StatusChange, writer::WriterCommand,
+  ack_waiter::AckWaiter,
+  datasample_cache::DataSampleCache,
+  flow_control::{FlowControlConfig, QueueDepth},
+  matched_readers::MatchedReaders,
+  rtps_config::RtpsWriterConfig,
+  statistics::{WriterCounters, WriterStatistics},
+  task_waker::TaskWaker,
+  values::result::StatusChange,
+  writer::{BatchingPolicy, WriterCommand, DATA_MESSAGE_OVERHEAD},
 };
 
+// Oldest status changes are dropped, if the application does not take them.
+const STATUS_CHANGE_QUEUE_LIMIT: usize = 100;
+
+/// Identifies an instance registered to a [DataWriter](struct.DataWriter.html).
+/// See [`DataWriter::register_instance`](struct.DataWriter.html#method.register_instance).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InstanceHandle {
+  key_hash: u128,
+}
+
 /// DDS DataWriter for keyed topics
 ///
 /// # Examples
@@ -49,11 +73,11 @@ use super::super::{
 /// use rustdds::dds::DomainParticipant;
 /// use rustdds::dds::qos::QosPolicyBuilder;
 /// use rustdds::dds::data_types::TopicKind;
-/// use rustdds::dds::With_Key_DataWriter as DataWriter;
+/// use rustdds::dds::WithKeyDataWriter as DataWriter;
 /// use rustdds::dds::traits::Keyed;
 /// use rustdds::serialization::CDRSerializerAdapter;
 ///
-/// let domain_participant = DomainParticipant::new(0);
+/// let domain_participant = DomainParticipant::new(0).unwrap();
 /// let qos = QosPolicyBuilder::new().build();
 /// let publisher = domain_participant.create_publisher(&qos).unwrap();
 ///
@@ -80,9 +104,33 @@ pub struct DataWriter<'a, D: Keyed + Serialize, SA: SerializerAdapter<D> = CDRSe
   cc_upload: mio_channel::SyncSender<WriterCommand>,
   discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
   dds_cache: Arc<RwLock<DDSCache>>,
+  clock: SharedClock,
   datasample_cache: DataSampleCache<D>,
   phantom: PhantomData<SA>,
   status_receiver: Receiver<StatusChange>,
+  offered_deadline_missed_status: Cell<OfferedDeadlineMissedStatus>,
+  offered_incompatible_qos_status: Cell<OfferedIncompatibleQosStatus>,
+  publication_matched_status: Cell<PublicationMatchedStatus>,
+  liveliness_lost_status: Cell<LivelinessLostStatus>,
+  // Not yet taken with get_status_change
+  status_changes: RefCell<VecDeque<DataWriterStatus>>,
+  command_waker: TaskWaker,
+  ack_waiter: AckWaiter,
+  flow_control_queue_depth: QueueDepth,
+  statistics: Arc<WriterCounters>,
+  // readers of the Writer, for reader_filter and max_message_size
+  matched_readers: MatchedReaders,
+  // of the participant, the largest message sent over UDP
+  max_message_size: usize,
+  // decides which readers get a written sample, see set_reader_filter
+  reader_filter: RefCell<Option<Box<dyn Fn(&GUID, &D) -> bool>>>,
+  // of the latest write or dispose
+  last_source_timestamp: Cell<Option<Timestamp>>,
+  // Instances registered explicitly or by writing, and not unregistered since, with
+  // their serialized keys for dispose_all. None if the key cannot be serialized.
+  registered_instances: RefCell<BTreeMap<InstanceHandle, Option<SerializedPayload>>>,
+  // Registered instances disposed since they were last written
+  disposed_instances: RefCell<BTreeSet<InstanceHandle>>,
 }
 
 impl<'a, D, SA> Drop for DataWriter<'a, D, SA>
@@ -91,16 +139,90 @@ where
   SA: SerializerAdapter<D>,
 {
   fn drop(&mut self) {
-    match self
-      .discovery_command
-      .send(DiscoveryCommand::REMOVE_LOCAL_WRITER {
-        guid: self.get_guid(),
-      }) {
-      Ok(_) => {}
-      Err(e) => error!(
-        "Failed to send REMOVE_LOCAL_WRITER DiscoveryCommand. {:?}",
-        e
-      ),
+    if self.autodispose() && self.my_publisher.get_participant().is_some() {
+      let now = self.clock.now();
+      let source_timestamp = self
+        .last_source_timestamp
+        .get()
+        .map_or(now, |last| std::cmp::max(last, now));
+      if let Err(e) = self.send_dispose_all(source_timestamp) {
+        debug!(
+          "DataWriter {:?} could not dispose its instances. {:?}",
+          self.get_guid(),
+          e
+        );
+      }
+    }
+    // Nothing to do if it was deleted explicitly.
+    if let Err(e) = self.my_publisher.remove_writer(self.get_guid()) {
+      debug!("DataWriter {:?} already deleted. {:?}", self.get_guid(), e);
+    }
+  }
+}
+
+// These need no Key bound on the key type, so that Drop can use them.
+impl<'a, D, SA> DataWriter<'a, D, SA>
+where
+  D: Keyed + Serialize,
+  SA: SerializerAdapter<D>,
+{
+  // WriterDataLifecycle autodispose_unregistered_instances
+  fn autodispose(&self) -> bool {
+    self
+      .qos_policy
+      .writer_data_lifecycle()
+      .map_or(false, |w| w.autodispose_unregistered_instances)
+  }
+
+  // Disposes every registered instance that is not disposed already, each with a
+  // dispose of its own. Waits for room in the command queue, as there may be more
+  // instances than fit in it.
+  fn send_dispose_all(&self, source_timestamp: Timestamp) -> Result<()> {
+    let instances: Vec<(InstanceHandle, Option<SerializedPayload>)> = {
+      let disposed = self.disposed_instances.borrow();
+      self
+        .registered_instances
+        .borrow()
+        .iter()
+        .filter(|(handle, _)| !disposed.contains(*handle))
+        .map(|(handle, key)| (*handle, key.clone()))
+        .collect()
+    };
+    for (handle, key) in instances {
+      let ddsdata = DDSData::from_serialized_dispose(key, handle.key_hash, Some(source_timestamp));
+      let command = WriterCommand::DDSData { data: ddsdata };
+      let sent = match self.cc_upload.send(command) {
+        Ok(()) => Ok(()),
+        Err(mio_channel::SendError::Io(e)) => Err(mio_channel::TrySendError::Io(e)),
+        Err(mio_channel::SendError::Disconnected(c)) => {
+          Err(mio_channel::TrySendError::Disconnected(c))
+        }
+      };
+      if let Err(e) = sent {
+        return Err(self.command_error("dispose", e));
+      }
+      self.ack_waiter.add_written();
+      self.disposed_instances.borrow_mut().insert(handle);
+    }
+    Ok(())
+  }
+
+  // Error of a command that could not be handed to the RTPS Writer
+  fn command_error(&self, action: &str, e: mio_channel::TrySendError<WriterCommand>) -> Error {
+    let reason = format!(
+      "cannot {} DataWriter {:?} of topic {}",
+      action,
+      self.get_guid(),
+      self.my_topic.get_name()
+    );
+    match e {
+      mio_channel::TrySendError::Io(e) => Error::io(reason, e),
+      mio_channel::TrySendError::Full(_) => {
+        Error::out_of_resources(format!("{}, its command queue is full", reason))
+      }
+      mio_channel::TrySendError::Disconnected(_) => {
+        Error::out_of_resources(format!("{}, DomainParticipant is shut down", reason))
+      }
     }
   }
 }
@@ -119,6 +241,11 @@ where
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
     dds_cache: Arc<RwLock<DDSCache>>,
     status_receiver: Receiver<StatusChange>,
+    command_waker: TaskWaker,
+    ack_waiter: AckWaiter,
+    flow_control_queue_depth: QueueDepth,
+    statistics: Arc<WriterCounters>,
+    matched_readers: MatchedReaders,
   ) -> Result<DataWriter<'a, D, SA>> {
     let entity_id = match guid {
       Some(g) => g.entityId.clone(),
@@ -128,8 +255,10 @@ where
     let dp = match publisher.get_participant() {
       Some(dp) => dp,
       None => {
-        error!("Cannot create new DataWriter, DomainParticipant doesn't exist.");
-        return Err(Error::PreconditionNotMet);
+        return Err(Error::precondition_not_met(format!(
+          "cannot create DataWriter of topic {}, DomainParticipant has been dropped",
+          topic.get_name()
+        )))
       }
     };
 
@@ -138,13 +267,14 @@ where
       entity_id,
     ));
 
-    match dds_cache.write() {
-      Ok(mut cache) => cache.add_new_topic(
-        &String::from(topic.get_name()),
-        TopicKind::NoKey,
-        topic.get_type(),
-      ),
-      Err(e) => panic!("DDSCache is poisoned. {:?}", e),
+    let clock = {
+      let mut cache = write_lock(&dds_cache);
+      let topic_name = String::from(topic.get_name());
+      cache.add_new_topic(&topic_name, topic.kind(), topic.get_type(), topic.get_qos());
+      cache
+        .add_topic_user(&topic_name, entity_attributes.guid)
+        .unwrap_or(());
+      cache.clock()
     };
 
     match topic.get_qos().liveliness {
@@ -171,9 +301,25 @@ where
       cc_upload,
       discovery_command,
       dds_cache,
+      clock,
       datasample_cache: DataSampleCache::new(topic.get_qos().clone()),
       phantom: PhantomData,
       status_receiver,
+      offered_deadline_missed_status: Cell::new(OfferedDeadlineMissedStatus::new()),
+      offered_incompatible_qos_status: Cell::new(OfferedIncompatibleQosStatus::new()),
+      publication_matched_status: Cell::new(PublicationMatchedStatus::new()),
+      liveliness_lost_status: Cell::new(LivelinessLostStatus::new()),
+      status_changes: RefCell::new(VecDeque::new()),
+      command_waker,
+      ack_waiter,
+      flow_control_queue_depth,
+      statistics,
+      matched_readers,
+      max_message_size: dp.local_network().max_message_size(),
+      reader_filter: RefCell::new(None),
+      last_source_timestamp: Cell::new(None),
+      registered_instances: RefCell::new(BTreeMap::new()),
+      disposed_instances: RefCell::new(BTreeSet::new()),
     })
   }
 
@@ -190,11 +336,11 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -237,6 +383,17 @@ where
 
   /// Writes single data instance to a topic.
   ///
+  /// With Reliable reliability, KeepAll history and ResourceLimits `max_samples`, at most
+  /// `max_samples` samples may wait for acknowledgement from matched reliable DataReaders.
+  /// When that many are waiting, `write` blocks up to `max_blocking_time`, and then returns
+  /// `Err(OutOfResources)`. Best effort DataReaders do not acknowledge, so they are not
+  /// waited for.
+  ///
+  /// Samples are not fragmented. When some matched DataReader is reached over UDP, a
+  /// sample that does not fit in `max_message_size` of the participant's
+  /// [DiscoveryConfig](../struct.DiscoveryConfig.html) is refused with
+  /// `Err(MessageTooLarge)`.
+  ///
   /// # Examples
   ///
   /// ```
@@ -244,11 +401,11 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -270,35 +427,217 @@ where
   /// data_writer.write(some_data, None).unwrap();
   /// ```
   pub fn write(&self, data: D, source_timestamp: Option<Timestamp>) -> Result<()> {
-    let mut ddsdata = DDSData::from(&data, source_timestamp);
-    // TODO key value should be unique always. This is not always unique.
-    // If sample with same values is given then hash is same for both samples.
-    // TODO FIX THIS
-    ddsdata.value_key_hash = data.get_key().into_hash_key();
-
-    let _data_sample = match source_timestamp {
-      // TODO: fix this to use something else than new_deprecated.
-      // We cannot generate DataSample at the writer side, because most of the
-      // SampleInfo fields cannot be known.
-      Some(t) => DataSample::new_deprecated(t, data, self.get_guid()),
-      None => DataSample::new_deprecated(Timestamp::now(), data, self.get_guid()),
-    };
+    self.send_data_command(self.data_command(&data, source_timestamp)?)
+  }
 
-    match self
-      .cc_upload
-      .try_send(WriterCommand::DDSData { data: ddsdata })
-    {
+  /// Writes a sample that relates to another sample, e.g. a reply to a request, and
+  /// returns the identity of the written sample.
+  ///
+  /// The related sample identity is sent as inline QoS, and DataReaders report it in
+  /// `SampleInfo::related_sample_identity`. The returned identity is what DataReaders
+  /// report as `SampleInfo::sample_identity` of this sample. Fragmented (large) samples
+  /// do not carry inline QoS, so their related sample identity is lost.
+  pub fn write_with_related_sample_identity(
+    &self,
+    data: D,
+    related_sample_identity: Option<SampleIdentity>,
+  ) -> Result<SampleIdentity> {
+    let mut command = self.data_command(&data, None)?;
+    if let WriterCommand::DDSData { data } = &mut command {
+      data.related_sample_identity = related_sample_identity;
+    }
+    let sequence_number = self.send_numbered_data_command(command)?;
+    Ok(SampleIdentity {
+      writer_guid: self.get_guid(),
+      sequence_number,
+    })
+  }
+
+  // Hands a sample (or dispose/unregister) to the RTPS Writer.
+  // A reliable KeepAll DataWriter with ResourceLimits max_samples keeps at most that many
+  // samples unacknowledged, and first waits up to max_blocking_time for the readers to
+  // acknowledge earlier samples. Then Err(OutOfResources) is returned, as the history is full.
+  fn send_data_command(&self, command: WriterCommand) -> Result<()> {
+    self.send_numbered_data_command(command).map(|_| ())
+  }
+
+  // As send_data_command, but returns the sequence number that the Writer gives the sample.
+  fn send_numbered_data_command(&self, command: WriterCommand) -> Result<i64> {
+    if let Some((max_samples, max_blocking_time)) = self.history_limit() {
+      if !self
+        .ack_waiter
+        .wait_for_room(max_samples, max_blocking_time)
+      {
+        return Err(Error::out_of_resources(format!(
+          "history of DataWriter {:?} of topic {} is full, {} samples not acknowledged \
+           within max_blocking_time",
+          self.get_guid(),
+          self.my_topic.get_name(),
+          self.ack_waiter.unacked()
+        )));
+      }
+    }
+    match self.cc_upload.try_send(command) {
       Ok(_) => {
+        let sequence_number = self.ack_waiter.add_written();
         self.refresh_manual_liveliness();
-        Ok(())
+        Ok(sequence_number)
+      }
+      Err(e) => Err(self.command_error("write", e)),
+    }
+  }
+
+  // Maximum number of unacknowledged samples, and how long to wait for room.
+  // Best effort writers do not wait for anything, and neither does KeepLast history,
+  // which just replaces the oldest samples.
+  fn history_limit(&self) -> Option<(i64, Duration)> {
+    match (
+      self.qos_policy.reliability,
+      self.qos_policy.history,
+      self.qos_policy.resource_limits,
+    ) {
+      (Some(Reliability::Reliable { max_blocking_time }), Some(History::KeepAll), Some(limits))
+        if limits.max_samples > 0 =>
+      {
+        Some((i64::from(limits.max_samples), max_blocking_time.to_std()))
       }
-      Err(e) => {
-        warn!("Failed to write new data. {:?}", e);
-        Err(Error::OutOfResources)
+      _ => None,
+    }
+  }
+
+  /// Writes a sample with the given source timestamp.
+  ///
+  /// The timestamp is sent to DataReaders in the RTPS InfoTimestamp submessage, and they
+  /// report it as `source_timestamp` in SampleInfo. If the DestinationOrder QoS policy is
+  /// BySourceTimeStamp, the timestamp must not be older than the one of the previous
+  /// write or dispose of this DataWriter, otherwise `Err(PreconditionNotMet)` is returned.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::{TopicKind, Timestamp};
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::serialization::CDRSerializerAdapter;
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  ///
+  /// #[derive(Serialize, Deserialize)]
+  /// struct SomeType { a: i32 }
+  /// impl Keyed for SomeType {
+  ///   type K = i32;
+  ///
+  ///   fn get_key(&self) -> Self::K {
+  ///     self.a
+  ///   }
+  /// }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
+  ///
+  /// let some_data = SomeType { a: 1 };
+  /// data_writer.write_with_timestamp(some_data, Timestamp::now()).unwrap();
+  /// ```
+  pub fn write_with_timestamp(&self, data: D, source_timestamp: Timestamp) -> Result<()> {
+    self.write(data, Some(source_timestamp))
+  }
+
+  // Resolves the source timestamp of a new write or dispose, and checks it against
+  // the DestinationOrder policy.
+  fn next_source_timestamp(&self, source_timestamp: Option<Timestamp>) -> Result<Timestamp> {
+    let ts = source_timestamp.unwrap_or_else(|| self.clock.now());
+    let by_source_timestamp =
+      self.qos_policy.destination_order() == Some(DestinationOrder::BySourceTimeStamp);
+    match self.last_source_timestamp.get() {
+      Some(last) if by_source_timestamp && ts < last => Err(Error::precondition_not_met(format!(
+        "source timestamp {:?} is older than previous {:?}, which DestinationOrder \
+           BySourceTimeStamp does not allow",
+        ts, last
+      ))),
+      last => {
+        self
+          .last_source_timestamp
+          .set(std::cmp::max(last, Some(ts)));
+        Ok(ts)
       }
     }
   }
 
+  // Writing implicitly registers the instance.
+  fn data_command(&self, data: &D, source_timestamp: Option<Timestamp>) -> Result<WriterCommand> {
+    let ts = self.next_source_timestamp(source_timestamp)?;
+    let handle = self.register_key(&data.get_key());
+    self.data_command_with_handle(data, handle, ts)
+  }
+
+  // Registers the instance, if it is not registered already. Its key is serialized now,
+  // so that dispose_all can dispose it without the key.
+  fn register_key(&self, key: &<D as Keyed>::K) -> InstanceHandle {
+    let handle = InstanceHandle {
+      key_hash: key.into_hash_key(),
+    };
+    self
+      .registered_instances
+      .borrow_mut()
+      .entry(handle)
+      .or_insert_with(|| {
+        SA::key_to_bytes(key)
+          .ok()
+          .map(|v| SerializedPayload::new(SA::output_encoding(), v))
+      });
+    handle
+  }
+
+  fn data_command_with_handle(
+    &self,
+    data: &D,
+    handle: InstanceHandle,
+    source_timestamp: Timestamp,
+  ) -> Result<WriterCommand> {
+    let mut ddsdata = DDSData::from::<D, SA>(data, Some(source_timestamp)).map_err(|e| {
+      Error::serialization(
+        format!(
+          "cannot serialize sample of topic {}",
+          self.my_topic.get_name()
+        ),
+        e,
+      )
+    })?;
+    // TODO key value should be unique always. This is not always unique.
+    // If sample with same values is given then hash is same for both samples.
+    // TODO FIX THIS
+    ddsdata.value_key_hash = handle.key_hash;
+    let payload_len = ddsdata.data().len();
+    if payload_len + DATA_MESSAGE_OVERHEAD > self.max_message_size
+      && self.matched_readers.over_udp()
+    {
+      return Err(Error::message_too_large(format!(
+        "sample of {} bytes of topic {} does not fit in max_message_size {}",
+        payload_len,
+        self.my_topic.get_name(),
+        self.max_message_size
+      )));
+    }
+    if let Some(reader_filter) = self.reader_filter.borrow().as_ref() {
+      ddsdata.filtered_readers = self
+        .matched_readers
+        .get()
+        .iter()
+        .filter(|&reader| !reader_filter(reader, data))
+        .copied()
+        .collect();
+    }
+    // alive again
+    self.disposed_instances.borrow_mut().remove(&handle);
+    Ok(WriterCommand::DDSData { data: ddsdata })
+  }
+
   /// Waits for all acknowledgements to finish
   ///
   /// # Examples
@@ -308,11 +647,11 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -350,10 +689,19 @@ where
     };
 
     // TODO: wait for actual acknowledgements to writers writes
-    return Err(Error::Unsupported);
+    return Err(Error::unsupported(
+      "waiting for acknowledgments is not implemented",
+    ));
   }
 
-  /// Gets mio Receiver for all status changes
+  /// Turns on batching of samples. Written samples are then collected and sent together
+  /// in one RTPS message, instead of one message per sample. The batch is sent when it has
+  /// `max_samples` samples or `max_bytes` bytes of serialized data, or when `max_delay` has
+  /// passed since the first sample of the batch was written, whichever comes first.
+  /// Each sample keeps its own sequence number, source timestamp and inline QoS.
+  ///
+  /// Setting `max_samples` to 1 turns batching off. Samples already in the batch are sent
+  /// when the settings change. Zero limits are `Err(BadParameter)`.
   ///
   /// # Examples
   ///
@@ -362,11 +710,11 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -384,39 +732,60 @@ where
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
   ///
-  /// // Some status has changed
-  ///
-  /// while let Ok(sc) = data_writer.get_status_listener().try_recv() {
-  ///   // do something
+  /// data_writer.set_batching(10, 8192, std::time::Duration::from_millis(5)).unwrap();
+  /// for a in 0..5 {
+  ///   data_writer.write(SomeType { a }, None).unwrap();
   /// }
+  /// // do not wait for max_delay
+  /// data_writer.flush().unwrap();
   /// ```
-  pub fn get_status_listener(&self) -> &Receiver<StatusChange> {
+  pub fn set_batching(
+    &self,
+    max_samples: usize,
+    max_bytes: usize,
+    max_delay: Duration,
+  ) -> Result<()> {
+    if max_samples == 0 || max_bytes == 0 {
+      return Err(Error::bad_parameter(format!(
+        "batching max_samples {} and max_bytes {} must be positive",
+        max_samples, max_bytes
+      )));
+    }
+    let batching = if max_samples == 1 {
+      None
+    } else {
+      Some(BatchingPolicy {
+        max_samples,
+        max_bytes,
+        max_delay: max_delay.into(),
+      })
+    };
     match self
       .cc_upload
-      .try_send(WriterCommand::ResetOfferedDeadlineMissedStatus {
-        writer_guid: self.get_guid(),
-      }) {
-      Ok(_) => (),
-      Err(e) => error!("Unable to send ResetOfferedDeadlineMissedStatus. {:?}", e),
-    };
-    &self.status_receiver
+      .try_send(WriterCommand::SetBatching { batching })
+    {
+      Ok(_) => Ok(()),
+      Err(e) => Err(self.command_error("set batching of", e)),
+    }
   }
 
-  /// Unimplemented. <b>Do not use</b>.
+  /// Sets the heartbeat period and AckNack response timing of a reliable writer.
+  /// Shorter periods recover lost samples faster, at the cost of more traffic.
+  ///
+  /// Returns `BadParameter` if the config does not pass
+  /// [`RtpsWriterConfig::validate`](../struct.RtpsWriterConfig.html#method.validate).
   ///
   /// # Examples
   ///
-  /// ```no_run
-  // TODO: enable when functional
+  /// ```
   /// # use serde::{Serialize, Deserialize};
-  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::{DomainParticipant, RtpsWriterConfig};
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -430,34 +799,68 @@ where
   ///   }
   /// }
   ///
-  /// // WithKey is important
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
   ///
-  /// // Liveliness lost status has changed
-  ///
-  /// if let Ok(lls) = data_writer.get_liveliness_lost_status() {
-  ///   // do something
-  /// }
+  /// data_writer.set_rtps_config(RtpsWriterConfig {
+  ///   heartbeat_period: std::time::Duration::from_millis(100),
+  ///   ..RtpsWriterConfig::default()
+  /// }).unwrap();
   /// ```
-  pub fn get_liveliness_lost_status(&self) -> Result<LivelinessLostStatus> {
-    todo!()
+  pub fn set_rtps_config(&self, config: RtpsWriterConfig) -> Result<()> {
+    config.validate()?;
+    match self
+      .cc_upload
+      .try_send(WriterCommand::SetRtpsConfig { config })
+    {
+      Ok(_) => Ok(()),
+      Err(e) => Err(self.command_error("set RTPS config of", e)),
+    }
   }
 
-  /// Should get latest offered deadline missed status. <b>Do not use yet</b> use `get_status_lister` instead for the moment.
+  /// Names this DataWriter for people, e.g. in tools such as RTI Admin Console,
+  /// which otherwise show only its GUID. Remote participants learn of the name with
+  /// the next discovery update. The name does not affect matching.
+  pub fn set_name(&self, name: &str) -> Result<()> {
+    let dp = match self.my_publisher.get_participant() {
+      Some(dp) => dp,
+      None => {
+        return Err(Error::precondition_not_met(format!(
+          "cannot set name of DataWriter {:?}, DomainParticipant has been dropped",
+          self.get_guid()
+        )))
+      }
+    };
+    write_lock(&dp.discovery_db()).update_local_writer_name(self.get_guid(), name);
+    Ok(())
+  }
+
+  /// Sends the samples collected to the current batch right away.
+  /// Does nothing if batching is not on. See [set_batching](#method.set_batching).
+  pub fn flush(&self) -> Result<()> {
+    match self.cc_upload.try_send(WriterCommand::Flush) {
+      Ok(_) => Ok(()),
+      Err(e) => Err(self.command_error("flush", e)),
+    }
+  }
+
+  /// Limits the rate at which this writer sends, new samples and repairs alike.
+  /// Repairs of lost samples go first. Messages over the budget wait in a queue, see
+  /// [get_flow_control_queue_depth](#method.get_flow_control_queue_depth).
+  /// `FlowControlConfig::default()` turns the limit off, and sends the queued messages
+  /// right away.
   ///
   /// # Examples
   ///
   /// ```
   /// # use serde::{Serialize, Deserialize};
-  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::{DomainParticipant, FlowControlConfig};
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -471,53 +874,54 @@ where
   ///   }
   /// }
   ///
-  /// // WithKey is important
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
   ///
-  /// // Deadline missed status has changed
+  /// data_writer.set_flow_control(FlowControlConfig {
+  ///   bytes_per_second: 500_000,
+  ///   max_burst_bytes: 64 * 1024,
+  /// }).unwrap();
+  /// data_writer.write(SomeType { a: 1 }, None).unwrap();
   ///
-  /// if let Ok(odms) = data_writer.get_offered_deadline_missed_status() {
-  ///   // do something
+  /// if data_writer.get_flow_control_queue_depth() > 1000 {
+  ///   // the link cannot keep up, write less
   /// }
   /// ```
-  pub fn get_offered_deadline_missed_status(&self) -> Result<OfferedDeadlineMissedStatus> {
-    let mut fstatus = OfferedDeadlineMissedStatus::new();
-    while let Ok(status) = self.status_receiver.try_recv() {
-      match status {
-        StatusChange::OfferedDeadlineMissedStatus(status) => fstatus = status,
-        // TODO: possibly save old statuses
-        _ => (),
-      }
-    }
-
+  pub fn set_flow_control(&self, config: FlowControlConfig) -> Result<()> {
     match self
       .cc_upload
-      .try_send(WriterCommand::ResetOfferedDeadlineMissedStatus {
-        writer_guid: self.get_guid(),
-      }) {
-      Ok(_) => (),
-      Err(e) => error!("Unable to send ResetOfferedDeadlineMissedStatus. {:?}", e),
-    };
+      .try_send(WriterCommand::SetFlowControl { config })
+    {
+      Ok(_) => Ok(()),
+      Err(e) => Err(self.command_error("set flow control of", e)),
+    }
+  }
 
-    Ok(fstatus)
+  /// Number of messages waiting for their turn to be sent under
+  /// [flow control](#method.set_flow_control). A depth that keeps growing means the
+  /// writer produces more than the configured rate allows. Always zero without flow
+  /// control.
+  pub fn get_flow_control_queue_depth(&self) -> usize {
+    self.flow_control_queue_depth.get()
   }
 
-  /// Unimplemented. <b>Do not use</b>.
+  /// Sends each written sample only to the matched DataReaders, remote and local, for
+  /// which `filter` returns true. The others get an RTPS GAP instead, which tells them
+  /// that the sample is not for them, so that reliable DataReaders do not wait for it.
+  /// The filter is run for every matched DataReader on each write. Disposes and
+  /// unregisters go to all DataReaders. Replaces the previous filter, if any.
   ///
   /// # Examples
   ///
-  /// ```no_run
-  // TODO: enable when functional
+  /// ```
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
-  /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::data_types::{TopicKind, GUID};
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -531,35 +935,46 @@ where
   ///   }
   /// }
   ///
-  /// // WithKey is important
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
   ///
-  /// // Liveliness lost status has changed
-  ///
-  /// if let Ok(oiqs) = data_writer.get_offered_incompatible_qos_status() {
-  ///   // do something
-  /// }
+  /// // Samples with a negative `a` go only to the first matched remote DataReader.
+  /// let first = data_writer.get_matched_subscriptions().first().copied();
+  /// data_writer.set_reader_filter(Box::new(move |reader: &GUID, sample: &SomeType| {
+  ///   sample.a >= 0 || Some(*reader) == first
+  /// }));
+  /// data_writer.write(SomeType { a: -1 }, None).unwrap();
   /// ```
-  pub fn get_offered_incompatible_qos_status(&self) -> Result<OfferedIncompatibleQosStatus> {
-    todo!()
+  pub fn set_reader_filter(&self, filter: Box<dyn Fn(&GUID, &D) -> bool>) {
+    *self.reader_filter.borrow_mut() = Some(filter);
   }
 
-  /// Unimplemented. <b>Do not use</b>.
+  /// Removes the filter set with [set_reader_filter](#method.set_reader_filter), so that
+  /// all matched DataReaders get all samples again.
+  pub fn remove_reader_filter(&self) {
+    *self.reader_filter.borrow_mut() = None;
+  }
+
+  /// Counts of the RTPS traffic this DataWriter has sent. See
+  /// [WriterStatistics](../struct.WriterStatistics.html).
+  pub fn get_statistics(&self) -> WriterStatistics {
+    self.statistics.get()
+  }
+
+  /// Gets mio Receiver for all status changes
   ///
   /// # Examples
   ///
-  /// ```no_run
-  // TODO: enable when functional
+  /// ```
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -577,17 +992,26 @@ where
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
   ///
-  /// // Liveliness lost status has changed
+  /// // Some status has changed
   ///
-  /// if let Ok(pms) = data_writer.get_publication_matched_status() {
+  /// while let Ok(sc) = data_writer.get_status_listener().try_recv() {
   ///   // do something
   /// }
   /// ```
-  pub fn get_publication_matched_status(&self) -> Result<PublicationMatchedStatus> {
-    todo!()
+  pub fn get_status_listener(&self) -> &Receiver<StatusChange> {
+    match self
+      .cc_upload
+      .try_send(WriterCommand::ResetOfferedDeadlineMissedStatus {
+        writer_guid: self.get_guid(),
+      }) {
+      Ok(_) => (),
+      Err(e) => error!("Unable to send ResetOfferedDeadlineMissedStatus. {:?}", e),
+    };
+    &self.status_receiver
   }
 
-  /// Topic assigned to this DataWriter
+  /// Gets LivelinessLostStatus, i.e. how many times a DataWriter with `ManualByTopic`
+  /// liveliness did not write or assert its liveliness within the lease duration.
   ///
   /// # Examples
   ///
@@ -596,11 +1020,11 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -618,13 +1042,30 @@ where
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
   ///
-  /// assert_eq!(data_writer.get_topic(), &topic);
+  /// // Liveliness lost status has changed
+  ///
+  /// if let Ok(lls) = data_writer.get_liveliness_lost_status() {
+  ///   // do something
+  /// }
   /// ```
-  pub fn get_topic(&self) -> &Topic {
-    &self.my_topic
+  pub fn get_liveliness_lost_status(&self) -> Result<LivelinessLostStatus> {
+    self.fetch_status_changes();
+    let status = self.liveliness_lost_status.get();
+    let mut reset_status = status;
+    reset_status.reset_change();
+    self.liveliness_lost_status.set(reset_status);
+
+    match self
+      .cc_upload
+      .try_send(WriterCommand::ResetLivelinessLostStatus {
+        writer_guid: self.get_guid(),
+      }) {
+      Ok(_) => Ok(status),
+      Err(e) => Err(self.command_error("reset LivelinessLostStatus of", e)),
+    }
   }
 
-  /// Publisher assigned to this DataWriter
+  /// Should get latest offered deadline missed status. <b>Do not use yet</b> use `get_status_lister` instead for the moment.
   ///
   /// # Examples
   ///
@@ -633,11 +1074,11 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -655,12 +1096,34 @@ where
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
   ///
-  /// assert_eq!(data_writer.get_publisher(), &publisher);
-  pub fn get_publisher(&self) -> &Publisher {
-    &self.my_publisher
+  /// // Deadline missed status has changed
+  ///
+  /// if let Ok(odms) = data_writer.get_offered_deadline_missed_status() {
+  ///   // do something
+  /// }
+  /// ```
+  pub fn get_offered_deadline_missed_status(&self) -> Result<OfferedDeadlineMissedStatus> {
+    self.fetch_status_changes();
+    let fstatus = self.offered_deadline_missed_status.get();
+    let mut reset_status = fstatus;
+    reset_status.reset_change();
+    self.offered_deadline_missed_status.set(reset_status);
+
+    match self
+      .cc_upload
+      .try_send(WriterCommand::ResetOfferedDeadlineMissedStatus {
+        writer_guid: self.get_guid(),
+      }) {
+      Ok(_) => (),
+      Err(e) => error!("Unable to send ResetOfferedDeadlineMissedStatus. {:?}", e),
+    };
+
+    Ok(fstatus)
   }
 
-  /// Manually asserts liveliness (use this instead of refresh) according to QoS
+  /// Gets OfferedIncompatibleQosStatus, i.e. how many times a remote DataReader of
+  /// the same topic was discovered, but could not be matched because it requests
+  /// QoS that this DataWriter does not offer.
   ///
   /// # Examples
   ///
@@ -669,11 +1132,11 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -691,20 +1154,284 @@ where
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
   ///
-  /// data_writer.assert_liveliness().unwrap();
+  /// if let Ok(oiqs) = data_writer.get_offered_incompatible_qos_status() {
+  ///   if let Some(policy) = oiqs.last_policy_id() {
+  ///     println!("A reader requests {:?} we do not offer", policy);
+  ///   }
+  /// }
   /// ```
-  pub fn assert_liveliness(&self) -> Result<()> {
-    self.refresh_manual_liveliness();
+  pub fn get_offered_incompatible_qos_status(&self) -> Result<OfferedIncompatibleQosStatus> {
+    self.fetch_status_changes();
+    let status = self.offered_incompatible_qos_status.get();
+    let mut reset_status = status;
+    reset_status.reset_change();
+    self.offered_incompatible_qos_status.set(reset_status);
 
-    match self.get_qos().liveliness {
-      Some(lv) => {
-        match lv {
-          Liveliness::Automatic { lease_duration: _ } => (),
-          Liveliness::ManualByParticipant { lease_duration: _ } => (),
-          Liveliness::ManualByTopic { lease_duration: _ } => {
-            match self
-              .discovery_command
-              .send(DiscoveryCommand::ASSERT_TOPIC_LIVELINESS {
+    match self
+      .cc_upload
+      .try_send(WriterCommand::ResetOfferedIncompatibleQosStatus {
+        writer_guid: self.get_guid(),
+      }) {
+      Ok(_) => Ok(status),
+      Err(e) => Err(self.command_error("reset OfferedIncompatibleQosStatus of", e)),
+    }
+  }
+
+  /// Unimplemented. <b>Do not use</b>.
+  ///
+  /// # Examples
+  ///
+  /// ```no_run
+  // TODO: enable when functional
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::serialization::CDRSerializerAdapter;
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  ///
+  /// #[derive(Serialize, Deserialize)]
+  /// struct SomeType { a: i32 }
+  /// impl Keyed for SomeType {
+  ///   type K = i32;
+  ///
+  ///   fn get_key(&self) -> Self::K {
+  ///     self.a
+  ///   }
+  /// }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
+  ///
+  /// if let Ok(pms) = data_writer.get_publication_matched_status() {
+  ///   // do something
+  /// }
+  /// ```
+  pub fn get_publication_matched_status(&self) -> Result<PublicationMatchedStatus> {
+    self.fetch_status_changes();
+    let status = self.publication_matched_status.get();
+    let mut reset_status = status;
+    reset_status.reset_change();
+    self.publication_matched_status.set(reset_status);
+
+    match self
+      .cc_upload
+      .try_send(WriterCommand::ResetPublicationMatchedStatus {
+        writer_guid: self.get_guid(),
+      }) {
+      Ok(_) => Ok(status),
+      Err(e) => Err(self.command_error("reset PublicationMatchedStatus of", e)),
+    }
+  }
+
+  /// Takes the oldest status change of this DataWriter, or `None` if there is none.
+  ///
+  /// The DataWriter is `Evented`, and is readable when the RTPS Writer has sent it
+  /// status changes. Register it to a mio `Poll`, and on each event call this until it
+  /// returns `None`. At most 100 changes are kept, and the oldest are dropped first.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::statuses::DataWriterStatus;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::serialization::CDRSerializerAdapter;
+  /// use mio::{Poll, PollOpt, Ready, Token};
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  ///
+  /// #[derive(Serialize, Deserialize)]
+  /// struct SomeType { a: i32 }
+  /// impl Keyed for SomeType {
+  ///   type K = i32;
+  ///
+  ///   fn get_key(&self) -> Self::K {
+  ///     self.a
+  ///   }
+  /// }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
+  ///
+  /// let poll = Poll::new().unwrap();
+  /// poll.register(&data_writer, Token(0), Ready::readable(), PollOpt::edge()).unwrap();
+  ///
+  /// // After a poll event of Token(0)
+  /// while let Some(status) = data_writer.get_status_change() {
+  ///   if let DataWriterStatus::PublicationMatched(pms) = status {
+  ///     println!("{} matched DataReaders", pms.current_count());
+  ///   }
+  /// }
+  /// ```
+  pub fn get_status_change(&self) -> Option<DataWriterStatus> {
+    self.fetch_status_changes();
+    self.status_changes.borrow_mut().pop_front()
+  }
+
+  // Collects the status changes sent by the RTPS Writer
+  fn fetch_status_changes(&self) {
+    while let Ok(status) = self.status_receiver.try_recv() {
+      let status = match status {
+        StatusChange::OfferedDeadlineMissedStatus(status) => {
+          self.offered_deadline_missed_status.set(status);
+          DataWriterStatus::OfferedDeadlineMissed(status)
+        }
+        StatusChange::OfferedIncompatibleQosStatus(status) => {
+          self.offered_incompatible_qos_status.set(status);
+          DataWriterStatus::OfferedIncompatibleQos(status)
+        }
+        StatusChange::PublicationMatchedStatus(status) => {
+          self.publication_matched_status.set(status);
+          DataWriterStatus::PublicationMatched(status)
+        }
+        StatusChange::LivelinessLostStatus(status) => {
+          self.liveliness_lost_status.set(status);
+          DataWriterStatus::LivelinessLost(status)
+        }
+        StatusChange::AcknowledgmentsComplete => DataWriterStatus::AcknowledgmentsComplete,
+        // reader statuses are not sent to DataWriters
+        _ => continue,
+      };
+      let mut status_changes = self.status_changes.borrow_mut();
+      if status_changes.len() >= STATUS_CHANGE_QUEUE_LIMIT {
+        status_changes.pop_front();
+      }
+      status_changes.push_back(status);
+    }
+  }
+
+  /// Topic assigned to this DataWriter
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::serialization::CDRSerializerAdapter;
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  ///
+  /// #[derive(Serialize, Deserialize)]
+  /// struct SomeType { a: i32 }
+  /// impl Keyed for SomeType {
+  ///   type K = i32;
+  ///
+  ///   fn get_key(&self) -> Self::K {
+  ///     self.a
+  ///   }
+  /// }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
+  ///
+  /// assert_eq!(data_writer.get_topic(), &topic);
+  /// ```
+  pub fn get_topic(&self) -> &Topic {
+    &self.my_topic
+  }
+
+  /// Publisher assigned to this DataWriter
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::serialization::CDRSerializerAdapter;
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  ///
+  /// #[derive(Serialize, Deserialize)]
+  /// struct SomeType { a: i32 }
+  /// impl Keyed for SomeType {
+  ///   type K = i32;
+  ///
+  ///   fn get_key(&self) -> Self::K {
+  ///     self.a
+  ///   }
+  /// }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
+  ///
+  /// assert_eq!(data_writer.get_publisher(), &publisher);
+  pub fn get_publisher(&self) -> &Publisher {
+    &self.my_publisher
+  }
+
+  /// Manually asserts liveliness (use this instead of refresh) according to QoS
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::serialization::CDRSerializerAdapter;
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  ///
+  /// #[derive(Serialize, Deserialize)]
+  /// struct SomeType { a: i32 }
+  /// impl Keyed for SomeType {
+  ///   type K = i32;
+  ///
+  ///   fn get_key(&self) -> Self::K {
+  ///     self.a
+  ///   }
+  /// }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
+  ///
+  /// data_writer.assert_liveliness().unwrap();
+  /// ```
+  pub fn assert_liveliness(&self) -> Result<()> {
+    self.refresh_manual_liveliness();
+
+    match self.get_qos().liveliness {
+      Some(lv) => {
+        match lv {
+          Liveliness::Automatic { lease_duration: _ } => (),
+          Liveliness::ManualByParticipant { lease_duration: _ } => (),
+          Liveliness::ManualByTopic { lease_duration: _ } => {
+            match self
+              .discovery_command
+              .send(DiscoveryCommand::ASSERT_TOPIC_LIVELINESS {
                 writer_guid: self.get_guid(),
               }) {
               Ok(_) => (),
@@ -724,21 +1451,21 @@ where
     Ok(())
   }
 
-  /// Unimplemented. <b>Do not use</b>.
+  /// GUIDs of the DataReaders this DataWriter is matched with: the remote ones and
+  /// those of our own participant that subscribe its topic with compatible QoS.
   ///
   /// # Examples
   ///
-  /// ```no_run
-  // TODO: enable when available
+  /// ```
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -756,11 +1483,28 @@ where
   /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
   /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
   ///
-  /// for sub in data_writer.get_matched_subscriptions().iter() {
-  ///   // do something
+  /// for reader_guid in data_writer.get_matched_subscriptions() {
+  ///   if let Some(data) = data_writer.get_matched_subscription_data(reader_guid) {
+  ///     // do something
+  ///   }
   /// }
-  pub fn get_matched_subscriptions(&self) -> Vec<SubscriptionBuiltinTopicData> {
-    todo!()
+  /// ```
+  pub fn get_matched_subscriptions(&self) -> Vec<GUID> {
+    self.matched_readers.get().clone()
+  }
+
+  /// Discovery data of a matched DataReader, remote or of our own participant. See
+  /// [get_matched_subscriptions](#method.get_matched_subscriptions).
+  pub fn get_matched_subscription_data(&self, reader_guid: GUID) -> Option<DiscoveredReaderData> {
+    if !self.matched_readers.get().contains(&reader_guid) {
+      return None;
+    }
+    let dp = self.my_publisher.get_participant()?;
+    let discovery_db = dp.discovery_db();
+    let db = read_lock(&discovery_db);
+    db.get_external_reader(reader_guid)
+      .or_else(|| db.get_local_topic_reader(reader_guid))
+      .cloned()
   }
 
   /// Disposes data instance with specified key
@@ -777,11 +1521,11 @@ where
   /// # use rustdds::dds::DomainParticipant;
   /// # use rustdds::dds::qos::QosPolicyBuilder;
   /// # use rustdds::dds::data_types::TopicKind;
-  /// # use rustdds::dds::With_Key_DataWriter as DataWriter;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
   /// # use rustdds::dds::traits::Keyed;
   /// # use rustdds::serialization::CDRSerializerAdapter;
   /// #
-  /// let domain_participant = DomainParticipant::new(0);
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
   /// let qos = QosPolicyBuilder::new().build();
   /// let publisher = domain_participant.create_publisher(&qos).unwrap();
   ///
@@ -811,42 +1555,168 @@ where
   /// data_writer.write(some_data_2_2, None).unwrap();
   ///
   /// // disposes both some_data_1_1 and some_data_1_2. They are no longer offered by this writer to this topic.
-  /// data_writer.dispose(1, None).unwrap();
+  /// data_writer.dispose(&1, None).unwrap();
   /// ```
-  pub fn dispose(&self, key: <D as Keyed>::K, source_timestamp: Option<Timestamp>) -> Result<()> {
-    /*
+  pub fn dispose(&self, key: &<D as Keyed>::K, source_timestamp: Option<Timestamp>) -> Result<()> {
+    // RTPS Writer sends a DATA submessage with the serialized key instead of data, and
+    // inline QoS parameters KeyHash and StatusInfo_t (see RTPS spec 9.6.3.4) to indicate "disposed".
+    let source_timestamp = self.next_source_timestamp(source_timestamp)?;
+    let ddsdata = DDSData::from_dispose::<D, SA>(key, Some(source_timestamp));
+    let handle = InstanceHandle {
+      key_hash: ddsdata.value_key_hash,
+    };
 
-    Removing this for now, as there is need to redesign the mechanism of transmitting dispose actions
-    to RTPS Writer. Then the RTPS writer needs to execute the dispose. That is done by sending a DATA
-    submessage with the serialized key instead of data, and sending inline QoS parameter
-    StatusInfo_t (see RTPS spec 9.6.3.4) to indicate "disposed"
-    */
+    self.send_data_command(WriterCommand::DDSData { data: ddsdata })?;
+    if self.registered_instances.borrow().contains_key(&handle) {
+      self.disposed_instances.borrow_mut().insert(handle);
+    }
+    Ok(())
+  }
 
-    let mut ddsdata = DDSData::from_dispose::<D>(key.clone(), source_timestamp);
-    // TODO key value should be unique always. This is not always unique.
-    // If sample with same values is given then hash is same for both samples.
-    // TODO FIX THIS
-    ddsdata.value_key_hash = key.into_hash_key();
+  /// Disposes the instance `key` with the given source timestamp.
+  /// The timestamp is handled as in [`write_with_timestamp`](#method.write_with_timestamp).
+  pub fn dispose_with_timestamp(
+    &self,
+    key: &<D as Keyed>::K,
+    source_timestamp: Timestamp,
+  ) -> Result<()> {
+    self.dispose(key, Some(source_timestamp))
+  }
 
-    // What does this block of code do? What is the purpose of _data_sample?
-    let _data_sample: DataSample<D> = match source_timestamp {
-      Some(t) => DataSample::<D>::new_disposed::<<D as Keyed>::K>(t, key, self.get_guid()),
-      None => DataSample::new_disposed::<<D as Keyed>::K>(Timestamp::now(), key, self.get_guid()),
-    };
+  /// Disposes every instance that this DataWriter has registered or written, and not
+  /// unregistered or disposed since. DataReaders get a dispose of each instance, as if
+  /// [`dispose`](#method.dispose) was called for each of them.
+  ///
+  /// Unlike `write`, this does not return `Err(OutOfResources)` when the command queue
+  /// of the DataWriter is full, but waits until there is room for the next dispose.
+  ///
+  /// If the topic QoS has the WriterDataLifecycle policy with
+  /// `autodispose_unregistered_instances`, dropping the DataWriter does this.
+  pub fn dispose_all(&self, source_timestamp: Option<Timestamp>) -> Result<()> {
+    let source_timestamp = self.next_source_timestamp(source_timestamp)?;
+    self.send_dispose_all(source_timestamp)?;
+    self.refresh_manual_liveliness();
+    Ok(())
+  }
 
-    match self
-      .cc_upload
-      .try_send(WriterCommand::DDSData { data: ddsdata })
-    {
-      Ok(_) => {
-        self.refresh_manual_liveliness();
-        Ok(())
-      }
-      Err(huh) => {
-        warn!("Error: {:?}", huh);
-        Err(Error::OutOfResources)
-      }
+  /// Registers an instance to this DataWriter, and returns a handle to it.
+  ///
+  /// The handle can be given to [`write_with_handle`](#method.write_with_handle), which
+  /// does not need to compute the key of the written sample. Writing a sample also registers
+  /// its instance. Registration is local to this DataWriter: RTPS has no message for it,
+  /// so Readers learn about the instance when it is written.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::dds::DomainParticipant;
+  /// # use rustdds::dds::qos::QosPolicyBuilder;
+  /// # use rustdds::dds::data_types::TopicKind;
+  /// # use rustdds::dds::WithKeyDataWriter as DataWriter;
+  /// # use rustdds::dds::traits::Keyed;
+  /// # use rustdds::serialization::CDRSerializerAdapter;
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  ///
+  /// #[derive(Serialize, Deserialize)]
+  /// struct SomeType { a: i32, val: usize }
+  /// impl Keyed for SomeType {
+  ///   type K = i32;
+  ///
+  ///   fn get_key(&self) -> Self::K {
+  ///     self.a
+  ///   }
+  /// }
+  ///
+  /// // WithKey is important
+  /// let topic = domain_participant.create_topic("some_topic", "SomeType", &qos, TopicKind::WithKey).unwrap();
+  /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(None, &topic, None).unwrap();
+  ///
+  /// let handle = data_writer.register_instance(&1);
+  /// data_writer.write_with_handle(SomeType { a: 1, val: 3 }, handle, None).unwrap();
+  ///
+  /// // This writer will no longer update instance 1, but does not dispose it.
+  /// data_writer.unregister_instance(&1, None).unwrap();
+  /// ```
+  pub fn register_instance(&self, key: &<D as Keyed>::K) -> InstanceHandle {
+    self.register_key(key)
+  }
+
+  /// Writes a sample of the instance registered as `handle`, without computing the key
+  /// hash of `data`. The key of `data` must be the key the instance was registered with.
+  /// This is checked only in debug builds.
+  ///
+  /// Returns `Err(PreconditionNotMet)` if the instance is not registered.
+  pub fn write_with_handle(
+    &self,
+    data: D,
+    handle: InstanceHandle,
+    source_timestamp: Option<Timestamp>,
+  ) -> Result<()> {
+    if !self.registered_instances.borrow().contains_key(&handle) {
+      return Err(Error::precondition_not_met(format!(
+        "instance {:?} is not registered",
+        handle
+      )));
     }
+    debug_assert!(
+      data.get_key().into_hash_key() == handle.key_hash,
+      "key of the sample does not match instance {:?}",
+      handle
+    );
+    let source_timestamp = self.next_source_timestamp(source_timestamp)?;
+    self.send_data_command(self.data_command_with_handle(&data, handle, source_timestamp)?)
+  }
+
+  /// Informs Readers that this DataWriter will no longer update the instance `key`.
+  /// Unlike [`dispose`](#method.dispose), this does not delete the instance. When its last
+  /// DataWriter unregisters, the instance becomes NotAlive_NoWriters in Readers.
+  /// If the topic QoS has the WriterDataLifecycle policy with
+  /// `autodispose_unregistered_instances`, the instance is disposed first.
+  ///
+  /// Returns `Err(PreconditionNotMet)` if the instance has not been registered or written.
+  pub fn unregister_instance(
+    &self,
+    key: &<D as Keyed>::K,
+    source_timestamp: Option<Timestamp>,
+  ) -> Result<()> {
+    self.unregister_instance_handle(
+      InstanceHandle {
+        key_hash: key.into_hash_key(),
+      },
+      source_timestamp,
+    )
+  }
+
+  /// Same as [`unregister_instance`](#method.unregister_instance), but the instance is
+  /// identified by a handle from [`register_instance`](#method.register_instance).
+  pub fn unregister_instance_handle(
+    &self,
+    handle: InstanceHandle,
+    source_timestamp: Option<Timestamp>,
+  ) -> Result<()> {
+    if !self.registered_instances.borrow().contains_key(&handle) {
+      return Err(Error::precondition_not_met(format!(
+        "instance {:?} is not registered",
+        handle
+      )));
+    }
+    let source_timestamp = self.next_source_timestamp(source_timestamp)?;
+    let key = self.registered_instances.borrow_mut().remove(&handle);
+    let disposed = self.disposed_instances.borrow_mut().remove(&handle);
+    if self.autodispose() && !disposed {
+      let ddsdata =
+        DDSData::from_serialized_dispose(key.flatten(), handle.key_hash, Some(source_timestamp));
+      self.send_data_command(WriterCommand::DDSData { data: ddsdata })?;
+    }
+
+    let mut ddsdata = DDSData::from_unregister(Some(source_timestamp));
+    ddsdata.value_key_hash = handle.key_hash;
+
+    self.send_data_command(WriterCommand::DDSData { data: ddsdata })
   }
 }
 
@@ -883,6 +1753,140 @@ where
 {
 }
 
+// Readable when the RTPS Writer has sent status changes. See get_status_change.
+impl<D, SA> Evented for DataWriter<'_, D, SA>
+where
+  D: Keyed + Serialize,
+  SA: SerializerAdapter<D>,
+{
+  fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    self.status_receiver.register(poll, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &Poll,
+    token: Token,
+    interest: Ready,
+    opts: PollOpt,
+  ) -> io::Result<()> {
+    self.status_receiver.reregister(poll, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &Poll) -> io::Result<()> {
+    self.status_receiver.deregister(poll)
+  }
+}
+
+#[cfg(feature = "async")]
+mod async_io {
+  use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+  };
+
+  use super::*;
+
+  impl<'a, D, SA> DataWriter<'a, D, SA>
+  where
+    D: Keyed + Serialize,
+    <D as Keyed>::K: Key,
+    SA: SerializerAdapter<D>,
+  {
+    /// Asynchronous version of [write](#method.write).
+    ///
+    /// Where `write` fails if the DataWriter has too many samples waiting to be
+    /// processed, the returned future waits until there is room. It resolves when
+    /// the sample has been accepted for sending. Acknowledgements from reliable
+    /// DataReaders are not waited for.
+    ///
+    /// Requires feature `async`.
+    pub fn async_write(
+      &self,
+      data: D,
+      source_timestamp: Option<Timestamp>,
+    ) -> WriteFuture<'_, 'a, D, SA> {
+      let (command, rejected) = match self.data_command(&data, source_timestamp) {
+        Ok(c) => (Some(c), None),
+        Err(e) => (None, Some(e)),
+      };
+      WriteFuture {
+        writer: self,
+        command,
+        rejected,
+      }
+    }
+
+    pub(crate) fn poll_write_command(
+      &self,
+      command: &mut Option<WriterCommand>,
+      cx: &mut Context<'_>,
+    ) -> Poll<Result<()>> {
+      let cmd = match command.take() {
+        Some(c) => c,
+        None => {
+          return Poll::Ready(Err(Error::precondition_not_met(
+            "WriteFuture polled after completion",
+          )))
+        }
+      };
+      // Register before trying, so that the event loop wakes us if it makes room
+      // right after a failed attempt.
+      if !self.command_waker.register(cx.waker()) {
+        return Poll::Ready(Err(Error::precondition_not_met(format!(
+          "RTPS Writer of DataWriter {:?} has been removed",
+          self.get_guid()
+        ))));
+      }
+      match self.cc_upload.try_send(cmd) {
+        Ok(_) => {
+          self.ack_waiter.add_written();
+          self.refresh_manual_liveliness();
+          Poll::Ready(Ok(()))
+        }
+        Err(mio_channel::TrySendError::Full(cmd)) => {
+          *command = Some(cmd);
+          Poll::Pending
+        }
+        Err(e) => Poll::Ready(Err(self.command_error("write", e))),
+      }
+    }
+  }
+
+  /// Future returned by [DataWriter::async_write](struct.DataWriter.html#method.async_write).
+  pub struct WriteFuture<'w, 'a, D, SA>
+  where
+    D: Keyed + Serialize,
+    SA: SerializerAdapter<D>,
+  {
+    writer: &'w DataWriter<'a, D, SA>,
+    command: Option<WriterCommand>,
+    // The write was rejected before sending, e.g. due to its source timestamp.
+    rejected: Option<Error>,
+  }
+
+  impl<'w, 'a, D, SA> Future for WriteFuture<'w, 'a, D, SA>
+  where
+    D: Keyed + Serialize,
+    <D as Keyed>::K: Key,
+    SA: SerializerAdapter<D>,
+  {
+    type Output = Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+      let this = self.get_mut();
+      if let Some(e) = this.rejected.take() {
+        return Poll::Ready(Err(e));
+      }
+      this.writer.poll_write_command(&mut this.command, cx)
+    }
+  }
+}
+
+#[cfg(feature = "async")]
+pub use async_io::WriteFuture;
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -891,12 +1895,16 @@ mod tests {
   use std::thread;
   use crate::dds::traits::key::Keyed;
   use crate::serialization::cdr_serializer::CDRSerializerAdapter;
+  use crate::serialization::cdr_deserializer::CDRDeserializerAdapter;
+  use crate::dds::qos::{policy::ResourceLimits, QosPolicyBuilder};
+  use crate::structure::duration::Duration as DDSDuration;
+  use crate::structure::topic_kind::TopicKind;
   use byteorder::LittleEndian;
   use log::info;
 
   #[test]
   fn dw_write_test() {
-    let domain_participant = DomainParticipant::new(0);
+    let domain_participant = DomainParticipant::new(0).unwrap();
     let qos = QosPolicies::qos_none();
     let _default_dw_qos = QosPolicies::qos_none();
     let publisher = domain_participant
@@ -932,7 +1940,7 @@ mod tests {
 
   #[test]
   fn dw_dispose_test() {
-    let domain_participant = DomainParticipant::new(0);
+    let domain_participant = DomainParticipant::new(0).unwrap();
     let qos = QosPolicies::qos_none();
     let publisher = domain_participant
       .create_publisher(&qos)
@@ -960,15 +1968,267 @@ mod tests {
 
     thread::sleep(Duration::from_millis(100));
     data_writer
-      .dispose(data.get_key(), None)
+      .dispose(&data.get_key(), None)
       .expect("Unable to dispose data");
 
     // TODO: verify that dispose is sent correctly
   }
 
+  #[test]
+  fn dw_register_unregister_test() {
+    let domain_participant = DomainParticipant::new(0).unwrap();
+    let qos = QosPolicies::qos_none();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic("Aasii", "Huh?", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+
+    let data_writer: DataWriter<'_, RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(None, &topic, None)
+        .expect("Failed to create datawriter");
+
+    let data = RandomData {
+      a: 4,
+      b: "Fobar".to_string(),
+    };
+
+    let handle = data_writer.register_instance(&data.get_key());
+    data_writer
+      .write_with_handle(data.clone(), handle, None)
+      .expect("Unable to write data with handle");
+
+    data_writer
+      .unregister_instance_handle(handle, None)
+      .expect("Unable to unregister instance");
+
+    // no longer registered
+    assert!(matches!(
+      data_writer.write_with_handle(data.clone(), handle, None),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    assert!(matches!(
+      data_writer.unregister_instance(&data.get_key(), None),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+
+    // writing registers again
+    data_writer
+      .write(data.clone(), None)
+      .expect("Unable to write data");
+    data_writer
+      .unregister_instance(&data.get_key(), None)
+      .expect("Unable to unregister written instance");
+  }
+
+  #[test]
+  fn dw_source_timestamp_order_test() {
+    let domain_participant = DomainParticipant::new(0).unwrap();
+    let qos = QosPolicyBuilder::new()
+      .destination_order(DestinationOrder::BySourceTimeStamp)
+      .build();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic("Aasii", "Huh?", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+
+    let data_writer: DataWriter<'_, RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(None, &topic, None)
+        .expect("Failed to create datawriter");
+
+    let data = RandomData {
+      a: 4,
+      b: "Fobar".to_string(),
+    };
+    let now = Timestamp::now();
+    let earlier = now - DDSDuration::from_millis(10);
+
+    data_writer
+      .write_with_timestamp(data.clone(), now)
+      .expect("Unable to write data with timestamp");
+    assert!(matches!(
+      data_writer.write_with_timestamp(data.clone(), earlier),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    assert!(matches!(
+      data_writer.dispose_with_timestamp(&data.get_key(), earlier),
+      Err(Error::PreconditionNotMet { .. })
+    ));
+    // same timestamp is not older
+    data_writer
+      .dispose_with_timestamp(&data.get_key(), now)
+      .expect("Unable to dispose with timestamp");
+  }
+
+  #[test]
+  fn dw_batching_test() {
+    // A domain of its own, so that readers of other tests are not matched.
+    let domain_participant = DomainParticipant::new(63).unwrap();
+    let reader_participant = DomainParticipant::new(63).unwrap();
+    let qos = QosPolicies::qos_none();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic("Batched", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let reader_topic = reader_participant
+      .create_topic("Batched", "RandomData", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let subscriber = reader_participant
+      .create_subscriber(&qos)
+      .expect("Failed to create subscriber");
+    let data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &reader_topic,
+        None,
+        None,
+      )
+      .expect("Failed to create datareader");
+
+    let data_writer: DataWriter<'_, RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(None, &topic, None)
+        .expect("Failed to create datawriter");
+
+    let reader_guid = data_reader.get_guid();
+    for _ in 0..100 {
+      if data_writer
+        .get_matched_subscriptions()
+        .contains(&reader_guid)
+      {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert!(data_writer
+      .get_matched_subscriptions()
+      .contains(&reader_guid));
+
+    assert!(matches!(
+      data_writer.set_batching(0, 1024, Duration::from_millis(10)),
+      Err(Error::BadParameter { .. })
+    ));
+    // The delay is long, so that only a full batch or a flush sends anything.
+    data_writer
+      .set_batching(4, 1024, Duration::from_secs(10))
+      .expect("Unable to set batching");
+
+    let write = |count| {
+      for a in 0..count {
+        let data = RandomData {
+          a,
+          b: "Fobar".to_string(),
+        };
+        data_writer.write(data, None).expect("Unable to write data");
+      }
+    };
+    // Waits until data_sent DATA have been sent, and returns the statistics then.
+    let sent = |data_sent| {
+      for _ in 0..100 {
+        if data_writer.get_statistics().data_sent >= data_sent {
+          break;
+        }
+        thread::sleep(Duration::from_millis(50));
+      }
+      data_writer.get_statistics()
+    };
+
+    // A full batch is one message.
+    write(4);
+    let statistics = sent(4);
+    assert_eq!(statistics.data_sent, 4);
+    assert_eq!(statistics.messages_sent, 1);
+
+    // So is the rest when flushed.
+    write(2);
+    data_writer.flush().expect("Unable to flush");
+    let statistics = sent(6);
+    assert_eq!(statistics.data_sent, 6);
+    assert_eq!(statistics.messages_sent, 2);
+
+    // batching off again
+    data_writer
+      .set_batching(1, 1024, Duration::from_millis(10))
+      .expect("Unable to set batching");
+    write(2);
+    let statistics = sent(8);
+    assert_eq!(statistics.data_sent, 8);
+    assert_eq!(statistics.messages_sent, 4);
+  }
+
+  #[test]
+  fn dw_reliable_history_limit_test() {
+    let domain_participant = DomainParticipant::new(0).unwrap();
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: DDSDuration::from_secs(2),
+      })
+      .history(History::KeepAll)
+      .resource_limits(ResourceLimits {
+        max_samples: 2,
+        max_instances: 2,
+        max_samples_per_instance: 2,
+      })
+      .build();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic("Aasii", "Huh?", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+
+    let data_writer: DataWriter<'_, RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(None, &topic, None)
+        .expect("Failed to create datawriter");
+
+    assert_eq!(
+      data_writer.history_limit(),
+      Some((2, Duration::from_secs(2)))
+    );
+    // Without matched readers nobody needs to acknowledge, so the history never stays full.
+    for a in 0..5 {
+      let data = RandomData {
+        a,
+        b: "Fobar".to_string(),
+      };
+      data_writer.write(data, None).expect("Unable to write data");
+    }
+
+    // KeepLast history replaces old samples instead of waiting for room.
+    let keep_last_qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: DDSDuration::from_secs(2),
+      })
+      .history(History::KeepLast { depth: 2 })
+      .resource_limits(ResourceLimits {
+        max_samples: 2,
+        max_instances: 2,
+        max_samples_per_instance: 2,
+      })
+      .build();
+    let keep_last_topic = domain_participant
+      .create_topic("KeepLastLimit", "Huh?", &keep_last_qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let keep_last_writer: DataWriter<
+      '_,
+      RandomData,
+      CDRSerializerAdapter<RandomData, LittleEndian>,
+    > = publisher
+      .create_datawriter(None, &keep_last_topic, None)
+      .expect("Failed to create datawriter");
+    assert_eq!(keep_last_writer.history_limit(), None);
+  }
+
   #[test]
   fn dw_wait_for_ack_test() {
-    let domain_participant = DomainParticipant::new(0);
+    let domain_participant = DomainParticipant::new(0).unwrap();
     let qos = QosPolicies::qos_none();
     let publisher = domain_participant
       .create_publisher(&qos)
@@ -994,4 +2254,162 @@ mod tests {
       .unwrap();
     assert_eq!(res, ());
   }
+
+  #[test]
+  fn dw_topic_qos_in_cache() {
+    let domain_participant = DomainParticipant::new(0).unwrap();
+    let qos = QosPolicyBuilder::new()
+      .history(History::KeepLast { depth: 7 })
+      .build();
+    let publisher = domain_participant
+      .create_publisher(&QosPolicies::qos_none())
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic("CacheQos", "Huh?", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let _data_writer: DataWriter<'_, RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(None, &topic, None)
+        .expect("Failed to create datawriter");
+
+    let dds_cache = domain_participant.get_dds_cache();
+    let cache = dds_cache.read().unwrap();
+    let topic_qos = cache.get_topic_qos(&"CacheQos".to_string()).unwrap();
+    assert_eq!(topic_qos.history, Some(History::KeepLast { depth: 7 }));
+  }
+
+  #[test]
+  fn dw_status_change_test() {
+    use mio::Events;
+    use crate::serialization::cdr_deserializer::CDRDeserializerAdapter;
+
+    let domain_participant = DomainParticipant::new(50).unwrap();
+    let qos = QosPolicies::qos_none();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let subscriber = domain_participant
+      .create_subscriber(&qos)
+      .expect("Failed to create subscriber");
+    let topic = domain_participant
+      .create_topic("StatusChange", "Huh?", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let data_writer: DataWriter<'_, RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(None, &topic, None)
+        .expect("Failed to create datawriter");
+    assert!(data_writer.get_status_change().is_none());
+
+    let poll = Poll::new().unwrap();
+    poll
+      .register(&data_writer, Token(0), Ready::readable(), PollOpt::edge())
+      .unwrap();
+    let _data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None, None)
+      .expect("Failed to create datareader");
+
+    let mut events = Events::with_capacity(4);
+    let mut matched = None;
+    for _ in 0..50 {
+      poll
+        .poll(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+      while let Some(status) = data_writer.get_status_change() {
+        if let DataWriterStatus::PublicationMatched(status) = status {
+          matched = Some(status.current_count());
+        }
+      }
+      if matched.is_some() {
+        break;
+      }
+    }
+    assert_eq!(matched, Some(1));
+    assert!(data_writer.get_status_change().is_none());
+    // the queue and the status getter see the same changes
+    assert_eq!(
+      data_writer
+        .get_publication_matched_status()
+        .unwrap()
+        .current_count(),
+      1
+    );
+  }
+
+  #[test]
+  fn dw_dispose_all_on_drop_test() {
+    use crate::dds::{
+      qos::policy::WriterDataLifecycle, readcondition::ReadCondition, sampleinfo::InstanceState,
+    };
+    use crate::serialization::cdr_deserializer::CDRDeserializerAdapter;
+
+    const INSTANCES: i64 = 1000;
+    let domain_participant = DomainParticipant::new(61).unwrap();
+    let qos = QosPolicyBuilder::new()
+      .writer_data_lifecycle(WriterDataLifecycle {
+        autodispose_unregistered_instances: true,
+      })
+      .build();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let subscriber = domain_participant
+      .create_subscriber(&qos)
+      .expect("Failed to create subscriber");
+    let topic = domain_participant
+      .create_topic("DisposeAll", "Huh?", &qos, TopicKind::WithKey)
+      .expect("Failed to create topic");
+    let mut data_reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None, None)
+      .expect("Failed to create datareader");
+    let data_writer: DataWriter<'_, RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(None, &topic, None)
+        .expect("Failed to create datawriter");
+
+    for _ in 0..50 {
+      let matched = data_writer.get_publication_matched_status().unwrap();
+      if matched.current_count() == 1 {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    for a in 0..INSTANCES {
+      let data = RandomData {
+        a,
+        b: "alive".to_string(),
+      };
+      // The command queue of the DataWriter is shorter than the burst.
+      while let Err(Error::OutOfResources { .. }) = data_writer.write(data.clone(), None) {
+        thread::sleep(Duration::from_millis(1));
+      }
+    }
+    // one dispose of each instance
+    drop(data_writer);
+
+    let mut states = BTreeMap::new();
+    for _ in 0..100 {
+      let samples = data_reader
+        .take(2 * INSTANCES as usize, ReadCondition::any())
+        .unwrap();
+      for sample in samples {
+        let key = match sample.value() {
+          Ok(data) => data.get_key(),
+          Err(key) => *key,
+        };
+        states.insert(key, sample.sample_info().instance_state);
+      }
+      if states.len() == INSTANCES as usize
+        && states
+          .values()
+          .all(|s| *s == InstanceState::NotAlive_Disposed)
+      {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(states.len(), INSTANCES as usize);
+    assert!(states
+      .values()
+      .all(|s| *s == InstanceState::NotAlive_Disposed));
+  }
 }
@@ -5,3 +5,4 @@ pub(crate) mod datawriter;
 pub use datareader::*;
 pub use datasample::*;
 pub use datawriter::*;
+pub use crate::dds::datasample_cache::LoanedSamples;
@@ -12,6 +12,8 @@ use std::{
   collections::{HashSet, HashMap, BTreeMap, hash_map::DefaultHasher},
 };
 use std::hash::Hasher;
+use std::cell::{Cell, RefCell};
+use std::time::Instant;
 
 //use crate::messages::submessages::info_destination::InfoDestination;
 use crate::{
@@ -20,47 +22,90 @@ use crate::{
     submessage::EntitySubmessage,
     info_timestamp::InfoTimestamp,
     submessage_elements::{parameter::Parameter, parameter_list::ParameterList},
-    submessage_elements::serialized_payload::RepresentationIdentifier,
     submessage_flag::*,
   },
   structure::parameter_id::ParameterId,
 };
 use crate::messages::submessages::data::Data;
-use crate::structure::time::Timestamp;
+use crate::structure::{clock::SharedClock, dds_cache::CacheInstant, time::Timestamp};
 use crate::structure::duration::Duration;
 use crate::messages::protocol_version::ProtocolVersion;
 use crate::messages::{header::Header, vendor_id::VendorId, protocol_id::ProtocolId};
 use crate::structure::guid::{GuidPrefix, EntityId, GUID};
-use crate::structure::sequence_number::{SequenceNumber};
+use crate::structure::sequence_number::{SequenceNumber, SequenceNumberSet};
 use crate::{
   messages::submessages::submessages::{
-    Heartbeat, SubmessageHeader, SubmessageKind, InterpreterSubmessage, AckNack, InfoDestination,
+    Gap, Heartbeat, SubmessageHeader, SubmessageKind, InterpreterSubmessage, AckNack,
+    InfoDestination,
   },
   structure::cache_change::{CacheChange, ChangeKind},
+  structure::inline_qos::KeyHash,
   serialization::{SubMessage, Message, SubmessageBody},
 };
 
-use crate::dds::{ddsdata::DDSData, qos::HasQoSPolicy};
+use crate::dds::{
+  ack_waiter::AckWaiter,
+  ddsdata::DDSData,
+  qos::HasQoSPolicy,
+  statistics::{NetworkCounters, WriterCounters},
+  task_waker::TaskWaker,
+};
+use crate::dds::rtps_config::RtpsWriterConfig;
+use crate::common::lock::read_lock;
+use crate::dds::flow_control::{Datagram, FlowControlConfig, FlowController, QueueDepth, Traffic};
+use crate::dds::matched_readers::MatchedReaders;
+use crate::discovery::discovery_config::DEFAULT_MAX_MESSAGE_SIZE;
+#[cfg(feature = "shm")]
+use crate::network::shm::ShmWriter;
 use crate::{
-  network::{constant::TimerMessageType, udp_sender::UDPSender},
+  network::{
+    capture::PacketCapture, constant::TimerMessageType, local_network::LocalNetwork,
+    tcp::TcpTransport, udp_sender::UDPSender, util::is_network_down,
+  },
   structure::{
     entity::{Entity, EntityAttributes},
     endpoint::{EndpointAttributes, Endpoint},
-    locator::LocatorKind,
+    locator::{LocatorKind, LocatorList},
     dds_cache::DDSCache,
   },
   common::timed_event_handler::{TimedEventHandler},
 };
 use super::{
-  qos::{policy, QosPolicies},
+  qos::{policy, QosPolicies, QosPolicyId},
   rtps_reader_proxy::RtpsReaderProxy,
   util::writer_util::WriterUtil,
+  values::result::LivelinessLostStatus,
   values::result::OfferedDeadlineMissedStatus,
+  values::result::OfferedIncompatibleQosStatus,
+  values::result::PublicationMatchedStatus,
   values::result::StatusChange,
 };
 use policy::{History, Reliability};
 //use crate::messages::submessages::submessage_elements::serialized_payload::SerializedPayload;
 
+// Serialized lengths of the RTPS message header, of a submessage header, and of an
+// InfoTimestamp submessage.
+const MESSAGE_HEADER_LEN: usize = 20;
+const SUBMESSAGE_HEADER_LEN: usize = 4;
+const INFO_TIMESTAMP_LEN: usize = 12;
+
+// While the network stays unreachable, udp_sender is recreated at most this often.
+const UDP_SENDER_RECREATE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+// How much longer than its serialized payload a message carrying a single DATA is at
+// most: the message header, InfoTimestamp, the DATA submessage header and fixed fields,
+// the encapsulation header, and the inline QoS we send, which is KeyHash, StatusInfo,
+// the related sample identity twice and the sentinel.
+pub(crate) const DATA_MESSAGE_OVERHEAD: usize = MESSAGE_HEADER_LEN
+  + INFO_TIMESTAMP_LEN
+  + SUBMESSAGE_HEADER_LEN
+  + 20
+  + 4
+  + (4 + 16)
+  + (4 + 4)
+  + 2 * (4 + 24)
+  + 4;
+
 pub(crate) struct Writer {
   source_version: ProtocolVersion,
   source_vendor_id: VendorId,
@@ -81,8 +126,6 @@ pub(crate) struct Writer {
   ///availability of data by sending a
   ///Heartbeat Message.
   pub heartbeat_period: Option<Duration>,
-  /// duration to launch cahche change remove from DDSCache
-  pub cahce_cleaning_perioid: Duration,
   ///Protocol tuning parameter that
   ///allows the RTPS Writer to delay
   ///the response to a request for data
@@ -115,18 +158,40 @@ pub(crate) struct Writer {
   ///The RTPS ReaderProxy class represents the information an RTPS StatefulWriter maintains on each matched
   ///RTPS Reader
   pub readers: Vec<RtpsReaderProxy>,
+  // Matched readers of our own participant. They get our changes in memory from the
+  // event loop, so there is nothing to send or acknowledge.
+  local_readers: Vec<GUID>,
   message: Option<Message>,
-  udp_sender: UDPSender,
+  // Replaced when sending fails because the network is unreachable
+  udp_sender: RefCell<UDPSender>,
+  // When udp_sender was last replaced
+  udp_sender_recreated: Cell<Option<Instant>>,
+  // The participant's network, to configure a replaced udp_sender
+  network: LocalNetwork,
+  // counts the replaced udp_senders, shared with the participant
+  network_counters: Arc<NetworkCounters>,
+  // Largest message udp_sender sends. Batches are split to fit.
+  max_message_size: usize,
+  // Our segment for readers on this host
+  #[cfg(feature = "shm")]
+  shm: Option<ShmWriter>,
+  // Connections of the participant, if it uses TCP
+  tcp: Option<TcpTransport>,
+  // Traffic capture of the participant, for what is not sent through udp_sender
+  capture: PacketCapture,
+  // The participant is passive: nothing is sent.
+  passive: bool,
   // This writer can read/write to only one of this DDSCache topic caches identified with my_topic_name
   dds_cache: Arc<RwLock<DDSCache>>,
+  clock: SharedClock,
   /// Writer can only read/write to this topic DDSHistoryCache.
   my_topic_name: String,
   /// Maps this writers local sequence numbers to DDSHistodyCache instants.
   /// Useful when negative acknack is recieved.
-  sequence_number_to_instant: BTreeMap<SequenceNumber, Timestamp>,
+  sequence_number_to_instant: BTreeMap<SequenceNumber, CacheInstant>,
   //// Maps this writers local sequence numbers to DDSHistodyCache instants.
   /// Useful when datawriter dispose is recieved.
-  key_to_instant: HashMap<u128, Timestamp>,
+  key_to_instant: HashMap<u128, CacheInstant>,
   /// Set of disposed samples.
   /// Useful when reader requires some sample with acknack.
   disposed_sequence_numbers: HashSet<SequenceNumber>,
@@ -145,11 +210,59 @@ pub(crate) struct Writer {
   // Used for sending status info about messages sent
   status_sender: SyncSender<StatusChange>,
   offered_deadline_status: OfferedDeadlineMissedStatus,
+  offered_incompatible_qos_status: OfferedIncompatibleQosStatus,
+  publication_matched_status: PublicationMatchedStatus,
+  liveliness_lost_status: LivelinessLostStatus,
+  // when the DataWriter last wrote or asserted its liveliness, and whether the lease
+  // has run out since
+  last_liveliness_assertion: Timestamp,
+  liveliness_lost: bool,
+  // whether all changes were acknowledged the last time acknowledgments changed
+  acknowledgments_complete: bool,
+
+  // wakes async tasks waiting for room in writer_command channel
+  command_waker: TaskWaker,
+  // tells reliable DataWriter how many of its changes all readers have acknowledged
+  ack_waiter: AckWaiter,
+  // tells DataWriter which readers its reader filter applies to
+  matched_readers: MatchedReaders,
+
+  // When set, new changes are not sent immediately, but collected to larger messages.
+  batching: Option<BatchingPolicy>,
+  // changes and payload bytes waiting in the current batch
+  batched_samples: usize,
+  batched_bytes: usize,
+
+  rtps_config: RtpsWriterConfig,
+  // a nack response timer is running
+  nack_response_pending: bool,
+
+  // When set, messages wait here for their turn to be sent within the rate limit.
+  flow_controller: Option<RefCell<FlowController>>,
+  // tells DataWriter how many messages are waiting in the flow controller
+  flow_control_queue_depth: QueueDepth,
+  // protocol statistics, shared with the DataWriter and the participant
+  statistics: Arc<WriterCounters>,
+}
+
+/// Limits of a batch of changes sent in one RTPS message. A batch is sent when any limit is hit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BatchingPolicy {
+  pub max_samples: usize,
+  pub max_bytes: usize,
+  pub max_delay: Duration,
 }
 
 pub(crate) enum WriterCommand {
   DDSData { data: DDSData },
   ResetOfferedDeadlineMissedStatus { writer_guid: GUID },
+  ResetOfferedIncompatibleQosStatus { writer_guid: GUID },
+  ResetPublicationMatchedStatus { writer_guid: GUID },
+  ResetLivelinessLostStatus { writer_guid: GUID },
+  SetBatching { batching: Option<BatchingPolicy> },
+  SetRtpsConfig { config: RtpsWriterConfig },
+  SetFlowControl { config: FlowControlConfig },
+  Flush,
 }
 
 impl Writer {
@@ -162,31 +275,10 @@ impl Writer {
     status_sender: SyncSender<StatusChange>,
   ) -> Writer {
     let entity_attributes = EntityAttributes::new(guid);
-
-    let heartbeat_period = match &qos_policies.reliability {
-      Some(r) => match r {
-        Reliability::BestEffort => None,
-        Reliability::Reliable {
-          max_blocking_time: _,
-        } => Some(Duration::from_secs(1)),
-      },
-      None => None,
-    };
-
-    let heartbeat_period = match heartbeat_period {
-      Some(hbp) => match qos_policies.liveliness {
-        Some(lv) => match lv {
-          policy::Liveliness::Automatic { lease_duration: _ } => Some(hbp),
-          policy::Liveliness::ManualByParticipant { lease_duration: _ } => Some(hbp),
-          policy::Liveliness::ManualByTopic { lease_duration } => {
-            let std_dur = Duration::from(lease_duration);
-            Some(std_dur / 3)
-          }
-        },
-        None => Some(hbp),
-      },
-      None => None,
-    };
+    let rtps_config = RtpsWriterConfig::default();
+    let heartbeat_period = Writer::heartbeat_period_for(&qos_policies, &rtps_config);
+    let clock = read_lock(&dds_cache).clock();
+    let now = clock.now();
 
     Writer {
       source_version: ProtocolVersion::PROTOCOLVERSION_2_3,
@@ -195,9 +287,8 @@ impl Writer {
       heartbeat_message_counter: 1,
       push_mode: true,
       heartbeat_period,
-      cahce_cleaning_perioid: Duration::from_secs(2 * 60),
-      nack_respose_delay: Duration::from_millis(200),
-      nack_suppression_duration: Duration::from_millis(0),
+      nack_respose_delay: rtps_config.nack_response_delay.into(),
+      nack_suppression_duration: rtps_config.nack_suppression_duration.into(),
       last_change_sequence_number: SequenceNumber::from(0),
       first_change_sequence_number: SequenceNumber::from(0),
       data_max_size_serialized: 999999999,
@@ -210,10 +301,21 @@ impl Writer {
         RtpsReaderProxy::new_for_unit_testing(1001),
         RtpsReaderProxy::new_for_unit_testing(1002),*/
       ],
+      local_readers: Vec::new(),
       message: None,
       endpoint_attributes: EndpointAttributes::default(),
-      udp_sender: UDPSender::new_with_random_port(),
+      udp_sender: RefCell::new(UDPSender::new_with_random_port()),
+      udp_sender_recreated: Cell::new(None),
+      network: LocalNetwork::default(),
+      network_counters: Arc::new(NetworkCounters::default()),
+      max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+      #[cfg(feature = "shm")]
+      shm: None,
+      tcp: None,
+      capture: PacketCapture::default(),
+      passive: false,
       dds_cache,
+      clock,
       my_topic_name: topic_name,
       sequence_number_to_instant: BTreeMap::new(),
       key_to_instant: HashMap::new(),
@@ -222,6 +324,198 @@ impl Writer {
       qos_policies,
       status_sender,
       offered_deadline_status: OfferedDeadlineMissedStatus::new(),
+      offered_incompatible_qos_status: OfferedIncompatibleQosStatus::new(),
+      publication_matched_status: PublicationMatchedStatus::new(),
+      liveliness_lost_status: LivelinessLostStatus::new(),
+      last_liveliness_assertion: now,
+      liveliness_lost: false,
+      acknowledgments_complete: true,
+      command_waker: TaskWaker::new(),
+      ack_waiter: AckWaiter::new(),
+      matched_readers: MatchedReaders::new(),
+      batching: None,
+      batched_samples: 0,
+      batched_bytes: 0,
+      rtps_config,
+      nack_response_pending: false,
+      flow_controller: None,
+      flow_control_queue_depth: QueueDepth::new(),
+      statistics: Arc::new(WriterCounters::default()),
+    }
+  }
+
+  // Reliable writers heartbeat. With ManualByTopic liveliness the heartbeats also check
+  // liveliness, and with Automatic liveliness they assert it, so at least three of them
+  // must fit within the lease. Best effort writers heartbeat only for Automatic liveliness.
+  fn heartbeat_period_for(qos: &QosPolicies, config: &RtpsWriterConfig) -> Option<Duration> {
+    let heartbeat_period = match &qos.reliability {
+      Some(Reliability::Reliable {
+        max_blocking_time: _,
+      }) => Some(Duration::from(config.heartbeat_period)),
+      _ => None,
+    };
+    let liveliness_period = match qos.liveliness {
+      Some(policy::Liveliness::Automatic { lease_duration })
+        if lease_duration != Duration::DURATION_INFINITE =>
+      {
+        Some(lease_duration / 3)
+      }
+      Some(policy::Liveliness::ManualByTopic { lease_duration }) if heartbeat_period.is_some() => {
+        Some(lease_duration / 3)
+      }
+      _ => None,
+    };
+    match (heartbeat_period, liveliness_period) {
+      (Some(heartbeat), Some(liveliness)) => Some(std::cmp::min(heartbeat, liveliness)),
+      (heartbeat, liveliness) => heartbeat.or(liveliness),
+    }
+  }
+
+  /// Takes new protocol tuning into use. The heartbeat timer restarts with the new period.
+  pub fn set_rtps_config(&mut self, config: RtpsWriterConfig) {
+    self.heartbeat_period = Writer::heartbeat_period_for(&self.qos_policies, &config);
+    self.nack_respose_delay = config.nack_response_delay.into();
+    self.nack_suppression_duration = config.nack_suppression_duration.into();
+    self.rtps_config = config;
+    self.set_heartbeat_timer();
+  }
+
+  pub(crate) fn command_waker(&self) -> TaskWaker {
+    self.command_waker.clone()
+  }
+
+  // Entity kind tells if the topic is keyed, both for user-defined and built-in writers.
+  fn is_keyed(&self) -> bool {
+    self.get_entity_id().get_kind() & 0x0F == 0x02
+  }
+
+  pub(crate) fn ack_waiter(&self) -> AckWaiter {
+    self.ack_waiter.clone()
+  }
+
+  pub(crate) fn flow_control_queue_depth(&self) -> QueueDepth {
+    self.flow_control_queue_depth.clone()
+  }
+
+  pub(crate) fn matched_readers(&self) -> MatchedReaders {
+    self.matched_readers.clone()
+  }
+
+  // Lets the DataWriter know the current matched readers, remote and local, and
+  // whether some of them is sent to over UDP.
+  fn update_matched_readers_of_datawriter(&self) {
+    let readers = self
+      .readers
+      .iter()
+      .map(|r| r.remote_reader_guid)
+      .chain(self.local_readers.iter().copied())
+      .collect();
+    let over_udp = self.readers.iter().any(|r| {
+      r.unicast_locator_list
+        .iter()
+        .chain(r.multicast_locator_list.iter())
+        .any(|l| l.kind.is_udp())
+    });
+    self.matched_readers.set(readers, over_udp);
+  }
+
+  pub(crate) fn statistics(&self) -> Arc<WriterCounters> {
+    self.statistics.clone()
+  }
+
+  // Reports to the DataWriter how many changes are acknowledged by all matched reliable
+  // readers. Best effort readers never acknowledge, so without reliable readers nobody
+  // needs to acknowledge anything.
+  // Also lets the DataWriter know when acknowledgments become complete.
+  fn update_ack_waiter(&mut self) {
+    let last = self.last_change_sequence_number;
+    let acked = self
+      .readers
+      .iter()
+      .filter(|r| r.is_reliable)
+      .map(|r| r.all_acked_up_to().min(last))
+      .min()
+      .unwrap_or(last);
+    self.ack_waiter.set_acked(i64::from(acked));
+
+    let complete = acked == last;
+    if complete && !self.acknowledgments_complete {
+      if let Err(e) = self
+        .status_sender
+        .try_send(StatusChange::AcknowledgmentsComplete)
+      {
+        error!("Failed to send acknowledgments complete status. {:?}", e);
+      }
+    }
+    self.acknowledgments_complete = complete;
+  }
+
+  // The DataWriter asserted its liveliness, by writing or explicitly.
+  pub fn assert_liveliness(&mut self) {
+    self.last_liveliness_assertion = self.clock.now();
+    self.liveliness_lost = false;
+  }
+
+  // A writer with Automatic liveliness asserts it with a HEARTBEAT, when it has not
+  // written within a third of the lease. Returns true if it did.
+  fn assert_automatic_liveliness(&mut self) -> bool {
+    let lease_duration = match self.qos_policies.liveliness {
+      Some(policy::Liveliness::Automatic { lease_duration })
+        if lease_duration != Duration::DURATION_INFINITE =>
+      {
+        lease_duration
+      }
+      _ => return false,
+    };
+    if self.clock.now() - self.last_liveliness_assertion < lease_duration / 3 {
+      return false;
+    }
+    self.assert_liveliness();
+    self.send_liveliness_heartbeat();
+    true
+  }
+
+  /// Sends the matched readers a HEARTBEAT with the liveliness flag, which asserts the
+  /// liveliness of this writer to them, whatever its liveliness kind.
+  pub fn send_liveliness_heartbeat(&mut self) {
+    dds_event!(trace, "liveliness asserted", writer = self.get_guid());
+    let message_header: Header = self.create_message_header();
+    let endianness = self.endianness;
+    for reader in self.readers.iter() {
+      match MessageBuilder::new()
+        .header(message_header.clone())
+        .dst_submessage(endianness, reader.remote_reader_guid.guidPrefix)
+        .heartbeat_msg(&self, reader.remote_reader_guid, true, true)
+        .build()
+      {
+        Ok(m) => self.send_message_to_reader(&m, reader, Traffic::Repair),
+        Err(e) => warn!("Failed to build liveliness heartbeat message. {}", e),
+      }
+    }
+    self.increase_heartbeat_counter();
+    self.send_queued();
+  }
+
+  // A DataWriter with ManualByTopic liveliness loses it when it neither writes nor
+  // asserts its liveliness within the lease. Checked at each heartbeat, which come more
+  // often than that. Lost once until asserted again.
+  fn check_liveliness(&mut self) {
+    let lease_duration = match self.qos_policies.liveliness {
+      Some(policy::Liveliness::ManualByTopic { lease_duration }) => lease_duration,
+      _ => return,
+    };
+    if self.liveliness_lost || self.clock.now() - self.last_liveliness_assertion <= lease_duration {
+      return;
+    }
+    self.liveliness_lost = true;
+    self.liveliness_lost_status.increase();
+    if let Err(e) = self
+      .status_sender
+      .try_send(StatusChange::LivelinessLostStatus(
+        self.liveliness_lost_status,
+      ))
+    {
+      error!("Failed to send new liveliness lost status. {:?}", e);
     }
   }
 
@@ -247,10 +541,79 @@ impl Writer {
 
   pub fn add_timed_event_handler(&mut self, time_handler: TimedEventHandler) {
     self.timed_event_handler = Some(time_handler);
-    self.set_cache_cleaning_timer();
     self.set_heartbeat_timer();
   }
 
+  // Applies the socket options and the message size limit of the participant, and sends
+  // our multicast through the interfaces it is restricted to. Creates our shared memory
+  // segment, if the participant uses shared memory, and sends also over its TCP
+  // connections. Sends nothing at all if the participant is passive.
+  pub fn use_local_network(&mut self, network: &LocalNetwork) {
+    self.tcp = network.tcp().cloned();
+    self.max_message_size = network.max_message_size();
+    self.passive = network.passive();
+    self.network = network.clone();
+    if let Err(e) = network.configure_sender(self.udp_sender.get_mut()) {
+      warn!(
+        "Cannot configure socket of writer {:?}. {:?}",
+        self.get_guid(),
+        e
+      );
+    }
+    #[cfg(feature = "shm")]
+    {
+      if network.shm_locator(self.get_guid()).is_some() {
+        match ShmWriter::create(self.get_guid(), network.shm_segment_size()) {
+          Ok(shm) => self.shm = Some(shm),
+          Err(e) => warn!(
+            "Cannot create shared memory segment of writer {:?}. {:?}",
+            self.get_guid(),
+            e
+          ),
+        }
+      }
+    }
+  }
+
+  // Writes what we send also to the traffic capture of the participant.
+  pub fn use_capture(&mut self, capture: &PacketCapture) {
+    self.udp_sender.get_mut().set_capture(capture.clone());
+    self.capture = capture.clone();
+  }
+
+  // Counts the times udp_sender is replaced in the statistics of the participant.
+  pub fn use_network_counters(&mut self, counters: &Arc<NetworkCounters>) {
+    self.network_counters = counters.clone();
+  }
+
+  // Replaces udp_sender after sending failed because the network was unreachable,
+  // e.g. as the interface went down. Its sockets may stay unusable even when the
+  // network comes back, e.g. if they send multicast through an address that is gone.
+  fn recreate_udp_sender(&self) {
+    let now = Instant::now();
+    if let Some(previous) = self.udp_sender_recreated.get() {
+      if now.duration_since(previous) < UDP_SENDER_RECREATE_INTERVAL {
+        return;
+      }
+    }
+    self.udp_sender_recreated.set(Some(now));
+    let mut sender = UDPSender::new_with_random_port();
+    if let Err(e) = self.network.configure_sender(&sender) {
+      debug!(
+        "Cannot configure socket of writer {:?} yet. {:?}",
+        self.get_guid(),
+        e
+      );
+    }
+    sender.set_capture(self.capture.clone());
+    *self.udp_sender.borrow_mut() = sender;
+    self.network_counters.socket_recreated();
+    warn!(
+      "Network unreachable, recreated the sockets of writer {:?}.",
+      self.get_guid()
+    );
+  }
+
   pub fn is_reliable(&self) -> bool {
     match self.qos_policies.reliability {
       Some(Reliability::Reliable {
@@ -264,7 +627,8 @@ impl Writer {
     }
   }
 
-  /// this should be called everytime cacheCleaning message is received.
+  /// Removes changes no matched reader needs anymore from DDSCache. This is called by
+  /// the periodic DDSCache garbage collection of the participant.
   pub fn handle_cache_cleaning(&mut self) {
     let mut removedChanges = vec![];
     match self.qos_policies.history {
@@ -284,14 +648,6 @@ impl Writer {
     for sq in removedChanges {
       self.sequence_number_to_instant.remove(&sq);
     }
-    self.set_cache_cleaning_timer();
-  }
-
-  fn set_cache_cleaning_timer(&mut self) {
-    self.timed_event_handler.as_mut().unwrap().set_timeout(
-      &chronoDuration::from(self.cahce_cleaning_perioid),
-      TimerMessageType::writer_cache_cleaning,
-    )
   }
 
   fn create_heartbeat_message_wdata(
@@ -301,17 +657,39 @@ impl Writer {
     writer: &Writer,
     reader_guid: GUID,
   ) -> Result<Message, String> {
+    // InfoTimestamp carries the source timestamp given by the DataWriter
+    let source_timestamp = writer
+      .find_cache_change_by_sn(seqnum)
+      .and_then(|cc| cc.source_timestamp);
     MessageBuilder::new()
       .header(message_header)
       .dst_submessage(endianness, reader_guid.guidPrefix)
-      .ts_msg(endianness, false)
+      .ts_msg(endianness, source_timestamp)
       .data_msg(seqnum, writer, reader_guid)
       .heartbeat_msg(writer, reader_guid, false, false)
       .build()
   }
 
   /// this should be called everytime heartbeat message with token is recieved.
-  pub fn handle_heartbeat_tick(&mut self) {
+  /// Sends the readers what they are missing, and heartbeats. Returns true if the
+  /// heartbeat asserted Automatic liveliness, which local readers need to hear without
+  /// the network.
+  pub fn handle_heartbeat_tick(&mut self) -> bool {
+    let _writer = dds_span!(trace, "writer", guid = self.get_guid());
+    self.check_liveliness();
+    let liveliness_asserted = self.assert_automatic_liveliness();
+    // Best effort writers heartbeat only for liveliness.
+    if !self.is_reliable() {
+      self.set_heartbeat_timer();
+      return liveliness_asserted;
+    }
+    dds_event!(
+      trace,
+      "heartbeat sent",
+      first = self.first_change_sequence_number,
+      last = self.last_change_sequence_number,
+      readers = self.readers.len(),
+    );
     // TODO Set some guidprefix if needed at all.
     // Not sure if DST submessage and TS submessage are needed when sending heartbeat.
 
@@ -323,6 +701,12 @@ impl Writer {
     let mut seqnums: HashMap<GUID, HashSet<SequenceNumber>> = HashMap::new();
     let mut requested_seqnums: HashMap<GUID, HashSet<SequenceNumber>> = HashMap::new();
 
+    // Readers sharing a multicast group get new changes and heartbeats through the
+    // group. Repairs still go to each reader.
+    let multicast_sent = self.send_unsent_to_multicast_groups();
+    let multicast_groups = self.multicast_groups();
+    let mut group_heartbeats: Vec<usize> = Vec::new();
+
     for reader in self.readers.iter() {
       let mut rtps_messages = Vec::new();
       let reader_guid = reader.remote_reader_guid;
@@ -342,8 +726,32 @@ impl Writer {
       all_changes.extend(unacked_changes);
       all_changes.extend(requested_changes);
       all_changes.extend(unsent_changes);
+      if let Some(sent) = multicast_sent.get(&reader_guid) {
+        all_changes.retain(|sn| !sent.contains(sn));
+      }
+
+      let (available, unavailable): (Vec<SequenceNumber>, Vec<SequenceNumber>) =
+        itertools::sorted(all_changes.into_iter())
+          .partition(|&sn| reader.change_is_relevant(sn) && self.change_is_available(sn));
+
+      // Changes we no longer have, or that are not for this reader, are gapped, so that
+      // the reader stops asking for them.
+      // The GAP goes first, so that a reliable reader can deliver the DATA right away.
+      let gaps = self.gaps_for_reader(reader, &unavailable);
+      if !gaps.is_empty() {
+        match MessageBuilder::new()
+          .header(message_header.clone())
+          .dst_submessage(endianness, reader_guid.guidPrefix)
+          .gap_msgs(&self, gaps)
+          .heartbeat_msg(&self, reader_guid, false, false)
+          .build()
+        {
+          Ok(m) => rtps_messages.push((m, Traffic::Repair)),
+          Err(e) => warn!("Failed to build gap message. {}", e),
+        }
+      }
 
-      for &seqnum in itertools::sorted(all_changes.iter()) {
+      for seqnum in available {
         match Writer::create_heartbeat_message_wdata(
           message_header.clone(),
           endianness,
@@ -360,12 +768,44 @@ impl Writer {
               None => (),
             };
             // adding the generated message
-            rtps_messages.push(m)
+            let traffic = if unsent_changes.contains(&seqnum) {
+              Traffic::Fresh
+            } else {
+              Traffic::Resend {
+                reader: reader_guid,
+                seqnum,
+              }
+            };
+            rtps_messages.push((m, traffic))
           }
           _ => (),
         };
       }
 
+      // Nothing to resend, but the reader still needs to hear which changes are
+      // available, so that it can ask for the ones it has missed.
+      if rtps_messages.is_empty() && self.last_change_sequence_number > SequenceNumber::from(0) {
+        match multicast_groups
+          .iter()
+          .position(|(_, guids)| guids.contains(&reader_guid))
+        {
+          Some(group) => {
+            if !group_heartbeats.contains(&group) {
+              group_heartbeats.push(group);
+            }
+          }
+          None => match MessageBuilder::new()
+            .header(message_header.clone())
+            .dst_submessage(endianness, reader_guid.guidPrefix)
+            .heartbeat_msg(&self, reader_guid, false, false)
+            .build()
+          {
+            Ok(m) => rtps_messages.push((m, Traffic::Repair)),
+            Err(e) => warn!("Failed to build heartbeat message. {}", e),
+          },
+        }
+      }
+
       // updating deadline for added sequence numbers
       match seqnums.get(&reader_guid) {
         Some(sqs) => {
@@ -376,7 +816,7 @@ impl Writer {
             match self.get_qos().deadline {
               Some(dl) => {
                 if let Some(instant) = instant {
-                  if dl.0 < Timestamp::now() - *instant {
+                  if dl.0 < self.clock.now() - instant.timestamp() {
                     self.offered_deadline_status.increase();
                     debug!(
                       "Trying to send status change {:?}",
@@ -400,6 +840,11 @@ impl Writer {
         None => (),
       }
 
+      // gapped changes are done with, like the sent ones
+      if let Some(v) = seqnums.get_mut(&reader_guid) {
+        v.extend(unavailable);
+      }
+
       match requested_seqnums.get_mut(&reader_guid) {
         Some(v) => match seqnums.get(&reader_guid) {
           Some(sqs) => v.extend(sqs.intersection(requested_changes)),
@@ -409,9 +854,24 @@ impl Writer {
       };
 
       // finally sending the messages
-      for rtps_message in rtps_messages.iter() {
-        self.send_unicast_message_to_reader(rtps_message, reader);
-        self.send_multicast_message_to_reader(rtps_message, reader);
+      for (rtps_message, traffic) in rtps_messages.iter() {
+        self.send_message_to_reader(rtps_message, reader, *traffic);
+      }
+    }
+
+    for group in group_heartbeats {
+      match MessageBuilder::new()
+        .header(message_header.clone())
+        .heartbeat_msg(&self, GUID::GUID_UNKNOWN, false, false)
+        .build()
+      {
+        Ok(m) => self.send_message(
+          &m,
+          &LocatorList::new(),
+          &multicast_groups[group].0,
+          Traffic::Repair,
+        ),
+        Err(e) => warn!("Failed to build heartbeat message. {}", e),
       }
     }
 
@@ -432,40 +892,238 @@ impl Writer {
       };
     }
 
+    self.send_queued();
     self.set_heartbeat_timer();
+    liveliness_asserted
+  }
+
+  /// Whether the change can still be sent to readers. Changes before
+  /// `first_change_sequence_number` are out of the history, even if the cache
+  /// still holds them.
+  fn change_is_available(&self, seqnum: SequenceNumber) -> bool {
+    if seqnum < self.first_change_sequence_number {
+      return false;
+    }
+    read_lock(&self.dds_cache)
+      .from_topic_get_change_by_sn(&self.my_topic_name, self.get_guid(), seqnum)
+      .is_some()
+  }
+
+  /// GAPs telling the reader which changes it will never get: the given unavailable or
+  /// irrelevant ones, and all not acknowledged changes that have dropped out of the history.
+  fn gaps_for_reader(&self, reader: &RtpsReaderProxy, unavailable: &[SequenceNumber]) -> Vec<Gap> {
+    let reader_id = reader.remote_reader_guid.entityId;
+    let writer_id = self.get_entity_id();
+    let first = self.first_change_sequence_number;
+    let mut gaps = Vec::new();
+
+    let not_acked = reader.all_acked_up_to() + SequenceNumber::from(1);
+    if not_acked < first {
+      gaps.push(Gap {
+        reader_id,
+        writer_id,
+        gap_start: not_acked,
+        gap_list: SequenceNumberSet::new(first),
+      });
+    }
+    let in_history: Vec<SequenceNumber> = unavailable
+      .iter()
+      .filter(|&&sn| sn >= first)
+      .cloned()
+      .collect();
+    gaps.extend(Gap::covering(reader_id, writer_id, &in_history));
+    gaps
   }
 
   /// after heartbeat is handled timer should be set running again.
   fn set_heartbeat_timer(&mut self) {
-    match self.heartbeat_period {
-      Some(period) => self.timed_event_handler.as_mut().unwrap().set_timeout(
+    match (self.heartbeat_period, self.timed_event_handler.as_mut()) {
+      (Some(period), Some(teh)) => teh.set_timeout(
         &chronoDuration::from(period),
         TimerMessageType::writer_heartbeat,
       ),
-      None => (),
+      (Some(_), None) => warn!("Unable to get timed_event_handler."),
+      (None, _) => (),
+    }
+  }
+
+  /// Resends the changes readers have requested with AckNacks.
+  /// Called when the nack response delay has passed.
+  pub fn handle_nack_response_timeout(&mut self) {
+    self.nack_response_pending = false;
+    let message_header: Header = self.create_message_header();
+    let endianness = self.endianness;
+
+    let mut sent: Vec<(GUID, HashSet<SequenceNumber>)> = Vec::new();
+    for reader in self.readers.iter() {
+      let mut sent_seqnums = HashSet::new();
+      let (available, unavailable): (Vec<SequenceNumber>, Vec<SequenceNumber>) =
+        itertools::sorted(reader.requested_changes().iter().cloned())
+          .partition(|&sn| reader.change_is_relevant(sn) && self.change_is_available(sn));
+
+      let gaps = self.gaps_for_reader(reader, &unavailable);
+      if !gaps.is_empty() {
+        match MessageBuilder::new()
+          .header(message_header.clone())
+          .dst_submessage(endianness, reader.remote_reader_guid.guidPrefix)
+          .gap_msgs(&self, gaps)
+          .heartbeat_msg(&self, reader.remote_reader_guid, false, false)
+          .build()
+        {
+          Ok(message) => {
+            self.send_message_to_reader(&message, reader, Traffic::Repair);
+            sent_seqnums.extend(unavailable);
+          }
+          Err(e) => warn!("Failed to build gap message. {}", e),
+        }
+      }
+
+      for seqnum in available {
+        match Writer::create_heartbeat_message_wdata(
+          message_header.clone(),
+          endianness,
+          seqnum,
+          &self,
+          reader.remote_reader_guid,
+        ) {
+          Ok(message) => {
+            let traffic = Traffic::Resend {
+              reader: reader.remote_reader_guid,
+              seqnum,
+            };
+            self.send_message_to_reader(&message, reader, traffic);
+            sent_seqnums.insert(seqnum);
+          }
+          Err(e) => debug!("Cannot resend requested change {:?}: {}", seqnum, e),
+        }
+      }
+      sent.push((reader.remote_reader_guid, sent_seqnums));
+    }
+
+    for (guid, seqnums) in sent {
+      if let Some(r) = self
+        .readers
+        .iter_mut()
+        .find(|p| p.remote_reader_guid == guid)
+      {
+        for &seq in seqnums.iter() {
+          r.remove_requested_change(seq);
+        }
+      }
+      self.increase_heartbeat_counter_and_remove_unsend_sequence_numbers(seqnums, &Some(guid));
+    }
+    self.send_queued();
+  }
+
+  // Repairs right away if there is no nack response delay, otherwise starts the
+  // delay unless it is already running.
+  fn schedule_nack_response(&mut self) {
+    if self.nack_response_pending {
+      return;
+    }
+    if self.rtps_config.nack_response_delay == std::time::Duration::from_secs(0) {
+      self.handle_nack_response_timeout();
+      return;
     }
+    match self.timed_event_handler.as_mut() {
+      Some(teh) => {
+        teh.set_timeout(
+          &chronoDuration::from(self.nack_respose_delay),
+          TimerMessageType::writer_nack_response,
+        );
+        self.nack_response_pending = true;
+      }
+      // the next heartbeat resends requested changes anyway
+      None => warn!("Unable to get timed_event_handler."),
+    }
+  }
+
+  // Sequence numbers of changes written less than nack_suppression_duration ago.
+  fn nack_suppressed_changes(&self) -> Vec<SequenceNumber> {
+    if self.rtps_config.nack_suppression_duration == std::time::Duration::from_secs(0) {
+      return Vec::new();
+    }
+    let now = self.clock.now();
+    self
+      .sequence_number_to_instant
+      .iter()
+      .rev()
+      .take_while(|(_, instant)| now - instant.timestamp() < self.nack_suppression_duration)
+      .map(|(&seqnum, _)| seqnum)
+      .collect()
   }
 
-  pub fn insert_to_history_cache(&mut self, data: DDSData) {
+  /// Returns the readers that the reader filter of the DataWriter kept the change from.
+  pub fn insert_to_history_cache(&mut self, mut data: DDSData) -> Vec<GUID> {
+    let filtered_readers = std::mem::take(&mut data.filtered_readers);
     WriterUtil::increment_writer_sequence_number(self);
     let new_cache_change = WriterUtil::create_cache_change_from_dds_data(self, data);
     let data_key = new_cache_change.key;
+    self.batched_samples += 1;
+    self.batched_bytes += new_cache_change
+      .data_value
+      .as_ref()
+      .map_or(0, |p| p.value.len());
 
     // inserting to DDSCache
-    let insta = Timestamp::now();
-    self.dds_cache.write().unwrap().to_topic_add_change(
-      &self.my_topic_name,
-      &insta,
-      new_cache_change,
-    );
+    let insta = CacheInstant::at(self.clock.now());
+    if let Err(e) =
+      read_lock(&self.dds_cache).to_topic_add_change(&self.my_topic_name, &insta, new_cache_change)
+    {
+      warn!(
+        "Writer {:?} could not store a change: {}",
+        self.get_guid(),
+        e
+      );
+      return Vec::new();
+    }
 
     // keeping table of instant sequence number pairs
     self
       .sequence_number_to_instant
       .insert(self.last_change_sequence_number, insta);
     self.key_to_instant.insert(data_key, insta);
+    dds_event!(
+      trace,
+      "sample written",
+      writer = self.get_guid(),
+      seq = self.last_change_sequence_number,
+    );
+
+    self.writer_set_unsent_changes(&filtered_readers);
+    if !filtered_readers.is_empty() {
+      self.send_gaps_to_filtered_readers(&filtered_readers);
+    }
+    self.assert_liveliness();
+    self.update_ack_waiter();
+    filtered_readers
+  }
 
-    self.writer_set_unsent_changes();
+  // Readers that the latest change was filtered from hear right away that it is not for
+  // them, so that reliable ones do not hold back the changes after it.
+  fn send_gaps_to_filtered_readers(&self, filtered_readers: &[GUID]) {
+    let seqnum = self.last_change_sequence_number;
+    let message_header = self.create_message_header();
+    for reader in self
+      .readers
+      .iter()
+      .filter(|r| filtered_readers.contains(&r.remote_reader_guid))
+    {
+      let gaps = Gap::covering(
+        reader.remote_reader_guid.entityId,
+        self.get_entity_id(),
+        &[seqnum],
+      );
+      match MessageBuilder::new()
+        .header(message_header.clone())
+        .dst_submessage(self.endianness, reader.remote_reader_guid.guidPrefix)
+        .gap_msgs(self, gaps)
+        .build()
+      {
+        Ok(message) => self.send_message_to_reader(&message, reader, Traffic::Repair),
+        Err(e) => warn!("Failed to build gap message. {}", e),
+      }
+    }
   }
 
   /// This needs to be called when dataWriter does dispose.
@@ -473,17 +1131,22 @@ impl Writer {
   pub fn handle_not_alive_disposed_cache_change(&mut self, data: DDSData) {
     let instant = self.key_to_instant.get(&data.value_key_hash);
 
-    if instant.is_some() {
-      self
-        .dds_cache
-        .write()
-        .unwrap()
-        .from_topic_set_change_to_not_alive_disposed(&self.my_topic_name, &instant.unwrap());
+    if let Some(instant) = instant {
+      if let Err(e) = read_lock(&self.dds_cache)
+        .from_topic_set_change_to_not_alive_disposed(&self.my_topic_name, instant)
+      {
+        warn!(
+          "Writer {:?} could not dispose a change: {}",
+          self.get_guid(),
+          e
+        );
+      }
     }
   }
 
   /// Removes permanently cacheChanges from DDSCache.
-  /// CacheChanges can be safely removed only if they are acked by all readers. (Reliable)
+  /// CacheChanges can be safely removed only if they are acked by all readers (Reliable)
+  /// and no reader is waiting for them to be sent or resent.
   /// Depth is QoS policy History depth.
   /// Returns SequenceNumbers of removed CacheChanges
   /// This is called repeadedly by handle_cache_cleaning action.
@@ -492,9 +1155,9 @@ impl Writer {
     let mut removed_change_sequence_numbers = vec![];
     let acked_by_all_readers = {
       //let mut acked_by_all: Vec<(&Timestamp, &SequenceNumber)> = vec![];
-      let mut acked_by_all: BTreeMap<&Timestamp, &SequenceNumber> = BTreeMap::new();
+      let mut acked_by_all: BTreeMap<&CacheInstant, &SequenceNumber> = BTreeMap::new();
       for (sq, i) in self.sequence_number_to_instant.iter() {
-        if self.change_with_sequence_number_is_acked_by_all(&sq) {
+        if self.change_is_collectable(*sq) {
           acked_by_all.insert(i, sq);
         }
       }
@@ -511,19 +1174,13 @@ impl Writer {
         if index >= amount_need_to_remove {
           break;
         }
-        let removed = self
-          .dds_cache
-          .write()
-          .unwrap()
-          .from_topic_remove_change(&self.my_topic_name, i);
-        if removed.is_some() {
-          removed_change_sequence_numbers.push(removed.unwrap().sequence_number);
-        } else {
-          warn!(
-            "Remove from {:?} failed. No results with {:?}",
-            &self.my_topic_name, i
-          );
-          debug!("{:?}", self.dds_cache);
+        let removed = read_lock(&self.dds_cache).from_topic_remove_change(&self.my_topic_name, i);
+        match removed {
+          Ok(change) => removed_change_sequence_numbers.push(change.sequence_number),
+          Err(e) => {
+            warn!("Remove from {:?} failed: {}", &self.my_topic_name, e);
+            debug!("{:?}", self.dds_cache);
+          }
         }
         index = index + 1;
       }
@@ -531,42 +1188,37 @@ impl Writer {
     return removed_change_sequence_numbers;
   }
 
-  fn remove_from_history_cache(&mut self, instant: &Timestamp) {
-    let removed_change = self
-      .dds_cache
-      .write()
-      .unwrap()
-      .from_topic_remove_change(&self.my_topic_name, instant);
+  fn remove_from_history_cache(&mut self, instant: &CacheInstant) {
+    let removed_change =
+      read_lock(&self.dds_cache).from_topic_remove_change(&self.my_topic_name, instant);
     debug!("removed change from DDShistoryCache {:?}", removed_change);
-    if removed_change.is_some() {
-      self
-        .disposed_sequence_numbers
-        .insert(removed_change.unwrap().sequence_number);
-    } else {
-      todo!();
+    match removed_change {
+      Ok(change) => {
+        self
+          .disposed_sequence_numbers
+          .insert(change.sequence_number);
+      }
+      Err(e) => warn!("Remove from {:?} failed: {}", &self.my_topic_name, e),
     }
   }
 
   fn remove_from_history_cache_with_sequence_number(&mut self, sequence_number: &SequenceNumber) {
     let instant = self.sequence_number_to_instant.get(sequence_number);
-    if instant.is_some() {
-      let removed_change = self
-        .dds_cache
-        .write()
-        .unwrap()
-        .from_topic_remove_change(&self.my_topic_name, instant.unwrap());
-      if removed_change.is_none() {
-        todo!(
-          "Cache change with seqnum {:?} and instant {:?} cold not be revod from DDSCache",
-          sequence_number,
-          instant
-        )
+    match instant {
+      Some(instant) => {
+        if let Err(e) =
+          read_lock(&self.dds_cache).from_topic_remove_change(&self.my_topic_name, instant)
+        {
+          warn!(
+            "Cache change with seqnum {:?} could not be removed from DDSCache: {}",
+            sequence_number, e
+          );
+        }
       }
-    } else {
-      todo!(
+      None => warn!(
         "sequence number: {:?} cannot be tranformed to instant ",
         sequence_number
-      );
+      ),
     }
   }
 
@@ -628,12 +1280,14 @@ impl Writer {
     return readers_remaining;
   }
 
+  // Only unsent changes count here. A reader with just requested changes would make
+  // send_all_unsend_messages loop forever, as sending does not clear them.
   fn get_some_reader_with_unsent_messages(&self) -> Option<&RtpsReaderProxy> {
-    self.readers.iter().find(|p| p.can_send())
+    self.readers.iter().find(|p| p.can_send_unsend())
   }
 
   fn get_some_reader_with_unsent_messages_mut(&mut self) -> Option<&mut RtpsReaderProxy> {
-    self.readers.iter_mut().find(|p| p.can_send())
+    self.readers.iter_mut().find(|p| p.can_send_unsend())
   }
 
   fn generate_message(&self, reader_proxy: &RtpsReaderProxy) -> Option<Message> {
@@ -646,20 +1300,7 @@ impl Writer {
         }
       };
 
-      let instant = match self.sequence_number_to_instant.get(&sequenceNumber) {
-        Some(i) => i,
-        None => {
-          warn!("Failed to get instant from sequence number.");
-          return None;
-        }
-      };
-
-      let cache = match self.dds_cache.read() {
-        Ok(c) => c,
-        Err(e) => panic!("DDSCache is poisoned. {:?}", e),
-      };
-
-      let change = match cache.from_topic_get_change(&self.my_topic_name, &instant) {
+      let change = match self.find_cache_change_by_sn(sequenceNumber) {
         Some(c) => c,
         None => {
           warn!("Failed to get cache change from topic.");
@@ -668,7 +1309,7 @@ impl Writer {
       };
 
       let reader_entity_id = reader_proxy.remote_reader_guid.entityId;
-      let message = self.write_user_msg(change.clone(), reader_entity_id);
+      let message = self.write_user_msg(change, reader_entity_id);
 
       return Some(message);
     }
@@ -678,30 +1319,30 @@ impl Writer {
   fn get_next_reader_next_unsend_message(&self) -> Option<(Message, GUID)> {
     self.readers.iter().find(|p| p.can_send()).map(|p| {
       let sequenceNumber = p.next_unsent_change();
-      let instant = self
-        .sequence_number_to_instant
-        .get(&sequenceNumber.unwrap());
-      let cache = self.dds_cache.read().unwrap();
-      let change = cache.from_topic_get_change(&self.my_topic_name, &instant.unwrap());
+      let change = self.find_cache_change_by_sn(sequenceNumber.unwrap());
       let reader_entity_id = p.remote_reader_guid.entityId.clone();
       let remote_reader_guid = p.remote_reader_guid.clone();
-      let message = self.write_user_msg(change.unwrap().clone(), reader_entity_id);
+      let message = self.write_user_msg(change.unwrap(), reader_entity_id);
       return (message, remote_reader_guid);
     })
   }
 
   fn get_next_reader_next_requested_message(&mut self) -> (Option<Message>, Option<GUID>) {
+    let writer_guid = self.get_guid();
     for reader_proxy in &mut self.readers {
       if reader_proxy.can_send() {
         let sequenceNumber = reader_proxy.next_requested_change();
-        let instant = self.sequence_number_to_instant.get(sequenceNumber.unwrap());
-        let cache = self.dds_cache.read().unwrap();
-        let change = cache.from_topic_get_change(&self.my_topic_name, &instant.unwrap());
+        let cache = read_lock(&self.dds_cache);
+        let change = cache.from_topic_get_change_by_sn(
+          &self.my_topic_name,
+          writer_guid,
+          *sequenceNumber.unwrap(),
+        );
         let message: Message;
         let reader_entity_id = reader_proxy.remote_reader_guid.entityId.clone();
         let remote_reader_guid = reader_proxy.remote_reader_guid.clone();
         {
-          message = self.write_user_msg(change.unwrap().clone(), reader_entity_id);
+          message = self.write_user_msg(change.unwrap(), reader_entity_id);
         }
         return (Some(message), Some(remote_reader_guid));
       }
@@ -722,15 +1363,14 @@ impl Writer {
       if let Some(message) = message {
         message_sequence_numbers = message.get_data_sub_message_sequence_numbers();
 
-        self.send_unicast_message_to_reader(&message, reader);
-        self.send_multicast_message_to_reader(&message, reader);
+        self.send_message_to_reader(&message, reader, Traffic::Fresh);
 
         if let Some(seqnum) = rem_sequece_number {
           let instant = self.sequence_number_to_instant(seqnum - SequenceNumber::from(1));
           match self.get_qos().deadline {
             Some(dl) => {
               if let Some(instant) = instant {
-                if dl.0 < Timestamp::now() - *instant {
+                if dl.0 < self.clock.now() - instant.timestamp() {
                   self.offered_deadline_status.increase();
                   debug!(
                     "Trying to send single status change {:?}",
@@ -767,30 +1407,117 @@ impl Writer {
     }
   }
 
-  fn send_unicast_message_to_reader(&self, message: &Message, reader: &RtpsReaderProxy) {
-    if let Ok(data) = message.write_to_vec_with_ctx(self.endianness) {
-      self
-        .udp_sender
-        .send_to_locator_list(&data, &reader.unicast_locator_list)
+  // Sends the message now, or queues it if flow control is on.
+  // Messages addressed to the reader with InfoDestination, like repairs, go to its
+  // unicast locators only. Other participants in its multicast group would just drop
+  // them. Multicast is used only if the reader has no unicast locators.
+  fn send_message_to_reader(&self, message: &Message, reader: &RtpsReaderProxy, traffic: Traffic) {
+    let no_locators = LocatorList::new();
+    let (unicast, multicast) = if !message.is_addressed() {
+      (&reader.unicast_locator_list, &reader.multicast_locator_list)
+    } else if !reader.unicast_locator_list.is_empty() {
+      (&reader.unicast_locator_list, &no_locators)
+    } else {
+      (&no_locators, &reader.multicast_locator_list)
+    };
+    self.send_message(message, unicast, multicast, traffic)
+  }
+
+  fn send_message(
+    &self,
+    message: &Message,
+    unicast: &LocatorList,
+    multicast: &LocatorList,
+    traffic: Traffic,
+  ) {
+    if self.passive {
+      return;
+    }
+    let buffer = match message.write_to_vec_with_ctx(self.endianness) {
+      Ok(b) => b,
+      Err(e) => {
+        warn!("Failed to serialize message. {:?}", e);
+        return;
+      }
+    };
+    let resent = matches!(traffic, Traffic::Resend { .. });
+    self.statistics.message_sent(message, buffer.len(), resent);
+    match &self.flow_controller {
+      None => self.send_to_locators(&buffer, unicast, multicast),
+      Some(fc) => fc.borrow_mut().enqueue(
+        traffic,
+        Datagram {
+          bytes: buffer,
+          unicast: unicast.clone(),
+          multicast: multicast.clone(),
+        },
+      ),
     }
   }
 
-  fn send_multicast_message_to_reader(&self, message: &Message, reader: &RtpsReaderProxy) {
-    let buffer = message.write_to_vec_with_ctx(self.endianness).unwrap();
-    for multiaddress in &reader.multicast_locator_list {
-      if multiaddress.kind == LocatorKind::LOCATOR_KIND_UDPv4 {
-        self
-          .udp_sender
-          .send_ipv4_multicast(&buffer, multiaddress.to_socket_address())
-          .expect("Unable to send multicast message.");
+  // Messages larger than max_message_size are not sent over UDP, as IP would fragment
+  // them or they would be dropped on the way. DataWriter refuses samples that do not
+  // fit when it has readers reached over UDP, so this happens only to samples written
+  // before such a reader matched.
+  fn send_to_locators(&self, buffer: &[u8], unicast: &LocatorList, multicast: &LocatorList) {
+    let no_locators = LocatorList::new();
+    let (udp_unicast, udp_multicast) = if buffer.len() <= self.max_message_size {
+      (unicast, multicast)
+    } else {
+      if unicast
+        .iter()
+        .chain(multicast.iter())
+        .any(|l| l.kind.is_udp())
+      {
+        warn!(
+          "Message of {} bytes of writer {:?} is larger than max_message_size {}. Not \
+           sending it over UDP.",
+          buffer.len(),
+          self.get_guid(),
+          self.max_message_size
+        );
+      }
+      (&no_locators, &no_locators)
+    };
+    let udp_sender = self.udp_sender.borrow();
+    udp_sender.send_to_locator_list(buffer, udp_unicast);
+    #[cfg(feature = "shm")]
+    {
+      if let Some(shm) = &self.shm {
+        shm.send(buffer, unicast);
+        self.capture.sent_over_shm(buffer, unicast);
+      }
+    }
+    if let Some(tcp) = &self.tcp {
+      tcp.send(buffer, unicast);
+      self.capture.sent_over_tcp(buffer, unicast);
+    }
+    for multiaddress in udp_multicast {
+      let sent = if multiaddress.kind == LocatorKind::LOCATOR_KIND_UDPv4 {
+        udp_sender.send_ipv4_multicast(buffer, multiaddress.to_socket_address())
       } else if multiaddress.kind == LocatorKind::LOCATOR_KIND_UDPv6 {
-        todo!();
+        // the host may not have IPv6
+        udp_sender.send_ipv6_multicast(buffer, multiaddress.to_socket_address())
+      } else {
+        continue;
+      };
+      match sent {
+        Ok(_) => (),
+        // handled below
+        Err(e) if is_network_down(&e) => debug!("Unable to send multicast message. {:?}", e),
+        Err(e) => warn!("Unable to send multicast message. {:?}", e),
       }
     }
+    let network_down = udp_sender.take_network_down();
+    drop(udp_sender);
+    if network_down {
+      self.recreate_udp_sender();
+    }
   }
 
   pub fn send_all_unsend_messages(&mut self) {
     if self.can_send_some() {
+      self.send_unsent_to_multicast_groups();
       while let Some(_) = self.get_some_reader_with_unsent_messages() {
         self.send_next_unsend_message();
       }
@@ -799,44 +1526,264 @@ impl Writer {
     }
   }
 
-  fn create_message_header(&self) -> Header {
-    let head: Header = Header {
-      protocol_id: ProtocolId::default(),
-      protocol_version: ProtocolVersion {
-        major: self.source_version.major,
-        minor: self.source_version.minor,
-      },
-      vendor_id: VendorId {
-        vendorId: self.source_vendor_id.vendorId,
-      },
-      guid_prefix: self.entity_attributes.guid.guidPrefix,
-    };
-    return head;
+  /// This should be called after new changes are inserted to history cache.
+  /// Sends the changes now, unless batching is on and the batch has room for more.
+  pub fn send_or_batch_unsent(&mut self) {
+    match self.batching {
+      None => self.flush(),
+      Some(batching) => {
+        if self.batched_samples >= batching.max_samples || self.batched_bytes >= batching.max_bytes
+        {
+          self.flush()
+        } else if self.batched_samples == 1 {
+          // first change of a new batch
+          self.timed_event_handler.as_mut().unwrap().set_timeout(
+            &chronoDuration::from(batching.max_delay),
+            TimerMessageType::writer_batch_flush,
+          )
+        }
+      }
+    }
   }
 
-  // TODO: Is this copy-pase code from serialization/message.rs
-  pub fn get_TS_submessage(&self, invalidiateFlagSet: bool) -> SubMessage {
-    let timestamp = InfoTimestamp {
-      timestamp: Timestamp::now(),
+  /// Sends all unsent changes, also the ones waiting in the current batch.
+  pub fn flush(&mut self) {
+    self.batched_samples = 0;
+    self.batched_bytes = 0;
+    match self.batching {
+      None => self.send_all_unsend_messages(),
+      Some(batching) => self.send_all_unsend_messages_batched(batching.max_bytes),
+    }
+    self.send_queued();
+  }
+
+  /// Sends the queued messages the flow control budget allows now, and sets a timer
+  /// for sending the rest.
+  pub fn send_queued(&mut self) {
+    let wait = match &self.flow_controller {
+      None => return,
+      Some(fc) => {
+        let now = Instant::now();
+        while let Some(d) = fc.borrow_mut().next_sendable(now) {
+          self.send_to_locators(&d.bytes, &d.unicast, &d.multicast);
+        }
+        fc.borrow().wait_time()
+      }
     };
-    let mes = &mut timestamp.write_to_vec_with_ctx(self.endianness).unwrap();
+    if let Some(wait) = wait {
+      let wait = wait.max(std::time::Duration::from_millis(1));
+      match self.timed_event_handler.as_mut() {
+        Some(teh) => teh.set_timeout(
+          &chronoDuration::from(Duration::from(wait)),
+          TimerMessageType::writer_flow_control,
+        ),
+        None => warn!("Unable to get timed_event_handler."),
+      }
+    }
+  }
 
-    let flags = BitFlags::<INFOTIMESTAMP_Flags>::from_endianness(self.endianness)
-      | (if invalidiateFlagSet {
-        INFOTIMESTAMP_Flags::Invalidate.into()
-      } else {
-        BitFlags::<INFOTIMESTAMP_Flags>::empty()
-      });
+  /// Turns rate limiting on, changes it, or turns it off. Turning it off sends all
+  /// queued messages right away.
+  pub fn set_flow_control(&mut self, config: FlowControlConfig) {
+    if config.is_unlimited() {
+      if let Some(fc) = self.flow_controller.take() {
+        for d in fc.into_inner().into_queued() {
+          self.send_to_locators(&d.bytes, &d.unicast, &d.multicast);
+        }
+      }
+      return;
+    }
+    match &self.flow_controller {
+      Some(fc) => fc.borrow_mut().set_config(config, Instant::now()),
+      None => {
+        self.flow_controller = Some(RefCell::new(FlowController::new(
+          config,
+          self.flow_control_queue_depth.clone(),
+          Instant::now(),
+        )))
+      }
+    }
+    self.send_queued();
+  }
 
-    let submessageHeader = SubmessageHeader {
-      kind: SubmessageKind::INFO_TS,
-      flags: flags.bits(),
-      content_length: mes.len() as u16, // This conversion should be safe, as timestamp length cannot exceed u16
-    };
-    SubMessage {
-      header: submessageHeader,
-      body: SubmessageBody::Interpreter(InterpreterSubmessage::InfoTimestamp(timestamp, flags)),
+  pub fn set_batching(&mut self, batching: Option<BatchingPolicy>) {
+    // Changes batched so far go out with the old settings.
+    self.flush();
+    self.batching = batching;
+  }
+
+  // Sends the unsent changes of each reader in as few messages as possible. Each DATA
+  // submessage is preceded by its own InfoTimestamp, so that the source timestamps are kept.
+  // A message is closed when its payload would exceed max_bytes.
+  fn send_all_unsend_messages_batched(&mut self, max_bytes: usize) {
+    if !self.can_send_some() {
+      return;
+    }
+    self.send_unsent_to_multicast_groups();
+    let mut sent = Vec::new();
+    for reader in self.readers.iter().filter(|r| r.can_send()) {
+      let mut sequence_numbers: Vec<SequenceNumber> =
+        reader.unsent_changes().iter().cloned().collect();
+      sequence_numbers.sort();
+
+      let reader_entity_id = reader.remote_reader_guid.entityId;
+      for message in self.user_messages(&sequence_numbers, reader_entity_id, Some(max_bytes)) {
+        self.send_message_to_reader(&message, reader, Traffic::Fresh);
+      }
+      sent.push((
+        reader.remote_reader_guid,
+        sequence_numbers.into_iter().collect::<HashSet<_>>(),
+      ));
+    }
+    for (reader_guid, sequence_numbers) in sent {
+      self.increase_heartbeat_counter_and_remove_unsend_sequence_numbers(
+        sequence_numbers,
+        &Some(reader_guid),
+      );
+    }
+  }
+
+  // Messages carrying the given changes to the reader reader_entity_id. With max_bytes,
+  // changes are put in the same message until its payload would exceed max_bytes, or
+  // the message max_message_size. Otherwise each change gets a message of its own.
+  fn user_messages(
+    &self,
+    sequence_numbers: &[SequenceNumber],
+    reader_entity_id: EntityId,
+    max_bytes: Option<usize>,
+  ) -> Vec<Message> {
+    let cache = read_lock(&self.dds_cache);
+    let mut messages = Vec::new();
+    let mut message = Message::new(self.create_message_header());
+    // payload bytes, and serialized length
+    let mut message_bytes = 0;
+    let mut message_len = MESSAGE_HEADER_LEN;
+    for sn in sequence_numbers.iter() {
+      let change =
+        match cache.from_topic_get_change_by_sn(&self.my_topic_name, self.get_guid(), *sn) {
+          Some(c) => c,
+          None => {
+            warn!("Failed to get cache change for sequence number {:?}", sn);
+            continue;
+          }
+        };
+      let change_bytes = change.data_value.as_ref().map_or(0, |p| p.value.len());
+      let source_timestamp = change.source_timestamp;
+      let data = self.get_DATA_msg_from_cache_change(change, reader_entity_id);
+      // With its InfoTimestamp. The content length of a DATA over 64 kB wraps around,
+      // but its payload alone is longer.
+      let change_len =
+        (INFO_TIMESTAMP_LEN + SUBMESSAGE_HEADER_LEN + usize::from(data.header.content_length))
+          .max(change_bytes);
+      let full = max_bytes.map_or(true, |max| message_bytes + change_bytes > max)
+        || message_len + change_len > self.max_message_size;
+      if message_bytes > 0 && full {
+        messages.push(std::mem::replace(
+          &mut message,
+          Message::new(self.create_message_header()),
+        ));
+        message_bytes = 0;
+        message_len = MESSAGE_HEADER_LEN;
+      }
+      self.add_data_submessage(&mut message, source_timestamp, data);
+      message_bytes += change_bytes.max(1);
+      message_len += change_len;
+    }
+    if message_bytes > 0 {
+      messages.push(message);
     }
+    messages
+  }
+
+  // Matched readers that share multicast locators, in groups of at least two, with
+  // the locators.
+  fn multicast_groups(&self) -> Vec<(LocatorList, Vec<GUID>)> {
+    let mut groups: Vec<(LocatorList, Vec<GUID>)> = Vec::new();
+    for reader in self
+      .readers
+      .iter()
+      .filter(|r| !r.multicast_locator_list.is_empty())
+    {
+      match groups
+        .iter_mut()
+        .find(|(locators, _)| *locators == reader.multicast_locator_list)
+      {
+        Some((_, guids)) => guids.push(reader.remote_reader_guid),
+        None => groups.push((
+          reader.multicast_locator_list.clone(),
+          vec![reader.remote_reader_guid],
+        )),
+      }
+    }
+    groups.retain(|(_, guids)| guids.len() > 1);
+    groups
+  }
+
+  // Sends each change that several readers of a multicast group are waiting for once
+  // to the group, instead of once to each reader. The reader id is ENTITYID_UNKNOWN, so
+  // every matched reader that receives it takes it. Returns the changes sent to each
+  // reader. The others are left to be sent reader by reader.
+  fn send_unsent_to_multicast_groups(&mut self) -> HashMap<GUID, HashSet<SequenceNumber>> {
+    let mut sent: HashMap<GUID, HashSet<SequenceNumber>> = HashMap::new();
+    let max_bytes = self.batching.map(|b| b.max_bytes);
+    for (locators, guids) in self.multicast_groups() {
+      let members: Vec<&RtpsReaderProxy> = self
+        .readers
+        .iter()
+        .filter(|r| guids.contains(&r.remote_reader_guid))
+        .collect();
+      let waiting = |sn: &SequenceNumber| {
+        members
+          .iter()
+          .filter(|r| r.unsent_changes().contains(sn))
+          .count()
+      };
+      let mut sequence_numbers: Vec<SequenceNumber> = members
+        .iter()
+        .flat_map(|r| r.unsent_changes().iter().cloned())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter(|sn| waiting(sn) > 1 && members.iter().all(|r| r.change_is_relevant(*sn)))
+        .collect();
+      if sequence_numbers.is_empty() {
+        continue;
+      }
+      sequence_numbers.sort();
+
+      for message in self.user_messages(&sequence_numbers, EntityId::ENTITYID_UNKNOWN, max_bytes) {
+        self.send_message(&message, &LocatorList::new(), &locators, Traffic::Fresh);
+      }
+      for reader in members {
+        let to_reader = sequence_numbers
+          .iter()
+          .filter(|sn| reader.unsent_changes().contains(sn))
+          .cloned()
+          .collect();
+        sent.insert(reader.remote_reader_guid, to_reader);
+      }
+    }
+    for (guid, sequence_numbers) in sent.iter() {
+      self.increase_heartbeat_counter_and_remove_unsend_sequence_numbers(
+        sequence_numbers.clone(),
+        &Some(*guid),
+      );
+    }
+    sent
+  }
+
+  fn create_message_header(&self) -> Header {
+    let head: Header = Header {
+      protocol_id: ProtocolId::default(),
+      protocol_version: ProtocolVersion {
+        major: self.source_version.major,
+        minor: self.source_version.minor,
+      },
+      vendor_id: VendorId {
+        vendorId: self.source_vendor_id.vendorId,
+      },
+      guid_prefix: self.entity_attributes.guid.guidPrefix,
+    };
+    return head;
   }
 
   // TODO: Is this copy-pase code from serialization/message.rs
@@ -879,19 +1826,45 @@ impl Writer {
     //data_message.reader_id = reader_entity_id;
     //data_message.writer_sn = change.sequence_number;
 
-    let inline_qos = match change.kind {
-      ChangeKind::ALIVE => None,
-      _ => {
-        let mut param_list = ParameterList::new();
-        let key_hash = Parameter {
-          parameter_id: ParameterId::PID_KEY_HASH,
-          value: change.key.to_le_bytes().to_vec(),
-        };
-        param_list.parameters.push(key_hash);
-        let status_info = Parameter::create_pid_status_info_parameter(true, true, false);
-        param_list.parameters.push(status_info);
-        Some(param_list)
+    let mut param_list = ParameterList::new();
+    // KeyHash identifies the instance of keyed data, and of dispose and unregister.
+    if self.is_keyed() || change.kind != ChangeKind::ALIVE {
+      let key_hash = Parameter {
+        parameter_id: ParameterId::PID_KEY_HASH,
+        value: KeyHash::from(change.key).to_octets().to_vec(),
+      };
+      param_list.parameters.push(key_hash);
+    }
+    if change.kind != ChangeKind::ALIVE {
+      // Readers must be able to tell dispose and unregister apart.
+      let status_info = Parameter::create_pid_status_info_parameter(
+        change.kind == ChangeKind::NOT_ALIVE_DISPOSED,
+        change.kind == ChangeKind::NOT_ALIVE_UNREGISTERED,
+        false,
+      );
+      param_list.parameters.push(status_info);
+    }
+    if let Some(identity) = change.related_sample_identity {
+      // Sent with both the standard and the vendor specific parameter id, because
+      // implementations differ in which one they understand.
+      let value = match self.endianness {
+        Endianness::LittleEndian => identity.to_octets::<byteorder::LittleEndian>(),
+        Endianness::BigEndian => identity.to_octets::<byteorder::BigEndian>(),
+      };
+      for parameter_id in &[
+        ParameterId::PID_RELATED_SAMPLE_IDENTITY,
+        ParameterId::PID_CUSTOM_RELATED_SAMPLE_IDENTITY,
+      ] {
+        param_list.parameters.push(Parameter {
+          parameter_id: *parameter_id,
+          value: value.to_vec(),
+        });
       }
+    }
+    let inline_qos = if param_list.parameters.is_empty() {
+      None
+    } else {
+      Some(param_list)
     };
 
     let mut data_message = Data {
@@ -902,23 +1875,24 @@ impl Writer {
       serialized_payload: change.data_value,
     };
 
-    if self.get_entity_id().get_kind() == 0xC2 {
-      match data_message.serialized_payload.as_mut() {
-        Some(sp) => sp.representation_identifier = u16::from(RepresentationIdentifier::PL_CDR_LE),
-        None => (),
-      }
+    // Built-in topic data is already a parameter list (PL_CDR), as serialized by the
+    // discovery writers. Built-in topic keys would have to be sent as parameter lists.
+    // KeyHash is enough.
+    if self.get_entity_id().get_kind() == 0xC2 && change.kind != ChangeKind::ALIVE {
+      data_message.serialized_payload = None;
     }
 
-    let flags: BitFlags<DATA_Flags> = BitFlags::<DATA_Flags>::from_endianness(self.endianness)
-      | (
-        if change.kind == ChangeKind::NOT_ALIVE_DISPOSED {
-          // No data, we send key instead
-          BitFlags::<DATA_Flags>::from_flag(DATA_Flags::InlineQos)
-        } else {
-          BitFlags::<DATA_Flags>::from_flag(DATA_Flags::Data)
-        }
-        // normal case
-      );
+    let mut flags: BitFlags<DATA_Flags> = BitFlags::<DATA_Flags>::from_endianness(self.endianness);
+    if data_message.inline_qos.is_some() {
+      flags |= DATA_Flags::InlineQos;
+    }
+    if change.kind == ChangeKind::ALIVE {
+      // normal case
+      flags |= DATA_Flags::Data;
+    } else if data_message.serialized_payload.is_some() {
+      // No data, we send key instead
+      flags |= DATA_Flags::Key;
+    }
 
     let size = data_message
       .write_to_vec_with_ctx(self.endianness)
@@ -942,7 +1916,9 @@ impl Writer {
     set_final_flag: bool,
     set_liveliness_flag: bool,
   ) -> Option<SubMessage> {
-    let first = self.first_change_sequence_number;
+    // Before anything is written first_change_sequence_number is 0, but a valid
+    // Heartbeat of no changes has first_sn 1 and last_sn 0.
+    let first = std::cmp::max(self.first_change_sequence_number, SequenceNumber::from(1));
     let last = self.last_change_sequence_number;
 
     let heartbeat = Heartbeat {
@@ -969,15 +1945,61 @@ impl Writer {
     let mut message: Vec<u8> = vec![];
 
     let mut RTPSMessage: Message = Message::new(self.create_message_header());
-    RTPSMessage.add_submessage(self.get_TS_submessage(false));
-    let data = self.get_DATA_msg_from_cache_change(change.clone(), reader_entity_id);
-    RTPSMessage.add_submessage(data);
+    self.add_user_submessages(&mut RTPSMessage, change, reader_entity_id);
     //RTPSMessage.add_submessage(self.get_heartbeat_msg());
     message.append(&mut RTPSMessage.write_to_vec_with_ctx(self.endianness).unwrap());
 
     return RTPSMessage;
   }
 
+  fn add_user_submessages(
+    &self,
+    message: &mut Message,
+    change: CacheChange,
+    reader_entity_id: EntityId,
+  ) {
+    let source_timestamp = change.source_timestamp;
+    let data = self.get_DATA_msg_from_cache_change(change, reader_entity_id);
+    self.add_data_submessage(message, source_timestamp, data);
+  }
+
+  fn add_data_submessage(
+    &self,
+    message: &mut Message,
+    source_timestamp: Option<Timestamp>,
+    data: SubMessage,
+  ) {
+    // InfoTimestamp carries the source timestamp given by the DataWriter. It applies to
+    // all the DATA that follow it in the message, so it is sent before the first DATA, and
+    // again only before a DATA with another timestamp. A change without one invalidates it.
+    if Self::timestamp_in_effect(message) != Some(source_timestamp) {
+      message.add_submessage(InfoTimestamp::create_submessage(
+        source_timestamp,
+        self.endianness,
+      ));
+    }
+    message.add_submessage(data);
+  }
+
+  // The timestamp the last InfoTimestamp of the message gives to the submessages that
+  // follow it: None if there is no InfoTimestamp, Some(None) if it invalidated the timestamp.
+  fn timestamp_in_effect(message: &Message) -> Option<Option<Timestamp>> {
+    message
+      .submessages
+      .iter()
+      .rev()
+      .find_map(|submessage| match &submessage.body {
+        SubmessageBody::Interpreter(InterpreterSubmessage::InfoTimestamp(info_ts, flags)) => {
+          if flags.contains(INFOTIMESTAMP_Flags::Invalidate) {
+            Some(None)
+          } else {
+            Some(Some(info_ts.timestamp))
+          }
+        }
+        _ => None,
+      })
+  }
+
   /// AckNack Is negative if reader_sn_state contains some sequenceNumbers in reader_sn_state set
   fn test_if_ack_nack_contains_not_recieved_sequence_numbers(ack_nack: &AckNack) -> bool {
     debug!("Testing ACKNACK set {:?}", ack_nack.reader_sn_state);
@@ -991,6 +2013,14 @@ impl Writer {
   ///respond by either sending the missing data samples, sending a GAP message when the sample is not relevant, or
   ///sending a HEARTBEAT message when the sample is no longer available
   pub fn handle_ack_nack(&mut self, guid_prefix: GuidPrefix, an: AckNack) {
+    let _writer = dds_span!(trace, "writer", guid = self.get_guid());
+    dds_event!(
+      trace,
+      "acknack received",
+      reader = GUID::new_with_prefix_and_id(guid_prefix, an.reader_id),
+      base = an.reader_sn_state.base,
+      count = an.count,
+    );
     if !self.is_reliable() {
       error!(
         "Writer {:x?} is best effort! It should not handle acknack messages!",
@@ -1009,13 +2039,28 @@ impl Writer {
         an.reader_sn_state.base,
         &an.reader_sn_state.set,
       );
-      if Writer::test_if_ack_nack_contains_not_recieved_sequence_numbers(&an) {
+      let nacked = Writer::test_if_ack_nack_contains_not_recieved_sequence_numbers(&an);
+      if nacked {
         // if ack nac says reader has NOT recieved data then add data to requested changes
         reader_proxy.add_requested_changes(an.reader_sn_state.base, an.reader_sn_state.set);
       } else {
         reader_proxy.acked_changes_set(an.reader_sn_state.base);
       }
+      reader_proxy.acked_before_set(an.reader_sn_state.base);
+
+      if nacked {
+        let suppressed = self.nack_suppressed_changes();
+        if let Some(reader_proxy) = self.matched_reader_lookup(guid_prefix, an.reader_id) {
+          for seqnum in suppressed {
+            reader_proxy.remove_requested_change(seqnum);
+          }
+          if !reader_proxy.requested_changes().is_empty() {
+            self.schedule_nack_response();
+          }
+        }
+      }
     }
+    self.update_ack_waiter();
   }
 
   pub fn matched_reader_add(&mut self, reader_proxy: RtpsReaderProxy) {
@@ -1025,7 +2070,20 @@ impl Writer {
     }) {
       panic!("Reader proxy with same group entityid and remotereader guid added already");
     };
+    self
+      .publication_matched_status
+      .increase(reader_proxy.remote_reader_guid);
+    dds_event!(
+      debug,
+      "reader matched",
+      guid = self.get_guid(),
+      reader = reader_proxy.remote_reader_guid,
+    );
     &self.readers.push(reader_proxy);
+    // the DataWriter knows the reader by the time it hears of the match
+    self.update_matched_readers_of_datawriter();
+    self.send_publication_matched_status();
+    self.update_ack_waiter();
   }
 
   pub fn matched_reader_remove(&mut self, reader_proxy: RtpsReaderProxy) {
@@ -1035,9 +2093,156 @@ impl Writer {
     });
     if pos.is_some() {
       &self.readers.remove(pos.unwrap());
+      self
+        .publication_matched_status
+        .decrease(reader_proxy.remote_reader_guid);
+      dds_event!(
+        debug,
+        "reader unmatched",
+        guid = self.get_guid(),
+        reader = reader_proxy.remote_reader_guid,
+      );
+      self.send_publication_matched_status();
+      self.update_ack_waiter();
+      self.update_matched_readers_of_datawriter();
     }
   }
 
+  // Removes all matched readers of the given participant.
+  pub fn matched_readers_remove_participant(&mut self, guid_prefix: GuidPrefix) {
+    let removed: Vec<RtpsReaderProxy> = self
+      .readers
+      .iter()
+      .filter(|p| p.remote_reader_guid.guidPrefix == guid_prefix)
+      .cloned()
+      .collect();
+    for proxy in removed {
+      self.matched_reader_remove(proxy);
+    }
+  }
+
+  /// Replaces matched readers with the given set, keeping
+  /// PublicationMatchedStatus up to date.
+  pub fn update_matched_readers(&mut self, mut readers: Vec<RtpsReaderProxy>) {
+    let mut changed = false;
+    for reader in readers.iter_mut() {
+      match self
+        .readers
+        .iter()
+        .find(|r| r.remote_reader_guid == reader.remote_reader_guid)
+      {
+        Some(old_reader) => {
+          reader.copy_all_acked_before(old_reader);
+          reader.copy_irrelevant_changes(old_reader);
+        }
+        None => {
+          self
+            .publication_matched_status
+            .increase(reader.remote_reader_guid);
+          dds_event!(
+            debug,
+            "reader matched",
+            guid = self.get_guid(),
+            reader = reader.remote_reader_guid,
+          );
+          changed = true;
+        }
+      }
+    }
+    for old_reader in self.readers.iter() {
+      if !readers
+        .iter()
+        .any(|r| r.remote_reader_guid == old_reader.remote_reader_guid)
+      {
+        self
+          .publication_matched_status
+          .decrease(old_reader.remote_reader_guid);
+        dds_event!(
+          debug,
+          "reader unmatched",
+          guid = self.get_guid(),
+          reader = old_reader.remote_reader_guid,
+        );
+        changed = true;
+      }
+    }
+    self.readers = readers;
+    // locators of the readers may have changed
+    self.update_matched_readers_of_datawriter();
+    if changed {
+      self.send_publication_matched_status();
+    }
+    self.update_ack_waiter();
+  }
+
+  pub fn local_readers(&self) -> &[GUID] {
+    &self.local_readers
+  }
+
+  /// Replaces matched readers of our own participant with the given set, keeping
+  /// PublicationMatchedStatus up to date. Returns the readers that were not matched
+  /// before.
+  pub fn update_matched_local_readers(&mut self, readers: Vec<GUID>) -> Vec<GUID> {
+    let added: Vec<GUID> = readers
+      .iter()
+      .filter(|r| !self.local_readers.contains(r))
+      .copied()
+      .collect();
+    let removed: Vec<GUID> = self
+      .local_readers
+      .iter()
+      .filter(|r| !readers.contains(r))
+      .copied()
+      .collect();
+    for reader in added.iter() {
+      self.publication_matched_status.increase(*reader);
+    }
+    for reader in removed.iter() {
+      self.publication_matched_status.decrease(*reader);
+    }
+    self.local_readers = readers;
+    if !added.is_empty() || !removed.is_empty() {
+      self.send_publication_matched_status();
+      self.update_matched_readers_of_datawriter();
+    }
+    added
+  }
+
+  // The latest change, to hand over to our local readers.
+  pub fn last_change(&self) -> Option<CacheChange> {
+    let instant = self
+      .sequence_number_to_instant
+      .get(&self.last_change_sequence_number)?;
+    read_lock(&self.dds_cache).from_topic_get_change(&self.my_topic_name, instant)
+  }
+
+  fn send_publication_matched_status(&self) {
+    match self
+      .status_sender
+      .try_send(StatusChange::PublicationMatchedStatus(
+        self.publication_matched_status,
+      )) {
+      Ok(_) => (),
+      Err(e) => error!("Failed to send new publication matched status. {:?}", e),
+    };
+  }
+
+  // Discovery found a reader of our topic that requests QoS we do not offer.
+  pub fn report_incompatible_qos(&mut self, policy: QosPolicyId) {
+    self.offered_incompatible_qos_status.increase(policy);
+    match self
+      .status_sender
+      .try_send(StatusChange::OfferedIncompatibleQosStatus(
+        self.offered_incompatible_qos_status,
+      )) {
+      Ok(_) => (),
+      Err(e) => error!(
+        "Failed to send new offered incompatible qos status. {:?}",
+        e
+      ),
+    };
+  }
+
   ///This operation finds the ReaderProxy with GUID_t a_reader_guid from the set
   /// get guid Prefix from RTPS message main header
   /// get reader guid from AckNack submessage readerEntityId
@@ -1077,6 +2282,17 @@ impl Writer {
     return true;
   }
 
+  // No matched reader can ask for the change anymore: it is not waiting to be sent or
+  // resent to any of them, and reliable readers have acknowledged it and all before it.
+  fn change_is_collectable(&self, sequence_number: SequenceNumber) -> bool {
+    let reliable = self.is_reliable();
+    self.readers.iter().all(|proxy| {
+      !proxy.unsent_changes().contains(&sequence_number)
+        && !proxy.requested_changes().contains(&sequence_number)
+        && (!reliable || proxy.all_acked_up_to() >= sequence_number)
+    })
+  }
+
   pub fn change_with_sequence_number_is_acked_by_all(
     &self,
     sequence_number: &SequenceNumber,
@@ -1134,24 +2350,32 @@ impl Writer {
     }
   }
 
-  pub fn writer_set_unsent_changes(&mut self) {
+  pub fn writer_set_unsent_changes(&mut self, filtered_readers: &[GUID]) {
+    let seqnum = self.last_change_sequence_number;
     for reader in &mut self.readers {
-      reader.unsend_changes_set(self.last_change_sequence_number);
+      if filtered_readers.contains(&reader.remote_reader_guid) {
+        reader.set_irrelevant_change(seqnum);
+      } else {
+        reader.unsend_changes_set(seqnum);
+      }
     }
   }
 
-  pub fn sequence_number_to_instant(&self, seqnumber: SequenceNumber) -> Option<&Timestamp> {
+  pub fn sequence_number_to_instant(&self, seqnumber: SequenceNumber) -> Option<&CacheInstant> {
     self.sequence_number_to_instant.get(&seqnumber)
   }
 
-  pub fn find_cache_change(&self, instant: &Timestamp) -> Option<CacheChange> {
-    match self.dds_cache.read() {
-      Ok(dc) => {
-        let cc = dc.from_topic_get_change(&self.my_topic_name, instant);
-        cc.cloned()
-      }
-      Err(e) => panic!("DDSCache is poisoned {:?}", e),
-    }
+  pub fn find_cache_change(&self, instant: &CacheInstant) -> Option<CacheChange> {
+    read_lock(&self.dds_cache).from_topic_get_change(&self.my_topic_name, instant)
+  }
+
+  /// Our change with the sequence number, to be sent or resent.
+  pub fn find_cache_change_by_sn(&self, sequence_number: SequenceNumber) -> Option<CacheChange> {
+    read_lock(&self.dds_cache).from_topic_get_change_by_sn(
+      &self.my_topic_name,
+      self.get_guid(),
+      sequence_number,
+    )
   }
 
   pub fn topic_name(&self) -> &String {
@@ -1161,6 +2385,25 @@ impl Writer {
   pub fn reset_offered_deadline_missed_status(&mut self) {
     self.offered_deadline_status.reset_change();
   }
+
+  pub fn reset_offered_incompatible_qos_status(&mut self) {
+    self.offered_incompatible_qos_status.reset_change();
+  }
+
+  pub fn reset_publication_matched_status(&mut self) {
+    self.publication_matched_status.reset_change();
+  }
+
+  pub fn reset_liveliness_lost_status(&mut self) {
+    self.liveliness_lost_status.reset_change();
+  }
+}
+
+impl Drop for Writer {
+  fn drop(&mut self) {
+    // async tasks waiting to write would otherwise wait forever
+    self.command_waker.close();
+  }
 }
 
 impl Entity for Writer {
@@ -1199,10 +2442,47 @@ mod tests {
   use crate::serialization::cdr_serializer::CDRSerializerAdapter;
   use byteorder::LittleEndian;
   use log::info;
+  use bytes::Bytes;
+  use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    sync::{Arc, RwLock},
+    time::{Duration as StdDuration, Instant},
+  };
+  use mio_extras::channel as mio_channel;
+  use super::{BatchingPolicy, Writer, WriterCommand};
+  use crate::common::timed_event_handler::TimedEventHandler;
+  use crate::dds::{
+    ddsdata::DDSData, message_receiver::MessageReceiver, qos::HasQoSPolicy, qos::QosPolicyBuilder,
+    qos::policy::History, qos::policy::Liveliness, qos::policy::Reliability, reader::Reader,
+    rtps_config::RtpsWriterConfig, rtps_reader_proxy::RtpsReaderProxy, typedesc::TypeDesc,
+    values::result::StatusChange,
+  };
+  use crate::discovery::discovery_config::IpFamily;
+  use crate::messages::submessages::{submessage::EntitySubmessage, submessages::AckNack};
+  use crate::messages::submessages::submessage_elements::serialized_payload::SerializedPayload;
+  use crate::messages::submessages::submessages::{
+    InterpreterSubmessage, RepresentationIdentifier, INFOTIMESTAMP_Flags,
+  };
+  use crate::network::{
+    constant::TimerMessageType, local_network::LocalNetwork, util::topic_multicast_group,
+  };
+  use crate::serialization::{Message, SubmessageBody};
+  use crate::structure::{
+    dds_cache::DDSCache,
+    duration::Duration,
+    entity::Entity,
+    guid::{EntityId, GUID},
+    locator::Locator,
+    sequence_number::{SequenceNumber, SequenceNumberSet},
+  };
+  use crate::structure::{
+    cache_change::{CacheChange, ChangeKind},
+    time::Timestamp,
+  };
 
   #[test]
   fn test_writer_recieves_datawriter_cache_change_notifications() {
-    let domain_participant = DomainParticipant::new(0);
+    let domain_participant = DomainParticipant::new(0).unwrap();
     let qos = QosPolicies::qos_none();
     let _default_dw_qos = QosPolicies::qos_none();
 
@@ -1247,4 +2527,885 @@ mod tests {
     thread::sleep(std::time::Duration::from_millis(100));
     info!("writerResult:  {:?}", writeResult);
   }
+
+  // Writes one change to a reader behind a transport that loses the datagram the change
+  // is first sent in, and plays the reader asking for the change again when the next
+  // heartbeat comes. Returns the time until the change got through.
+  fn lossy_recovery_time(heartbeat_period: StdDuration) -> StdDuration {
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache.write().unwrap().add_new_topic(
+      &"lossy".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("lossy".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let (_command_sender, command_receiver) = mio_channel::sync_channel::<WriterCommand>(10);
+    let (status_sender, _status_receiver) = mio_channel::sync_channel(100);
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_secs(1),
+      })
+      .build();
+    let mut writer = Writer::new(
+      GUID::new(),
+      command_receiver,
+      dds_cache,
+      "lossy".to_string(),
+      qos,
+      status_sender,
+    );
+    let (timer_sender, timer_receiver) = mio_channel::sync_channel::<TimerMessageType>(10);
+    writer.add_timed_event_handler(TimedEventHandler::new(timer_sender));
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+      .set_read_timeout(Some(StdDuration::from_secs(5)))
+      .unwrap();
+    let reader_proxy = RtpsReaderProxy::new_for_unit_testing(socket.local_addr().unwrap().port());
+    let reader_guid = reader_proxy.remote_reader_guid;
+    writer.matched_reader_add(reader_proxy);
+    writer.set_rtps_config(RtpsWriterConfig {
+      heartbeat_period,
+      nack_response_delay: StdDuration::from_millis(0),
+      ..RtpsWriterConfig::default()
+    });
+
+    let start = Instant::now();
+    writer.insert_to_history_cache(DDSData::new(SerializedPayload::default()));
+    writer.send_or_batch_unsent();
+    let mut buf = [0u8; 2048];
+
+    let seqnum = SequenceNumber::from(1);
+    // A reliable writer sends the change with its first heartbeat.
+    let mut lost = false;
+    loop {
+      assert!(start.elapsed() < StdDuration::from_secs(5), "no recovery");
+      match timer_receiver.try_recv() {
+        Ok(TimerMessageType::writer_heartbeat) => {
+          writer.handle_heartbeat_tick();
+        }
+        _ => {
+          thread::sleep(StdDuration::from_millis(1));
+          continue;
+        }
+      }
+      let len = socket.recv(&mut buf).unwrap();
+      let message = Message::read_from_buffer(&Bytes::copy_from_slice(&buf[..len])).unwrap();
+      let has_change = message
+        .get_data_sub_message_sequence_numbers()
+        .contains(&seqnum);
+      if !lost {
+        lost = has_change;
+        continue;
+      }
+      if has_change {
+        return start.elapsed();
+      }
+      // The heartbeat announced the change, so the reader asks for it.
+      let mut missing = SequenceNumberSet::new(seqnum);
+      missing.insert(seqnum);
+      writer.handle_ack_nack(
+        reader_guid.guidPrefix,
+        AckNack {
+          reader_id: reader_guid.entityId,
+          writer_id: writer.get_entity_id(),
+          reader_sn_state: missing,
+          count: 1,
+        },
+      );
+      let len = socket.recv(&mut buf).unwrap();
+      let message = Message::read_from_buffer(&Bytes::copy_from_slice(&buf[..len])).unwrap();
+      assert!(message
+        .get_data_sub_message_sequence_numbers()
+        .contains(&seqnum));
+      return start.elapsed();
+    }
+  }
+
+  #[test]
+  fn writer_heartbeat_period_bounds_recovery_time() {
+    let slow = lossy_recovery_time(StdDuration::from_millis(400));
+    let fast = lossy_recovery_time(StdDuration::from_millis(50));
+    info!(
+      "recovery time {:?} with slow heartbeat, {:?} with fast",
+      slow, fast
+    );
+
+    // the change goes out with the first heartbeat, and is repaired after the next one
+    assert!(slow >= StdDuration::from_millis(800));
+    assert!(fast >= StdDuration::from_millis(100));
+    assert!(fast < StdDuration::from_millis(300));
+    assert!(fast * 2 < slow);
+  }
+
+  fn receive_all(socket: &UdpSocket) -> Vec<Bytes> {
+    let mut datagrams = Vec::new();
+    let mut buf = [0u8; 2048];
+    while let Ok(len) = socket.recv(&mut buf) {
+      datagrams.push(Bytes::copy_from_slice(&buf[..len]));
+    }
+    datagrams
+  }
+
+  fn received_sequence_numbers(dds_cache: &Arc<RwLock<DDSCache>>) -> Vec<SequenceNumber> {
+    let mut seqnums: Vec<SequenceNumber> = dds_cache
+      .read()
+      .unwrap()
+      .from_topic_get_all_changes("late")
+      .iter()
+      .map(|(_, cc)| cc.sequence_number)
+      .collect();
+    seqnums.sort();
+    seqnums
+  }
+
+  #[test]
+  fn late_reliable_reader_converges_with_gaps() {
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_secs(1),
+      })
+      .history(History::KeepLast { depth: 10 })
+      .build();
+    let new_cache = || {
+      let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+      dds_cache.write().unwrap().add_new_topic(
+        &"late".to_string(),
+        TopicKind::NoKey,
+        &TypeDesc::new("late".to_string()),
+        &QosPolicies::qos_none(),
+      );
+      dds_cache
+    };
+    let data_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let ack_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for socket in [&data_socket, &ack_socket].iter() {
+      socket
+        .set_read_timeout(Some(StdDuration::from_millis(100)))
+        .unwrap();
+    }
+
+    let mut writer_guid = GUID::new();
+    writer_guid.entityId = EntityId::createCustomEntityID([1, 2, 3], 2);
+    let (_command_sender, command_receiver) = mio_channel::sync_channel::<WriterCommand>(10);
+    let (status_sender, _status_receiver) = mio_channel::sync_channel(100);
+    let mut writer = Writer::new(
+      writer_guid,
+      command_receiver,
+      new_cache(),
+      "late".to_string(),
+      qos.clone(),
+      status_sender,
+    );
+    writer.set_rtps_config(RtpsWriterConfig {
+      nack_response_delay: StdDuration::from_millis(0),
+      ..RtpsWriterConfig::default()
+    });
+    for _ in 0..100 {
+      writer.insert_to_history_cache(DDSData::new(SerializedPayload::default()));
+    }
+
+    let mut reader_guid = GUID::new();
+    reader_guid.entityId = EntityId::createCustomEntityID([1, 2, 3], 7);
+    let reader_cache = new_cache();
+    let (notification_sender, _notification_receiver) = mio_channel::sync_channel::<()>(100);
+    let (reader_status_sender, reader_status_receiver) =
+      mio_channel::sync_channel::<StatusChange>(100);
+    let (_reader_command_sender, reader_command_receiver) = mio_channel::sync_channel(10);
+    let mut reader = Reader::new(
+      reader_guid,
+      notification_sender,
+      reader_status_sender,
+      reader_cache.clone(),
+      "late".to_string(),
+      reader_command_receiver,
+    );
+    reader.set_qos(&qos).unwrap();
+    reader.matched_writer_add(
+      writer_guid,
+      EntityId::ENTITYID_UNKNOWN,
+      vec![Locator::from(ack_socket.local_addr().unwrap())],
+      vec![],
+    );
+    let (reader_acknack_sender, _reader_acknack_receiver) = mio_channel::sync_channel(10);
+    let mut reader_receiver = MessageReceiver::new(reader_guid.guidPrefix, reader_acknack_sender);
+    reader_receiver.add_reader(reader);
+    let (acknack_sender, acknack_receiver) = mio_channel::sync_channel(100);
+    let mut writer_receiver = MessageReceiver::new(writer_guid.guidPrefix, acknack_sender);
+
+    // The reader matches after 90 of the samples have dropped out of the history.
+    let mut reader_proxy =
+      RtpsReaderProxy::new_for_unit_testing(data_socket.local_addr().unwrap().port());
+    reader_proxy.remote_reader_guid = reader_guid;
+    writer.matched_reader_add(reader_proxy);
+
+    // The DATA get lost, so the reader only hears the GAP and HEARTBEAT and asks
+    // for 91..=100.
+    writer.handle_heartbeat_tick();
+    for datagram in receive_all(&data_socket) {
+      let message = Message::read_from_buffer(&datagram).unwrap();
+      if message.get_data_sub_message_sequence_numbers().is_empty() {
+        reader_receiver.handle_user_msg(datagram);
+      }
+    }
+    assert!(received_sequence_numbers(&reader_cache).is_empty());
+
+    // Before the writer answers, those drop out of its history too.
+    for _ in 0..10 {
+      writer.insert_to_history_cache(DDSData::new(SerializedPayload::default()));
+    }
+
+    let expected: Vec<SequenceNumber> = (101..=110).map(SequenceNumber::from).collect();
+    let mut rounds = 0;
+    while received_sequence_numbers(&reader_cache) != expected {
+      rounds += 1;
+      assert!(rounds <= 5, "reader does not converge");
+      for datagram in receive_all(&ack_socket) {
+        writer_receiver.handle_user_msg(datagram);
+      }
+      while let Ok((guid_prefix, acknack)) = acknack_receiver.try_recv() {
+        writer.handle_ack_nack(guid_prefix, acknack);
+      }
+      for datagram in receive_all(&data_socket) {
+        reader_receiver.handle_user_msg(datagram);
+      }
+      writer.handle_heartbeat_tick();
+    }
+
+    assert!(writer.readers[0].requested_changes().is_empty());
+    // gapped changes are not lost ones
+    while let Ok(status) = reader_status_receiver.try_recv() {
+      assert!(!matches!(status, StatusChange::SampleLostStatus(_)));
+    }
+  }
+
+  // Three readers in one multicast group, each also with a unicast socket of its own.
+  // Returns a socket that has joined the group too.
+  fn multicast_group_readers() -> (UdpSocket, Vec<(UdpSocket, RtpsReaderProxy)>) {
+    let group = match topic_multicast_group(0, "multicast", IpFamily::V4) {
+      IpAddr::V4(group) => group,
+      IpAddr::V6(_) => unreachable!(),
+    };
+    let group_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    group_socket
+      .join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)
+      .unwrap();
+    group_socket
+      .set_read_timeout(Some(StdDuration::from_millis(100)))
+      .unwrap();
+    let group_locator = Locator::from(SocketAddr::new(
+      IpAddr::V4(group),
+      group_socket.local_addr().unwrap().port(),
+    ));
+
+    let readers = (0..3)
+      .map(|i| {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+          .set_read_timeout(Some(StdDuration::from_millis(100)))
+          .unwrap();
+        let mut proxy = RtpsReaderProxy::new_for_unit_testing(socket.local_addr().unwrap().port());
+        proxy.remote_reader_guid.entityId = EntityId::createCustomEntityID([0, 0, i], 7);
+        proxy.multicast_locator_list = vec![group_locator];
+        (socket, proxy)
+      })
+      .collect();
+    (group_socket, readers)
+  }
+
+  fn multicast_writer(qos: &QosPolicies) -> Writer {
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache.write().unwrap().add_new_topic(
+      &"multicast".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("multicast".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let mut writer_guid = GUID::new();
+    writer_guid.entityId = EntityId::createCustomEntityID([1, 2, 3], 2);
+    let (_command_sender, command_receiver) = mio_channel::sync_channel::<WriterCommand>(10);
+    let (status_sender, _status_receiver) = mio_channel::sync_channel(100);
+    Writer::new(
+      writer_guid,
+      command_receiver,
+      dds_cache,
+      "multicast".to_string(),
+      qos.clone(),
+      status_sender,
+    )
+  }
+
+  #[test]
+  fn writer_sends_info_timestamp_when_it_changes() {
+    let writer = multicast_writer(&QosPolicies::qos_none());
+    let first_ts = Timestamp::now();
+    let second_ts = Timestamp::TIME_ZERO;
+    let mut message = Message::new(writer.create_message_header());
+    for (i, source_timestamp) in [Some(first_ts), Some(first_ts), Some(second_ts), None, None]
+      .iter()
+      .enumerate()
+    {
+      let mut change = CacheChange::new(
+        ChangeKind::ALIVE,
+        writer.get_guid(),
+        SequenceNumber::from(i as i64 + 1),
+        Some(DDSData::new(SerializedPayload::new(
+          RepresentationIdentifier::CDR_LE,
+          vec![],
+        ))),
+      );
+      change.source_timestamp = *source_timestamp;
+      writer.add_user_submessages(&mut message, change, EntityId::ENTITYID_UNKNOWN);
+    }
+
+    // None is a DATA, Some the timestamp of an InfoTimestamp
+    let submessages: Vec<Option<Option<Timestamp>>> = message
+      .submessages
+      .iter()
+      .map(|submessage| match &submessage.body {
+        SubmessageBody::Interpreter(InterpreterSubmessage::InfoTimestamp(info_ts, flags)) => {
+          if flags.contains(INFOTIMESTAMP_Flags::Invalidate) {
+            Some(None)
+          } else {
+            Some(Some(info_ts.timestamp))
+          }
+        }
+        _ => None,
+      })
+      .collect();
+    assert_eq!(
+      submessages,
+      vec![
+        Some(Some(first_ts)),
+        None,
+        None,
+        Some(Some(second_ts)),
+        None,
+        Some(None),
+        None,
+        None,
+      ]
+    );
+  }
+
+  // Reader ids of the DATA and HEARTBEAT submessages
+  fn reader_ids(datagram: &Bytes) -> Vec<EntityId> {
+    Message::read_from_buffer(datagram)
+      .unwrap()
+      .submessages
+      .iter()
+      .filter_map(|s| match &s.body {
+        SubmessageBody::Entity(EntitySubmessage::Data(data, _)) => Some(data.reader_id),
+        SubmessageBody::Entity(EntitySubmessage::Heartbeat(hb, _)) => Some(hb.reader_id),
+        _ => None,
+      })
+      .collect()
+  }
+
+  #[test]
+  fn writer_sends_once_to_multicast_group() {
+    let mut writer = multicast_writer(&QosPolicyBuilder::new().build());
+    let (group_socket, readers) = multicast_group_readers();
+    for (_, proxy) in readers.iter() {
+      writer.matched_reader_add(proxy.clone());
+    }
+
+    for _ in 0..3 {
+      writer.insert_to_history_cache(DDSData::new(SerializedPayload::default()));
+      writer.flush();
+    }
+
+    // one send per sample, not one per reader
+    assert_eq!(writer.udp_sender.borrow().sent_datagrams(), 3);
+    let datagrams = receive_all(&group_socket);
+    assert_eq!(datagrams.len(), 3);
+    for datagram in datagrams.iter() {
+      assert_eq!(reader_ids(datagram), vec![EntityId::ENTITYID_UNKNOWN]);
+    }
+    for (socket, _) in readers.iter() {
+      assert!(receive_all(socket).is_empty());
+    }
+    assert!(writer.readers.iter().all(|r| r.unsent_changes().is_empty()));
+  }
+
+  #[test]
+  fn writer_keeps_datagrams_within_max_message_size() {
+    let mut writer = multicast_writer(&QosPolicyBuilder::new().build());
+    let mut network = LocalNetwork::default();
+    network.set_max_message_size(1400);
+    writer.use_local_network(&network);
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+      .set_read_timeout(Some(StdDuration::from_millis(100)))
+      .unwrap();
+    writer.matched_reader_add(RtpsReaderProxy::new_for_unit_testing(
+      socket.local_addr().unwrap().port(),
+    ));
+    assert!(writer.matched_readers().over_udp());
+
+    // A batch much larger than a message is split. Two samples fit in a message.
+    writer.set_batching(Some(BatchingPolicy {
+      max_samples: 100,
+      max_bytes: 1 << 20,
+      max_delay: Duration::from_secs(1),
+    }));
+    for _ in 0..10 {
+      writer.insert_to_history_cache(DDSData::new(SerializedPayload::new(
+        RepresentationIdentifier::CDR_LE,
+        vec![0; 500],
+      )));
+    }
+    writer.flush();
+    let datagrams = receive_all(&socket);
+    assert_eq!(datagrams.len(), 5);
+    assert!(datagrams.iter().all(|d| d.len() <= 1400));
+    let (data, _) = data_and_gap_sequence_numbers(&datagrams);
+    assert_eq!(data.len(), 10);
+    assert_eq!(writer.udp_sender.borrow().sent_datagrams(), 5);
+
+    // A sample that does not fit alone is not sent over UDP.
+    writer.set_batching(None);
+    writer.insert_to_history_cache(DDSData::new(SerializedPayload::new(
+      RepresentationIdentifier::CDR_LE,
+      vec![0; 1400],
+    )));
+    writer.flush();
+    assert!(receive_all(&socket).is_empty());
+    assert_eq!(writer.udp_sender.borrow().sent_datagrams(), 5);
+    assert!(writer.udp_sender.borrow().largest_datagram() <= 1400);
+  }
+
+  #[test]
+  fn multicast_group_repairs_by_unicast() {
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_secs(1),
+      })
+      .history(History::KeepLast { depth: 10 })
+      .build();
+    let mut writer = multicast_writer(&qos);
+    writer.set_rtps_config(RtpsWriterConfig {
+      nack_response_delay: StdDuration::from_millis(0),
+      ..RtpsWriterConfig::default()
+    });
+    let (group_socket, readers) = multicast_group_readers();
+    for (_, proxy) in readers.iter() {
+      writer.matched_reader_add(proxy.clone());
+    }
+    for _ in 0..2 {
+      writer.insert_to_history_cache(DDSData::new(SerializedPayload::default()));
+    }
+
+    // both changes, then a heartbeat, each sent once to the group
+    writer.handle_heartbeat_tick();
+    assert_eq!(writer.udp_sender.borrow().sent_datagrams(), 3);
+    let datagrams = receive_all(&group_socket);
+    assert_eq!(datagrams.len(), 3);
+    for datagram in datagrams.iter() {
+      assert_eq!(reader_ids(datagram), vec![EntityId::ENTITYID_UNKNOWN]);
+    }
+
+    // The last reader has lost the first change, the others have both.
+    for (i, (_, proxy)) in readers.iter().enumerate() {
+      let mut reader_sn_state = SequenceNumberSet::new(SequenceNumber::from(3));
+      if i == 2 {
+        reader_sn_state = SequenceNumberSet::new(SequenceNumber::from(1));
+        reader_sn_state.insert(SequenceNumber::from(1));
+      }
+      writer.handle_ack_nack(
+        proxy.remote_reader_guid.guidPrefix,
+        AckNack {
+          reader_id: proxy.remote_reader_guid.entityId,
+          writer_id: writer.get_entity_id(),
+          reader_sn_state,
+          count: 1,
+        },
+      );
+    }
+
+    // the repair goes to that reader only
+    let repairs = receive_all(&readers[2].0);
+    assert_eq!(repairs.len(), 1);
+    assert!(reader_ids(&repairs[0]).contains(&readers[2].1.remote_reader_guid.entityId));
+    assert!(receive_all(&group_socket).is_empty());
+    for (socket, _) in readers[..2].iter() {
+      assert!(receive_all(socket).is_empty());
+    }
+  }
+
+  // Sequence numbers of the DATA submessages, and those the GAP submessages cover
+  fn data_and_gap_sequence_numbers(
+    datagrams: &[Bytes],
+  ) -> (Vec<SequenceNumber>, Vec<SequenceNumber>) {
+    let mut data = Vec::new();
+    let mut gapped = Vec::new();
+    for datagram in datagrams.iter() {
+      for submessage in Message::read_from_buffer(datagram).unwrap().submessages {
+        match submessage.body {
+          SubmessageBody::Entity(EntitySubmessage::Data(d, _)) => data.push(d.writer_sn),
+          SubmessageBody::Entity(EntitySubmessage::Gap(gap, _)) => {
+            let mut sn = gap.gap_start;
+            while sn < gap.gap_list.base {
+              gapped.push(sn);
+              sn = sn + SequenceNumber::from(1);
+            }
+            gapped.extend(gap.gap_list.into_iter());
+          }
+          _ => (),
+        }
+      }
+    }
+    data.sort();
+    data.dedup();
+    gapped.sort();
+    gapped.dedup();
+    (data, gapped)
+  }
+
+  #[test]
+  fn reader_filter_splits_samples_between_readers() {
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_secs(1),
+      })
+      .history(History::KeepLast { depth: 10 })
+      .build();
+    let mut writer = multicast_writer(&qos);
+    let (group_socket, readers) = multicast_group_readers();
+    for (_, proxy) in readers.iter() {
+      writer.matched_reader_add(proxy.clone());
+    }
+    let guids: Vec<GUID> = readers.iter().map(|(_, p)| p.remote_reader_guid).collect();
+    assert_eq!(*writer.matched_readers().get(), guids);
+
+    // sample n is for reader (n - 1) % 3 only
+    for n in 1..=6 {
+      let mut data = DDSData::new(SerializedPayload::default());
+      data.filtered_readers = guids
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != (n - 1) % 3)
+        .map(|(_, guid)| *guid)
+        .collect();
+      assert_eq!(writer.insert_to_history_cache(data).len(), 2);
+      writer.flush();
+    }
+
+    let sns = |numbers: &[i64]| -> Vec<SequenceNumber> {
+      numbers.iter().map(|&n| SequenceNumber::from(n)).collect()
+    };
+    let expected = [
+      (sns(&[1, 4]), sns(&[2, 3, 5, 6])),
+      (sns(&[2, 5]), sns(&[1, 3, 4, 6])),
+      (sns(&[3, 6]), sns(&[1, 2, 4, 5])),
+    ];
+    // The writer holds back new changes until the last one is acknowledged, but the
+    // readers the changes were filtered from hear of them right away.
+    for ((socket, _), (_, others)) in readers.iter().zip(expected.iter()) {
+      assert_eq!(
+        data_and_gap_sequence_numbers(&receive_all(socket)),
+        (vec![], others.clone())
+      );
+    }
+    assert!(receive_all(&group_socket).is_empty());
+
+    // The heartbeat sends the rest, and keeps the split: the filtered samples are gapped
+    // again, never sent.
+    writer.handle_heartbeat_tick();
+    for ((socket, _), (own, others)) in readers.iter().zip(expected.iter()) {
+      let (data, gapped) = data_and_gap_sequence_numbers(&receive_all(socket));
+      assert_eq!(data, *own);
+      assert!(!gapped.is_empty() && gapped.iter().all(|sn| others.contains(sn)));
+    }
+  }
+
+  #[test]
+  fn writer_local_readers() {
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache.write().unwrap().add_new_topic(
+      &"local".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("local".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let mut writer_guid = GUID::new();
+    writer_guid.entityId = EntityId::createCustomEntityID([1, 2, 3], 2);
+    let (_command_sender, command_receiver) = mio_channel::sync_channel::<WriterCommand>(10);
+    let (status_sender, status_receiver) = mio_channel::sync_channel(100);
+    let mut writer = Writer::new(
+      writer_guid,
+      command_receiver,
+      dds_cache,
+      "local".to_string(),
+      QosPolicyBuilder::new().build(),
+      status_sender,
+    );
+    let reader = |i| {
+      GUID::new_with_prefix_and_id(
+        writer_guid.guidPrefix,
+        EntityId::createCustomEntityID([0, 0, i], 7),
+      )
+    };
+    let current_count = || {
+      let mut count = None;
+      while let Ok(status) = status_receiver.try_recv() {
+        if let StatusChange::PublicationMatchedStatus(status) = status {
+          count = Some(status.current_count());
+        }
+      }
+      count
+    };
+
+    assert_eq!(
+      writer.update_matched_local_readers(vec![reader(1), reader(2)]),
+      vec![reader(1), reader(2)]
+    );
+    assert_eq!(current_count(), Some(2));
+    assert_eq!(
+      writer.update_matched_local_readers(vec![reader(2), reader(3)]),
+      vec![reader(3)]
+    );
+    assert_eq!(current_count(), Some(2));
+    // nothing changed
+    assert!(writer
+      .update_matched_local_readers(vec![reader(2), reader(3)])
+      .is_empty());
+    assert_eq!(current_count(), None);
+
+    // nothing goes to the network for them
+    assert!(writer.last_change().is_none());
+    writer.insert_to_history_cache(DDSData::new(SerializedPayload::default()));
+    writer.flush();
+    assert_eq!(writer.udp_sender.borrow().sent_datagrams(), 0);
+    let change = writer.last_change().unwrap();
+    assert_eq!(change.writer_guid, writer_guid);
+    assert_eq!(change.sequence_number, SequenceNumber::from(1));
+    assert_eq!(change.received_by, None);
+  }
+
+  fn status_writer(qos: QosPolicies) -> (Writer, mio_channel::Receiver<StatusChange>) {
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+    dds_cache.write().unwrap().add_new_topic(
+      &"status".to_string(),
+      TopicKind::NoKey,
+      &TypeDesc::new("status".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let mut writer_guid = GUID::new();
+    writer_guid.entityId = EntityId::createCustomEntityID([1, 2, 3], 2);
+    let (_command_sender, command_receiver) = mio_channel::sync_channel::<WriterCommand>(10);
+    let (status_sender, status_receiver) = mio_channel::sync_channel(100);
+    let writer = Writer::new(
+      writer_guid,
+      command_receiver,
+      dds_cache,
+      "status".to_string(),
+      qos,
+      status_sender,
+    );
+    (writer, status_receiver)
+  }
+
+  #[test]
+  fn writer_acknowledgments_complete() {
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_secs(1),
+      })
+      .build();
+    let (mut writer, status_receiver) = status_writer(qos);
+    let acknowledgments_complete = || {
+      let mut count = 0;
+      while let Ok(status) = status_receiver.try_recv() {
+        if let StatusChange::AcknowledgmentsComplete = status {
+          count += 1;
+        }
+      }
+      count
+    };
+    let mut reader_proxy = RtpsReaderProxy::new_for_unit_testing(0);
+    reader_proxy.remote_reader_guid.entityId = EntityId::createCustomEntityID([1, 2, 3], 7);
+    let reader_guid = reader_proxy.remote_reader_guid;
+    writer.matched_reader_add(reader_proxy);
+    acknowledgments_complete();
+
+    for _ in 0..2 {
+      writer.insert_to_history_cache(DDSData::new(SerializedPayload::default()));
+    }
+    assert_eq!(acknowledgments_complete(), 0);
+
+    let ack = |writer: &mut Writer, base, count| {
+      let writer_id = writer.get_entity_id();
+      writer.handle_ack_nack(
+        reader_guid.guidPrefix,
+        AckNack {
+          reader_id: reader_guid.entityId,
+          writer_id,
+          reader_sn_state: SequenceNumberSet::new(SequenceNumber::from(base)),
+          count,
+        },
+      );
+    };
+    // the first change only
+    ack(&mut writer, 2, 1);
+    assert_eq!(acknowledgments_complete(), 0);
+    ack(&mut writer, 3, 2);
+    assert_eq!(acknowledgments_complete(), 1);
+    // once per completion
+    ack(&mut writer, 3, 3);
+    assert_eq!(acknowledgments_complete(), 0);
+  }
+
+  #[test]
+  fn writer_ack_waiter_ignores_best_effort_readers() {
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_secs(1),
+      })
+      .build();
+    let (mut writer, status_receiver) = status_writer(qos);
+    let ack_waiter = writer.ack_waiter();
+
+    let mut reliable_proxy = RtpsReaderProxy::new_for_unit_testing(0);
+    reliable_proxy.remote_reader_guid.entityId = EntityId::createCustomEntityID([1, 2, 3], 7);
+    let reader_guid = reliable_proxy.remote_reader_guid;
+    writer.matched_reader_add(reliable_proxy);
+    let mut best_effort_proxy = RtpsReaderProxy::new_for_unit_testing(0);
+    best_effort_proxy.remote_reader_guid.entityId = EntityId::createCustomEntityID([1, 2, 4], 7);
+    best_effort_proxy.is_reliable = false;
+    writer.matched_reader_add(best_effort_proxy);
+    while status_receiver.try_recv().is_ok() {}
+
+    for _ in 0..2 {
+      ack_waiter.add_written();
+      writer.insert_to_history_cache(DDSData::new(SerializedPayload::default()));
+    }
+    assert_eq!(ack_waiter.unacked(), 2);
+
+    let writer_id = writer.get_entity_id();
+    writer.handle_ack_nack(
+      reader_guid.guidPrefix,
+      AckNack {
+        reader_id: reader_guid.entityId,
+        writer_id,
+        reader_sn_state: SequenceNumberSet::new(SequenceNumber::from(3)),
+        count: 1,
+      },
+    );
+    // The best effort reader never acknowledges, and is not waited for.
+    assert_eq!(ack_waiter.unacked(), 0);
+    assert!(ack_waiter.wait_for_room(1, StdDuration::from_millis(0)));
+    assert!(matches!(
+      status_receiver.try_recv(),
+      Ok(StatusChange::AcknowledgmentsComplete)
+    ));
+  }
+
+  #[test]
+  fn writer_send_all_skips_requested_only_readers() {
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_secs(1),
+      })
+      .build();
+    let (mut writer, _status_receiver) = status_writer(qos);
+    let mut reader_proxy = RtpsReaderProxy::new_for_unit_testing(0);
+    reader_proxy.remote_reader_guid.entityId = EntityId::createCustomEntityID([1, 2, 3], 7);
+    let reader_guid = reader_proxy.remote_reader_guid;
+    writer.matched_reader_add(reader_proxy);
+
+    writer.insert_to_history_cache(DDSData::new(SerializedPayload::default()));
+    writer.handle_heartbeat_tick();
+    assert!(writer.readers[0].unsent_changes().is_empty());
+
+    let writer_id = writer.get_entity_id();
+    let acknack = |reader_sn_state, count| AckNack {
+      reader_id: reader_guid.entityId,
+      writer_id,
+      reader_sn_state,
+      count,
+    };
+    writer.handle_ack_nack(
+      reader_guid.guidPrefix,
+      acknack(SequenceNumberSet::new(SequenceNumber::from(2)), 1),
+    );
+    // A late duplicate of an earlier AckNack asks for the change again. The answer
+    // waits for the nack response delay.
+    let mut missing = SequenceNumberSet::new(SequenceNumber::from(1));
+    missing.insert(SequenceNumber::from(1));
+    writer.handle_ack_nack(reader_guid.guidPrefix, acknack(missing, 2));
+    assert!(!writer.readers[0].requested_changes().is_empty());
+    assert!(writer.can_send_some());
+
+    // With nothing unsent, this must return at once instead of spinning.
+    let (done_sender, done_receiver) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+      writer.send_all_unsend_messages();
+      done_sender.send(writer).unwrap();
+    });
+    let writer = done_receiver
+      .recv_timeout(StdDuration::from_secs(5))
+      .expect("send_all_unsend_messages did not return");
+    assert!(!writer.readers[0].requested_changes().is_empty());
+  }
+
+  #[test]
+  fn writer_liveliness_lost() {
+    let qos = QosPolicyBuilder::new()
+      .liveliness(Liveliness::ManualByTopic {
+        lease_duration: Duration::from_millis(50),
+      })
+      .build();
+    let (mut writer, status_receiver) = status_writer(qos);
+    let liveliness_lost = || {
+      let mut count = None;
+      while let Ok(status) = status_receiver.try_recv() {
+        if let StatusChange::LivelinessLostStatus(status) = status {
+          count = Some(status.count());
+        }
+      }
+      count
+    };
+
+    writer.handle_heartbeat_tick();
+    assert_eq!(liveliness_lost(), None);
+    std::thread::sleep(StdDuration::from_millis(100));
+    writer.handle_heartbeat_tick();
+    assert_eq!(liveliness_lost(), Some(1));
+    // lost once until asserted again
+    writer.handle_heartbeat_tick();
+    assert_eq!(liveliness_lost(), None);
+
+    writer.insert_to_history_cache(DDSData::new(SerializedPayload::default()));
+    writer.handle_heartbeat_tick();
+    assert_eq!(liveliness_lost(), None);
+    std::thread::sleep(StdDuration::from_millis(100));
+    writer.handle_heartbeat_tick();
+    assert_eq!(liveliness_lost(), Some(2));
+  }
+
+  #[test]
+  fn writer_automatic_liveliness_heartbeat() {
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::BestEffort)
+      .liveliness(Liveliness::Automatic {
+        lease_duration: Duration::from_millis(300),
+      })
+      .build();
+    let (mut writer, _status_receiver) = status_writer(qos);
+    // best effort writers heartbeat only to assert liveliness
+    assert_eq!(writer.heartbeat_period, Some(Duration::from_millis(100)));
+
+    assert!(!writer.handle_heartbeat_tick());
+    std::thread::sleep(StdDuration::from_millis(150));
+    let heartbeat_count = writer.heartbeat_message_counter;
+    assert!(writer.handle_heartbeat_tick());
+    assert_eq!(writer.heartbeat_message_counter, heartbeat_count + 1);
+    assert!(!writer.handle_heartbeat_tick());
+
+    // written data asserts liveliness as well
+    std::thread::sleep(StdDuration::from_millis(150));
+    writer.insert_to_history_cache(DDSData::new(SerializedPayload::default()));
+    assert!(!writer.handle_heartbeat_tick());
+  }
 }
@@ -7,8 +7,6 @@ use crate::{
     participant::DomainParticipant,
     rtps_writer_proxy::RtpsWriterProxy,
   },
-  network::util::get_local_multicast_locators,
-  network::util::get_local_unicast_socket_address,
 };
 
 use crate::messages::{protocol_version::ProtocolVersion, vendor_id::VendorId};
@@ -50,9 +48,30 @@ pub struct SPDPDiscoveredParticipantData {
   pub manual_liveliness_count: Option<i32>,
   pub builtin_enpoint_qos: Option<BuiltinEndpointQos>,
   pub entity_name: Option<String>,
+  // None is the same as the default empty tag, which is not sent.
+  pub domain_tag: Option<String>,
 }
 
 impl SPDPDiscoveredParticipantData {
+  // Whether this announces the same as other, which may have been received at another
+  // time.
+  pub(crate) fn same_announcement(&self, other: &SPDPDiscoveredParticipantData) -> bool {
+    self.protocol_version == other.protocol_version
+      && self.vendor_id == other.vendor_id
+      && self.expects_inline_qos == other.expects_inline_qos
+      && self.participant_guid == other.participant_guid
+      && self.metatraffic_unicast_locators == other.metatraffic_unicast_locators
+      && self.metatraffic_multicast_locators == other.metatraffic_multicast_locators
+      && self.default_unicast_locators == other.default_unicast_locators
+      && self.default_multicast_locators == other.default_multicast_locators
+      && self.available_builtin_endpoints == other.available_builtin_endpoints
+      && self.lease_duration == other.lease_duration
+      && self.manual_liveliness_count == other.manual_liveliness_count
+      && self.builtin_enpoint_qos == other.builtin_enpoint_qos
+      && self.entity_name == other.entity_name
+      && self.domain_tag == other.domain_tag
+  }
+
   pub(crate) fn as_reader_proxy(
     &self,
     is_metatraffic: bool,
@@ -121,18 +140,22 @@ impl SPDPDiscoveredParticipantData {
     lease_duration: Duration,
   ) -> SPDPDiscoveredParticipantData {
     let spdp_multicast_port = get_spdp_well_known_multicast_port(participant.domain_id());
-    let metatraffic_multicast_locators = get_local_multicast_locators(spdp_multicast_port);
+    let network = participant.local_network();
+    let metatraffic_multicast_locators = network.multicast_locators(spdp_multicast_port);
 
     let spdp_unicast_port =
       get_spdp_well_known_unicast_port(participant.domain_id(), participant.participant_id());
-    let metatraffic_unicast_locators = get_local_unicast_socket_address(spdp_unicast_port);
+    let mut metatraffic_unicast_locators = network.unicast_locators(spdp_unicast_port);
+    let tcp_locators = network.tcp_locators(participant.get_guid().guidPrefix);
+    metatraffic_unicast_locators.extend(tcp_locators.iter().cloned());
 
     let multicast_port = get_user_traffic_multicast_port(participant.domain_id());
-    let default_multicast_locators = get_local_multicast_locators(multicast_port);
+    let default_multicast_locators = network.multicast_locators(multicast_port);
 
     let unicast_port =
       get_user_traffic_unicast_port(participant.domain_id(), participant.participant_id());
-    let default_unicast_locators = get_local_unicast_socket_address(unicast_port);
+    let mut default_unicast_locators = network.unicast_locators(unicast_port);
+    default_unicast_locators.extend(tcp_locators);
 
     let builtin_endpoints = BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_PARTICIPANT_ANNOUNCER
       | BuiltinEndpointSet::DISC_BUILTIN_ENDPOINT_PARTICIPANT_DETECTOR
@@ -159,7 +182,15 @@ impl SPDPDiscoveredParticipantData {
       lease_duration: Some(Duration::from(lease_duration)),
       manual_liveliness_count: None,
       builtin_enpoint_qos: None,
-      entity_name: None,
+      entity_name: Some(participant.name().to_string()),
+      domain_tag: None,
+    }
+  }
+
+  pub fn domain_tag(&self) -> &str {
+    match &self.domain_tag {
+      Some(tag) => tag,
+      None => "",
     }
   }
 }
@@ -205,6 +236,7 @@ mod tests {
   use crate::serialization::pl_cdr_deserializer::PlCdrDeserializerAdapter;
   use crate::serialization::cdr_serializer::{to_bytes};
   use byteorder::LittleEndian;
+  use bytes::Bytes;
   use crate::{
     messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier,
     test::test_data::*,
@@ -215,7 +247,7 @@ mod tests {
   fn pdata_deserialize_serialize() {
     let data = spdp_participant_data_raw();
 
-    let rtpsmsg = Message::read_from_buffer(&data).unwrap();
+    let rtpsmsg = Message::read_from_buffer(&Bytes::copy_from_slice(&data)).unwrap();
     let submsgs = rtpsmsg.submessages();
 
     for submsg in submsgs.iter() {
@@ -253,4 +285,46 @@ mod tests {
       }
     }
   }
+
+  #[test]
+  fn pdata_domain_tag() {
+    let mut participant_data = spdp_participant_data().unwrap();
+    assert_eq!(participant_data.domain_tag(), "");
+
+    let sdata = to_bytes::<SPDPDiscoveredParticipantData, LittleEndian>(&participant_data).unwrap();
+    let deserialized: SPDPDiscoveredParticipantData =
+      PlCdrDeserializerAdapter::from_bytes(&sdata, RepresentationIdentifier::PL_CDR_LE).unwrap();
+    assert_eq!(deserialized.domain_tag, None);
+
+    participant_data.domain_tag = Some(String::from("tag"));
+    let sdata = to_bytes::<SPDPDiscoveredParticipantData, LittleEndian>(&participant_data).unwrap();
+    let deserialized: SPDPDiscoveredParticipantData =
+      PlCdrDeserializerAdapter::from_bytes(&sdata, RepresentationIdentifier::PL_CDR_LE).unwrap();
+    assert_eq!(deserialized.domain_tag(), "tag");
+    assert_eq!(
+      deserialized.participant_guid,
+      participant_data.participant_guid
+    );
+  }
+
+  #[test]
+  fn pdata_truncated_payloads() {
+    let participant_data = spdp_participant_data().unwrap();
+    let sdata = to_bytes::<SPDPDiscoveredParticipantData, LittleEndian>(&participant_data).unwrap();
+
+    // A truncated parameter list must not panic. Parameters that were received
+    // whole are still parsed.
+    for len in 0..sdata.len() {
+      let result: Result<SPDPDiscoveredParticipantData, _> =
+        PlCdrDeserializerAdapter::from_bytes(&sdata[..len], RepresentationIdentifier::PL_CDR_LE);
+      if let Ok(truncated) = result {
+        if truncated.participant_guid.is_some() {
+          assert_eq!(
+            truncated.participant_guid,
+            participant_data.participant_guid
+          );
+        }
+      }
+    }
+  }
 }
@@ -23,9 +23,7 @@ use crate::{
   dds::qos::QosPolicies,
   dds::traits::{key::Key, TopicDescription},
   discovery::content_filter_property::ContentFilterProperty,
-  dds::qos::QosPolicyBuilder,
   network::constant::get_user_traffic_unicast_port,
-  network::util::get_local_unicast_socket_address,
   serialization::{
     builtin_data_serializer::BuiltinDataSerializer,
     builtin_data_deserializer::BuiltinDataDeserializer,
@@ -92,6 +90,7 @@ pub struct SubscriptionBuiltinTopicData {
   participant_key: Option<GUID>,
   topic_name: Option<String>,
   type_name: Option<String>,
+  entity_name: Option<String>,
   durability: Option<Durability>,
   deadline: Option<Deadline>,
   latency_budget: Option<LatencyBudget>,
@@ -121,6 +120,7 @@ impl SubscriptionBuiltinTopicData {
       participant_key: None,
       topic_name: Some(topic_name.to_string()),
       type_name: Some(type_name.to_string()),
+      entity_name: None,
       durability: None,
       deadline: None,
       latency_budget: None,
@@ -169,6 +169,15 @@ impl SubscriptionBuiltinTopicData {
     self.type_name = Some(String::from(type_name));
   }
 
+  /// Name of the DataReader, only shown to people. It does not affect matching.
+  pub fn entity_name(&self) -> &Option<String> {
+    &self.entity_name
+  }
+
+  pub fn set_entity_name(&mut self, entity_name: &str) {
+    self.entity_name = Some(String::from(entity_name));
+  }
+
   pub fn durability(&self) -> &Option<Durability> {
     &self.durability
   }
@@ -223,15 +232,22 @@ impl SubscriptionBuiltinTopicData {
   }
 
   pub fn generate_qos(&self) -> QosPolicies {
-    let qos = QosPolicyBuilder::new();
-
-    let qos = match self.durability {
-      Some(d) => qos.durability(d),
-      None => qos,
-    };
-
-    // TODO: fill all fields, continue
-    qos.build()
+    QosPolicies {
+      durability: self.durability,
+      presentation: self.presentation,
+      deadline: self.deadline,
+      latency_budget: self.latency_budget,
+      ownership: self.ownership,
+      liveliness: self.liveliness,
+      time_based_filter: self.time_based_filter,
+      reliability: self.reliability,
+      destination_order: self.destination_order,
+      history: None,
+      resource_limits: None,
+      lifespan: self.lifespan,
+      reader_data_lifecycle: None,
+      writer_data_lifecycle: None,
+    }
   }
 }
 
@@ -396,6 +412,8 @@ pub struct PublicationBuiltinTopicData {
   pub participant_key: Option<GUID>,
   pub topic_name: Option<String>,
   pub type_name: Option<String>,
+  /// Name of the DataWriter, only shown to people. It does not affect matching.
+  pub entity_name: Option<String>,
   pub durability: Option<Durability>,
   pub deadline: Option<Deadline>,
   pub latency_budget: Option<LatencyBudget>,
@@ -420,6 +438,7 @@ impl PublicationBuiltinTopicData {
       participant_key: Some(participant_guid),
       topic_name: Some(topic_name.clone()),
       type_name: Some(type_name.clone()),
+      entity_name: None,
       durability: None,
       deadline: None,
       latency_budget: None,
@@ -445,6 +464,25 @@ impl PublicationBuiltinTopicData {
     self.destination_order = qos.destination_order;
     self.presentation = qos.presentation;
   }
+
+  pub fn generate_qos(&self) -> QosPolicies {
+    QosPolicies {
+      durability: self.durability,
+      presentation: self.presentation,
+      deadline: self.deadline,
+      latency_budget: self.latency_budget,
+      ownership: self.ownership,
+      liveliness: self.liveliness,
+      time_based_filter: self.time_based_filter,
+      reliability: self.reliability,
+      destination_order: self.destination_order,
+      history: None,
+      resource_limits: None,
+      lifespan: self.lifespan,
+      reader_data_lifecycle: None,
+      writer_data_lifecycle: None,
+    }
+  }
 }
 
 impl<'de> Deserialize<'de> for PublicationBuiltinTopicData {
@@ -494,7 +532,11 @@ impl DiscoveredWriterData {
     dp: &DomainParticipant,
   ) -> DiscoveredWriterData {
     let unicast_port = get_user_traffic_unicast_port(dp.domain_id(), dp.participant_id());
-    let unicast_addresses = get_local_unicast_socket_address(unicast_port);
+    let network = dp.local_network();
+    let mut unicast_addresses = network.unicast_locators(unicast_port);
+    // Readers on this host take our data from shared memory, if we have it.
+    unicast_addresses.extend(network.shm_locator(writer.get_guid()));
+    unicast_addresses.extend(network.tcp_locators(dp.get_guid().guidPrefix));
 
     let writer_proxy = WriterProxy::new(writer.get_guid(), vec![], unicast_addresses);
     let mut publication_topic_data = PublicationBuiltinTopicData::new(
@@ -559,6 +601,27 @@ pub struct TopicBuiltinTopicData {
   pub ownership: Option<Ownership>,
 }
 
+impl TopicBuiltinTopicData {
+  pub fn generate_qos(&self) -> QosPolicies {
+    QosPolicies {
+      durability: self.durability,
+      presentation: self.presentation,
+      deadline: self.deadline,
+      latency_budget: self.latency_budget,
+      ownership: self.ownership,
+      liveliness: self.liveliness,
+      time_based_filter: None,
+      reliability: self.reliability,
+      destination_order: self.destination_order,
+      history: self.history,
+      resource_limits: self.resource_limits,
+      lifespan: self.lifespan,
+      reader_data_lifecycle: None,
+      writer_data_lifecycle: None,
+    }
+  }
+}
+
 impl<'de> Deserialize<'de> for TopicBuiltinTopicData {
   fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
   where
@@ -683,7 +746,11 @@ impl Keyed for ParticipantMessageData {
   }
 }
 
-impl Key for (GuidPrefix, ParticipantMessageDataKind) {}
+impl Key for (GuidPrefix, ParticipantMessageDataKind) {
+  fn may_exceed_16_bytes() -> bool {
+    false
+  }
+}
 
 #[cfg(test)]
 mod tests {
@@ -691,17 +758,19 @@ mod tests {
 
   //use crate::serialization::cdr_serializer::to_little_endian_binary;
   use crate::serialization::{
-    Message,
+    Message, SubmessageBody,
     cdr_serializer::{to_bytes},
   };
+  use crate::messages::submessages::submessages::EntitySubmessage;
   use byteorder::LittleEndian;
+  use bytes::Bytes;
   use log::info;
   use crate::serialization::pl_cdr_deserializer::PlCdrDeserializerAdapter;
 
   use crate::{
     test::test_data::{
       subscription_builtin_topic_data, reader_proxy_data, content_filter_data, writer_proxy_data,
-      publication_builtin_topic_data, topic_data,
+      publication_builtin_topic_data, topic_data, spdp_publication_msg,
     },
   };
   use crate::{
@@ -798,7 +867,7 @@ mod tests {
       0x00,
     ];
 
-    let msg = Message::read_from_buffer(&raw_data).unwrap();
+    let msg = Message::read_from_buffer(&Bytes::copy_from_slice(&raw_data)).unwrap();
     info!("{:?}", msg);
   }
 
@@ -825,6 +894,34 @@ mod tests {
     assert_eq!(sdata, sdata2);
   }
 
+  #[test]
+  fn td_writer_key_hash_matches_fastdds() {
+    // Fast-DDS sends PID_KEY_HASH in the publication data. Our key hash must be byte-exact.
+    let msg = spdp_publication_msg();
+    let data = msg
+      .submessages
+      .iter()
+      .find_map(|sm| match &sm.body {
+        SubmessageBody::Entity(EntitySubmessage::Data(d, _)) => Some(d),
+        _ => None,
+      })
+      .unwrap();
+    let dwd: DiscoveredWriterData = PlCdrDeserializerAdapter::from_bytes(
+      &data.serialized_payload.as_ref().unwrap().value,
+      RepresentationIdentifier::PL_CDR_LE,
+    )
+    .unwrap();
+
+    let key_hash_from_fastdds: [u8; 16] = [
+      0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+      0x02,
+    ];
+    assert_eq!(
+      dwd.get_key().into_hash_key().to_le_bytes(),
+      key_hash_from_fastdds
+    );
+  }
+
   #[test]
   fn td_topic_data_ser_deser() {
     let topic_data = topic_data().unwrap();
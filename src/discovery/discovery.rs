@@ -1,6 +1,6 @@
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use mio::{Ready, Poll, PollOpt, Events};
-use mio_extras::timer::Timer;
+use mio_extras::timer::{Timeout, Timer};
 use mio_extras::channel as mio_channel;
 
 use std::{
@@ -28,33 +28,48 @@ use crate::{
   dds::values::result::Error,
   serialization::CDRDeserializerAdapter,
   structure::entity::Entity,
-  structure::guid::GUID,
+  structure::guid::{GuidPrefix, GUID},
   dds::qos::QosPolicyBuilder,
 };
 
 use crate::discovery::{
   data_types::spdp_participant_data::SPDPDiscoveredParticipantData,
   data_types::topic_data::{DiscoveredWriterData, DiscoveredReaderData},
+  discovery_config::DiscoveryConfig,
   discovery_db::DiscoveryDB,
 };
 
-use crate::structure::{duration::Duration, guid::EntityId, time::Timestamp};
+use crate::structure::{clock::SharedClock, duration::Duration, guid::EntityId, time::Timestamp};
 
-use crate::serialization::{CDRSerializerAdapter, pl_cdr_deserializer::PlCdrDeserializerAdapter};
+use crate::serialization::{
+  CDRSerializerAdapter, pl_cdr_deserializer::PlCdrDeserializerAdapter,
+  pl_cdr_serializer::PlCdrSerializerAdapter,
+};
 
 use crate::network::constant::*;
+use crate::common::lock::{read_lock, write_lock};
 use super::data_types::topic_data::{
   DiscoveredTopicData, ParticipantMessageData, ParticipantMessageDataKind,
 };
 use byteorder::LittleEndian;
 
+// New remote participants appearing within this window get a single burst of
+// our endpoint data, so that many participants starting at once do not flood.
+const NEW_PARTICIPANT_BURST_DELAY: StdDuration = StdDuration::from_millis(100);
+
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum DiscoveryCommand {
   STOP_DISCOVERY,
   REMOVE_LOCAL_WRITER { guid: GUID },
   REMOVE_LOCAL_READER { guid: GUID },
+  REMOVE_LOCAL_TOPIC { key: GUID },
   REFRESH_LAST_MANUAL_LIVELINESS,
   ASSERT_TOPIC_LIVELINESS { writer_guid: GUID },
+  IGNORE_PARTICIPANT { guid_prefix: GuidPrefix },
+  IGNORE_PUBLICATION { guid: GUID },
+  IGNORE_SUBSCRIPTION { guid: GUID },
+  STATIC_ENDPOINTS_ADDED,
+  LOCAL_ENDPOINTS_CHANGED,
 }
 
 pub struct LivelinessState {
@@ -63,10 +78,10 @@ pub struct LivelinessState {
 }
 
 impl LivelinessState {
-  pub fn new() -> LivelinessState {
+  pub fn new(now: Timestamp) -> LivelinessState {
     LivelinessState {
-      last_auto_update: Timestamp::now(),
-      last_manual_participant_update: Timestamp::now(),
+      last_auto_update: now,
+      last_manual_participant_update: now,
     }
   }
 }
@@ -78,20 +93,15 @@ pub(crate) struct Discovery {
   discovery_started_sender: std::sync::mpsc::Sender<Result<(), Error>>,
   discovery_updated_sender: mio_channel::SyncSender<DiscoveryNotificationType>,
   discovery_command_receiver: mio_channel::Receiver<DiscoveryCommand>,
+  config: DiscoveryConfig,
+  // the clock of the participant, for liveliness and lease expiry
+  clock: SharedClock,
 }
 
 unsafe impl Sync for Discovery {}
 unsafe impl Send for Discovery {}
 
 impl Discovery {
-  const PARTICIPANT_CLEANUP_PERIOD: StdDuration = StdDuration::from_secs(2);
-  const TOPIC_CLEANUP_PERIOD: StdDuration = StdDuration::from_secs(10); // timer for cleaning up inactive topics
-  const SEND_PARTICIPANT_INFO_PERIOD: StdDuration = StdDuration::from_secs(2);
-  const SEND_READERS_INFO_PERIOD: StdDuration = StdDuration::from_secs(2);
-  const SEND_WRITERS_INFO_PERIOD: StdDuration = StdDuration::from_secs(2);
-  const SEND_TOPIC_INFO_PERIOD: StdDuration = StdDuration::from_secs(20);
-  const CHECK_PARTICIPANT_MESSAGES: StdDuration = StdDuration::from_secs(1);
-
   pub(crate) const PARTICIPANT_MESSAGE_QOS: QosPolicies = QosPolicies {
     durability: Some(Durability::TransientLocal),
     presentation: None,
@@ -107,6 +117,8 @@ impl Discovery {
     history: Some(History::KeepLast { depth: 1 }),
     resource_limits: None,
     lifespan: None,
+    reader_data_lifecycle: None,
+    writer_data_lifecycle: None,
   };
 
   pub fn new(
@@ -115,29 +127,36 @@ impl Discovery {
     discovery_started_sender: std::sync::mpsc::Sender<Result<(), Error>>,
     discovery_updated_sender: mio_channel::SyncSender<DiscoveryNotificationType>,
     discovery_command_receiver: mio_channel::Receiver<DiscoveryCommand>,
-  ) -> Discovery {
-    let poll = match mio::Poll::new() {
-      Ok(p) => p,
-      Err(e) => {
-        error!("Failed to start discovery poll. {:?}", e);
-        discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
-        panic!("");
-      }
+    config: DiscoveryConfig,
+  ) -> Result<Discovery, Error> {
+    let poll = mio::Poll::new().map_err(|e| Error::io("failed to start discovery poll", e))?;
+
+    let clock = {
+      let mut db = write_lock(&discovery_db);
+      db.set_domain_tag(config.domain_tag.clone());
+      db.set_ip_families(config.ip_families.clone());
+      db.clock()
     };
 
-    Discovery {
+    Ok(Discovery {
       poll,
       domain_participant,
       discovery_db,
       discovery_started_sender,
       discovery_updated_sender,
       discovery_command_receiver,
-    }
+      config,
+      clock,
+    })
   }
 
-  fn create_spdp_patricipant_qos() -> QosPolicies {
+  // Tells the DomainParticipant why Discovery did not start
+  fn send_start_error(&self, error: Error) {
+    // were trying to quit, if send fails just ignore
+    self.discovery_started_sender.send(Err(error)).unwrap_or(());
+  }
+
+  pub(crate) fn create_spdp_patricipant_qos() -> QosPolicies {
     QosPolicyBuilder::new()
       .reliability(Reliability::BestEffort)
       .history(History::KeepLast { depth: 1 })
@@ -145,7 +164,9 @@ impl Discovery {
   }
 
   pub fn discovery_event_loop(discovery: Discovery) {
-    let mut liveliness_state = LivelinessState::new();
+    let participant = discovery.domain_participant.get_guid_prefix();
+    let _participant = dds_span!(info, "participant", participant = participant);
+    let mut liveliness_state = LivelinessState::new(discovery.clock.now());
 
     match discovery.poll.register(
       &discovery.discovery_command_receiver,
@@ -155,12 +176,7 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Failed to register Discovery STOP. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io("failed to register Discovery STOP", e));
         return;
       }
     };
@@ -172,12 +188,10 @@ impl Discovery {
     {
       Ok(s) => s,
       Err(e) => {
-        error!("Unable to create Discovery Subscriber. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create Discovery Subscriber: {}",
+          e
+        )));
         return;
       }
     };
@@ -189,12 +203,10 @@ impl Discovery {
     {
       Ok(p) => p,
       Err(e) => {
-        error!("Unable to create Discovery Publisher. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create Discovery Publisher: {}",
+          e
+        )));
         return;
       }
     };
@@ -208,12 +220,10 @@ impl Discovery {
     ) {
       Ok(t) => t,
       Err(e) => {
-        error!("Unable to create DCPSParticipant topic. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create DCPSParticipant topic: {}",
+          e
+        )));
         return;
       }
     };
@@ -226,12 +236,10 @@ impl Discovery {
       ) {
         Ok(r) => r,
         Err(e) => {
-          error!("Unable to create DataReader for DCPSParticipant. {:?}", e);
-           // were trying to quit, if send fails just ignore
-           discovery
-            .discovery_started_sender
-            .send(Err(Error::PreconditionNotMet))
-            .unwrap_or(());
+          discovery.send_start_error(Error::precondition_not_met(format!(
+            "unable to create DataReader for DCPSParticipant: {}",
+            e
+          )));
           return;
         }
       };
@@ -245,19 +253,21 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Failed to register participant reader to poll. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io(
+          "failed to register participant reader to poll",
+          e,
+        ));
         return;
       }
     };
 
-    // create lease duration check timer
+    // create lease duration check timer. It fires periodically, and also when the
+    // lease of some remote participant expires.
     let mut participant_cleanup_timer: Timer<()> = Timer::default();
-    participant_cleanup_timer.set_timeout(Discovery::PARTICIPANT_CLEANUP_PERIOD, ());
+    let mut participant_cleanup_at =
+      discovery.clock.instant() + discovery.config.participant_cleanup_period;
+    let mut participant_cleanup_timeout: Timeout =
+      participant_cleanup_timer.set_timeout(discovery.config.participant_cleanup_period, ());
     match discovery.poll.register(
       &participant_cleanup_timer,
       DISCOVERY_PARTICIPANT_CLEANUP_TOKEN,
@@ -266,37 +276,32 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Unable to create participant cleanup timer. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io("unable to register participant cleanup timer", e));
         return;
       }
     };
 
-    let dcps_participant_writer = match discovery_publisher
-      .create_datawriter::<SPDPDiscoveredParticipantData, CDRSerializerAdapter<SPDPDiscoveredParticipantData,LittleEndian> >(
+    let mut dcps_participant_writer = match discovery_publisher
+      .create_datawriter::<SPDPDiscoveredParticipantData, PlCdrSerializerAdapter<SPDPDiscoveredParticipantData,LittleEndian> >(
         Some(EntityId::ENTITYID_SPDP_BUILTIN_PARTICIPANT_WRITER),
         &dcps_participant_topic,
         None,
       ) {
         Ok(w) => w,
         Err(e) => {
-          error!("Unable to create DataWriter for DCPSParticipant. {:?}", e);
-          // were trying to quit, if send fails just ignore
-          discovery
-            .discovery_started_sender
-            .send(Err(Error::PreconditionNotMet))
-            .unwrap_or(());
+          discovery.send_start_error(Error::precondition_not_met(format!(
+            "unable to create DataWriter for DCPSParticipant: {}",
+            e
+          )));
           return;
         }
       };
 
     // creating timer for sending out own participant data
     let mut participant_send_info_timer: Timer<()> = Timer::default();
-    participant_send_info_timer.set_timeout(Discovery::SEND_PARTICIPANT_INFO_PERIOD, ());
+    if discovery.announces_participant() {
+      participant_send_info_timer.set_timeout(discovery.config.participant_announce_period(), ());
+    }
 
     match discovery.poll.register(
       &participant_send_info_timer,
@@ -306,12 +311,7 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Unable to register participant info sender. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io("unable to register participant info sender", e));
         return;
       }
     };
@@ -326,12 +326,10 @@ impl Discovery {
     ) {
       Ok(t) => t,
       Err(e) => {
-        error!("Unable to create DCPSSubscription topic. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create DCPSSubscription topic: {}",
+          e
+        )));
         return;
       }
     };
@@ -344,12 +342,10 @@ impl Discovery {
       ) {
       Ok(r) => r,
       Err(e) => {
-        error!("Unable to create DataReader for DCPSSubscription. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create DataReader for DCPSSubscription: {}",
+          e
+        )));
         return;
       }
     };
@@ -362,36 +358,29 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Unable to register subscription reader. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io("unable to register subscription reader", e));
         return;
       }
     };
 
     let mut dcps_subscription_writer = match discovery_publisher
-      .create_datawriter::<DiscoveredReaderData,CDRSerializerAdapter<DiscoveredReaderData,LittleEndian>>(
+      .create_datawriter::<DiscoveredReaderData,PlCdrSerializerAdapter<DiscoveredReaderData,LittleEndian>>(
         Some(EntityId::ENTITYID_SEDP_BUILTIN_SUBSCRIPTIONS_WRITER),
         &dcps_subscription_topic,
         None,
       ) {
         Ok(w) => w,
         Err(e) => {
-          error!("Unable to create DataWriter for DCPSSubscription. {:?}", e);
-          // were trying to quit, if send fails just ignore
-          discovery
-            .discovery_started_sender
-            .send(Err(Error::PreconditionNotMet))
-            .unwrap_or(());
+          discovery.send_start_error(Error::precondition_not_met(format!(
+            "unable to create DataWriter for DCPSSubscription: {}",
+            e
+          )));
           return;
         }
       };
 
     let mut readers_send_info_timer: Timer<()> = Timer::default();
-    readers_send_info_timer.set_timeout(Discovery::SEND_READERS_INFO_PERIOD, ());
+    readers_send_info_timer.set_timeout(discovery.config.send_readers_info_period, ());
     match discovery.poll.register(
       &readers_send_info_timer,
       DISCOVERY_SEND_READERS_INFO_TOKEN,
@@ -400,12 +389,7 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Unable to register readers info sender. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io("unable to register readers info sender", e));
         return;
       }
     };
@@ -420,12 +404,10 @@ impl Discovery {
     ) {
       Ok(t) => t,
       Err(e) => {
-        error!("Unable to create DCPSPublication topic. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create DCPSPublication topic: {}",
+          e
+        )));
         return;
       }
     };
@@ -438,12 +420,10 @@ impl Discovery {
       ) {
       Ok(r) => r,
       Err(e) => {
-        error!("Unable to create DataReader for DCPSPublication. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create DataReader for DCPSPublication: {}",
+          e
+        )));
         return;
       }
     };
@@ -456,36 +436,29 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Unable to regiser writers info sender. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io("unable to register publication reader", e));
         return;
       }
     };
 
     let mut dcps_publication_writer = match discovery_publisher
-      .create_datawriter::<DiscoveredWriterData, CDRSerializerAdapter<DiscoveredWriterData,LittleEndian>>(
+      .create_datawriter::<DiscoveredWriterData, PlCdrSerializerAdapter<DiscoveredWriterData,LittleEndian>>(
         Some(EntityId::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_WRITER),
         &dcps_publication_topic,
         None,
       ) {
         Ok(w) => w,
         Err(e) => {
-          error!("Unable to create DataWriter for DCPSPublication. {:?}", e);
-          // were trying to quit, if send fails just ignore
-          discovery
-            .discovery_started_sender
-            .send(Err(Error::PreconditionNotMet))
-            .unwrap_or(());
+          discovery.send_start_error(Error::precondition_not_met(format!(
+            "unable to create DataWriter for DCPSPublication: {}",
+            e
+          )));
           return;
         }
       };
 
     let mut writers_send_info_timer: Timer<()> = Timer::default();
-    writers_send_info_timer.set_timeout(Discovery::SEND_WRITERS_INFO_PERIOD, ());
+    writers_send_info_timer.set_timeout(discovery.config.send_writers_info_period, ());
     match discovery.poll.register(
       &writers_send_info_timer,
       DISCOVERY_SEND_WRITERS_INFO_TOKEN,
@@ -494,12 +467,7 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Unable to register readers info sender. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io("unable to register writers info sender", e));
         return;
       }
     };
@@ -514,19 +482,17 @@ impl Discovery {
     ) {
       Ok(t) => t,
       Err(e) => {
-        error!("Unable to create DCPSTopic topic. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create DCPSTopic topic: {}",
+          e
+        )));
         return;
       }
     };
 
     // create lease duration check timer
     let mut topic_cleanup_timer: Timer<()> = Timer::default();
-    topic_cleanup_timer.set_timeout(Discovery::TOPIC_CLEANUP_PERIOD, ());
+    topic_cleanup_timer.set_timeout(discovery.config.topic_cleanup_period, ());
     match discovery.poll.register(
       &topic_cleanup_timer,
       DISCOVERY_TOPIC_CLEANUP_TOKEN,
@@ -535,12 +501,7 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Unable to register topic cleanup timer. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io("unable to register topic cleanup timer", e));
         return;
       }
     };
@@ -553,12 +514,10 @@ impl Discovery {
       ) {
       Ok(r) => r,
       Err(e) => {
-        error!("Unable to create DataReader for DCPSTopic. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create DataReader for DCPSTopic: {}",
+          e
+        )));
         return;
       }
     };
@@ -571,36 +530,31 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Unable to register topic reader. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io("unable to register topic reader", e));
         return;
       }
     };
 
     let mut dcps_writer = match discovery_publisher
-      .create_datawriter::<DiscoveredTopicData, CDRSerializerAdapter<DiscoveredTopicData,LittleEndian>>(
+      .create_datawriter::<DiscoveredTopicData, PlCdrSerializerAdapter<DiscoveredTopicData,LittleEndian>>(
         Some(EntityId::ENTITYID_SEDP_BUILTIN_TOPIC_WRITER),
         &dcps_topic,
         None,
       ) {
         Ok(w) => w,
         Err(e) => {
-          error!("Unable to create DataWriter for DCPSTopic. {:?}", e);
-          // were trying to quit, if send fails just ignore
-          discovery
-            .discovery_started_sender
-            .send(Err(Error::PreconditionNotMet))
-            .unwrap_or(());
+          discovery.send_start_error(Error::precondition_not_met(format!(
+            "unable to create DataWriter for DCPSTopic: {}",
+            e
+          )));
           return;
         }
       };
 
     let mut topic_info_send_timer: Timer<()> = Timer::default();
-    topic_info_send_timer.set_timeout(Discovery::SEND_TOPIC_INFO_PERIOD, ());
+    if discovery.announces_endpoints() {
+      topic_info_send_timer.set_timeout(discovery.config.send_topic_info_period, ());
+    }
     match discovery.poll.register(
       &topic_info_send_timer,
       DISCOVERY_SEND_TOPIC_INFO_TOKEN,
@@ -609,12 +563,7 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Unable to register topic info sender. {:?}", e);
-        // were trying to quit, if send fails just ignore
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io("unable to register topic info sender", e));
         return;
       }
     };
@@ -628,11 +577,10 @@ impl Discovery {
     ) {
       Ok(t) => t,
       Err(e) => {
-        error!("Unable to create DCPSParticipantMessage topic. {:?}", e);
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create DCPSParticipantMessage topic: {}",
+          e
+        )));
         return;
       }
     };
@@ -645,11 +593,10 @@ impl Discovery {
       ) {
       Ok(r) => r,
       Err(e) => {
-        error!("Unable to create DCPSParticipantMessage reader. {:?}", e);
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create DCPSParticipantMessage reader: {}",
+          e
+        )));
         return;
       }
     };
@@ -662,11 +609,10 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Unable to register DCPSParticipantMessage reader. {:?}", e);
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io(
+          "unable to register DCPSParticipantMessage reader",
+          e,
+        ));
         return;
       }
     };
@@ -679,17 +625,19 @@ impl Discovery {
       ) {
       Ok(w) => w,
       Err(e) => {
-        error!("Unable to create DCPSParticipantMessage writer. {:?}", e);
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::precondition_not_met(format!(
+          "unable to create DCPSParticipantMessage writer: {}",
+          e
+        )));
         return;
       }
     };
 
     let mut dcps_participant_message_timer = mio_extras::timer::Timer::default();
-    dcps_participant_message_timer.set_timeout(Discovery::CHECK_PARTICIPANT_MESSAGES, ());
+    if !discovery.config.passive {
+      dcps_participant_message_timer
+        .set_timeout(discovery.config.check_participant_messages_period, ());
+    }
     match discovery.poll.register(
       &dcps_participant_message_timer,
       DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN,
@@ -698,11 +646,29 @@ impl Discovery {
     ) {
       Ok(_) => (),
       Err(e) => {
-        error!("Unable to register DCPSParticipantMessage timer. {:?}", e);
-        discovery
-          .discovery_started_sender
-          .send(Err(Error::PreconditionNotMet))
-          .unwrap_or(());
+        discovery.send_start_error(Error::io(
+          "unable to register DCPSParticipantMessage timer",
+          e,
+        ));
+        return;
+      }
+    };
+
+    // Fired shortly after a new remote participant appears. Not armed until then.
+    let mut new_participant_burst_timer: Timer<()> = Timer::default();
+    let mut new_participant_burst_pending = false;
+    match discovery.poll.register(
+      &new_participant_burst_timer,
+      DISCOVERY_NEW_PARTICIPANT_BURST_TOKEN,
+      Ready::readable(),
+      PollOpt::edge(),
+    ) {
+      Ok(_) => (),
+      Err(e) => {
+        discovery.send_start_error(Error::io(
+          "unable to register new participant burst timer",
+          e,
+        ));
         return;
       }
     };
@@ -735,26 +701,21 @@ impl Discovery {
               DiscoveryCommand::STOP_DISCOVERY => {
                 info!("Stopping Discovery");
 
-                // disposing readers
-                let db = discovery.discovery_db_read();
-                let readers = db.get_all_local_topic_readers();
-                for reader in readers {
-                  dcps_subscription_writer
-                    .dispose(reader.reader_proxy.remote_reader_guid.unwrap(), None)
-                    .unwrap_or(());
-                }
-
-                let writers = db.get_all_local_topic_writers();
-                for writer in writers {
-                  dcps_publication_writer
-                    .dispose(writer.writer_proxy.remote_writer_guid.unwrap(), None)
-                    .unwrap_or(());
+                // disposing the readers, writers and topics we have announced
+                if discovery.announces_endpoints() {
+                  dcps_subscription_writer.dispose_all(None).unwrap_or(());
+                  dcps_publication_writer.dispose_all(None).unwrap_or(());
+                  dcps_writer.dispose_all(None).unwrap_or(());
                 }
 
                 // finally disposing the participant we have
-                let guid = discovery.domain_participant.get_guid();
-                dcps_participant_writer.dispose(guid, None).unwrap_or(());
+                if discovery.announces_participant() {
+                  dcps_participant_writer.dispose_all(None).unwrap_or(());
+                }
 
+                // Make room for the commands our own endpoints send when dropped, so
+                // that dropping them does not block.
+                while discovery.discovery_command_receiver.try_recv().is_ok() {}
                 return;
               }
               DiscoveryCommand::REMOVE_LOCAL_WRITER { guid } => {
@@ -762,102 +723,184 @@ impl Discovery {
                   continue;
                 }
 
-                dcps_publication_writer.dispose(guid, None).unwrap_or(());
+                if discovery.announces_endpoints() {
+                  dcps_publication_writer.dispose(&guid, None).unwrap_or(());
+                }
 
-                match discovery.discovery_db.write() {
-                  Ok(mut db) => {
-                    db.remove_local_topic_writer(guid);
-                  }
-                  Err(e) => panic!("DiscoveryDB is poisoned. {:?}", e),
-                };
+                discovery
+                  .discovery_db_write()
+                  .remove_local_topic_writer(guid);
+                // our own readers matched with it
+                discovery
+                  .send_discovery_notification(DiscoveryNotificationType::ReadersInfoUpdated);
               }
               DiscoveryCommand::REMOVE_LOCAL_READER { guid } => {
                 if guid == dcps_subscription_writer.get_guid() {
                   continue;
                 }
 
-                dcps_subscription_writer.dispose(guid, None).unwrap_or(());
+                if discovery.announces_endpoints() {
+                  dcps_subscription_writer.dispose(&guid, None).unwrap_or(());
+                }
 
-                match discovery.discovery_db.write() {
-                  Ok(mut db) => {
-                    db.remove_local_topic_reader(guid);
-                  }
-                  Err(e) => panic!("DiscoveryDB is poisoned. {:?}", e),
-                };
+                discovery
+                  .discovery_db_write()
+                  .remove_local_topic_reader(guid);
+                // our own writers matched with it
+                discovery.send_discovery_notification(
+                  DiscoveryNotificationType::WritersInfoUpdated {
+                    needs_new_cache_change: false,
+                  },
+                );
+              }
+              // DiscoveryDB no longer has the topic, it was removed when it got unused
+              DiscoveryCommand::REMOVE_LOCAL_TOPIC { key } => {
+                if discovery.announces_endpoints() {
+                  dcps_writer.dispose(&key, None).unwrap_or(());
+                }
               }
               DiscoveryCommand::REFRESH_LAST_MANUAL_LIVELINESS => {
-                liveliness_state.last_manual_participant_update = Timestamp::now();
+                liveliness_state.last_manual_participant_update = discovery.clock.now();
               }
               DiscoveryCommand::ASSERT_TOPIC_LIVELINESS { writer_guid } => {
                 discovery.send_discovery_notification(
                   DiscoveryNotificationType::AssertTopicLiveliness { writer_guid },
                 );
               }
+              DiscoveryCommand::IGNORE_PARTICIPANT { guid_prefix } => {
+                discovery
+                  .discovery_db_write()
+                  .ignore_participant(guid_prefix);
+                discovery.send_discovery_notification(
+                  DiscoveryNotificationType::ParticipantIgnored { guid_prefix },
+                );
+                discovery.send_discovery_notification(
+                  DiscoveryNotificationType::WritersInfoUpdated {
+                    needs_new_cache_change: false,
+                  },
+                );
+                discovery
+                  .send_discovery_notification(DiscoveryNotificationType::ReadersInfoUpdated);
+              }
+              DiscoveryCommand::IGNORE_PUBLICATION { guid } => {
+                discovery.discovery_db_write().ignore_publication(guid);
+                discovery
+                  .send_discovery_notification(DiscoveryNotificationType::ReadersInfoUpdated);
+              }
+              DiscoveryCommand::IGNORE_SUBSCRIPTION { guid } => {
+                discovery.discovery_db_write().ignore_subscription(guid);
+                discovery.send_discovery_notification(
+                  DiscoveryNotificationType::WritersInfoUpdated {
+                    needs_new_cache_change: false,
+                  },
+                );
+              }
+              DiscoveryCommand::STATIC_ENDPOINTS_ADDED => {
+                discovery.send_discovery_notification(
+                  DiscoveryNotificationType::WritersInfoUpdated {
+                    needs_new_cache_change: true,
+                  },
+                );
+                discovery
+                  .send_discovery_notification(DiscoveryNotificationType::ReadersInfoUpdated);
+                discovery.report_qos_mismatches();
+              }
+              DiscoveryCommand::LOCAL_ENDPOINTS_CHANGED => {
+                discovery.send_discovery_notification(
+                  DiscoveryNotificationType::WritersInfoUpdated {
+                    needs_new_cache_change: false,
+                  },
+                );
+                discovery
+                  .send_discovery_notification(DiscoveryNotificationType::ReadersInfoUpdated);
+              }
             };
           }
         } else if event.token() == DISCOVERY_PARTICIPANT_DATA_TOKEN {
           let data = discovery.handle_participant_reader(&mut dcps_participant_reader);
           match data {
-            Some(dat) => {
-              discovery.update_spdp_participant_writer(dat);
+            Some(is_new) => {
+              // A participant with a short lease may expire before the next cleanup.
+              let next_expiry = discovery
+                .discovery_db_read()
+                .next_participant_lease_expiry();
+              if let Some(expiry) = next_expiry {
+                if expiry < participant_cleanup_at {
+                  participant_cleanup_timer.cancel_timeout(&participant_cleanup_timeout);
+                  participant_cleanup_timeout = participant_cleanup_timer.set_timeout(
+                    expiry.saturating_duration_since(discovery.clock.instant()),
+                    (),
+                  );
+                  participant_cleanup_at = expiry;
+                }
+              }
+              // Don't make the newcomer wait for our periodic announcements.
+              let announces = discovery.announces_participant() || discovery.announces_endpoints();
+              if is_new && announces && !new_participant_burst_pending {
+                new_participant_burst_timer.set_timeout(NEW_PARTICIPANT_BURST_DELAY, ());
+                new_participant_burst_pending = true;
+              }
             }
             None => (),
           }
         } else if event.token() == DISCOVERY_PARTICIPANT_CLEANUP_TOKEN {
           discovery.participant_cleanup();
           // setting next cleanup timeout
-          participant_cleanup_timer.set_timeout(Discovery::PARTICIPANT_CLEANUP_PERIOD, ());
+          let delay = discovery.participant_cleanup_delay();
+          participant_cleanup_at = discovery.clock.instant() + delay;
+          participant_cleanup_timeout = participant_cleanup_timer.set_timeout(delay, ());
         } else if event.token() == DISCOVERY_SEND_PARTICIPANT_INFO_TOKEN {
-          // setting 3 times the duration so lease doesn't break if we fail once for some reason
-          let lease_duration = Discovery::SEND_PARTICIPANT_INFO_PERIOD
-            + Discovery::SEND_PARTICIPANT_INFO_PERIOD
-            + Discovery::SEND_PARTICIPANT_INFO_PERIOD;
-          let strong_dp = match discovery.domain_participant.clone().upgrade() {
-            Some(dp) => dp,
-            None => {
-              error!("DomainParticipant doesn't exist anymore, exiting Discovery.");
-              return;
-            }
-          };
-          let data = SPDPDiscoveredParticipantData::from_participant(
-            &strong_dp,
-            Duration::from(lease_duration),
-          );
-
-          dcps_participant_writer.write(data, None).unwrap_or(());
+          if !discovery.write_participant_info(&mut dcps_participant_writer) {
+            error!("DomainParticipant doesn't exist anymore, exiting Discovery.");
+            return;
+          }
           // reschedule timer
-          participant_send_info_timer.set_timeout(Discovery::SEND_PARTICIPANT_INFO_PERIOD, ());
+          participant_send_info_timer
+            .set_timeout(discovery.config.participant_announce_period(), ());
+        } else if event.token() == DISCOVERY_NEW_PARTICIPANT_BURST_TOKEN {
+          new_participant_burst_pending = false;
+          if !discovery.write_participant_info(&mut dcps_participant_writer) {
+            error!("DomainParticipant doesn't exist anymore, exiting Discovery.");
+            return;
+          }
+          discovery.write_readers_info(&mut dcps_subscription_writer);
+          discovery.write_writers_info(&mut dcps_publication_writer);
         } else if event.token() == DISCOVERY_READER_DATA_TOKEN {
           discovery.handle_subscription_reader(&mut dcps_subscription_reader);
+          discovery.report_qos_mismatches();
         } else if event.token() == DISCOVERY_SEND_READERS_INFO_TOKEN {
           if discovery.read_readers_info() {
+            discovery.report_qos_mismatches();
             discovery.write_readers_info(&mut dcps_subscription_writer);
           }
 
-          readers_send_info_timer.set_timeout(Discovery::SEND_READERS_INFO_PERIOD, ());
+          readers_send_info_timer.set_timeout(discovery.config.send_readers_info_period, ());
         } else if event.token() == DISCOVERY_WRITER_DATA_TOKEN {
           discovery.handle_publication_reader(&mut dcps_publication_reader);
+          discovery.report_qos_mismatches();
         } else if event.token() == DISCOVERY_SEND_WRITERS_INFO_TOKEN {
           if discovery.read_writers_info() {
+            discovery.report_qos_mismatches();
             discovery.write_writers_info(&mut dcps_publication_writer);
           }
 
-          writers_send_info_timer.set_timeout(Discovery::SEND_WRITERS_INFO_PERIOD, ());
+          writers_send_info_timer.set_timeout(discovery.config.send_writers_info_period, ());
         } else if event.token() == DISCOVERY_TOPIC_DATA_TOKEN {
           discovery.handle_topic_reader(&mut dcps_reader);
         } else if event.token() == DISCOVERY_TOPIC_CLEANUP_TOKEN {
           discovery.topic_cleanup();
 
-          topic_cleanup_timer.set_timeout(Discovery::TOPIC_CLEANUP_PERIOD, ());
+          topic_cleanup_timer.set_timeout(discovery.config.topic_cleanup_period, ());
         } else if event.token() == DISCOVERY_SEND_TOPIC_INFO_TOKEN {
           discovery.write_topic_info(&mut dcps_writer);
-          topic_info_send_timer.set_timeout(Discovery::SEND_TOPIC_INFO_PERIOD, ());
+          topic_info_send_timer.set_timeout(discovery.config.send_topic_info_period, ());
         } else if event.token() == DISCOVERY_PARTICIPANT_MESSAGE_TOKEN {
           discovery.handle_participant_message_reader(&mut dcps_participant_message_reader);
         } else if event.token() == DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN {
           discovery
             .write_participant_message(&mut dcps_participant_message_writer, &mut liveliness_state);
-          dcps_participant_message_timer.set_timeout(Discovery::CHECK_PARTICIPANT_MESSAGES, ());
+          dcps_participant_message_timer
+            .set_timeout(discovery.config.check_participant_messages_period, ());
         }
       }
     }
@@ -872,20 +915,26 @@ impl Discovery {
     });
   }
 
+  // Returns whether the participant is new, if it announced something else than before
   pub fn handle_participant_reader(
     &self,
     reader: &mut DataReader<
       SPDPDiscoveredParticipantData,
       PlCdrDeserializerAdapter<SPDPDiscoveredParticipantData>,
     >,
-  ) -> Option<SPDPDiscoveredParticipantData> {
+  ) -> Option<bool> {
+    if !self.config.spdp_enabled {
+      // drop everything, remote participants are not discovered
+      while let Ok(Some(_)) = reader.take_next_sample() {}
+      return None;
+    }
     let participant_data = match reader.take_next_sample() {
       Ok(d) => match d {
-        Some(d) => match d.value() {
-          Ok(aaaaa) => (aaaaa).clone(),
+        Some(d) => match d.into_value() {
+          Ok(participant_data) => participant_data,
           Err(key) => {
             // we should dispose participant here
-            self.discovery_db_write().remove_participant(*key);
+            self.discovery_db_write().remove_participant(key);
             self.send_discovery_notification(DiscoveryNotificationType::WritersInfoUpdated {
               needs_new_cache_change: false,
             });
@@ -899,17 +948,19 @@ impl Discovery {
     };
 
     let mut db = self.discovery_db_write();
-    let updated = db.update_participant(&participant_data);
-    if updated {
-      self.send_discovery_notification(DiscoveryNotificationType::WritersInfoUpdated {
-        needs_new_cache_change: false,
-      });
-      self.send_discovery_notification(DiscoveryNotificationType::ReadersInfoUpdated);
-
-      return Some(participant_data);
+    let is_new = match participant_data.participant_guid {
+      Some(guid) => db.get_participant(guid.guidPrefix).is_none(),
+      None => false,
+    };
+    if !db.update_participant(&participant_data) {
+      return None;
     }
-
-    None
+    // Our announcements are sent again, also to the participant if it is new.
+    self.send_discovery_notification(DiscoveryNotificationType::WritersInfoUpdated {
+      needs_new_cache_change: true,
+    });
+    self.send_discovery_notification(DiscoveryNotificationType::ReadersInfoUpdated);
+    Some(is_new)
   }
 
   pub fn handle_subscription_reader(
@@ -917,25 +968,34 @@ impl Discovery {
     reader: &mut DataReader<DiscoveredReaderData, PlCdrDeserializerAdapter<DiscoveredReaderData>>,
   ) {
     match reader.take(100, ReadCondition::not_read()) {
+      // with SEDP disabled remote endpoints come only from static configuration
+      Ok(_) if !self.config.sedp_enabled => (),
       Ok(d) => {
         let mut db = self.discovery_db_write();
+        let mut updated = false;
+        let mut needs_new_cache_change = false;
         for data in d.into_iter() {
           match data.value() {
             Ok(val) => {
-              db.update_subscription(&val);
-              self.send_discovery_notification(DiscoveryNotificationType::WritersInfoUpdated {
-                needs_new_cache_change: true,
-              });
+              if !db.update_subscription(&val) {
+                continue;
+              }
+              updated = true;
+              needs_new_cache_change = true;
               db.update_topic_data_drd(&val);
             }
             Err(guid) => {
               db.remove_topic_reader(*guid);
-              self.send_discovery_notification(DiscoveryNotificationType::WritersInfoUpdated {
-                needs_new_cache_change: false,
-              });
+              updated = true;
             }
           }
         }
+        // one notification for all the samples
+        if updated {
+          self.send_discovery_notification(DiscoveryNotificationType::WritersInfoUpdated {
+            needs_new_cache_change,
+          });
+        }
       }
       _ => (),
     };
@@ -946,53 +1006,72 @@ impl Discovery {
     reader: &mut DataReader<DiscoveredWriterData, PlCdrDeserializerAdapter<DiscoveredWriterData>>,
   ) {
     match reader.take(100, ReadCondition::not_read()) {
+      // with SEDP disabled remote endpoints come only from static configuration
+      Ok(_) if !self.config.sedp_enabled => (),
       Ok(d) => {
         let mut db = self.discovery_db_write();
+        let mut updated = false;
         for data in d.into_iter() {
           match data.value() {
             Ok(val) => {
-              db.update_publication(&val);
-              self.send_discovery_notification(DiscoveryNotificationType::ReadersInfoUpdated);
+              if !db.update_publication(&val) {
+                continue;
+              }
+              updated = true;
               db.update_topic_data_dwd(&val);
             }
             Err(guid) => {
               db.remove_topic_writer(*guid);
-              self.send_discovery_notification(DiscoveryNotificationType::ReadersInfoUpdated);
+              updated = true;
             }
           }
         }
+        // one notification for all the samples
+        if updated {
+          self.send_discovery_notification(DiscoveryNotificationType::ReadersInfoUpdated);
+        }
       }
       _ => (),
     };
   }
 
+  // Logs local and remote endpoints that cannot match because of incompatible QoS,
+  // and lets the local DataReaders and DataWriters know.
+  pub fn report_qos_mismatches(&self) {
+    let mismatches = self.discovery_db_write().new_qos_mismatches();
+    for (topic_name, local, remote, policy) in mismatches {
+      warn!(
+        "Topic {:?}: local endpoint {:?} does not match remote {:?}, incompatible {:?} QoS.",
+        topic_name, local, remote, policy
+      );
+      self.send_discovery_notification(DiscoveryNotificationType::QosMismatch {
+        local,
+        remote,
+        policy,
+      });
+    }
+  }
+
   pub fn handle_topic_reader(
     &self,
     reader: &mut DataReader<DiscoveredTopicData, PlCdrDeserializerAdapter<DiscoveredTopicData>>,
   ) {
-    let topic_data_vec: Option<Vec<DiscoveredTopicData>> =
-      match reader.take(100, ReadCondition::any()) {
-        Ok(d) => Some(
-          d.into_iter()
-            .map(|p| p.value().clone())
-            .filter_map(Result::ok)
-            .collect(),
-        ),
-        _ => None,
-      };
-
-    let topic_data_vec = match topic_data_vec {
-      Some(d) => d,
-      None => return,
+    let topic_data_vec = match reader.take(100, ReadCondition::any()) {
+      Ok(d) => d,
+      _ => return,
     };
 
     let mut db = self.discovery_db_write();
-    topic_data_vec.iter().for_each(|data| {
-      let updated = db.update_topic_data(data);
+    for data in topic_data_vec.into_iter() {
+      let updated = match data.into_value() {
+        Ok(data) => db.update_topic_data(&data),
+        // the topic is no longer used by the participant that announced it
+        Err(key) => db.remove_topic_with_key(key),
+      };
       if updated {
         self.send_discovery_notification(DiscoveryNotificationType::TopicsInfoUpdated);
       }
-    });
+    }
   }
 
   pub fn handle_participant_message_reader(
@@ -1001,11 +1080,12 @@ impl Discovery {
   ) {
     let participant_messages: Option<Vec<ParticipantMessageData>> =
       match reader.take(100, ReadCondition::any()) {
+        // Samples that failed to deserialize never get here, the DataReader counts
+        // them. Samples without data dispose messages, which assert nothing.
         Ok(msgs) => Some(
           msgs
             .into_iter()
-            .map(|p| p.value().clone())
-            .filter_map(Result::ok)
+            .filter_map(|p| p.into_value().ok())
             .collect(),
         ),
         _ => None,
@@ -1016,9 +1096,22 @@ impl Discovery {
       None => return,
     };
 
-    let mut db = self.discovery_db_write();
-    for msg in msgs.into_iter() {
-      db.update_lease_duration(msg);
+    let mut liveliness_assertions = Vec::with_capacity(msgs.len());
+    {
+      let mut db = self.discovery_db_write();
+      for msg in msgs.into_iter() {
+        let manual = msg.kind
+          == ParticipantMessageDataKind::PARTICIPANT_MESSAGE_DATA_KIND_MANUAL_LIVELINESS_UPDATE;
+        liveliness_assertions.push((msg.guid, manual));
+        db.update_lease_duration(msg);
+      }
+    }
+    // Readers track the liveliness of each writer, see Reader::participant_liveliness_asserted
+    for (guid_prefix, manual) in liveliness_assertions {
+      self.send_discovery_notification(DiscoveryNotificationType::ParticipantLivelinessAsserted {
+        guid_prefix,
+        manual,
+      });
     }
   }
 
@@ -1058,7 +1151,7 @@ impl Discovery {
         Liveliness::ManualByTopic { lease_duration: _ } => false,
       });
 
-    let inow = Timestamp::now();
+    let inow = self.clock.now();
 
     // Automatic
     {
@@ -1093,6 +1186,13 @@ impl Discovery {
                 return;
               }
             }
+            // our own readers do not receive the message
+            self.send_discovery_notification(
+              DiscoveryNotificationType::ParticipantLivelinessAsserted {
+                guid_prefix: self.domain_participant.get_guid_prefix(),
+                manual: false,
+              },
+            );
             liveliness_state.last_auto_update = inow;
           }
         }
@@ -1129,6 +1229,12 @@ impl Discovery {
                 return;
               }
             }
+            self.send_discovery_notification(
+              DiscoveryNotificationType::ParticipantLivelinessAsserted {
+                guid_prefix: self.domain_participant.get_guid_prefix(),
+                manual: true,
+              },
+            );
           }
         }
         None => (),
@@ -1136,23 +1242,60 @@ impl Discovery {
     }
   }
 
+  // Removes remote participants whose lease has expired, and unmatches their readers
+  // and writers from ours.
   pub fn participant_cleanup(&self) {
-    self.discovery_db_write().participant_cleanup();
+    let removed = self.discovery_db_write().participant_cleanup();
+    if !removed.is_empty() {
+      self.send_discovery_notification(DiscoveryNotificationType::WritersInfoUpdated {
+        needs_new_cache_change: false,
+      });
+      self.send_discovery_notification(DiscoveryNotificationType::ReadersInfoUpdated);
+    }
+  }
+
+  // Time until the next participant cleanup: the cleanup period, or less if the lease
+  // of a remote participant expires before that.
+  fn participant_cleanup_delay(&self) -> StdDuration {
+    let period = self.config.participant_cleanup_period;
+    match self.discovery_db_read().next_participant_lease_expiry() {
+      Some(expiry) => std::cmp::min(
+        period,
+        expiry.saturating_duration_since(self.clock.instant()),
+      ),
+      None => period,
+    }
   }
 
   pub fn topic_cleanup(&self) {
     self.discovery_db_write().topic_cleanup();
   }
 
-  pub fn update_spdp_participant_writer(&self, data: SPDPDiscoveredParticipantData) -> bool {
-    if !self.discovery_db_write().update_participant(&data) {
-      return false;
+  // Announces our own participant (SPDP). Returns false if the DomainParticipant is
+  // already gone.
+  pub fn write_participant_info(
+    &self,
+    writer: &mut DataWriter<
+      SPDPDiscoveredParticipantData,
+      PlCdrSerializerAdapter<SPDPDiscoveredParticipantData, LittleEndian>,
+    >,
+  ) -> bool {
+    let strong_dp = match self.domain_participant.clone().upgrade() {
+      Some(dp) => dp,
+      None => return false,
+    };
+    if !self.announces_participant() {
+      return true;
+    }
+    let mut data = SPDPDiscoveredParticipantData::from_participant(
+      &strong_dp,
+      Duration::from(self.config.participant_lease_duration),
+    );
+    if !self.config.domain_tag.is_empty() {
+      data.domain_tag = Some(self.config.domain_tag.clone());
     }
 
-    self.send_discovery_notification(DiscoveryNotificationType::WritersInfoUpdated {
-      needs_new_cache_change: true,
-    });
-
+    writer.write(data, None).unwrap_or(());
     true
   }
 
@@ -1180,9 +1323,12 @@ impl Discovery {
     &self,
     writer: &mut DataWriter<
       DiscoveredReaderData,
-      CDRSerializerAdapter<DiscoveredReaderData, LittleEndian>,
+      PlCdrSerializerAdapter<DiscoveredReaderData, LittleEndian>,
     >,
   ) {
+    if !self.announces_endpoints() {
+      return;
+    }
     let db = self.discovery_db_read();
     let datas = db.get_all_local_topic_readers();
     for data in datas
@@ -1211,9 +1357,12 @@ impl Discovery {
     &self,
     writer: &mut DataWriter<
       DiscoveredWriterData,
-      CDRSerializerAdapter<DiscoveredWriterData, LittleEndian>,
+      PlCdrSerializerAdapter<DiscoveredWriterData, LittleEndian>,
     >,
   ) {
+    if !self.announces_endpoints() {
+      return;
+    }
     let db = self.discovery_db_read();
     let datas = db.get_all_local_topic_writers();
     for data in datas.filter(|p| {
@@ -1240,11 +1389,14 @@ impl Discovery {
     &self,
     writer: &mut DataWriter<
       DiscoveredTopicData,
-      CDRSerializerAdapter<DiscoveredTopicData, LittleEndian>,
+      PlCdrSerializerAdapter<DiscoveredTopicData, LittleEndian>,
     >,
   ) {
+    if !self.announces_endpoints() {
+      return;
+    }
     let db = self.discovery_db_read();
-    let datas = db.get_all_topics();
+    let datas = db.get_local_topics();
     for data in datas {
       match writer.write(data.clone(), None) {
         Ok(_) => (),
@@ -1282,18 +1434,22 @@ impl Discovery {
       .build()
   }
 
+  // We send participant announcements (SPDP).
+  fn announces_participant(&self) -> bool {
+    self.config.spdp_enabled && !self.config.passive
+  }
+
+  // We announce our readers, writers and topics (SEDP).
+  fn announces_endpoints(&self) -> bool {
+    self.config.sedp_enabled && self.config.sedp_announce && !self.config.passive
+  }
+
   fn discovery_db_read(&self) -> RwLockReadGuard<DiscoveryDB> {
-    match self.discovery_db.read() {
-      Ok(db) => db,
-      Err(e) => panic!("DiscoveryDB is poisoned {:?}.", e),
-    }
+    read_lock(&self.discovery_db)
   }
 
   fn discovery_db_write(&self) -> RwLockWriteGuard<DiscoveryDB> {
-    match self.discovery_db.write() {
-      Ok(db) => db,
-      Err(e) => panic!("DiscoveryDB is poisoned {:?}.", e),
-    }
+    write_lock(&self.discovery_db)
   }
 
   fn send_discovery_notification(&self, dntype: DiscoveryNotificationType) {
@@ -1372,7 +1528,7 @@ mod tests {
 
   #[test]
   fn discovery_reader_data_test() {
-    let participant = DomainParticipant::new(0);
+    let participant = DomainParticipant::new(0).unwrap();
 
     let topic = participant
       .create_topic(
@@ -1437,7 +1593,7 @@ mod tests {
             drd.reader_proxy.multicast_locator_list.clear();
 
             data = to_bytes::<DiscoveredReaderData, byteorder::LittleEndian>(&drd).unwrap();
-            d.serialized_payload.as_mut().unwrap().value = data.clone();
+            d.serialized_payload.as_mut().unwrap().value = data.clone().into();
           }
           _ => continue,
         },
@@ -1461,7 +1617,7 @@ mod tests {
 
   #[test]
   fn discovery_writer_data_test() {
-    let participant = DomainParticipant::new(0);
+    let participant = DomainParticipant::new(0).unwrap();
 
     let topic = participant
       .create_topic(
@@ -1540,7 +1696,7 @@ mod tests {
 
   #[test]
   fn discovery_topic_data_test() {
-    let _participant = DomainParticipant::new(0);
+    let _participant = DomainParticipant::new(0).unwrap();
 
     let topic_data = DiscoveredTopicData::new(TopicBuiltinTopicData {
       key: None,
@@ -1577,4 +1733,98 @@ mod tests {
 
     udp_sender.send_to_all(&rr, &addresses);
   }
+
+  #[test]
+  fn discovery_notifies_changed_publications_only() {
+    use crate::discovery::static_endpoint::StaticEndpoint;
+    use std::thread;
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .history(History::KeepAll)
+      .build();
+    let dp = DomainParticipant::new(57).unwrap();
+    // stands in for the SEDP publications of a remote participant
+    let topic = dp
+      .create_topic(
+        "FakePublications",
+        "DiscoveredWriterData",
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let subscriber = dp.create_subscriber(&qos).unwrap();
+    let mut reader = subscriber
+      .create_datareader::<DiscoveredWriterData, PlCdrDeserializerAdapter<DiscoveredWriterData>>(
+        &topic, None, None,
+      )
+      .unwrap();
+    let publisher = dp.create_publisher(&qos).unwrap();
+    let writer = publisher
+      .create_datawriter::<DiscoveredWriterData, PlCdrSerializerAdapter<DiscoveredWriterData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .unwrap();
+    for _ in 0..100 {
+      if writer
+        .get_publication_matched_status()
+        .unwrap()
+        .current_count()
+        == 1
+      {
+        break;
+      }
+      thread::sleep(StdDuration::from_millis(100));
+    }
+
+    let (notification_sender, notification_receiver) = mio_channel::sync_channel(100);
+    let (_command_sender, command_receiver) = mio_channel::sync_channel(10);
+    let (started_sender, _started_receiver) = std::sync::mpsc::channel();
+    let discovery = Discovery::new(
+      dp.weak_clone(),
+      Arc::new(RwLock::new(DiscoveryDB::new())),
+      started_sender,
+      notification_sender,
+      command_receiver,
+      DiscoveryConfig::default(),
+    )
+    .unwrap();
+
+    let poll = Poll::new().unwrap();
+    poll
+      .register(&reader, Token(0), Ready::readable(), PollOpt::edge())
+      .unwrap();
+    let mut events = Events::with_capacity(4);
+    let remote_writer = StaticEndpoint {
+      guid: GUID::new_with_prefix_and_id(
+        GuidPrefix::new(vec![7; 12]),
+        EntityId::createCustomEntityID([1, 2, 3], 0x02),
+      ),
+      topic_name: String::from("Square"),
+      type_name: String::from("ShapeType"),
+      qos: QosPolicies::qos_none(),
+      unicast_locators: vec!["10.0.0.1:7411".parse().unwrap()],
+      multicast_locators: vec![],
+    };
+    // The remote participant announces the same writer again and again.
+    for _ in 0..10 {
+      writer.write(remote_writer.to_writer_data(), None).unwrap();
+      poll
+        .poll(&mut events, Some(StdDuration::from_secs(5)))
+        .unwrap();
+      assert!(!events.is_empty(), "announcement was not received");
+      discovery.handle_publication_reader(&mut reader);
+    }
+
+    let mut notifications = 0;
+    while let Ok(notification) = notification_receiver.try_recv() {
+      assert!(matches!(
+        notification,
+        DiscoveryNotificationType::ReadersInfoUpdated
+      ));
+      notifications += 1;
+    }
+    assert_eq!(notifications, 1);
+  }
 }
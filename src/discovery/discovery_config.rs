@@ -0,0 +1,593 @@
+use std::{
+  net::{IpAddr, SocketAddr, SocketAddrV4, ToSocketAddrs},
+  time::Duration,
+};
+
+use crate::{
+  dds::values::result::{Error, Result},
+  network::constant::get_spdp_well_known_unicast_port,
+  structure::locator::Locator,
+};
+
+// Default and limits of DiscoveryConfig::max_message_size. The upper limit is the
+// largest payload of an IPv4 UDP datagram.
+pub(crate) const DEFAULT_MAX_MESSAGE_SIZE: usize = 64000;
+const MIN_MAX_MESSAGE_SIZE: usize = 1400;
+const MAX_UDP_PAYLOAD: usize = 65507;
+
+/// Timing and peers of the built-in discovery of a
+/// [DomainParticipant](struct.DomainParticipant.html).
+///
+/// All periods must be non-zero. The default values are used by
+/// `DomainParticipant::new`.
+///
+/// Participant announcements (SPDP) are sent to the well-known multicast
+/// address unless `spdp_multicast` is false, and always unicast to every
+/// address in `initial_peers`. A peer is either `host:port` or just `host`,
+/// in which case the well-known SPDP unicast ports of the first
+/// `initial_peer_participant_ids` participants in the domain are used. The
+/// multicast group is listened to in any case, so without multicast and initial
+/// peers the participant discovers others but is discovered only by the peers of
+/// others.
+///
+/// `sedp_announce` and `passive` make a participant quieter still, e.g. for
+/// monitoring tools that should not disturb the system they observe.
+///
+/// Our sockets and announced locators use the IP versions in `ip_families`. Listing
+/// both makes the participant dual-stack. On a host with several network interfaces,
+/// `interfaces` restricts DDS traffic to some of them. `socket` tunes every socket the
+/// participant opens. `tcp` adds a TCP transport.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::{DiscoveryConfig, DomainParticipant};
+/// # use std::time::Duration;
+/// let config = DiscoveryConfig {
+///   send_participant_info_period: Duration::from_millis(500),
+///   participant_lease_duration: Duration::from_secs(2),
+///   ..DiscoveryConfig::default()
+/// };
+/// let domain_participant = DomainParticipant::with_discovery_config(0, config).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveryConfig {
+  /// How often expired remote participants are removed.
+  pub participant_cleanup_period: Duration,
+  /// How often inactive topics are removed.
+  pub topic_cleanup_period: Duration,
+  /// How often our own participant data (SPDP) is announced. Announcements are sent
+  /// more often if needed to fit three of them within `participant_lease_duration`.
+  pub send_participant_info_period: Duration,
+  /// How often our local readers are announced (SEDP).
+  pub send_readers_info_period: Duration,
+  /// How often our local writers are announced (SEDP).
+  pub send_writers_info_period: Duration,
+  /// How often our local topics are announced.
+  pub send_topic_info_period: Duration,
+  /// How often participant liveliness messages are checked and sent.
+  pub check_participant_messages_period: Duration,
+  /// Lease duration announced to remote participants. They consider us gone if they do
+  /// not hear from us within this time. Defaults to three times the default
+  /// `send_participant_info_period`.
+  pub participant_lease_duration: Duration,
+  /// Send participant announcements to the SPDP multicast group. The group is
+  /// listened to also when this is false.
+  pub spdp_multicast: bool,
+  /// Hosts that receive our participant announcements by unicast.
+  pub initial_peers: Vec<String>,
+  /// How many participant ids are tried on an initial peer given without a port.
+  pub initial_peer_participant_ids: u16,
+  /// Domain tag (RTPS 2.4) announced with our participant data. Only participants
+  /// with the same tag are discovered. The default empty tag is not sent, and matches
+  /// participants that do not send a tag at all.
+  pub domain_tag: String,
+  /// Announce our participant and accept announcements of others (SPDP). When
+  /// disabled, remote participants are not discovered and do not discover us.
+  pub spdp_enabled: bool,
+  /// Announce our readers and writers and accept remote ones (SEDP). When
+  /// disabled, remote endpoints are only known if added as
+  /// [StaticEndpoint](struct.StaticEndpoint.html)s.
+  pub sedp_enabled: bool,
+  /// Announce our readers and writers (SEDP). When disabled, remote endpoints are
+  /// still discovered, but no remote participant matches our readers and writers.
+  pub sedp_announce: bool,
+  /// Send nothing: no participant announcements, no endpoint announcements, no
+  /// participant liveliness messages, no AckNacks and nothing our DataWriters write.
+  /// The participant only parses the discovery data and user data it receives.
+  ///
+  /// Remote participants do not know about us, so we receive only what they send
+  /// by multicast: participant announcements, and endpoint announcements and
+  /// samples that are multicast to other participants. Reliable reading does not
+  /// work, as our readers send no AckNacks: they cannot ask for a sample they
+  /// missed, and hold back the samples after it. Use best effort readers with a
+  /// passive participant.
+  pub passive: bool,
+  /// IP versions to use, preferred first. Must not be empty or repeat a family. When a
+  /// remote participant announces locators of several of these, only those of the most
+  /// preferred family are used.
+  pub ip_families: Vec<IpFamily>,
+  /// Network interfaces to use, each given by name (`eth0`) or as an address range
+  /// (`10.1.0.0/16`). Empty uses all interfaces. Otherwise only one address of these
+  /// per IP family is announced, unicast sockets are bound to it if there is just one,
+  /// and multicast is joined and sent on its interface. Participant creation fails if
+  /// an interface is not found.
+  pub interfaces: Vec<String>,
+  /// Buffer sizes and multicast options of our sockets.
+  pub socket: SocketConfig,
+  /// Hand samples of our DataWriters to our matching DataReaders in memory. When
+  /// disabled, they go through the network like samples to other participants, which
+  /// can help debugging.
+  pub intra_process_delivery: bool,
+  /// Size in bytes of the shared memory segment of each of our DataWriters. With the
+  /// `shm` feature on Linux, participants that see the same `/dev/shm` send user data
+  /// to each other through these segments instead of UDP. Discovery still uses UDP.
+  /// Zero disables the transport. Messages larger than the segment are not sent.
+  pub shm_segment_size: usize,
+  /// Largest RTPS message, in bytes, that our DataWriters send over UDP. Batches are
+  /// split to fit, and writing a sample that does not fit even alone fails with
+  /// `Error::MessageTooLarge` when some matched DataReader is reached over UDP. The
+  /// default fits in the 65507 byte UDP payload limit. Lower it, down to 1400, to
+  /// avoid IP fragmentation on a network with a standard 1500 byte MTU.
+  pub max_message_size: usize,
+  /// Send RTPS messages also over TCP. None uses UDP only.
+  pub tcp: Option<TcpConfig>,
+  /// How often the multicast groups we listen to are joined again, if the operating
+  /// system has dropped our membership, e.g. when a network interface went down and
+  /// up. Multicast sockets that could not be bound are bound then too. Groups still
+  /// joined are not disturbed. With the `netlink` feature on Linux, this is done also
+  /// as soon as an interface changes.
+  pub multicast_rejoin_period: Duration,
+}
+
+/// Options of the UDP sockets of a [DomainParticipant](struct.DomainParticipant.html),
+/// both those of discovery and those of user traffic.
+///
+/// The defaults leave the operating system defaults in place. The kernel may grant a
+/// smaller buffer than requested, e.g. Linux clamps it to `net.core.rmem_max` and
+/// `net.core.wmem_max`. The granted size is logged.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::{DiscoveryConfig, SocketConfig};
+/// // large bursts of fragmented samples, SPDP across one router
+/// let config = DiscoveryConfig {
+///   socket: SocketConfig {
+///     receive_buffer_size: 4 * 1024 * 1024,
+///     multicast_ttl: 2,
+///     ..SocketConfig::default()
+///   },
+///   ..DiscoveryConfig::default()
+/// };
+/// assert!(config.validate().is_ok());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketConfig {
+  /// Receive buffer (`SO_RCVBUF`) of our listening sockets, in bytes. Zero keeps the
+  /// OS default.
+  pub receive_buffer_size: usize,
+  /// Send buffer (`SO_SNDBUF`) of our sending sockets, in bytes. Zero keeps the OS
+  /// default.
+  pub send_buffer_size: usize,
+  /// Time to live, or IPv6 hop limit, of the multicast we send. 1 keeps it in the
+  /// local network, each router on the way takes one more. At most 255.
+  pub multicast_ttl: u32,
+  /// Deliver the multicast we send also to participants on this host.
+  pub multicast_loopback: bool,
+}
+
+impl Default for SocketConfig {
+  fn default() -> SocketConfig {
+    SocketConfig {
+      receive_buffer_size: 0,
+      send_buffer_size: 0,
+      multicast_ttl: 1,
+      multicast_loopback: true,
+    }
+  }
+}
+
+/// TCP transport of a [DomainParticipant](struct.DomainParticipant.html), for networks
+/// where UDP does not get through, e.g. across NAT to a server in the cloud.
+///
+/// The participant connects to `peers`, and accepts connections on `listen_port` if
+/// set. One connection to each remote participant carries all traffic between the two,
+/// in both directions, so a participant behind NAT only needs `peers`. Connections to
+/// `peers` are re-established when lost, waiting longer after each failed attempt. Each
+/// RTPS message goes as its length, a big-endian 4-byte integer, followed by the
+/// message. Only RustDDS participants speak this protocol.
+///
+/// The participant announces TCP locators in discovery, and uses TCP with every remote
+/// participant that announces them. Other participants are reached by UDP, unless
+/// `udp` is false.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::{DiscoveryConfig, TcpConfig};
+/// // behind NAT, talking to a server only
+/// let config = DiscoveryConfig {
+///   tcp: Some(TcpConfig {
+///     peers: vec![String::from("dds.example.com:7500")],
+///     udp: false,
+///     ..TcpConfig::default()
+///   }),
+///   ..DiscoveryConfig::default()
+/// };
+/// assert!(config.validate().is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpConfig {
+  /// Port to accept connections on. Zero picks a free port. None accepts no
+  /// connections.
+  pub listen_port: Option<u16>,
+  /// Address announced for `listen_port`, when connections come through a port
+  /// forwarding. By default the local IPv4 addresses are announced.
+  pub public_address: Option<SocketAddrV4>,
+  /// Participants to connect to, each `host:port`.
+  pub peers: Vec<String>,
+  /// Use UDP as well. When false, no UDP locators are announced, SPDP multicast and
+  /// `initial_peers` are not used, and only participants announcing TCP locators are
+  /// reached.
+  pub udp: bool,
+}
+
+impl Default for TcpConfig {
+  fn default() -> TcpConfig {
+    TcpConfig {
+      listen_port: None,
+      public_address: None,
+      peers: Vec::new(),
+      udp: true,
+    }
+  }
+}
+
+impl TcpConfig {
+  // Resolves `peers`. IPv4 addresses are preferred.
+  pub(crate) fn peer_addresses(&self) -> Result<Vec<SocketAddr>> {
+    let mut addresses = Vec::new();
+    for peer in self.peers.iter() {
+      let resolved: Vec<SocketAddr> = match peer.to_socket_addrs() {
+        Ok(addrs) => addrs.collect(),
+        Err(e) => {
+          return Err(Error::bad_parameter(format!(
+            "cannot resolve TCP peer {:?}: {}",
+            peer, e
+          )))
+        }
+      };
+      match resolved
+        .iter()
+        .find(|a| a.is_ipv4())
+        .or_else(|| resolved.first())
+      {
+        Some(a) if a.port() != 0 && !a.ip().is_unspecified() => addresses.push(*a),
+        _ => {
+          return Err(Error::bad_parameter(format!(
+            "TCP peer {:?} is not a usable address",
+            peer
+          )))
+        }
+      }
+    }
+    Ok(addresses)
+  }
+}
+
+/// IP version of the sockets and locators of a
+/// [DomainParticipant](struct.DomainParticipant.html).
+///
+/// With `V6` the SPDP multicast groups are `ff02::ffff:239.255.0.1` and
+/// `ff03::ffff:239.255.0.1`, as used by other DDS implementations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpFamily {
+  V4,
+  V6,
+}
+
+impl IpFamily {
+  pub fn of(address: &IpAddr) -> IpFamily {
+    match address {
+      IpAddr::V4(_) => IpFamily::V4,
+      IpAddr::V6(_) => IpFamily::V6,
+    }
+  }
+}
+
+impl DiscoveryConfig {
+  /// Checks that none of the periods is zero, that `ip_families` is not empty and has
+  /// no duplicates, that the multicast TTL is at most 255, and that `max_message_size`
+  /// is between 1400 and 65507.
+  pub fn validate(&self) -> Result<()> {
+    let values = [
+      (
+        "participant_cleanup_period",
+        self.participant_cleanup_period,
+      ),
+      ("topic_cleanup_period", self.topic_cleanup_period),
+      (
+        "send_participant_info_period",
+        self.send_participant_info_period,
+      ),
+      ("send_readers_info_period", self.send_readers_info_period),
+      ("send_writers_info_period", self.send_writers_info_period),
+      ("send_topic_info_period", self.send_topic_info_period),
+      (
+        "check_participant_messages_period",
+        self.check_participant_messages_period,
+      ),
+      (
+        "participant_lease_duration",
+        self.participant_lease_duration,
+      ),
+      ("multicast_rejoin_period", self.multicast_rejoin_period),
+    ];
+    if let Some((name, _)) = values.iter().find(|(_, d)| *d == Duration::from_secs(0)) {
+      return Err(Error::bad_parameter(format!(
+        "DiscoveryConfig {} is zero",
+        name
+      )));
+    }
+    let families = &self.ip_families;
+    if families.is_empty() || (1..families.len()).any(|i| families[..i].contains(&families[i])) {
+      return Err(Error::bad_parameter(format!(
+        "DiscoveryConfig ip_families {:?} is empty or has duplicates",
+        families
+      )));
+    }
+    if self.socket.multicast_ttl > 255 {
+      return Err(Error::bad_parameter(format!(
+        "multicast TTL {} is larger than 255",
+        self.socket.multicast_ttl
+      )));
+    }
+    if !(MIN_MAX_MESSAGE_SIZE..=MAX_UDP_PAYLOAD).contains(&self.max_message_size) {
+      return Err(Error::bad_parameter(format!(
+        "max_message_size {} is not between {} and {}",
+        self.max_message_size, MIN_MAX_MESSAGE_SIZE, MAX_UDP_PAYLOAD
+      )));
+    }
+    Ok(())
+  }
+
+  // Period of our SPDP announcements. At least three announcements fit within our
+  // lease, so that the lease doesn't break if one or two of them are lost.
+  pub(crate) fn participant_announce_period(&self) -> Duration {
+    std::cmp::min(
+      self.send_participant_info_period,
+      self.participant_lease_duration / 3,
+    )
+  }
+
+  // Resolves initial_peers to SPDP unicast locators of the given domain. Host names
+  // resolve to an address of the most preferred of our IP families.
+  pub(crate) fn initial_peer_locators(&self, domain_id: u16) -> Result<Vec<Locator>> {
+    let mut locators = Vec::new();
+    for peer in self.initial_peers.iter() {
+      let addresses: Vec<SocketAddr> = if let Ok(sa) = peer.parse::<SocketAddr>() {
+        vec![sa]
+      } else {
+        let (host, port) = match peer.parse::<IpAddr>() {
+          // bare IPv6 addresses also contain ':'
+          Ok(_) => (peer.as_str(), None),
+          Err(_) => match peer.rfind(':') {
+            Some(i) => match peer[i + 1..].parse::<u16>() {
+              Ok(port) => (&peer[..i], Some(port)),
+              Err(_) => {
+                return Err(Error::bad_parameter(format!(
+                  "invalid port in initial peer {:?}",
+                  peer
+                )))
+              }
+            },
+            None => (peer.as_str(), None),
+          },
+        };
+        let ip = match (host, 0).to_socket_addrs() {
+          Ok(addrs) => {
+            let addrs: Vec<SocketAddr> = addrs.collect();
+            let preferred = self
+              .ip_families
+              .iter()
+              .find_map(|f| addrs.iter().find(|a| IpFamily::of(&a.ip()) == *f));
+            match preferred {
+              Some(a) => a.ip(),
+              None => {
+                return Err(Error::bad_parameter(format!(
+                  "initial peer {:?} has no address of {:?}",
+                  peer, self.ip_families
+                )))
+              }
+            }
+          }
+          Err(e) => {
+            return Err(Error::bad_parameter(format!(
+              "cannot resolve initial peer {:?}: {}",
+              peer, e
+            )))
+          }
+        };
+        match port {
+          Some(port) => vec![SocketAddr::new(ip, port)],
+          None => (0..self.initial_peer_participant_ids)
+            .map(|pid| SocketAddr::new(ip, get_spdp_well_known_unicast_port(domain_id, pid)))
+            .collect(),
+        }
+      };
+      for a in addresses {
+        if !self.ip_families.contains(&IpFamily::of(&a.ip())) || a.ip().is_unspecified() {
+          return Err(Error::bad_parameter(format!(
+            "initial peer {:?} is not a usable address",
+            peer
+          )));
+        }
+        locators.push(Locator::from(a));
+      }
+    }
+    Ok(locators)
+  }
+}
+
+impl Default for DiscoveryConfig {
+  fn default() -> DiscoveryConfig {
+    let send_participant_info_period = Duration::from_secs(2);
+    DiscoveryConfig {
+      participant_cleanup_period: Duration::from_secs(2),
+      topic_cleanup_period: Duration::from_secs(10),
+      send_participant_info_period,
+      send_readers_info_period: Duration::from_secs(2),
+      send_writers_info_period: Duration::from_secs(2),
+      send_topic_info_period: Duration::from_secs(20),
+      check_participant_messages_period: Duration::from_secs(1),
+      // 3 times the send period so lease doesn't break if we fail once for some reason
+      participant_lease_duration: send_participant_info_period * 3,
+      spdp_multicast: true,
+      initial_peers: Vec::new(),
+      initial_peer_participant_ids: 4,
+      domain_tag: String::new(),
+      spdp_enabled: true,
+      sedp_enabled: true,
+      sedp_announce: true,
+      passive: false,
+      ip_families: vec![IpFamily::V4],
+      interfaces: Vec::new(),
+      socket: SocketConfig::default(),
+      intra_process_delivery: true,
+      shm_segment_size: 16 * 1024 * 1024,
+      max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+      tcp: None,
+      multicast_rejoin_period: Duration::from_secs(10),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn discovery_config_validate() {
+    let config = DiscoveryConfig::default();
+    assert!(config.validate().is_ok());
+    assert_eq!(config.participant_lease_duration, Duration::from_secs(6));
+
+    let config = DiscoveryConfig {
+      send_readers_info_period: Duration::from_secs(0),
+      ..DiscoveryConfig::default()
+    };
+    assert!(matches!(config.validate(), Err(Error::BadParameter { .. })));
+
+    let config = DiscoveryConfig {
+      participant_lease_duration: Duration::from_secs(0),
+      ..DiscoveryConfig::default()
+    };
+    assert!(matches!(config.validate(), Err(Error::BadParameter { .. })));
+
+    let config = DiscoveryConfig {
+      ip_families: vec![IpFamily::V6, IpFamily::V4],
+      ..DiscoveryConfig::default()
+    };
+    assert!(config.validate().is_ok());
+    for ip_families in vec![vec![], vec![IpFamily::V6, IpFamily::V6]] {
+      let config = DiscoveryConfig {
+        ip_families,
+        ..DiscoveryConfig::default()
+      };
+      assert!(matches!(config.validate(), Err(Error::BadParameter { .. })));
+    }
+
+    let config = DiscoveryConfig {
+      socket: SocketConfig {
+        multicast_ttl: 256,
+        ..SocketConfig::default()
+      },
+      ..DiscoveryConfig::default()
+    };
+    assert!(matches!(config.validate(), Err(Error::BadParameter { .. })));
+
+    for (max_message_size, valid) in &[(1399, false), (1400, true), (65507, true), (65508, false)] {
+      let config = DiscoveryConfig {
+        max_message_size: *max_message_size,
+        ..DiscoveryConfig::default()
+      };
+      assert_eq!(config.validate().is_ok(), *valid);
+    }
+  }
+
+  #[test]
+  fn discovery_config_announce_period() {
+    let config = DiscoveryConfig::default();
+    assert_eq!(config.participant_announce_period(), Duration::from_secs(2));
+
+    let config = DiscoveryConfig {
+      participant_lease_duration: Duration::from_millis(1500),
+      ..DiscoveryConfig::default()
+    };
+    assert_eq!(
+      config.participant_announce_period(),
+      Duration::from_millis(500)
+    );
+  }
+
+  #[test]
+  fn discovery_config_initial_peers() {
+    let config = DiscoveryConfig {
+      initial_peers: vec![String::from("10.0.0.5:7777"), String::from("127.0.0.1")],
+      initial_peer_participant_ids: 2,
+      ..DiscoveryConfig::default()
+    };
+    let locators: Vec<SocketAddr> = config
+      .initial_peer_locators(1)
+      .unwrap()
+      .into_iter()
+      .map(SocketAddr::from)
+      .collect();
+    assert_eq!(
+      locators,
+      vec![
+        "10.0.0.5:7777".parse().unwrap(),
+        "127.0.0.1:7660".parse().unwrap(),
+        "127.0.0.1:7662".parse().unwrap(),
+      ]
+    );
+
+    let config = DiscoveryConfig {
+      initial_peers: vec![String::from("127.0.0.1:notaport")],
+      ..DiscoveryConfig::default()
+    };
+    assert!(matches!(
+      config.initial_peer_locators(0),
+      Err(Error::BadParameter { .. })
+    ));
+  }
+
+  #[test]
+  fn discovery_config_initial_peers_ipv6() {
+    let peers = vec![String::from("[::1]:7777"), String::from("::1")];
+    let config = DiscoveryConfig {
+      initial_peers: peers.clone(),
+      initial_peer_participant_ids: 1,
+      ..DiscoveryConfig::default()
+    };
+    assert!(matches!(
+      config.initial_peer_locators(0),
+      Err(Error::BadParameter { .. })
+    ));
+
+    let config = DiscoveryConfig {
+      initial_peers: peers,
+      initial_peer_participant_ids: 1,
+      ip_families: vec![IpFamily::V4, IpFamily::V6],
+      ..DiscoveryConfig::default()
+    };
+    let locators: Vec<SocketAddr> = config
+      .initial_peer_locators(0)
+      .unwrap()
+      .into_iter()
+      .map(SocketAddr::from)
+      .collect();
+    assert_eq!(
+      locators,
+      vec!["[::1]:7777".parse().unwrap(), "[::1]:7410".parse().unwrap()]
+    );
+  }
+}
@@ -1,19 +1,27 @@
 use std::{
-  collections::{hash_map::Iter as HashIter, HashMap},
+  collections::{hash_map::Iter as HashIter, BTreeMap, HashMap, HashSet},
   iter::Map,
   slice::Iter,
   time::Instant,
 };
 
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use itertools::Itertools;
-use log::warn;
+use log::{debug, warn};
 
 use crate::{
-  dds::qos::HasQoSPolicy, network::util::get_local_multicast_locators, structure::guid::EntityId,
+  dds::qos::{HasQoSPolicy, QosPolicyId},
+  network::util::{get_local_multicast_locators, same_host_shm_locators, select_locators},
+  structure::locator::LocatorKind,
+  structure::guid::EntityId,
   structure::guid::GuidPrefix,
 };
 
-use crate::structure::{guid::GUID, duration::Duration, entity::Entity};
+use crate::structure::{
+  clock::SharedClock, guid::GUID, duration::Duration, entity::Entity, locator::LocatorList,
+};
+use crate::structure::locator::Locator;
 
 use crate::{
   dds::{
@@ -22,8 +30,18 @@ use crate::{
   },
 };
 
+use crate::structure::topic_kind::TopicKind;
+
+// RTPS defines no entity kind for topics. The keys of our topic announcements have this
+// one, which no RTPS entity has.
+const TOPIC_ENTITY_KIND: u8 = 0x0A;
+
+use crate::common::lock::PoisonRecovery;
+
 use super::{
   discovery::Discovery,
+  discovery_snapshot::{DiscoverySnapshot, EndpointSnapshot, ParticipantSnapshot},
+  discovery_config::IpFamily,
   data_types::{
     spdp_participant_data::SPDPDiscoveredParticipantData,
     topic_data::{
@@ -35,6 +53,8 @@ use super::{
 
 pub(crate) struct DiscoveryDB {
   participant_proxies: HashMap<GUID, SPDPDiscoveredParticipantData>,
+  // when we last heard from each participant, for lease expiration
+  participant_last_life_signs: HashMap<GUID, Instant>,
   // local writer proxies for topics (topic name acts as key)
   local_topic_writers: HashMap<GUID, DiscoveredWriterData>,
   // local reader proxies for topics (topic name acts as key)
@@ -44,45 +64,271 @@ pub(crate) struct DiscoveryDB {
   external_topic_writers: Vec<DiscoveredWriterData>,
 
   topics: HashMap<String, DiscoveredTopicData>,
+  // Our topics, which Discovery announces, keyed apart from remote announcements of
+  // the same topic.
+  local_topics: HashMap<String, DiscoveredTopicData>,
+  local_topic_count: u32,
+
+  // Entities ignored by the application. Ignoring is permanent, so these are never
+  // removed.
+  ignored_participants: HashSet<GuidPrefix>,
+  ignored_publications: HashSet<GUID>,
+  ignored_subscriptions: HashSet<GUID>,
+
+  // Our domain tag, and participants that announced a different one. Neither their
+  // participant nor endpoint data is accepted.
+  domain_tag: String,
+  foreign_domain_participants: HashSet<GuidPrefix>,
+
+  // Our IP versions, preferred first. Remote locators of other versions are dropped.
+  ip_families: Vec<IpFamily>,
+  // Which /dev/shm we see, if we use shared memory. Remote endpoints announcing a shared
+  // memory locator of the same one are on this host.
+  shm_namespace: Option<u32>,
+  // Whether we use UDP and TCP. Remote participants that announce TCP locators are
+  // reached only by TCP, if we use it.
+  udp: bool,
+  tcp: bool,
+
+  // (local, remote) endpoint pairs with incompatible QoS that are already reported
+  qos_mismatches: HashSet<(GUID, GUID)>,
 
   readers_updated: bool,
   writers_updated: bool,
+
+  // the clock of the participant, for lease expiration
+  clock: SharedClock,
+
+  // times a thread panicked holding the lock of this
+  poison_recoveries: AtomicU64,
 }
 
 impl DiscoveryDB {
   pub fn new() -> DiscoveryDB {
+    DiscoveryDB::with_clock(SharedClock::default())
+  }
+
+  pub fn with_clock(clock: SharedClock) -> DiscoveryDB {
     DiscoveryDB {
       participant_proxies: HashMap::new(),
+      participant_last_life_signs: HashMap::new(),
       local_topic_writers: HashMap::new(),
       local_topic_readers: HashMap::new(),
       external_topic_readers: Vec::new(),
       external_topic_writers: Vec::new(),
       topics: HashMap::new(),
+      local_topics: HashMap::new(),
+      local_topic_count: 0,
+      ignored_participants: HashSet::new(),
+      ignored_publications: HashSet::new(),
+      ignored_subscriptions: HashSet::new(),
+      domain_tag: String::new(),
+      foreign_domain_participants: HashSet::new(),
+      ip_families: vec![IpFamily::V4],
+      shm_namespace: None,
+      udp: true,
+      tcp: false,
+      qos_mismatches: HashSet::new(),
       readers_updated: false,
       writers_updated: false,
+      clock,
+      poison_recoveries: AtomicU64::new(0),
     }
   }
 
+  pub fn clock(&self) -> SharedClock {
+    self.clock.clone()
+  }
+
+  pub fn poison_recoveries(&self) -> u64 {
+    self.poison_recoveries.load(Ordering::Relaxed)
+  }
+
+  pub fn set_domain_tag(&mut self, domain_tag: String) {
+    self.domain_tag = domain_tag;
+  }
+
+  pub fn set_ip_families(&mut self, ip_families: Vec<IpFamily>) {
+    self.ip_families = ip_families;
+  }
+
+  pub fn set_shm_namespace(&mut self, shm_namespace: Option<u32>) {
+    self.shm_namespace = shm_namespace;
+  }
+
+  pub fn set_transports(&mut self, udp: bool, tcp: bool) {
+    self.udp = udp;
+    self.tcp = tcp;
+  }
+
+  // Picks the (unicast, multicast) locators we use of those a remote entity announces.
+  fn select_transport(
+    &self,
+    unicast: &LocatorList,
+    multicast: &LocatorList,
+  ) -> (LocatorList, LocatorList) {
+    if self.tcp {
+      let tcp: LocatorList = unicast
+        .iter()
+        .filter(|l| l.kind == LocatorKind::LOCATOR_KIND_TCPv4)
+        .cloned()
+        .collect();
+      if !tcp.is_empty() {
+        return (tcp, LocatorList::new());
+      }
+    }
+    if self.udp {
+      (
+        select_locators(unicast, &self.ip_families),
+        select_locators(multicast, &self.ip_families),
+      )
+    } else {
+      (LocatorList::new(), LocatorList::new())
+    }
+  }
+
+  // Returns true if the participant is new or announced something else than before.
+  // Repeated announcements of the same data only renew its lease.
   pub fn update_participant(&mut self, data: &SPDPDiscoveredParticipantData) -> bool {
-    let data = data.clone();
+    let mut data = data.clone();
+    let (unicast, multicast) = self.select_transport(
+      &data.metatraffic_unicast_locators,
+      &data.metatraffic_multicast_locators,
+    );
+    data.metatraffic_unicast_locators = unicast;
+    data.metatraffic_multicast_locators = multicast;
+    let (unicast, multicast) = self.select_transport(
+      &data.default_unicast_locators,
+      &data.default_multicast_locators,
+    );
+    data.default_unicast_locators = unicast;
+    data.default_multicast_locators = multicast;
 
     match data.participant_guid {
+      Some(guid) if self.ignored_participants.contains(&guid.guidPrefix) => false,
+      Some(guid) if data.domain_tag() != self.domain_tag => {
+        if self.foreign_domain_participants.insert(guid.guidPrefix) {
+          debug!(
+            "Participant {:?} has domain tag {:?}, ours is {:?}. Ignoring it.",
+            guid.guidPrefix,
+            data.domain_tag(),
+            self.domain_tag
+          );
+        }
+        false
+      }
       Some(guid) => {
-        self.participant_proxies.insert(guid, data);
-        true
+        let changed = match self.participant_proxies.get(&guid) {
+          Some(stored) => !stored.same_announcement(&data),
+          None => {
+            self.remove_restarted_participants(guid, &data);
+            true
+          }
+        };
+        if changed {
+          self.participant_proxies.insert(guid, data);
+        }
+        self
+          .participant_last_life_signs
+          .insert(guid, self.clock.instant());
+        changed
       }
       _ => false,
     }
   }
 
+  // A restarted process comes back with a new participant GUID, but usually on the same
+  // metatraffic unicast ports, which only one participant can have at a time. Then the
+  // old participant is gone, and it is removed with its readers and writers right away
+  // instead of when its lease expires. This way our readers see the instances of the old
+  // writers lose their writers before the restarted writers write them again.
+  fn remove_restarted_participants(&mut self, guid: GUID, data: &SPDPDiscoveredParticipantData) {
+    let udp_locators = |locators: &LocatorList| -> HashSet<Locator> {
+      locators
+        .iter()
+        .filter(|l| {
+          l.kind == LocatorKind::LOCATOR_KIND_UDPv4 || l.kind == LocatorKind::LOCATOR_KIND_UDPv6
+        })
+        .copied()
+        .collect()
+    };
+    let ports = udp_locators(&data.metatraffic_unicast_locators);
+    if ports.is_empty() {
+      return;
+    }
+    let restarted: Vec<GUID> = self
+      .participant_proxies
+      .iter()
+      .filter(|(g, p)| **g != guid && udp_locators(&p.metatraffic_unicast_locators) == ports)
+      .map(|(g, _)| *g)
+      .collect();
+    for old in restarted {
+      debug!(
+        "Participant {:?} has restarted as {:?}.",
+        old.guidPrefix, guid.guidPrefix
+      );
+      self.remove_participant(old);
+    }
+  }
+
   pub fn remove_participant(&mut self, guid: GUID) {
     self.participant_proxies.remove(&guid);
+    self.participant_last_life_signs.remove(&guid);
 
     self.remove_topic_reader_with_prefix(guid.guidPrefix);
 
     self.remove_topic_writer_with_prefix(guid.guidPrefix);
   }
 
+  // Forgets the participant and all of its readers and writers, and drops any
+  // discovery data from it in the future.
+  pub fn ignore_participant(&mut self, guid_prefix: GuidPrefix) {
+    self.ignored_participants.insert(guid_prefix);
+
+    let guids: Vec<GUID> = self
+      .participant_proxies
+      .keys()
+      .filter(|g| g.guidPrefix == guid_prefix)
+      .copied()
+      .collect();
+    for guid in guids {
+      self.participant_proxies.remove(&guid);
+      self.participant_last_life_signs.remove(&guid);
+    }
+
+    self.remove_topic_reader_with_prefix(guid_prefix);
+    self.remove_topic_writer_with_prefix(guid_prefix);
+  }
+
+  pub fn ignore_publication(&mut self, guid: GUID) {
+    self.ignored_publications.insert(guid);
+    self.remove_topic_writer(guid);
+  }
+
+  pub fn ignore_subscription(&mut self, guid: GUID) {
+    self.ignored_subscriptions.insert(guid);
+    self.remove_topic_reader(guid);
+  }
+
+  pub fn is_ignored_publication(&self, guid: GUID) -> bool {
+    self.is_ignored(Some(guid), &self.ignored_publications)
+  }
+
+  pub fn is_ignored_subscription(&self, guid: GUID) -> bool {
+    self.is_ignored(Some(guid), &self.ignored_subscriptions)
+  }
+
+  fn is_ignored(&self, guid: Option<GUID>, ignored_endpoints: &HashSet<GUID>) -> bool {
+    match guid {
+      Some(g) => {
+        self.ignored_participants.contains(&g.guidPrefix)
+          || self.foreign_domain_participants.contains(&g.guidPrefix)
+          || ignored_endpoints.contains(&g)
+      }
+      None => false,
+    }
+  }
+
   fn remove_topic_reader_with_prefix(&mut self, guid_prefix: GuidPrefix) {
     self
       .external_topic_readers
@@ -123,38 +369,60 @@ impl DiscoveryDB {
       });
   }
 
-  pub fn participant_cleanup(&mut self) {
-    let inow = Instant::now();
+  // Lease duration of a participant that does not advertise one,
+  // RTPS spec 9.6.2.2 PID_PARTICIPANT_LEASE_DURATION default.
+  const DEFAULT_PARTICIPANT_LEASE_DURATION: Duration = Duration::from_secs(100);
 
-    let grouped = self
-      .external_topic_writers
+  fn participant_lease_duration(data: &SPDPDiscoveredParticipantData) -> Duration {
+    data
+      .lease_duration
+      .unwrap_or(DiscoveryDB::DEFAULT_PARTICIPANT_LEASE_DURATION)
+  }
+
+  // Removes participants we have not heard from within their own advertised lease,
+  // together with their readers and writers. Participants with an infinite lease are
+  // never removed. Returns the removed participants.
+  pub fn participant_cleanup(&mut self) -> Vec<GUID> {
+    let inow = self.clock.instant();
+    let expired: Vec<GUID> = self
+      .participant_proxies
       .iter()
-      .filter(|p| p.writer_proxy.remote_writer_guid.is_some())
-      .map(|p| {
-        (
-          p.writer_proxy.remote_writer_guid.unwrap(),
-          inow.duration_since(p.last_updated),
-        )
+      .filter(|(g, sp)| {
+        let lease_duration = DiscoveryDB::participant_lease_duration(sp);
+        if lease_duration == Duration::DURATION_INFINITE {
+          return false;
+        }
+        match self.participant_last_life_signs.get(g) {
+          Some(last) => inow.duration_since(*last) > lease_duration.to_std(),
+          None => true,
+        }
       })
-      .map(|(g, d)| (g, Duration::from_std(d)))
-      .group_by(|(p, _)| p.clone());
-
-    let durations: HashMap<GUID, Duration> =
-      grouped.into_iter().filter_map(|(_, p)| p.min()).collect();
-
-    self.participant_proxies.retain(|g, sp| {
-      let lease_duration = match sp.lease_duration {
-        Some(ld) => ld,
-        None => Duration::DURATION_INFINITE,
-      };
+      .map(|(g, _)| *g)
+      .collect();
 
-      let min_update = durations.get(g);
+    for guid in expired.iter() {
+      debug!("Participant {:?} lease expired.", guid.guidPrefix);
+      self.remove_participant(*guid);
+    }
+    expired
+  }
 
-      match min_update {
-        Some(&dur) => lease_duration > dur,
-        None => lease_duration > Duration::DURATION_INFINITE,
-      }
-    });
+  // When the lease of the first remote participant expires, if we hear nothing more.
+  pub fn next_participant_lease_expiry(&self) -> Option<Instant> {
+    self
+      .participant_proxies
+      .iter()
+      .filter_map(|(g, sp)| {
+        let lease_duration = DiscoveryDB::participant_lease_duration(sp);
+        if lease_duration == Duration::DURATION_INFINITE {
+          return None;
+        }
+        self
+          .participant_last_life_signs
+          .get(g)
+          .and_then(|last| last.checked_add(lease_duration.to_std()))
+      })
+      .min()
   }
 
   fn topic_has_writers_or_readers(&self, topic_name: &String) -> bool {
@@ -215,7 +483,7 @@ impl DiscoveryDB {
       .topics
       .iter()
       .map(|(tn, _)| tn)
-      .filter(|tn| !self.topic_has_writers_or_readers(tn))
+      .filter(|tn| !self.local_topics.contains_key(*tn) && !self.topic_has_writers_or_readers(tn))
       .map(|tn| tn.clone())
       .collect();
     for dt in dead_topics.iter() {
@@ -251,9 +519,25 @@ impl DiscoveryDB {
     self.writers_updated = true;
   }
 
-  pub fn remove_local_topic_writer(&mut self, guid: GUID) {
-    self.local_topic_writers.remove(&guid);
+  // Set with DataWriter::set_name, announced with the next SEDP update
+  pub fn update_local_writer_name(&mut self, guid: GUID, name: &str) {
+    if let Some(writer) = self.local_topic_writers.get_mut(&guid) {
+      writer.publication_topic_data.entity_name = Some(name.to_string());
+      self.writers_updated = true;
+    }
+  }
+
+  pub fn is_local_topic_writer(&self, guid: GUID) -> bool {
+    self.local_topic_writers.contains_key(&guid)
+  }
+
+  pub fn get_local_topic_writer(&self, guid: GUID) -> Option<&DiscoveredWriterData> {
+    self.local_topic_writers.get(&guid)
+  }
+
+  pub fn remove_local_topic_writer(&mut self, guid: GUID) -> Option<DiscoveredWriterData> {
     self.writers_updated = true;
+    self.local_topic_writers.remove(&guid)
   }
 
   pub fn get_external_reader_proxies<'a>(&'a self) -> Iter<'a, DiscoveredReaderData> {
@@ -336,28 +620,92 @@ impl DiscoveryDB {
     }
   }
 
-  pub fn update_subscription(&mut self, data: &DiscoveredReaderData) {
-    self.add_reader_to_local_writer(data);
+  // Returns true if the subscription is new or changed, false if it is ignored or the
+  // same as before. Remote participants announce their subscriptions again and again.
+  pub fn update_subscription(&mut self, data: &DiscoveredReaderData) -> bool {
+    if self.is_ignored(
+      data.reader_proxy.remote_reader_guid,
+      &self.ignored_subscriptions,
+    ) {
+      return false;
+    }
+
+    let mut data = data.clone();
+    let proxy = &mut data.reader_proxy;
+    // A reader on this host gets everything through shared memory.
+    let shm = same_host_shm_locators(&proxy.unicast_locator_list, self.shm_namespace);
+    if shm.is_empty() {
+      let (unicast, multicast) =
+        self.select_transport(&proxy.unicast_locator_list, &proxy.multicast_locator_list);
+      proxy.unicast_locator_list = unicast;
+      proxy.multicast_locator_list = multicast;
+    } else {
+      proxy.unicast_locator_list = shm;
+      proxy.multicast_locator_list.clear();
+    }
 
-    self.external_topic_readers.push(data.clone());
-    self.external_topic_readers = self
+    let stored = self
       .external_topic_readers
-      .clone()
-      .into_iter()
-      .unique()
-      .collect();
+      .iter()
+      .position(|r| r.reader_proxy.remote_reader_guid == data.reader_proxy.remote_reader_guid);
+    if let Some(i) = stored {
+      if self.external_topic_readers[i] == data {
+        return false;
+      }
+    }
+
+    self.add_reader_to_local_writer(&data);
+
+    match stored {
+      Some(i) => self.external_topic_readers[i] = data,
+      None => self.external_topic_readers.push(data),
+    }
+    true
   }
 
-  pub fn update_publication(&mut self, data: &DiscoveredWriterData) {
-    self.add_writer_to_local_reader(data);
+  // Returns true if the publication is new or changed, false if it is ignored or the
+  // same as before.
+  pub fn update_publication(&mut self, data: &DiscoveredWriterData) -> bool {
+    if self.is_ignored(
+      data.writer_proxy.remote_writer_guid,
+      &self.ignored_publications,
+    ) {
+      return false;
+    }
 
-    self.external_topic_writers.push(data.clone());
-    self.external_topic_writers = self
+    let mut data = data.clone();
+    let proxy = &mut data.writer_proxy;
+    // A writer on this host sends data through shared memory, but AckNacks still go to
+    // it by UDP.
+    let shm = same_host_shm_locators(&proxy.unicast_locator_list, self.shm_namespace);
+    let (unicast, multicast) =
+      self.select_transport(&proxy.unicast_locator_list, &proxy.multicast_locator_list);
+    proxy.unicast_locator_list = unicast;
+    proxy.unicast_locator_list.extend(shm);
+    proxy.multicast_locator_list = multicast;
+
+    let stored = self
       .external_topic_writers
-      .clone()
-      .into_iter()
-      .unique()
-      .collect();
+      .iter()
+      .position(|w| w.writer_proxy.remote_writer_guid == data.writer_proxy.remote_writer_guid);
+    if let Some(i) = stored {
+      let stored = &mut self.external_topic_writers[i];
+      if stored.writer_proxy == data.writer_proxy
+        && stored.publication_topic_data == data.publication_topic_data
+      {
+        // The announcement is a sign of life of the writer all the same.
+        stored.last_updated = data.last_updated;
+        return false;
+      }
+    }
+
+    self.add_writer_to_local_reader(&data);
+
+    match stored {
+      Some(i) => self.external_topic_writers[i] = data,
+      None => self.external_topic_writers.push(data),
+    }
+    true
   }
 
   pub fn update_topic_data_drd(&mut self, drd: &DiscoveredReaderData) {
@@ -403,8 +751,14 @@ impl DiscoveryDB {
   }
 
   pub fn update_topic_data_p(&mut self, topic: &Topic) {
+    // Builtin topics are not announced.
+    let key = if topic.get_name().starts_with("DCPS") {
+      None
+    } else {
+      Some(self.local_topic_key(topic))
+    };
     let topic_data = DiscoveredTopicData::new(TopicBuiltinTopicData {
-      key: None,
+      key,
       name: Some(String::from(topic.get_name())),
       type_name: Some(String::from(topic.get_type().name())),
       durability: topic.get_qos().durability.clone(),
@@ -420,9 +774,70 @@ impl DiscoveryDB {
       ownership: topic.get_qos().ownership.clone(),
     });
 
+    if key.is_some() {
+      self
+        .local_topics
+        .insert(topic.get_name().to_string(), topic_data.clone());
+    }
     self.update_topic_data(&topic_data);
   }
 
+  // Our topics are announced each under a key of their own, which stays the same as
+  // long as the topic is used.
+  fn local_topic_key(&mut self, topic: &Topic) -> GUID {
+    if let Some(key) = self
+      .local_topics
+      .get(topic.get_name())
+      .and_then(|t| t.topic_data.key)
+    {
+      return key;
+    }
+    self.local_topic_count += 1;
+    let count = self.local_topic_count.to_be_bytes();
+    GUID::new_with_prefix_and_id(
+      topic.participant_guid_prefix(),
+      EntityId::createCustomEntityID([count[1], count[2], count[3]], TOPIC_ENTITY_KIND),
+    )
+  }
+
+  // Stops announcing one of our topics. Its discovered data goes too, unless some
+  // remote DataReader or DataWriter uses the topic. Returns the key of the
+  // announcement, for disposing it.
+  pub fn remove_local_topic(&mut self, topic_name: &str) -> Option<GUID> {
+    let removed = self.local_topics.remove(topic_name)?;
+    if !self.topic_has_writers_or_readers(&topic_name.to_string()) {
+      self.topics.remove(topic_name);
+    }
+    removed.topic_data.key
+  }
+
+  // A remote participant disposed its announcement of a topic. Returns true if the topic
+  // was removed, which it is not if we or some other participant still use it. The
+  // endpoints of the disposing participant are being disposed too.
+  pub fn remove_topic_with_key(&mut self, key: GUID) -> bool {
+    let topic_name = match self
+      .topics
+      .iter()
+      .find(|(_, t)| t.topic_data.key == Some(key))
+    {
+      Some((name, _)) => name.clone(),
+      None => return false,
+    };
+    if self.local_topics.contains_key(&topic_name)
+      || self
+        .topic_endpoints(&topic_name)
+        .any(|guid| guid.guidPrefix != key.guidPrefix)
+    {
+      return false;
+    }
+    self.topics.remove(&topic_name);
+    true
+  }
+
+  pub fn get_local_topics<'a>(&'a self) -> impl Iterator<Item = &'a DiscoveredTopicData> {
+    self.local_topics.values()
+  }
+
   pub fn update_topic_data(&mut self, data: &DiscoveredTopicData) -> bool {
     let topic_name = match &data.topic_data.name {
       Some(n) => n,
@@ -433,7 +848,15 @@ impl DiscoveryDB {
     };
 
     match self.topics.get_mut(topic_name) {
-      Some(t) => *t = data.clone(),
+      Some(t) => {
+        // Topic data from DataReaders and DataWriters does not have the key of the topic
+        // announcement.
+        let key = t.topic_data.key;
+        *t = data.clone();
+        if t.topic_data.key.is_none() {
+          t.topic_data.key = key;
+        }
+      }
       None => {
         self.topics.insert(topic_name.clone(), data.clone());
       }
@@ -448,7 +871,7 @@ impl DiscoveryDB {
       EntityId::ENTITYID_SPDP_BUILTIN_PARTICIPANT_READER,
     );
     let mut reader_proxy = ReaderProxy::new(guid);
-    reader_proxy.multicast_locator_list = get_local_multicast_locators(port);
+    reader_proxy.multicast_locator_list = get_local_multicast_locators(port, &self.ip_families);
 
     let sub_topic_data = SubscriptionBuiltinTopicData::new(
       guid,
@@ -478,6 +901,7 @@ impl DiscoveryDB {
       reader,
       domain_participant.domain_id(),
       domain_participant.participant_id(),
+      &domain_participant.local_network(),
     );
 
     let mut subscription_data = SubscriptionBuiltinTopicData::new(
@@ -504,9 +928,34 @@ impl DiscoveryDB {
     self.readers_updated = true;
   }
 
-  pub fn remove_local_topic_reader(&mut self, guid: GUID) {
-    self.local_topic_readers.remove(&guid);
+  // Set with DataReader::set_multicast. Matched writers hear of them with the next
+  // SEDP update.
+  pub fn update_local_reader_multicast_locators(&mut self, guid: GUID, locators: LocatorList) {
+    if let Some(reader) = self.local_topic_readers.get_mut(&guid) {
+      reader.reader_proxy.multicast_locator_list = locators;
+      self.readers_updated = true;
+    }
+  }
+
+  // Set with DataReader::set_name, announced with the next SEDP update
+  pub fn update_local_reader_name(&mut self, guid: GUID, name: &str) {
+    if let Some(reader) = self.local_topic_readers.get_mut(&guid) {
+      reader.subscription_topic_data.set_entity_name(name);
+      self.readers_updated = true;
+    }
+  }
+
+  pub fn is_local_topic_reader(&self, guid: GUID) -> bool {
+    self.local_topic_readers.contains_key(&guid)
+  }
+
+  pub fn get_local_topic_reader(&self, guid: GUID) -> Option<&DiscoveredReaderData> {
+    self.local_topic_readers.get(&guid)
+  }
+
+  pub fn remove_local_topic_reader(&mut self, guid: GUID) -> Option<DiscoveredReaderData> {
     self.readers_updated = true;
+    self.local_topic_readers.remove(&guid)
   }
 
   pub fn is_readers_updated(&self) -> bool {
@@ -537,6 +986,48 @@ impl DiscoveryDB {
     self.local_topic_writers.iter().map(|(_, p)| p)
   }
 
+  pub fn get_participant(&self, guid_prefix: GuidPrefix) -> Option<&SPDPDiscoveredParticipantData> {
+    self
+      .participant_proxies
+      .iter()
+      .find(|(g, _)| g.guidPrefix == guid_prefix)
+      .map(|(_, p)| p)
+  }
+
+  pub fn get_topic(&self, topic_name: &str) -> Option<&DiscoveredTopicData> {
+    self.topics.get(topic_name)
+  }
+
+  // Topic data does not tell whether the topic is keyed, but the entity kinds of its
+  // DataReaders and DataWriters do. None if no DataReader or DataWriter of the topic
+  // is known.
+  pub fn topic_kind(&self, topic_name: &str) -> Option<TopicKind> {
+    self
+      .topic_endpoints(topic_name)
+      .find_map(|guid| match guid.entityId.entityKind {
+        0x02 | 0x07 => Some(TopicKind::WithKey),
+        0x03 | 0x04 => Some(TopicKind::NoKey),
+        _ => None,
+      })
+  }
+
+  // GUIDs of the local and remote DataWriters and DataReaders of the topic
+  fn topic_endpoints<'a>(&'a self, topic_name: &'a str) -> impl Iterator<Item = GUID> + 'a {
+    let writers = self
+      .local_topic_writers
+      .values()
+      .chain(self.external_topic_writers.iter())
+      .filter(move |w| w.publication_topic_data.topic_name.as_deref() == Some(topic_name))
+      .filter_map(|w| w.writer_proxy.remote_writer_guid);
+    let readers = self
+      .local_topic_readers
+      .values()
+      .chain(self.external_topic_readers.iter())
+      .filter(move |r| r.subscription_topic_data.topic_name().as_deref() == Some(topic_name))
+      .filter_map(|r| r.reader_proxy.remote_reader_guid);
+    writers.chain(readers)
+  }
+
   pub fn get_all_topics<'a>(&'a self) -> impl Iterator<Item = &'a DiscoveredTopicData> {
     self
       .topics
@@ -564,8 +1055,195 @@ impl DiscoveryDB {
       .collect()
   }
 
+  pub fn get_external_topic_writers<'a>(
+    &'a self,
+    topic_name: &'a str,
+  ) -> impl Iterator<Item = &'a DiscoveredWriterData> {
+    self
+      .external_topic_writers
+      .iter()
+      .filter(move |p| match p.publication_topic_data.topic_name.as_ref() {
+        Some(tn) => tn == topic_name,
+        None => false,
+      })
+  }
+
+  pub fn get_external_topic_readers<'a>(
+    &'a self,
+    topic_name: &'a str,
+  ) -> impl Iterator<Item = &'a DiscoveredReaderData> {
+    self
+      .external_topic_readers
+      .iter()
+      .filter(
+        move |p| match p.subscription_topic_data.topic_name().as_ref() {
+          Some(tn) => tn == topic_name,
+          None => false,
+        },
+      )
+  }
+
+  pub fn get_external_writer(&self, guid: GUID) -> Option<&DiscoveredWriterData> {
+    self
+      .external_topic_writers
+      .iter()
+      .find(|p| p.writer_proxy.remote_writer_guid == Some(guid))
+  }
+
+  pub fn get_external_reader(&self, guid: GUID) -> Option<&DiscoveredReaderData> {
+    self
+      .external_topic_readers
+      .iter()
+      .find(|p| p.reader_proxy.remote_reader_guid == Some(guid))
+  }
+
+  // The first policy that prevents our local writer from matching the remote reader.
+  pub fn local_writer_qos_mismatch(
+    &self,
+    writer_guid: GUID,
+    reader: &DiscoveredReaderData,
+  ) -> Option<QosPolicyId> {
+    self.local_topic_writers.get(&writer_guid).and_then(|w| {
+      w.publication_topic_data
+        .generate_qos()
+        .compliance_failure_wrt(&reader.subscription_topic_data.generate_qos())
+    })
+  }
+
+  // The first policy that prevents our local reader from matching the remote writer.
+  pub fn local_reader_qos_mismatch(
+    &self,
+    reader_guid: GUID,
+    writer: &DiscoveredWriterData,
+  ) -> Option<QosPolicyId> {
+    self.local_topic_readers.get(&reader_guid).and_then(|r| {
+      writer
+        .publication_topic_data
+        .generate_qos()
+        .compliance_failure_wrt(&r.subscription_topic_data.generate_qos())
+    })
+  }
+
+  // Finds local and remote endpoints of the same topic that cannot match because of
+  // their QoS. Returns (topic name, local guid, remote guid, policy) of each pair that
+  // was not returned by a previous call.
+  pub fn new_qos_mismatches(&mut self) -> Vec<(String, GUID, GUID, QosPolicyId)> {
+    let mut mismatches = Vec::new();
+
+    for (local_guid, writer) in self.local_topic_writers.iter() {
+      let topic_name = match writer.publication_topic_data.topic_name.as_ref() {
+        Some(tn) => tn,
+        None => continue,
+      };
+      for reader in self.get_external_topic_readers(topic_name) {
+        let remote_guid = match reader.reader_proxy.remote_reader_guid {
+          Some(g) => g,
+          None => continue,
+        };
+        if let Some(policy) = self.local_writer_qos_mismatch(*local_guid, reader) {
+          mismatches.push((topic_name.clone(), *local_guid, remote_guid, policy));
+        }
+      }
+    }
+
+    for (local_guid, reader) in self.local_topic_readers.iter() {
+      let topic_name = match reader.subscription_topic_data.topic_name().as_ref() {
+        Some(tn) => tn,
+        None => continue,
+      };
+      for writer in self.get_external_topic_writers(topic_name) {
+        let remote_guid = match writer.writer_proxy.remote_writer_guid {
+          Some(g) => g,
+          None => continue,
+        };
+        if let Some(policy) = self.local_reader_qos_mismatch(*local_guid, writer) {
+          mismatches.push((topic_name.clone(), *local_guid, remote_guid, policy));
+        }
+      }
+    }
+
+    // Pairs that are gone are forgotten, so they are reported again if they reappear.
+    let previous = std::mem::replace(
+      &mut self.qos_mismatches,
+      mismatches.iter().map(|(_, l, r, _)| (*l, *r)).collect(),
+    );
+    mismatches
+      .into_iter()
+      .filter(|(_, l, r, _)| !previous.contains(&(*l, *r)))
+      .collect()
+  }
+
+  // Copies out what we know about remote participants and their endpoints.
+  pub fn snapshot(&self, own_prefix: GuidPrefix) -> DiscoverySnapshot {
+    let now = self.clock.instant();
+    let system_now = self.clock.system_time();
+
+    fn participant_entry(
+      participants: &mut BTreeMap<GuidPrefix, ParticipantSnapshot>,
+      guid_prefix: GuidPrefix,
+    ) -> &mut ParticipantSnapshot {
+      participants
+        .entry(guid_prefix)
+        .or_insert_with(|| ParticipantSnapshot {
+          guid_prefix,
+          name: None,
+          last_spdp_received: None,
+          readers: Vec::new(),
+          writers: Vec::new(),
+        })
+    }
+
+    let mut participants = BTreeMap::new();
+    let mut participant_count = 0;
+    for (guid, data) in self.participant_proxies.iter() {
+      if guid.guidPrefix == own_prefix {
+        continue;
+      }
+      participant_count += 1;
+      let last_spdp_received = self
+        .participant_last_life_signs
+        .get(guid)
+        .and_then(|last| system_now.checked_sub(now.duration_since(*last)));
+      let entry = participant_entry(&mut participants, guid.guidPrefix);
+      entry.name = data.entity_name.clone();
+      entry.last_spdp_received = last_spdp_received;
+    }
+
+    let mut reader_count = 0;
+    for reader in self.external_topic_readers.iter() {
+      let guid = match reader.reader_proxy.remote_reader_guid {
+        Some(g) if g.guidPrefix != own_prefix => g,
+        _ => continue,
+      };
+      reader_count += 1;
+      participant_entry(&mut participants, guid.guidPrefix)
+        .readers
+        .push(EndpointSnapshot::from_reader(guid, reader));
+    }
+
+    let mut writer_count = 0;
+    for writer in self.external_topic_writers.iter() {
+      let guid = match writer.writer_proxy.remote_writer_guid {
+        Some(g) if g.guidPrefix != own_prefix => g,
+        _ => continue,
+      };
+      writer_count += 1;
+      participant_entry(&mut participants, guid.guidPrefix)
+        .writers
+        .push(EndpointSnapshot::from_writer(guid, writer));
+    }
+
+    DiscoverySnapshot {
+      participant_count,
+      reader_count,
+      writer_count,
+      topic_count: self.get_all_topics().count(),
+      participants: participants.into_iter().map(|(_, p)| p).collect(),
+    }
+  }
+
   pub fn update_lease_duration(&mut self, data: ParticipantMessageData) {
-    let i = Instant::now();
+    let i = self.clock.instant();
     self
       .external_topic_writers
       .iter_mut()
@@ -574,6 +1252,24 @@ impl DiscoveryDB {
         None => false,
       })
       .for_each(|p| p.last_updated = i);
+
+    // liveliness message is also a sign of life of the participant
+    if let Some(last) = self
+      .participant_last_life_signs
+      .iter_mut()
+      .find(|(g, _)| g.guidPrefix == data.guid)
+      .map(|(_, last)| last)
+    {
+      *last = i;
+    }
+  }
+}
+
+impl PoisonRecovery for DiscoveryDB {
+  const NAME: &'static str = "DiscoveryDB";
+
+  fn count_poison_recovery(&self) {
+    self.poison_recoveries.fetch_add(1, Ordering::Relaxed);
   }
 }
 
@@ -596,9 +1292,14 @@ mod tests {
   use crate::structure::guid::*;
   use crate::serialization::cdr_serializer::CDRSerializerAdapter;
   use byteorder::LittleEndian;
-  use std::time::Duration as StdDuration;
+  use std::time::{Duration as StdDuration, SystemTime};
   use crate::dds::values::result::StatusChange;
+  use crate::structure::clock::{Clock, ManualClock};
   use crate::dds::with_key::datareader::ReaderCommand;
+  use crate::dds::qos::policy::Reliability;
+  use crate::structure::locator::{Locator, LocatorList, SocketAddr};
+  use crate::discovery::static_endpoint::StaticEndpoint;
+  use crate::network::util::{shm_locator, tcp_locator};
 
   #[test]
   fn discdb_participant_operations() {
@@ -616,9 +1317,431 @@ mod tests {
     discoverydb.participant_cleanup();
     assert!(discoverydb.participant_proxies.len() == 0);
 
+    // infinite lease never expires
+    data.lease_duration = Some(Duration::DURATION_INFINITE);
+    discoverydb.update_participant(&data);
+    discoverydb.participant_cleanup();
+    assert!(discoverydb.participant_proxies.len() == 1);
+
     // TODO: more operations tests
   }
 
+  #[test]
+  fn discdb_participant_lease_expiry() {
+    let clock = Arc::new(ManualClock::new());
+    let mut discoverydb = DiscoveryDB::with_clock(SharedClock::from(clock.clone()));
+    let mut data = spdp_participant_data().unwrap();
+    data.lease_duration = Some(Duration::from(StdDuration::from_secs(1)));
+    let participant_guid = data.participant_guid.unwrap();
+    assert!(discoverydb.update_participant(&data));
+
+    let mut reader_data =
+      DiscoveredReaderData::default(&String::from("some_topic"), &String::from("RandomData"));
+    reader_data.reader_proxy.remote_reader_guid = Some(GUID::new_with_prefix_and_id(
+      participant_guid.guidPrefix,
+      EntityId::createCustomEntityID([1, 2, 3], 0x07),
+    ));
+    assert!(discoverydb.update_subscription(&reader_data));
+
+    let expiry = discoverydb.next_participant_lease_expiry().unwrap();
+    assert_eq!(
+      expiry.saturating_duration_since(clock.instant()),
+      StdDuration::from_secs(1)
+    );
+
+    // not yet
+    clock.advance(StdDuration::from_millis(1000));
+    assert!(discoverydb.participant_cleanup().is_empty());
+    assert_eq!(discoverydb.participant_proxies.len(), 1);
+    assert_eq!(discoverydb.external_topic_readers.len(), 1);
+
+    // lease has expired, readers and writers go with the participant
+    clock.advance(StdDuration::from_millis(1));
+    assert_eq!(discoverydb.participant_cleanup(), vec![participant_guid]);
+    assert!(discoverydb.participant_proxies.is_empty());
+    assert!(discoverydb.external_topic_readers.is_empty());
+    assert!(discoverydb.next_participant_lease_expiry().is_none());
+
+    // infinite lease never expires
+    data.lease_duration = Some(Duration::DURATION_INFINITE);
+    discoverydb.update_participant(&data);
+    assert!(discoverydb.next_participant_lease_expiry().is_none());
+    assert!(discoverydb.participant_cleanup().is_empty());
+  }
+
+  #[test]
+  fn discdb_repeated_announcements() {
+    let clock = Arc::new(ManualClock::new());
+    let mut discoverydb = DiscoveryDB::with_clock(SharedClock::from(clock.clone()));
+    let mut data = spdp_participant_data().unwrap();
+    data.lease_duration = Some(Duration::from(StdDuration::from_secs(1)));
+    let participant_guid = data.participant_guid.unwrap();
+    assert!(discoverydb.update_participant(&data));
+
+    // the same again, received later, only renews the lease
+    clock.advance(StdDuration::from_millis(500));
+    for _ in 0..10 {
+      data.updated_time += 1;
+      assert!(!discoverydb.update_participant(&data));
+    }
+    let expiry = discoverydb.next_participant_lease_expiry().unwrap();
+    assert_eq!(
+      expiry.saturating_duration_since(clock.instant()),
+      StdDuration::from_secs(1)
+    );
+    data.lease_duration = Some(Duration::from(StdDuration::from_secs(2)));
+    assert!(discoverydb.update_participant(&data));
+
+    let remote = GUID::new_with_prefix_and_id(
+      participant_guid.guidPrefix,
+      EntityId::createCustomEntityID([1, 2, 3], 0x07),
+    );
+    let mut reader_data =
+      DiscoveredReaderData::default(&String::from("some_topic"), &String::from("RandomData"));
+    reader_data.reader_proxy.remote_reader_guid = Some(remote);
+    assert!(discoverydb.update_subscription(&reader_data));
+    for _ in 0..10 {
+      assert!(!discoverydb.update_subscription(&reader_data));
+    }
+    let udp = Locator::from("10.0.0.1:7411".parse::<SocketAddr>().unwrap());
+    reader_data.reader_proxy.unicast_locator_list = vec![udp];
+    assert!(discoverydb.update_subscription(&reader_data));
+    assert_eq!(discoverydb.external_topic_readers.len(), 1);
+
+    let writer = StaticEndpoint {
+      guid: remote,
+      topic_name: String::from("some_topic"),
+      type_name: String::from("RandomData"),
+      qos: QosPolicies::qos_none(),
+      unicast_locators: vec!["10.0.0.1:7411".parse().unwrap()],
+      multicast_locators: vec![],
+    };
+    assert!(discoverydb.update_publication(&writer.to_writer_data()));
+    // each announcement is received at a time of its own
+    for _ in 0..10 {
+      assert!(!discoverydb.update_publication(&writer.to_writer_data()));
+    }
+    assert_eq!(discoverydb.external_topic_writers.len(), 1);
+  }
+
+  #[test]
+  fn discdb_restarted_participant_replaces_old() {
+    let mut discoverydb = DiscoveryDB::new();
+    let locator = Locator::from(SocketAddr::from(([10, 0, 0, 1], 7410)));
+    let mut data = spdp_participant_data().unwrap();
+    data.metatraffic_unicast_locators = vec![locator];
+    let old_guid = data.participant_guid.unwrap();
+    assert!(discoverydb.update_participant(&data));
+
+    let mut reader_data =
+      DiscoveredReaderData::default(&String::from("some_topic"), &String::from("RandomData"));
+    reader_data.reader_proxy.remote_reader_guid = Some(GUID::new_with_prefix_and_id(
+      old_guid.guidPrefix,
+      EntityId::createCustomEntityID([1, 2, 3], 0x07),
+    ));
+    assert!(discoverydb.update_subscription(&reader_data));
+
+    // Same ports, but elsewhere: a different participant
+    let mut elsewhere = data.clone();
+    elsewhere.participant_guid = Some(GUID::new_with_prefix_and_id(
+      GuidPrefix::new(vec![0xDD; 12]),
+      EntityId::ENTITYID_PARTICIPANT,
+    ));
+    elsewhere.metatraffic_unicast_locators =
+      vec![Locator::from(SocketAddr::from(([10, 0, 0, 2], 7410)))];
+    assert!(discoverydb.update_participant(&elsewhere));
+    assert_eq!(discoverydb.participant_proxies.len(), 2);
+
+    // The same process restarted, with a new GUID
+    let mut restarted = data.clone();
+    let new_guid = GUID::new_with_prefix_and_id(
+      GuidPrefix::new(vec![0xEE; 12]),
+      EntityId::ENTITYID_PARTICIPANT,
+    );
+    restarted.participant_guid = Some(new_guid);
+    assert!(discoverydb.update_participant(&restarted));
+    assert_eq!(discoverydb.participant_proxies.len(), 2);
+    assert!(!discoverydb.participant_proxies.contains_key(&old_guid));
+    assert!(discoverydb.participant_proxies.contains_key(&new_guid));
+    assert!(discoverydb.external_topic_readers.is_empty());
+  }
+
+  #[test]
+  fn discdb_snapshot() {
+    let mut discoverydb = DiscoveryDB::new();
+    let mut data = spdp_participant_data().unwrap();
+    data.entity_name = Some(String::from("robot 7"));
+    let prefix = data.participant_guid.unwrap().guidPrefix;
+    assert!(discoverydb.update_participant(&data));
+
+    let mut reader_data =
+      DiscoveredReaderData::default(&String::from("some_topic"), &String::from("RandomData"));
+    let reader_guid =
+      GUID::new_with_prefix_and_id(prefix, EntityId::createCustomEntityID([1, 2, 3], 0x07));
+    reader_data.reader_proxy.remote_reader_guid = Some(reader_guid);
+    reader_data
+      .subscription_topic_data
+      .set_entity_name("camera reader");
+    assert!(discoverydb.update_subscription(&reader_data));
+    discoverydb.update_topic_data_drd(&reader_data);
+
+    let snapshot = discoverydb.snapshot(GuidPrefix::new(vec![0xEE; 12]));
+    assert_eq!(snapshot.participant_count, 1);
+    assert_eq!(snapshot.reader_count, 1);
+    assert_eq!(snapshot.writer_count, 0);
+    assert_eq!(snapshot.topic_count, 1);
+    assert_eq!(snapshot.participants.len(), 1);
+    let participant = &snapshot.participants[0];
+    assert_eq!(participant.guid_prefix, prefix);
+    assert_eq!(participant.name.as_deref(), Some("robot 7"));
+    assert!(participant.last_spdp_received.unwrap() <= SystemTime::now());
+    assert!(participant.writers.is_empty());
+    assert_eq!(participant.readers[0].guid, reader_guid);
+    assert_eq!(participant.readers[0].topic_name, "some_topic");
+    assert_eq!(participant.readers[0].type_name, "RandomData");
+    assert_eq!(
+      participant.readers[0].name.as_deref(),
+      Some("camera reader")
+    );
+
+    // our own participant is left out
+    let snapshot = discoverydb.snapshot(prefix);
+    assert_eq!(snapshot.participant_count, 0);
+    assert_eq!(snapshot.reader_count, 0);
+    assert!(snapshot.participants.is_empty());
+  }
+
+  #[test]
+  fn discdb_domain_tag() {
+    let mut discoverydb = DiscoveryDB::new();
+    let mut data = spdp_participant_data().unwrap();
+
+    // empty tag matches participants that don't send one
+    assert!(discoverydb.update_participant(&data));
+
+    let mut discoverydb = DiscoveryDB::new();
+    discoverydb.set_domain_tag(String::from("fleet_a"));
+    assert!(!discoverydb.update_participant(&data));
+    data.domain_tag = Some(String::from("fleet_b"));
+    assert!(!discoverydb.update_participant(&data));
+    assert!(discoverydb.participant_proxies.is_empty());
+
+    // endpoints of a participant from another domain are not accepted either
+    let prefix = data.participant_guid.unwrap().guidPrefix;
+    let mut reader_data =
+      DiscoveredReaderData::default(&String::from("some_topic"), &String::from("RandomData"));
+    reader_data.reader_proxy.remote_reader_guid = Some(GUID::new_with_prefix_and_id(
+      prefix,
+      EntityId::createCustomEntityID([1, 2, 3], 0x07),
+    ));
+    assert!(!discoverydb.update_subscription(&reader_data));
+
+    data.domain_tag = Some(String::from("fleet_a"));
+    let mut discoverydb = DiscoveryDB::new();
+    discoverydb.set_domain_tag(String::from("fleet_a"));
+    assert!(discoverydb.update_participant(&data));
+  }
+
+  #[test]
+  fn discdb_preferred_ip_family() {
+    let locators = |addresses: &[&str]| -> LocatorList {
+      addresses
+        .iter()
+        .map(|a| Locator::from(a.parse::<SocketAddr>().unwrap()))
+        .collect()
+    };
+    let dual_stack = locators(&["10.0.0.1:7411", "[fd00::1]:7411"]);
+
+    let mut data = spdp_participant_data().unwrap();
+    data.default_unicast_locators = dual_stack.clone();
+    data.metatraffic_multicast_locators = locators(&["239.255.0.1:7400"]);
+    let guid = data.participant_guid.unwrap();
+
+    let mut discoverydb = DiscoveryDB::new();
+    assert!(discoverydb.update_participant(&data));
+    assert_eq!(
+      discoverydb.participant_proxies[&guid].default_unicast_locators,
+      locators(&["10.0.0.1:7411"])
+    );
+
+    let mut discoverydb = DiscoveryDB::new();
+    discoverydb.set_ip_families(vec![IpFamily::V6, IpFamily::V4]);
+    assert!(discoverydb.update_participant(&data));
+    let proxy = &discoverydb.participant_proxies[&guid];
+    assert_eq!(
+      proxy.default_unicast_locators,
+      locators(&["[fd00::1]:7411"])
+    );
+    // IPv4 is still used if that is all there is
+    assert_eq!(
+      proxy.metatraffic_multicast_locators,
+      locators(&["239.255.0.1:7400"])
+    );
+
+    let mut reader_data =
+      DiscoveredReaderData::default(&String::from("some_topic"), &String::from("RandomData"));
+    reader_data.reader_proxy.remote_reader_guid = Some(GUID::new_with_prefix_and_id(
+      guid.guidPrefix,
+      EntityId::createCustomEntityID([1, 2, 3], 0x07),
+    ));
+    reader_data.reader_proxy.unicast_locator_list = dual_stack;
+    assert!(discoverydb.update_subscription(&reader_data));
+    assert_eq!(
+      discoverydb.external_topic_readers[0]
+        .reader_proxy
+        .unicast_locator_list,
+      locators(&["[fd00::1]:7411"])
+    );
+  }
+
+  #[test]
+  fn discdb_shm_locators() {
+    let udp = Locator::from("10.0.0.1:7411".parse::<SocketAddr>().unwrap());
+    let remote = GUID::new_with_prefix_and_id(
+      GuidPrefix::new(vec![3; 12]),
+      EntityId::createCustomEntityID([1, 2, 3], 0x07),
+    );
+    let mut discoverydb = DiscoveryDB::new();
+    discoverydb.set_shm_namespace(Some(42));
+
+    let mut reader_data =
+      DiscoveredReaderData::default(&String::from("some_topic"), &String::from("RandomData"));
+    reader_data.reader_proxy.remote_reader_guid = Some(remote);
+    reader_data.reader_proxy.unicast_locator_list = vec![shm_locator(42, remote), udp];
+    assert!(discoverydb.update_subscription(&reader_data));
+    assert_eq!(
+      discoverydb.external_topic_readers[0]
+        .reader_proxy
+        .unicast_locator_list,
+      vec![shm_locator(42, remote)]
+    );
+
+    // another host
+    reader_data.reader_proxy.unicast_locator_list = vec![shm_locator(43, remote), udp];
+    assert!(discoverydb.update_subscription(&reader_data));
+    assert_eq!(discoverydb.external_topic_readers.len(), 1);
+    assert_eq!(
+      discoverydb.external_topic_readers[0]
+        .reader_proxy
+        .unicast_locator_list,
+      vec![udp]
+    );
+
+    let mut writer_data = StaticEndpoint {
+      guid: remote,
+      topic_name: String::from("some_topic"),
+      type_name: String::from("RandomData"),
+      qos: QosPolicies::qos_none(),
+      unicast_locators: vec![],
+      multicast_locators: vec![],
+    }
+    .to_writer_data();
+    writer_data.writer_proxy.unicast_locator_list = vec![shm_locator(42, remote), udp];
+    assert!(discoverydb.update_publication(&writer_data));
+    assert_eq!(
+      discoverydb.external_topic_writers[0]
+        .writer_proxy
+        .unicast_locator_list,
+      vec![udp, shm_locator(42, remote)]
+    );
+  }
+
+  #[test]
+  fn discdb_tcp_locators() {
+    let udp = Locator::from("10.0.0.1:7411".parse::<SocketAddr>().unwrap());
+    let multicast = Locator::from("239.255.0.1:7401".parse::<SocketAddr>().unwrap());
+    let mut data = spdp_participant_data().unwrap();
+    let guid = data.participant_guid.unwrap();
+    let tcp = tcp_locator(guid.guidPrefix, "10.0.0.1:7500".parse().unwrap());
+    data.default_unicast_locators = vec![udp, tcp];
+    data.default_multicast_locators = vec![multicast];
+
+    // TCP is preferred when both sides use it
+    let mut discoverydb = DiscoveryDB::new();
+    discoverydb.set_transports(true, true);
+    assert!(discoverydb.update_participant(&data));
+    let proxy = &discoverydb.participant_proxies[&guid];
+    assert_eq!(proxy.default_unicast_locators, vec![tcp]);
+    assert!(proxy.default_multicast_locators.is_empty());
+
+    let mut discoverydb = DiscoveryDB::new();
+    assert!(discoverydb.update_participant(&data));
+    let proxy = &discoverydb.participant_proxies[&guid];
+    assert_eq!(proxy.default_unicast_locators, vec![udp]);
+    assert_eq!(proxy.default_multicast_locators, vec![multicast]);
+
+    // without UDP, participants not announcing TCP cannot be reached
+    let mut discoverydb = DiscoveryDB::new();
+    discoverydb.set_transports(false, true);
+    data.default_unicast_locators = vec![udp];
+    assert!(discoverydb.update_participant(&data));
+    let proxy = &discoverydb.participant_proxies[&guid];
+    assert!(proxy.default_unicast_locators.is_empty());
+    assert!(proxy.default_multicast_locators.is_empty());
+  }
+
+  #[test]
+  fn discdb_qos_mismatches() {
+    let mut discovery_db = DiscoveryDB::new();
+
+    let dp = DomainParticipant::new(0).unwrap();
+    let best_effort = QosPolicies::builder()
+      .reliability(Reliability::BestEffort)
+      .build();
+    let topic = dp
+      .create_topic("Foobar", "RandomData", &best_effort, TopicKind::WithKey)
+      .unwrap();
+    let publisher = dp.create_publisher(&best_effort).unwrap();
+    let dw = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(
+        None, &topic, None,
+      )
+      .unwrap();
+    let writer_data = DiscoveredWriterData::new(&dw, &topic, &dp);
+    let writer_guid = writer_data.writer_proxy.remote_writer_guid.unwrap();
+    discovery_db.update_local_topic_writer(writer_data);
+
+    let reliable = QosPolicies::builder()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::DURATION_ZERO,
+      })
+      .build();
+    let reader_guid = GUID::new();
+    let reader_data = DiscoveredReaderData {
+      reader_proxy: ReaderProxy::new(reader_guid),
+      subscription_topic_data: SubscriptionBuiltinTopicData::new(
+        reader_guid,
+        topic.get_name(),
+        "RandomData",
+        &reliable,
+      ),
+      content_filter: None,
+    };
+    discovery_db.update_subscription(&reader_data);
+
+    assert_eq!(
+      discovery_db.local_writer_qos_mismatch(writer_guid, &reader_data),
+      Some(QosPolicyId::Reliability)
+    );
+    assert_eq!(
+      discovery_db.new_qos_mismatches(),
+      vec![(
+        String::from("Foobar"),
+        writer_guid,
+        reader_guid,
+        QosPolicyId::Reliability
+      )]
+    );
+    // already reported
+    assert!(discovery_db.new_qos_mismatches().is_empty());
+
+    // reported again after the reader has been away
+    discovery_db.remove_topic_reader(reader_guid);
+    assert!(discovery_db.new_qos_mismatches().is_empty());
+    discovery_db.update_subscription(&reader_data);
+    assert_eq!(discovery_db.new_qos_mismatches().len(), 1);
+  }
+
   #[test]
   fn discdb_writer_proxies() {
     let _discoverydb = DiscoveryDB::new();
@@ -633,7 +1756,7 @@ mod tests {
   fn discdb_subscription_operations() {
     let mut discovery_db = DiscoveryDB::new();
 
-    let domain_participant = DomainParticipant::new(0);
+    let domain_participant = DomainParticipant::new(0).unwrap();
     let topic = domain_participant
       .create_topic(
         "Foobar",
@@ -720,7 +1843,7 @@ mod tests {
 
   #[test]
   fn discdb_local_topic_reader() {
-    let dp = DomainParticipant::new(0);
+    let dp = DomainParticipant::new(0).unwrap();
     let topic = dp
       .create_topic(
         "some topic name",
@@ -768,4 +1891,57 @@ mod tests {
     assert_eq!(discoverydb.get_local_topic_readers(&topic).len(), 2);
     assert_eq!(discoverydb.get_all_local_topic_readers().count(), 2);
   }
+
+  #[test]
+  fn discdb_local_topics() {
+    let dp = DomainParticipant::new(0).unwrap();
+    let qos = QosPolicies::qos_none();
+    let topic = dp
+      .create_topic("LocalTopic", "Wazzup", &qos, TopicKind::WithKey)
+      .unwrap();
+    let builtin = dp
+      .create_topic("DCPSWazzup", "Wazzup", &qos, TopicKind::WithKey)
+      .unwrap();
+    let mut discoverydb = DiscoveryDB::new();
+
+    discoverydb.update_topic_data_p(&topic);
+    discoverydb.update_topic_data_p(&builtin);
+    let key = discoverydb
+      .get_topic("LocalTopic")
+      .unwrap()
+      .topic_data
+      .key
+      .unwrap();
+    assert_eq!(key.guidPrefix, dp.get_guid_prefix());
+    assert_eq!(key.entityId.entityKind, TOPIC_ENTITY_KIND);
+    assert_eq!(discoverydb.get_local_topics().count(), 1);
+
+    // the key stays the same, also over topic data without a key
+    discoverydb.update_topic_data_p(&topic);
+    let mut keyless = discoverydb.get_topic("LocalTopic").unwrap().clone();
+    keyless.topic_data.key = None;
+    discoverydb.update_topic_data(&keyless);
+    assert_eq!(
+      discoverydb.get_topic("LocalTopic").unwrap().topic_data.key,
+      Some(key)
+    );
+
+    assert_eq!(discoverydb.remove_local_topic("LocalTopic"), Some(key));
+    assert!(discoverydb.get_topic("LocalTopic").is_none());
+    assert_eq!(discoverydb.get_local_topics().count(), 0);
+    assert_eq!(discoverydb.remove_local_topic("LocalTopic"), None);
+
+    // remote announcements are removed when disposed
+    let remote_key = GUID::new_with_prefix_and_id(
+      GuidPrefix::new(vec![1; 12]),
+      EntityId::createCustomEntityID([0, 0, 1], TOPIC_ENTITY_KIND),
+    );
+    let mut remote = keyless;
+    remote.topic_data.key = Some(remote_key);
+    discoverydb.update_topic_data(&remote);
+    assert!(discoverydb.get_topic("LocalTopic").is_some());
+    assert!(!discoverydb.remove_topic_with_key(key));
+    assert!(discoverydb.remove_topic_with_key(remote_key));
+    assert!(discoverydb.get_topic("LocalTopic").is_none());
+  }
 }
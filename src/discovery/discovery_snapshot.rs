@@ -0,0 +1,97 @@
+use std::time::SystemTime;
+
+use crate::{
+  dds::qos::QosPolicies,
+  structure::guid::{GuidPrefix, GUID},
+};
+use crate::discovery::data_types::topic_data::{DiscoveredReaderData, DiscoveredWriterData};
+
+/// What a [DomainParticipant](struct.DomainParticipant.html) knows about
+/// remote participants and their readers and writers at one moment. This is a
+/// plain copy, it does not change when discovery goes on.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::DomainParticipant;
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// let snapshot = domain_participant.discovery_snapshot();
+/// println!(
+///   "{} participants, {} readers, {} writers, {} topics",
+///   snapshot.participant_count,
+///   snapshot.reader_count,
+///   snapshot.writer_count,
+///   snapshot.topic_count
+/// );
+/// for participant in snapshot.participants.iter() {
+///   if let Some(last_spdp) = participant.last_spdp_received {
+///     println!("{:?} last heard at {:?}", participant.guid_prefix, last_spdp);
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoverySnapshot {
+  /// Remote participants discovered with SPDP
+  pub participant_count: usize,
+  /// Remote DataReaders
+  pub reader_count: usize,
+  /// Remote DataWriters
+  pub writer_count: usize,
+  /// Topics known either from local or remote readers and writers
+  pub topic_count: usize,
+  /// Remote participants and their endpoints, ordered by GuidPrefix. Also
+  /// contains participants whose endpoints are known, but not the participant
+  /// itself, e.g. static endpoints.
+  pub participants: Vec<ParticipantSnapshot>,
+}
+
+/// A remote participant in a [DiscoverySnapshot](struct.DiscoverySnapshot.html)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParticipantSnapshot {
+  pub guid_prefix: GuidPrefix,
+  /// Name the participant announced, if any. Names are for people, and several
+  /// participants may have the same one.
+  pub name: Option<String>,
+  /// When participant data (SPDP) was last received from it, if ever
+  pub last_spdp_received: Option<SystemTime>,
+  pub readers: Vec<EndpointSnapshot>,
+  pub writers: Vec<EndpointSnapshot>,
+}
+
+/// A remote DataReader or DataWriter in a
+/// [DiscoverySnapshot](struct.DiscoverySnapshot.html), or a local one found with
+/// [Subscriber::lookup_datareader](struct.Subscriber.html#method.lookup_datareader) or
+/// [Publisher::lookup_datawriter](struct.Publisher.html#method.lookup_datawriter).
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointSnapshot {
+  pub guid: GUID,
+  pub topic_name: String,
+  pub type_name: String,
+  /// Name the endpoint announced, if any
+  pub name: Option<String>,
+  /// QoS requested (reader) or offered (writer), as announced by the endpoint
+  pub qos: QosPolicies,
+}
+
+impl EndpointSnapshot {
+  pub(crate) fn from_reader(guid: GUID, reader: &DiscoveredReaderData) -> EndpointSnapshot {
+    let topic_data = &reader.subscription_topic_data;
+    EndpointSnapshot {
+      guid,
+      topic_name: topic_data.topic_name().clone().unwrap_or_default(),
+      type_name: topic_data.type_name().clone().unwrap_or_default(),
+      name: topic_data.entity_name().clone(),
+      qos: topic_data.generate_qos(),
+    }
+  }
+
+  pub(crate) fn from_writer(guid: GUID, writer: &DiscoveredWriterData) -> EndpointSnapshot {
+    let topic_data = &writer.publication_topic_data;
+    EndpointSnapshot {
+      guid,
+      topic_name: topic_data.topic_name.clone().unwrap_or_default(),
+      type_name: topic_data.type_name.clone().unwrap_or_default(),
+      name: topic_data.entity_name.clone(),
+      qos: topic_data.generate_qos(),
+    }
+  }
+}
@@ -1,5 +1,8 @@
 pub(crate) mod content_filter_property;
 pub(crate) mod data_types;
 pub(crate) mod discovery;
+pub(crate) mod discovery_config;
 pub(crate) mod discovery_db;
+pub(crate) mod discovery_snapshot;
 pub(crate) mod participant_proxy;
+pub(crate) mod static_endpoint;
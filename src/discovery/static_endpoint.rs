@@ -0,0 +1,101 @@
+use std::{net::SocketAddr, time::Instant};
+
+use crate::{
+  dds::qos::QosPolicies,
+  structure::{
+    guid::{EntityId, GUID},
+    locator::{Locator, LocatorList},
+  },
+};
+
+use super::data_types::topic_data::{
+  DiscoveredReaderData, DiscoveredWriterData, PublicationBuiltinTopicData, ReaderProxy,
+  SubscriptionBuiltinTopicData, WriterProxy,
+};
+
+/// A remote DataReader or DataWriter known in advance, for static discovery.
+///
+/// Static endpoints are given to
+/// [`DomainParticipant::add_static_remote_writer`](struct.DomainParticipant.html#method.add_static_remote_writer)
+/// and
+/// [`DomainParticipant::add_static_remote_reader`](struct.DomainParticipant.html#method.add_static_remote_reader),
+/// and are matched with our local readers and writers as if they had been
+/// discovered with SEDP. Together with `DiscoveryConfig::sedp_enabled` this
+/// allows running without endpoint discovery traffic.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::{DomainParticipant, StaticEndpoint};
+/// # use rustdds::dds::qos::QosPolicyBuilder;
+/// # use rustdds::dds::data_types::{EntityId, GuidPrefix, GUID};
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// let remote_writer = StaticEndpoint {
+///   guid: GUID::new_with_prefix_and_id(
+///     GuidPrefix::new(vec![1; 12]),
+///     EntityId::createCustomEntityID([0, 0, 1], 0x02),
+///   ),
+///   topic_name: String::from("some_topic"),
+///   type_name: String::from("SomeType"),
+///   qos: QosPolicyBuilder::new().build(),
+///   unicast_locators: vec!["192.168.1.10:7411".parse().unwrap()],
+///   multicast_locators: Vec::new(),
+/// };
+/// domain_participant.add_static_remote_writer(&remote_writer).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticEndpoint {
+  pub guid: GUID,
+  pub topic_name: String,
+  pub type_name: String,
+  /// QoS offered (writer) or requested (reader) by the remote endpoint.
+  pub qos: QosPolicies,
+  /// Addresses where the remote endpoint receives RTPS messages.
+  pub unicast_locators: Vec<SocketAddr>,
+  pub multicast_locators: Vec<SocketAddr>,
+}
+
+impl StaticEndpoint {
+  fn locators(addresses: &[SocketAddr]) -> LocatorList {
+    addresses.iter().map(|a| Locator::from(*a)).collect()
+  }
+
+  fn participant_guid(&self) -> GUID {
+    GUID::new_with_prefix_and_id(self.guid.guidPrefix, EntityId::ENTITYID_PARTICIPANT)
+  }
+
+  pub(crate) fn to_writer_data(&self) -> DiscoveredWriterData {
+    let mut publication_topic_data = PublicationBuiltinTopicData::new(
+      self.guid,
+      self.participant_guid(),
+      &self.topic_name,
+      &self.type_name,
+    );
+    publication_topic_data.read_qos(&self.qos);
+
+    DiscoveredWriterData {
+      last_updated: Instant::now(),
+      writer_proxy: WriterProxy::new(
+        self.guid,
+        StaticEndpoint::locators(&self.multicast_locators),
+        StaticEndpoint::locators(&self.unicast_locators),
+      ),
+      publication_topic_data,
+    }
+  }
+
+  pub(crate) fn to_reader_data(&self) -> DiscoveredReaderData {
+    let mut reader_proxy = ReaderProxy::new(self.guid);
+    reader_proxy.unicast_locator_list = StaticEndpoint::locators(&self.unicast_locators);
+    reader_proxy.multicast_locator_list = StaticEndpoint::locators(&self.multicast_locators);
+
+    let mut subscription_topic_data =
+      SubscriptionBuiltinTopicData::new(self.guid, &self.topic_name, &self.type_name, &self.qos);
+    subscription_topic_data.set_participant_key(self.participant_guid());
+
+    DiscoveredReaderData {
+      reader_proxy,
+      subscription_topic_data,
+      content_filter: None,
+    }
+  }
+}
@@ -13,6 +13,9 @@
 //! Additionally, there is a [ROS2](https://index.ros.org/doc/ros2/) interface, that is simpler to use than DDS
 //! when communicating to ROS2 components.
 //!
+//! The most used types are re-exported at the crate root, and [prelude](prelude/index.html) has everything
+//! needed to publish and subscribe: `use rustdds::prelude::*;`.
+//!
 
 #![allow(dead_code)]
 #![allow(non_camel_case_types)]
@@ -28,10 +31,14 @@ extern crate speedy;
 extern crate tokio_util;
 extern crate uuid;
 
+// Code generated by the atosdds_derive macros refers to rustdds, also within this crate.
+extern crate self as rustdds;
+
 #[macro_use]
 mod serialization_test;
 #[macro_use]
 mod checked_impl;
+#[macro_use]
 mod common;
 mod discovery;
 mod messages;
@@ -43,7 +50,16 @@ mod test;
 
 // Public modules
 pub mod dds;
+#[cfg(feature = "ros2")]
 pub mod ros2;
 
 /// Helpers for (De)serialization and definitions of (De)serializer adapters
 pub mod serialization;
+
+pub mod prelude;
+
+pub use dds::{
+  DomainParticipant, DomainParticipantBuilder, NoKeyDataReader, NoKeyDataWriter, Publisher,
+  Subscriber, Topic, WithKeyDataReader, WithKeyDataWriter,
+};
+pub use serialization::{CDRDeserializerAdapter, CDRSerializerAdapter};
@@ -22,6 +22,13 @@ impl Header {
       guid_prefix: guid,
     }
   }
+
+  // Versions before 2.1 are not interoperable with 2.x. Later minor versions
+  // of 2 must be, so they are supported, too.
+  pub fn is_supported_version(&self) -> bool {
+    self.protocol_version.major == ProtocolVersion::THIS_IMPLEMENTATION.major
+      && self.protocol_version >= ProtocolVersion::PROTOCOLVERSION_2_1
+  }
 }
 
 impl Validity for Header {
@@ -52,6 +59,30 @@ mod tests {
     assert!(!header.valid());
   }
 
+  #[test]
+  fn header_supported_versions() {
+    let mut header = Header::new(GuidPrefix::GUIDPREFIX_UNKNOWN);
+    for version in &[
+      ProtocolVersion::PROTOCOLVERSION_2_1,
+      ProtocolVersion::PROTOCOLVERSION_2_2,
+      ProtocolVersion::PROTOCOLVERSION_2_3,
+      ProtocolVersion::PROTOCOLVERSION_2_4,
+      ProtocolVersion { major: 2, minor: 5 },
+    ] {
+      header.protocol_version = *version;
+      assert!(header.is_supported_version());
+    }
+    for version in &[
+      ProtocolVersion::PROTOCOLVERSION_1_0,
+      ProtocolVersion::PROTOCOLVERSION_1_1,
+      ProtocolVersion::PROTOCOLVERSION_2_0,
+      ProtocolVersion { major: 3, minor: 0 },
+    ] {
+      header.protocol_version = *version;
+      assert!(!header.is_supported_version());
+    }
+  }
+
   #[test]
   fn header_protocol_id_same_as_rtps() {
     let mut header = Header::new(GuidPrefix::GUIDPREFIX_UNKNOWN);
@@ -45,6 +45,11 @@ pub struct AckNack {
 }
 
 impl AckNack {
+  /// Whether the AckNack is valid (RTPS spec 8.3.7.1.3), i.e. its readerSNState is.
+  pub fn is_valid(&self) -> bool {
+    self.reader_sn_state.is_valid()
+  }
+
   pub fn create_submessage(self, flags: BitFlags<ACKNACK_Flags>) -> Option<SubMessage> {
     let submessage_len = match self.write_to_vec() {
       Ok(bytes) => bytes.len() as u16,
@@ -3,9 +3,13 @@ use crate::messages::submessages::submessage_elements::serialized_payload::Seria
 use crate::messages::submessages::submessages::*;
 use crate::structure::guid::EntityId;
 use crate::structure::sequence_number::SequenceNumber;
+use crate::structure::inline_qos::SampleIdentity;
+use crate::structure::parameter_id::ParameterId;
 
 use speedy::{Readable, Writable, Context, Writer, Error};
 use enumflags2::BitFlags;
+use bytes::Bytes;
+use byteorder::{BigEndian, LittleEndian};
 use std::io;
 
 /// This Submessage is sent from an RTPS Writer (NO_KEY or WITH_KEY)
@@ -46,8 +50,8 @@ impl Data {
   /// DATA submessage cannot be speedy Readable because deserializing this requires info from submessage header.
   /// Required iformation is  expect_qos and expect_payload whish are told on submessage headerflags.
 
-  pub fn deserialize_data(buffer: &[u8], flags: BitFlags<DATA_Flags>) -> io::Result<Data> {
-    let mut cursor = io::Cursor::new(buffer);
+  pub fn deserialize_data(buffer: &Bytes, flags: BitFlags<DATA_Flags>) -> io::Result<Data> {
+    let mut cursor = io::Cursor::new(&buffer[..]);
     let endianness = endianness_flag(flags.bits());
     let map_speedy_err = |p: Error| io::Error::new(io::ErrorKind::Other, p);
 
@@ -66,21 +70,38 @@ impl Data {
     let expect_data = flags.contains(DATA_Flags::Data) || flags.contains(DATA_Flags::Key);
 
     let rtps_v23_data_header_size: u16 = 16;
+    if octets_to_inline_qos < rtps_v23_data_header_size {
+      return Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Data has too low octetsToInlineQos",
+      ));
+    }
     let extra_octets = octets_to_inline_qos - rtps_v23_data_header_size;
     cursor.set_position(cursor.position() + extra_octets as u64);
 
     let parameter_list = if expect_qos {
-      Some(
-        ParameterList::read_from_stream_with_ctx(endianness, &mut cursor)
-          .map_err(map_speedy_err)?,
-      )
+      let mut parameter_list = ParameterList::read_from_stream_with_ctx(endianness, &mut cursor)
+        .map_err(map_speedy_err)?;
+      // The sequence number in a related sample identity is in the byte order of this
+      // submessage, which the reader no longer knows. Store it in little endian.
+      if endianness == speedy::Endianness::BigEndian {
+        for p in parameter_list.parameters.iter_mut().filter(|p| {
+          p.parameter_id == ParameterId::PID_RELATED_SAMPLE_IDENTITY
+            || p.parameter_id == ParameterId::PID_CUSTOM_RELATED_SAMPLE_IDENTITY
+        }) {
+          if let Ok(identity) = SampleIdentity::from_octets::<BigEndian>(&p.value) {
+            p.value = identity.to_octets::<LittleEndian>().to_vec();
+          }
+        }
+      }
+      Some(parameter_list)
     } else {
       None
     };
 
     let payload = if expect_data {
       Some(SerializedPayload::from_bytes(
-        &buffer[cursor.position() as usize..],
+        &buffer.slice(cursor.position() as usize..),
       )?)
     } else {
       None
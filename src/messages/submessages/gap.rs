@@ -1,7 +1,14 @@
-use crate::structure::guid::EntityId;
+use crate::{
+  serialization::SubMessage, serialization::SubmessageBody, structure::guid::EntityId,
+  messages::submessages::submessages::SubmessageHeader,
+};
 use crate::structure::sequence_number::{SequenceNumber, SequenceNumberSet};
+use enumflags2::BitFlags;
+use log::error;
 use speedy::{Readable, Writable};
 
+use super::{submessage::EntitySubmessage, submessage_flag::GAP_Flags, submessage_kind::SubmessageKind};
+
 /// This Submessage is sent from an RTPS Writer to an RTPS Reader and
 /// indicates to the RTPS Reader that a range of sequence numbers
 /// is no longer relevant. The set may be a contiguous range of
@@ -28,6 +35,81 @@ pub struct Gap {
   pub gap_list: SequenceNumberSet,
 }
 
+impl Gap {
+  /// Gaps covering the given sequence numbers, which must be sorted and unique.
+  /// Consecutive numbers go to the range of a Gap and the rest to its list, so
+  /// that a new Gap is needed only when the list would grow beyond 256 numbers.
+  pub fn covering(
+    reader_id: EntityId,
+    writer_id: EntityId,
+    sequence_numbers: &[SequenceNumber],
+  ) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+    let mut rest = sequence_numbers;
+    while let Some(&gap_start) = rest.first() {
+      // the range gap_start <= sn < base
+      let mut range_len = 1;
+      while range_len < rest.len()
+        && rest[range_len] == gap_start + SequenceNumber::from(range_len as i64)
+      {
+        range_len += 1;
+      }
+      let mut gap_list = SequenceNumberSet::new(gap_start + SequenceNumber::from(range_len as i64));
+      let mut used = range_len;
+      while used < rest.len() && gap_list.insert(rest[used]) {
+        used += 1;
+      }
+      gaps.push(Gap {
+        reader_id,
+        writer_id,
+        gap_start,
+        gap_list,
+      });
+      rest = &rest[used..];
+    }
+    gaps
+  }
+
+  pub fn create_submessage(self, flags: BitFlags<GAP_Flags>) -> Option<SubMessage> {
+    let submessage_len = match self.write_to_vec() {
+      Ok(bytes) => bytes.len() as u16,
+      Err(e) => {
+        error!("Writer couldn't write gap to bytes. Error: {}", e);
+        return None;
+      }
+    };
+
+    Some(SubMessage {
+      header: SubmessageHeader {
+        kind: SubmessageKind::GAP,
+        flags: flags.bits(),
+        content_length: submessage_len,
+      },
+      body: SubmessageBody::Entity(EntitySubmessage::Gap(self, flags)),
+    })
+  }
+
+  /// Whether the Gap is valid (RTPS spec 8.3.7.4.3): gapStart is at least 1 and
+  /// gapList is a valid set.
+  pub fn is_valid(&self) -> bool {
+    self.gap_start >= SequenceNumber::from(1) && self.gap_list.is_valid()
+  }
+
+  /// All sequence numbers this Gap marks irrelevant.
+  pub fn irrelevant_sequence_numbers(&self) -> Vec<SequenceNumber> {
+    let mut irrelevant = Vec::new();
+    let mut sn = self.gap_start;
+    while sn < self.gap_list.base {
+      irrelevant.push(sn);
+      sn = sn + SequenceNumber::from(1);
+    }
+    for offset in self.gap_list.set.iter() {
+      irrelevant.push(self.gap_list.base + SequenceNumber::from(offset as i64));
+    }
+    irrelevant
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -56,4 +138,46 @@ mod tests {
             0x00, 0x00, 0x00, 0x07,
             0x00, 0x00, 0x00, 0x00]
   });
+
+  #[test]
+  fn gap_covering() {
+    let sn = |v: i64| SequenceNumber::from(v);
+    let reader_id = EntityId::ENTITYID_UNKNOWN;
+    let writer_id = EntityId::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_WRITER;
+
+    let mut seqnums: Vec<SequenceNumber> = (3..=5).chain(vec![8, 10]).map(sn).collect();
+    // beyond the reach of the first list
+    seqnums.push(sn(400));
+    let gaps = Gap::covering(reader_id, writer_id, &seqnums);
+
+    assert_eq!(gaps.len(), 2);
+    assert_eq!(gaps[0].gap_start, sn(3));
+    assert_eq!(gaps[0].gap_list.base, sn(6));
+    assert_eq!(gaps[1].gap_start, sn(400));
+    let covered: Vec<SequenceNumber> = gaps
+      .iter()
+      .flat_map(|gap| gap.irrelevant_sequence_numbers())
+      .collect();
+    assert_eq!(covered, seqnums);
+
+    assert!(Gap::covering(reader_id, writer_id, &[]).is_empty());
+  }
+
+  #[test]
+  fn gap_validity() {
+    let gap = |start: i64, base: i64| Gap {
+      reader_id: EntityId::ENTITYID_UNKNOWN,
+      writer_id: EntityId::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_WRITER,
+      gap_start: SequenceNumber::from(start),
+      gap_list: SequenceNumberSet::new(SequenceNumber::from(base)),
+    };
+    assert!(gap(1, 1).is_valid());
+    assert!(gap(1, std::i64::MAX).is_valid());
+    assert!(!gap(0, 5).is_valid());
+    assert!(!gap(3, 0).is_valid());
+
+    let mut too_long = gap(1, 10);
+    too_long.gap_list.set.insert(300);
+    assert!(!too_long.is_valid());
+  }
 }
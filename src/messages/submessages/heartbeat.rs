@@ -47,6 +47,15 @@ pub struct Heartbeat {
 }
 
 impl Heartbeat {
+  /// Whether the Heartbeat is valid (RTPS spec 8.3.7.5.3): firstSN is at least 1,
+  /// lastSN at least 0, and lastSN at least firstSN - 1. A Heartbeat without
+  /// changes has lastSN = firstSN - 1.
+  pub fn is_valid(&self) -> bool {
+    let first = i64::from(self.first_sn);
+    let last = i64::from(self.last_sn);
+    first >= 1 && last >= 0 && last >= first - 1
+  }
+
   pub fn create_submessage(self, flags: BitFlags<HEARTBEAT_Flags>) -> Option<SubMessage> {
     let submessage_len = match self.write_to_vec() {
       Ok(bytes) => bytes.len() as u16,
@@ -96,4 +105,24 @@ mod tests {
             0x00, 0x00, 0x00, 0x07,
             0x00, 0x00, 0x00, 0x09]
   });
+
+  #[test]
+  fn heartbeat_validity() {
+    let heartbeat = |first: i64, last: i64| Heartbeat {
+      reader_id: EntityId::ENTITYID_UNKNOWN,
+      writer_id: EntityId::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_WRITER,
+      first_sn: SequenceNumber::from(first),
+      last_sn: SequenceNumber::from(last),
+      count: 1,
+    };
+    assert!(heartbeat(1, 0).is_valid());
+    assert!(heartbeat(1, 1).is_valid());
+    assert!(heartbeat(1, std::i64::MAX).is_valid());
+    assert!(heartbeat(std::i64::MAX, std::i64::MAX).is_valid());
+
+    assert!(!heartbeat(0, 1).is_valid());
+    assert!(!heartbeat(5, 3).is_valid());
+    assert!(!heartbeat(std::i64::MIN, std::i64::MAX).is_valid());
+    assert!(!heartbeat(1, std::i64::MIN).is_valid());
+  }
 }
@@ -1,5 +1,16 @@
+use crate::{
+  serialization::SubMessage, serialization::SubmessageBody,
+  messages::submessages::submessages::SubmessageHeader,
+};
 use crate::structure::time::Timestamp;
-use speedy::{Readable, Writable};
+use enumflags2::BitFlags;
+use speedy::{Endianness, Readable, Writable};
+
+use super::{
+  submessage::InterpreterSubmessage,
+  submessage_flag::{FromEndianness, INFOTIMESTAMP_Flags},
+  submessage_kind::SubmessageKind,
+};
 
 /// This message modifies the logical source of the Submessages
 /// that follow.
@@ -8,6 +19,50 @@ pub struct InfoTimestamp {
   /// Contains the timestamp that should be used to interpret the
   /// subsequent Submessages
   ///
-  /// Present only if the InvalidateFlag is not set in the header.
+  /// Present only if the InvalidateFlag is not set in the header. It is
+  /// TIME_INVALID if the flag is set, and then it is neither read nor written.
   pub timestamp: Timestamp,
 }
+
+impl InfoTimestamp {
+  /// INFO_TS that gives `timestamp` to the Submessages that follow. Without a
+  /// timestamp the InvalidateFlag is set, and the following Submessages have none.
+  pub fn create_submessage(timestamp: Option<Timestamp>, endianness: Endianness) -> SubMessage {
+    let mut flags = BitFlags::<INFOTIMESTAMP_Flags>::from_endianness(endianness);
+    if timestamp.is_none() {
+      flags.insert(INFOTIMESTAMP_Flags::Invalidate);
+    }
+    SubMessage {
+      header: SubmessageHeader {
+        kind: SubmessageKind::INFO_TS,
+        flags: flags.bits(),
+        content_length: if timestamp.is_some() { 8 } else { 0 },
+      },
+      body: SubmessageBody::Interpreter(InterpreterSubmessage::InfoTimestamp(
+        InfoTimestamp {
+          timestamp: timestamp.unwrap_or(Timestamp::TIME_INVALID),
+        },
+        flags,
+      )),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn info_ts_invalidate_has_no_timestamp() {
+    let with_ts =
+      InfoTimestamp::create_submessage(Some(Timestamp::now()), Endianness::LittleEndian);
+    assert_eq!(with_ts.write_to_vec().unwrap().len(), 4 + 8);
+
+    let invalidate = InfoTimestamp::create_submessage(None, Endianness::LittleEndian);
+    // only the submessage header, with the Invalidate and Endianness flags
+    assert_eq!(
+      invalidate.write_to_vec().unwrap(),
+      vec![0x09, 0x03, 0x00, 0x00]
+    );
+  }
+}
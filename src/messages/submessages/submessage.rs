@@ -27,6 +27,19 @@ pub enum EntitySubmessage {
   NackFrag(NackFrag, BitFlags<NACKFRAG_Flags>),
 }
 
+impl EntitySubmessage {
+  /// Whether the sequence numbers of the submessage are valid. Invalid submessages
+  /// are to be ignored.
+  pub fn is_valid(&self) -> bool {
+    match self {
+      EntitySubmessage::AckNack(acknack, _) => acknack.is_valid(),
+      EntitySubmessage::Gap(gap, _) => gap.is_valid(),
+      EntitySubmessage::Heartbeat(heartbeat, _) => heartbeat.is_valid(),
+      _ => true,
+    }
+  }
+}
+
 // we must write this manually, because
 // 1) we cannot implement Writable for *Flags defined using enumflags2, as they are foreign types (coherence rules)
 // 2) Writer should not use any enum variant tag in this type, as we have SubmessageHeader already.
@@ -60,7 +73,14 @@ impl<C: Context> Writable<C> for InterpreterSubmessage {
       InterpreterSubmessage::InfoSource(s, _f) => writer.write_value(s),
       InterpreterSubmessage::InfoDestination(s, _f) => writer.write_value(s),
       InterpreterSubmessage::InfoReply(s, _f) => writer.write_value(s),
-      InterpreterSubmessage::InfoTimestamp(s, _f) => writer.write_value(s),
+      InterpreterSubmessage::InfoTimestamp(s, f) => {
+        // With the Invalidate flag the submessage has no timestamp.
+        if f.contains(INFOTIMESTAMP_Flags::Invalidate) {
+          Ok(())
+        } else {
+          writer.write_value(s)
+        }
+      }
     }
   }
 }
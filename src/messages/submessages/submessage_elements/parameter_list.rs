@@ -25,7 +25,6 @@ impl ParameterList {
 /// that follows and its length can be anything (as long as it is a multiple of
 /// 4)
 pub const PID_PAD: u16 = 0x00;
-const SENTINEL: u32 = 0x00000001;
 
 impl<C: Context> Writable<C> for ParameterList {
   #[inline]
@@ -34,7 +33,9 @@ impl<C: Context> Writable<C> for ParameterList {
       writer.write_value(param)?;
     }
 
-    writer.write_u32(SENTINEL)?;
+    // PID_SENTINEL with length zero, in the byte order of the list
+    writer.write_value(&ParameterId::PID_SENTINEL)?;
+    writer.write_u16(0)?;
 
     Ok(())
   }
@@ -48,12 +49,13 @@ impl<'a, C: Context> Readable<'a, C> for ParameterList {
     // loop ends in failure to read something or catching sentinel
     loop {
       let parameter_id = ParameterId::read_from(reader)?;
+      let length = u16::read_from(reader)?;
+      // the length of the sentinel is ignored, but it is read so that what
+      // follows the list, such as the payload of a DATA, starts where it should
       if parameter_id == ParameterId::PID_SENTINEL {
         return Ok(parameters);
       }
 
-      let length = u16::read_from(reader)?;
-
       let parameter = Parameter {
         parameter_id,
         value: reader.read_vec(length as usize)?,
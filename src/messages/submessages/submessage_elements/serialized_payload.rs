@@ -1,7 +1,7 @@
 use speedy::{Writable, Writer, Context};
 use std::io;
-use std::io::Read;
 use byteorder::{ReadBytesExt, BigEndian};
+use bytes::Bytes;
 
 use num_enum::{TryFromPrimitive, IntoPrimitive};
 
@@ -13,13 +13,17 @@ pub enum RepresentationIdentifier {
   CDR_LE = 1,
   PL_CDR_BE = 2,
   PL_CDR_LE = 3,
-  CDR2_BE = 0x0010,
-  CDR2_LE = 0x0011,
-  PL_CDR2_BE = 0x0012,
-  PL_CDR2_LE = 0x0013,
-  D_CDR_BE = 0x0014,
-  D_CDR_LE = 0x0015,
+  // XCDR2 values are from DDS-XTypes v1.3 Section 7.6.3.1.2, Table 60.
+  // RTPS v2.3 lists older values (0x0010-0x0015), which no implementation uses.
+  CDR2_BE = 0x0006,
+  CDR2_LE = 0x0007,
+  D_CDR2_BE = 0x0008,
+  D_CDR2_LE = 0x0009,
+  PL_CDR2_BE = 0x000a,
+  PL_CDR2_LE = 0x000b,
   XML = 0x0004,
+  // Vendor-specific, not defined by any spec. Written by JsonSerializerAdapter.
+  JSON = 0x8001,
 
   INVALID = 0xffff,
 }
@@ -40,11 +44,14 @@ impl RepresentationIdentifier {
 /// representation_options that it requires." and "The [2.3] version of the protocol
 /// does not use the representation_options: The sender shall set the representation_options
 /// to zero. The receiver shall ignore the value of the representation_options."
+///
+/// The value of a received payload is a view into the buffer of the datagram it
+/// arrived in, so cloning it, e.g. into the DDSCache, does not copy the data.
 #[derive(Debug, PartialEq, Clone)]
 pub struct SerializedPayload {
   pub representation_identifier: u16, // This is u16, not RepresentationIdentifier, because we need to be able to deserialize whatever is on the wire
   pub representation_options: [u8; 2], // Not used. Send as zero, ignore on receive.
-  pub value: Vec<u8>,
+  pub value: Bytes,
 }
 
 impl SerializedPayload {
@@ -52,17 +59,17 @@ impl SerializedPayload {
     SerializedPayload {
       representation_identifier: rep_id as u16,
       representation_options: [0, 0],
-      value: payload,
+      value: Bytes::from(payload),
     }
   }
 
   // Implement deserialization here, because Speedy just makes it difficult.
-  pub fn from_bytes(bytes: &[u8]) -> io::Result<SerializedPayload> {
-    let mut reader = io::Cursor::new(bytes);
+  // The value shares the buffer of bytes.
+  pub fn from_bytes(bytes: &Bytes) -> io::Result<SerializedPayload> {
+    let mut reader = io::Cursor::new(&bytes[..]);
     let representation_identifier = reader.read_u16::<BigEndian>()?;
     let representation_options = [reader.read_u8()?, reader.read_u8()?];
-    let mut value = Vec::with_capacity(bytes.len() - 4); // still length == 0
-    reader.read_to_end(&mut value)?;
+    let value = bytes.slice(4..);
 
     Ok(SerializedPayload {
       representation_identifier,
@@ -22,6 +22,11 @@ impl SubmessageKind {
   pub const HEARTBEAT_FRAG: SubmessageKind = SubmessageKind { value: 0x13 };
   pub const DATA: SubmessageKind = SubmessageKind { value: 0x15 };
   pub const DATA_FRAG: SubmessageKind = SubmessageKind { value: 0x16 };
+
+  // RTPS spec 9.4.5.1.1: kinds 0x80 to 0xff are for vendors to define.
+  pub fn is_vendor_specific(&self) -> bool {
+    self.value >= 0x80
+  }
 }
 
 impl Debug for SubmessageKind {
@@ -19,6 +19,41 @@ impl VendorId {
 
   pub const THIS_IMPLEMENTATION: VendorId = VendorId::ATOSTEK;
 
+  pub const RTI_CONNEXT: VendorId = VendorId {
+    vendorId: [0x01, 0x01],
+  };
+
+  pub const OCI_OPENDDS: VendorId = VendorId {
+    vendorId: [0x01, 0x03],
+  };
+
+  pub const EPROSIMA_FASTDDS: VendorId = VendorId {
+    vendorId: [0x01, 0x0f],
+  };
+
+  pub const ECLIPSE_CYCLONEDDS: VendorId = VendorId {
+    vendorId: [0x01, 0x10],
+  };
+
+  /// Whether messages of this vendor use the parameter id in the meaning RustDDS
+  /// gives it. Vendor specific ids are defined by each vendor for itself, and
+  /// only some of them are known to be shared.
+  pub fn understands_parameter(&self, parameter_id: ParameterId) -> bool {
+    if !parameter_id.is_vendor_specific() {
+      return true;
+    }
+    match parameter_id {
+      ParameterId::PID_CUSTOM_RELATED_SAMPLE_IDENTITY => [
+        VendorId::THIS_IMPLEMENTATION,
+        VendorId::RTI_CONNEXT,
+        VendorId::EPROSIMA_FASTDDS,
+        VendorId::ECLIPSE_CYCLONEDDS,
+      ]
+      .contains(self),
+      _ => *self == VendorId::THIS_IMPLEMENTATION,
+    }
+  }
+
 }
 
 impl Default for VendorId {
@@ -83,6 +118,17 @@ mod tests {
     );
   }
 
+  #[test]
+  fn vendor_specific_parameters() {
+    let related = ParameterId::PID_CUSTOM_RELATED_SAMPLE_IDENTITY;
+    assert!(VendorId::ECLIPSE_CYCLONEDDS.understands_parameter(related));
+    assert!(VendorId::THIS_IMPLEMENTATION.understands_parameter(related));
+    assert!(!VendorId::OCI_OPENDDS.understands_parameter(related));
+    assert!(!VendorId::VENDOR_UNKNOWN.understands_parameter(related));
+    // Standard parameters mean the same for everyone
+    assert!(VendorId::OCI_OPENDDS.understands_parameter(ParameterId::PID_RELATED_SAMPLE_IDENTITY));
+  }
+
   serialization_test!( type = VendorId,
   {
       vendor_unknown,
@@ -0,0 +1,626 @@
+use log::error;
+
+use std::{
+  fmt,
+  fs::{self, File},
+  io::{self, BufWriter, Write},
+  net::{IpAddr, Ipv4Addr, SocketAddr},
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    mpsc, Arc, Mutex,
+  },
+  thread::{self, JoinHandle},
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+  dds::values::result::{Error, Result},
+  network::util::tcp_locator_parts,
+  structure::locator::{Locator, LocatorKind},
+};
+
+// Datagrams waiting to be written. When full, new ones are dropped and counted.
+const QUEUE_LENGTH: usize = 4096;
+// How long written packets may stay in the file buffer
+const FLUSH_PERIOD: Duration = Duration::from_secs(1);
+
+// pcapng block types and options
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 1;
+const ENHANCED_PACKET_BLOCK: u32 = 6;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const OPT_END: u16 = 0;
+const IF_NAME: u16 = 2;
+const EPB_FLAGS: u16 = 2;
+// Packets begin with an IPv4 or IPv6 header
+const LINKTYPE_RAW: u16 = 101;
+
+/// Where [DomainParticipant::start_capture](struct.DomainParticipant.html#method.start_capture)
+/// writes the RTPS traffic of the participant, and how much of it is kept.
+///
+/// The file is in pcapng format, for Wireshark. Each datagram gets IP and UDP
+/// headers with its source and destination, so that the RTPS dissector decodes it.
+/// Messages over shared memory and TCP appear as UDP too, on interfaces named "shm"
+/// and "tcp", with unspecified addresses where there are none to tell.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::CaptureConfig;
+/// let config = CaptureConfig {
+///   max_file_size: 10 * 1024 * 1024,
+///   ..CaptureConfig::new("/tmp/rtps.pcapng")
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureConfig {
+  /// The file written to. Older files are renamed by appending .1, .2 and so on.
+  pub path: PathBuf,
+  /// When the file would grow larger than this, it is renamed and a new one begun.
+  /// Zero means no limit.
+  pub max_file_size: u64,
+  /// How many renamed files are kept. Older ones are deleted.
+  pub max_files: usize,
+}
+
+impl CaptureConfig {
+  /// Files of at most 100 MB, of which 4 are kept besides the one being written.
+  pub fn new<P: Into<PathBuf>>(path: P) -> CaptureConfig {
+    CaptureConfig {
+      path: path.into(),
+      max_file_size: 100 * 1024 * 1024,
+      max_files: 4,
+    }
+  }
+
+  fn rotated_path(&self, index: usize) -> PathBuf {
+    let mut path = self.path.clone().into_os_string();
+    path.push(format!(".{}", index));
+    PathBuf::from(path)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+  Received,
+  Sent,
+}
+
+// Each transport is an interface of its own in the file, in this order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Transport {
+  Udp,
+  Tcp,
+  Shm,
+}
+
+impl Transport {
+  const ALL: [Transport; 3] = [Transport::Udp, Transport::Tcp, Transport::Shm];
+
+  fn name(self) -> &'static str {
+    match self {
+      Transport::Udp => "udp",
+      Transport::Tcp => "tcp",
+      Transport::Shm => "shm",
+    }
+  }
+}
+
+struct Packet {
+  time: SystemTime,
+  direction: Direction,
+  transport: Transport,
+  source: SocketAddr,
+  destination: SocketAddr,
+  data: Vec<u8>,
+}
+
+// Shared by a participant and all its sockets. Cheap to check while nothing is
+// captured.
+#[derive(Clone, Default)]
+pub(crate) struct PacketCapture {
+  inner: Arc<CaptureState>,
+}
+
+#[derive(Default)]
+struct CaptureState {
+  active: AtomicBool,
+  dropped: AtomicU64,
+  sender: Mutex<Option<mpsc::SyncSender<Packet>>>,
+  writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl PacketCapture {
+  // Starts writing to the file of `config`, after stopping the capture that is
+  // going on. Returns BadParameter if the file cannot be created.
+  pub fn start(&self, config: CaptureConfig) -> Result<()> {
+    self.stop();
+    let path = config.path.clone();
+    let file = match CaptureFile::create(config) {
+      Ok(f) => f,
+      Err(e) => {
+        return Err(Error::bad_parameter(format!(
+          "cannot create capture file {}: {}",
+          path.display(),
+          e
+        )))
+      }
+    };
+    let (sender, receiver) = mpsc::sync_channel(QUEUE_LENGTH);
+    let writer = match thread::Builder::new()
+      .name("RTPS capture".to_string())
+      .spawn(move || write_packets(file, receiver))
+    {
+      Ok(w) => w,
+      Err(e) => return Err(Error::io("failed to start capture thread", e)),
+    };
+    *self.lock_sender() = Some(sender);
+    if let Ok(mut w) = self.inner.writer.lock() {
+      *w = Some(writer);
+    }
+    self.inner.active.store(true, Ordering::Release);
+    Ok(())
+  }
+
+  // Stops capturing. Returns when the queued packets are in the file.
+  pub fn stop(&self) {
+    self.inner.active.store(false, Ordering::Release);
+    self.lock_sender().take();
+    let writer = match self.inner.writer.lock() {
+      Ok(mut w) => w.take(),
+      Err(_) => None,
+    };
+    if let Some(writer) = writer {
+      if writer.join().is_err() {
+        error!("Capture thread panicked.");
+      }
+    }
+  }
+
+  pub fn is_active(&self) -> bool {
+    self.inner.active.load(Ordering::Acquire)
+  }
+
+  // Packets not written because the writer thread could not keep up
+  pub fn dropped(&self) -> u64 {
+    self.inner.dropped.load(Ordering::Relaxed)
+  }
+
+  pub fn sent(&self, transport: Transport, data: &[u8], from: SocketAddr, to: SocketAddr) {
+    self.capture(Direction::Sent, transport, data, from, to);
+  }
+
+  pub fn received(&self, transport: Transport, data: &[u8], from: SocketAddr, to: SocketAddr) {
+    self.capture(Direction::Received, transport, data, from, to);
+  }
+
+  // A message given to the TCP transport, once for each TCP locator
+  pub fn sent_over_tcp(&self, data: &[u8], locators: &[Locator]) {
+    if !self.is_active() {
+      return;
+    }
+    for locator in locators
+      .iter()
+      .filter(|l| l.kind == LocatorKind::LOCATOR_KIND_TCPv4)
+    {
+      let (_, address) = tcp_locator_parts(locator);
+      self.sent(Transport::Tcp, data, no_address(), SocketAddr::V4(address));
+    }
+  }
+
+  // A message written to our shared memory segment
+  #[cfg(feature = "shm")]
+  pub fn sent_over_shm(&self, data: &[u8], locators: &[Locator]) {
+    if locators
+      .iter()
+      .any(|l| l.kind == LocatorKind::LOCATOR_KIND_SHM)
+    {
+      self.sent(Transport::Shm, data, no_address(), no_address());
+    }
+  }
+
+  fn capture(
+    &self,
+    direction: Direction,
+    transport: Transport,
+    data: &[u8],
+    source: SocketAddr,
+    destination: SocketAddr,
+  ) {
+    if !self.is_active() {
+      return;
+    }
+    let packet = Packet {
+      time: SystemTime::now(),
+      direction,
+      transport,
+      source,
+      destination,
+      data: data.to_vec(),
+    };
+    let sent = match self.lock_sender().as_ref() {
+      Some(sender) => sender.try_send(packet).is_ok(),
+      None => false,
+    };
+    if !sent {
+      self.inner.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+  }
+
+  fn lock_sender(&self) -> std::sync::MutexGuard<'_, Option<mpsc::SyncSender<Packet>>> {
+    match self.inner.sender.lock() {
+      Ok(s) => s,
+      Err(e) => e.into_inner(),
+    }
+  }
+}
+
+impl fmt::Debug for PacketCapture {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("PacketCapture")
+      .field("active", &self.is_active())
+      .finish()
+  }
+}
+
+// Address of traffic that does not go over IP
+pub(crate) fn no_address() -> SocketAddr {
+  SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+}
+
+fn write_packets(mut file: CaptureFile, packets: mpsc::Receiver<Packet>) {
+  loop {
+    let result = match packets.recv_timeout(FLUSH_PERIOD) {
+      Ok(packet) => file.write(&packet),
+      Err(mpsc::RecvTimeoutError::Timeout) => file.flush(),
+      Err(mpsc::RecvTimeoutError::Disconnected) => break,
+    };
+    if let Err(e) = result {
+      // The queue fills up, and the rest is counted as dropped
+      error!(
+        "Writing capture file {:?} failed. {:?}",
+        file.config.path, e
+      );
+      return;
+    }
+  }
+  if let Err(e) = file.flush() {
+    error!(
+      "Writing capture file {:?} failed. {:?}",
+      file.config.path, e
+    );
+  }
+}
+
+struct CaptureFile {
+  config: CaptureConfig,
+  out: BufWriter<File>,
+  size: u64,
+  header_size: u64,
+  flushed: Instant,
+}
+
+impl CaptureFile {
+  fn create(config: CaptureConfig) -> io::Result<CaptureFile> {
+    let out = BufWriter::new(File::create(&config.path)?);
+    let mut file = CaptureFile {
+      config,
+      out,
+      size: 0,
+      header_size: 0,
+      flushed: Instant::now(),
+    };
+    file.write_header()?;
+    Ok(file)
+  }
+
+  fn write_header(&mut self) -> io::Result<()> {
+    let mut header = section_header_block();
+    for transport in Transport::ALL.iter() {
+      header.extend(interface_description_block(transport.name()));
+    }
+    self.out.write_all(&header)?;
+    self.size = header.len() as u64;
+    self.header_size = self.size;
+    Ok(())
+  }
+
+  fn write(&mut self, packet: &Packet) -> io::Result<()> {
+    let block = enhanced_packet_block(packet);
+    let limit = self.config.max_file_size;
+    if limit > 0 && self.size > self.header_size && self.size + block.len() as u64 > limit {
+      self.rotate()?;
+    }
+    self.out.write_all(&block)?;
+    self.size += block.len() as u64;
+    if self.flushed.elapsed() >= FLUSH_PERIOD {
+      self.flush()?;
+    }
+    Ok(())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.flushed = Instant::now();
+    self.out.flush()
+  }
+
+  // Renames the file to .1, the one that was .1 to .2 and so on, and begins a new one.
+  fn rotate(&mut self) -> io::Result<()> {
+    self.out.flush()?;
+    let keep = self.config.max_files;
+    if keep == 0 {
+      self.out = BufWriter::new(File::create(&self.config.path)?);
+      return self.write_header();
+    }
+    remove_if_exists(&self.config.rotated_path(keep))?;
+    for index in (1..keep).rev() {
+      let from = self.config.rotated_path(index);
+      if from.exists() {
+        fs::rename(&from, self.config.rotated_path(index + 1))?;
+      }
+    }
+    fs::rename(&self.config.path, self.config.rotated_path(1))?;
+    self.out = BufWriter::new(File::create(&self.config.path)?);
+    self.write_header()
+  }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+  match fs::remove_file(path) {
+    Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+    _ => Ok(()),
+  }
+}
+
+// Block type, length, body and length again. The body is padded to 32 bits.
+fn block(block_type: u32, mut body: Vec<u8>) -> Vec<u8> {
+  pad(&mut body);
+  let length = (body.len() + 12) as u32;
+  let mut block = Vec::with_capacity(length as usize);
+  block.extend_from_slice(&block_type.to_le_bytes());
+  block.extend_from_slice(&length.to_le_bytes());
+  block.extend(body);
+  block.extend_from_slice(&length.to_le_bytes());
+  block
+}
+
+fn pad(bytes: &mut Vec<u8>) {
+  while bytes.len() % 4 != 0 {
+    bytes.push(0);
+  }
+}
+
+fn option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+  body.extend_from_slice(&code.to_le_bytes());
+  body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+  body.extend_from_slice(value);
+  pad(body);
+}
+
+fn section_header_block() -> Vec<u8> {
+  let mut body = Vec::new();
+  body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+  body.extend_from_slice(&1u16.to_le_bytes()); // major version
+  body.extend_from_slice(&0u16.to_le_bytes()); // minor version
+  body.extend_from_slice(&(-1i64).to_le_bytes()); // section length not known
+  block(SECTION_HEADER_BLOCK, body)
+}
+
+// Timestamps are in microseconds, the default resolution.
+fn interface_description_block(name: &str) -> Vec<u8> {
+  let mut body = Vec::new();
+  body.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+  body.extend_from_slice(&0u16.to_le_bytes()); // reserved
+  body.extend_from_slice(&0u32.to_le_bytes()); // no snap length
+  option(&mut body, IF_NAME, name.as_bytes());
+  option(&mut body, OPT_END, &[]);
+  block(INTERFACE_DESCRIPTION_BLOCK, body)
+}
+
+fn enhanced_packet_block(packet: &Packet) -> Vec<u8> {
+  let data = ip_udp_packet(packet.source, packet.destination, &packet.data);
+  let micros = packet
+    .time
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_micros() as u64)
+    .unwrap_or(0);
+  let interface = Transport::ALL
+    .iter()
+    .position(|t| *t == packet.transport)
+    .unwrap_or(0) as u32;
+  let flags: u32 = match packet.direction {
+    Direction::Received => 1,
+    Direction::Sent => 2,
+  };
+  let mut body = Vec::with_capacity(data.len() + 40);
+  body.extend_from_slice(&interface.to_le_bytes());
+  body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+  body.extend_from_slice(&(micros as u32).to_le_bytes());
+  body.extend_from_slice(&(data.len() as u32).to_le_bytes()); // captured length
+  body.extend_from_slice(&(data.len() as u32).to_le_bytes()); // original length
+  body.extend(data);
+  pad(&mut body);
+  option(&mut body, EPB_FLAGS, &flags.to_le_bytes());
+  option(&mut body, OPT_END, &[]);
+  block(ENHANCED_PACKET_BLOCK, body)
+}
+
+// Wraps the payload in IP and UDP headers. The UDP checksum is left out, which
+// IPv4 allows and Wireshark does not check by default.
+fn ip_udp_packet(source: SocketAddr, destination: SocketAddr, payload: &[u8]) -> Vec<u8> {
+  let udp_length = (payload.len() + 8).min(u16::MAX as usize) as u16;
+  let mut udp = Vec::with_capacity(8);
+  udp.extend_from_slice(&source.port().to_be_bytes());
+  udp.extend_from_slice(&destination.port().to_be_bytes());
+  udp.extend_from_slice(&udp_length.to_be_bytes());
+  udp.extend_from_slice(&0u16.to_be_bytes());
+
+  let mut packet = match (source.ip(), destination.ip()) {
+    (IpAddr::V4(s), IpAddr::V4(d)) => {
+      let total_length = (udp_length as usize + 20).min(u16::MAX as usize) as u16;
+      let mut header = vec![0x45, 0];
+      header.extend_from_slice(&total_length.to_be_bytes());
+      header.extend_from_slice(&[0, 0, 0x40, 0, 64, 17, 0, 0]); // don't fragment, UDP
+      header.extend_from_slice(&s.octets());
+      header.extend_from_slice(&d.octets());
+      let checksum = ipv4_checksum(&header);
+      header[10..12].copy_from_slice(&checksum.to_be_bytes());
+      header
+    }
+    (s, d) => {
+      let mut header = vec![0x60, 0, 0, 0];
+      header.extend_from_slice(&udp_length.to_be_bytes());
+      header.extend_from_slice(&[17, 64]); // UDP, hop limit
+      header.extend_from_slice(&ipv6_octets(s));
+      header.extend_from_slice(&ipv6_octets(d));
+      header
+    }
+  };
+  packet.extend(udp);
+  packet.extend_from_slice(payload);
+  packet
+}
+
+fn ipv6_octets(ip: IpAddr) -> [u8; 16] {
+  match ip {
+    IpAddr::V4(ip4) => ip4.to_ipv6_mapped().octets(),
+    IpAddr::V6(ip6) => ip6.octets(),
+  }
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+  let mut sum: u32 = header
+    .chunks(2)
+    .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+    .sum();
+  while sum > 0xFFFF {
+    sum = (sum & 0xFFFF) + (sum >> 16);
+  }
+  !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::thread;
+
+  use super::*;
+  use crate::dds::DomainParticipant;
+
+  fn address(a: &str) -> SocketAddr {
+    a.parse().unwrap()
+  }
+
+  // (block type, block) of each block in a pcapng file
+  fn blocks(bytes: &[u8]) -> Vec<(u32, Vec<u8>)> {
+    let mut blocks = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+      let block_type = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]);
+      let length = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+      assert_eq!(length % 4, 0);
+      assert_eq!(&rest[length - 4..length], &rest[4..8]);
+      blocks.push((block_type, rest[..length].to_vec()));
+      rest = &rest[length..];
+    }
+    blocks
+  }
+
+  #[test]
+  fn capture_writes_pcapng() {
+    let dir = std::env::temp_dir().join(format!("rustdds_capture_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let config = CaptureConfig {
+      max_file_size: 1000,
+      max_files: 1,
+      ..CaptureConfig::new(dir.join("test.pcapng"))
+    };
+    let capture = PacketCapture::default();
+    let (from, to) = (address("10.0.0.1:7410"), address("239.255.0.1:7400"));
+    capture.sent(Transport::Udp, b"not captured", from, to);
+    assert!(!capture.is_active());
+
+    capture.start(config.clone()).unwrap();
+    assert!(capture.is_active());
+    capture.sent(Transport::Udp, b"RTPS first", from, to);
+    capture.received(Transport::Shm, b"RTPS second", to, from);
+    capture.stop();
+    capture.sent(Transport::Udp, b"not captured", from, to);
+
+    let bytes = fs::read(&config.path).unwrap();
+    let blocks = blocks(&bytes);
+    let types: Vec<u32> = blocks.iter().map(|(t, _)| *t).collect();
+    assert_eq!(types, vec![SECTION_HEADER_BLOCK, 1, 1, 1, 6, 6]);
+    let (_, sent) = &blocks[4];
+    // interface 0 is UDP
+    assert_eq!(&sent[8..12], &0u32.to_le_bytes());
+    let ip = &sent[28..];
+    assert_eq!(ip[0], 0x45);
+    assert_eq!(ipv4_checksum(&ip[..20]), 0);
+    assert_eq!(&ip[12..16], &[10, 0, 0, 1]);
+    assert_eq!(&ip[16..20], &[239, 255, 0, 1]);
+    assert_eq!(&ip[20..22], &7410u16.to_be_bytes());
+    assert_eq!(&ip[28..38], b"RTPS first");
+    let (_, received) = &blocks[5];
+    assert_eq!(&received[8..12], &2u32.to_le_bytes());
+    // inbound
+    assert_eq!(
+      &received[received.len() - 12..received.len() - 8],
+      &1u32.to_le_bytes()
+    );
+    assert_eq!(capture.dropped(), 0);
+
+    // Rotation keeps one older file
+    capture.start(config.clone()).unwrap();
+    for _ in 0..30 {
+      capture.sent(Transport::Udp, &[0; 100], from, to);
+    }
+    capture.stop();
+    let current = fs::metadata(&config.path).unwrap().len();
+    let older = fs::metadata(config.rotated_path(1)).unwrap().len();
+    assert!(current <= 1000 && older <= 1000);
+    assert!(!config.rotated_path(2).exists());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn capture_to_bad_path_fails() {
+    let capture = PacketCapture::default();
+    let config = CaptureConfig::new("/nonexistent/directory/rtps.pcapng");
+    assert!(matches!(
+      capture.start(config),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(!capture.is_active());
+  }
+
+  #[test]
+  fn participant_captures_discovery_traffic() {
+    let path = std::env::temp_dir().join(format!("rustdds_dp_{}.pcapng", std::process::id()));
+    let dp_a = DomainParticipant::new(44).unwrap();
+    dp_a.start_capture(CaptureConfig::new(&path)).unwrap();
+    let dp_b = DomainParticipant::new(44).unwrap();
+    // SPDP both ways goes through the sockets of dp_a
+    for _ in 0..100 {
+      let a_found_b = dp_a.discovery_snapshot().participant_count > 0;
+      if a_found_b && dp_b.discovery_snapshot().participant_count > 0 {
+        break;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    dp_a.stop_capture();
+
+    let bytes = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let packets: Vec<Vec<u8>> = blocks(&bytes)
+      .into_iter()
+      .filter(|(block_type, _)| *block_type == ENHANCED_PACKET_BLOCK)
+      .map(|(_, block)| block)
+      .collect();
+    let direction = |block: &Vec<u8>| block[block.len() - 12];
+    assert!(packets.iter().any(|p| direction(p) == 1));
+    assert!(packets.iter().any(|p| direction(p) == 2));
+    // RTPS after the IPv4 and UDP headers
+    assert!(packets.iter().all(|p| &p[56..60] == b"RTPS"));
+    assert_eq!(dp_a.capture_dropped(), 0);
+  }
+}
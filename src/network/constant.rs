@@ -1,7 +1,10 @@
 use mio::Token;
 use mio_extras::channel as mio_channel;
 
-use crate::structure::guid::GUID;
+use crate::{
+  dds::qos::QosPolicyId,
+  structure::guid::{GuidPrefix, GUID},
+};
 
 pub const STOP_POLL_TOKEN: Token = Token(0);
 
@@ -35,6 +38,15 @@ pub const ACKNACK_MESSGAGE_TO_LOCAL_WRITER_TOKEN: Token = Token(20);
 pub const DISCOVERY_UPDATE_NOTIFICATION_TOKEN: Token = Token(21);
 pub const DISCOVERY_COMMAND_TOKEN: Token = Token(22);
 
+pub const ADD_READER_LISTENER_TOKEN: Token = Token(23);
+pub const SHM_DOORBELL_TOKEN: Token = Token(24);
+pub const TCP_LISTENER_TOKEN: Token = Token(25);
+pub const TCP_RECONNECT_TIMER_TOKEN: Token = Token(26);
+pub const NETWORK_INTERFACE_CHANGE_TOKEN: Token = Token(27);
+// TCP connections. Clear of the fixed tokens above and of entity tokens, which have
+// 15 digits.
+pub const TCP_CONNECTION_TOKENS: std::ops::Range<usize> = 1000..1_000_000_000;
+
 pub const DISCOVERY_PARTICIPANT_DATA_TOKEN: Token = Token(30);
 pub const DISCOVERY_PARTICIPANT_CLEANUP_TOKEN: Token = Token(31);
 pub const DISCOVERY_SEND_PARTICIPANT_INFO_TOKEN: Token = Token(32);
@@ -47,8 +59,11 @@ pub const DISCOVERY_TOPIC_CLEANUP_TOKEN: Token = Token(38);
 pub const DISCOVERY_SEND_TOPIC_INFO_TOKEN: Token = Token(39);
 pub const DISCOVERY_PARTICIPANT_MESSAGE_TOKEN: Token = Token(40);
 pub const DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN: Token = Token(41);
+pub const DISCOVERY_NEW_PARTICIPANT_BURST_TOKEN: Token = Token(42);
 
 pub const DPEV_ACKNACK_TIMER_TOKEN: Token = Token(50);
+pub const DPEV_CACHE_GC_TIMER_TOKEN: Token = Token(51);
+pub const DPEV_MULTICAST_TIMER_TOKEN: Token = Token(52);
 
 pub struct TokenReceiverPair<T> {
   pub token: Token,
@@ -83,8 +98,12 @@ pub fn get_user_traffic_unicast_port(domain_id: u16, participant_id: u16) -> u16
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TimerMessageType {
   writer_heartbeat,
-  writer_cache_cleaning,
+  writer_batch_flush,
+  writer_nack_response,
+  writer_flow_control,
   reader_deadline_missed_check,
+  reader_heartbeat_response,
+  reader_liveliness_check,
 }
 
 #[derive(Debug)]
@@ -93,4 +112,14 @@ pub enum DiscoveryNotificationType {
   WritersInfoUpdated { needs_new_cache_change: bool },
   TopicsInfoUpdated,
   AssertTopicLiveliness { writer_guid: GUID },
+  // A remote participant asserted the liveliness of its writers with a participant
+  // message, manually or automatically.
+  ParticipantLivelinessAsserted { guid_prefix: GuidPrefix, manual: bool },
+  ParticipantIgnored { guid_prefix: GuidPrefix },
+  // A local reader or writer cannot match a remote one, because of this policy.
+  QosMismatch {
+    local: GUID,
+    remote: GUID,
+    policy: QosPolicyId,
+  },
 }
@@ -0,0 +1,69 @@
+// Notifications of network interface changes (Linux).
+//
+// The kernel tells the subscribers of its routing netlink groups when an interface
+// goes up or down, or gains or loses an address. The participant then joins its
+// multicast groups again right away, instead of at its next periodic attempt. The
+// notifications themselves are not parsed: joining again is harmless, so any change
+// is reason enough.
+
+use log::debug;
+use nix::{
+  libc,
+  sys::socket::{self, AddressFamily, SockAddr, SockFlag, SockType},
+};
+
+use std::{
+  fs::File,
+  io::{self, Read},
+  os::unix::io::{AsRawFd, FromRawFd, RawFd},
+};
+
+use crate::network::util::nix_to_io_error;
+
+pub struct InterfaceMonitor {
+  // The netlink socket. A File closes it when dropped.
+  socket: File,
+}
+
+impl InterfaceMonitor {
+  pub fn new() -> io::Result<InterfaceMonitor> {
+    // Protocol 0 is NETLINK_ROUTE.
+    let fd = socket::socket(
+      AddressFamily::Netlink,
+      SockType::Raw,
+      SockFlag::SOCK_NONBLOCK | SockFlag::SOCK_CLOEXEC,
+      None,
+    )
+    .map_err(nix_to_io_error)?;
+    // closes the socket, if binding fails
+    let socket = unsafe { File::from_raw_fd(fd) };
+    let groups = libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR;
+    socket::bind(fd, &SockAddr::new_netlink(0, groups as u32)).map_err(nix_to_io_error)?;
+    Ok(InterfaceMonitor { socket })
+  }
+
+  // To register with mio
+  pub fn fd(&self) -> RawFd {
+    self.socket.as_raw_fd()
+  }
+
+  // Whether some interface changed since the last call. Reads all the notifications,
+  // as mio tells only about new ones.
+  pub fn changed(&mut self) -> bool {
+    let mut buffer = [0; 8192];
+    let mut changed = false;
+    loop {
+      match self.socket.read(&mut buffer) {
+        Ok(0) => return changed,
+        Ok(_) => changed = true,
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => return changed,
+        // Notifications were lost, as we did not read them fast enough.
+        Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => changed = true,
+        Err(e) => {
+          debug!("Cannot read network interface changes. {:?}", e);
+          return changed;
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,620 @@
+use log::info;
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+
+use crate::{
+  dds::values::result::{Error, Result},
+  discovery::discovery_config::{IpFamily, SocketConfig, TcpConfig, DEFAULT_MAX_MESSAGE_SIZE},
+  network::{
+    tcp::TcpTransport,
+    udp_listener::UDPListener,
+    udp_sender::UDPSender,
+    util::{
+      get_local_multicast_locators, get_local_unicast_socket_address, shm_locator, tcp_locator,
+      unspecified_address, MULTICAST_GROUPS_V6, MULTICAST_GROUP_V4,
+    },
+  },
+  structure::{
+    guid::{GuidPrefix, GUID},
+    locator::{Locator, LocatorList},
+  },
+};
+
+// Where a participant sends and receives: its IP versions, optionally the local
+// addresses it is restricted to, the options of its sockets, the largest message it
+// sends over UDP, whether it uses shared memory, TCP and UDP, and whether it is
+// passive. Resolved from DiscoveryConfig when the participant is created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LocalNetwork {
+  ip_families: Vec<IpFamily>,
+  // One address per family, preferred first. None if all interfaces are used.
+  addresses: Option<Vec<IpAddr>>,
+  // Interfaces for multicast. Unspecified and 0 let the OS choose.
+  multicast_interface_v4: Ipv4Addr,
+  multicast_interface_v6: u32,
+  socket_config: SocketConfig,
+  max_message_size: usize,
+  // Which /dev/shm we see, if the shared memory transport is in use.
+  shm_namespace: Option<u32>,
+  shm_segment_size: usize,
+  tcp: Option<TcpTransport>,
+  // Addresses announced for accepting TCP connections. Empty if we do not accept any.
+  tcp_addresses: Vec<SocketAddrV4>,
+  udp: bool,
+  // A passive participant only listens: its DataReaders send no AckNacks and its
+  // DataWriters send nothing.
+  passive: bool,
+}
+
+impl Default for LocalNetwork {
+  fn default() -> LocalNetwork {
+    LocalNetwork {
+      ip_families: vec![IpFamily::V4],
+      addresses: None,
+      multicast_interface_v4: Ipv4Addr::UNSPECIFIED,
+      multicast_interface_v6: 0,
+      socket_config: SocketConfig::default(),
+      max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+      shm_namespace: None,
+      shm_segment_size: 0,
+      tcp: None,
+      tcp_addresses: Vec::new(),
+      udp: true,
+      passive: false,
+    }
+  }
+}
+
+impl LocalNetwork {
+  // Each of `interfaces` is an interface name or an address range like 10.1.0.0/16.
+  // Empty uses all interfaces.
+  pub fn new(
+    ip_families: &[IpFamily],
+    interfaces: &[String],
+    socket_config: SocketConfig,
+  ) -> Result<LocalNetwork> {
+    let mut network = LocalNetwork {
+      ip_families: ip_families.to_vec(),
+      socket_config,
+      ..LocalNetwork::default()
+    };
+    if interfaces.is_empty() {
+      return Ok(network);
+    }
+
+    let local: Vec<(String, IpAddr)> = match get_if_addrs::get_if_addrs() {
+      Ok(ifs) => ifs.into_iter().map(|i| (i.name.clone(), i.ip())).collect(),
+      Err(e) => {
+        return Err(Error::bad_parameter(format!(
+          "cannot list network interfaces: {}",
+          e
+        )))
+      }
+    };
+    let selected = select_addresses(ip_families, interfaces, &local)?;
+
+    for (name, ip) in selected.iter() {
+      match ip {
+        IpAddr::V4(ip4) => network.multicast_interface_v4 = *ip4,
+        IpAddr::V6(_) => {
+          network.multicast_interface_v6 = match nix::net::if_::if_nametoindex(name.as_str()) {
+            Ok(index) => index,
+            Err(e) => {
+              return Err(Error::bad_parameter(format!(
+                "cannot find index of network interface {}: {}",
+                name, e
+              )))
+            }
+          }
+        }
+      }
+    }
+    network.addresses = Some(selected.into_iter().map(|(_, ip)| ip).collect());
+    Ok(network)
+  }
+
+  pub fn ip_families(&self) -> &[IpFamily] {
+    &self.ip_families
+  }
+
+  pub fn set_max_message_size(&mut self, max_message_size: usize) {
+    self.max_message_size = max_message_size;
+  }
+
+  pub fn max_message_size(&self) -> usize {
+    self.max_message_size
+  }
+
+  pub fn set_passive(&mut self, passive: bool) {
+    self.passive = passive;
+  }
+
+  pub fn passive(&self) -> bool {
+    self.passive
+  }
+
+  // Starts using shared memory with participants on this host, if the segment size is
+  // not zero. Without the shm feature this does nothing.
+  pub fn enable_shm(&mut self, segment_size: usize) {
+    #[cfg(feature = "shm")]
+    {
+      if segment_size == 0 {
+        return;
+      }
+      crate::network::shm::remove_stale_segments();
+      match crate::network::shm::namespace_id() {
+        Ok(namespace) => {
+          self.shm_namespace = Some(namespace);
+          self.shm_segment_size = segment_size;
+        }
+        Err(e) => log::warn!("Shared memory transport not available. {:?}", e),
+      }
+    }
+    #[cfg(not(feature = "shm"))]
+    let _ = segment_size;
+  }
+
+  pub fn shm_namespace(&self) -> Option<u32> {
+    self.shm_namespace
+  }
+
+  pub fn shm_segment_size(&self) -> usize {
+    self.shm_segment_size
+  }
+
+  // Shared memory locator we announce for a user endpoint.
+  pub fn shm_locator(&self, guid: GUID) -> Option<Locator> {
+    match self.shm_namespace {
+      Some(namespace) if !guid.entityId.is_builtin() => Some(shm_locator(namespace, guid)),
+      _ => None,
+    }
+  }
+
+  // Starts the TCP transport. It is bound to our unicast address, if we are restricted
+  // to an IPv4 one.
+  pub fn enable_tcp(&mut self, config: &TcpConfig) -> Result<()> {
+    let peers = config.peer_addresses()?;
+    let bind_ip = match self.unicast_bind_address() {
+      IpAddr::V4(ip) => ip,
+      IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+    };
+    let listen = config
+      .listen_port
+      .map(|port| SocketAddr::V4(SocketAddrV4::new(bind_ip, port)));
+    let tcp = match TcpTransport::new(listen, peers) {
+      Ok(tcp) => tcp,
+      Err(e) => {
+        let reason = match listen {
+          Some(address) => format!("cannot listen to TCP on {}: {}", address, e),
+          None => format!("cannot start TCP transport: {}", e),
+        };
+        return Err(Error::bad_parameter(reason));
+      }
+    };
+
+    self.tcp_addresses = match (tcp.listen_address(), config.public_address) {
+      (None, _) => Vec::new(),
+      (Some(_), Some(public)) => vec![public],
+      (Some(bound), None) => {
+        let port = bound.port();
+        if bind_ip.is_unspecified() {
+          get_local_unicast_socket_address(port, &[IpFamily::V4])
+            .iter()
+            .filter_map(|l| match l.to_socket_address() {
+              SocketAddr::V4(a) => Some(a),
+              SocketAddr::V6(_) => None,
+            })
+            .collect()
+        } else {
+          vec![SocketAddrV4::new(bind_ip, port)]
+        }
+      }
+    };
+    self.tcp = Some(tcp);
+    self.udp = config.udp;
+    Ok(())
+  }
+
+  pub fn tcp(&self) -> Option<&TcpTransport> {
+    self.tcp.as_ref()
+  }
+
+  pub fn udp_enabled(&self) -> bool {
+    self.udp
+  }
+
+  // TCP locators we announce for our participant. If we do not accept connections, the
+  // port is zero: remote participants can answer only through connections we open.
+  pub fn tcp_locators(&self, guid_prefix: GuidPrefix) -> LocatorList {
+    if self.tcp.is_none() {
+      return LocatorList::new();
+    }
+    if self.tcp_addresses.is_empty() {
+      return vec![tcp_locator(
+        guid_prefix,
+        SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0),
+      )];
+    }
+    self
+      .tcp_addresses
+      .iter()
+      .map(|a| tcp_locator(guid_prefix, *a))
+      .collect()
+  }
+
+  // Locators we announce for our unicast sockets.
+  pub fn unicast_locators(&self, port: u16) -> LocatorList {
+    if !self.udp {
+      return LocatorList::new();
+    }
+    match &self.addresses {
+      Some(addresses) => addresses
+        .iter()
+        .map(|ip| Locator::from(SocketAddr::new(*ip, port)))
+        .collect(),
+      None => get_local_unicast_socket_address(port, &self.ip_families),
+    }
+  }
+
+  pub fn multicast_locators(&self, port: u16) -> LocatorList {
+    if !self.udp {
+      return LocatorList::new();
+    }
+    get_local_multicast_locators(port, &self.ip_families)
+  }
+
+  // Unicast sockets are bound to the announced address, if there is only one.
+  pub fn unicast_bind_address(&self) -> IpAddr {
+    match &self.addresses {
+      Some(addresses) if addresses.len() == 1 => addresses[0],
+      _ => unspecified_address(&self.ip_families),
+    }
+  }
+
+  // Multicast sockets have to be bound to the unspecified address to receive anything.
+  pub fn multicast_bind_address(&self) -> IpAddr {
+    unspecified_address(&self.ip_families)
+  }
+
+  // The SPDP groups our multicast listeners join, none if we do not use UDP.
+  pub fn multicast_groups(&self) -> Vec<IpAddr> {
+    if !self.udp {
+      return Vec::new();
+    }
+    let mut groups = Vec::new();
+    for family in self.ip_families.iter() {
+      match family {
+        IpFamily::V4 => groups.push(IpAddr::V4(MULTICAST_GROUP_V4)),
+        IpFamily::V6 => groups.extend(MULTICAST_GROUPS_V6.iter().map(|g| IpAddr::V6(*g))),
+      }
+    }
+    groups
+  }
+
+  pub fn join_multicast(&self, listener: &UDPListener) -> std::io::Result<()> {
+    for group in self.multicast_groups().iter() {
+      self.join_multicast_group(listener, group)?;
+    }
+    Ok(())
+  }
+
+  // Joins those of `groups` the listener is no longer a member of, e.g. because the OS
+  // dropped the membership when the interface went down. Joining a group again fails
+  // with AddrInUse, so the groups still joined are not disturbed. Returns how many
+  // groups were joined.
+  pub fn rejoin_multicast(
+    &self,
+    listener: &UDPListener,
+    groups: &[IpAddr],
+  ) -> std::io::Result<usize> {
+    let mut joined = 0;
+    for group in groups.iter() {
+      match self.join_multicast_group(listener, group) {
+        Ok(()) => joined += 1,
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => (),
+        Err(e) => return Err(e),
+      }
+    }
+    Ok(joined)
+  }
+
+  // Joins the group of a DataReader, on our interface of the group's family.
+  pub fn join_multicast_group(
+    &self,
+    listener: &UDPListener,
+    group: &IpAddr,
+  ) -> std::io::Result<()> {
+    match group {
+      IpAddr::V4(group) => listener.join_multicast_on(group, &self.multicast_interface_v4),
+      IpAddr::V6(group) => listener.join_multicast_v6(group, self.multicast_interface_v6),
+    }
+  }
+
+  pub fn leave_multicast_group(
+    &self,
+    listener: &UDPListener,
+    group: &IpAddr,
+  ) -> std::io::Result<()> {
+    match group {
+      IpAddr::V4(group) => listener.leave_multicast_on(group, &self.multicast_interface_v4),
+      IpAddr::V6(group) => listener.leave_multicast_v6(group, self.multicast_interface_v6),
+    }
+  }
+
+  // Applies the socket options of the participant to a listening socket.
+  pub fn configure_listener(&self, listener: &UDPListener) -> std::io::Result<()> {
+    let size = self.socket_config.receive_buffer_size;
+    if size > 0 {
+      let granted = listener.set_receive_buffer_size(size)?;
+      info!(
+        "Receive buffer of port {}: {} bytes requested, {} granted.",
+        listener.port(),
+        size,
+        granted
+      );
+    }
+    Ok(())
+  }
+
+  // Applies the socket options of the participant to a sending socket, and makes its
+  // multicast leave through our interfaces.
+  pub fn configure_sender(&self, sender: &UDPSender) -> std::io::Result<()> {
+    let size = self.socket_config.send_buffer_size;
+    if size > 0 {
+      let granted = sender.set_send_buffer_size(size)?;
+      info!(
+        "Send buffer: {} bytes requested, {} granted.",
+        size, granted
+      );
+    }
+    sender.set_multicast_ttl(self.socket_config.multicast_ttl)?;
+    sender.set_multicast_loop(self.socket_config.multicast_loopback)?;
+    self.set_multicast_interface(sender)
+  }
+
+  // Makes multicast from the sender leave through our interfaces.
+  fn set_multicast_interface(&self, sender: &UDPSender) -> std::io::Result<()> {
+    if self.addresses.is_none() {
+      return Ok(());
+    }
+    for family in self.ip_families.iter() {
+      match family {
+        IpFamily::V4 => sender.set_multicast_interface_v4(&self.multicast_interface_v4)?,
+        IpFamily::V6 => sender.set_multicast_interface_v6(self.multicast_interface_v6)?,
+      }
+    }
+    Ok(())
+  }
+}
+
+// Picks, for each family in order of preference, the first address that is on one of
+// `interfaces`. `local` lists (interface name, address) pairs of this host.
+fn select_addresses(
+  ip_families: &[IpFamily],
+  interfaces: &[String],
+  local: &[(String, IpAddr)],
+) -> Result<Vec<(String, IpAddr)>> {
+  let mut matching = Vec::new();
+  for interface in interfaces.iter() {
+    let found: Vec<&(String, IpAddr)> = match parse_address_range(interface)? {
+      Some((network, prefix_len)) => local
+        .iter()
+        .filter(|(_, ip)| in_address_range(ip, &network, prefix_len))
+        .collect(),
+      None => local.iter().filter(|(name, _)| name == interface).collect(),
+    };
+    if found.is_empty() {
+      return Err(Error::bad_parameter(format!(
+        "network interface {:?} not found, local interfaces are {:?}",
+        interface, local
+      )));
+    }
+    matching.extend(found);
+  }
+
+  let selected: Vec<(String, IpAddr)> = ip_families
+    .iter()
+    .filter_map(|family| {
+      matching.iter().find(|(_, ip)| {
+        IpFamily::of(ip) == *family
+          && match ip {
+            IpAddr::V4(_) => true,
+            IpAddr::V6(ip6) => ip6.segments()[0] & 0xffc0 != 0xfe80,
+          }
+      })
+    })
+    .map(|m| (*m).clone())
+    .collect();
+  if selected.is_empty() {
+    return Err(Error::bad_parameter(format!(
+      "network interfaces {:?} have no usable address of {:?}",
+      interfaces, ip_families
+    )));
+  }
+  Ok(selected)
+}
+
+// Some((network, prefix length)) for "10.1.0.0/16", None for an interface name.
+fn parse_address_range(s: &str) -> Result<Option<(IpAddr, u8)>> {
+  let i = match s.find('/') {
+    Some(i) => i,
+    None => return Ok(None),
+  };
+  let network = s[..i].parse::<IpAddr>();
+  let prefix_len = s[i + 1..].parse::<u8>();
+  match (network, prefix_len) {
+    (Ok(network), Ok(prefix_len)) if prefix_len <= max_prefix_len(&network) => {
+      Ok(Some((network, prefix_len)))
+    }
+    _ => Err(Error::bad_parameter(format!(
+      "invalid address range {:?}",
+      s
+    ))),
+  }
+}
+
+fn max_prefix_len(ip: &IpAddr) -> u8 {
+  match ip {
+    IpAddr::V4(_) => 32,
+    IpAddr::V6(_) => 128,
+  }
+}
+
+fn in_address_range(ip: &IpAddr, network: &IpAddr, prefix_len: u8) -> bool {
+  let (ip, network, bits) = match (ip, network) {
+    (IpAddr::V4(ip), IpAddr::V4(network)) => (
+      u128::from(u32::from(*ip)),
+      u128::from(u32::from(*network)),
+      32,
+    ),
+    (IpAddr::V6(ip), IpAddr::V6(network)) => (u128::from(*ip), u128::from(*network), 128),
+    _ => return false,
+  };
+  let shift = bits - u32::from(prefix_len);
+  shift >= bits || (ip >> shift) == (network >> shift)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn local() -> Vec<(String, IpAddr)> {
+    vec![
+      (String::from("lo"), "127.0.0.1".parse().unwrap()),
+      (String::from("eth0"), "10.1.2.3".parse().unwrap()),
+      (String::from("wlan0"), "192.168.0.7".parse().unwrap()),
+      (String::from("wlan0"), "fe80::7".parse().unwrap()),
+      (String::from("wlan0"), "fd00::7".parse().unwrap()),
+    ]
+  }
+
+  fn select(ip_families: &[IpFamily], interfaces: &[&str]) -> Result<Vec<IpAddr>> {
+    let interfaces: Vec<String> = interfaces.iter().map(|s| s.to_string()).collect();
+    select_addresses(ip_families, &interfaces, &local())
+      .map(|s| s.into_iter().map(|(_, ip)| ip).collect())
+  }
+
+  fn ips(addresses: &[&str]) -> Vec<IpAddr> {
+    addresses.iter().map(|a| a.parse().unwrap()).collect()
+  }
+
+  #[test]
+  fn local_network_select_addresses() {
+    let v4 = [IpFamily::V4];
+    let dual = [IpFamily::V6, IpFamily::V4];
+    assert_eq!(select(&v4, &["wlan0"]).unwrap(), ips(&["192.168.0.7"]));
+    assert_eq!(select(&v4, &["10.0.0.0/8"]).unwrap(), ips(&["10.1.2.3"]));
+    assert_eq!(select(&v4, &["lo"]).unwrap(), ips(&["127.0.0.1"]));
+    // link-local addresses are skipped
+    assert_eq!(
+      select(&dual, &["wlan0"]).unwrap(),
+      ips(&["fd00::7", "192.168.0.7"])
+    );
+    assert_eq!(
+      select(&dual, &["10.1.0.0/16", "wlan0"]).unwrap(),
+      ips(&["fd00::7", "10.1.2.3"])
+    );
+    assert_eq!(select(&v4, &["0.0.0.0/0"]).unwrap(), ips(&["127.0.0.1"]));
+
+    assert!(matches!(
+      select(&v4, &["eth1"]),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      select(&v4, &["172.16.0.0/12"]),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      select(&[IpFamily::V6], &["eth0"]),
+      Err(Error::BadParameter { .. })
+    ));
+    for invalid in &["10.0.0.0/33", "10.0.0/8", "eth0/"] {
+      assert!(matches!(
+        select(&v4, &[invalid]),
+        Err(Error::BadParameter { .. })
+      ));
+    }
+  }
+
+  #[test]
+  fn local_network_addresses() {
+    let network = LocalNetwork::default();
+    assert_eq!(
+      network.unicast_bind_address(),
+      "0.0.0.0".parse::<IpAddr>().unwrap()
+    );
+
+    let network = LocalNetwork {
+      ip_families: vec![IpFamily::V4],
+      addresses: Some(ips(&["10.1.2.3"])),
+      multicast_interface_v4: "10.1.2.3".parse().unwrap(),
+      multicast_interface_v6: 0,
+      socket_config: SocketConfig::default(),
+      max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+      shm_namespace: None,
+      shm_segment_size: 0,
+      tcp: None,
+      tcp_addresses: Vec::new(),
+      udp: true,
+      passive: false,
+    };
+    assert_eq!(
+      network.unicast_locators(7411),
+      vec![Locator::from(
+        "10.1.2.3:7411".parse::<SocketAddr>().unwrap()
+      )]
+    );
+    assert_eq!(network.unicast_bind_address(), ips(&["10.1.2.3"])[0]);
+    assert_eq!(
+      network.multicast_bind_address(),
+      "0.0.0.0".parse::<IpAddr>().unwrap()
+    );
+  }
+
+  #[test]
+  fn local_network_socket_config() {
+    let socket_config = SocketConfig {
+      receive_buffer_size: 128 * 1024,
+      send_buffer_size: 96 * 1024,
+      multicast_ttl: 3,
+      multicast_loopback: false,
+    };
+    let network = LocalNetwork::new(&[IpFamily::V4], &[], socket_config).unwrap();
+
+    let listener = UDPListener::new(mio::Token(0), "127.0.0.1", 0);
+    let default_size = listener.receive_buffer_size().unwrap();
+    LocalNetwork::default()
+      .configure_listener(&listener)
+      .unwrap();
+    assert_eq!(listener.receive_buffer_size().unwrap(), default_size);
+    network.configure_listener(&listener).unwrap();
+    assert!(listener.receive_buffer_size().unwrap() >= 128 * 1024);
+
+    let sender = UDPSender::new_with_random_port();
+    network.configure_sender(&sender).unwrap();
+    assert!(sender.send_buffer_size().unwrap() >= 96 * 1024);
+  }
+
+  #[test]
+  fn local_network_rejoin_multicast() {
+    let network = LocalNetwork::default();
+    let groups = network.multicast_groups();
+    assert_eq!(groups, vec![IpAddr::V4(MULTICAST_GROUP_V4)]);
+    let listener = UDPListener::try_bind_shared(mio::Token(0), "0.0.0.0", 10006).unwrap();
+    network.join_multicast(&listener).unwrap();
+    // joined groups are left as they are
+    assert_eq!(network.rejoin_multicast(&listener, &groups).unwrap(), 0);
+
+    // as if the OS had dropped the membership when the interface went down
+    listener.leave_multicast(&MULTICAST_GROUP_V4).unwrap();
+    assert_eq!(network.rejoin_multicast(&listener, &groups).unwrap(), 1);
+    assert_eq!(network.rejoin_multicast(&listener, &groups).unwrap(), 0);
+
+    let data: Vec<u8> = vec![1, 3, 5];
+    UDPSender::new_with_random_port()
+      .send_multicast(&data, MULTICAST_GROUP_V4, 10006)
+      .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    assert_eq!(listener.get_message(), data);
+  }
+}
@@ -1,4 +1,11 @@
+pub mod capture;
 pub mod constant;
+#[cfg(feature = "netlink")]
+pub mod interface_monitor;
+pub mod local_network;
+#[cfg(feature = "shm")]
+pub mod shm;
+pub mod tcp;
 pub mod udp_listener;
 pub mod udp_sender;
 pub mod util;
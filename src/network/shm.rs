@@ -0,0 +1,686 @@
+// Shared memory transport between participants on the same host.
+//
+// Each DataWriter has a segment in /dev/shm, a ring buffer of the RTPS messages it
+// sends to readers on this host. The readers' participants map the segments of their
+// matched writers and take the messages from there. A writer rings the doorbell, a
+// FIFO of the reader's participant, after writing, so that the reader does not have
+// to poll. The writer never waits for readers: one that falls a whole ring behind
+// skips to the newest message, and the lost changes are repaired like lost datagrams.
+//
+// Participants use the transport with each other if they see the same /dev/shm. They
+// find out by comparing a random namespace id kept there.
+
+use bytes::Bytes;
+use log::{debug, warn};
+use nix::libc;
+
+use std::{
+  cell::RefCell,
+  collections::{hash_map::Entry, HashMap, HashSet},
+  fs::{self, File, OpenOptions},
+  io::{self, Read, Write},
+  os::unix::{
+    ffi::OsStrExt,
+    fs::OpenOptionsExt,
+    io::{AsRawFd, RawFd},
+  },
+  path::{Path, PathBuf},
+  ptr,
+  sync::atomic::{fence, AtomicU64, Ordering},
+  thread,
+  time::Duration,
+};
+
+use crate::{
+  network::util::shm_locator_guid,
+  structure::{
+    guid::{GuidPrefix, GUID},
+    locator::{Locator, LocatorKind},
+  },
+};
+
+const SHM_DIR: &str = "/dev/shm";
+const NAME_PREFIX: &str = "rustdds_";
+const NAMESPACE_FILE: &str = "rustdds_namespace";
+
+// "RTPSRING"
+const MAGIC: u64 = 0x5254_5053_5249_4e47;
+// magic, owner pid, capacity, reserved, committed
+const HEADER_LEN: usize = 64;
+const OWNER_PID_OFFSET: usize = 8;
+const CAPACITY_OFFSET: usize = 16;
+const RESERVED_OFFSET: usize = 24;
+const COMMITTED_OFFSET: usize = 32;
+// Each message is preceded by its length, and padded to 8 bytes.
+const RECORD_HEADER_LEN: usize = 8;
+// Length of the filler at the end of the ring, when a message does not fit there.
+const PADDING: u32 = u32::MAX;
+
+fn prefix_hex(prefix: GuidPrefix) -> String {
+  prefix
+    .entityKey
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect()
+}
+
+fn segment_path(writer: GUID) -> PathBuf {
+  let id = writer.entityId;
+  Path::new(SHM_DIR).join(format!(
+    "{}{}_{:02x}{:02x}{:02x}{:02x}.ring",
+    NAME_PREFIX,
+    prefix_hex(writer.guidPrefix),
+    id.entityKey[0],
+    id.entityKey[1],
+    id.entityKey[2],
+    id.entityKind
+  ))
+}
+
+fn doorbell_path(participant: GuidPrefix) -> PathBuf {
+  Path::new(SHM_DIR).join(format!("{}{}.bell", NAME_PREFIX, prefix_hex(participant)))
+}
+
+fn align8(n: usize) -> usize {
+  (n + 7) & !7
+}
+
+// Id of the /dev/shm we see. Created by the first participant that looks for it.
+pub fn namespace_id() -> io::Result<u32> {
+  let path = Path::new(SHM_DIR).join(NAMESPACE_FILE);
+  match OpenOptions::new()
+    .write(true)
+    .create_new(true)
+    .mode(0o644)
+    .open(&path)
+  {
+    Ok(mut file) => {
+      let id = rand::random::<u32>() | 1;
+      file.write_all(&id.to_be_bytes())?;
+      Ok(id)
+    }
+    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+      // The creator may not have written it yet.
+      for _ in 0..100 {
+        let bytes = fs::read(&path)?;
+        if bytes.len() >= 4 {
+          return Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+        }
+        thread::sleep(Duration::from_millis(1));
+      }
+      Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Shared memory namespace id is missing",
+      ))
+    }
+    Err(e) => Err(e),
+  }
+}
+
+fn process_is_alive(pid: u32) -> bool {
+  let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+  result == 0 || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+// Removes the segments and doorbells left behind by participants that died.
+pub fn remove_stale_segments() {
+  let entries = match fs::read_dir(SHM_DIR) {
+    Ok(entries) => entries,
+    Err(e) => {
+      debug!("Cannot list {}. {:?}", SHM_DIR, e);
+      return;
+    }
+  };
+  for entry in entries.filter_map(|e| e.ok()) {
+    let path = entry.path();
+    let name = entry.file_name();
+    let name = name.as_bytes();
+    if !name.starts_with(NAME_PREFIX.as_bytes()) {
+      continue;
+    }
+    let stale = if name.ends_with(b".ring") {
+      match segment_owner(&path) {
+        Ok(pid) => !process_is_alive(pid),
+        Err(_) => false,
+      }
+    } else if name.ends_with(b".bell") {
+      // Nobody has it open for reading.
+      match OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(&path)
+      {
+        Err(e) => e.raw_os_error() == Some(libc::ENXIO),
+        Ok(_) => false,
+      }
+    } else {
+      false
+    };
+    if stale {
+      debug!("Removing stale shared memory file {:?}", path);
+      fs::remove_file(&path).unwrap_or(());
+    }
+  }
+}
+
+fn segment_owner(path: &Path) -> io::Result<u32> {
+  let mut header = [0; 16];
+  File::open(path)?.read_exact(&mut header)?;
+  let mut magic = [0; 8];
+  magic.copy_from_slice(&header[..8]);
+  if u64::from_ne_bytes(magic) != MAGIC {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a segment"));
+  }
+  let mut pid = [0; 4];
+  pid.copy_from_slice(&header[OWNER_PID_OFFSET..OWNER_PID_OFFSET + 4]);
+  Ok(u32::from_ne_bytes(pid))
+}
+
+// A mapped segment file. The owner removes the file when done.
+struct Segment {
+  ptr: *mut u8,
+  len: usize,
+  path: PathBuf,
+  owner: bool,
+}
+
+// The mapping stays valid wherever the Segment goes, and the ring protocol makes
+// concurrent access safe.
+unsafe impl Send for Segment {}
+
+impl Segment {
+  fn map(file: &File, len: usize, path: PathBuf, owner: bool) -> io::Result<Segment> {
+    let ptr = unsafe {
+      libc::mmap(
+        ptr::null_mut(),
+        len,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_SHARED,
+        file.as_raw_fd(),
+        0,
+      )
+    };
+    if ptr == libc::MAP_FAILED {
+      return Err(io::Error::last_os_error());
+    }
+    Ok(Segment {
+      ptr: ptr as *mut u8,
+      len,
+      path,
+      owner,
+    })
+  }
+
+  fn atomic(&self, offset: usize) -> &AtomicU64 {
+    unsafe { &*(self.ptr.add(offset) as *const AtomicU64) }
+  }
+
+  fn read_u64(&self, offset: usize) -> u64 {
+    unsafe { ptr::read_volatile(self.ptr.add(offset) as *const u64) }
+  }
+
+  fn write_u64(&self, offset: usize, value: u64) {
+    unsafe { ptr::write_volatile(self.ptr.add(offset) as *mut u64, value) }
+  }
+
+  fn capacity(&self) -> usize {
+    self.read_u64(CAPACITY_OFFSET) as usize
+  }
+
+  fn reserved(&self) -> &AtomicU64 {
+    self.atomic(RESERVED_OFFSET)
+  }
+
+  fn committed(&self) -> &AtomicU64 {
+    self.atomic(COMMITTED_OFFSET)
+  }
+
+  // Ring offset to pointer
+  fn data(&self, offset: usize) -> *mut u8 {
+    unsafe { self.ptr.add(HEADER_LEN + offset) }
+  }
+}
+
+impl Drop for Segment {
+  fn drop(&mut self) {
+    unsafe {
+      libc::munmap(self.ptr as *mut libc::c_void, self.len);
+    }
+    if self.owner {
+      fs::remove_file(&self.path).unwrap_or(());
+    }
+  }
+}
+
+// The writing end of a ring. Positions are byte counts since the start, the ring
+// offset is the position modulo the capacity.
+struct RingWriter {
+  segment: Segment,
+}
+
+impl RingWriter {
+  fn create(path: PathBuf, capacity: usize) -> io::Result<RingWriter> {
+    let capacity = align8(capacity.max(4096));
+    // A leftover of an earlier writer with the same GUID
+    fs::remove_file(&path).unwrap_or(());
+    let file = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .create_new(true)
+      .mode(0o644)
+      .open(&path)?;
+    let len = HEADER_LEN + capacity;
+    file.set_len(len as u64)?;
+    let segment = Segment::map(&file, len, path, true)?;
+    segment.write_u64(CAPACITY_OFFSET, capacity as u64);
+    unsafe {
+      ptr::write_volatile(
+        segment.ptr.add(OWNER_PID_OFFSET) as *mut u32,
+        std::process::id(),
+      );
+    }
+    fence(Ordering::SeqCst);
+    // readers check the magic last
+    segment.write_u64(0, MAGIC);
+    Ok(RingWriter { segment })
+  }
+
+  fn push(&self, message: &[u8]) -> io::Result<()> {
+    let segment = &self.segment;
+    let capacity = segment.capacity();
+    let record_len = align8(RECORD_HEADER_LEN + message.len());
+    if record_len > capacity {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Message does not fit in the shared memory segment",
+      ));
+    }
+    let start = segment.committed().load(Ordering::Relaxed) as usize;
+    let offset = start % capacity;
+    let (padding, position) = if offset + record_len > capacity {
+      (capacity - offset, start + capacity - offset)
+    } else {
+      (0, start)
+    };
+    let end = position + record_len;
+    // Readers copying from the space we are about to overwrite see that they were
+    // overtaken.
+    segment.reserved().store(end as u64, Ordering::Relaxed);
+    fence(Ordering::SeqCst);
+    unsafe {
+      if padding > 0 {
+        ptr::write_volatile(segment.data(offset) as *mut u32, PADDING);
+      }
+      let record = segment.data(position % capacity);
+      ptr::write_volatile(record as *mut u32, message.len() as u32);
+      ptr::copy_nonoverlapping(
+        message.as_ptr(),
+        record.add(RECORD_HEADER_LEN),
+        message.len(),
+      );
+    }
+    segment.committed().store(end as u64, Ordering::Release);
+    Ok(())
+  }
+}
+
+// The reading end of a ring
+struct RingReader {
+  segment: Segment,
+  position: usize,
+}
+
+impl RingReader {
+  fn open(path: PathBuf) -> io::Result<RingReader> {
+    let file = OpenOptions::new().read(true).write(true).open(&path)?;
+    let len = file.metadata()?.len() as usize;
+    if len <= HEADER_LEN {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Segment too short",
+      ));
+    }
+    let segment = Segment::map(&file, len, path, false)?;
+    if segment.read_u64(0) != MAGIC || HEADER_LEN + segment.capacity() != len {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Segment not initialized",
+      ));
+    }
+    fence(Ordering::SeqCst);
+    // From the oldest message still in the ring
+    let committed = segment.committed().load(Ordering::Acquire) as usize;
+    let position = if committed <= segment.capacity() {
+      0
+    } else {
+      committed
+    };
+    Ok(RingReader { segment, position })
+  }
+
+  // The messages written since the last call. Returns also the number of times messages
+  // were lost because the writer overtook us.
+  fn pop_all(&mut self) -> (Vec<Bytes>, usize) {
+    let segment = &self.segment;
+    let capacity = segment.capacity();
+    let mut messages = Vec::new();
+    let mut overtaken = 0;
+    let committed = segment.committed().load(Ordering::Acquire) as usize;
+    if committed - self.position > capacity {
+      self.position = committed;
+      return (messages, 1);
+    }
+    while self.position < committed {
+      let offset = self.position % capacity;
+      let len = unsafe { ptr::read_volatile(segment.data(offset) as *const u32) };
+      let (message, next) = if len == PADDING {
+        (None, self.position + capacity - offset)
+      } else if RECORD_HEADER_LEN + len as usize <= capacity - offset {
+        let mut message = vec![0; len as usize];
+        unsafe {
+          ptr::copy_nonoverlapping(
+            segment.data(offset + RECORD_HEADER_LEN),
+            message.as_mut_ptr(),
+            message.len(),
+          );
+        }
+        (
+          Some(message),
+          self.position + align8(RECORD_HEADER_LEN + len as usize),
+        )
+      } else {
+        // overwritten under us
+        (None, usize::MAX)
+      };
+      fence(Ordering::Acquire);
+      let reserved = segment.reserved().load(Ordering::Relaxed) as usize;
+      if next == usize::MAX || reserved > self.position + capacity {
+        overtaken += 1;
+        self.position = segment.committed().load(Ordering::Acquire) as usize;
+        break;
+      }
+      if let Some(message) = message {
+        messages.push(Bytes::from(message));
+      }
+      self.position = next;
+    }
+    (messages, overtaken)
+  }
+}
+
+// Shared memory sending of a DataWriter
+pub struct ShmWriter {
+  ring: RingWriter,
+  // of the participants of our readers
+  doorbells: RefCell<HashMap<GuidPrefix, File>>,
+}
+
+impl ShmWriter {
+  pub fn create(writer: GUID, segment_size: usize) -> io::Result<ShmWriter> {
+    Ok(ShmWriter {
+      ring: RingWriter::create(segment_path(writer), segment_size)?,
+      doorbells: RefCell::new(HashMap::new()),
+    })
+  }
+
+  // Writes the message to our segment, if any of `locators` is a shared memory one,
+  // and lets the participants of those readers know.
+  pub fn send(&self, buffer: &[u8], locators: &[Locator]) {
+    let participants: HashSet<GuidPrefix> = locators
+      .iter()
+      .filter(|l| l.kind == LocatorKind::LOCATOR_KIND_SHM)
+      .map(|l| shm_locator_guid(l).guidPrefix)
+      .collect();
+    if participants.is_empty() {
+      return;
+    }
+    if let Err(e) = self.ring.push(buffer) {
+      warn!(
+        "Cannot send {} bytes through shared memory. {:?}",
+        buffer.len(),
+        e
+      );
+      return;
+    }
+    let mut doorbells = self.doorbells.borrow_mut();
+    for participant in participants {
+      let doorbell = match doorbells.entry(participant) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => match OpenOptions::new()
+          .write(true)
+          .custom_flags(libc::O_NONBLOCK)
+          .open(doorbell_path(participant))
+        {
+          Ok(file) => entry.insert(file),
+          Err(e) => {
+            debug!("Cannot open doorbell of {:?}. {:?}", participant, e);
+            continue;
+          }
+        },
+      };
+      match doorbell.write(&[0]) {
+        Ok(_) => (),
+        // already rung
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => (),
+        Err(e) => {
+          debug!("Doorbell of {:?} is gone. {:?}", participant, e);
+          doorbells.remove(&participant);
+        }
+      }
+    }
+  }
+}
+
+// Shared memory receiving of a DomainParticipant
+pub struct ShmReceiver {
+  doorbell: File,
+  doorbell_path: PathBuf,
+  // Segments of matched writers on this host. None until it can be opened.
+  rings: HashMap<GUID, Option<RingReader>>,
+}
+
+impl ShmReceiver {
+  pub fn new(participant: GuidPrefix) -> io::Result<ShmReceiver> {
+    let doorbell_path = doorbell_path(participant);
+    fs::remove_file(&doorbell_path).unwrap_or(());
+    let c_path = std::ffi::CString::new(doorbell_path.as_os_str().as_bytes())
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o622) } != 0 {
+      return Err(io::Error::last_os_error());
+    }
+    // Open for writing too, so that it never reports the end of file.
+    let doorbell = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .custom_flags(libc::O_NONBLOCK)
+      .open(&doorbell_path)?;
+    Ok(ShmReceiver {
+      doorbell,
+      doorbell_path,
+      rings: HashMap::new(),
+    })
+  }
+
+  // To register with mio
+  pub fn doorbell_fd(&self) -> RawFd {
+    self.doorbell.as_raw_fd()
+  }
+
+  // Reads from the segments of these writers from now on.
+  pub fn set_writers(&mut self, writers: &HashSet<GUID>) {
+    self.rings.retain(|w, _| writers.contains(w));
+    for writer in writers {
+      self.rings.entry(*writer).or_insert(None);
+    }
+  }
+
+  // The messages written to our writers' segments since the last call.
+  pub fn receive(&mut self) -> Vec<Bytes> {
+    let mut buffer = [0; 64];
+    loop {
+      match self.doorbell.read(&mut buffer) {
+        Ok(n) if n > 0 => continue,
+        _ => break,
+      }
+    }
+
+    let mut messages = Vec::new();
+    for (writer, ring) in self.rings.iter_mut() {
+      if ring.is_none() {
+        // The writer may not have created it yet.
+        *ring = RingReader::open(segment_path(*writer)).ok();
+      }
+      if let Some(ring) = ring {
+        let (mut received, overtaken) = ring.pop_all();
+        if overtaken > 0 {
+          debug!(
+            "Shared memory reader fell behind writer {:?}. Messages lost.",
+            writer
+          );
+        }
+        messages.append(&mut received);
+      }
+    }
+    messages
+  }
+}
+
+impl Drop for ShmReceiver {
+  fn drop(&mut self) {
+    fs::remove_file(&self.doorbell_path).unwrap_or(());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{network::util::shm_locator, structure::guid::EntityId};
+
+  fn test_guid(prefix: u8, kind: u8) -> GUID {
+    GUID::new_with_prefix_and_id(
+      GuidPrefix::new(vec![prefix; 12]),
+      EntityId::createCustomEntityID([prefix, 0, 1], kind),
+    )
+  }
+
+  fn message(n: usize, len: usize) -> Vec<u8> {
+    (0..len).map(|i| (n + i) as u8).collect()
+  }
+
+  #[test]
+  fn shm_ring_wraps_around() {
+    let path = segment_path(test_guid(0xa1, 0x02));
+    let writer = RingWriter::create(path.clone(), 4096).unwrap();
+    let mut reader = RingReader::open(path.clone()).unwrap();
+    assert!(reader.pop_all().0.is_empty());
+
+    // Several times around the ring, with sizes that do not divide it.
+    for round in 0..20 {
+      let messages: Vec<Vec<u8>> = (0..3).map(|i| message(round + i, 500 + i * 7)).collect();
+      for m in messages.iter() {
+        writer.push(m).unwrap();
+      }
+      let (received, overtaken) = reader.pop_all();
+      assert_eq!(overtaken, 0);
+      let received: Vec<Vec<u8>> = received.iter().map(|b| b.to_vec()).collect();
+      assert_eq!(received, messages);
+    }
+    assert!(writer.push(&vec![0; 4096]).is_err());
+
+    drop(reader);
+    drop(writer);
+    assert!(!path.exists());
+  }
+
+  #[test]
+  fn shm_reader_overtaken() {
+    let path = segment_path(test_guid(0xa2, 0x02));
+    let writer = RingWriter::create(path.clone(), 4096).unwrap();
+    let mut reader = RingReader::open(path).unwrap();
+
+    for n in 0..20 {
+      writer.push(&message(n, 1000)).unwrap();
+    }
+    // skips to the newest
+    let (received, overtaken) = reader.pop_all();
+    assert!(received.is_empty());
+    assert_eq!(overtaken, 1);
+    writer.push(&message(20, 1000)).unwrap();
+    assert_eq!(reader.pop_all().0, vec![Bytes::from(message(20, 1000))]);
+
+    // a late reader gets what is still in the ring
+    let writer_guid = test_guid(0xa3, 0x02);
+    let writer = RingWriter::create(segment_path(writer_guid), 4096).unwrap();
+    writer.push(&message(1, 100)).unwrap();
+    let mut late = RingReader::open(segment_path(writer_guid)).unwrap();
+    assert_eq!(late.pop_all().0, vec![Bytes::from(message(1, 100))]);
+  }
+
+  #[test]
+  fn shm_writer_to_receiver() {
+    let writer_guid = test_guid(0xa4, 0x02);
+    let reader_guid = test_guid(0xa5, 0x07);
+    let namespace = namespace_id().unwrap();
+    assert_eq!(namespace_id().unwrap(), namespace);
+
+    let mut receiver = ShmReceiver::new(reader_guid.guidPrefix).unwrap();
+    let writer = ShmWriter::create(writer_guid, 64 * 1024).unwrap();
+    let mut writers = HashSet::new();
+    writers.insert(writer_guid);
+    receiver.set_writers(&writers);
+
+    let reader_locator = shm_locator(namespace, reader_guid);
+    writer.send(&message(1, 3000), &[reader_locator]);
+    writer.send(&message(2, 10), &[reader_locator]);
+    // no shared memory readers
+    writer.send(&message(3, 10), &[]);
+
+    // the doorbell rang
+    let mut fds = libc::pollfd {
+      fd: receiver.doorbell_fd(),
+      events: libc::POLLIN,
+      revents: 0,
+    };
+    assert_eq!(unsafe { libc::poll(&mut fds, 1, 1000) }, 1);
+    assert_eq!(
+      receiver.receive(),
+      vec![Bytes::from(message(1, 3000)), Bytes::from(message(2, 10))]
+    );
+    assert_eq!(unsafe { libc::poll(&mut fds, 1, 0) }, 0);
+
+    // Dropping the writer removes its segment
+    receiver.set_writers(&HashSet::new());
+    let path = segment_path(writer_guid);
+    assert!(path.exists());
+    drop(writer);
+    assert!(!path.exists());
+  }
+
+  #[test]
+  fn shm_remove_stale_segments() {
+    // a segment whose owner is gone
+    let path = segment_path(test_guid(0xa6, 0x02));
+    let writer = RingWriter::create(path.clone(), 4096).unwrap();
+    unsafe {
+      ptr::write_volatile(
+        writer.segment.ptr.add(OWNER_PID_OFFSET) as *mut u32,
+        i32::MAX as u32,
+      );
+    }
+    let live = RingWriter::create(segment_path(test_guid(0xa7, 0x02)), 4096).unwrap();
+    // a doorbell nobody listens to
+    let bell = doorbell_path(GuidPrefix::new(vec![0xa8; 12]));
+    let c_bell = std::ffi::CString::new(bell.as_os_str().as_bytes()).unwrap();
+    fs::remove_file(&bell).unwrap_or(());
+    assert_eq!(unsafe { libc::mkfifo(c_bell.as_ptr(), 0o622) }, 0);
+    let listened = ShmReceiver::new(GuidPrefix::new(vec![0xa9; 12])).unwrap();
+
+    remove_stale_segments();
+    assert!(!path.exists());
+    assert!(!bell.exists());
+    assert!(live.segment.path.exists());
+    assert!(listened.doorbell_path.exists());
+    // its file is gone already
+    std::mem::forget(writer);
+  }
+}
@@ -0,0 +1,645 @@
+// RTPS over TCP, for networks where UDP does not get through, e.g. across NAT to a
+// server in the cloud.
+//
+// Each RTPS message is sent as a frame: its length as a big-endian u32, then the
+// message. The first frame on a connection is a bare RTPS header, which tells the other
+// end whose connection it is. A participant keeps at most one connection to each other
+// participant. It carries everything between the two in both directions, so that a
+// participant behind NAT only needs to connect out. If both ends connect at the same
+// time, the connection opened by the participant with the smaller guid prefix stays.
+//
+// Connections to the configured peers are re-established when lost, waiting longer
+// after each failed attempt. Other connections are opened when there is something to
+// send to a participant that accepts connections.
+
+use bytes::{Bytes, BytesMut};
+use log::{debug, info, warn};
+use mio::{
+  net::{TcpListener, TcpStream},
+  Poll, PollOpt, Ready, Token,
+};
+use mio_extras::timer::Timer;
+
+use std::{
+  cmp::min,
+  collections::HashMap,
+  fmt,
+  io::{self, Read, Write},
+  net::SocketAddr,
+  sync::{Arc, Mutex, MutexGuard},
+  time::{Duration, Instant},
+};
+
+use crate::{
+  messages::{protocol_version::ProtocolVersion, vendor_id::VendorId},
+  network::{
+    constant::{TCP_CONNECTION_TOKENS, TCP_LISTENER_TOKEN, TCP_RECONNECT_TIMER_TOKEN},
+    util::tcp_locator_parts,
+  },
+  structure::{
+    guid::GuidPrefix,
+    locator::{Locator, LocatorKind},
+  },
+};
+
+// RTPS header: protocol, version, vendor id, guid prefix
+const HEADER_LEN: usize = 20;
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+// Messages to a connection that is this far behind are dropped, like datagrams to a
+// full socket buffer.
+const MAX_QUEUED_BYTES: usize = 16 * 1024 * 1024;
+const RECONNECT_DELAY_MIN: Duration = Duration::from_millis(100);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(10);
+
+struct Connection {
+  stream: TcpStream,
+  // Whose connection it is, known from its first frame
+  peer: Option<GuidPrefix>,
+  // The address we connected to, if we opened it
+  connected_to: Option<SocketAddr>,
+  input: BytesMut,
+  output: Vec<u8>,
+}
+
+impl Connection {
+  fn queue(&mut self, message: &[u8]) {
+    if self.output.len() + 4 + message.len() > MAX_QUEUED_BYTES {
+      debug!(
+        "TCP connection to {:?} is congested. Dropping a message.",
+        self.peer
+      );
+      return;
+    }
+    self
+      .output
+      .extend_from_slice(&(message.len() as u32).to_be_bytes());
+    self.output.extend_from_slice(message);
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    while !self.output.is_empty() {
+      match self.stream.write(&self.output) {
+        Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+        Ok(n) => {
+          self.output.drain(..n);
+        }
+        // still connecting, or the send buffer is full
+        Err(e)
+          if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::NotConnected =>
+        {
+          return Ok(())
+        }
+        Err(e) => return Err(e),
+      }
+    }
+    Ok(())
+  }
+
+  // Reads the complete frames that have arrived.
+  fn read(&mut self, frames: &mut Vec<Bytes>) -> io::Result<()> {
+    let mut buffer = [0; 64 * 1024];
+    loop {
+      match self.stream.read(&mut buffer) {
+        Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+        Ok(n) => self.input.extend_from_slice(&buffer[..n]),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+        Err(e) => return Err(e),
+      }
+    }
+    while self.input.len() >= 4 {
+      let len =
+        u32::from_be_bytes([self.input[0], self.input[1], self.input[2], self.input[3]]) as usize;
+      if len > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidData,
+          format!("Frame of {} bytes", len),
+        ));
+      }
+      if self.input.len() < 4 + len {
+        break;
+      }
+      frames.push(self.input.split_to(4 + len).freeze().slice(4..));
+    }
+    Ok(())
+  }
+}
+
+struct Connections {
+  guid_prefix: GuidPrefix,
+  listener: Option<TcpListener>,
+  // Addresses we keep connected to
+  peers: Vec<SocketAddr>,
+  connections: HashMap<Token, Connection>,
+  // not yet registered with the poll
+  unregistered: Vec<Token>,
+  next_token: usize,
+  // Addresses that failed: how long we waited last, and when we may try again
+  backoff: HashMap<SocketAddr, (Duration, Instant)>,
+  reconnect_timer: Timer<SocketAddr>,
+}
+
+impl Connections {
+  fn hello(&self) -> Vec<u8> {
+    let version = ProtocolVersion::THIS_IMPLEMENTATION;
+    let mut header = b"RTPS".to_vec();
+    header.extend_from_slice(&[version.major, version.minor]);
+    header.extend_from_slice(&VendorId::THIS_IMPLEMENTATION.vendorId);
+    header.extend_from_slice(&self.guid_prefix.entityKey);
+    header
+  }
+
+  fn add(&mut self, stream: TcpStream, connected_to: Option<SocketAddr>) -> Token {
+    if let Err(e) = stream.set_nodelay(true) {
+      debug!("Cannot set TCP_NODELAY. {:?}", e);
+    }
+    let token = Token(self.next_token);
+    self.next_token += 1;
+    if !TCP_CONNECTION_TOKENS.contains(&self.next_token) {
+      self.next_token = TCP_CONNECTION_TOKENS.start;
+    }
+    let mut connection = Connection {
+      stream,
+      peer: None,
+      connected_to,
+      input: BytesMut::new(),
+      output: Vec::new(),
+    };
+    connection.queue(&self.hello());
+    self.connections.insert(token, connection);
+    self.unregistered.push(token);
+    token
+  }
+
+  fn connect(&mut self, address: SocketAddr) -> Option<Token> {
+    if let Some((_, not_before)) = self.backoff.get(&address) {
+      if Instant::now() < *not_before {
+        return None;
+      }
+    }
+    match TcpStream::connect(&address) {
+      Ok(stream) => {
+        debug!("Connecting to {}", address);
+        Some(self.add(stream, Some(address)))
+      }
+      Err(e) => {
+        debug!("Cannot connect to {}. {:?}", address, e);
+        self.connect_failed(address);
+        None
+      }
+    }
+  }
+
+  fn connect_failed(&mut self, address: SocketAddr) {
+    let delay = match self.backoff.get(&address) {
+      Some((delay, _)) => min(*delay * 2, RECONNECT_DELAY_MAX),
+      None => RECONNECT_DELAY_MIN,
+    };
+    self
+      .backoff
+      .insert(address, (delay, Instant::now() + delay));
+    if self.peers.contains(&address) {
+      self.reconnect_timer.set_timeout(delay, address);
+    }
+  }
+
+  fn accept(&mut self) {
+    loop {
+      let accepted = match &self.listener {
+        Some(listener) => listener.accept(),
+        None => return,
+      };
+      match accepted {
+        Ok((stream, address)) => {
+          info!("TCP connection from {}", address);
+          self.add(stream, None);
+        }
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+        Err(e) => {
+          warn!("Cannot accept TCP connection. {:?}", e);
+          return;
+        }
+      }
+    }
+  }
+
+  fn close(&mut self, token: Token, reconnect: bool) {
+    if let Some(connection) = self.connections.remove(&token) {
+      self.unregistered.retain(|t| *t != token);
+      if let (Some(address), true) = (connection.connected_to, reconnect) {
+        self.connect_failed(address);
+      }
+    }
+  }
+
+  fn connection_of(&self, peer: GuidPrefix) -> Option<Token> {
+    self
+      .connections
+      .iter()
+      .find(|(_, c)| c.peer == Some(peer))
+      .map(|(t, _)| *t)
+  }
+
+  fn connection_to(&self, address: SocketAddr) -> Option<Token> {
+    self
+      .connections
+      .iter()
+      .find(|(_, c)| c.connected_to == Some(address))
+      .map(|(t, _)| *t)
+  }
+
+  // The first frame of a connection. Returns false if the connection is to be closed.
+  fn identify(&mut self, token: Token, hello: &[u8]) -> bool {
+    if hello.len() < HEADER_LEN || &hello[..4] != b"RTPS" {
+      warn!("TCP connection did not start with an RTPS header. Closing it.");
+      return false;
+    }
+    let peer = GuidPrefix::new(hello[8..HEADER_LEN].to_vec());
+    let earlier = self.connection_of(peer);
+    let connection = match self.connections.get_mut(&token) {
+      Some(c) => c,
+      None => return false,
+    };
+    connection.peer = Some(peer);
+    info!("TCP connection {:?} is to participant {:?}", token, peer);
+    if let Some(address) = connection.connected_to {
+      self.backoff.remove(&address);
+    }
+
+    match earlier {
+      None => true,
+      Some(earlier) => {
+        // Both ends decide the same way which one stays.
+        let own = self.guid_prefix;
+        let opener = |t: Token| match self.connections.get(&t) {
+          Some(c) if c.connected_to.is_some() => own,
+          _ => peer,
+        };
+        let (new_by, earlier_by) = (opener(token), opener(earlier));
+        if new_by != earlier_by && new_by < earlier_by {
+          self.close(earlier, false);
+          true
+        } else {
+          false
+        }
+      }
+    }
+  }
+}
+
+// The TCP connections of a participant. Clones share them.
+#[derive(Clone)]
+pub struct TcpTransport {
+  connections: Arc<Mutex<Connections>>,
+}
+
+impl TcpTransport {
+  // Accepts connections on `listen`, if given, and keeps connected to `peers` once
+  // started.
+  pub fn new(listen: Option<SocketAddr>, peers: Vec<SocketAddr>) -> io::Result<TcpTransport> {
+    let listener = match listen {
+      Some(address) => Some(TcpListener::bind(&address)?),
+      None => None,
+    };
+    Ok(TcpTransport {
+      connections: Arc::new(Mutex::new(Connections {
+        guid_prefix: GuidPrefix::GUIDPREFIX_UNKNOWN,
+        listener,
+        peers,
+        connections: HashMap::new(),
+        unregistered: Vec::new(),
+        next_token: TCP_CONNECTION_TOKENS.start,
+        backoff: HashMap::new(),
+        reconnect_timer: Timer::default(),
+      })),
+    })
+  }
+
+  fn lock(&self) -> MutexGuard<'_, Connections> {
+    match self.connections.lock() {
+      Ok(c) => c,
+      Err(e) => panic!("TCP connections are poisoned. {:?}", e),
+    }
+  }
+
+  pub fn is_tcp_token(token: Token) -> bool {
+    token == TCP_LISTENER_TOKEN
+      || token == TCP_RECONNECT_TIMER_TOKEN
+      || TCP_CONNECTION_TOKENS.contains(&token.0)
+  }
+
+  // Where we accept connections, if we do
+  pub fn listen_address(&self) -> Option<SocketAddr> {
+    self
+      .lock()
+      .listener
+      .as_ref()
+      .and_then(|l| l.local_addr().ok())
+  }
+
+  // Registers with the event loop of the participant, and connects to the peers.
+  pub fn start(&self, guid_prefix: GuidPrefix, poll: &Poll) -> io::Result<()> {
+    let mut connections = self.lock();
+    connections.guid_prefix = guid_prefix;
+    if let Some(listener) = &connections.listener {
+      poll.register(
+        listener,
+        TCP_LISTENER_TOKEN,
+        Ready::readable(),
+        PollOpt::edge(),
+      )?;
+    }
+    poll.register(
+      &connections.reconnect_timer,
+      TCP_RECONNECT_TIMER_TOKEN,
+      Ready::readable(),
+      PollOpt::edge(),
+    )?;
+    for peer in connections.peers.clone() {
+      connections.connect(peer);
+    }
+    drop(connections);
+    self.register_connections(poll);
+    Ok(())
+  }
+
+  // Registers connections opened since the last call. Sending may open connections.
+  pub fn register_connections(&self, poll: &Poll) {
+    let mut connections = self.lock();
+    let tokens: Vec<Token> = connections.unregistered.drain(..).collect();
+    for token in tokens {
+      let result = match connections.connections.get(&token) {
+        Some(c) => poll.register(
+          &c.stream,
+          token,
+          Ready::readable() | Ready::writable(),
+          PollOpt::edge(),
+        ),
+        None => continue,
+      };
+      if let Err(e) = result {
+        warn!("Cannot register TCP connection. {:?}", e);
+        connections.close(token, true);
+      }
+    }
+  }
+
+  // Handles a poll event of one of our tokens. Returns the RTPS messages received.
+  pub fn handle_event(&self, token: Token) -> Vec<Bytes> {
+    let mut connections = self.lock();
+    if token == TCP_LISTENER_TOKEN {
+      connections.accept();
+      return Vec::new();
+    }
+    if token == TCP_RECONNECT_TIMER_TOKEN {
+      while let Some(address) = connections.reconnect_timer.poll() {
+        if connections.connection_to(address).is_none() {
+          connections.connect(address);
+        }
+      }
+      return Vec::new();
+    }
+
+    let mut frames = Vec::new();
+    let result = match connections.connections.get_mut(&token) {
+      Some(c) => c.flush().and_then(|_| c.read(&mut frames)),
+      None => return Vec::new(),
+    };
+    let mut messages = Vec::new();
+    for frame in frames {
+      let identified = match connections.connections.get(&token) {
+        Some(c) => c.peer.is_some(),
+        None => break,
+      };
+      if identified {
+        messages.push(frame);
+      } else if !connections.identify(token, &frame) {
+        connections.close(token, false);
+        return messages;
+      }
+    }
+    if let Err(e) = result {
+      if let Some(c) = connections.connections.get(&token) {
+        info!("TCP connection to {:?} closed. {:?}", c.peer, e);
+      }
+      connections.close(token, true);
+    }
+    messages
+  }
+
+  // Sends the message to the participants of the TCP locators among `locators`, each
+  // at most once.
+  pub fn send(&self, buffer: &[u8], locators: &[Locator]) {
+    let mut targets: Vec<Token> = Vec::new();
+    let mut connections = self.lock();
+    for locator in locators
+      .iter()
+      .filter(|l| l.kind == LocatorKind::LOCATOR_KIND_TCPv4)
+    {
+      let (peer, address) = tcp_locator_parts(locator);
+      if peer == GuidPrefix::GUIDPREFIX_UNKNOWN {
+        targets.extend(connections.connections.keys());
+      } else if peer == connections.guid_prefix {
+        continue;
+      } else if let Some(token) = connections.connection_of(peer) {
+        targets.push(token);
+      } else if address.port() != 0 {
+        let address = SocketAddr::V4(address);
+        let token = match connections.connection_to(address) {
+          Some(token) => Some(token),
+          None => connections.connect(address),
+        };
+        targets.extend(token);
+      }
+    }
+    targets.sort();
+    targets.dedup();
+
+    for token in targets {
+      let result = match connections.connections.get_mut(&token) {
+        Some(c) => {
+          c.queue(buffer);
+          c.flush()
+        }
+        None => continue,
+      };
+      if let Err(e) = result {
+        debug!("Cannot send to TCP connection {:?}. {:?}", token, e);
+        connections.close(token, true);
+      }
+    }
+  }
+
+  #[cfg(test)]
+  fn connected_peers(&self) -> Vec<GuidPrefix> {
+    self
+      .lock()
+      .connections
+      .values()
+      .filter_map(|c| c.peer)
+      .collect()
+  }
+}
+
+impl fmt::Debug for TcpTransport {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("TcpTransport")
+      .field("listen_address", &self.listen_address())
+      .finish()
+  }
+}
+
+// Clones are the same transport.
+impl PartialEq for TcpTransport {
+  fn eq(&self, other: &TcpTransport) -> bool {
+    Arc::ptr_eq(&self.connections, &other.connections)
+  }
+}
+
+impl Eq for TcpTransport {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::network::util::tcp_locator;
+  use mio::Events;
+  use std::net::{Ipv4Addr, SocketAddrV4};
+
+  struct Participant {
+    tcp: TcpTransport,
+    poll: Poll,
+    prefix: GuidPrefix,
+    received: Vec<Bytes>,
+  }
+
+  impl Participant {
+    fn new(id: u8, listen: Option<SocketAddr>, peers: Vec<SocketAddr>) -> Participant {
+      let prefix = GuidPrefix::new(vec![id; 12]);
+      let tcp = TcpTransport::new(listen, peers).unwrap();
+      let poll = Poll::new().unwrap();
+      tcp.start(prefix, &poll).unwrap();
+      Participant {
+        tcp,
+        poll,
+        prefix,
+        received: Vec::new(),
+      }
+    }
+
+    // Locator for sending to this participant
+    fn locator(&self) -> Locator {
+      let port = self.tcp.listen_address().map(|a| a.port()).unwrap_or(0);
+      tcp_locator(self.prefix, SocketAddrV4::new(Ipv4Addr::LOCALHOST, port))
+    }
+
+    fn run(&mut self) {
+      let mut events = Events::with_capacity(64);
+      self
+        .poll
+        .poll(&mut events, Some(Duration::from_millis(10)))
+        .unwrap();
+      for event in events.iter() {
+        let mut messages = self.tcp.handle_event(event.token());
+        self.received.append(&mut messages);
+      }
+      self.tcp.register_connections(&self.poll);
+    }
+  }
+
+  fn run_until(participants: &mut [&mut Participant], done: impl Fn(&[&mut Participant]) -> bool) {
+    for _ in 0..500 {
+      if done(participants) {
+        return;
+      }
+      for p in participants.iter_mut() {
+        p.run();
+      }
+    }
+    panic!("Timed out");
+  }
+
+  fn loopback() -> Option<SocketAddr> {
+    Some("127.0.0.1:0".parse().unwrap())
+  }
+
+  #[test]
+  fn tcp_client_and_server() {
+    let mut server = Participant::new(1, loopback(), vec![]);
+    let mut client = Participant::new(2, None, vec![server.tcp.listen_address().unwrap()]);
+    let (server_prefix, client_prefix) = (server.prefix, client.prefix);
+    run_until(&mut [&mut server, &mut client], |p| {
+      p[0].tcp.connected_peers() == vec![client_prefix]
+        && p[1].tcp.connected_peers() == vec![server_prefix]
+    });
+
+    // The client accepts no connections, but the server reaches it through the one the
+    // client opened.
+    let large = vec![7; 200_000];
+    server.tcp.send(b"to client", &[client.locator()]);
+    server
+      .tcp
+      .send(&large, &[client.locator(), client.locator()]);
+    client.tcp.send(b"to server", &[server.locator()]);
+    // not TCP, or ourselves
+    client.tcp.send(b"nowhere", &[client.locator()]);
+    run_until(&mut [&mut server, &mut client], |p| {
+      p[0].received.len() == 1 && p[1].received.len() == 2
+    });
+    assert_eq!(server.received, vec![Bytes::from_static(b"to server")]);
+    assert_eq!(
+      client.received,
+      vec![Bytes::from_static(b"to client"), Bytes::from(large)]
+    );
+
+    server.tcp.send(
+      b"everyone",
+      &[crate::network::util::tcp_all_connections_locator()],
+    );
+    run_until(&mut [&mut server, &mut client], |p| {
+      p[1].received.len() == 3
+    });
+  }
+
+  #[test]
+  fn tcp_one_connection_per_peer() {
+    // both connect to each other
+    let mut a = Participant::new(3, loopback(), vec![]);
+    let mut b = Participant::new(4, loopback(), vec![a.tcp.listen_address().unwrap()]);
+    a.tcp.send(b"hello", &[b.locator()]);
+    let (prefix_a, prefix_b) = (a.prefix, b.prefix);
+    run_until(&mut [&mut a, &mut b], |p| {
+      p[0].tcp.connected_peers() == vec![prefix_b]
+        && p[1].tcp.connected_peers() == vec![prefix_a]
+        && p[0].tcp.lock().connections.len() == 1
+        && p[1].tcp.lock().connections.len() == 1
+    });
+    // the kept connection works both ways
+    a.tcp.send(b"from a", &[b.locator()]);
+    b.tcp.send(b"from b", &[a.locator()]);
+    run_until(&mut [&mut a, &mut b], |p| {
+      p[0].received.contains(&Bytes::from_static(b"from b"))
+        && p[1].received.contains(&Bytes::from_static(b"from a"))
+    });
+  }
+
+  #[test]
+  fn tcp_reconnect() {
+    let server = Participant::new(5, loopback(), vec![]);
+    let address = server.tcp.listen_address().unwrap();
+    let mut client = Participant::new(6, None, vec![address]);
+    let mut server = server;
+    run_until(&mut [&mut server, &mut client], |p| {
+      p[1].tcp.connected_peers().len() == 1
+    });
+
+    drop(server);
+    run_until(&mut [&mut client], |p| {
+      p[0].tcp.connected_peers().is_empty()
+    });
+    // The client keeps trying, backing off.
+    let mut server = Participant::new(5, Some(address), vec![]);
+    run_until(&mut [&mut server, &mut client], |p| {
+      p[1].tcp.connected_peers() == vec![p[0].prefix]
+    });
+    assert!(client.tcp.lock().backoff.is_empty());
+  }
+}
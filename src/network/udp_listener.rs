@@ -1,10 +1,17 @@
-use std::net::{Ipv4Addr, SocketAddr};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::io;
 
 use mio::Token;
-use log::{debug, error};
+use log::debug;
+use bytes::Bytes;
 use mio::net::UdpSocket;
+use nix::libc;
+use nix::sys::socket::{self, AddressFamily, InetAddr, SockAddr, SockFlag, SockType};
 use std::net::UdpSocket as StdUdpSocket;
+use std::os::unix::io::FromRawFd;
+
+use crate::network::util::{get_socket_option, nix_to_io_error, set_socket_option};
+use crate::network::capture::{PacketCapture, Transport};
 
 //use std::os::unix::io::AsRawFd;
 //use nix::sys::socket::setsockopt;
@@ -19,6 +26,7 @@ const BUFFER_SIZE: usize = 64 * 1024;
 pub struct UDPListener {
   socket: UdpSocket,
   token: Token,
+  capture: PacketCapture,
 }
 
 impl UDPListener {
@@ -36,47 +44,71 @@ impl UDPListener {
     UDPListener {
       socket: socket,
       token: token,
+      capture: PacketCapture::default(),
     }
   }
 
-  pub fn try_bind(token: Token, host: &str, port: u16) -> Option<UDPListener> {
+  pub fn try_bind(token: Token, host: &str, port: u16) -> io::Result<UDPListener> {
     let host = match host.parse() {
       Ok(h) => h,
-      _ => return None,
+      Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidInput, e)),
     };
 
     let address = SocketAddr::new(host, port);
-    let err_msg = format!("Unable to bind address {}", address.to_string());
-    let std_socket = match StdUdpSocket::bind(address) {
-      Ok(sock) => sock,
-      Err(_) => {
-        error!("{}", &err_msg);
-        return None;
-      }
-    };
-    match std_socket.set_nonblocking(true) {
-      Ok(_) => (),
-      Err(e) => {
-        error!("Failed to set std socket to non blocking. {:?}", e);
-        return None;
-      }
-    };
+    let std_socket = StdUdpSocket::bind(address)?;
+    std_socket.set_nonblocking(true)?;
+    let socket = UdpSocket::from_socket(std_socket)?;
+
+    Ok(UDPListener {
+      socket,
+      token,
+      capture: PacketCapture::default(),
+    })
+  }
 
-    let socket = match UdpSocket::from_socket(std_socket) {
-      Ok(s) => s,
-      Err(e) => {
-        error!("Failed to create mio socket. {:?}", e);
-        return None;
-      }
+  /// Binds a socket that other sockets of this host may bind to the same port as well,
+  /// e.g. the multicast sockets of other participants in this or other processes. Each
+  /// of them receives the multicast sent to the port.
+  pub fn try_bind_shared(token: Token, host: &str, port: u16) -> io::Result<UDPListener> {
+    let host = match host.parse() {
+      Ok(h) => h,
+      Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidInput, e)),
     };
-
-    Some(UDPListener { socket, token })
+    let address = SocketAddr::new(host, port);
+    let family = match address {
+      SocketAddr::V4(_) => AddressFamily::Inet,
+      SocketAddr::V6(_) => AddressFamily::Inet6,
+    };
+    let fd = socket::socket(family, SockType::Datagram, SockFlag::empty(), None)
+      .map_err(nix_to_io_error)?;
+    // closes the socket, if the rest fails
+    let std_socket = unsafe { StdUdpSocket::from_raw_fd(fd) };
+    let reuse: libc::c_int = 1;
+    set_socket_option(&std_socket, libc::SOL_SOCKET, libc::SO_REUSEADDR, &reuse)?;
+    // Linux shares the port with SO_REUSEADDR alone. With SO_REUSEPORT it would spread
+    // the unicast sent to the port among the sockets.
+    #[cfg(not(target_os = "linux"))]
+    set_socket_option(&std_socket, libc::SOL_SOCKET, libc::SO_REUSEPORT, &reuse)?;
+    socket::bind(fd, &SockAddr::new_inet(InetAddr::from_std(&address))).map_err(nix_to_io_error)?;
+    std_socket.set_nonblocking(true)?;
+    let socket = UdpSocket::from_socket(std_socket)?;
+
+    Ok(UDPListener {
+      socket,
+      token,
+      capture: PacketCapture::default(),
+    })
   }
 
   pub fn get_token(&self) -> Token {
     self.token
   }
 
+  /// Writes what is received by get_messages also to `capture`, while it is capturing.
+  pub fn set_capture(&mut self, capture: PacketCapture) {
+    self.capture = capture;
+  }
+
   pub fn mio_socket(&mut self) -> &mut UdpSocket {
     &mut self.socket
   }
@@ -88,6 +120,21 @@ impl UDPListener {
     }
   }
 
+  /// Sets the receive buffer size. Returns the size the kernel granted.
+  pub fn set_receive_buffer_size(&self, size: usize) -> io::Result<usize> {
+    set_socket_option(
+      &self.socket,
+      libc::SOL_SOCKET,
+      libc::SO_RCVBUF,
+      &(size as libc::c_int),
+    )?;
+    self.receive_buffer_size()
+  }
+
+  pub fn receive_buffer_size(&self) -> io::Result<usize> {
+    get_socket_option(&self.socket, libc::SOL_SOCKET, libc::SO_RCVBUF).map(|s| s as usize)
+  }
+
   /// Returns all messages that have come from listen_addresses.
   /// Converts/prunes individual results to Vec
   pub fn get_message(&self) -> Vec<u8> {
@@ -107,22 +154,34 @@ impl UDPListener {
     message
   }
 
-  pub fn get_messages(&self) -> Vec<Vec<u8>> {
+  /// Each message is copied once out of the receive buffer. The parsed payloads
+  /// share that allocation.
+  pub fn get_messages(&self) -> Vec<Bytes> {
     let mut datas = vec![];
     let mut buf: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
 
-    while let Ok(nbytes) = self.socket.recv(&mut buf) {
-      datas.push(buf[..nbytes].to_vec());
+    while let Ok((nbytes, source)) = self.socket.recv_from(&mut buf) {
+      if self.capture.is_active() {
+        if let Ok(local) = self.socket.local_addr() {
+          self
+            .capture
+            .received(Transport::Udp, &buf[..nbytes], source, local);
+        }
+      }
+      datas.push(Bytes::copy_from_slice(&buf[..nbytes]));
     }
 
     datas
   }
 
   pub fn join_multicast(&self, address: &Ipv4Addr) -> io::Result<()> {
+    self.join_multicast_on(address, &Ipv4Addr::UNSPECIFIED)
+  }
+
+  /// Joins on the interface that has address `interface`.
+  pub fn join_multicast_on(&self, address: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
     if address.is_multicast() {
-      return self
-        .socket
-        .join_multicast_v4(address, &Ipv4Addr::UNSPECIFIED);
+      return self.socket.join_multicast_v4(address, interface);
     }
     io::Result::Err(io::Error::new(
       io::ErrorKind::Other,
@@ -131,10 +190,35 @@ impl UDPListener {
   }
 
   pub fn leave_multicast(&self, address: &Ipv4Addr) -> io::Result<()> {
+    self.leave_multicast_on(address, &Ipv4Addr::UNSPECIFIED)
+  }
+
+  /// Leaves a group joined with `join_multicast_on`.
+  pub fn leave_multicast_on(&self, address: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+    if address.is_multicast() {
+      return self.socket.leave_multicast_v4(address, interface);
+    }
+    io::Result::Err(io::Error::new(
+      io::ErrorKind::Other,
+      "Not a multicast address",
+    ))
+  }
+
+  /// Joins on the interface with index `interface`, or the default one if 0. The
+  /// socket must be bound to an IPv6 address.
+  pub fn join_multicast_v6(&self, address: &Ipv6Addr, interface: u32) -> io::Result<()> {
+    if address.is_multicast() {
+      return self.socket.join_multicast_v6(address, interface);
+    }
+    io::Result::Err(io::Error::new(
+      io::ErrorKind::Other,
+      "Not a multicast address",
+    ))
+  }
+
+  pub fn leave_multicast_v6(&self, address: &Ipv6Addr, interface: u32) -> io::Result<()> {
     if address.is_multicast() {
-      return self
-        .socket
-        .leave_multicast_v4(address, &Ipv4Addr::UNSPECIFIED);
+      return self.socket.leave_multicast_v6(address, interface);
     }
     io::Result::Err(io::Error::new(
       io::ErrorKind::Other,
@@ -199,4 +283,62 @@ mod tests {
     assert_eq!(rec_data.len(), 3);
     assert_eq!(rec_data, data);
   }
+
+  #[test]
+  fn udpl_shared_multicast_port() {
+    let group = Ipv4Addr::new(239, 255, 0, 1);
+    let listeners: Vec<UDPListener> = (0..2)
+      .map(|_| UDPListener::try_bind_shared(Token(0), "0.0.0.0", 10004).unwrap())
+      .collect();
+    for listener in listeners.iter() {
+      listener.join_multicast(&group).unwrap();
+    }
+    // not shared with sockets that did not ask for it
+    assert!(UDPListener::try_bind(Token(0), "0.0.0.0", 10004).is_err());
+
+    let sender = UDPSender::new_with_random_port();
+    let data: Vec<u8> = vec![4, 8, 12];
+    sender.send_multicast(&data, group, 10004).unwrap();
+    thread::sleep(time::Duration::from_secs(1));
+
+    for listener in listeners.iter() {
+      assert_eq!(listener.get_message(), data);
+      listener.leave_multicast(&group).unwrap();
+    }
+  }
+
+  #[test]
+  fn udpl_receive_buffer_size() {
+    let listener = UDPListener::new(Token(0), "127.0.0.1", 0);
+    let granted = listener
+      .set_receive_buffer_size(128 * 1024)
+      .expect("Failed to set receive buffer size");
+    assert!(granted >= 128 * 1024);
+    assert_eq!(listener.receive_buffer_size().unwrap(), granted);
+  }
+
+  #[test]
+  fn udpl_ipv6_multicast_address() {
+    let listener = UDPListener::new(Token(0), "::", 10003);
+    let sender = UDPSender::new_with_random_port();
+    let group: Ipv6Addr = "ff02::ffff:239.255.0.1".parse().unwrap();
+
+    let data: Vec<u8> = vec![3, 6, 9];
+
+    listener
+      .join_multicast_v6(&group, 0)
+      .expect("Failed to join multicast.");
+
+    sender
+      .send_ipv6_multicast(&data, SocketAddr::new(group.into(), 10003))
+      .expect("Failed to send multicast");
+
+    thread::sleep(time::Duration::from_secs(1));
+
+    let rec_data = listener.get_message();
+
+    listener.leave_multicast_v6(&group, 0).unwrap();
+
+    assert_eq!(rec_data, data);
+  }
 }
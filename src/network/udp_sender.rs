@@ -1,13 +1,33 @@
 use log::debug;
 use mio::net::UdpSocket;
+use nix::libc;
 
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::io;
-use crate::structure::locator::{LocatorKind, LocatorList};
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(test)]
+use std::sync::atomic::AtomicUsize;
+use crate::{
+  network::{
+    capture::{PacketCapture, Transport},
+    util::{get_socket_option, is_network_down, set_socket_option},
+  },
+  structure::locator::{LocatorKind, LocatorList},
+};
 
+// Sends IPv4 through `socket`, and IPv6 through `socket_v6` if the host has IPv6.
 #[derive(Debug)]
 pub struct UDPSender {
   socket: UdpSocket,
+  socket_v6: Option<UdpSocket>,
+  capture: PacketCapture,
+  // a send failed because we had no network, since take_network_down
+  network_down: AtomicBool,
+  // datagrams sent so far, and the length of the largest, for tests to check
+  #[cfg(test)]
+  sent: AtomicUsize,
+  #[cfg(test)]
+  largest: AtomicUsize,
 }
 
 fn create_socket_to_available_port() -> Option<UdpSocket> {
@@ -21,22 +41,169 @@ fn create_socket_to_available_port() -> Option<UdpSocket> {
   None
 }
 
+fn create_ipv6_socket() -> Option<UdpSocket> {
+  let saddr: SocketAddr = SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0);
+  match UdpSocket::bind(&saddr) {
+    Ok(s) => Some(s),
+    Err(e) => {
+      debug!("No IPv6 socket, sending to IPv4 only. {:?}", e);
+      None
+    }
+  }
+}
+
 impl UDPSender {
   pub fn new(sender_port: u16) -> UDPSender {
     let saddr: SocketAddr = SocketAddr::new("0.0.0.0".parse().unwrap(), sender_port);
     let socket: UdpSocket = UdpSocket::bind(&saddr).unwrap();
 
-    UDPSender { socket: socket }
+    UDPSender {
+      socket: socket,
+      socket_v6: create_ipv6_socket(),
+      capture: PacketCapture::default(),
+      network_down: AtomicBool::new(false),
+      #[cfg(test)]
+      sent: AtomicUsize::new(0),
+      #[cfg(test)]
+      largest: AtomicUsize::new(0),
+    }
   }
 
   pub fn new_with_random_port() -> UDPSender {
     let socket: UdpSocket = create_socket_to_available_port().unwrap();
-    UDPSender { socket: socket }
+    UDPSender {
+      socket: socket,
+      socket_v6: create_ipv6_socket(),
+      capture: PacketCapture::default(),
+      network_down: AtomicBool::new(false),
+      #[cfg(test)]
+      sent: AtomicUsize::new(0),
+      #[cfg(test)]
+      largest: AtomicUsize::new(0),
+    }
+  }
+
+  /// Writes what is sent also to `capture`, while it is capturing.
+  pub fn set_capture(&mut self, capture: PacketCapture) {
+    self.capture = capture;
+  }
+
+  /// Sends IPv4 multicast through the interface that has address `interface`.
+  pub fn set_multicast_interface_v4(&self, interface: &Ipv4Addr) -> io::Result<()> {
+    let address = libc::in_addr {
+      s_addr: u32::from_ne_bytes(interface.octets()),
+    };
+    set_socket_option(
+      &self.socket,
+      libc::IPPROTO_IP,
+      libc::IP_MULTICAST_IF,
+      &address,
+    )
+  }
+
+  /// Sends IPv6 multicast through the interface with index `interface`.
+  pub fn set_multicast_interface_v6(&self, interface: u32) -> io::Result<()> {
+    match &self.socket_v6 {
+      Some(socket_v6) => set_socket_option(
+        socket_v6,
+        libc::IPPROTO_IPV6,
+        libc::IPV6_MULTICAST_IF,
+        &(interface as libc::c_int),
+      ),
+      None => Ok(()),
+    }
+  }
+
+  /// Sets the send buffer size of our sockets. Returns the size the kernel granted.
+  pub fn set_send_buffer_size(&self, size: usize) -> io::Result<usize> {
+    for socket in self.sockets() {
+      set_socket_option(
+        socket,
+        libc::SOL_SOCKET,
+        libc::SO_SNDBUF,
+        &(size as libc::c_int),
+      )?;
+    }
+    self.send_buffer_size()
+  }
+
+  pub fn send_buffer_size(&self) -> io::Result<usize> {
+    get_socket_option(&self.socket, libc::SOL_SOCKET, libc::SO_SNDBUF).map(|s| s as usize)
+  }
+
+  /// Time to live of IPv4 multicast, and hop limit of IPv6 multicast.
+  pub fn set_multicast_ttl(&self, ttl: u32) -> io::Result<()> {
+    self.socket.set_multicast_ttl_v4(ttl)?;
+    match &self.socket_v6 {
+      Some(socket_v6) => set_socket_option(
+        socket_v6,
+        libc::IPPROTO_IPV6,
+        libc::IPV6_MULTICAST_HOPS,
+        &(ttl as libc::c_int),
+      ),
+      None => Ok(()),
+    }
+  }
+
+  /// Whether our multicast is delivered to listeners on this host too.
+  pub fn set_multicast_loop(&self, on: bool) -> io::Result<()> {
+    self.socket.set_multicast_loop_v4(on)?;
+    match &self.socket_v6 {
+      Some(socket_v6) => socket_v6.set_multicast_loop_v6(on),
+      None => Ok(()),
+    }
+  }
+
+  /// Whether a send has failed because we had no network since the last call, e.g.
+  /// because the interface went down. The sockets may need to be replaced.
+  pub fn take_network_down(&self) -> bool {
+    self.network_down.swap(false, Ordering::Relaxed)
+  }
+
+  fn sockets(&self) -> impl Iterator<Item = &UdpSocket> {
+    std::iter::once(&self.socket).chain(self.socket_v6.iter())
+  }
+
+  #[cfg(test)]
+  pub fn sent_datagrams(&self) -> usize {
+    self.sent.load(Ordering::Relaxed)
+  }
+
+  #[cfg(test)]
+  pub fn largest_datagram(&self) -> usize {
+    self.largest.load(Ordering::Relaxed)
+  }
+
+  fn send_to(&self, buffer: &[u8], address: &SocketAddr) -> io::Result<usize> {
+    #[cfg(test)]
+    {
+      self.sent.fetch_add(1, Ordering::Relaxed);
+      self.largest.fetch_max(buffer.len(), Ordering::Relaxed);
+    }
+    let socket = match (address, &self.socket_v6) {
+      (SocketAddr::V4(_), _) => &self.socket,
+      (SocketAddr::V6(_), Some(socket_v6)) => socket_v6,
+      (SocketAddr::V6(_), None) => {
+        return io::Result::Err(io::Error::new(io::ErrorKind::Other, "No IPv6 socket"))
+      }
+    };
+    let sent = socket.send_to(buffer, address).map_err(|e| {
+      if is_network_down(&e) {
+        self.network_down.store(true, Ordering::Relaxed);
+      }
+      e
+    })?;
+    if self.capture.is_active() {
+      if let Ok(local) = socket.local_addr() {
+        self.capture.sent(Transport::Udp, buffer, local, *address);
+      }
+    }
+    Ok(sent)
   }
 
   pub fn send_to_all(&self, buffer: &[u8], addresses: &Vec<SocketAddr>) {
     for address in addresses.iter() {
-      match self.socket.send_to(buffer, address) {
+      match self.send_to(buffer, address) {
         Ok(_) => (),
         _ => debug!("Unable to send to {}", address),
       };
@@ -47,7 +214,7 @@ impl UDPSender {
     for l in locators {
       if l.kind == LocatorKind::LOCATOR_KIND_UDPv4 || l.kind == LocatorKind::LOCATOR_KIND_UDPv6 {
         let a = SocketAddr::from(l.to_socket_address());
-        match self.socket.send_to(buffer, &a) {
+        match self.send_to(buffer, &a) {
           Ok(_) => (),
           _ => debug!("Unable to send to {}", a),
         };
@@ -67,8 +234,18 @@ impl UDPSender {
   }
 
   pub fn send_ipv4_multicast(&self, buffer: &[u8], address: SocketAddr) -> io::Result<usize> {
-    if address.ip().is_multicast() {
-      return self.socket.send_to(buffer, &address);
+    if address.is_ipv4() && address.ip().is_multicast() {
+      return self.send_to(buffer, &address);
+    }
+    io::Result::Err(io::Error::new(
+      io::ErrorKind::Other,
+      "Not a multicast address",
+    ))
+  }
+
+  pub fn send_ipv6_multicast(&self, buffer: &[u8], address: SocketAddr) -> io::Result<usize> {
+    if address.is_ipv6() && address.ip().is_multicast() {
+      return self.send_to(buffer, &address);
     }
     io::Result::Err(io::Error::new(
       io::ErrorKind::Other,
@@ -81,6 +258,7 @@ impl UDPSender {
 mod tests {
   use super::*;
   use crate::network::udp_listener::*;
+  use crate::structure::locator::Locator;
   use mio::Token;
 
   #[test]
@@ -121,4 +299,54 @@ mod tests {
     assert_eq!(rec_data_2.len(), 6);
     assert_eq!(rec_data_2, data);
   }
+
+  #[test]
+  fn udps_ipv6_send() {
+    let listener_v4 = UDPListener::new(Token(0), "127.0.0.1", 10401);
+    let listener_v6 = UDPListener::new(Token(1), "::1", 10402);
+    let sender = UDPSender::new_with_random_port();
+
+    let data: Vec<u8> = vec![6, 6, 6];
+    let locators: LocatorList = vec![
+      Locator::from(SocketAddr::new("127.0.0.1".parse().unwrap(), 10401)),
+      Locator::from(SocketAddr::new("::1".parse().unwrap(), 10402)),
+    ];
+    sender.send_to_locator_list(&data, &locators);
+
+    assert_eq!(listener_v4.get_message(), data);
+    assert_eq!(listener_v6.get_message(), data);
+  }
+
+  #[test]
+  fn udps_multicast_interface() {
+    let sender = UDPSender::new_with_random_port();
+    sender
+      .set_multicast_interface_v4(&Ipv4Addr::LOCALHOST)
+      .expect("Failed to set multicast interface");
+    // not an address of this host
+    assert!(sender
+      .set_multicast_interface_v4(&Ipv4Addr::new(192, 0, 2, 99))
+      .is_err());
+    sender
+      .set_multicast_interface_v6(0)
+      .expect("Failed to set multicast interface");
+  }
+
+  #[test]
+  fn udps_socket_options() {
+    let sender = UDPSender::new_with_random_port();
+    let granted = sender
+      .set_send_buffer_size(128 * 1024)
+      .expect("Failed to set send buffer size");
+    assert!(granted >= 128 * 1024);
+    assert_eq!(sender.send_buffer_size().unwrap(), granted);
+    sender
+      .set_multicast_ttl(2)
+      .expect("Failed to set multicast TTL");
+    assert_eq!(sender.socket.multicast_ttl_v4().unwrap(), 2);
+    sender
+      .set_multicast_loop(false)
+      .expect("Failed to set multicast loopback");
+    assert!(!sender.socket.multicast_loop_v4().unwrap());
+  }
 }
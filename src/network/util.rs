@@ -1,35 +1,363 @@
+use nix::libc;
+
 use std::{
-  net::{SocketAddr, IpAddr},
-  io::Error,
+  net::{SocketAddr, SocketAddrV4, IpAddr, Ipv4Addr, Ipv6Addr},
+  io::{self, Error},
+  os::unix::io::AsRawFd,
+};
+
+use crate::{
+  discovery::discovery_config::IpFamily,
+  structure::{
+    guid::{EntityId, GuidPrefix, GUID},
+    locator::{LocatorKind, LocatorList, Locator},
+  },
 };
 
-use crate::structure::locator::{LocatorList, Locator};
+// SPDP multicast groups. The IPv6 ones embed the IPv4 group, as in other DDS
+// implementations.
+pub const MULTICAST_GROUP_V4: Ipv4Addr = Ipv4Addr::new(239, 255, 0, 1);
+pub const MULTICAST_GROUPS_V6: [Ipv6Addr; 2] = [
+  Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0xffff, 0xefff, 0x0001),
+  Ipv6Addr::new(0xff03, 0, 0, 0, 0, 0xffff, 0xefff, 0x0001),
+];
+
+// Address to bind our listening sockets to. An IPv6 socket also receives IPv4.
+pub fn unspecified_address(ip_families: &[IpFamily]) -> IpAddr {
+  if ip_families.contains(&IpFamily::V6) {
+    IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+  } else {
+    IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+  }
+}
+
+pub fn get_local_multicast_locators(port: u16, ip_families: &[IpFamily]) -> LocatorList {
+  ip_families
+    .iter()
+    .map(|family| match family {
+      IpFamily::V4 => IpAddr::V4(MULTICAST_GROUP_V4),
+      IpFamily::V6 => IpAddr::V6(MULTICAST_GROUPS_V6[0]),
+    })
+    .map(|ip| Locator::from(SocketAddr::new(ip, port)))
+    .collect()
+}
 
-pub fn get_local_multicast_locators(port: u16) -> LocatorList {
-  let saddr = SocketAddr::new("239.255.0.1".parse().unwrap(), port);
-  vec![Locator::from(saddr)]
+// Multicast group for the user data of a topic, the same in all participants of the
+// domain. It is in 239.255.1.0 - 239.255.255.255, clear of the SPDP group. IPv6 groups
+// embed the IPv4 one, like the SPDP groups do.
+pub fn topic_multicast_group(domain_id: u16, topic_name: &str, ip_family: IpFamily) -> IpAddr {
+  // FNV-1a. Unlike the std hasher, it stays the same across Rust versions.
+  let mut hash: u32 = 0x811c_9dc5;
+  for byte in domain_id.to_be_bytes().iter().chain(topic_name.as_bytes()) {
+    hash = (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193);
+  }
+  let group = Ipv4Addr::new(239, 255, 1 + (hash % 255) as u8, (hash >> 8) as u8);
+  match ip_family {
+    IpFamily::V4 => IpAddr::V4(group),
+    IpFamily::V6 => {
+      let [a, b, c, d] = group.octets();
+      IpAddr::V6(Ipv6Addr::new(
+        0xff02,
+        0,
+        0,
+        0,
+        0,
+        0xffff,
+        u16::from_be_bytes([a, b]),
+        u16::from_be_bytes([c, d]),
+      ))
+    }
+  }
 }
 
-pub fn get_local_unicast_socket_address(port: u16) -> LocatorList {
+// One address of each family, in order of preference. Loopback and IPv6 link-local
+// addresses are not usable by remote participants.
+pub fn get_local_unicast_socket_address(port: u16, ip_families: &[IpFamily]) -> LocatorList {
   let local_ips: Result<Vec<IpAddr>, Error> = get_if_addrs::get_if_addrs().map(|p| {
     p.iter()
       .filter(|ip| !ip.is_loopback())
       .map(|ip| ip.ip())
+      .filter(|ip| match ip {
+        IpAddr::V4(_) => true,
+        IpAddr::V6(ip6) => ip6.segments()[0] & 0xffc0 != 0xfe80,
+      })
       .collect()
   });
 
   match local_ips {
-    Ok(ips) => {
-      let loc = ips
-        .into_iter()
-        .map(|p| SocketAddr::new(p, port))
-        .map(|p| Locator::from(p))
-        .next();
-      match loc {
-        Some(l) => vec![l],
-        None => vec![],
+    Ok(ips) => ip_families
+      .iter()
+      .filter_map(|family| ips.iter().find(|ip| IpFamily::of(ip) == *family))
+      .map(|ip| Locator::from(SocketAddr::new(*ip, port)))
+      .collect(),
+    _ => vec![],
+  }
+}
+
+// Locators of a remote endpoint or participant that we can use. If it announces
+// several of our families, only those of the most preferred one are kept.
+pub fn select_locators(locators: &LocatorList, ip_families: &[IpFamily]) -> LocatorList {
+  for family in ip_families {
+    let kind = match family {
+      IpFamily::V4 => LocatorKind::LOCATOR_KIND_UDPv4,
+      IpFamily::V6 => LocatorKind::LOCATOR_KIND_UDPv6,
+    };
+    let selected: LocatorList = locators
+      .iter()
+      .filter(|l| l.kind == kind)
+      .cloned()
+      .collect();
+    if !selected.is_empty() {
+      return selected;
+    }
+  }
+  vec![]
+}
+
+// Locator of the shared memory segment of an endpoint: the namespace, that is, which
+// /dev/shm it is in, the guid prefix of its participant, and its entity id as the port.
+pub fn shm_locator(namespace: u32, guid: GUID) -> Locator {
+  let mut address = [0; 16];
+  address[..4].copy_from_slice(&namespace.to_be_bytes());
+  address[4..].copy_from_slice(&guid.guidPrefix.entityKey);
+  let id = guid.entityId;
+  let [k0, k1, k2] = id.entityKey;
+  Locator {
+    kind: LocatorKind::LOCATOR_KIND_SHM,
+    port: u32::from_be_bytes([k0, k1, k2, id.entityKind]),
+    address,
+  }
+}
+
+pub fn shm_locator_guid(locator: &Locator) -> GUID {
+  let [k0, k1, k2, kind] = locator.port.to_be_bytes();
+  GUID::new_with_prefix_and_id(
+    GuidPrefix::new(locator.address[4..].to_vec()),
+    EntityId::createCustomEntityID([k0, k1, k2], kind),
+  )
+}
+
+// Shared memory locators of an endpoint that we can use, the ones in our namespace.
+pub fn same_host_shm_locators(locators: &LocatorList, namespace: Option<u32>) -> LocatorList {
+  let namespace = match namespace {
+    Some(ns) => ns.to_be_bytes(),
+    None => return vec![],
+  };
+  locators
+    .iter()
+    .filter(|l| l.kind == LocatorKind::LOCATOR_KIND_SHM && l.address[..4] == namespace)
+    .cloned()
+    .collect()
+}
+
+// Locator of a participant reachable through TCP. The guid prefix of the participant
+// goes to the first 12 bytes, which IPv4 leaves unused, so that the locator finds the
+// connection to the participant whichever end opened it. Port 0 if the participant
+// accepts no connections.
+pub fn tcp_locator(guid_prefix: GuidPrefix, address: SocketAddrV4) -> Locator {
+  let mut bytes = [0; 16];
+  bytes[..12].copy_from_slice(&guid_prefix.entityKey);
+  bytes[12..].copy_from_slice(&address.ip().octets());
+  Locator {
+    kind: LocatorKind::LOCATOR_KIND_TCPv4,
+    port: u32::from(address.port()),
+    address: bytes,
+  }
+}
+
+pub fn tcp_locator_parts(locator: &Locator) -> (GuidPrefix, SocketAddrV4) {
+  let a = &locator.address;
+  (
+    GuidPrefix::new(a[..12].to_vec()),
+    SocketAddrV4::new(
+      Ipv4Addr::new(a[12], a[13], a[14], a[15]),
+      locator.port as u16,
+    ),
+  )
+}
+
+// Sending to this reaches every participant we have a TCP connection with.
+pub fn tcp_all_connections_locator() -> Locator {
+  tcp_locator(
+    GuidPrefix::GUIDPREFIX_UNKNOWN,
+    SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0),
+  )
+}
+
+pub fn set_socket_option<S: AsRawFd, T>(
+  socket: &S,
+  level: libc::c_int,
+  name: libc::c_int,
+  value: &T,
+) -> io::Result<()> {
+  let result = unsafe {
+    libc::setsockopt(
+      socket.as_raw_fd(),
+      level,
+      name,
+      value as *const T as *const libc::c_void,
+      std::mem::size_of::<T>() as libc::socklen_t,
+    )
+  };
+  if result == 0 {
+    Ok(())
+  } else {
+    Err(io::Error::last_os_error())
+  }
+}
+
+// For options with an int value
+pub fn get_socket_option<S: AsRawFd>(
+  socket: &S,
+  level: libc::c_int,
+  name: libc::c_int,
+) -> io::Result<libc::c_int> {
+  let mut value: libc::c_int = 0;
+  let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+  let result = unsafe {
+    libc::getsockopt(
+      socket.as_raw_fd(),
+      level,
+      name,
+      &mut value as *mut libc::c_int as *mut libc::c_void,
+      &mut len,
+    )
+  };
+  if result == 0 {
+    Ok(value)
+  } else {
+    Err(io::Error::last_os_error())
+  }
+}
+
+pub fn nix_to_io_error(e: nix::Error) -> io::Error {
+  match e.as_errno() {
+    Some(errno) => io::Error::from_raw_os_error(errno as i32),
+    None => io::Error::new(io::ErrorKind::Other, e),
+  }
+}
+
+// Whether sending failed because we have no network, e.g. the interface is down or has
+// lost its address.
+pub fn is_network_down(e: &io::Error) -> bool {
+  matches!(
+    e.raw_os_error(),
+    Some(libc::ENETUNREACH) | Some(libc::ENETDOWN) | Some(libc::EADDRNOTAVAIL)
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn locators(addresses: &[&str]) -> LocatorList {
+    addresses
+      .iter()
+      .map(|a| Locator::from(a.parse::<SocketAddr>().unwrap()))
+      .collect()
+  }
+
+  #[test]
+  fn util_select_locators() {
+    let announced = locators(&["10.0.0.1:7411", "[fd00::1]:7411", "10.0.0.2:7411"]);
+    let mut shm = Locator::from("10.0.0.3:7411".parse::<SocketAddr>().unwrap());
+    shm.kind = LocatorKind::LOCATOR_KIND_RESERVED;
+    let mut with_shm = announced.clone();
+    with_shm.insert(0, shm);
+
+    assert_eq!(
+      select_locators(&with_shm, &[IpFamily::V4]),
+      locators(&["10.0.0.1:7411", "10.0.0.2:7411"])
+    );
+    assert_eq!(
+      select_locators(&announced, &[IpFamily::V6, IpFamily::V4]),
+      locators(&["[fd00::1]:7411"])
+    );
+    // falls back to the less preferred family
+    assert_eq!(
+      select_locators(&locators(&["10.0.0.1:7411"]), &[IpFamily::V6, IpFamily::V4]),
+      locators(&["10.0.0.1:7411"])
+    );
+    assert!(select_locators(&locators(&["10.0.0.1:7411"]), &[IpFamily::V6]).is_empty());
+  }
+
+  #[test]
+  fn util_local_multicast_locators() {
+    assert_eq!(
+      get_local_multicast_locators(7400, &[IpFamily::V6, IpFamily::V4]),
+      locators(&["[ff02::ffff:239.255.0.1]:7400", "239.255.0.1:7400"])
+    );
+    assert_eq!(
+      unspecified_address(&[IpFamily::V4]),
+      "0.0.0.0".parse::<IpAddr>().unwrap()
+    );
+    assert_eq!(
+      unspecified_address(&[IpFamily::V4, IpFamily::V6]),
+      "::".parse::<IpAddr>().unwrap()
+    );
+  }
+
+  #[test]
+  fn util_shm_locators() {
+    let guid = GUID::new_with_prefix_and_id(
+      GuidPrefix::new(vec![7; 12]),
+      EntityId::createCustomEntityID([1, 2, 3], 0x02),
+    );
+    let shm = shm_locator(0xabcd_0123, guid);
+    assert_eq!(shm_locator_guid(&shm), guid);
+
+    let mut announced = locators(&["10.0.0.1:7411"]);
+    announced.push(shm);
+    announced.push(shm_locator(0x1111_1111, guid));
+    assert_eq!(
+      same_host_shm_locators(&announced, Some(0xabcd_0123)),
+      vec![shm]
+    );
+    assert!(same_host_shm_locators(&announced, None).is_empty());
+    // not an IP locator
+    assert_eq!(
+      select_locators(&announced, &[IpFamily::V4]),
+      locators(&["10.0.0.1:7411"])
+    );
+  }
+
+  #[test]
+  fn util_tcp_locators() {
+    let prefix = GuidPrefix::new(vec![9; 12]);
+    let address = "192.168.4.1:7500".parse::<SocketAddrV4>().unwrap();
+    let locator = tcp_locator(prefix, address);
+    assert_eq!(tcp_locator_parts(&locator), (prefix, address));
+    assert!(select_locators(&vec![locator], &[IpFamily::V4]).is_empty());
+
+    let (prefix, address) = tcp_locator_parts(&tcp_all_connections_locator());
+    assert_eq!(prefix, GuidPrefix::GUIDPREFIX_UNKNOWN);
+    assert_eq!(address.port(), 0);
+  }
+
+  #[test]
+  fn util_topic_multicast_group() {
+    let group = topic_multicast_group(0, "video", IpFamily::V4);
+    assert_eq!(group, topic_multicast_group(0, "video", IpFamily::V4));
+    match group {
+      IpAddr::V4(g) => {
+        assert!(g.is_multicast());
+        assert_eq!(g.octets()[..2], [239, 255]);
+        assert_ne!(g.octets()[2], 0);
       }
+      IpAddr::V6(_) => panic!("IPv4 group expected"),
     }
-    _ => vec![],
+    assert_ne!(group, topic_multicast_group(1, "video", IpFamily::V4));
+    assert_ne!(group, topic_multicast_group(0, "audio", IpFamily::V4));
+
+    let group_v6 = topic_multicast_group(0, "video", IpFamily::V6);
+    assert!(group_v6.is_multicast());
+    let embedded = match (group, group_v6) {
+      (IpAddr::V4(g), IpAddr::V6(g6)) => {
+        assert_eq!(g6.segments()[..6], [0xff02, 0, 0, 0, 0, 0xffff]);
+        g6.octets()[12..] == g.octets()
+      }
+      _ => false,
+    };
+    assert!(embedded);
   }
 }
@@ -0,0 +1,33 @@
+//! The supported public API, for glob import.
+//!
+//! ```
+//! use rustdds::prelude::*;
+//!
+//! let domain_participant = DomainParticipant::new(0).unwrap();
+//! let qos = QosPolicyBuilder::new()
+//!   .reliability(Reliability::BestEffort)
+//!   .build();
+//! let topic = domain_participant
+//!   .create_topic("some_topic", "SomeType", &qos, TopicKind::NoKey)
+//!   .unwrap();
+//! ```
+//!
+//! Error types are left out, as `Result` would hide the one of the standard
+//! library. They are in [rustdds::dds::error](../dds/error/index.html).
+
+pub use crate::dds::{
+  data_types::{
+    DiscoveredReaderData, DiscoveredTopicData, DiscoveredWriterData, Duration, EntityId,
+    GuidPrefix, InstanceHandle, InstanceState, ReadCondition, SampleInfo, SampleState, SelectByKey,
+    Timestamp, TopicKind, ViewState, GUID,
+  },
+  qos::{policy::*, QosPolicies, QosPolicyBuilder},
+  statuses::*,
+  traits::{DdsType, Key, Keyed, TopicDescription},
+  DiscoveryConfig, DomainParticipant, DomainParticipantBuilder, NoKeyDataReader, NoKeyDataWriter,
+  ParticipantConfig, Publisher, Subscriber, Topic, WithKeyDataReader, WithKeyDataWriter,
+};
+pub use crate::serialization::{
+  CDRDeserializerAdapter, CDRSerializerAdapter, DeserializerAdapter, SerializerAdapter,
+  Xcdr2DeserializerAdapter, Xcdr2SerializerAdapter,
+};
@@ -1,3 +1,5 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use serde::{Serialize, Deserialize};
 
 use crate::{
@@ -57,6 +59,14 @@ impl NodeInfo {
     name
   }
 
+  pub fn get_reader_gids(&self) -> &[Gid] {
+    &self.reader_guid
+  }
+
+  pub fn get_writer_gids(&self) -> &[Gid] {
+    &self.writer_guid
+  }
+
   pub fn add_writer(&mut self, gid: Gid) {
     if !self.writer_guid.contains(&gid) {
       self.writer_guid.push(gid);
@@ -135,10 +145,43 @@ pub struct ParameterValue {
   string_array: Vec<String>,
 }
 
-/// Rosout message structure, received from RosParticipant rosout reader
+/// ROS 2 time (`builtin_interfaces/msg/Time`): seconds and nanoseconds since the Unix epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Time {
+  sec: i32,
+  nanosec: u32,
+}
+
+impl Time {
+  pub fn now() -> Time {
+    Time::from(SystemTime::now())
+  }
+
+  pub fn get_sec(&self) -> i32 {
+    self.sec
+  }
+
+  pub fn get_nanosec(&self) -> u32 {
+    self.nanosec
+  }
+}
+
+impl From<SystemTime> for Time {
+  fn from(time: SystemTime) -> Time {
+    // times before the epoch are not representable
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Time {
+      sec: since_epoch.as_secs() as i32,
+      nanosec: since_epoch.subsec_nanos(),
+    }
+  }
+}
+
+/// Rosout message structure (`rcl_interfaces/msg/Log`), received from RosParticipant
+/// rosout reader
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Log {
-  timestamp: Timestamp,
+  timestamp: Time,
   level: u8,
   name: String,
   msg: String,
@@ -148,8 +191,38 @@ pub struct Log {
 }
 
 impl Log {
+  pub const DEBUG: u8 = 10;
+  pub const INFO: u8 = 20;
+  pub const WARN: u8 = 30;
+  pub const ERROR: u8 = 40;
+  pub const FATAL: u8 = 50;
+
+  /// # Arguments
+  ///
+  /// * `level` - One of [DEBUG](#associatedconstant.DEBUG) ... [FATAL](#associatedconstant.FATAL)
+  /// * `name` - Name of the logger, usually the name of the node
+  pub fn new(
+    timestamp: Time,
+    level: u8,
+    name: String,
+    msg: String,
+    file: String,
+    function: String,
+    line: u32,
+  ) -> Log {
+    Log {
+      timestamp,
+      level,
+      name,
+      msg,
+      file,
+      function,
+      line,
+    }
+  }
+
   /// Timestamp when rosout message was sent
-  pub fn get_timestamp(&self) -> &Timestamp {
+  pub fn get_timestamp(&self) -> &Time {
     &self.timestamp
   }
 
@@ -7,6 +7,8 @@ use crate::{
   structure::duration::Duration,
 };
 
+use super::qos;
+
 pub struct ROSDiscoveryTopic {}
 
 impl ROSDiscoveryTopic {
@@ -31,6 +33,8 @@ impl ROSDiscoveryTopic {
     lifespan: Some(Lifespan {
       duration: Duration::DURATION_INFINITE,
     }),
+    reader_data_lifecycle: None,
+    writer_data_lifecycle: None,
   };
 
   const TOPIC_NAME: &'static str = "ros_discovery_info";
@@ -52,23 +56,6 @@ impl ROSDiscoveryTopic {
 pub struct ParameterEventsTopic {}
 
 impl ParameterEventsTopic {
-  const QOS: QosPolicies = QosPolicies {
-    durability: Some(Durability::TransientLocal),
-    presentation: None,
-    deadline: None,
-    latency_budget: None,
-    ownership: None,
-    liveliness: None,
-    time_based_filter: None,
-    reliability: Some(Reliability::Reliable {
-      max_blocking_time: Duration::DURATION_ZERO,
-    }),
-    destination_order: None,
-    history: Some(History::KeepLast { depth: 1 }),
-    resource_limits: None,
-    lifespan: None,
-  };
-
   const TOPIC_NAME: &'static str = "rt/parameter_events";
   const TYPE_NAME: &'static str = "rcl_interfaces::msg::dds_::ParameterEvent_";
 
@@ -80,37 +67,14 @@ impl ParameterEventsTopic {
     ParameterEventsTopic::TYPE_NAME
   }
 
-  pub fn get_qos() -> QosPolicies {
-    ParameterEventsTopic::QOS
+  pub const fn get_qos() -> QosPolicies {
+    qos::parameter_events()
   }
 }
 
 pub struct RosOutTopic {}
 
 impl RosOutTopic {
-  const QOS: QosPolicies = QosPolicies {
-    durability: Some(Durability::TransientLocal),
-    presentation: None,
-    deadline: Some(Deadline(Duration::DURATION_INFINITE)),
-    latency_budget: Some(LatencyBudget {
-      duration: Duration::DURATION_ZERO,
-    }),
-    ownership: Some(Ownership::Shared),
-    liveliness: Some(Liveliness::Automatic {
-      lease_duration: Duration::DURATION_INFINITE,
-    }),
-    time_based_filter: None,
-    reliability: Some(Reliability::Reliable {
-      max_blocking_time: Duration::DURATION_ZERO,
-    }),
-    destination_order: Some(DestinationOrder::ByReceptionTimestamp),
-    history: Some(History::KeepLast { depth: 1 }),
-    resource_limits: None,
-    lifespan: Some(Lifespan {
-      duration: Duration::from_secs(10),
-    }),
-  };
-
   const TOPIC_NAME: &'static str = "rt/rosout";
   const TYPE_NAME: &'static str = "rcl_interfaces::msg::dds_::Log_";
 
@@ -122,7 +86,7 @@ impl RosOutTopic {
     RosOutTopic::TYPE_NAME
   }
 
-  pub fn get_qos() -> QosPolicies {
-    RosOutTopic::QOS
+  pub const fn get_qos() -> QosPolicies {
+    qos::rosout()
   }
 }
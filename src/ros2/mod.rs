@@ -5,7 +5,6 @@
 //! ```
 //! use rustdds::dds::DomainParticipant;
 //! use rustdds::dds::data_types::TopicKind;
-//! use rustdds::dds::traits::Entity;
 //! use rustdds::ros2::RosContext;
 //! use rustdds::ros2::RosParticipant;
 //! use rustdds::ros2::NodeOptions;
@@ -13,19 +12,19 @@
 //! use rustdds::ros2::IRosNodeControl;
 //! use rustdds::ros2::RosNodeBuilder;
 //! use rustdds::ros2::builtin_datatypes::NodeInfo;
-//! use rustdds::dds::qos::QosPolicies;
+//! use rustdds::ros2::qos;
 //! use rustdds::serialization::CDRSerializerAdapter;
 //!
 //!
 //! // DomainParticipant is always needed
-//! let domain_participant = DomainParticipant::new(0);
+//! let domain_participant = DomainParticipant::new(0).unwrap();
 //!
 //! // RosContext should be defined for each thread and second parameter set true if RosParticipant
 //! // is handled in this thread
 //! let ros_context = RosContext::new(domain_participant.clone(), true).unwrap();
 //!
 //! // RosParticipant is needed for defined RosNodes to be visible in ROS2 network.
-//! let mut ros_participant = RosParticipant::new(&ros_context).unwrap();
+//! let ros_participant = RosParticipant::new(&ros_context).unwrap();
 //!
 //! // Node options simply adjust configuration of the node (as in ROS Client library (RCL)).
 //! let ros_node_options = NodeOptions::new(domain_participant.domain_id(), false);
@@ -34,8 +33,8 @@
 //! let some_topic = RosNode::create_ros_topic(
 //!     &domain_participant,
 //!     "some_topic_name",
-//!     "NodeInfo",
-//!     QosPolicies::builder().build(),
+//!     "rmw_dds_common/msg/NodeEntitiesInfo",
+//!     qos::default(),
 //!     TopicKind::NoKey)
 //!   .unwrap();
 //!
@@ -47,6 +46,8 @@
 //!     .namespace("/some_namespace")
 //!     .node_options(ros_node_options)
 //!     .ros_context(&ros_context)
+//!     // announces the node, so that others in the ROS2 network can find it
+//!     .ros_participant(&ros_participant)
 //!     .build()
 //!     .unwrap();
 //!
@@ -56,15 +57,11 @@
 //!       &some_topic, None)
 //!     .unwrap();
 //!
-//!   // RosNode needs to be updated manually about our writers and readers (lifetime magic)
-//!   ros_node.add_writer(some_writer.get_guid());
+//!   // The writer is now part of the announced node info. Readers and RosParticipant implement
+//!   // mio Evented trait and thus function the same way as std::sync::mpcs and can be handled
+//!   // the same way for reading the data
 //!
-//!   // Readers and RosParticipant implement mio Evented trait and thus function the same way as
-//!   // std::sync::mpcs and can be handled the same way for reading the data
-//!
-//!   // When you add or remove nodes remember to update RosParticipant so others in the ROS2
-//!   // network can find your nodes
-//!   ros_participant.add_node_info(ros_node.generate_node_info());
+//!   // Dropping the node removes it from the ROS2 network
 //! }
 //! ```
 
@@ -72,10 +69,16 @@
 pub mod builtin_datatypes;
 /// Some convenience topic infos for ROS2 communication
 pub mod builtin_topics;
+pub mod names;
+pub mod qos;
 
 pub(crate) mod ros_node;
+pub(crate) mod rosout;
+pub(crate) mod service;
 
 pub use ros_node::*;
+pub use rosout::RosoutLogger;
+pub use service::{ReplyToken, RequestId, RosServiceClient, RosServiceServer, RosServiceTopics};
 
 pub type RosSubscriber<'a, D, DA> = crate::dds::no_key::datareader::DataReader<'a, D, DA>;
 
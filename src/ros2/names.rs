@@ -0,0 +1,324 @@
+//! Mapping of ROS 2 topic, service and type names to DDS topic and type names
+//! and back, following the conventions of the rmw implementations.
+//!
+//! ROS topic `/turtle1/cmd_vel` is DDS topic `rt/turtle1/cmd_vel`, and ROS type
+//! `geometry_msgs/msg/Twist` is DDS type `geometry_msgs::msg::dds_::Twist_`.
+//! Service `/add_two_ints` is DDS topics `rq/add_two_intsRequest` and
+//! `rr/add_two_intsReply`.
+
+use crate::dds::values::result::Error;
+
+/// ROS name of the topic where nodes publish changes to their parameters
+pub const PARAMETER_EVENTS: &str = "/parameter_events";
+/// ROS name of the topic where nodes publish their log messages
+pub const ROSOUT: &str = "/rosout";
+/// Names of the parameter services each node has, relative to the node (`~/`).
+pub const PARAMETER_SERVICES: [&str; 6] = [
+  "describe_parameters",
+  "get_parameter_types",
+  "get_parameters",
+  "list_parameters",
+  "set_parameters",
+  "set_parameters_atomically",
+];
+
+const TOPIC_PREFIX: &str = "rt";
+const REQUEST_PREFIX: &str = "rq";
+const REPLY_PREFIX: &str = "rr";
+const REQUEST_SUFFIX: &str = "Request";
+const REPLY_SUFFIX: &str = "Reply";
+
+/// What kind of ROS name a DDS topic name is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RosNameKind {
+  Topic,
+  ServiceRequest,
+  ServiceReply,
+}
+
+/// Resolves a ROS topic or service name into a fully qualified name.
+///
+/// Names starting with `/` are absolute, names starting with `~` are private
+/// to the node and other names are relative to the namespace of the node.
+///
+/// # Examples
+/// ```
+/// # use rustdds::ros2::names::fully_qualified_name;
+/// assert_eq!(fully_qualified_name("/ns", "node", "chatter").unwrap(), "/ns/chatter");
+/// assert_eq!(fully_qualified_name("/ns", "node", "/chatter").unwrap(), "/chatter");
+/// assert_eq!(fully_qualified_name("/ns", "node", "~/state").unwrap(), "/ns/node/state");
+/// ```
+pub fn fully_qualified_name(namespace: &str, node_name: &str, name: &str) -> Result<String, Error> {
+  let namespace = namespace.trim_end_matches('/');
+  let fqn = if name.starts_with('/') {
+    name.to_string()
+  } else if let Some(private) = name.strip_prefix('~') {
+    if !(private.is_empty() || private.starts_with('/')) {
+      return Err(Error::bad_parameter(format!(
+        "private ROS name {:?} has to start with \"~/\"",
+        name
+      )));
+    }
+    format!("{}/{}{}", namespace, node_name, private)
+  } else {
+    format!("{}/{}", namespace, name)
+  };
+  validate_name(&fqn)?;
+  Ok(fqn)
+}
+
+/// Is the ROS name hidden, i.e. does any of its parts start with `_`.
+/// Hidden topics are not listed by default by tools like `ros2 topic list`.
+pub fn is_hidden(name: &str) -> bool {
+  name.split('/').any(|token| token.starts_with('_'))
+}
+
+/// DDS topic name of a ROS topic. Relative names are resolved against the
+/// root namespace.
+pub fn ros_topic_to_dds(name: &str) -> Result<String, Error> {
+  Ok(format!("{}{}", TOPIC_PREFIX, absolute(name)?))
+}
+
+/// DDS topic names of the request and the reply topic of a ROS service.
+/// Relative names are resolved against the root namespace.
+pub fn ros_service_to_dds(name: &str) -> Result<(String, String), Error> {
+  let name = absolute(name)?;
+  Ok((
+    format!("{}{}{}", REQUEST_PREFIX, name, REQUEST_SUFFIX),
+    format!("{}{}{}", REPLY_PREFIX, name, REPLY_SUFFIX),
+  ))
+}
+
+/// ROS name of a DDS topic, or `None` if the topic is not a ROS topic or
+/// service topic.
+pub fn dds_topic_to_ros(dds_name: &str) -> Option<(RosNameKind, String)> {
+  let (kind, name) = if let Some(name) = dds_name.strip_prefix(TOPIC_PREFIX) {
+    (RosNameKind::Topic, name)
+  } else if let Some(name) = dds_name
+    .strip_prefix(REQUEST_PREFIX)
+    .and_then(|n| n.strip_suffix(REQUEST_SUFFIX))
+  {
+    (RosNameKind::ServiceRequest, name)
+  } else if let Some(name) = dds_name
+    .strip_prefix(REPLY_PREFIX)
+    .and_then(|n| n.strip_suffix(REPLY_SUFFIX))
+  {
+    (RosNameKind::ServiceReply, name)
+  } else {
+    return None;
+  };
+  if name.starts_with('/') && validate_name(name).is_ok() {
+    Some((kind, name.to_string()))
+  } else {
+    None
+  }
+}
+
+/// DDS type name of a ROS message type, e.g. `geometry_msgs/msg/Twist` or
+/// `geometry_msgs/Twist`. Names that already are DDS type names are returned
+/// as they are.
+pub fn ros_type_to_dds(type_name: &str) -> Result<String, Error> {
+  if type_name.contains("::dds_::") {
+    return Ok(type_name.to_string());
+  }
+  let (package, interface, name) = split_type(type_name, "msg")?;
+  Ok(format!("{}::{}::dds_::{}_", package, interface, name))
+}
+
+/// DDS type names of the request and the response of a ROS service type, e.g.
+/// `example_interfaces/srv/AddTwoInts`.
+pub fn ros_service_type_to_dds(type_name: &str) -> Result<(String, String), Error> {
+  let (package, interface, name) = split_type(type_name, "srv")?;
+  let prefix = format!("{}::{}::dds_::{}", package, interface, name);
+  Ok((
+    format!("{}_Request_", prefix),
+    format!("{}_Response_", prefix),
+  ))
+}
+
+/// ROS type name of a DDS type, or `None` if the type does not follow the ROS
+/// naming convention.
+pub fn dds_type_to_ros(dds_type_name: &str) -> Option<String> {
+  let (namespace, name) = dds_type_name.split_once("::dds_::")?;
+  let name = name.strip_suffix('_')?;
+  if namespace.is_empty() || name.is_empty() {
+    return None;
+  }
+  Some(format!("{}/{}", namespace.replace("::", "/"), name))
+}
+
+fn absolute(name: &str) -> Result<String, Error> {
+  fully_qualified_name("/", "", name)
+}
+
+// Splits "package/interface/Name" or "package/Name"
+fn split_type<'a>(
+  type_name: &'a str,
+  default_interface: &'a str,
+) -> Result<(&'a str, &'a str, &'a str), Error> {
+  let parts: Vec<&str> = type_name.split('/').collect();
+  let (package, interface, name) = match parts.as_slice() {
+    [package, name] => (*package, default_interface, *name),
+    [package, interface, name] => (*package, *interface, *name),
+    _ => {
+      return Err(Error::bad_parameter(format!(
+        "{:?} is not a ROS type name",
+        type_name
+      )))
+    }
+  };
+  if [package, interface, name].iter().all(|p| valid_token(p)) {
+    Ok((package, interface, name))
+  } else {
+    Err(Error::bad_parameter(format!(
+      "{:?} is not a ROS type name",
+      type_name
+    )))
+  }
+}
+
+fn validate_name(fqn: &str) -> Result<(), Error> {
+  let valid = match fqn.strip_prefix('/') {
+    Some(rest) => rest.split('/').all(valid_token),
+    None => false,
+  };
+  if valid {
+    Ok(())
+  } else {
+    Err(Error::bad_parameter(format!(
+      "{:?} is not a valid ROS name",
+      fqn
+    )))
+  }
+}
+
+// Alphanumerics and underscores, not starting with a number
+fn valid_token(token: &str) -> bool {
+  match token.chars().next() {
+    Some(c) if !c.is_ascii_digit() => token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ros2::builtin_topics::{ParameterEventsTopic, RosOutTopic};
+
+  // Names as seen on the wire from rmw_fastrtps_cpp (ROS 2 Foxy)
+  #[test]
+  fn names_match_rmw_fastrtps() {
+    assert_eq!(
+      ros_topic_to_dds("/turtle1/cmd_vel").unwrap(),
+      "rt/turtle1/cmd_vel"
+    );
+    assert_eq!(ros_topic_to_dds("chatter").unwrap(), "rt/chatter");
+    assert_eq!(
+      ros_type_to_dds("geometry_msgs/msg/Twist").unwrap(),
+      "geometry_msgs::msg::dds_::Twist_"
+    );
+    assert_eq!(
+      ros_type_to_dds("std_msgs/String").unwrap(),
+      "std_msgs::msg::dds_::String_"
+    );
+    assert_eq!(
+      ros_service_to_dds("/add_two_ints").unwrap(),
+      (
+        "rq/add_two_intsRequest".to_string(),
+        "rr/add_two_intsReply".to_string()
+      )
+    );
+    assert_eq!(
+      ros_service_type_to_dds("example_interfaces/srv/AddTwoInts").unwrap(),
+      (
+        "example_interfaces::srv::dds_::AddTwoInts_Request_".to_string(),
+        "example_interfaces::srv::dds_::AddTwoInts_Response_".to_string()
+      )
+    );
+    assert_eq!(
+      ros_service_to_dds(&fully_qualified_name("/", "turtlesim", "~/get_parameters").unwrap())
+        .unwrap()
+        .0,
+      "rq/turtlesim/get_parametersRequest"
+    );
+    assert_eq!(
+      ros_topic_to_dds(PARAMETER_EVENTS).unwrap(),
+      ParameterEventsTopic::topic_name()
+    );
+    assert_eq!(ros_topic_to_dds(ROSOUT).unwrap(), RosOutTopic::topic_name());
+  }
+
+  #[test]
+  fn names_round_trip() {
+    assert_eq!(
+      dds_topic_to_ros("rt/turtle1/cmd_vel"),
+      Some((RosNameKind::Topic, "/turtle1/cmd_vel".to_string()))
+    );
+    assert_eq!(
+      dds_topic_to_ros("rq/add_two_intsRequest"),
+      Some((RosNameKind::ServiceRequest, "/add_two_ints".to_string()))
+    );
+    assert_eq!(
+      dds_topic_to_ros("rr/add_two_intsReply"),
+      Some((RosNameKind::ServiceReply, "/add_two_ints".to_string()))
+    );
+    assert_eq!(dds_topic_to_ros("ros_discovery_info"), None);
+    assert_eq!(dds_topic_to_ros("Square"), None);
+    assert_eq!(
+      dds_type_to_ros("geometry_msgs::msg::dds_::Twist_").unwrap(),
+      "geometry_msgs/msg/Twist"
+    );
+    assert_eq!(
+      dds_type_to_ros("example_interfaces::srv::dds_::AddTwoInts_Request_").unwrap(),
+      "example_interfaces/srv/AddTwoInts_Request"
+    );
+    assert_eq!(dds_type_to_ros("ShapeType"), None);
+    assert_eq!(
+      ros_type_to_dds("geometry_msgs::msg::dds_::Twist_").unwrap(),
+      "geometry_msgs::msg::dds_::Twist_"
+    );
+  }
+
+  #[test]
+  fn names_namespaces_and_validation() {
+    assert_eq!(
+      fully_qualified_name("/ns/", "node", "a/b").unwrap(),
+      "/ns/a/b"
+    );
+    assert_eq!(fully_qualified_name("", "node", "a").unwrap(), "/a");
+    assert_eq!(
+      fully_qualified_name("/ns", "node", "~").unwrap(),
+      "/ns/node"
+    );
+    assert!(is_hidden("/ns/_hidden/topic"));
+    assert!(!is_hidden("/ns/not_hidden"));
+    assert!(matches!(
+      ros_topic_to_dds(""),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      ros_topic_to_dds("/a//b"),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      ros_topic_to_dds("/a/"),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      ros_topic_to_dds("/1abc"),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      ros_topic_to_dds("/a-b"),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      fully_qualified_name("/", "node", "~x"),
+      Err(Error::BadParameter { .. })
+    ));
+    assert!(matches!(
+      ros_type_to_dds("Twist"),
+      Err(Error::BadParameter { .. })
+    ));
+  }
+}
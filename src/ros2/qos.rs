@@ -0,0 +1,142 @@
+//! QoS profiles used by ROS 2 (rmw) for topics and services. Readers and
+//! writers of other ROS 2 nodes are matched most reliably when the same
+//! profile is used on both sides.
+//!
+//! Besides reliability, durability and history, the profiles set the policies
+//! that ROS leaves to the system default to the values rmw_fastrtps_cpp
+//! announces in discovery, so that the profiles are identical to the ones of
+//! ROS 2 nodes.
+
+use crate::{
+  dds::qos::{
+    QosPolicies, QosPolicyBuilder,
+    policy::{
+      Deadline, DestinationOrder, Durability, History, LatencyBudget, Lifespan, Liveliness,
+      Ownership, Reliability,
+    },
+  },
+  structure::duration::Duration,
+};
+
+const RELIABLE: Reliability = Reliability::Reliable {
+  max_blocking_time: Duration::from_millis(100),
+};
+
+// Policies that ROS profiles leave to the system default
+const fn profile(reliability: Reliability, durability: Durability, depth: i32) -> QosPolicyBuilder {
+  QosPolicyBuilder::new()
+    .reliability(reliability)
+    .durability(durability)
+    .history(History::KeepLast { depth })
+    .deadline(Deadline(Duration::DURATION_INFINITE))
+    .latency_budget(LatencyBudget {
+      duration: Duration::DURATION_ZERO,
+    })
+    .ownership(Ownership::Shared)
+    .liveliness(Liveliness::Automatic {
+      lease_duration: Duration::DURATION_INFINITE,
+    })
+    .destination_order(DestinationOrder::ByReceptionTimestamp)
+    .lifespan(Lifespan {
+      duration: Duration::DURATION_INFINITE,
+    })
+}
+
+/// Default profile of ROS 2 publishers and subscriptions: reliable, volatile,
+/// keep last 10.
+pub const fn default() -> QosPolicies {
+  profile(RELIABLE, Durability::Volatile, 10).build()
+}
+
+/// For sensor data, where timeliness matters more than getting every sample:
+/// best effort, volatile, keep last 5.
+pub const fn sensor_data() -> QosPolicies {
+  profile(Reliability::BestEffort, Durability::Volatile, 5).build()
+}
+
+/// For parameter services: reliable, volatile, keep last 1000.
+pub const fn parameters() -> QosPolicies {
+  profile(RELIABLE, Durability::Volatile, 1000).build()
+}
+
+/// For the `/parameter_events` topic: reliable, volatile, keep last 1000.
+pub const fn parameter_events() -> QosPolicies {
+  profile(RELIABLE, Durability::Volatile, 1000).build()
+}
+
+/// For services: reliable, volatile, keep last 10.
+pub const fn services_default() -> QosPolicies {
+  default()
+}
+
+/// For the `/rosout` topic: reliable, transient local, keep last 1000, and
+/// log messages expire after 10 seconds.
+pub const fn rosout() -> QosPolicies {
+  profile(RELIABLE, Durability::TransientLocal, 1000)
+    .lifespan(Lifespan {
+      duration: Duration::from_secs(10),
+    })
+    .build()
+}
+
+/// Leaves every policy to the DDS defaults.
+pub const fn system_default() -> QosPolicies {
+  QosPolicyBuilder::new().build()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Default DataReaderQos and DataWriterQos of Fast DDS 2.x, as announced in discovery
+  const fn fast_dds(reliability: Reliability, durability: Durability) -> QosPolicies {
+    profile(reliability, durability, 1).build()
+  }
+
+  #[test]
+  fn ros_profiles_match_fast_dds_defaults() {
+    let fast_dds_reader = fast_dds(Reliability::BestEffort, Durability::Volatile);
+    let fast_dds_writer = fast_dds(RELIABLE, Durability::TransientLocal);
+    let profiles = [
+      default(),
+      sensor_data(),
+      parameters(),
+      parameter_events(),
+      services_default(),
+      rosout(),
+      system_default(),
+    ];
+    for p in profiles.iter() {
+      assert_eq!(p.compliance_failure_wrt(&fast_dds_reader), None, "{:?}", p);
+      assert_eq!(fast_dds_writer.compliance_failure_wrt(p), None, "{:?}", p);
+      assert_eq!(p.compliance_failure_wrt(p), None, "{:?}", p);
+    }
+  }
+
+  #[test]
+  fn ros_profiles_are_rmw_profiles() {
+    let depth = |qos: QosPolicies| match qos.history() {
+      Some(History::KeepLast { depth }) => depth,
+      other => panic!("{:?}", other),
+    };
+    assert_eq!(depth(default()), 10);
+    assert_eq!(depth(sensor_data()), 5);
+    assert_eq!(depth(parameters()), 1000);
+    assert_eq!(depth(parameter_events()), 1000);
+    assert_eq!(depth(rosout()), 1000);
+    assert_eq!(sensor_data().reliability(), Some(Reliability::BestEffort));
+    assert_eq!(rosout().durability(), Some(Durability::TransientLocal));
+    assert_eq!(
+      rosout().lifespan(),
+      Some(Lifespan {
+        duration: Duration::from_secs(10)
+      })
+    );
+    assert_eq!(services_default(), default());
+    // a sensor data publisher cannot serve a subscription that asks for reliability
+    assert_eq!(
+      sensor_data().compliance_failure_wrt(&default()),
+      Some(crate::dds::qos::QosPolicyId::Reliability)
+    );
+  }
+}
@@ -1,4 +1,7 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+  cell::RefCell,
+  collections::{HashMap, HashSet},
+};
 
 use byteorder::LittleEndian;
 use log::error;
@@ -19,7 +22,7 @@ use crate::{
     traits::key::Keyed,
     traits::serde_adapters::DeserializerAdapter,
     traits::serde_adapters::SerializerAdapter,
-    values::result::Error,
+    values::result::{DataWriterStatus, Error},
   },
   serialization::cdr_deserializer::CDRDeserializerAdapter,
   serialization::cdr_serializer::CDRSerializerAdapter,
@@ -32,6 +35,9 @@ use super::{
   builtin_datatypes::{Gid, Log, ParameterEvents, ROSParticipantInfo},
   builtin_topics::ParameterEventsTopic,
   builtin_topics::{ROSDiscoveryTopic, RosOutTopic},
+  names,
+  rosout::RosoutLogger,
+  service::{RosServiceClient, RosServiceServer, RosServiceTopics},
 };
 
 /// Trait for RCL interface
@@ -48,16 +54,18 @@ pub trait IRosNode {
   fn get_domain_id(&self) -> u16;
 }
 
-/// Trait for necessary DDS interface functions
+/// Trait for necessary DDS interface functions. Readers and writers created
+/// through these are added to the node info of the node.
 pub trait IRosNodeControl<'a> {
   /// Creates ROS2 topic and handles necessary conversions from DDS to ROS2
   ///
   /// # Arguments
   ///
   /// * `domain_participant` - [DomainParticipant](../dds/struct.DomainParticipant.html)
-  /// * `name` - Name of the topic
-  /// * `type_name` - What type the topic holds in string form
-  /// * `qos` - Quality of Service parameters for the topic (not restricted only to ROS2)
+  /// * `name` - ROS name of the topic, e.g. `/turtle1/cmd_vel`. Relative names are relative to the root namespace.
+  /// * `type_name` - ROS type of the topic, e.g. `geometry_msgs/msg/Twist`, or the DDS type name
+  /// * `qos` - Quality of Service parameters for the topic (not restricted only to ROS2),
+  /// usually one of the ROS 2 profiles in [qos](qos/index.html), e.g. [default](qos/fn.default.html)
   /// * `topic_kind` - Does the topic have a key (multiple DDS instances)? NoKey or WithKey
   fn create_ros_topic(
     domain_participant: &DomainParticipant,
@@ -86,7 +94,7 @@ pub trait IRosNodeControl<'a> {
   /// * `topic` - Reference to topic created with `create_ros_topic`.
   /// * `qos` - Should take [QOS](../dds/qos/struct.QosPolicies.html) and use it if it's compatible with topics QOS. `None` indicates the use of Topics QOS.
   fn create_ros_subscriber<D, DA: DeserializerAdapter<D> + 'a>(
-    &self,
+    &mut self,
     topic: &'a Topic,
     qos: Option<QosPolicies>,
   ) -> Result<KeyedRosSubscriber<'a, D, DA>, Error>
@@ -101,7 +109,7 @@ pub trait IRosNodeControl<'a> {
   /// * `topic` - Reference to topic created with `create_ros_topic`.
   /// * `qos` - Should take [QOS](../dds/qos/struct.QosPolicies.html) and use it if it's compatible with topics QOS. `None` indicates the use of Topics QOS.
   fn create_ros_nokey_publisher<D: Serialize + 'a, SA: SerializerAdapter<D> + 'a>(
-    &self,
+    &mut self,
     topic: &'a Topic,
     qos: Option<QosPolicies>,
   ) -> Result<RosPublisher<'a, D, SA>, Error>;
@@ -113,7 +121,7 @@ pub trait IRosNodeControl<'a> {
   /// * `topic` - Reference to topic created with `create_ros_topic`.
   /// * `qos` - Should take [QOS](../dds/qos/struct.QosPolicies.html) and use it if it's compatible with topics QOS. `None` indicates the use of Topics QOS.
   fn create_ros_publisher<D, SA: SerializerAdapter<D> + 'a>(
-    &self,
+    &mut self,
     topic: &'a Topic,
     qos: Option<QosPolicies>,
   ) -> Result<KeyedRosPublisher<'a, D, SA>, Error>
@@ -124,7 +132,8 @@ pub trait IRosNodeControl<'a> {
 
 /// [RosParticipant](struct.RosParticipant.html) sends and receives other participants information in ROS2 network
 pub struct RosParticipant<'a> {
-  nodes: HashMap<String, NodeInfo>,
+  // RosNodes update their own info through a shared reference
+  nodes: RefCell<HashMap<String, NodeInfo>>,
   external_nodes: HashMap<Gid, Vec<NodeInfo>>,
   node_reader: NoKeyDataReader<'a, ROSParticipantInfo, CDRDeserializerAdapter<ROSParticipantInfo>>,
   node_writer:
@@ -137,8 +146,9 @@ impl<'a> RosParticipant<'a> {
     let dtopic = match ros_context.get_ros_discovery_topic() {
       Some(t) => t,
       None => {
-        error!("RosContext has no discovery topic.");
-        return Err(Error::PreconditionNotMet);
+        return Err(Error::precondition_not_met(
+          "RosContext has no ros_discovery_info topic",
+        ))
       }
     };
 
@@ -151,7 +161,7 @@ impl<'a> RosParticipant<'a> {
       .create_datawriter_no_key(None, dtopic, None)?;
 
     Ok(RosParticipant {
-      nodes: HashMap::new(),
+      nodes: RefCell::new(HashMap::new()),
       external_nodes: HashMap::new(),
       node_reader,
       node_writer,
@@ -163,33 +173,37 @@ impl<'a> RosParticipant<'a> {
   pub fn get_ros_participant_info(&self) -> ROSParticipantInfo {
     ROSParticipantInfo::new(
       Gid::from_guid(self.ros_context.domain_participant.get_guid()),
-      self.nodes.iter().map(|(_, p)| p.clone()).collect(),
+      self.nodes.borrow().values().cloned().collect(),
     )
   }
 
-  /// Adds new NodeInfo and updates our RosParticipantInfo to ROS2 network
-  pub fn add_node_info(&mut self, mut node_info: NodeInfo) {
+  /// Adds new or changed NodeInfo and updates our RosParticipantInfo to ROS2 network
+  pub fn add_node_info(&self, mut node_info: NodeInfo) {
     node_info.add_reader(Gid::from_guid(self.node_reader.get_guid()));
     node_info.add_writer(Gid::from_guid(self.node_writer.get_guid()));
 
-    match self.nodes.insert(node_info.get_full_name(), node_info) {
-      Some(_) => (),
-      None => self.write_info(),
+    let previous = self
+      .nodes
+      .borrow_mut()
+      .insert(node_info.get_full_name(), node_info.clone());
+    if previous.as_ref() != Some(&node_info) {
+      self.write_info()
     }
   }
 
   /// Removes NodeInfo and updates our RosParticipantInfo to ROS2 network
-  pub fn remove_node_info(&mut self, node_info: &NodeInfo) {
-    match self.nodes.remove(&node_info.get_full_name()) {
-      Some(_) => self.write_info(),
-      None => (),
+  pub fn remove_node_info(&self, node_info: &NodeInfo) {
+    let removed = self.nodes.borrow_mut().remove(&node_info.get_full_name());
+    if removed.is_some() {
+      self.write_info()
     }
   }
 
   /// Clears all nodes and updates our RosParticipantInfo to ROS2 network
-  pub fn clear(&mut self) {
-    if !self.nodes.is_empty() {
-      self.nodes.clear();
+  pub fn clear(&self) {
+    let was_empty = self.nodes.borrow().is_empty();
+    if !was_empty {
+      self.nodes.borrow_mut().clear();
       self.write_info()
     }
   }
@@ -204,11 +218,36 @@ impl<'a> RosParticipant<'a> {
     }
   }
 
+  /// Status changes of the DataWriter of our RosParticipantInfo. Register this to
+  /// a mio `Poll`, and call [handle_node_writer_status](#method.handle_node_writer_status)
+  /// on its events.
+  pub fn node_writer_status_listener(&self) -> &dyn Evented {
+    &self.node_writer
+  }
+
+  /// Handles the status changes of the DataWriter of our RosParticipantInfo. When it
+  /// matches a DataReader, our info is written again, so that it is soon seen by
+  /// participants that joined after the last change.
+  pub fn handle_node_writer_status(&self) {
+    let mut matched = false;
+    while let Some(status) = self.node_writer.get_status_change() {
+      if let DataWriterStatus::PublicationMatched(_) = status {
+        matched = true;
+      }
+    }
+    if matched && !self.nodes.borrow().is_empty() {
+      self.write_info()
+    }
+  }
+
   /// Fetches all unread ROSParticipantInfos we have received
   pub fn handle_node_read(&mut self) -> Vec<ROSParticipantInfo> {
     let mut pts = Vec::new();
     while let Ok(Some(sample)) = self.node_reader.take_next_sample() {
-      let rpi = sample.value();
+      let rpi = match sample.into_value() {
+        Some(rpi) => rpi,
+        None => continue, // a participant went away, its nodes are not touched here
+      };
       match self.external_nodes.get_mut(&rpi.guid()) {
         Some(rpi2) => {
           *rpi2 = rpi.nodes().to_vec();
@@ -217,7 +256,7 @@ impl<'a> RosParticipant<'a> {
           self.external_nodes.insert(rpi.guid(), rpi.nodes().to_vec());
         }
       };
-      pts.push(rpi.clone());
+      pts.push(rpi);
     }
     pts
   }
@@ -334,6 +373,7 @@ pub struct RosNodeBuilder<'a> {
   node_options: Option<NodeOptions>,
   // lifetime bound
   ros_context: Option<&'a RosContext>,
+  ros_participant: Option<&'a RosParticipant<'a>>,
 }
 
 impl<'a> RosNodeBuilder<'a> {
@@ -343,6 +383,7 @@ impl<'a> RosNodeBuilder<'a> {
       namespace: None,
       node_options: None,
       ros_context: None,
+      ros_participant: None,
     }
   }
 
@@ -370,20 +411,27 @@ impl<'a> RosNodeBuilder<'a> {
     self
   }
 
+  /// Optional. The node announces itself through the
+  /// [RosParticipant](struct.RosParticipant.html), so that it is visible to
+  /// e.g. `ros2 node list`, keeps the announced readers and writers up to date
+  /// and withdraws the announcement when dropped.
+  pub fn ros_participant(mut self, ros_participant: &'a RosParticipant<'a>) -> RosNodeBuilder<'a> {
+    self.ros_participant = Some(ros_participant);
+    self
+  }
+
   pub fn build(self) -> Result<RosNode<'a>, Error> {
     let name = match self.name {
       Some(n) => n,
-      None => {
-        error!("Node name needs to be defined.");
-        return Err(Error::PreconditionNotMet);
-      }
+      None => return Err(Error::precondition_not_met("RosNode name is not defined")),
     };
 
     let namespace = match self.namespace {
       Some(ns) => ns,
       None => {
-        error!("Node namespace needs to be defined.");
-        return Err(Error::PreconditionNotMet);
+        return Err(Error::precondition_not_met(
+          "RosNode namespace is not defined",
+        ))
       }
     };
 
@@ -395,12 +443,21 @@ impl<'a> RosNodeBuilder<'a> {
     let ros_context = match self.ros_context {
       Some(ctx) => ctx,
       None => {
-        error!("RosContext needs to be defined.");
-        return Err(Error::PreconditionNotMet);
+        return Err(Error::precondition_not_met(
+          "RosContext of RosNode is not defined",
+        ))
       }
     };
 
-    RosNode::new(&name, &namespace, node_options, ros_context)
+    let node = RosNode::new(
+      &name,
+      &namespace,
+      node_options,
+      ros_context,
+      self.ros_participant,
+    )?;
+    node.announce();
+    Ok(node)
   }
 }
 
@@ -413,6 +470,7 @@ pub struct RosNode<'a> {
   options: NodeOptions,
 
   ros_context: &'a RosContext,
+  ros_participant: Option<&'a RosParticipant<'a>>,
 
   // dynamic
   readers: HashSet<GUID>,
@@ -431,6 +489,7 @@ impl<'a> RosNode<'a> {
     namespace: &str,
     options: NodeOptions,
     ros_context: &'a RosContext,
+    ros_participant: Option<&'a RosParticipant<'a>>,
   ) -> Result<RosNode<'a>, Error> {
     let paramtopic = ros_context.get_parameter_events_topic();
     let rosout_topic = ros_context.get_rosout_topic();
@@ -454,6 +513,7 @@ impl<'a> RosNode<'a> {
       namespace: String::from(namespace),
       options,
       ros_context,
+      ros_participant,
       readers: HashSet::new(),
       writers: HashSet::new(),
       rosout_writer,
@@ -493,26 +553,129 @@ impl<'a> RosNode<'a> {
     node_info
   }
 
+  /// Same as [create_ros_topic](trait.IRosNodeControl.html#tymethod.create_ros_topic), but
+  /// relative names are resolved against the namespace of this node and private names (`~/`)
+  /// against the node itself.
+  pub fn create_topic(
+    &self,
+    name: &str,
+    type_name: &str,
+    qos: QosPolicies,
+    topic_kind: TopicKind,
+  ) -> Result<Topic, Error> {
+    let name = names::fully_qualified_name(&self.namespace, &self.name, name)?;
+    RosNode::create_ros_topic(
+      &self.ros_context.domain_participant,
+      &name,
+      type_name,
+      qos,
+      topic_kind,
+    )
+  }
+
+  /// Creates the server side of a ROS 2 service. Its reader and writer are added to the node
+  /// info of this node.
+  ///
+  /// # Arguments
+  ///
+  /// * `topics` - Topics of the service, created with [RosServiceTopics::new](struct.RosServiceTopics.html#method.new)
+  /// * `qos` - `None` indicates the use of Topics QOS.
+  pub fn create_ros_service_server<Req, Rep>(
+    &mut self,
+    topics: &'a RosServiceTopics,
+    qos: Option<QosPolicies>,
+  ) -> Result<RosServiceServer<'a, Req, Rep>, Error>
+  where
+    Req: DeserializeOwned + 'static,
+    Rep: Serialize + 'a,
+  {
+    let request_reader = self.create_ros_nokey_subscriber(topics.request_topic(), qos.clone())?;
+    let reply_writer = self.create_ros_nokey_publisher(topics.reply_topic(), qos)?;
+    Ok(RosServiceServer::new(request_reader, reply_writer))
+  }
+
+  /// Creates the client side of a ROS 2 service. Its reader and writer are added to the node
+  /// info of this node.
+  ///
+  /// # Arguments
+  ///
+  /// * `topics` - Topics of the service, created with [RosServiceTopics::new](struct.RosServiceTopics.html#method.new)
+  /// * `qos` - `None` indicates the use of Topics QOS.
+  pub fn create_ros_service_client<Req, Rep>(
+    &mut self,
+    topics: &'a RosServiceTopics,
+    qos: Option<QosPolicies>,
+  ) -> Result<RosServiceClient<'a, Req, Rep>, Error>
+  where
+    Req: Serialize + 'a,
+    Rep: DeserializeOwned + 'static,
+  {
+    let request_writer = self.create_ros_nokey_publisher(topics.request_topic(), qos.clone())?;
+    let reply_reader = self.create_ros_nokey_subscriber(topics.reply_topic(), qos)?;
+    Ok(RosServiceClient::new(request_writer, reply_reader))
+  }
+
+  // Sends our current node info, if we have a RosParticipant
+  fn announce(&self) {
+    if let Some(ros_participant) = self.ros_participant {
+      ros_participant.add_node_info(self.generate_node_info());
+    }
+  }
+
+  /// Readers created with [IRosNodeControl](trait.IRosNodeControl.html) are added automatically.
   pub fn add_reader(&mut self, reader: GUID) {
-    self.readers.insert(reader);
+    if self.readers.insert(reader) {
+      self.announce();
+    }
   }
 
   pub fn remove_reader(&mut self, reader: &GUID) {
-    self.readers.remove(reader);
+    if self.readers.remove(reader) {
+      self.announce();
+    }
   }
 
+  /// Writers created with [IRosNodeControl](trait.IRosNodeControl.html) are added automatically.
   pub fn add_writer(&mut self, writer: GUID) {
-    self.writers.insert(writer);
+    if self.writers.insert(writer) {
+      self.announce();
+    }
   }
 
   pub fn remove_writer(&mut self, writer: &GUID) {
-    self.writers.remove(writer);
+    if self.writers.remove(writer) {
+      self.announce();
+    }
+  }
+
+  /// Creates a [RosoutLogger](struct.RosoutLogger.html) named after this node,
+  /// and adds its writer to the node. Records logged with the log crate show
+  /// up in `/rosout` once the logger is installed.
+  pub fn create_rosout_logger(&mut self) -> Result<RosoutLogger, Error> {
+    let namespace = self.namespace.trim_matches('/').replace('/', ".");
+    let name = if namespace.is_empty() {
+      self.name.clone()
+    } else {
+      format!("{}.{}", namespace, self.name)
+    };
+    let logger = RosoutLogger::new(&self.ros_context.domain_participant, &name)?;
+    self.add_writer(logger.writer_guid());
+    Ok(logger)
   }
 
   /// Clears both all reader and writer guids from this node.
   pub fn clear_node(&mut self) {
     self.readers.clear();
     self.writers.clear();
+    self.announce();
+  }
+}
+
+impl Drop for RosNode<'_> {
+  fn drop(&mut self) {
+    if let Some(ros_participant) = self.ros_participant {
+      ros_participant.remove_node_info(&self.generate_node_info());
+    }
   }
 }
 
@@ -574,9 +737,9 @@ impl<'a> IRosNodeControl<'a> for RosNode<'a> {
     qos: QosPolicies,
     topic_kind: TopicKind,
   ) -> Result<Topic, Error> {
-    let mut oname = "rt".to_owned();
-    oname.push_str(name);
-    let topic = domain_participant.create_topic(&oname, type_name, &qos, topic_kind)?;
+    let dds_name = names::ros_topic_to_dds(name)?;
+    let dds_type_name = names::ros_type_to_dds(type_name)?;
+    let topic = domain_participant.create_topic(&dds_name, &dds_type_name, &qos, topic_kind)?;
     Ok(topic)
   }
 
@@ -585,14 +748,16 @@ impl<'a> IRosNodeControl<'a> for RosNode<'a> {
     topic: &'a Topic,
     qos: Option<QosPolicies>,
   ) -> Result<RosSubscriber<'a, D, DA>, Error> {
-    self
+    let reader = self
       .ros_context
       .get_ros_discovery_subscriber()
-      .create_datareader_no_key::<D, DA>(topic, None, qos)
+      .create_datareader_no_key::<D, DA>(topic, None, qos)?;
+    self.add_reader(reader.get_guid());
+    Ok(reader)
   }
 
   fn create_ros_subscriber<D, DA: DeserializerAdapter<D> + 'a>(
-    &self,
+    &mut self,
     topic: &'a Topic,
     qos: Option<QosPolicies>,
   ) -> Result<KeyedRosSubscriber<'a, D, DA>, Error>
@@ -600,25 +765,29 @@ impl<'a> IRosNodeControl<'a> for RosNode<'a> {
     D: Keyed + DeserializeOwned + 'static,
     D::K: Key,
   {
-    self
+    let reader = self
       .ros_context
       .get_ros_discovery_subscriber()
-      .create_datareader::<D, DA>(topic, None, qos)
+      .create_datareader::<D, DA>(topic, None, qos)?;
+    self.add_reader(reader.get_guid());
+    Ok(reader)
   }
 
   fn create_ros_nokey_publisher<D: Serialize + 'a, SA: SerializerAdapter<D> + 'a>(
-    &self,
+    &mut self,
     topic: &'a Topic,
     qos: Option<QosPolicies>,
   ) -> Result<RosPublisher<'a, D, SA>, Error> {
-    self
+    let writer = self
       .ros_context
       .get_ros_discovery_publisher()
-      .create_datawriter_no_key(None, topic, qos)
+      .create_datawriter_no_key(None, topic, qos)?;
+    self.add_writer(writer.get_guid());
+    Ok(writer)
   }
 
   fn create_ros_publisher<D, SA: SerializerAdapter<D> + 'a>(
-    &self,
+    &mut self,
     topic: &'a Topic,
     qos: Option<QosPolicies>,
   ) -> Result<KeyedRosPublisher<'a, D, SA>, Error>
@@ -626,9 +795,66 @@ impl<'a> IRosNodeControl<'a> for RosNode<'a> {
     D: Keyed + Serialize + 'a,
     D::K: Key,
   {
-    self
+    let writer = self
       .ros_context
       .get_ros_discovery_publisher()
-      .create_datawriter(None, topic, qos)
+      .create_datawriter(None, topic, qos)?;
+    self.add_writer(writer.get_guid());
+    Ok(writer)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dds::qos::QosPolicyBuilder;
+  use crate::ros2::builtin_datatypes::Log;
+
+  #[test]
+  fn ros_node_announces_its_readers_and_writers() {
+    let domain_participant = DomainParticipant::new(39).unwrap();
+    let ros_context = RosContext::new(domain_participant.clone(), true).unwrap();
+    let ros_participant = RosParticipant::new(&ros_context).unwrap();
+    let topic = RosNode::create_ros_topic(
+      &domain_participant,
+      "/ros_node_test",
+      "rcl_interfaces::msg::dds_::Log_",
+      QosPolicyBuilder::new().build(),
+      TopicKind::NoKey,
+    )
+    .unwrap();
+
+    {
+      let mut ros_node = RosNodeBuilder::new()
+        .name("test_node")
+        .namespace("/")
+        .ros_context(&ros_context)
+        .ros_participant(&ros_participant)
+        .build()
+        .unwrap();
+      let nodes = ros_participant.get_ros_participant_info().into_nodes();
+      assert_eq!(nodes.len(), 1);
+      assert_eq!(nodes[0].get_full_name(), "/test_node");
+
+      let writer = ros_node
+        .create_ros_nokey_publisher::<Log, CDRSerializerAdapter<Log>>(&topic, None)
+        .unwrap();
+      let reader = ros_node
+        .create_ros_nokey_subscriber::<Log, CDRDeserializerAdapter<Log>>(&topic, None)
+        .unwrap();
+      let nodes = ros_participant.get_ros_participant_info().into_nodes();
+      assert!(nodes[0]
+        .get_writer_gids()
+        .contains(&Gid::from_guid(writer.get_guid())));
+      assert!(nodes[0]
+        .get_reader_gids()
+        .contains(&Gid::from_guid(reader.get_guid())));
+    }
+
+    // dropping the node withdraws it
+    assert!(ros_participant
+      .get_ros_participant_info()
+      .nodes()
+      .is_empty());
   }
 }
@@ -0,0 +1,318 @@
+//! Forwards records of the [log](https://docs.rs/log) crate to the ROS 2
+//! `/rosout` topic, where `ros2 topic echo /rosout` and rqt_console show them.
+//!
+//! The global logger of an application can be only one, so
+//! [RosoutLogger](struct.RosoutLogger.html) passes the records also to another
+//! logger, if given one with [chain](struct.RosoutLogger.html#method.chain).
+
+use std::{
+  cell::Cell,
+  sync::{mpsc, Mutex},
+  thread,
+};
+
+use log::{Level, LevelFilter, Metadata, Record, SetLoggerError};
+
+use crate::{
+  dds::{
+    qos::{policy::Reliability, QosPolicies},
+    topic::TopicKind,
+    values::result::Error,
+    DomainParticipant,
+  },
+  serialization::CDRSerializerAdapter,
+  structure::{entity::Entity, guid::GUID},
+};
+
+use super::{
+  builtin_datatypes::{Log, Time},
+  builtin_topics::RosOutTopic,
+  qos,
+};
+
+// Records waiting to be written. When full, new records are dropped.
+const QUEUE_LENGTH: usize = 1000;
+
+thread_local! {
+  // Set on the thread that writes to /rosout. What RustDDS logs while writing is not
+  // forwarded, as writing it would log more.
+  static WRITING_ROSOUT: Cell<bool> = Cell::new(false);
+}
+
+/// A [log::Log](https://docs.rs/log/0.4/log/trait.Log.html) that writes log
+/// records as `rcl_interfaces/msg/Log` samples to `/rosout`.
+///
+/// Logging never blocks: the samples are written by a thread of their own
+/// with a best effort writer, and records are dropped if that thread falls
+/// behind. Subscribers asking for reliable `/rosout` do not get them, but
+/// `ros2 topic echo /rosout` adapts to the publishers.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use log::LevelFilter;
+/// # use rustdds::dds::DomainParticipant;
+/// # use rustdds::ros2::RosoutLogger;
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// RosoutLogger::new(&domain_participant, "my_node")
+///   .unwrap()
+///   .level(LevelFilter::Warn)
+///   .install()
+///   .unwrap();
+/// log::warn!("Shown by ros2 topic echo /rosout");
+/// ```
+pub struct RosoutLogger {
+  name: String,
+  level: LevelFilter,
+  sender: Mutex<mpsc::SyncSender<Log>>,
+  writer_guid: GUID,
+  next: Option<(Box<dyn log::Log>, LevelFilter)>,
+}
+
+impl RosoutLogger {
+  /// Starts the thread that writes to `/rosout`. It stops when the
+  /// RosoutLogger is dropped.
+  ///
+  /// # Arguments
+  ///
+  /// * `domain_participant` - [DomainParticipant](../dds/struct.DomainParticipant.html)
+  /// * `name` - Name of the logger shown in `/rosout`, usually the name of the node
+  pub fn new(domain_participant: &DomainParticipant, name: &str) -> Result<RosoutLogger, Error> {
+    let (sender, receiver) = mpsc::sync_channel(QUEUE_LENGTH);
+    let (started_sender, started_receiver) = mpsc::channel();
+    let domain_participant = domain_participant.clone();
+    if let Err(e) = thread::Builder::new()
+      .name("rosout".to_string())
+      .spawn(move || write_rosout(domain_participant, receiver, started_sender))
+    {
+      return Err(Error::io("failed to start rosout thread", e));
+    }
+    let writer_guid = match started_receiver.recv() {
+      Ok(started) => started?,
+      Err(_) => {
+        return Err(Error::out_of_resources(
+          "rosout thread stopped before creating its DataWriter",
+        ))
+      }
+    };
+    Ok(RosoutLogger {
+      name: name.to_string(),
+      level: LevelFilter::Info,
+      sender: Mutex::new(sender),
+      writer_guid,
+      next: None,
+    })
+  }
+
+  /// Most verbose level written to `/rosout`. The default is `Info`.
+  pub fn level(mut self, level: LevelFilter) -> RosoutLogger {
+    self.level = level;
+    self
+  }
+
+  /// Passes records up to `level` also to `logger`, e.g. to keep logging to a
+  /// file or the terminal as before.
+  pub fn chain(mut self, logger: Box<dyn log::Log>, level: LevelFilter) -> RosoutLogger {
+    self.next = Some((logger, level));
+    self
+  }
+
+  /// Makes this the global logger.
+  pub fn install(self) -> Result<(), SetLoggerError> {
+    let max_level = match &self.next {
+      Some((_, level)) => std::cmp::max(self.level, *level),
+      None => self.level,
+    };
+    log::set_boxed_logger(Box::new(self))?;
+    log::set_max_level(max_level);
+    Ok(())
+  }
+
+  /// GUID of the DataWriter of `/rosout`, e.g. for
+  /// [RosNode::add_writer](struct.RosNode.html#method.add_writer)
+  pub fn writer_guid(&self) -> GUID {
+    self.writer_guid
+  }
+
+  fn to_rosout(&self, record: &Record) -> Log {
+    let level = match record.level() {
+      Level::Error => Log::ERROR,
+      Level::Warn => Log::WARN,
+      Level::Info => Log::INFO,
+      Level::Debug | Level::Trace => Log::DEBUG,
+    };
+    Log::new(
+      Time::now(),
+      level,
+      self.name.clone(),
+      record.args().to_string(),
+      record.file().unwrap_or_default().to_string(),
+      // Rust does not tell the function
+      record.module_path().unwrap_or_default().to_string(),
+      record.line().unwrap_or(0),
+    )
+  }
+}
+
+impl log::Log for RosoutLogger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= self.level
+      || match &self.next {
+        Some((next, level)) => metadata.level() <= *level && next.enabled(metadata),
+        None => false,
+      }
+  }
+
+  fn log(&self, record: &Record) {
+    if let Some((next, level)) = &self.next {
+      if record.level() <= *level {
+        next.log(record);
+      }
+    }
+    let writing_rosout = WRITING_ROSOUT.try_with(|w| w.get()).unwrap_or(true);
+    if record.level() > self.level || writing_rosout {
+      return;
+    }
+    if let Ok(sender) = self.sender.lock() {
+      // Full queue or stopped thread: there is nowhere to report that
+      let _ = sender.try_send(self.to_rosout(record));
+    }
+  }
+
+  fn flush(&self) {
+    if let Some((next, _)) = &self.next {
+      next.flush();
+    }
+  }
+}
+
+// Best effort, so that writing never waits for the readers
+fn writer_qos() -> QosPolicies {
+  let mut qos = qos::rosout();
+  qos.reliability = Some(Reliability::BestEffort);
+  qos
+}
+
+fn write_rosout(
+  domain_participant: DomainParticipant,
+  records: mpsc::Receiver<Log>,
+  started: mpsc::Sender<Result<GUID, Error>>,
+) {
+  WRITING_ROSOUT.with(|w| w.set(true));
+  let qos = writer_qos();
+  let entities = domain_participant
+    .create_topic(
+      RosOutTopic::topic_name(),
+      RosOutTopic::type_name(),
+      &qos,
+      TopicKind::NoKey,
+    )
+    .and_then(|topic| Ok((topic, domain_participant.create_publisher(&qos)?)));
+  let (topic, publisher) = match entities {
+    Ok(e) => e,
+    Err(e) => {
+      let _ = started.send(Err(e));
+      return;
+    }
+  };
+  let writer = match publisher.create_datawriter_no_key::<Log, CDRSerializerAdapter<Log>>(
+    None,
+    &topic,
+    Some(qos),
+  ) {
+    Ok(w) => w,
+    Err(e) => {
+      let _ = started.send(Err(e));
+      return;
+    }
+  };
+  let _ = started.send(Ok(writer.get_guid()));
+  // ends when the RosoutLogger is dropped
+  for log in records.iter() {
+    let _ = writer.write(log, None);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration as StdDuration;
+
+  use log::Log as _;
+
+  use super::*;
+  use crate::{serialization::CDRDeserializerAdapter, structure::duration::Duration};
+
+  #[test]
+  fn rosout_logger_writes_records() {
+    let domain_participant = DomainParticipant::new(42).unwrap();
+    let logger = RosoutLogger::new(&domain_participant, "rosout_test")
+      .unwrap()
+      .level(LevelFilter::Warn);
+
+    let mut reader_qos = qos::rosout();
+    reader_qos.reliability = Some(Reliability::BestEffort);
+    let topic = domain_participant
+      .create_topic(
+        RosOutTopic::topic_name(),
+        RosOutTopic::type_name(),
+        &reader_qos,
+        TopicKind::NoKey,
+      )
+      .unwrap();
+    let subscriber = domain_participant.create_subscriber(&reader_qos).unwrap();
+    let mut reader = subscriber
+      .create_datareader_no_key::<Log, CDRDeserializerAdapter<Log>>(&topic, None, None)
+      .unwrap();
+    assert!(logger.enabled(&Metadata::builder().level(Level::Warn).build()));
+    assert!(!logger.enabled(&Metadata::builder().level(Level::Info).build()));
+
+    let mut received = None;
+    for _ in 0..50 {
+      logger.log(
+        &Record::builder()
+          .level(Level::Info)
+          .args(format_args!("not forwarded"))
+          .build(),
+      );
+      logger.log(
+        &Record::builder()
+          .level(Level::Warn)
+          .args(format_args!("forwarded"))
+          .file(Some("rosout.rs"))
+          .line(Some(7))
+          .build(),
+      );
+      if let Some(sample) = reader
+        .take_next_sample_timeout(Duration::from_millis(100))
+        .unwrap()
+      {
+        received = sample.into_value();
+        break;
+      }
+      std::thread::sleep(StdDuration::from_millis(10));
+    }
+    let log = received.expect("Nothing written to rosout");
+    assert_eq!(log.get_msg(), "forwarded");
+    assert_eq!(log.get_level(), Log::WARN);
+    assert_eq!(log.get_name(), "rosout_test");
+    assert_eq!(log.get_file(), "rosout.rs");
+    assert_eq!(log.get_line(), 7);
+    assert!(log.get_timestamp().get_sec() > 0);
+
+    // nothing is forwarded from the writing thread
+    WRITING_ROSOUT.with(|w| w.set(true));
+    logger.log(
+      &Record::builder()
+        .level(Level::Error)
+        .args(format_args!("recursion"))
+        .build(),
+    );
+    WRITING_ROSOUT.with(|w| w.set(false));
+    while let Some(sample) = reader
+      .take_next_sample_timeout(Duration::from_millis(300))
+      .unwrap()
+    {
+      assert_eq!(sample.value().unwrap().get_msg(), "forwarded");
+    }
+  }
+}
@@ -0,0 +1,467 @@
+//! ROS 2 services: a request topic (`rq/<service>Request`) and a reply topic
+//! (`rr/<service>Reply`), with a header in front of every request and reply
+//! to match them.
+//!
+//! The header is the client id (64 bits) and the sequence number of the
+//! request, as in the "basic" service mapping used by rmw_cyclonedds_cpp.
+//! rmw_fastrtps_cpp and rmw_connextdds instead carry the request id in the
+//! related sample identity inline QoS of the reply, which these services do
+//! not use (see [dds::Requester](../dds/struct.Requester.html) for that). So
+//! services talk only to peers using the basic mapping, e.g. other RustDDS
+//! nodes or ROS 2 with `RMW_IMPLEMENTATION=rmw_cyclonedds_cpp`.
+
+use std::{collections::BTreeMap, time::Instant};
+
+use log::debug;
+use mio::Evented;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+  dds::{
+    qos::QosPolicies,
+    topic::{Topic, TopicKind},
+    values::result::Error,
+    DomainParticipant,
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+  structure::{duration::Duration, entity::Entity, guid::GUID},
+};
+
+use super::{names, RosPublisher, RosSubscriber};
+
+/// Identifies a request among the requests of one client
+pub type RequestId = i64;
+
+/// Header in front of service requests and replies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RequestHeader {
+  client_id: u64,
+  sequence_number: RequestId,
+}
+
+/// Request or reply on the wire: the header followed by the message fields
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ServiceMessage<T> {
+  header: RequestHeader,
+  message: T,
+}
+
+/// Request and reply topics of a service. Create with
+/// [new](struct.RosServiceTopics.html#method.new) and keep alive longer than
+/// the clients and servers using them.
+pub struct RosServiceTopics {
+  request_topic: Topic,
+  reply_topic: Topic,
+}
+
+impl RosServiceTopics {
+  /// # Arguments
+  ///
+  /// * `domain_participant` - [DomainParticipant](../dds/struct.DomainParticipant.html)
+  /// * `name` - ROS name of the service, e.g. `/add_two_ints`
+  /// * `type_name` - ROS type of the service, e.g. `example_interfaces/srv/AddTwoInts`
+  /// * `qos` - Quality of Service parameters for both topics, usually
+  /// [services_default](qos/fn.services_default.html)
+  pub fn new(
+    domain_participant: &DomainParticipant,
+    name: &str,
+    type_name: &str,
+    qos: QosPolicies,
+  ) -> Result<RosServiceTopics, Error> {
+    let (request_name, reply_name) = names::ros_service_to_dds(name)?;
+    let (request_type, reply_type) = names::ros_service_type_to_dds(type_name)?;
+    Ok(RosServiceTopics {
+      request_topic: domain_participant.create_topic(
+        &request_name,
+        &request_type,
+        &qos,
+        TopicKind::NoKey,
+      )?,
+      reply_topic: domain_participant.create_topic(
+        &reply_name,
+        &reply_type,
+        &qos,
+        TopicKind::NoKey,
+      )?,
+    })
+  }
+
+  pub fn request_topic(&self) -> &Topic {
+    &self.request_topic
+  }
+
+  pub fn reply_topic(&self) -> &Topic {
+    &self.reply_topic
+  }
+}
+
+/// Tells [RosServiceServer](struct.RosServiceServer.html) where a reply goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplyToken {
+  header: RequestHeader,
+}
+
+/// Server side of a ROS 2 service. Create with
+/// [RosNode::create_ros_service_server](struct.RosNode.html#method.create_ros_service_server).
+pub struct RosServiceServer<'a, Req, Rep>
+where
+  Req: DeserializeOwned + 'static,
+  Rep: Serialize + 'a,
+{
+  request_reader:
+    RosSubscriber<'a, ServiceMessage<Req>, CDRDeserializerAdapter<ServiceMessage<Req>>>,
+  reply_writer: RosPublisher<'a, ServiceMessage<Rep>, CDRSerializerAdapter<ServiceMessage<Rep>>>,
+}
+
+impl<'a, Req, Rep> RosServiceServer<'a, Req, Rep>
+where
+  Req: DeserializeOwned + 'static,
+  Rep: Serialize + 'a,
+{
+  pub(crate) fn new(
+    request_reader: RosSubscriber<
+      'a,
+      ServiceMessage<Req>,
+      CDRDeserializerAdapter<ServiceMessage<Req>>,
+    >,
+    reply_writer: RosPublisher<'a, ServiceMessage<Rep>, CDRSerializerAdapter<ServiceMessage<Rep>>>,
+  ) -> RosServiceServer<'a, Req, Rep> {
+    RosServiceServer {
+      request_reader,
+      reply_writer,
+    }
+  }
+
+  /// Takes the next request, if any. Answer it with
+  /// [send_reply](#method.send_reply) using the returned token.
+  pub fn take_request(&mut self) -> Result<Option<(ReplyToken, Req)>, Error> {
+    // Samples without data only tell that the clients are gone.
+    while let Some(sample) = self.request_reader.take_next_sample()? {
+      if let Some(request) = sample.into_value() {
+        return Ok(Some((
+          ReplyToken {
+            header: request.header,
+          },
+          request.message,
+        )));
+      }
+    }
+    Ok(None)
+  }
+
+  /// Sends the reply to the client the token came from.
+  pub fn send_reply(&self, token: ReplyToken, reply: Rep) -> Result<(), Error> {
+    self.reply_writer.write(
+      ServiceMessage {
+        header: token.header,
+        message: reply,
+      },
+      None,
+    )
+  }
+
+  pub fn request_reader_guid(&self) -> GUID {
+    self.request_reader.get_guid()
+  }
+
+  pub fn reply_writer_guid(&self) -> GUID {
+    self.reply_writer.get_guid()
+  }
+}
+
+impl<'a, Req, Rep> Evented for RosServiceServer<'a, Req, Rep>
+where
+  Req: DeserializeOwned + 'static,
+  Rep: Serialize + 'a,
+{
+  fn register(
+    &self,
+    poll: &mio::Poll,
+    token: mio::Token,
+    interest: mio::Ready,
+    opts: mio::PollOpt,
+  ) -> std::io::Result<()> {
+    poll.register(&self.request_reader, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &mio::Poll,
+    token: mio::Token,
+    interest: mio::Ready,
+    opts: mio::PollOpt,
+  ) -> std::io::Result<()> {
+    poll.reregister(&self.request_reader, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &mio::Poll) -> std::io::Result<()> {
+    poll.deregister(&self.request_reader)
+  }
+}
+
+/// Client side of a ROS 2 service. Create with
+/// [RosNode::create_ros_service_client](struct.RosNode.html#method.create_ros_service_client).
+pub struct RosServiceClient<'a, Req, Rep>
+where
+  Req: Serialize + 'a,
+  Rep: DeserializeOwned + 'static,
+{
+  client_id: u64,
+  sequence_number: RequestId,
+  request_writer: RosPublisher<'a, ServiceMessage<Req>, CDRSerializerAdapter<ServiceMessage<Req>>>,
+  reply_reader: RosSubscriber<'a, ServiceMessage<Rep>, CDRDeserializerAdapter<ServiceMessage<Rep>>>,
+  // replies that arrived while waiting for another one
+  received_replies: BTreeMap<RequestId, Rep>,
+}
+
+impl<'a, Req, Rep> RosServiceClient<'a, Req, Rep>
+where
+  Req: Serialize + 'a,
+  Rep: DeserializeOwned + 'static,
+{
+  pub(crate) fn new(
+    request_writer: RosPublisher<
+      'a,
+      ServiceMessage<Req>,
+      CDRSerializerAdapter<ServiceMessage<Req>>,
+    >,
+    reply_reader: RosSubscriber<
+      'a,
+      ServiceMessage<Rep>,
+      CDRDeserializerAdapter<ServiceMessage<Rep>>,
+    >,
+  ) -> RosServiceClient<'a, Req, Rep> {
+    RosServiceClient {
+      client_id: client_id(request_writer.get_guid()),
+      sequence_number: 0,
+      request_writer,
+      reply_reader,
+      received_replies: BTreeMap::new(),
+    }
+  }
+
+  /// Sends a request without waiting for the reply. The reply can be picked
+  /// up with [receive_reply](#method.receive_reply).
+  pub fn send_request(&mut self, request: Req) -> Result<RequestId, Error> {
+    self.sequence_number += 1;
+    self.request_writer.write(
+      ServiceMessage {
+        header: RequestHeader {
+          client_id: self.client_id,
+          sequence_number: self.sequence_number,
+        },
+        message: request,
+      },
+      None,
+    )?;
+    Ok(self.sequence_number)
+  }
+
+  /// Takes the next reply to a request of this client, if any.
+  pub fn receive_reply(&mut self) -> Result<Option<(RequestId, Rep)>, Error> {
+    if let Some(id) = self.received_replies.keys().next().copied() {
+      return Ok(self.received_replies.remove(&id).map(|reply| (id, reply)));
+    }
+    while let Some(sample) = self.reply_reader.take_next_sample()? {
+      if let Some(reply) = sample.into_value().and_then(|r| self.own_reply(r)) {
+        return Ok(Some(reply));
+      }
+    }
+    Ok(None)
+  }
+
+  /// Sends a request and waits for at most `timeout` for its reply. Returns
+  /// `Ok(None)` if the reply did not arrive in time. Replies to other requests
+  /// that arrive meanwhile are kept for [receive_reply](#method.receive_reply).
+  pub fn call(&mut self, request: Req, timeout: Duration) -> Result<Option<Rep>, Error> {
+    let id = self.send_request(request)?;
+    let deadline = Instant::now() + timeout.to_std();
+    loop {
+      let now = Instant::now();
+      if now >= deadline {
+        debug!("Service call {} timed out.", id);
+        return Ok(None);
+      }
+      let remaining = Duration::from_std(deadline - now);
+      if let Some(sample) = self.reply_reader.take_next_sample_timeout(remaining)? {
+        match sample.into_value().and_then(|r| self.own_reply(r)) {
+          Some((reply_id, reply)) if reply_id == id => return Ok(Some(reply)),
+          Some((reply_id, reply)) => {
+            self.received_replies.insert(reply_id, reply);
+          }
+          None => (),
+        }
+      }
+    }
+  }
+
+  pub fn request_writer_guid(&self) -> GUID {
+    self.request_writer.get_guid()
+  }
+
+  pub fn reply_reader_guid(&self) -> GUID {
+    self.reply_reader.get_guid()
+  }
+
+  // Replies to other clients arrive on the same topic
+  fn own_reply(&self, reply: ServiceMessage<Rep>) -> Option<(RequestId, Rep)> {
+    if reply.header.client_id == self.client_id {
+      Some((reply.header.sequence_number, reply.message))
+    } else {
+      None
+    }
+  }
+}
+
+impl<'a, Req, Rep> Evented for RosServiceClient<'a, Req, Rep>
+where
+  Req: Serialize + 'a,
+  Rep: DeserializeOwned + 'static,
+{
+  fn register(
+    &self,
+    poll: &mio::Poll,
+    token: mio::Token,
+    interest: mio::Ready,
+    opts: mio::PollOpt,
+  ) -> std::io::Result<()> {
+    poll.register(&self.reply_reader, token, interest, opts)
+  }
+
+  fn reregister(
+    &self,
+    poll: &mio::Poll,
+    token: mio::Token,
+    interest: mio::Ready,
+    opts: mio::PollOpt,
+  ) -> std::io::Result<()> {
+    poll.reregister(&self.reply_reader, token, interest, opts)
+  }
+
+  fn deregister(&self, poll: &mio::Poll) -> std::io::Result<()> {
+    poll.deregister(&self.reply_reader)
+  }
+}
+
+// 64 bit client id from the GUID of the request writer. The participant part
+// of a RustDDS GuidPrefix is random, the entity id tells apart the writers
+// of one participant.
+fn client_id(guid: GUID) -> u64 {
+  let prefix = guid.guidPrefix.entityKey;
+  let entity = guid.entityId;
+  u64::from_be_bytes([
+    prefix[8],
+    prefix[9],
+    prefix[10],
+    prefix[11],
+    entity.entityKey[0],
+    entity.entityKey[1],
+    entity.entityKey[2],
+    entity.entityKind,
+  ])
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{thread, time::Duration as StdDuration};
+
+  use super::*;
+  use crate::{
+    dds::traits::TopicDescription,
+    ros2::{qos, RosContext, RosNodeBuilder},
+  };
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct AddTwoIntsRequest {
+    a: i64,
+    b: i64,
+  }
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct AddTwoIntsResponse {
+    sum: i64,
+  }
+
+  #[test]
+  fn service_loopback() {
+    let domain_participant = DomainParticipant::new(40).unwrap();
+    let ros_context = RosContext::new(domain_participant.clone(), false).unwrap();
+    let topics = RosServiceTopics::new(
+      &domain_participant,
+      "/add_two_ints",
+      "example_interfaces/srv/AddTwoInts",
+      qos::services_default(),
+    )
+    .unwrap();
+    assert_eq!(
+      TopicDescription::get_name(topics.request_topic()),
+      "rq/add_two_intsRequest"
+    );
+    assert_eq!(
+      TopicDescription::get_name(topics.reply_topic()),
+      "rr/add_two_intsReply"
+    );
+
+    let mut ros_node = RosNodeBuilder::new()
+      .name("add_two_ints")
+      .namespace("/")
+      .ros_context(&ros_context)
+      .build()
+      .unwrap();
+    let mut server = ros_node
+      .create_ros_service_server::<AddTwoIntsRequest, AddTwoIntsResponse>(&topics, None)
+      .unwrap();
+    let mut client = ros_node
+      .create_ros_service_client::<AddTwoIntsRequest, AddTwoIntsResponse>(&topics, None)
+      .unwrap();
+    let mut other_client = ros_node
+      .create_ros_service_client::<AddTwoIntsRequest, AddTwoIntsResponse>(&topics, None)
+      .unwrap();
+
+    // nobody answers
+    assert!(client
+      .call(AddTwoIntsRequest { a: 0, b: 0 }, Duration::from_millis(100))
+      .unwrap()
+      .is_none());
+
+    let first = client
+      .send_request(AddTwoIntsRequest { a: 1, b: 2 })
+      .unwrap();
+    let second = client
+      .send_request(AddTwoIntsRequest { a: 3, b: 4 })
+      .unwrap();
+    assert_ne!(first, second);
+
+    // the request of the timed out call may also be answered
+    let mut sums = Vec::new();
+    for _ in 0..50 {
+      while let Some((token, request)) = server.take_request().unwrap() {
+        let sum = request.a + request.b;
+        server
+          .send_reply(token, AddTwoIntsResponse { sum })
+          .unwrap();
+        sums.push(sum);
+      }
+      if sums.contains(&3) && sums.contains(&7) {
+        break;
+      }
+      thread::sleep(StdDuration::from_millis(50));
+    }
+    assert!(sums.contains(&3) && sums.contains(&7));
+
+    let mut replies = Vec::new();
+    for _ in 0..50 {
+      while let Some(reply) = client.receive_reply().unwrap() {
+        replies.push(reply);
+      }
+      if replies.iter().any(|(id, _)| *id == second) {
+        break;
+      }
+      thread::sleep(StdDuration::from_millis(50));
+    }
+    assert!(replies.contains(&(first, AddTwoIntsResponse { sum: 3 })));
+    assert!(replies.contains(&(second, AddTwoIntsResponse { sum: 7 })));
+    // replies go to the client that sent the request
+    assert!(other_client.receive_reply().unwrap().is_none());
+  }
+}
@@ -1,11 +1,12 @@
 use std::time::Instant;
 
 use serde::Deserialize;
+use log::debug;
 
 use chrono::Utc;
 
 use crate::{
-  dds::{qos::QosPolicyBuilder},
+  dds::qos::{QosPolicies, QosPolicyBuilder},
   structure::{
     guid::GUID,
     parameter_id::ParameterId,
@@ -61,6 +62,7 @@ pub struct BuiltinDataDeserializer {
   pub manual_liveliness_count: Option<i32>,
   pub builtin_enpoint_qos: Option<BuiltinEndpointQos>,
   pub entity_name: Option<String>,
+  pub domain_tag: Option<String>,
   pub sentinel: Option<u32>,
 
   pub endpoint_guid: Option<GUID>,
@@ -107,6 +109,7 @@ impl BuiltinDataDeserializer {
       manual_liveliness_count: None,
       builtin_enpoint_qos: None,
       entity_name: None,
+      domain_tag: None,
       sentinel: None,
 
       endpoint_guid: None,
@@ -150,6 +153,7 @@ impl BuiltinDataDeserializer {
       manual_liveliness_count: self.manual_liveliness_count,
       builtin_enpoint_qos: self.builtin_enpoint_qos,
       entity_name: self.entity_name.clone(),
+      domain_tag: self.domain_tag.clone(),
     }
   }
 
@@ -248,6 +252,10 @@ impl BuiltinDataDeserializer {
       None => (),
     };
 
+    if let Some(name) = &self.entity_name {
+      sbtd.set_entity_name(name);
+    }
+
     Ok(sbtd)
   }
 
@@ -257,6 +265,7 @@ impl BuiltinDataDeserializer {
       participant_key: self.participant_guid,
       topic_name: self.topic_name.clone(),
       type_name: self.type_name.clone(),
+      entity_name: self.entity_name.clone(),
       durability: self.durability,
       deadline: self.deadline,
       latency_budget: self.latency_budget,
@@ -289,6 +298,27 @@ impl BuiltinDataDeserializer {
     }
   }
 
+  /// All QoS policies found, including history and resource limits, which only
+  /// topic data carries.
+  pub fn qos(&self) -> QosPolicies {
+    QosPolicies {
+      durability: self.durability,
+      presentation: self.presentation,
+      deadline: self.deadline,
+      latency_budget: self.latency_budget,
+      ownership: self.ownership,
+      liveliness: self.liveliness,
+      time_based_filter: self.time_based_filter,
+      reliability: self.reliability,
+      destination_order: self.destination_order,
+      history: self.history,
+      resource_limits: self.resource_limits,
+      lifespan: self.lifespan,
+      reader_data_lifecycle: None,
+      writer_data_lifecycle: None,
+    }
+  }
+
   pub fn generate_discovered_reader_data(self) -> Result<DiscoveredReaderData, Error> {
     let reader_proxy = self.generate_reader_proxy();
     let subscription_topic_data = self.generate_subscription_topic_data()?;
@@ -331,9 +361,22 @@ impl BuiltinDataDeserializer {
     buffer: &mut Vec<u8>,
     rep: RepresentationIdentifier,
   ) -> BuiltinDataDeserializer {
-    let parameter_id = BuiltinDataDeserializer::read_parameter_id(&buffer, rep).unwrap();
-    let mut parameter_length: usize =
-      BuiltinDataDeserializer::read_parameter_length(&buffer, rep).unwrap() as usize;
+    // A truncated parameter header ends the parameter list
+    let header = match (
+      BuiltinDataDeserializer::read_parameter_id(&buffer, rep),
+      BuiltinDataDeserializer::read_parameter_length(&buffer, rep),
+    ) {
+      (Some(id), Some(length)) if buffer.len() >= 4 => Some((id, length as usize)),
+      _ => None,
+    };
+    let (parameter_id, mut parameter_length) = match header {
+      Some(h) => h,
+      None => {
+        debug!("Truncated parameter header: {:?}", buffer);
+        buffer.clear();
+        return self;
+      }
+    };
 
     if (parameter_length + 4) > buffer.len() {
       parameter_length = buffer.len() - 4;
@@ -496,6 +539,18 @@ impl BuiltinDataDeserializer {
           _ => (),
         }
       }
+      ParameterId::PID_DOMAIN_TAG => {
+        let tag: Result<String, Error> =
+          CDRDeserializerAdapter::from_bytes(&buffer[4..4 + parameter_length], rep);
+        match tag {
+          Ok(t) => {
+            self.domain_tag = Some(t);
+            buffer.drain(..4 + parameter_length);
+            return self;
+          }
+          _ => (),
+        }
+      }
       ParameterId::PID_ENDPOINT_GUID => {
         let guid: Result<GUID, Error> =
           CDRDeserializerAdapter::from_bytes(&buffer[4..4 + parameter_length], rep);
@@ -829,6 +884,10 @@ impl BuiltinDataDeserializer {
   }
 
   pub fn read_parameter_id(buffer: &Vec<u8>, rep: RepresentationIdentifier) -> Option<ParameterId> {
+    if buffer.len() < 2 {
+      return None;
+    }
+
     let par: Result<ParameterId, Error> = CDRDeserializerAdapter::from_bytes(&buffer[..2], rep);
     match par {
       Ok(val) => Some(val),
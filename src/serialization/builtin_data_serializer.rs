@@ -23,9 +23,12 @@ use crate::{
     vendor_id::{VendorId, VendorIdData},
     protocol_version::{ProtocolVersion, ProtocolVersionData},
   },
-  dds::qos::policy::{
-    Deadline, Durability, LatencyBudget, Liveliness, Reliability, Ownership, DestinationOrder,
-    TimeBasedFilter, Presentation, Lifespan, History, ResourceLimits, QosData,
+  dds::qos::{
+    QosPolicies,
+    policy::{
+      Deadline, Durability, LatencyBudget, Liveliness, Reliability, Ownership, DestinationOrder,
+      TimeBasedFilter, Presentation, Lifespan, History, ResourceLimits, QosData,
+    },
   },
 };
 use serde::{Serialize, Serializer, ser::SerializeStruct, Deserialize};
@@ -120,6 +123,7 @@ pub struct BuiltinDataSerializer<'a> {
   pub manual_liveliness_count: Option<i32>,
   pub builtin_enpoint_qos: Option<BuiltinEndpointQos>,
   pub entity_name: Option<&'a String>,
+  pub domain_tag: Option<&'a String>,
 
   pub endpoint_guid: Option<GUID>,
 
@@ -203,6 +207,10 @@ impl<'a> BuiltinDataSerializer<'a> {
       Some(v) => Some(v),
       None => self.entity_name,
     };
+    self.domain_tag = match other.domain_tag {
+      Some(v) => Some(v),
+      None => self.domain_tag,
+    };
     self.endpoint_guid = match other.endpoint_guid {
       Some(v) => Some(v),
       None => self.endpoint_guid,
@@ -300,6 +308,7 @@ impl<'a> BuiltinDataSerializer<'a> {
       manual_liveliness_count: participant_data.manual_liveliness_count,
       builtin_enpoint_qos: participant_data.builtin_enpoint_qos,
       entity_name: participant_data.entity_name.as_ref(),
+      domain_tag: participant_data.domain_tag.as_ref(),
       endpoint_guid: None,
       unicast_locator_list: None,
       multicast_locator_list: None,
@@ -337,6 +346,7 @@ impl<'a> BuiltinDataSerializer<'a> {
       manual_liveliness_count: None,
       builtin_enpoint_qos: None,
       entity_name: None,
+      domain_tag: None,
       endpoint_guid: reader_proxy.remote_reader_guid,
       unicast_locator_list: Some(&reader_proxy.unicast_locator_list),
       multicast_locator_list: Some(&reader_proxy.multicast_locator_list),
@@ -374,6 +384,7 @@ impl<'a> BuiltinDataSerializer<'a> {
       manual_liveliness_count: None,
       builtin_enpoint_qos: None,
       entity_name: None,
+      domain_tag: None,
       endpoint_guid: writer_proxy.remote_writer_guid,
       unicast_locator_list: Some(&writer_proxy.unicast_locator_list),
       multicast_locator_list: Some(&writer_proxy.multicast_locator_list),
@@ -412,7 +423,8 @@ impl<'a> BuiltinDataSerializer<'a> {
       lease_duration: None,
       manual_liveliness_count: None,
       builtin_enpoint_qos: None,
-      entity_name: None,
+      entity_name: subscription_topic_data.entity_name().as_ref(),
+      domain_tag: None,
       endpoint_guid: subscription_topic_data.key().clone(),
       unicast_locator_list: None,
       multicast_locator_list: None,
@@ -451,7 +463,8 @@ impl<'a> BuiltinDataSerializer<'a> {
       lease_duration: None,
       manual_liveliness_count: None,
       builtin_enpoint_qos: None,
-      entity_name: None,
+      entity_name: publication_topic_data.entity_name.as_ref(),
+      domain_tag: None,
       endpoint_guid: publication_topic_data.key,
       unicast_locator_list: None,
       multicast_locator_list: None,
@@ -489,6 +502,7 @@ impl<'a> BuiltinDataSerializer<'a> {
       manual_liveliness_count: None,
       builtin_enpoint_qos: None,
       entity_name: None,
+      domain_tag: None,
       endpoint_guid: topic_data.key,
       unicast_locator_list: None,
       multicast_locator_list: None,
@@ -511,6 +525,44 @@ impl<'a> BuiltinDataSerializer<'a> {
     }
   }
 
+  pub fn from_qos(qos: &QosPolicies) -> BuiltinDataSerializer<'a> {
+    BuiltinDataSerializer {
+      protocol_version: None,
+      vendor_id: None,
+      expects_inline_qos: None,
+      participant_guid: None,
+      metatraffic_unicast_locators: None,
+      metatraffic_multicast_locators: None,
+      default_unicast_locators: None,
+      default_multicast_locators: None,
+      available_builtin_endpoints: None,
+      lease_duration: None,
+      manual_liveliness_count: None,
+      builtin_enpoint_qos: None,
+      entity_name: None,
+      domain_tag: None,
+      endpoint_guid: None,
+      unicast_locator_list: None,
+      multicast_locator_list: None,
+      data_max_size_serialized: None,
+      topic_name: None,
+      type_name: None,
+      durability: qos.durability,
+      deadline: qos.deadline,
+      latency_budget: qos.latency_budget,
+      liveliness: qos.liveliness,
+      reliability: qos.reliability,
+      ownership: qos.ownership,
+      destination_order: qos.destination_order,
+      time_based_filter: qos.time_based_filter,
+      presentation: qos.presentation,
+      lifespan: qos.lifespan,
+      history: qos.history,
+      resource_limits: qos.resource_limits,
+      content_filter_property: None,
+    }
+  }
+
   pub fn from_discovered_reader_data(
     discovered_reader_data: &'a DiscoveredReaderData,
   ) -> BuiltinDataSerializer<'a> {
@@ -556,6 +608,7 @@ impl<'a> BuiltinDataSerializer<'a> {
     self.add_manual_liveliness_count::<S>(&mut s);
     self.add_builtin_endpoint_qos::<S>(&mut s);
     self.add_entity_name::<S>(&mut s);
+    self.add_domain_tag::<S>(&mut s);
 
     self.add_endpoint_guid::<S>(&mut s);
     self.add_unicast_locator_list::<S>(&mut s);
@@ -581,7 +634,9 @@ impl<'a> BuiltinDataSerializer<'a> {
     self.add_content_filter_property::<S>(&mut s);
 
     if add_sentinel {
-      s.serialize_field("sentinel", &(1 as u32)).unwrap();
+      // parameter id and zero length
+      s.serialize_field("sentinel", &(ParameterId::PID_SENTINEL, 0 as u16))
+        .unwrap();
     }
 
     s.end()
@@ -608,6 +663,7 @@ impl<'a> BuiltinDataSerializer<'a> {
     count = count + self.manual_liveliness_count.is_some() as usize;
     count = count + self.builtin_enpoint_qos.is_some() as usize;
     count = count + self.entity_name.is_some() as usize;
+    count = count + self.domain_tag.is_some() as usize;
 
     count = count + self.endpoint_guid.is_some() as usize;
     count = count + self.unicast_locator_list.unwrap_or(&empty_ll).len();
@@ -641,12 +697,7 @@ impl<'a> BuiltinDataSerializer<'a> {
         s.serialize_field("protocol_version", &ProtocolVersionData::from(pv))
           .unwrap();
       }
-      None => s
-        .serialize_field(
-          "protocol_version",
-          &ProtocolVersionData::from(ProtocolVersion::PROTOCOLVERSION_2_3),
-        )
-        .unwrap(),
+      None => (),
     }
   }
 
@@ -656,9 +707,7 @@ impl<'a> BuiltinDataSerializer<'a> {
         s.serialize_field("vendor_id", &VendorIdData::from(vid))
           .unwrap();
       }
-      None => s
-        .serialize_field("vendor_id", &VendorIdData::from(VendorId::VENDOR_UNKNOWN))
-        .unwrap(),
+      None => (),
     }
   }
 
@@ -815,6 +864,19 @@ impl<'a> BuiltinDataSerializer<'a> {
     }
   }
 
+  fn add_domain_tag<S: Serializer>(&self, s: &mut S::SerializeStruct) {
+    match self.domain_tag.as_ref() {
+      Some(tag) => {
+        s.serialize_field(
+          "domain_tag",
+          &StringData::new(ParameterId::PID_DOMAIN_TAG, tag),
+        )
+        .unwrap();
+      }
+      None => (),
+    }
+  }
+
   fn add_endpoint_guid<S: Serializer>(&self, s: &mut S::SerializeStruct) {
     match self.endpoint_guid {
       Some(guid) => {
@@ -61,6 +61,18 @@ where
 /// [`SerializerAdapter`]. CDR_serializer cannot directly implement the trait itself, because
 /// CDR_serializer has the type parameter BO open, and the adapter needs to be bi-endian.
 ///
+/// Rust types that have no direct counterpart in CDR are mapped as follows:
+/// * An enum with only unit variants is an IDL enum: the variant index as unsigned long.
+/// * An enum with data is an IDL union with an unsigned long discriminant, the variant
+///   index. The discriminant is followed by the member of the variant, if any. Tuple and
+///   struct variants have a struct as member.
+/// * `Option<T>` is a union of `None` and `Some`: an unsigned long 0 or 1, followed by
+///   the value if there is one. With the `cdr_option_bool` feature it is an optional
+///   member instead: a boolean telling if the value is present, followed by the value.
+///   The two encodings do not interoperate.
+///
+/// [`CDRDeserializerAdapter`](struct.CDRDeserializerAdapter.html) reads the same mapping.
+///
 /// [`SerializerAdapter`]: ../dds/traits/serde_adapters/trait.SerializerAdapter.html
 
 // A struct separate from CDR_serializer is needed, because the neme to_writer is already taken
@@ -72,6 +84,19 @@ where
   ghost: PhantomData<BO>,
 }
 
+impl<D, BO> CDRSerializerAdapter<D, BO>
+where
+  D: Serialize,
+  BO: ByteOrder,
+{
+  /// The number of bytes `value` serializes to, e.g. to allocate an exactly sized
+  /// buffer for [`to_writer`](#method.to_writer). This is a serialization pass of its
+  /// own, so it costs about as much time as serializing.
+  pub fn serialized_size(value: &D) -> Result<usize> {
+    serialized_size::<D, BO>(value)
+  }
+}
+
 impl<D> SerializerAdapter<D> for CDRSerializerAdapter<D, LittleEndian>
 where
   D: Serialize,
@@ -83,6 +108,13 @@ where
   fn to_writer<W: io::Write>(writer: W, value: &D) -> Result<()> {
     to_writer::<D, LittleEndian, W>(writer, value)
   }
+
+  fn key_to_bytes<K>(value: &K) -> Result<Vec<u8>>
+  where
+    K: Serialize,
+  {
+    to_bytes::<K, LittleEndian>(value)
+  }
 }
 
 impl<D> SerializerAdapter<D> for CDRSerializerAdapter<D, BigEndian>
@@ -96,6 +128,13 @@ where
   fn to_writer<W: io::Write>(writer: W, value: &D) -> Result<()> {
     to_writer::<D, BigEndian, W>(writer, value)
   }
+
+  fn key_to_bytes<K>(value: &K) -> Result<Vec<u8>>
+  where
+    K: Serialize,
+  {
+    to_bytes::<K, BigEndian>(value)
+  }
 }
 
 // ---------------------------------------------------------------------------------
@@ -126,15 +165,28 @@ where
   fn calculate_padding_need_and_write_padding(&mut self, typeOctetAlignment: u8) -> Result<()> {
     let modulo: u32 = self.writer.count() as u32 % typeOctetAlignment as u32;
     if modulo != 0 {
-      let paddingNeed: u32 = typeOctetAlignment as u32 - modulo;
-      for _x in 0..paddingNeed {
-        self.writer.write_u8(0)?
-      }
+      let paddingNeed = (typeOctetAlignment as u32 - modulo) as usize;
+      self.writer.write_all(&PADDING[..paddingNeed])?;
     }
     Ok(())
   }
+
+  // Tells if an Option has a value, see serialize_none
+  fn serialize_option_tag(&mut self, is_some: bool) -> Result<()> {
+    if cfg!(feature = "cdr_option_bool") {
+      ser::Serializer::serialize_bool(self, is_some)
+    } else {
+      ser::Serializer::serialize_u32(self, is_some as u32)
+    }
+  }
 }
 
+// Enough zeros for the largest alignment, that of u128
+const PADDING: [u8; 16] = [0; 16];
+
+/// Serializes `value` directly into `writer`, e.g. a buffer taken from a pool, without
+/// intermediate buffers. Alignment is counted from the first byte written, so `writer`
+/// may already contain e.g. a header.
 pub fn to_writer<T, BO, W>(writer: W, value: &T) -> Result<()>
 where
   T: Serialize,
@@ -144,6 +196,17 @@ where
   value.serialize(&mut CDR_serializer::<W, BO>::new(writer))
 }
 
+/// The number of bytes `value` serializes to. Nothing is allocated for this.
+pub fn serialized_size<T, BO>(value: &T) -> Result<usize>
+where
+  T: Serialize,
+  BO: ByteOrder,
+{
+  let mut serializer = CDR_serializer::<io::Sink, BO>::new(io::sink());
+  value.serialize(&mut serializer)?;
+  Ok(serializer.writer.count() as usize)
+}
+
 pub fn to_bytes<T, BO>(value: &T) -> Result<Vec<u8>>
 where
   T: Serialize,
@@ -298,26 +361,30 @@ where
     self.calculate_padding_need_and_write_padding(4)?;
     let byte_count: u32 = v.as_bytes().len() as u32 + 1;
     self.serialize_u32(byte_count)?; // +1 for terminator
-    self.writer.write(v.as_bytes())?;
+    self.writer.write_all(v.as_bytes())?;
     self.writer.write_u8(0)?; // CDR spec requires a null terminator
     Ok(())
     // The end result is not UTF-8-encoded string, but how could we do better in CDR?
   }
 
   fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-    self.writer.write(v)?;
+    self.writer.write_all(v)?;
     Ok(())
   }
 
+  // CDR 1 has no optional members, so Option is a union with None as the first variant
+  // and Some as the second. With the cdr_option_bool feature Option is an optional
+  // member, which is preceded by a boolean telling if it is present, like in XCDR2
+  // (DDS-XTypes v1.3 Section 7.4.3.5.3).
   fn serialize_none(self) -> Result<()> {
-    self.serialize_u32(0) // None is the first variant
+    self.serialize_option_tag(false)
   }
 
   fn serialize_some<T>(self, t: &T) -> Result<()>
   where
     T: ?Sized + Serialize,
   {
-    self.serialize_u32(1)?; // Some is the second variant
+    self.serialize_option_tag(true)?;
     t.serialize(self)?;
     Ok(())
   }
@@ -355,18 +422,20 @@ where
     value.serialize(self)
   }
 
-  // As this is technically an enum, we treat this as union with one variat.
+  // Enums with data are IDL unions (CDR 15.3.2.7): the discriminant, an unsigned long
+  // like for plain enums, followed by the member of the active variant. Unit variants
+  // are cases without a member, and tuple and struct variants have a struct as member.
   fn serialize_newtype_variant<T>(
     self,
     _name: &'static str,
-    _variant_index: u32,
+    variant_index: u32,
     _variant: &'static str,
     value: &T,
   ) -> Result<()>
   where
     T: ?Sized + Serialize,
   {
-    self.serialize_u32(0)?;
+    self.serialize_u32(variant_index)?;
     value.serialize(self)
   }
 
@@ -571,7 +640,9 @@ impl<'a, W: io::Write, BO: ByteOrder> ser::SerializeStructVariant
 mod tests {
   use crate::serialization::cdr_serializer::to_little_endian_binary;
   use crate::serialization::cdr_serializer::to_big_endian_binary;
+  use crate::serialization::cdr_serializer::{serialized_size, to_writer};
   use crate::serialization::cdr_deserializer::deserialize_from_little_endian;
+  use byteorder::LittleEndian;
   use log::info;
   use serde::{Serialize, Deserialize};
   use serde_repr::{Serialize_repr, Deserialize_repr};
@@ -792,4 +863,65 @@ mod tests {
     ];
     assert_eq!(expected, sarjallistettu)
   }
+
+  #[derive(Serialize)]
+  struct Point {
+    x: f32,
+    y: f32,
+    z: f32,
+  }
+
+  #[derive(Serialize)]
+  struct Cloud {
+    frame: u16,
+    points: Vec<Point>,
+    rings: Vec<Vec<u8>>,
+  }
+
+  fn cloud() -> Cloud {
+    Cloud {
+      frame: 1,
+      points: vec![
+        Point {
+          x: 1.0,
+          y: 2.0,
+          z: -1.0,
+        },
+        Point {
+          x: 0.5,
+          y: 0.0,
+          z: 4.0,
+        },
+      ],
+      rings: vec![vec![1, 2, 3], vec![9]],
+    }
+  }
+
+  #[test]
+  fn CDR_serialize_nested_sequences() {
+    let expected: Vec<u8> = vec![
+      0x01, 0x00, 0x00, 0x00, // frame, padding
+      0x02, 0x00, 0x00, 0x00, // number of points
+      0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x80, 0xbf, // point
+      0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x40, // point
+      0x02, 0x00, 0x00, 0x00, // number of rings
+      0x03, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03, 0x00, // ring, padding
+      0x01, 0x00, 0x00, 0x00, 0x09, // ring
+    ];
+    let serialized = to_little_endian_binary(&cloud()).unwrap();
+    assert_eq!(expected, serialized);
+    assert_eq!(
+      serialized_size::<Cloud, LittleEndian>(&cloud()).unwrap(),
+      expected.len()
+    );
+  }
+
+  #[test]
+  fn CDR_serialize_to_writer_after_header() {
+    // Alignment is counted from the start of the serialization, not of the buffer.
+    let mut buffer: Vec<u8> = vec![0xaa; 3];
+    to_writer::<Cloud, LittleEndian, &mut Vec<u8>>(&mut buffer, &cloud()).unwrap();
+    assert_eq!(buffer[..3], [0xaa; 3]);
+    assert_eq!(buffer[3..], to_little_endian_binary(&cloud()).unwrap()[..]);
+  }
 }
@@ -0,0 +1,148 @@
+use std::{io, marker::PhantomData};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::serialization::error::Error;
+use crate::serialization::error::Result;
+use crate::dds::traits::serde_adapters::{DeserializerAdapter, SerializerAdapter};
+use crate::messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier;
+
+/// Serializes data as JSON, with the vendor-specific representation identifier
+/// `JSON`. This is meant for debugging and for bridging to systems that do not
+/// speak CDR. Other DDS implementations do not understand it, and DataReaders using a
+/// CDR adapter reject such samples.
+///
+/// Read the data with [`JsonDeserializerAdapter`](struct.JsonDeserializerAdapter.html).
+pub struct JsonSerializerAdapter<D> {
+  phantom: PhantomData<D>,
+}
+
+impl<D> SerializerAdapter<D> for JsonSerializerAdapter<D>
+where
+  D: Serialize,
+{
+  fn output_encoding() -> RepresentationIdentifier {
+    RepresentationIdentifier::JSON
+  }
+
+  fn to_writer<W: io::Write>(writer: W, value: &D) -> Result<()> {
+    serde_json::to_writer(writer, value).map_err(json_error)
+  }
+
+  fn key_to_bytes<K>(value: &K) -> Result<Vec<u8>>
+  where
+    K: Serialize,
+  {
+    serde_json::to_vec(value).map_err(json_error)
+  }
+}
+
+/// Deserializes data written by
+/// [`JsonSerializerAdapter`](struct.JsonSerializerAdapter.html). Samples in any other
+/// representation are rejected.
+pub struct JsonDeserializerAdapter<D> {
+  phantom: PhantomData<D>,
+}
+
+const repr_ids: [RepresentationIdentifier; 1] = [RepresentationIdentifier::JSON];
+
+impl<D> DeserializerAdapter<D> for JsonDeserializerAdapter<D>
+where
+  D: DeserializeOwned,
+{
+  fn supported_encodings() -> &'static [RepresentationIdentifier] {
+    &repr_ids
+  }
+
+  fn from_bytes<'de>(input_bytes: &'de [u8], encoding: RepresentationIdentifier) -> Result<D> {
+    from_bytes(input_bytes, encoding)
+  }
+
+  fn key_from_bytes<'de, K>(input_bytes: &'de [u8], encoding: RepresentationIdentifier) -> Result<K>
+  where
+    K: DeserializeOwned,
+  {
+    from_bytes(input_bytes, encoding)
+  }
+}
+
+fn from_bytes<T>(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<T>
+where
+  T: DeserializeOwned,
+{
+  match encoding {
+    RepresentationIdentifier::JSON => serde_json::from_slice(input_bytes).map_err(json_error),
+    repr_id => Err(Error::Message(format!(
+      "Unknown representaiton identifier {}.",
+      u16::from(repr_id)
+    ))),
+  }
+}
+
+fn json_error(e: serde_json::Error) -> Error {
+  Error::Message(format!("JSON: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde::Deserialize;
+  use crate::serialization::CDRDeserializerAdapter;
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq)]
+  struct Sample {
+    id: u32,
+    name: String,
+    values: Vec<f64>,
+    parent: Option<Box<Sample>>,
+  }
+
+  fn sample() -> Sample {
+    Sample {
+      id: 7,
+      name: "child".to_string(),
+      values: vec![1.5, -2.0],
+      parent: Some(Box::new(Sample {
+        id: 1,
+        name: "parent".to_string(),
+        values: vec![],
+        parent: None,
+      })),
+    }
+  }
+
+  #[test]
+  fn json_round_trip() {
+    type SA = JsonSerializerAdapter<Sample>;
+    type DA = JsonDeserializerAdapter<Sample>;
+
+    let bytes = SA::to_bytes(&sample()).unwrap();
+    assert_eq!(
+      std::str::from_utf8(&bytes).unwrap(),
+      r#"{"id":7,"name":"child","values":[1.5,-2.0],"parent":{"id":1,"name":"parent","values":[],"parent":null}}"#
+    );
+    assert!(DA::supported_encodings().contains(&SA::output_encoding()));
+    let decoded = DA::from_bytes(&bytes, SA::output_encoding()).unwrap();
+    assert_eq!(decoded, sample());
+
+    let key_bytes = SA::key_to_bytes(&(7u32, "child".to_string())).unwrap();
+    let key: (u32, String) = DA::key_from_bytes(&key_bytes, SA::output_encoding()).unwrap();
+    assert_eq!(key, (7, "child".to_string()));
+  }
+
+  #[test]
+  fn json_rejects_other_representations() {
+    type DA = JsonDeserializerAdapter<Sample>;
+
+    let bytes = JsonSerializerAdapter::<Sample>::to_bytes(&sample()).unwrap();
+    assert!(DA::from_bytes(&bytes, RepresentationIdentifier::CDR_LE).is_err());
+    assert!(DA::from_bytes(&bytes[..bytes.len() - 1], RepresentationIdentifier::JSON).is_err());
+
+    // and CDR readers do not take JSON
+    assert!(!CDRDeserializerAdapter::<Sample>::supported_encodings()
+      .contains(&RepresentationIdentifier::JSON));
+    assert!(
+      CDRDeserializerAdapter::<Sample>::from_bytes(&bytes, RepresentationIdentifier::JSON).is_err()
+    );
+  }
+}
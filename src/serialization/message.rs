@@ -3,7 +3,7 @@ use std::{collections::HashSet, io};
 use crate::{
   structure::entity::Entity,
   dds::{
-    data_types::{DDSTimestamp, GUID},
+    data_types::{Timestamp, GUID},
     writer::Writer as RtpsWriter,
   },
   messages::header::Header,
@@ -11,9 +11,10 @@ use crate::{
   serialization::submessage::{SubMessage, SubmessageBody},
   structure::{sequence_number::SequenceNumber, guid::GuidPrefix},
 };
-use log::warn;
+use log::{debug, warn};
 use speedy::{Readable, Writable, Endianness, Context, Writer};
 use enumflags2::BitFlags;
+use bytes::Bytes;
 //use time::{Timespec, get_time};
 
 #[derive(Debug)]
@@ -62,19 +63,32 @@ impl<'a> Message {
     sequence_numbers
   }
 
+  // Whether the message is addressed to one participant with InfoDestination.
+  pub fn is_addressed(&self) -> bool {
+    self.submessages.iter().any(|s| match &s.body {
+      SubmessageBody::Interpreter(InterpreterSubmessage::InfoDestination(dst, _)) => {
+        dst.guid_prefix != GuidPrefix::GUIDPREFIX_UNKNOWN
+      }
+      _ => false,
+    })
+  }
+
   // We implement this instead of Speedy trait Readable, because
   // we need to run-time decide which endianness we input. Speedy requires the
   // top level to fix that. And there seems to be no reasonable way to change endianness.
   // TODO: The error type should be something better
-  pub fn read_from_buffer(buffer: &'a [u8]) -> io::Result<Message> {
+  // Payloads of DATA and DATA_FRAG submessages are slices of buffer, not copies.
+  // Submessages that are of unknown kind or fail to parse are skipped, and the rest
+  // of the message is read, because their length is known from the submessage header.
+  pub fn read_from_buffer(buffer: &Bytes) -> io::Result<Message> {
     // The Header deserializes the same
     let rtps_header =
       Header::read_from_buffer(buffer).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
     let mut message = Message::new(rtps_header);
-    let mut submessages_left = &buffer[20..]; // header is 20 bytes
-                                              // submessage loop
+    let mut submessages_left = buffer.slice(20..); // header is 20 bytes
+                                                   // submessage loop
     while submessages_left.len() > 0 {
-      let sub_header = SubmessageHeader::read_from_buffer(submessages_left)
+      let sub_header = SubmessageHeader::read_from_buffer(&submessages_left)
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
       // Try to figure out how large this submessage is.
       let sub_header_length = 4; // 4 bytes
@@ -97,120 +111,145 @@ impl<'a> Message {
         sub_header.content_length as usize
       };
 
-      // we have to use temporary variable new_submessages_left to avoid creating another
-      // submessages_left
-      let (sub_buffer, new_submessages_left) =
-        submessages_left.split_at(sub_header_length + sub_content_length);
-      submessages_left = new_submessages_left;
-      let (_sub_header, sub_content_buffer) = sub_buffer.split_at(sub_header_length);
-
-      let e = endianness_flag(sub_header.flags);
-      let mk_e_subm = move |s: EntitySubmessage| {
-        Ok(SubMessage {
-          header: sub_header,
-          body: SubmessageBody::Entity(s),
-        })
-      };
-      let mk_i_subm = move |s: InterpreterSubmessage| {
-        Ok(SubMessage {
-          header: sub_header,
-          body: SubmessageBody::Interpreter(s),
-        })
-      };
+      if sub_header_length + sub_content_length > submessages_left.len() {
+        return Err(io::Error::new(
+          io::ErrorKind::UnexpectedEof,
+          "Submessage is longer than the rest of the message",
+        ));
+      }
+      let sub_content_buffer = submessages_left
+        .split_to(sub_header_length + sub_content_length)
+        .slice(sub_header_length..);
+
+      match Message::read_submessage(sub_header, &sub_content_buffer) {
+        Ok(Some(submessage)) => message.submessages.push(submessage),
+        Ok(None) => (),
+        Err(e) => warn!(
+          "Skipping a {:?} submessage that failed to parse: {:?}",
+          sub_header.kind, e
+        ),
+      }
+    } // loop
 
-      let new_submessage_result: io::Result<SubMessage> = match sub_header.kind {
-        SubmessageKind::DATA => {
-          // Manually implemented deserialization for DATA. Speedy does not quite cut it.
-          let f = BitFlags::<DATA_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::Data(
-            Data::deserialize_data(sub_content_buffer, f)?,
-            f,
-          ))
-        }
+    Ok(message)
+  }
 
-        SubmessageKind::DATA_FRAG => {
-          // Manually implemented deserialization for DATA. Speedy does not quite cut it.
-          let f = BitFlags::<DATAFRAG_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::DataFrag(
-            DataFrag::deserialize(sub_content_buffer, f)?,
-            f,
-          ))
-        }
+  // None for submessages that are skipped
+  fn read_submessage(
+    sub_header: SubmessageHeader,
+    sub_content_buffer: &Bytes,
+  ) -> io::Result<Option<SubMessage>> {
+    let e = endianness_flag(sub_header.flags);
+    let mk_e_subm = move |s: EntitySubmessage| -> io::Result<Option<SubMessage>> {
+      Ok(Some(SubMessage {
+        header: sub_header,
+        body: SubmessageBody::Entity(s),
+      }))
+    };
+    let mk_i_subm = move |s: InterpreterSubmessage| -> io::Result<Option<SubMessage>> {
+      Ok(Some(SubMessage {
+        header: sub_header,
+        body: SubmessageBody::Interpreter(s),
+      }))
+    };
 
-        SubmessageKind::GAP => {
-          let f = BitFlags::<GAP_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::Gap(
-            Gap::read_from_buffer_with_ctx(e, sub_content_buffer)?,
-            f,
-          ))
-        }
+    match sub_header.kind {
+      SubmessageKind::DATA => {
+        // Manually implemented deserialization for DATA. Speedy does not quite cut it.
+        let f = BitFlags::<DATA_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::Data(
+          Data::deserialize_data(sub_content_buffer, f)?,
+          f,
+        ))
+      }
 
-        SubmessageKind::ACKNACK => {
-          let f = BitFlags::<ACKNACK_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::AckNack(
-            AckNack::read_from_buffer_with_ctx(e, sub_content_buffer)?,
-            f,
-          ))
-        }
+      SubmessageKind::DATA_FRAG => {
+        // Manually implemented deserialization for DATA. Speedy does not quite cut it.
+        let f = BitFlags::<DATAFRAG_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::DataFrag(
+          DataFrag::deserialize(sub_content_buffer, f)?,
+          f,
+        ))
+      }
 
-        SubmessageKind::NACK_FRAG => {
-          let f = BitFlags::<NACKFRAG_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::NackFrag(
-            NackFrag::read_from_buffer_with_ctx(e, sub_content_buffer)?,
-            f,
-          ))
-        }
+      SubmessageKind::GAP => {
+        let f = BitFlags::<GAP_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::Gap(
+          Gap::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
 
-        SubmessageKind::HEARTBEAT => {
-          let f = BitFlags::<HEARTBEAT_Flags>::from_bits_truncate(sub_header.flags);
-          mk_e_subm(EntitySubmessage::Heartbeat(
-            Heartbeat::read_from_buffer_with_ctx(e, sub_content_buffer)?,
-            f,
-          ))
-        }
+      SubmessageKind::ACKNACK => {
+        let f = BitFlags::<ACKNACK_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::AckNack(
+          AckNack::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
 
-        // interpreter submessages
-        SubmessageKind::INFO_DST => {
-          let f = BitFlags::<INFODESTINATION_Flags>::from_bits_truncate(sub_header.flags);
-          mk_i_subm(InterpreterSubmessage::InfoDestination(
-            InfoDestination::read_from_buffer_with_ctx(e, sub_content_buffer)?,
-            f,
-          ))
-        }
-        SubmessageKind::INFO_SRC => {
-          let f = BitFlags::<INFOSOURCE_Flags>::from_bits_truncate(sub_header.flags);
-          mk_i_subm(InterpreterSubmessage::InfoSource(
-            InfoSource::read_from_buffer_with_ctx(e, sub_content_buffer)?,
-            f,
-          ))
-        }
-        SubmessageKind::INFO_TS => {
-          let f = BitFlags::<INFOTIMESTAMP_Flags>::from_bits_truncate(sub_header.flags);
-          mk_i_subm(InterpreterSubmessage::InfoTimestamp(
-            InfoTimestamp::read_from_buffer_with_ctx(e, sub_content_buffer)?,
-            f,
-          ))
-        }
-        SubmessageKind::INFO_REPLY => {
-          let f = BitFlags::<INFOREPLY_Flags>::from_bits_truncate(sub_header.flags);
-          mk_i_subm(InterpreterSubmessage::InfoReply(
-            InfoReply::read_from_buffer_with_ctx(e, sub_content_buffer)?,
-            f,
-          ))
-        }
-        SubmessageKind::PAD => {
-          continue; // nothing to do here
-        }
-        unknown_kind => {
-          warn!("Received unknown submessage kind {:?}", unknown_kind);
-          continue;
-        }
-      }; // match
+      SubmessageKind::NACK_FRAG => {
+        let f = BitFlags::<NACKFRAG_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::NackFrag(
+          NackFrag::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
 
-      message.submessages.push(new_submessage_result?)
-    } // loop
+      SubmessageKind::HEARTBEAT => {
+        let f = BitFlags::<HEARTBEAT_Flags>::from_bits_truncate(sub_header.flags);
+        mk_e_subm(EntitySubmessage::Heartbeat(
+          Heartbeat::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
 
-    Ok(message)
+      // interpreter submessages
+      SubmessageKind::INFO_DST => {
+        let f = BitFlags::<INFODESTINATION_Flags>::from_bits_truncate(sub_header.flags);
+        mk_i_subm(InterpreterSubmessage::InfoDestination(
+          InfoDestination::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
+      SubmessageKind::INFO_SRC => {
+        let f = BitFlags::<INFOSOURCE_Flags>::from_bits_truncate(sub_header.flags);
+        mk_i_subm(InterpreterSubmessage::InfoSource(
+          InfoSource::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
+      SubmessageKind::INFO_TS => {
+        let f = BitFlags::<INFOTIMESTAMP_Flags>::from_bits_truncate(sub_header.flags);
+        // With the Invalidate flag the submessage has no timestamp.
+        let info_ts = if f.contains(INFOTIMESTAMP_Flags::Invalidate) {
+          InfoTimestamp {
+            timestamp: Timestamp::TIME_INVALID,
+          }
+        } else {
+          InfoTimestamp::read_from_buffer_with_ctx(e, sub_content_buffer)?
+        };
+        mk_i_subm(InterpreterSubmessage::InfoTimestamp(info_ts, f))
+      }
+      SubmessageKind::INFO_REPLY => {
+        let f = BitFlags::<INFOREPLY_Flags>::from_bits_truncate(sub_header.flags);
+        mk_i_subm(InterpreterSubmessage::InfoReply(
+          InfoReply::read_from_buffer_with_ctx(e, sub_content_buffer)?,
+          f,
+        ))
+      }
+      SubmessageKind::PAD => {
+        Ok(None) // nothing to do here
+      }
+      vendor_kind if vendor_kind.is_vendor_specific() => {
+        debug!("Skipping vendor specific submessage kind {:?}", vendor_kind);
+        Ok(None)
+      }
+      unknown_kind => {
+        warn!("Received unknown submessage kind {:?}", unknown_kind);
+        Ok(None)
+      }
+    } // match
   }
 }
 
@@ -284,31 +323,11 @@ impl MessageBuilder {
     self
   }
 
-  pub fn ts_msg(mut self, endianness: Endianness, invalidate_flagset: bool) -> MessageBuilder {
-    let timestamp = InfoTimestamp {
-      timestamp: DDSTimestamp::now(),
-    };
-    let mes = &mut timestamp.write_to_vec_with_ctx(endianness).unwrap();
-
-    let flags = BitFlags::<INFOTIMESTAMP_Flags>::from_endianness(endianness)
-      | (if invalidate_flagset {
-        INFOTIMESTAMP_Flags::Invalidate.into()
-      } else {
-        BitFlags::<INFOTIMESTAMP_Flags>::empty()
-      });
-
-    let submessageHeader = SubmessageHeader {
-      kind: SubmessageKind::INFO_TS,
-      flags: flags.bits(),
-      content_length: mes.len() as u16, // This conversion should be safe, as timestamp length cannot exceed u16
-    };
-
-    let submsg = SubMessage {
-      header: submessageHeader,
-      body: SubmessageBody::Interpreter(InterpreterSubmessage::InfoTimestamp(timestamp, flags)),
-    };
-
-    self.submessages.push(submsg);
+  // INFO_TS with the timestamp, or with the Invalidate flag for None
+  pub fn ts_msg(mut self, endianness: Endianness, timestamp: Option<Timestamp>) -> MessageBuilder {
+    self
+      .submessages
+      .push(InfoTimestamp::create_submessage(timestamp, endianness));
     self
   }
 
@@ -318,12 +337,7 @@ impl MessageBuilder {
     writer: &RtpsWriter,
     reader_guid: GUID,
   ) -> MessageBuilder {
-    let instant = match writer.sequence_number_to_instant(seqnum) {
-      Some(i) => i,
-      None => return self,
-    };
-
-    let cache_change = match writer.find_cache_change(instant) {
+    let cache_change = match writer.find_cache_change_by_sn(seqnum) {
       Some(cc) => cc,
       None => return self,
     };
@@ -341,7 +355,9 @@ impl MessageBuilder {
     set_final_flag: bool,
     set_liveliness_flag: bool,
   ) -> MessageBuilder {
-    let first = writer.first_change_sequence_number;
+    // Before anything is written first_change_sequence_number is 0, but a valid
+    // Heartbeat of no changes has first_sn 1 and last_sn 0.
+    let first = std::cmp::max(writer.first_change_sequence_number, SequenceNumber::from(1));
     let last = writer.last_change_sequence_number;
 
     let heartbeat = Heartbeat {
@@ -369,6 +385,17 @@ impl MessageBuilder {
     self
   }
 
+  pub fn gap_msgs(mut self, writer: &RtpsWriter, gaps: Vec<Gap>) -> MessageBuilder {
+    let flags = BitFlags::<GAP_Flags>::from_endianness(writer.endianness);
+    for gap in gaps {
+      match gap.create_submessage(flags) {
+        Some(sm) => self.submessages.push(sm),
+        None => return self,
+      }
+    }
+    self
+  }
+
   pub fn build(self) -> Result<Message, String> {
     let header = match self.header {
       Some(h) => h,
@@ -407,7 +434,7 @@ mod tests {
       0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x5b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
       0x5b, 0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00,
     ];
-    let rtps = Message::read_from_buffer(&bits1).unwrap();
+    let rtps = Message::read_from_buffer(&Bytes::copy_from_slice(&bits1)).unwrap();
     info!("{:?}", rtps);
 
     let serialized = rtps
@@ -444,7 +471,7 @@ mod tests {
       0x00,
     ];
 
-    let rtps_data = Message::read_from_buffer(&bits2).unwrap();
+    let rtps_data = Message::read_from_buffer(&Bytes::copy_from_slice(&bits2)).unwrap();
 
     let serialized_data = rtps_data
       .write_to_vec_with_ctx(Endianness::LittleEndian)
@@ -473,7 +500,7 @@ mod tests {
       0x70, 0x61, 0x6e, 0x74, 0x00, 0x01, 0x00, 0x00, 0x00,
     ];
 
-    let rtps = Message::read_from_buffer(&bits1).unwrap();
+    let rtps = Message::read_from_buffer(&Bytes::copy_from_slice(&bits1)).unwrap();
     info!("{:?}", rtps);
 
     let serialized = rtps
@@ -497,7 +524,7 @@ mod tests {
       0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
     ];
 
-    let rtps = Message::read_from_buffer(&bits1).unwrap();
+    let rtps = Message::read_from_buffer(&Bytes::copy_from_slice(&bits1)).unwrap();
     info!("{:?}", rtps);
 
     let serialized = rtps
@@ -527,7 +554,7 @@ mod tests {
       0x70, 0x61, 0x6e, 0x74, 0x00, 0x01, 0x00, 0x00, 0x00,
     ];
 
-    let rtps = Message::read_from_buffer(&bits1).unwrap();
+    let rtps = Message::read_from_buffer(&Bytes::copy_from_slice(&bits1)).unwrap();
     info!("{:?}", rtps);
 
     let serialized = rtps
@@ -569,7 +596,7 @@ mod tests {
       0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
     ];
 
-    let rtps = Message::read_from_buffer(&bits1).unwrap();
+    let rtps = Message::read_from_buffer(&Bytes::copy_from_slice(&bits1)).unwrap();
     info!("{:?}", rtps);
 
     let dataSubmessage = match &rtps.submessages[2] {
@@ -593,6 +620,78 @@ mod tests {
     assert_eq!(bits1, serialized);
   }
 
+  #[test]
+  fn message_skips_vendor_specific_and_malformed_submessages() {
+    // INFO_DST, INFO_TS, DATA, HEARTBEAT captured from shapes demo
+    let capture: Vec<u8> = vec![
+      0x52, 0x54, 0x50, 0x53, 0x02, 0x03, 0x01, 0x0f, 0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00,
+      0x00, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x01, 0x0c, 0x00, 0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d,
+      0x31, 0xa2, 0x28, 0x20, 0x02, 0x08, 0x09, 0x01, 0x08, 0x00, 0x1a, 0x15, 0xf3, 0x5e, 0x00,
+      0xcc, 0xfb, 0x13, 0x15, 0x05, 0x2c, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x07,
+      0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x5b, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+      0x00, 0x04, 0x00, 0x00, 0x00, 0x52, 0x45, 0x44, 0x00, 0x69, 0x00, 0x00, 0x00, 0x17, 0x00,
+      0x00, 0x00, 0x1e, 0x00, 0x00, 0x00, 0x07, 0x01, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00,
+      0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x5b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+      0x5b, 0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00,
+    ];
+    let header = &capture[..20];
+    let info_dst = &capture[20..36];
+    let info_ts = &capture[36..48];
+    let data = &capture[48..96];
+    let heartbeat = &capture[96..];
+    // octetsToInlineQos shorter than the DATA header
+    let mut malformed_data = data.to_vec();
+    malformed_data[6] = 0x04;
+
+    let parts: [&[u8]; 8] = [
+      header,
+      info_dst,
+      &[0x80, 0x01, 0x04, 0x00, 0x01, 0x02, 0x03, 0x04],
+      info_ts,
+      data,
+      &[0xff, 0x01, 0x04, 0x00, 0xde, 0xad, 0xbe, 0xef],
+      &malformed_data,
+      heartbeat,
+    ];
+    let message = Message::read_from_buffer(&Bytes::from(parts.concat())).unwrap();
+
+    let kinds: Vec<SubmessageKind> = message.submessages.iter().map(|s| s.header.kind).collect();
+    assert_eq!(
+      kinds,
+      vec![
+        SubmessageKind::INFO_DST,
+        SubmessageKind::INFO_TS,
+        SubmessageKind::DATA,
+        SubmessageKind::HEARTBEAT
+      ]
+    );
+    let serialized = message
+      .write_to_vec_with_ctx(Endianness::LittleEndian)
+      .unwrap();
+    assert_eq!(serialized, capture);
+  }
+
+  #[test]
+  fn message_is_addressed() {
+    let prefix = GUID::new().guidPrefix;
+    let header = Header::new(prefix);
+    assert!(!Message::new(header.clone()).is_addressed());
+
+    let to_unknown = MessageBuilder::new()
+      .header(header.clone())
+      .dst_submessage(Endianness::LittleEndian, GuidPrefix::GUIDPREFIX_UNKNOWN)
+      .build()
+      .unwrap();
+    assert!(!to_unknown.is_addressed());
+
+    let to_reader = MessageBuilder::new()
+      .header(header)
+      .dst_submessage(Endianness::LittleEndian, prefix)
+      .build()
+      .unwrap();
+    assert!(to_reader.is_addressed());
+  }
+
   // removed case test_RTPS_submessage_flags_helper , as it was cut-and-paste from
   // submessage_flag module - and obsoleted there.
 }
@@ -3,8 +3,14 @@ pub(crate) mod builtin_data_serializer;
 pub(crate) mod cdr_deserializer;
 pub(crate) mod cdr_serializer;
 pub(crate) mod error;
+#[cfg(feature = "json")]
+pub(crate) mod json_adapter;
+pub(crate) mod parameter_list;
 pub(crate) mod pl_cdr_deserializer;
+pub(crate) mod pl_cdr_serializer;
 pub(crate) mod visitors;
+pub(crate) mod xcdr2_deserializer;
+pub(crate) mod xcdr2_serializer;
 
 pub(crate) mod message;
 pub(crate) mod submessage;
@@ -16,4 +22,15 @@ pub(crate) use submessage::*;
 // public exports
 pub use cdr_serializer::{CDRSerializerAdapter};
 pub use cdr_deserializer::{CDRDeserializerAdapter};
+#[cfg(feature = "json")]
+pub use json_adapter::{JsonDeserializerAdapter, JsonSerializerAdapter};
+pub use pl_cdr_deserializer::PlCdrDeserializerAdapter;
+pub use pl_cdr_serializer::PlCdrSerializerAdapter;
+pub use parameter_list::ParameterList;
+pub use xcdr2_deserializer::Xcdr2DeserializerAdapter;
+pub use xcdr2_serializer::{
+  AppendableExtensibility, FinalExtensibility, Xcdr2Extensibility, Xcdr2SerializerAdapter,
+};
 pub use crate::dds::traits::serde_adapters::{SerializerAdapter, DeserializerAdapter};
+pub use crate::messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier;
+pub use crate::structure::parameter_id::ParameterId;
@@ -0,0 +1,543 @@
+use std::convert::TryFrom;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use serde::{de::DeserializeOwned, Serialize, Serializer};
+
+use crate::{
+  dds::{
+    qos::QosPolicies,
+    traits::serde_adapters::{DeserializerAdapter, SerializerAdapter},
+  },
+  discovery::data_types::{
+    spdp_participant_data::SPDPDiscoveredParticipantData,
+    topic_data::{DiscoveredReaderData, DiscoveredWriterData},
+  },
+  messages::submessages::submessage_elements::{
+    parameter::Parameter, serialized_payload::RepresentationIdentifier,
+  },
+  structure::{guid::GUID, locator::Locator, parameter_id::ParameterId},
+};
+
+use super::{
+  builtin_data_deserializer::BuiltinDataDeserializer,
+  builtin_data_serializer::BuiltinDataSerializer,
+  cdr_deserializer::CDRDeserializerAdapter,
+  cdr_serializer,
+  error::{Error, Result},
+  pl_cdr_deserializer::PlCdrDeserializerAdapter,
+  pl_cdr_serializer::PlCdrSerializerAdapter,
+};
+
+// The parameters of the QoS policies, which set_qos replaces
+const QOS_PARAMETER_IDS: [ParameterId; 13] = [
+  ParameterId::PID_DURABILITY,
+  ParameterId::PID_DEADLINE,
+  ParameterId::PID_LATENCY_BUDGET,
+  ParameterId::PID_LIVELINESS,
+  ParameterId::PID_RELIABILITY,
+  ParameterId::PID_OWNERSHIP,
+  ParameterId::PID_OWNERSHIP_STRENGTH,
+  ParameterId::PID_DESTINATION_ORDER,
+  ParameterId::PID_TIME_BASED_FILTER,
+  ParameterId::PID_PRESENTATION,
+  ParameterId::PID_LIFESPAN,
+  ParameterId::PID_HISTORY,
+  ParameterId::PID_RESOURCE_LIMITS,
+];
+
+/// A parameter list (PL_CDR), the encoding of discovery data in RTPS.
+///
+/// The parameters are kept in the order they were read or set. Parameters this
+/// implementation does not know, such as vendor specific ones, are kept as they are, so
+/// a list read with [`from_bytes`](#method.from_bytes) is written back unchanged by
+/// [`to_bytes`](#method.to_bytes). Known parameters have typed getters and setters, and
+/// the discovery data types convert to and from a `ParameterList` with `TryFrom`.
+///
+/// ```
+/// use rustdds::{
+///   dds::data_types::GUID,
+///   serialization::{ParameterId, ParameterList},
+/// };
+///
+/// let guid = GUID::new();
+/// let mut list = ParameterList::new();
+/// list.set_participant_guid(guid);
+/// list.set_entity_name("participant");
+///
+/// let parsed = ParameterList::from_bytes(&list.to_bytes(), list.encoding()).unwrap();
+/// assert_eq!(parsed.participant_guid(), Some(guid));
+/// assert_eq!(parsed.entity_name(), Some("participant".to_string()));
+/// assert!(parsed.get(ParameterId::PID_PARTICIPANT_GUID).is_some());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterList {
+  // PL_CDR_LE or PL_CDR_BE
+  encoding: RepresentationIdentifier,
+  // without the sentinel
+  parameters: Vec<Parameter>,
+}
+
+impl ParameterList {
+  /// An empty little endian parameter list.
+  pub fn new() -> ParameterList {
+    ParameterList {
+      encoding: RepresentationIdentifier::PL_CDR_LE,
+      parameters: Vec::new(),
+    }
+  }
+
+  /// Reads a parameter list up to its sentinel, or to the end of `bytes` if there is
+  /// none. The encoding tells the byte order: PL_CDR_LE and CDR_LE are little endian,
+  /// PL_CDR_BE and CDR_BE big endian.
+  pub fn from_bytes(bytes: &[u8], encoding: RepresentationIdentifier) -> Result<ParameterList> {
+    let encoding = match encoding {
+      RepresentationIdentifier::PL_CDR_LE | RepresentationIdentifier::CDR_LE => {
+        RepresentationIdentifier::PL_CDR_LE
+      }
+      RepresentationIdentifier::PL_CDR_BE | RepresentationIdentifier::CDR_BE => {
+        RepresentationIdentifier::PL_CDR_BE
+      }
+      repr_id => {
+        return Err(Error::Message(format!(
+          "Unknown representation identifier {}",
+          u16::from(repr_id)
+        )))
+      }
+    };
+    let mut list = ParameterList {
+      encoding,
+      parameters: Vec::new(),
+    };
+
+    let mut rest = bytes;
+    while !rest.is_empty() {
+      if rest.len() < 4 {
+        return Err(Error::Eof);
+      }
+      let parameter_id = ParameterId::from(list.read_u16(&rest[0..2]));
+      let length = list.read_u16(&rest[2..4]) as usize;
+      if parameter_id == ParameterId::PID_SENTINEL {
+        break;
+      }
+      if rest.len() < 4 + length {
+        return Err(Error::Eof);
+      }
+      list.parameters.push(Parameter {
+        parameter_id,
+        value: rest[4..4 + length].to_vec(),
+      });
+      rest = &rest[4 + length..];
+    }
+    Ok(list)
+  }
+
+  /// Writes the parameters in order, followed by the sentinel.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for parameter in &self.parameters {
+      bytes.extend_from_slice(&self.u16_bytes(parameter.parameter_id.into()));
+      bytes.extend_from_slice(&self.u16_bytes(parameter.value.len() as u16));
+      bytes.extend_from_slice(&parameter.value);
+    }
+    bytes.extend_from_slice(&self.u16_bytes(ParameterId::PID_SENTINEL.into()));
+    bytes.extend_from_slice(&[0, 0]);
+    bytes
+  }
+
+  /// PL_CDR_LE or PL_CDR_BE
+  pub fn encoding(&self) -> RepresentationIdentifier {
+    self.encoding
+  }
+
+  /// The value of the first parameter with the id, as it is in the list.
+  pub fn get(&self, parameter_id: ParameterId) -> Option<&[u8]> {
+    self.get_all(parameter_id).next()
+  }
+
+  /// The values of all parameters with the id, for parameters that may appear many
+  /// times, such as locators.
+  pub fn get_all(&self, parameter_id: ParameterId) -> impl Iterator<Item = &[u8]> {
+    self
+      .parameters
+      .iter()
+      .filter(move |p| p.parameter_id == parameter_id)
+      .map(|p| p.value.as_slice())
+  }
+
+  /// Replaces all parameters with the id by one with the value, at the place of the
+  /// first one. The value must already be encoded in the byte order of the list and
+  /// padded to a multiple of 4 bytes.
+  ///
+  /// # Panics
+  /// If the value is longer than 65535 bytes.
+  pub fn set(&mut self, parameter_id: ParameterId, value: Vec<u8>) {
+    let first = self
+      .parameters
+      .iter()
+      .position(|p| p.parameter_id == parameter_id);
+    self.remove(parameter_id);
+    let parameter = ParameterList::parameter(parameter_id, value);
+    match first {
+      Some(index) => self.parameters.insert(index, parameter),
+      None => self.parameters.push(parameter),
+    }
+  }
+
+  /// Adds a parameter to the end of the list, keeping those with the same id.
+  ///
+  /// # Panics
+  /// If the value is longer than 65535 bytes.
+  pub fn add(&mut self, parameter_id: ParameterId, value: Vec<u8>) {
+    let parameter = ParameterList::parameter(parameter_id, value);
+    self.parameters.push(parameter);
+  }
+
+  /// Removes all parameters with the id.
+  pub fn remove(&mut self, parameter_id: ParameterId) {
+    self.parameters.retain(|p| p.parameter_id != parameter_id);
+  }
+
+  /// All parameters in order, without the sentinel.
+  pub fn iter(&self) -> impl Iterator<Item = (ParameterId, &[u8])> {
+    self
+      .parameters
+      .iter()
+      .map(|p| (p.parameter_id, p.value.as_slice()))
+  }
+
+  pub fn participant_guid(&self) -> Option<GUID> {
+    self.decode(ParameterId::PID_PARTICIPANT_GUID)
+  }
+
+  pub fn set_participant_guid(&mut self, guid: GUID) {
+    let value = self.encode(&guid);
+    self.set(ParameterId::PID_PARTICIPANT_GUID, value);
+  }
+
+  /// The GUID of the reader or writer that the list describes
+  pub fn endpoint_guid(&self) -> Option<GUID> {
+    self.decode(ParameterId::PID_ENDPOINT_GUID)
+  }
+
+  pub fn set_endpoint_guid(&mut self, guid: GUID) {
+    let value = self.encode(&guid);
+    self.set(ParameterId::PID_ENDPOINT_GUID, value);
+  }
+
+  pub fn entity_name(&self) -> Option<String> {
+    self.decode(ParameterId::PID_ENTITY_NAME)
+  }
+
+  pub fn set_entity_name(&mut self, name: &str) {
+    let value = self.encode(&name.to_string());
+    self.set(ParameterId::PID_ENTITY_NAME, value);
+  }
+
+  pub fn topic_name(&self) -> Option<String> {
+    self.decode(ParameterId::PID_TOPIC_NAME)
+  }
+
+  pub fn set_topic_name(&mut self, name: &str) {
+    let value = self.encode(&name.to_string());
+    self.set(ParameterId::PID_TOPIC_NAME, value);
+  }
+
+  pub fn type_name(&self) -> Option<String> {
+    self.decode(ParameterId::PID_TYPE_NAME)
+  }
+
+  pub fn set_type_name(&mut self, name: &str) {
+    let value = self.encode(&name.to_string());
+    self.set(ParameterId::PID_TYPE_NAME, value);
+  }
+
+  /// The locators of a locator parameter, such as
+  /// `ParameterId::PID_METATRAFFIC_UNICAST_LOCATOR`. Values that are not locators
+  /// are skipped.
+  pub fn locators(&self, parameter_id: ParameterId) -> Vec<Locator> {
+    self
+      .get_all(parameter_id)
+      .filter_map(|value| CDRDeserializerAdapter::from_bytes(value, self.encoding).ok())
+      .collect()
+  }
+
+  /// Replaces the locators of a locator parameter.
+  pub fn set_locators(&mut self, parameter_id: ParameterId, locators: &[Locator]) {
+    self.remove(parameter_id);
+    for locator in locators {
+      let value = self.encode(locator);
+      self.add(parameter_id, value);
+    }
+  }
+
+  /// The QoS policies in the list. Policies that are missing or cannot be read are
+  /// `None`.
+  pub fn qos(&self) -> QosPolicies {
+    let bytes = self.to_bytes();
+    let deserializer = BuiltinDataDeserializer::new();
+    let deserializer = match self.encoding {
+      RepresentationIdentifier::PL_CDR_BE => deserializer.parse_data_big_endian(&bytes),
+      _ => deserializer.parse_data_little_endian(&bytes),
+    };
+    deserializer.qos()
+  }
+
+  /// Replaces the QoS policy parameters of the list with those of `qos`.
+  pub fn set_qos(&mut self, qos: &QosPolicies) {
+    for parameter_id in QOS_PARAMETER_IDS.iter() {
+      self.remove(*parameter_id);
+    }
+    let qos_parameters = QosParameters(qos);
+    let bytes = match self.encoding {
+      RepresentationIdentifier::PL_CDR_BE => {
+        PlCdrSerializerAdapter::<QosParameters, BigEndian>::to_bytes(&qos_parameters)
+      }
+      _ => PlCdrSerializerAdapter::<QosParameters, LittleEndian>::to_bytes(&qos_parameters),
+    };
+    let qos_list = bytes
+      .and_then(|bytes| ParameterList::from_bytes(&bytes, self.encoding))
+      .expect("Serializing QoS policies failed");
+    self.parameters.extend(qos_list.parameters);
+  }
+
+  fn parameter(parameter_id: ParameterId, value: Vec<u8>) -> Parameter {
+    assert!(
+      value.len() <= u16::MAX as usize,
+      "Parameter value of {} bytes does not fit in a parameter list",
+      value.len()
+    );
+    Parameter {
+      parameter_id,
+      value,
+    }
+  }
+
+  fn decode<T: DeserializeOwned>(&self, parameter_id: ParameterId) -> Option<T> {
+    self
+      .get(parameter_id)
+      .and_then(|value| CDRDeserializerAdapter::from_bytes(value, self.encoding).ok())
+  }
+
+  // CDR in the byte order of the list, padded to a multiple of 4 bytes
+  fn encode<T: Serialize>(&self, value: &T) -> Vec<u8> {
+    let mut bytes = match self.encoding {
+      RepresentationIdentifier::PL_CDR_BE => cdr_serializer::to_bytes::<T, BigEndian>(value),
+      _ => cdr_serializer::to_bytes::<T, LittleEndian>(value),
+    }
+    .expect("Serializing a parameter failed");
+    bytes.resize((bytes.len() + 3) / 4 * 4, 0);
+    bytes
+  }
+
+  fn read_u16(&self, bytes: &[u8]) -> u16 {
+    match self.encoding {
+      RepresentationIdentifier::PL_CDR_BE => BigEndian::read_u16(bytes),
+      _ => LittleEndian::read_u16(bytes),
+    }
+  }
+
+  fn u16_bytes(&self, value: u16) -> [u8; 2] {
+    match self.encoding {
+      RepresentationIdentifier::PL_CDR_BE => value.to_be_bytes(),
+      _ => value.to_le_bytes(),
+    }
+  }
+
+  fn from_data<D: Serialize>(data: &D) -> Result<ParameterList> {
+    let bytes = PlCdrSerializerAdapter::<D, LittleEndian>::to_bytes(data)?;
+    ParameterList::from_bytes(&bytes, RepresentationIdentifier::PL_CDR_LE)
+  }
+
+  fn to_data<D: DeserializeOwned>(&self) -> Result<D> {
+    PlCdrDeserializerAdapter::<D>::from_bytes(&self.to_bytes(), self.encoding)
+  }
+}
+
+impl Default for ParameterList {
+  fn default() -> ParameterList {
+    ParameterList::new()
+  }
+}
+
+// Serializes only the QoS policy parameters, without a sentinel
+struct QosParameters<'a>(&'a QosPolicies);
+
+impl Serialize for QosParameters<'_> {
+  fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    BuiltinDataSerializer::from_qos(self.0).serialize::<S>(serializer, false)
+  }
+}
+
+macro_rules! discovery_data_conversions {
+  ($($data:ty),*) => {
+    $(
+      impl TryFrom<&$data> for ParameterList {
+        type Error = Error;
+
+        fn try_from(data: &$data) -> Result<ParameterList> {
+          ParameterList::from_data(data)
+        }
+      }
+
+      impl TryFrom<&ParameterList> for $data {
+        type Error = Error;
+
+        fn try_from(list: &ParameterList) -> Result<$data> {
+          list.to_data()
+        }
+      }
+    )*
+  };
+}
+
+discovery_data_conversions!(
+  SPDPDiscoveredParticipantData,
+  DiscoveredReaderData,
+  DiscoveredWriterData
+);
+
+#[cfg(test)]
+mod tests {
+  use std::{net::SocketAddr, time::Instant};
+
+  use super::*;
+
+  use crate::{
+    dds::qos::policy::{History, Ownership, Reliability},
+    structure::duration::Duration,
+    test::test_data::{publication_builtin_topic_data, spdp_participant_data, writer_proxy_data},
+  };
+
+  #[test]
+  fn parameter_list_keeps_unknown_parameters() {
+    // a vendor specific parameter, a topic name "abc" and a parameter of no known id
+    let big_endian = [
+      0x80, 0x01, 0x00, 0x04, 0x01, 0x02, 0x03, 0x04, // vendor specific
+      0x00, 0x05, 0x00, 0x08, 0x00, 0x00, 0x00, 0x04, 0x61, 0x62, 0x63, 0x00, // topic name
+      0x0f, 0xff, 0x00, 0x00, // unknown, empty
+      0x00, 0x01, 0x00, 0x00, // sentinel
+    ];
+    let little_endian = [
+      0x01, 0x80, 0x04, 0x00, 0x01, 0x02, 0x03, 0x04, // vendor specific
+      0x05, 0x00, 0x08, 0x00, 0x04, 0x00, 0x00, 0x00, 0x61, 0x62, 0x63, 0x00, // topic name
+      0xff, 0x0f, 0x00, 0x00, // unknown, empty
+      0x01, 0x00, 0x00, 0x00, // sentinel
+    ];
+
+    for (bytes, encoding) in [
+      (&big_endian, RepresentationIdentifier::PL_CDR_BE),
+      (&little_endian, RepresentationIdentifier::PL_CDR_LE),
+    ]
+    .iter()
+    {
+      let list = ParameterList::from_bytes(*bytes, *encoding).unwrap();
+      assert_eq!(list.encoding(), *encoding);
+      assert_eq!(list.iter().count(), 3);
+      assert_eq!(
+        list.get(ParameterId::from(0x8001_u16)),
+        Some(&[1u8, 2, 3, 4][..])
+      );
+      assert_eq!(list.get(ParameterId::from(0x0fff_u16)), Some(&[][..]));
+      assert_eq!(list.topic_name(), Some("abc".to_string()));
+      assert_eq!(&list.to_bytes()[..], &bytes[..]);
+
+      // the sentinel is optional, a parameter must not be cut short
+      let without_sentinel = ParameterList::from_bytes(&bytes[..24], *encoding).unwrap();
+      assert_eq!(without_sentinel, list);
+      assert!(ParameterList::from_bytes(&bytes[..16], *encoding).is_err());
+    }
+    assert!(ParameterList::from_bytes(&little_endian, RepresentationIdentifier::CDR2_LE).is_err());
+  }
+
+  #[test]
+  fn parameter_list_typed_parameters() {
+    let guid = GUID::new();
+    let locators = [
+      Locator::from("10.0.0.1:7410".parse::<SocketAddr>().unwrap()),
+      Locator::from("10.0.0.2:7410".parse::<SocketAddr>().unwrap()),
+    ];
+    let qos = QosPolicies::builder()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: Duration::from_millis(100),
+      })
+      .history(History::KeepLast { depth: 5 })
+      .ownership(Ownership::Exclusive { strength: 3 })
+      .build();
+
+    let mut list = ParameterList::new();
+    list.set_participant_guid(guid);
+    list.set_entity_name("participant");
+    list.add(ParameterId::from(0x8001_u16), vec![1, 2, 3, 4]);
+    list.set_locators(ParameterId::PID_DEFAULT_UNICAST_LOCATOR, &locators);
+    list.set_qos(&qos);
+    // replaces the earlier value in its place
+    list.set_entity_name("renamed");
+    assert_eq!(list.iter().nth(1).unwrap().0, ParameterId::PID_ENTITY_NAME);
+
+    let parsed = ParameterList::from_bytes(&list.to_bytes(), list.encoding()).unwrap();
+    assert_eq!(parsed, list);
+    assert_eq!(parsed.participant_guid(), Some(guid));
+    assert_eq!(parsed.endpoint_guid(), None);
+    assert_eq!(parsed.entity_name(), Some("renamed".to_string()));
+    assert_eq!(
+      parsed.locators(ParameterId::PID_DEFAULT_UNICAST_LOCATOR),
+      locators.to_vec()
+    );
+    assert_eq!(parsed.qos(), qos);
+
+    list.set_qos(&QosPolicies::builder().build());
+    assert_eq!(list.qos(), QosPolicies::builder().build());
+    assert_eq!(
+      list.get(ParameterId::from(0x8001_u16)),
+      Some(&[1u8, 2, 3, 4][..])
+    );
+  }
+
+  #[test]
+  fn parameter_list_converts_discovery_data() {
+    let participant_data = spdp_participant_data().unwrap();
+    let list = ParameterList::try_from(&participant_data).unwrap();
+    assert_eq!(list.participant_guid(), participant_data.participant_guid);
+    assert_eq!(
+      list.locators(ParameterId::PID_METATRAFFIC_UNICAST_LOCATOR),
+      participant_data.metatraffic_unicast_locators
+    );
+    let converted = SPDPDiscoveredParticipantData::try_from(&list).unwrap();
+    assert_eq!(
+      converted.participant_guid,
+      participant_data.participant_guid
+    );
+    assert_eq!(
+      converted.default_unicast_locators,
+      participant_data.default_unicast_locators
+    );
+
+    let writer_proxy = writer_proxy_data().unwrap();
+    let mut publication_topic_data = publication_builtin_topic_data().unwrap();
+    publication_topic_data.key = writer_proxy.remote_writer_guid;
+    let writer_data = DiscoveredWriterData {
+      last_updated: Instant::now(),
+      writer_proxy,
+      publication_topic_data,
+    };
+    let mut list = ParameterList::try_from(&writer_data).unwrap();
+    assert_eq!(
+      list.endpoint_guid(),
+      writer_data.writer_proxy.remote_writer_guid
+    );
+    list.set_topic_name("renamed");
+    let converted = DiscoveredWriterData::try_from(&list).unwrap();
+    assert_eq!(converted.writer_proxy, writer_data.writer_proxy);
+    assert_eq!(
+      converted.publication_topic_data.topic_name,
+      Some("renamed".to_string())
+    );
+    assert_eq!(
+      converted.publication_topic_data.reliability,
+      writer_data.publication_topic_data.reliability
+    );
+  }
+}
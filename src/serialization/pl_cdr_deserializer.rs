@@ -71,6 +71,15 @@ impl<'de> PlCdrDeserializer<'de> {
     T::deserialize(deserializer)
   }
 
+  // Only builtin topic data, which takes the whole parameter list as bytes, can be
+  // deserialized.
+  fn unsupported(what: &str) -> Error {
+    Error::Message(format!(
+      "PL_CDR deserializer cannot deserialize {}, only builtin topic data.",
+      what
+    ))
+  }
+
   fn custom_deserialize_any<V>(self, visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
@@ -80,7 +89,7 @@ impl<'de> PlCdrDeserializer<'de> {
     match self.endianness {
       RepresentationIdentifier::PL_CDR_LE | RepresentationIdentifier::PL_CDR_BE => {
         let rep: Result<Vec<u8>> = to_bytes::<u16, LittleEndian>(&u16::from(self.endianness));
-        visitor.visit_bytes(&[&rep.unwrap(), self.input].concat())
+        visitor.visit_bytes(&[&rep?, self.input].concat())
       }
       e => Err(Error::Message(format!("Unsupported endianness {:?}", e))),
     }
@@ -101,98 +110,98 @@ impl<'de> Deserializer<'de> for PlCdrDeserializer<'de> {
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("bool"))
   }
 
   fn deserialize_i8<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("i8"))
   }
 
   fn deserialize_i16<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("i16"))
   }
 
   fn deserialize_i32<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("i32"))
   }
 
   fn deserialize_i64<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("i64"))
   }
 
   fn deserialize_u8<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("u8"))
   }
 
   fn deserialize_u16<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("u16"))
   }
 
   fn deserialize_u32<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("u32"))
   }
 
   fn deserialize_u64<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("u64"))
   }
 
   fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("f32"))
   }
 
   fn deserialize_f64<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("f64"))
   }
 
   fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("char"))
   }
 
   fn deserialize_str<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("str"))
   }
 
   fn deserialize_string<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("string"))
   }
 
   fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
@@ -206,49 +215,49 @@ impl<'de> Deserializer<'de> for PlCdrDeserializer<'de> {
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("byte_buf"))
   }
 
   fn deserialize_option<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("option"))
   }
 
   fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("unit"))
   }
 
   fn deserialize_unit_struct<V>(self, _name: &'static str, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("unit_struct"))
   }
 
   fn deserialize_newtype_struct<V>(self, _name: &'static str, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("newtype_struct"))
   }
 
   fn deserialize_seq<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("seq"))
   }
 
   fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("tuple"))
   }
 
   fn deserialize_tuple_struct<V>(
@@ -260,14 +269,14 @@ impl<'de> Deserializer<'de> for PlCdrDeserializer<'de> {
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("tuple_struct"))
   }
 
   fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("map"))
   }
 
   fn deserialize_struct<V>(
@@ -291,20 +300,20 @@ impl<'de> Deserializer<'de> for PlCdrDeserializer<'de> {
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("enum"))
   }
 
   fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("identifier"))
   }
 
   fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
   where
     V: serde::de::Visitor<'de>,
   {
-    unimplemented!()
+    Err(PlCdrDeserializer::unsupported("ignored_any"))
   }
 }
@@ -0,0 +1,371 @@
+use std::{io, marker::PhantomData};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
+use serde::{
+  ser::{self, Impossible},
+  Serialize,
+};
+
+use crate::serialization::error::Error;
+use crate::serialization::error::Result;
+use crate::dds::traits::serde_adapters::SerializerAdapter;
+use crate::messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier;
+
+use super::cdr_serializer;
+
+/// Serializes builtin discovery data (`SPDPDiscoveredParticipantData`,
+/// `DiscoveredReaderData`, `DiscoveredWriterData` and `DiscoveredTopicData`) as an
+/// RTPS parameter list (PL_CDR), the counterpart of [`PlCdrDeserializerAdapter`].
+///
+/// Every parameter is written as parameter id, length and value. The value is padded to
+/// a multiple of 4 bytes, and the length tells the padded size. Only parameters present
+/// in the data are written, and the list is terminated with a sentinel.
+///
+/// [`PlCdrDeserializerAdapter`]: struct.PlCdrDeserializerAdapter.html
+pub struct PlCdrSerializerAdapter<D, BO = LittleEndian>
+where
+  BO: ByteOrder,
+{
+  phantom: PhantomData<D>,
+  ghost: PhantomData<BO>,
+}
+
+impl<D> SerializerAdapter<D> for PlCdrSerializerAdapter<D, LittleEndian>
+where
+  D: Serialize,
+{
+  fn output_encoding() -> RepresentationIdentifier {
+    RepresentationIdentifier::PL_CDR_LE
+  }
+
+  fn to_writer<W: io::Write>(writer: W, value: &D) -> Result<()> {
+    to_writer::<D, LittleEndian, W>(writer, value)
+  }
+}
+
+impl<D> SerializerAdapter<D> for PlCdrSerializerAdapter<D, BigEndian>
+where
+  D: Serialize,
+{
+  fn output_encoding() -> RepresentationIdentifier {
+    RepresentationIdentifier::PL_CDR_BE
+  }
+
+  fn to_writer<W: io::Write>(writer: W, value: &D) -> Result<()> {
+    to_writer::<D, BigEndian, W>(writer, value)
+  }
+}
+
+pub fn to_writer<T, BO, W>(writer: W, value: &T) -> Result<()>
+where
+  T: Serialize,
+  BO: ByteOrder,
+  W: io::Write,
+{
+  value.serialize(&mut PlCdrSerializer::<W, BO>::new(writer))
+}
+
+// The builtin data types serialize themselves as a struct where every field is one
+// parameter: parameter id, parameter length and value. The fields are serialized with
+// CDR, but the length is computed again from the actual value, and the value is padded,
+// because the CDR serializer does not know where a parameter ends.
+struct PlCdrSerializer<W, BO> {
+  writer: W,
+  phantom: PhantomData<BO>,
+}
+
+impl<W, BO> PlCdrSerializer<W, BO>
+where
+  W: io::Write,
+  BO: ByteOrder,
+{
+  fn new(writer: W) -> PlCdrSerializer<W, BO> {
+    PlCdrSerializer {
+      writer,
+      phantom: PhantomData,
+    }
+  }
+
+  fn write_parameter(&mut self, cdr_bytes: &[u8]) -> Result<()> {
+    if cdr_bytes.len() < 4 {
+      return Err(Error::Message(format!(
+        "Parameter is too short: {} bytes.",
+        cdr_bytes.len()
+      )));
+    }
+    let parameter_id = BO::read_u16(&cdr_bytes[0..2]);
+    // bytes 2..4 are the length as given by the data type, which is not trusted
+    let value = &cdr_bytes[4..];
+    let padding = (4 - value.len() % 4) % 4;
+    let parameter_length = value.len() + padding;
+    if parameter_length > u16::MAX as usize {
+      return Err(Error::Message(format!(
+        "Parameter {:#06x} is too long: {} bytes.",
+        parameter_id, parameter_length
+      )));
+    }
+
+    self.writer.write_u16::<BO>(parameter_id)?;
+    self.writer.write_u16::<BO>(parameter_length as u16)?;
+    self.writer.write_all(value)?;
+    self.writer.write_all(&[0u8; 3][..padding])?;
+    Ok(())
+  }
+
+  fn not_a_parameter_list() -> Error {
+    Error::Message(String::from(
+      "PL_CDR serializer can only serialize builtin data parameter lists.",
+    ))
+  }
+}
+
+macro_rules! not_a_parameter_list {
+  ($($method:ident($($arg_type:ty),*)),* $(,)?) => {
+    $(
+      fn $method(self, $(_: $arg_type),*) -> Result<()> {
+        Err(PlCdrSerializer::<W, BO>::not_a_parameter_list())
+      }
+    )*
+  };
+}
+
+impl<'a, W, BO> ser::Serializer for &'a mut PlCdrSerializer<W, BO>
+where
+  W: io::Write,
+  BO: ByteOrder,
+{
+  type Ok = ();
+  type Error = Error;
+
+  type SerializeSeq = Impossible<(), Error>;
+  type SerializeTuple = Impossible<(), Error>;
+  type SerializeTupleStruct = Impossible<(), Error>;
+  type SerializeTupleVariant = Impossible<(), Error>;
+  type SerializeMap = Impossible<(), Error>;
+  type SerializeStruct = Self;
+  type SerializeStructVariant = Impossible<(), Error>;
+
+  not_a_parameter_list!(
+    serialize_bool(bool),
+    serialize_i8(i8),
+    serialize_i16(i16),
+    serialize_i32(i32),
+    serialize_i64(i64),
+    serialize_u8(u8),
+    serialize_u16(u16),
+    serialize_u32(u32),
+    serialize_u64(u64),
+    serialize_f32(f32),
+    serialize_f64(f64),
+    serialize_char(char),
+    serialize_str(&str),
+    serialize_bytes(&[u8]),
+    serialize_none(),
+    serialize_unit(),
+    serialize_unit_struct(&'static str),
+    serialize_unit_variant(&'static str, u32, &'static str),
+  );
+
+  fn serialize_some<T>(self, _value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    Err(PlCdrSerializer::<W, BO>::not_a_parameter_list())
+  }
+
+  fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_variant<T>(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    _variant: &'static str,
+    _value: &T,
+  ) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    Err(PlCdrSerializer::<W, BO>::not_a_parameter_list())
+  }
+
+  fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+    Err(PlCdrSerializer::<W, BO>::not_a_parameter_list())
+  }
+
+  fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+    Err(PlCdrSerializer::<W, BO>::not_a_parameter_list())
+  }
+
+  fn serialize_tuple_struct(
+    self,
+    _name: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeTupleStruct> {
+    Err(PlCdrSerializer::<W, BO>::not_a_parameter_list())
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeTupleVariant> {
+    Err(PlCdrSerializer::<W, BO>::not_a_parameter_list())
+  }
+
+  fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+    Err(PlCdrSerializer::<W, BO>::not_a_parameter_list())
+  }
+
+  fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+    Ok(self)
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeStructVariant> {
+    Err(PlCdrSerializer::<W, BO>::not_a_parameter_list())
+  }
+}
+
+impl<'a, W, BO> ser::SerializeStruct for &'a mut PlCdrSerializer<W, BO>
+where
+  W: io::Write,
+  BO: ByteOrder,
+{
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    let cdr_bytes = cdr_serializer::to_bytes::<&T, BO>(&value)?;
+    self.write_parameter(&cdr_bytes)
+  }
+
+  fn end(self) -> Result<()> {
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{
+    discovery::data_types::{
+      spdp_participant_data::SPDPDiscoveredParticipantData,
+      topic_data::{DiscoveredTopicData, TopicBuiltinTopicData},
+    },
+    serialization::pl_cdr_deserializer::PlCdrDeserializerAdapter,
+    dds::traits::serde_adapters::DeserializerAdapter,
+    test::test_data::spdp_participant_data,
+  };
+
+  fn topic_data(name: &str, type_name: &str) -> DiscoveredTopicData {
+    DiscoveredTopicData::new(TopicBuiltinTopicData {
+      key: None,
+      name: Some(String::from(name)),
+      type_name: Some(String::from(type_name)),
+      durability: None,
+      deadline: None,
+      latency_budget: None,
+      liveliness: None,
+      reliability: None,
+      lifespan: None,
+      destination_order: None,
+      presentation: None,
+      history: None,
+      resource_limits: None,
+      ownership: None,
+    })
+  }
+
+  #[test]
+  fn pl_cdr_golden_topic_and_type_name() {
+    // Topic and type name parameters as in a captured SEDP publication from another
+    // vendor (see test_data), followed by a sentinel.
+    let expected: Vec<u8> = vec![
+      0x05, 0x00, 0x0c, 0x00, 0x07, 0x00, 0x00, 0x00, 0x53, 0x71, 0x75, 0x61, 0x72, 0x65, 0x00,
+      0x00, 0x07, 0x00, 0x10, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x53, 0x68, 0x61, 0x70, 0x65, 0x54,
+      0x79, 0x70, 0x65, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+    ];
+    let data = topic_data("Square", "ShapeType");
+    let sdata = PlCdrSerializerAdapter::<DiscoveredTopicData>::to_bytes(&data).unwrap();
+    assert_eq!(sdata, expected);
+    assert_eq!(
+      PlCdrSerializerAdapter::<DiscoveredTopicData>::output_encoding(),
+      RepresentationIdentifier::PL_CDR_LE
+    );
+  }
+
+  #[test]
+  fn pl_cdr_big_endian() {
+    let expected: Vec<u8> = vec![
+      0x00, 0x05, 0x00, 0x08, 0x00, 0x00, 0x00, 0x02, 0x54, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00,
+      0x08, 0x00, 0x00, 0x00, 0x04, 0x61, 0x62, 0x63, 0x00, 0x00, 0x01, 0x00, 0x00,
+    ];
+    let data = topic_data("T", "abc");
+    let sdata = PlCdrSerializerAdapter::<DiscoveredTopicData, BigEndian>::to_bytes(&data).unwrap();
+    assert_eq!(sdata, expected);
+
+    let deserialized: DiscoveredTopicData =
+      PlCdrDeserializerAdapter::from_bytes(&sdata, RepresentationIdentifier::PL_CDR_BE).unwrap();
+    assert_eq!(deserialized.topic_data, data.topic_data);
+  }
+
+  #[test]
+  fn pl_cdr_participant_data_roundtrip() {
+    let mut participant_data = spdp_participant_data().unwrap();
+    // odd lengths need padding
+    participant_data.entity_name = Some(String::from("abcde"));
+    participant_data.domain_tag = Some(String::from("tag"));
+
+    let sdata =
+      PlCdrSerializerAdapter::<SPDPDiscoveredParticipantData>::to_bytes(&participant_data).unwrap();
+    assert_eq!(sdata.len() % 4, 0);
+    assert_eq!(&sdata[sdata.len() - 4..], &[0x01, 0x00, 0x00, 0x00]);
+
+    // every parameter is 4-byte aligned and its length covers the padded value
+    let mut pos = 0;
+    loop {
+      let parameter_id = LittleEndian::read_u16(&sdata[pos..pos + 2]);
+      let parameter_length = LittleEndian::read_u16(&sdata[pos + 2..pos + 4]) as usize;
+      assert_eq!(parameter_length % 4, 0);
+      pos += 4 + parameter_length;
+      if parameter_id == 0x0001 {
+        break;
+      }
+    }
+    assert_eq!(pos, sdata.len());
+
+    let deserialized: SPDPDiscoveredParticipantData =
+      PlCdrDeserializerAdapter::from_bytes(&sdata, RepresentationIdentifier::PL_CDR_LE).unwrap();
+    assert_eq!(
+      deserialized.participant_guid,
+      participant_data.participant_guid
+    );
+    assert_eq!(
+      deserialized.metatraffic_unicast_locators,
+      participant_data.metatraffic_unicast_locators
+    );
+    assert_eq!(
+      deserialized.default_unicast_locators,
+      participant_data.default_unicast_locators
+    );
+    assert_eq!(deserialized.lease_duration, participant_data.lease_duration);
+    assert_eq!(deserialized.entity_name, participant_data.entity_name);
+    assert_eq!(deserialized.domain_tag(), "tag");
+  }
+}
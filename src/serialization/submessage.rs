@@ -29,6 +29,7 @@ impl<C: Context> Writable<C> for SubMessage {
 #[cfg(test)]
 mod tests {
   use enumflags2::BitFlags;
+  use bytes::Bytes;
   use log::info;
   use super::SubMessage;
   use speedy::{Readable, Writable};
@@ -48,8 +49,8 @@ mod tests {
     let header = SubmessageHeader::read_from_buffer(&serializedDataSubMessage[0..4])
       .expect("could not create submessage header");
     let flags = BitFlags::<DATA_Flags>::from_bits_truncate(header.flags);
-    let suba = Data::deserialize_data(&serializedDataSubMessage[4..], flags)
-      .expect("DATA deserialization failed.");
+    let content = Bytes::copy_from_slice(&serializedDataSubMessage[4..]);
+    let suba = Data::deserialize_data(&content, flags).expect("DATA deserialization failed.");
     let sub = SubMessage {
       header,
       body: SubmessageBody::Entity(EntitySubmessage::Data(suba, flags)),
@@ -131,4 +132,45 @@ mod tests {
 
     assert_eq!(serializedInfoTSMessage, messageBuffer);
   }
+
+  #[test]
+  fn submessage_data_with_inline_qos_and_payload() {
+    use speedy::Endianness;
+    use crate::{
+      messages::submessages::submessage_elements::{
+        parameter::Parameter,
+        parameter_list::ParameterList,
+        serialized_payload::{RepresentationIdentifier, SerializedPayload},
+      },
+      structure::{guid::EntityId, parameter_id::ParameterId, sequence_number::SequenceNumber},
+    };
+
+    let data = Data {
+      reader_id: EntityId::ENTITYID_UNKNOWN,
+      writer_id: EntityId::createCustomEntityID([1, 2, 3], 0x02),
+      writer_sn: SequenceNumber::from(7),
+      inline_qos: Some(ParameterList {
+        parameters: vec![Parameter {
+          parameter_id: ParameterId::PID_KEY_HASH,
+          value: (0..16).collect(),
+        }],
+      }),
+      serialized_payload: Some(SerializedPayload::new(
+        RepresentationIdentifier::CDR_LE,
+        vec![0x2a, 0, 0, 0, 3, 0, 0, 0, 0x61, 0x62, 0x63, 0],
+      )),
+    };
+    for endianness in [Endianness::LittleEndian, Endianness::BigEndian].iter() {
+      let mut flags = DATA_Flags::InlineQos | DATA_Flags::Data;
+      if *endianness == Endianness::LittleEndian {
+        flags |= DATA_Flags::Endianness;
+      }
+      let bytes = data
+        .write_to_vec_with_ctx(*endianness)
+        .expect("DATA serialization failed");
+      let deserialized =
+        Data::deserialize_data(&Bytes::from(bytes), flags).expect("DATA deserialization failed");
+      assert_eq!(deserialized, data);
+    }
+  }
 }
@@ -0,0 +1,577 @@
+use std::marker::PhantomData;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use serde::de::{
+  self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, SeqAccess, VariantAccess,
+  Visitor,
+};
+
+use crate::serialization::error::Error;
+use crate::serialization::error::Result;
+use crate::serialization::cdr_deserializer::CDRDeserializerAdapter;
+use crate::dds::traits::serde_adapters::DeserializerAdapter;
+use crate::messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier;
+
+/// Deserializes data in XCDR2 encoding, written e.g. by
+/// [`Xcdr2SerializerAdapter`](struct.Xcdr2SerializerAdapter.html).
+///
+/// The received representation identifier tells the extensibility: `CDR2` is final and
+/// `D_CDR2` is appendable. Plain CDR is accepted as well, so one DataReader can receive
+/// from writers using either encoding. Members appended to an appendable struct by a
+/// newer writer are skipped, and members missing from an older writer can be filled in
+/// with `#[serde(default)]`.
+pub struct Xcdr2DeserializerAdapter<D> {
+  phantom: PhantomData<D>,
+}
+
+const repr_ids: [RepresentationIdentifier; 8] = [
+  RepresentationIdentifier::CDR2_BE,
+  RepresentationIdentifier::CDR2_LE,
+  RepresentationIdentifier::D_CDR2_BE,
+  RepresentationIdentifier::D_CDR2_LE,
+  RepresentationIdentifier::CDR_BE,
+  RepresentationIdentifier::CDR_LE,
+  RepresentationIdentifier::PL_CDR_BE,
+  RepresentationIdentifier::PL_CDR_LE,
+];
+
+impl<D> DeserializerAdapter<D> for Xcdr2DeserializerAdapter<D>
+where
+  D: DeserializeOwned,
+{
+  fn supported_encodings() -> &'static [RepresentationIdentifier] {
+    &repr_ids
+  }
+
+  fn from_bytes<'de>(input_bytes: &'de [u8], encoding: RepresentationIdentifier) -> Result<D> {
+    match encoding {
+      RepresentationIdentifier::CDR2_LE => from_bytes::<D, LittleEndian>(input_bytes, false),
+      RepresentationIdentifier::CDR2_BE => from_bytes::<D, BigEndian>(input_bytes, false),
+      RepresentationIdentifier::D_CDR2_LE => from_bytes::<D, LittleEndian>(input_bytes, true),
+      RepresentationIdentifier::D_CDR2_BE => from_bytes::<D, BigEndian>(input_bytes, true),
+      other => CDRDeserializerAdapter::<D>::from_bytes(input_bytes, other),
+    }
+  }
+
+  fn key_from_bytes<'de, K>(input_bytes: &'de [u8], encoding: RepresentationIdentifier) -> Result<K>
+  where
+    K: DeserializeOwned,
+  {
+    match encoding {
+      RepresentationIdentifier::CDR2_LE => from_bytes::<K, LittleEndian>(input_bytes, false),
+      RepresentationIdentifier::CDR2_BE => from_bytes::<K, BigEndian>(input_bytes, false),
+      RepresentationIdentifier::D_CDR2_LE => from_bytes::<K, LittleEndian>(input_bytes, true),
+      RepresentationIdentifier::D_CDR2_BE => from_bytes::<K, BigEndian>(input_bytes, true),
+      other => CDRDeserializerAdapter::<D>::key_from_bytes(input_bytes, other),
+    }
+  }
+}
+
+pub fn from_bytes<T, BO>(input: &[u8], appendable: bool) -> Result<T>
+where
+  T: DeserializeOwned,
+  BO: ByteOrder,
+{
+  let mut deserializer = Xcdr2Deserializer::<BO>::new(input, appendable);
+  T::deserialize(&mut deserializer)
+}
+
+// Whether a sequence or array has a DHEADER is known only when the first element starts
+// deserializing and tells if it is primitive.
+enum PendingHeader {
+  // The first word of a sequence: element count, or DHEADER followed by the count
+  Sequence(u32),
+  // An array has a DHEADER in front of the first element, or nothing
+  Array,
+  // Element count of a sequence, or None for an array
+  Resolved(Option<u32>),
+  // A sequence of non-primitive elements turned out to be empty
+  Empty,
+}
+
+struct Xcdr2Deserializer<'de, BO> {
+  input: &'de [u8],
+  // read position, for alignment
+  pos: usize,
+  appendable: bool,
+  // Sequences and arrays waiting for their first element, innermost last
+  pending_headers: Vec<PendingHeader>,
+  phantom: PhantomData<BO>,
+}
+
+impl<'de, BO> Xcdr2Deserializer<'de, BO>
+where
+  BO: ByteOrder,
+{
+  fn new(input: &'de [u8], appendable: bool) -> Xcdr2Deserializer<'de, BO> {
+    Xcdr2Deserializer {
+      input,
+      pos: 0,
+      appendable,
+      pending_headers: Vec::new(),
+      phantom: PhantomData,
+    }
+  }
+
+  fn next_bytes(&mut self, count: usize) -> Result<&'de [u8]> {
+    if self.pos + count > self.input.len() {
+      return Err(Error::Eof);
+    }
+    let bytes = &self.input[self.pos..self.pos + count];
+    self.pos += count;
+    Ok(bytes)
+  }
+
+  // XCDR2 aligns to at most 4 bytes
+  fn align(&mut self, alignment: usize) -> Result<()> {
+    let alignment = alignment.min(4);
+    let padding = (alignment - self.pos % alignment) % alignment;
+    self.next_bytes(padding).map(|_| ())
+  }
+
+  fn read_u32(&mut self) -> Result<u32> {
+    self.align(4)?;
+    Ok(BO::read_u32(self.next_bytes(4)?))
+  }
+
+  // Called first thing by each value, so that a pending sequence or array header can be
+  // resolved.
+  fn value_kind(&mut self, primitive: bool) -> Result<()> {
+    let resolved = match self.pending_headers.last() {
+      Some(PendingHeader::Sequence(count)) if primitive => PendingHeader::Resolved(Some(*count)),
+      Some(PendingHeader::Sequence(_)) => match self.read_u32()? {
+        0 => PendingHeader::Empty,
+        count => PendingHeader::Resolved(Some(count)),
+      },
+      Some(PendingHeader::Array) => {
+        if !primitive {
+          let _dheader = self.read_u32()?;
+        }
+        PendingHeader::Resolved(None)
+      }
+      _ => return Ok(()),
+    };
+    let empty = if let PendingHeader::Empty = resolved {
+      true
+    } else {
+      false
+    };
+    *self.pending_headers.last_mut().unwrap() = resolved;
+    if empty {
+      // There is no element to deserialize. SequenceAccess discards this error.
+      Err(Error::Eof)
+    } else {
+      Ok(())
+    }
+  }
+
+  fn deserialize_members<V>(&mut self, count: usize, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.value_kind(false)?;
+    let end = if self.appendable {
+      let size = self.read_u32()? as usize;
+      Some(self.pos + size)
+    } else {
+      None
+    };
+    let value = visitor.visit_seq(StructAccess {
+      de: &mut *self,
+      remaining: count,
+      end,
+    })?;
+    if let Some(end) = end {
+      if self.pos > end || end > self.input.len() {
+        return Err(Error::Message(String::from(
+          "Struct does not match its DHEADER.",
+        )));
+      }
+      // skip members unknown to us
+      self.pos = end;
+    }
+    Ok(value)
+  }
+}
+
+macro_rules! deserialize_multibyte_number {
+  ($method:ident, $visit:ident, $num_type:ty, $read:ident) => {
+    fn $method<V>(self, visitor: V) -> Result<V::Value>
+    where
+      V: Visitor<'de>,
+    {
+      const SIZE: usize = std::mem::size_of::<$num_type>();
+      self.value_kind(true)?;
+      self.align(SIZE)?;
+      visitor.$visit(BO::$read(self.next_bytes(SIZE)?))
+    }
+  };
+}
+
+impl<'de, 'a, BO> de::Deserializer<'de> for &'a mut Xcdr2Deserializer<'de, BO>
+where
+  BO: ByteOrder,
+{
+  type Error = Error;
+
+  /// XCDR2 is not self-describing.
+  fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    Err(Error::Message(String::from(
+      "XCDR2 is not self-describing, type must be known.",
+    )))
+  }
+
+  fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.value_kind(true)?;
+    match self.next_bytes(1)?[0] {
+      0 => visitor.visit_bool(false),
+      1 => visitor.visit_bool(true),
+      x => Err(Error::BadBoolean(x)),
+    }
+  }
+
+  fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.value_kind(true)?;
+    visitor.visit_i8(self.next_bytes(1)?[0] as i8)
+  }
+
+  fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.value_kind(true)?;
+    visitor.visit_u8(self.next_bytes(1)?[0])
+  }
+
+  deserialize_multibyte_number!(deserialize_i16, visit_i16, i16, read_i16);
+  deserialize_multibyte_number!(deserialize_i32, visit_i32, i32, read_i32);
+  deserialize_multibyte_number!(deserialize_i64, visit_i64, i64, read_i64);
+  deserialize_multibyte_number!(deserialize_u16, visit_u16, u16, read_u16);
+  deserialize_multibyte_number!(deserialize_u32, visit_u32, u32, read_u32);
+  deserialize_multibyte_number!(deserialize_u64, visit_u64, u64, read_u64);
+  deserialize_multibyte_number!(deserialize_f32, visit_f32, f32, read_f32);
+  deserialize_multibyte_number!(deserialize_f64, visit_f64, f64, read_f64);
+
+  // IDL char is one octet
+  fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.value_kind(true)?;
+    visitor.visit_char(self.next_bytes(1)?[0] as char)
+  }
+
+  fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.value_kind(false)?;
+    // length includes the terminating null
+    let len = self.read_u32()? as usize;
+    let bytes = self.next_bytes(len)?;
+    match std::str::from_utf8(&bytes[..len.saturating_sub(1)]) {
+      Ok(s) => visitor.visit_borrowed_str(s),
+      Err(utf8_err) => Err(Error::BadString(utf8_err)),
+    }
+  }
+
+  fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_str(visitor)
+  }
+
+  fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.value_kind(false)?;
+    let len = self.read_u32()? as usize;
+    visitor.visit_borrowed_bytes(self.next_bytes(len)?)
+  }
+
+  fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_bytes(visitor)
+  }
+
+  fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.value_kind(false)?;
+    match self.next_bytes(1)?[0] {
+      0 => visitor.visit_none(),
+      1 => visitor.visit_some(self),
+      x => Err(Error::BadOption(x as u32)),
+    }
+  }
+
+  fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    visitor.visit_unit()
+  }
+
+  fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    visitor.visit_unit()
+  }
+
+  fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    visitor.visit_newtype_struct(self)
+  }
+
+  fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.value_kind(false)?;
+    let first_word = self.read_u32()?;
+    // A DHEADER is never zero, as it covers the element count.
+    let header = if first_word == 0 {
+      None
+    } else {
+      Some(PendingHeader::Sequence(first_word))
+    };
+    visitor.visit_seq(SequenceAccess {
+      de: self,
+      header,
+      remaining: 0,
+    })
+  }
+
+  fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.value_kind(false)?;
+    let header = if len == 0 {
+      None
+    } else {
+      Some(PendingHeader::Array)
+    };
+    visitor.visit_seq(SequenceAccess {
+      de: self,
+      header,
+      remaining: len as u32,
+    })
+  }
+
+  fn deserialize_tuple_struct<V>(
+    self,
+    _name: &'static str,
+    len: usize,
+    visitor: V,
+  ) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_members(len, visitor)
+  }
+
+  fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    Err(Error::Message(String::from(
+      "Maps are not supported in XCDR2 deserialization.",
+    )))
+  }
+
+  fn deserialize_struct<V>(
+    self,
+    _name: &'static str,
+    fields: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_members(fields.len(), visitor)
+  }
+
+  fn deserialize_enum<V>(
+    self,
+    _name: &'static str,
+    _variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.value_kind(true)?;
+    visitor.visit_enum(EnumerationHelper { de: self })
+  }
+
+  fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_u32(visitor)
+  }
+
+  fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    self.deserialize_any(visitor)
+  }
+}
+
+// ----------------------------------------------------------
+
+struct EnumerationHelper<'a, 'de: 'a, BO> {
+  de: &'a mut Xcdr2Deserializer<'de, BO>,
+}
+
+impl<'de, 'a, BO> EnumAccess<'de> for EnumerationHelper<'a, 'de, BO>
+where
+  BO: ByteOrder,
+{
+  type Error = Error;
+  type Variant = Self;
+
+  fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+  where
+    V: DeserializeSeed<'de>,
+  {
+    let enum_tag = self.de.read_u32()?;
+    let val: Result<_> = seed.deserialize(enum_tag.into_deserializer());
+    Ok((val?, self))
+  }
+}
+
+impl<'de, 'a, BO> VariantAccess<'de> for EnumerationHelper<'a, 'de, BO>
+where
+  BO: ByteOrder,
+{
+  type Error = Error;
+
+  fn unit_variant(self) -> Result<()> {
+    Ok(())
+  }
+
+  fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+  where
+    T: DeserializeSeed<'de>,
+  {
+    seed.deserialize(self.de)
+  }
+
+  fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    visitor.visit_seq(StructAccess {
+      de: self.de,
+      remaining: len,
+      end: None,
+    })
+  }
+
+  fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+  where
+    V: Visitor<'de>,
+  {
+    visitor.visit_seq(StructAccess {
+      de: self.de,
+      remaining: fields.len(),
+      end: None,
+    })
+  }
+}
+
+// ----------------------------------------------------------
+
+// Members of a struct, which end early if the DHEADER says so
+struct StructAccess<'a, 'de: 'a, BO> {
+  de: &'a mut Xcdr2Deserializer<'de, BO>,
+  remaining: usize,
+  end: Option<usize>,
+}
+
+impl<'a, 'de, BO> SeqAccess<'de> for StructAccess<'a, 'de, BO>
+where
+  BO: ByteOrder,
+{
+  type Error = Error;
+
+  fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+  where
+    T: DeserializeSeed<'de>,
+  {
+    let at_end = self.end.map_or(false, |end| self.de.pos >= end);
+    if self.remaining == 0 || at_end {
+      Ok(None)
+    } else {
+      self.remaining -= 1;
+      seed.deserialize(&mut *self.de).map(Some)
+    }
+  }
+}
+
+// Elements of a sequence or array
+struct SequenceAccess<'a, 'de: 'a, BO> {
+  de: &'a mut Xcdr2Deserializer<'de, BO>,
+  // Until the first element is deserialized
+  header: Option<PendingHeader>,
+  remaining: u32,
+}
+
+impl<'a, 'de, BO> SeqAccess<'de> for SequenceAccess<'a, 'de, BO>
+where
+  BO: ByteOrder,
+{
+  type Error = Error;
+
+  fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+  where
+    T: DeserializeSeed<'de>,
+  {
+    let header = match self.header.take() {
+      None if self.remaining == 0 => return Ok(None),
+      None => {
+        self.remaining -= 1;
+        return seed.deserialize(&mut *self.de).map(Some);
+      }
+      Some(header) => header,
+    };
+
+    self.de.pending_headers.push(header);
+    let result = seed.deserialize(&mut *self.de);
+    let count = match self.de.pending_headers.pop() {
+      Some(PendingHeader::Empty) => return Ok(None),
+      Some(PendingHeader::Resolved(Some(count))) | Some(PendingHeader::Sequence(count)) => count,
+      _ => self.remaining,
+    };
+    let value = result?;
+    self.remaining = count.saturating_sub(1);
+    Ok(Some(value))
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    match self.header {
+      Some(_) => None,
+      None => Some(self.remaining as usize),
+    }
+  }
+}
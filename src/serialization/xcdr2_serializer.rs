@@ -0,0 +1,832 @@
+use std::{io, marker::PhantomData};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use serde::{
+  ser::{self, Impossible},
+  Serialize,
+};
+
+use crate::serialization::error::Error;
+use crate::serialization::error::Result;
+use crate::dds::traits::serde_adapters::SerializerAdapter;
+use crate::messages::submessages::submessage_elements::serialized_payload::RepresentationIdentifier;
+
+/// Extensibility kind (DDS-XTypes) of the data types serialized with
+/// [`Xcdr2SerializerAdapter`](struct.Xcdr2SerializerAdapter.html). It applies to the
+/// top-level type and to all structs nested in it.
+pub trait Xcdr2Extensibility {
+  fn is_appendable() -> bool;
+}
+
+/// Final types are serialized without any headers, like in plain CDR.
+pub struct FinalExtensibility;
+
+impl Xcdr2Extensibility for FinalExtensibility {
+  fn is_appendable() -> bool {
+    false
+  }
+}
+
+/// Appendable types have each struct preceded by a DHEADER telling its size, so that
+/// members can be added to the end of the type.
+pub struct AppendableExtensibility;
+
+impl Xcdr2Extensibility for AppendableExtensibility {
+  fn is_appendable() -> bool {
+    true
+  }
+}
+
+/// Serializes data in XCDR2 encoding (DDS-XTypes 1.3, section 7.4.3), as negotiated by
+/// newer DDS implementations and ROS 2 Iron onwards.
+///
+/// Compared to CDR, 8-byte values are aligned to 4 bytes only, optional values are
+/// preceded by a boolean, and with [`AppendableExtensibility`] structs are preceded by a
+/// DHEADER. Sequences and arrays of non-primitive elements are preceded by a DHEADER as
+/// well. Mutable types (PL_CDR2) are not supported.
+///
+/// Serde does not tell the element type of a sequence, so it is found out from the first
+/// element. Therefore an empty sequence is always written without a DHEADER. Unions
+/// (Rust enums with data) are written without a DHEADER.
+///
+/// [`AppendableExtensibility`]: struct.AppendableExtensibility.html
+pub struct Xcdr2SerializerAdapter<D, BO = LittleEndian, E = AppendableExtensibility>
+where
+  BO: ByteOrder,
+  E: Xcdr2Extensibility,
+{
+  phantom: PhantomData<D>,
+  ghost: PhantomData<(BO, E)>,
+}
+
+impl<D, E> SerializerAdapter<D> for Xcdr2SerializerAdapter<D, LittleEndian, E>
+where
+  D: Serialize,
+  E: Xcdr2Extensibility,
+{
+  fn output_encoding() -> RepresentationIdentifier {
+    if E::is_appendable() {
+      RepresentationIdentifier::D_CDR2_LE
+    } else {
+      RepresentationIdentifier::CDR2_LE
+    }
+  }
+
+  fn to_writer<W: io::Write>(mut writer: W, value: &D) -> Result<()> {
+    writer.write_all(&to_bytes::<D, LittleEndian>(value, E::is_appendable())?)?;
+    Ok(())
+  }
+
+  fn key_to_bytes<K>(value: &K) -> Result<Vec<u8>>
+  where
+    K: Serialize,
+  {
+    to_bytes::<K, LittleEndian>(value, E::is_appendable())
+  }
+}
+
+impl<D, E> SerializerAdapter<D> for Xcdr2SerializerAdapter<D, BigEndian, E>
+where
+  D: Serialize,
+  E: Xcdr2Extensibility,
+{
+  fn output_encoding() -> RepresentationIdentifier {
+    if E::is_appendable() {
+      RepresentationIdentifier::D_CDR2_BE
+    } else {
+      RepresentationIdentifier::CDR2_BE
+    }
+  }
+
+  fn to_writer<W: io::Write>(mut writer: W, value: &D) -> Result<()> {
+    writer.write_all(&to_bytes::<D, BigEndian>(value, E::is_appendable())?)?;
+    Ok(())
+  }
+
+  fn key_to_bytes<K>(value: &K) -> Result<Vec<u8>>
+  where
+    K: Serialize,
+  {
+    to_bytes::<K, BigEndian>(value, E::is_appendable())
+  }
+}
+
+pub fn to_bytes<T, BO>(value: &T, appendable: bool) -> Result<Vec<u8>>
+where
+  T: Serialize + ?Sized,
+  BO: ByteOrder,
+{
+  let mut serializer = Xcdr2Serializer::<BO>::new(appendable);
+  value.serialize(&mut serializer)?;
+  Ok(serializer.output)
+}
+
+// The whole payload is serialized into one buffer, because alignment is counted from the
+// start of the payload, and DHEADERs are filled in after the value they measure.
+struct Xcdr2Serializer<BO> {
+  output: Vec<u8>,
+  appendable: bool,
+  // Sequences and arrays being serialized, innermost last. When their first element is
+  // serialized, it records here whether it is of a primitive type.
+  element_kinds: Vec<Option<bool>>,
+  phantom: PhantomData<BO>,
+}
+
+impl<BO> Xcdr2Serializer<BO>
+where
+  BO: ByteOrder,
+{
+  fn new(appendable: bool) -> Xcdr2Serializer<BO> {
+    Xcdr2Serializer {
+      output: Vec::with_capacity(32),
+      appendable,
+      element_kinds: Vec::new(),
+      phantom: PhantomData,
+    }
+  }
+
+  // XCDR2 aligns to at most 4 bytes
+  fn align(&mut self, alignment: usize) {
+    let alignment = alignment.min(4);
+    while self.output.len() % alignment != 0 {
+      self.output.push(0);
+    }
+  }
+
+  fn value_kind(&mut self, primitive: bool) {
+    if let Some(kind @ None) = self.element_kinds.last_mut() {
+      *kind = Some(primitive);
+    }
+  }
+
+  fn write_u32(&mut self, v: u32) {
+    self.align(4);
+    let mut buf = [0u8; 4];
+    BO::write_u32(&mut buf, v);
+    self.output.extend_from_slice(&buf);
+  }
+
+  fn write_length(&mut self, len: usize) -> Result<()> {
+    if len > u32::MAX as usize {
+      return Err(Error::Message(format!(
+        "Length {} does not fit in u32.",
+        len
+      )));
+    }
+    self.write_u32(len as u32);
+    Ok(())
+  }
+
+  // Reserves a DHEADER and returns its position for end_dheader.
+  fn begin_dheader(&mut self) -> usize {
+    self.align(4);
+    let pos = self.output.len();
+    self.output.extend_from_slice(&[0u8; 4]);
+    pos
+  }
+
+  fn end_dheader(&mut self, pos: usize) -> Result<()> {
+    let size = self.output.len() - (pos + 4);
+    if size > u32::MAX as usize {
+      return Err(Error::Message(format!(
+        "Size {} does not fit in DHEADER.",
+        size
+      )));
+    }
+    BO::write_u32(&mut self.output[pos..pos + 4], size as u32);
+    Ok(())
+  }
+
+  fn compound(&mut self, appendable: bool) -> Compound<BO> {
+    let dheader_pos = if appendable {
+      Some(self.begin_dheader())
+    } else {
+      None
+    };
+    Compound {
+      ser: self,
+      dheader_pos,
+      pending_header: None,
+    }
+  }
+}
+
+// A struct, sequence, array or union being serialized.
+struct Compound<'a, BO> {
+  ser: &'a mut Xcdr2Serializer<BO>,
+  dheader_pos: Option<usize>,
+  // Sequences and arrays get a DHEADER only if their elements are not primitive, which is
+  // known after the first element: (position for the DHEADER, sequence length)
+  pending_header: Option<(usize, Option<u32>)>,
+}
+
+impl<'a, BO> Compound<'a, BO>
+where
+  BO: ByteOrder,
+{
+  fn member<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    let (header_pos, seq_len) = match self.pending_header.take() {
+      None => return value.serialize(&mut *self.ser),
+      Some(h) => h,
+    };
+
+    self.ser.element_kinds.push(None);
+    let result = value.serialize(&mut *self.ser);
+    let primitive = self.ser.element_kinds.pop().flatten().unwrap_or(true);
+    result?;
+    if !primitive {
+      // Start over with a DHEADER in front. Inserting it would break the alignment.
+      self.ser.output.truncate(header_pos);
+      self.dheader_pos = Some(self.ser.begin_dheader());
+      if let Some(len) = seq_len {
+        self.ser.write_u32(len);
+      }
+      value.serialize(&mut *self.ser)?;
+    }
+    Ok(())
+  }
+
+  fn finish(self) -> Result<()> {
+    match self.dheader_pos {
+      Some(pos) => self.ser.end_dheader(pos),
+      None => Ok(()),
+    }
+  }
+}
+
+macro_rules! serialize_multibyte_number {
+  ($method:ident, $num_type:ty, $write:ident) => {
+    fn $method(self, v: $num_type) -> Result<()> {
+      const SIZE: usize = std::mem::size_of::<$num_type>();
+      self.value_kind(true);
+      self.align(SIZE);
+      let mut buf = [0u8; SIZE];
+      BO::$write(&mut buf, v);
+      self.output.extend_from_slice(&buf);
+      Ok(())
+    }
+  };
+}
+
+impl<'a, BO> ser::Serializer for &'a mut Xcdr2Serializer<BO>
+where
+  BO: ByteOrder,
+{
+  type Ok = ();
+  type Error = Error;
+
+  type SerializeSeq = Compound<'a, BO>;
+  type SerializeTuple = Compound<'a, BO>;
+  type SerializeTupleStruct = Compound<'a, BO>;
+  type SerializeTupleVariant = Compound<'a, BO>;
+  type SerializeMap = Impossible<(), Error>;
+  type SerializeStruct = Compound<'a, BO>;
+  type SerializeStructVariant = Compound<'a, BO>;
+
+  fn serialize_bool(self, v: bool) -> Result<()> {
+    self.value_kind(true);
+    self.output.push(v as u8);
+    Ok(())
+  }
+
+  fn serialize_i8(self, v: i8) -> Result<()> {
+    self.value_kind(true);
+    self.output.push(v as u8);
+    Ok(())
+  }
+
+  fn serialize_u8(self, v: u8) -> Result<()> {
+    self.value_kind(true);
+    self.output.push(v);
+    Ok(())
+  }
+
+  serialize_multibyte_number!(serialize_i16, i16, write_i16);
+  serialize_multibyte_number!(serialize_i32, i32, write_i32);
+  serialize_multibyte_number!(serialize_i64, i64, write_i64);
+  serialize_multibyte_number!(serialize_u16, u16, write_u16);
+  serialize_multibyte_number!(serialize_u32, u32, write_u32);
+  serialize_multibyte_number!(serialize_u64, u64, write_u64);
+  serialize_multibyte_number!(serialize_f32, f32, write_f32);
+  serialize_multibyte_number!(serialize_f64, f64, write_f64);
+
+  // IDL char is one octet
+  fn serialize_char(self, v: char) -> Result<()> {
+    if v as u32 > 0xFF {
+      return Err(Error::BadChar(v as u32));
+    }
+    self.value_kind(true);
+    self.output.push(v as u8);
+    Ok(())
+  }
+
+  fn serialize_str(self, v: &str) -> Result<()> {
+    self.value_kind(false);
+    // length includes the terminating null
+    self.write_length(v.len() + 1)?;
+    self.output.extend_from_slice(v.as_bytes());
+    self.output.push(0);
+    Ok(())
+  }
+
+  fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+    self.value_kind(false);
+    self.write_length(v.len())?;
+    self.output.extend_from_slice(v);
+    Ok(())
+  }
+
+  // Optional members are preceded by a boolean telling if they are present
+  fn serialize_none(self) -> Result<()> {
+    self.value_kind(false);
+    self.output.push(0);
+    Ok(())
+  }
+
+  fn serialize_some<T>(self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.value_kind(false);
+    self.output.push(1);
+    value.serialize(self)
+  }
+
+  fn serialize_unit(self) -> Result<()> {
+    Ok(())
+  }
+
+  fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+    Ok(())
+  }
+
+  fn serialize_unit_variant(
+    self,
+    _name: &'static str,
+    variant_index: u32,
+    _variant: &'static str,
+  ) -> Result<()> {
+    self.value_kind(true);
+    self.write_u32(variant_index);
+    Ok(())
+  }
+
+  fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_variant<T>(
+    self,
+    _name: &'static str,
+    variant_index: u32,
+    _variant: &'static str,
+    value: &T,
+  ) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.value_kind(true);
+    self.write_u32(variant_index);
+    value.serialize(self)
+  }
+
+  fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+    let len = len.ok_or(Error::SequenceLengthUnknown)?;
+    self.value_kind(false);
+    self.align(4);
+    let header_pos = self.output.len();
+    self.write_length(len)?;
+    let mut compound = self.compound(false);
+    compound.pending_header = Some((header_pos, Some(len as u32)));
+    Ok(compound)
+  }
+
+  // Fixed length arrays have no length
+  fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+    self.value_kind(false);
+    let header_pos = self.output.len();
+    let mut compound = self.compound(false);
+    compound.pending_header = Some((header_pos, None));
+    Ok(compound)
+  }
+
+  fn serialize_tuple_struct(
+    self,
+    _name: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeTupleStruct> {
+    self.value_kind(false);
+    let appendable = self.appendable;
+    Ok(self.compound(appendable))
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    variant_index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeTupleVariant> {
+    self.value_kind(true);
+    self.write_u32(variant_index);
+    Ok(self.compound(false))
+  }
+
+  fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+    Err(Error::Message(String::from(
+      "Maps are not supported in XCDR2 serialization.",
+    )))
+  }
+
+  fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+    self.value_kind(false);
+    let appendable = self.appendable;
+    Ok(self.compound(appendable))
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    variant_index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeStructVariant> {
+    self.value_kind(true);
+    self.write_u32(variant_index);
+    Ok(self.compound(false))
+  }
+}
+
+impl<'a, BO> ser::SerializeSeq for Compound<'a, BO>
+where
+  BO: ByteOrder,
+{
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.member(value)
+  }
+
+  fn end(self) -> Result<()> {
+    self.finish()
+  }
+}
+
+impl<'a, BO> ser::SerializeTuple for Compound<'a, BO>
+where
+  BO: ByteOrder,
+{
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.member(value)
+  }
+
+  fn end(self) -> Result<()> {
+    self.finish()
+  }
+}
+
+impl<'a, BO> ser::SerializeTupleStruct for Compound<'a, BO>
+where
+  BO: ByteOrder,
+{
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.member(value)
+  }
+
+  fn end(self) -> Result<()> {
+    self.finish()
+  }
+}
+
+impl<'a, BO> ser::SerializeTupleVariant for Compound<'a, BO>
+where
+  BO: ByteOrder,
+{
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.member(value)
+  }
+
+  fn end(self) -> Result<()> {
+    self.finish()
+  }
+}
+
+impl<'a, BO> ser::SerializeStruct for Compound<'a, BO>
+where
+  BO: ByteOrder,
+{
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.member(value)
+  }
+
+  fn end(self) -> Result<()> {
+    self.finish()
+  }
+}
+
+impl<'a, BO> ser::SerializeStructVariant for Compound<'a, BO>
+where
+  BO: ByteOrder,
+{
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+  where
+    T: ?Sized + Serialize,
+  {
+    self.member(value)
+  }
+
+  fn end(self) -> Result<()> {
+    self.finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde::Deserialize;
+
+  use crate::{
+    serialization::{
+      cdr_deserializer::CDRDeserializerAdapter, cdr_serializer::CDRSerializerAdapter,
+      xcdr2_deserializer::Xcdr2DeserializerAdapter,
+    },
+    dds::traits::serde_adapters::DeserializerAdapter,
+  };
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+  struct StringMessage {
+    data: String,
+  }
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+  struct Point {
+    x: i32,
+    y: i32,
+  }
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+  struct Point3 {
+    x: i32,
+    y: i32,
+    #[serde(default)]
+    z: i32,
+  }
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+  struct Polygon {
+    name: String,
+    points: Vec<Point>,
+    ids: Vec<u16>,
+  }
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+  enum Color {
+    Red,
+    Green,
+  }
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+  struct Everything {
+    flag: bool,
+    small: u8,
+    stamp: f64,
+    maybe: Option<u16>,
+    nothing: Option<String>,
+    color: Color,
+    bytes: [u8; 3],
+    corners: [Point; 2],
+    names: Vec<String>,
+    nested: Vec<Vec<u32>>,
+  }
+
+  fn everything() -> Everything {
+    Everything {
+      flag: true,
+      small: 7,
+      stamp: 1.5,
+      maybe: Some(0x1234),
+      nothing: None,
+      color: Color::Green,
+      bytes: [1, 2, 3],
+      corners: [Point { x: -1, y: 1 }, Point { x: 2, y: -2 }],
+      names: vec![String::from("a"), String::from("bcd")],
+      nested: vec![vec![1, 2], vec![], vec![3]],
+    }
+  }
+
+  fn roundtrip<SA, T>(value: &T) -> T
+  where
+    SA: SerializerAdapter<T>,
+    T: Serialize + serde::de::DeserializeOwned,
+  {
+    let bytes = SA::to_bytes(value).unwrap();
+    Xcdr2DeserializerAdapter::<T>::from_bytes(&bytes, SA::output_encoding()).unwrap()
+  }
+
+  #[test]
+  fn xcdr2_appendable_string_message() {
+    // DHEADER, string length including null, string
+    let expected: Vec<u8> = vec![
+      0x0a, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x00,
+    ];
+    let message = StringMessage {
+      data: String::from("hello"),
+    };
+    type SA = Xcdr2SerializerAdapter<StringMessage>;
+    assert_eq!(SA::output_encoding(), RepresentationIdentifier::D_CDR2_LE);
+    assert_eq!(SA::to_bytes(&message).unwrap(), expected);
+    assert_eq!(roundtrip::<SA, _>(&message), message);
+  }
+
+  #[test]
+  fn xcdr2_final_aligns_8_byte_values_to_4() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Stamped {
+      a: u8,
+      b: u64,
+    }
+    let value = Stamped {
+      a: 1,
+      b: 0x0102_0304_0506_0708,
+    };
+    type SA = Xcdr2SerializerAdapter<Stamped, LittleEndian, FinalExtensibility>;
+    assert_eq!(SA::output_encoding(), RepresentationIdentifier::CDR2_LE);
+    assert_eq!(
+      SA::to_bytes(&value).unwrap(),
+      vec![0x01, 0x00, 0x00, 0x00, 0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]
+    );
+    assert_eq!(roundtrip::<SA, _>(&value), value);
+  }
+
+  #[test]
+  fn xcdr2_sequence_of_structs_has_dheader() {
+    let polygon = Polygon {
+      name: String::from("a"),
+      points: vec![Point { x: 1, y: 2 }],
+      ids: vec![7],
+    };
+    let expected: Vec<u8> = vec![
+      0x22, 0x00, 0x00, 0x00, // DHEADER of Polygon
+      0x02, 0x00, 0x00, 0x00, 0x61, 0x00, 0x00, 0x00, // name and padding
+      0x10, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // DHEADER and length of points
+      0x08, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, // Point
+      0x01, 0x00, 0x00, 0x00, 0x07, 0x00, // ids, no DHEADER
+    ];
+    type SA = Xcdr2SerializerAdapter<Polygon>;
+    assert_eq!(SA::to_bytes(&polygon).unwrap(), expected);
+    assert_eq!(roundtrip::<SA, _>(&polygon), polygon);
+  }
+
+  #[test]
+  fn xcdr2_empty_sequence_of_structs() {
+    // Other implementations know the element type, so an empty sequence of structs
+    // still has a DHEADER.
+    let bytes: Vec<u8> = vec![
+      0x14, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x61, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+      0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let polygon: Polygon =
+      Xcdr2DeserializerAdapter::from_bytes(&bytes, RepresentationIdentifier::D_CDR2_LE).unwrap();
+    assert_eq!(
+      polygon,
+      Polygon {
+        name: String::from("a"),
+        points: vec![],
+        ids: vec![],
+      }
+    );
+  }
+
+  #[test]
+  fn xcdr2_appendable_type_evolution() {
+    let old = Polygon {
+      name: String::from("old"),
+      points: vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }],
+      ids: vec![1, 2, 3],
+    };
+    let bytes = Xcdr2SerializerAdapter::<Polygon>::to_bytes(&old).unwrap();
+
+    // A newer reader fills in the missing member with its default
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct PolygonV2 {
+      name: String,
+      points: Vec<Point3>,
+      ids: Vec<u16>,
+      #[serde(default)]
+      area: f64,
+    }
+    let newer: PolygonV2 =
+      Xcdr2DeserializerAdapter::from_bytes(&bytes, RepresentationIdentifier::D_CDR2_LE).unwrap();
+    assert_eq!(newer.points[1], Point3 { x: 3, y: 4, z: 0 });
+    assert_eq!(newer.ids, vec![1, 2, 3]);
+    assert_eq!(newer.area, 0.0);
+
+    // An older reader skips members it does not know
+    let point3 = Point3 { x: 5, y: 6, z: 7 };
+    let bytes = Xcdr2SerializerAdapter::<Point3>::to_bytes(&point3).unwrap();
+    let point: Point =
+      Xcdr2DeserializerAdapter::from_bytes(&bytes, RepresentationIdentifier::D_CDR2_LE).unwrap();
+    assert_eq!(point, Point { x: 5, y: 6 });
+  }
+
+  #[test]
+  fn xcdr2_roundtrip_all_variants() {
+    let value = everything();
+    assert_eq!(
+      roundtrip::<Xcdr2SerializerAdapter<Everything, LittleEndian, AppendableExtensibility>, _>(
+        &value
+      ),
+      value
+    );
+    assert_eq!(
+      roundtrip::<Xcdr2SerializerAdapter<Everything, BigEndian, AppendableExtensibility>, _>(
+        &value
+      ),
+      value
+    );
+    assert_eq!(
+      roundtrip::<Xcdr2SerializerAdapter<Everything, LittleEndian, FinalExtensibility>, _>(&value),
+      value
+    );
+    assert_eq!(
+      roundtrip::<Xcdr2SerializerAdapter<Everything, BigEndian, FinalExtensibility>, _>(&value),
+      value
+    );
+  }
+
+  #[test]
+  fn xcdr2_dispatch_on_representation_identifier() {
+    let value = everything();
+
+    // CDR reader accepts XCDR2
+    type XA = Xcdr2SerializerAdapter<Everything, BigEndian>;
+    let bytes = XA::to_bytes(&value).unwrap();
+    assert_eq!(
+      CDRDeserializerAdapter::<Everything>::from_bytes(&bytes, XA::output_encoding()).unwrap(),
+      value
+    );
+
+    // XCDR2 reader accepts CDR
+    type CA = CDRSerializerAdapter<Everything, LittleEndian>;
+    let polygon = Polygon {
+      name: String::from("cdr"),
+      points: vec![Point { x: 1, y: 2 }],
+      ids: vec![],
+    };
+    let bytes = CDRSerializerAdapter::<Polygon>::to_bytes(&polygon).unwrap();
+    assert_eq!(
+      Xcdr2DeserializerAdapter::<Polygon>::from_bytes(&bytes, RepresentationIdentifier::CDR_LE)
+        .unwrap(),
+      polygon
+    );
+    assert!(
+      Xcdr2DeserializerAdapter::<Everything>::supported_encodings()
+        .contains(&CA::output_encoding())
+    );
+
+    // Mutable types are not supported
+    assert!(Xcdr2DeserializerAdapter::<Polygon>::from_bytes(
+      &bytes,
+      RepresentationIdentifier::PL_CDR2_LE
+    )
+    .is_err());
+  }
+}
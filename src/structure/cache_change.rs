@@ -1,5 +1,7 @@
 use crate::structure::guid::GUID;
 use crate::structure::sequence_number::SequenceNumber;
+use crate::structure::time::Timestamp;
+use crate::structure::inline_qos::SampleIdentity;
 use crate::messages::submessages::submessage_elements::serialized_payload::SerializedPayload;
 use crate::dds::ddsdata::DDSData;
 
@@ -17,6 +19,13 @@ pub struct CacheChange {
   pub sequence_number: SequenceNumber,
   pub data_value: Option<SerializedPayload>,
   pub key: u128,
+  // Sent in InfoTimestamp submessage. Received changes have None, if there was no InfoTimestamp.
+  pub source_timestamp: Option<Timestamp>,
+  // The Reader that received this change. None on changes of our own writers, which
+  // share the TopicCache with received ones.
+  pub received_by: Option<GUID>,
+  // Sent as inline QoS to relate this change to another sample, e.g. a reply to its request.
+  pub related_sample_identity: Option<SampleIdentity>,
   //pub inline_qos: ParameterList,
 
   //stps_chage_for_reader : RTPSChangeForReader
@@ -46,9 +55,14 @@ impl CacheChange {
     sequence_number: SequenceNumber,
     data_value: Option<DDSData>,
   ) -> CacheChange {
-    let (key, data_value) = match data_value {
-      Some(d) => (d.value_key_hash, d.value()),
-      None => (0, None),
+    let (key, data_value, source_timestamp, related_sample_identity) = match data_value {
+      Some(d) => (
+        d.value_key_hash,
+        d.value(),
+        Some(d.source_timestamp()),
+        d.related_sample_identity,
+      ),
+      None => (0, None, None, None),
     };
 
     CacheChange {
@@ -57,6 +71,9 @@ impl CacheChange {
       sequence_number,
       data_value,
       key,
+      source_timestamp,
+      received_by: None,
+      related_sample_identity,
       //inline_qos: ParameterList::new(),
       //rtps_chage_for_reader : RTPSChangeForReader::new(),
     }
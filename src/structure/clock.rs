@@ -0,0 +1,180 @@
+use std::{
+  fmt,
+  ops::Deref,
+  sync::{Arc, Mutex},
+  time::{Duration as StdDuration, Instant, SystemTime},
+};
+
+use super::time::Timestamp;
+
+/// Source of the current time for a DomainParticipant and everything it contains:
+/// lease expiry, deadlines, lifespan, history garbage collection and the timestamps of
+/// written samples.
+///
+/// Blocking calls, such as waiting for acknowledgments, and socket level timers
+/// always use real time, because they actually sleep.
+pub trait Clock: Send + Sync {
+  /// Current wall clock time.
+  fn system_time(&self) -> SystemTime;
+
+  /// Current monotonic time, for measuring intervals.
+  fn instant(&self) -> Instant;
+
+  /// Current wall clock time as an RTPS Timestamp.
+  fn now(&self) -> Timestamp {
+    Timestamp::from(self.system_time())
+  }
+}
+
+/// The real time of the host. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn system_time(&self) -> SystemTime {
+    SystemTime::now()
+  }
+
+  fn instant(&self) -> Instant {
+    Instant::now()
+  }
+}
+
+/// A clock that stands still until advanced, for testing time dependent behavior
+/// without sleeping. It starts at the real time of its creation.
+///
+/// # Examples
+/// ```
+/// # use std::{sync::Arc, time::Duration};
+/// # use rustdds::dds::{Clock, ManualClock, SharedClock};
+/// let clock = Arc::new(ManualClock::new());
+/// let shared = SharedClock::from(clock.clone());
+/// let start = shared.instant();
+/// clock.advance(Duration::from_secs(10));
+/// assert_eq!(shared.instant() - start, Duration::from_secs(10));
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+  start_system_time: SystemTime,
+  start_instant: Instant,
+  elapsed: Mutex<StdDuration>,
+}
+
+impl ManualClock {
+  pub fn new() -> ManualClock {
+    ManualClock {
+      start_system_time: SystemTime::now(),
+      start_instant: Instant::now(),
+      elapsed: Mutex::new(StdDuration::from_secs(0)),
+    }
+  }
+
+  /// Moves the clock forward.
+  pub fn advance(&self, duration: StdDuration) {
+    *self.elapsed.lock().unwrap() += duration;
+  }
+
+  fn elapsed(&self) -> StdDuration {
+    *self.elapsed.lock().unwrap()
+  }
+}
+
+impl Default for ManualClock {
+  fn default() -> ManualClock {
+    ManualClock::new()
+  }
+}
+
+impl Clock for ManualClock {
+  fn system_time(&self) -> SystemTime {
+    self.start_system_time + self.elapsed()
+  }
+
+  fn instant(&self) -> Instant {
+    self.start_instant + self.elapsed()
+  }
+}
+
+/// A [Clock](trait.Clock.html) shared by the parts of a DomainParticipant. The default
+/// is the [SystemClock](struct.SystemClock.html). Two SharedClocks are equal if they
+/// are the same clock.
+#[derive(Clone, Default)]
+pub struct SharedClock {
+  // None is the SystemClock
+  clock: Option<Arc<dyn Clock>>,
+}
+
+static SYSTEM_CLOCK: SystemClock = SystemClock;
+
+impl SharedClock {
+  pub fn new<C: Clock + 'static>(clock: C) -> SharedClock {
+    SharedClock::from(Arc::new(clock))
+  }
+}
+
+impl<C: Clock + 'static> From<Arc<C>> for SharedClock {
+  fn from(clock: Arc<C>) -> SharedClock {
+    SharedClock { clock: Some(clock) }
+  }
+}
+
+impl Deref for SharedClock {
+  type Target = dyn Clock;
+
+  fn deref(&self) -> &Self::Target {
+    match &self.clock {
+      Some(clock) => &**clock,
+      None => &SYSTEM_CLOCK,
+    }
+  }
+}
+
+impl PartialEq for SharedClock {
+  fn eq(&self, other: &SharedClock) -> bool {
+    match (&self.clock, &other.clock) {
+      (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+      (None, None) => true,
+      _ => false,
+    }
+  }
+}
+
+impl Eq for SharedClock {}
+
+impl fmt::Debug for SharedClock {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.clock {
+      None => f.write_str("SharedClock(SystemClock)"),
+      Some(_) => f.write_str("SharedClock(custom)"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn manual_clock_advances() {
+    let clock = Arc::new(ManualClock::new());
+    let shared = SharedClock::from(clock.clone());
+    let instant = shared.instant();
+    let system_time = shared.system_time();
+    let timestamp = shared.now();
+    assert_eq!(shared.instant(), instant);
+
+    clock.advance(StdDuration::from_millis(1500));
+    assert_eq!(shared.instant() - instant, StdDuration::from_millis(1500));
+    assert_eq!(
+      shared.system_time().duration_since(system_time).unwrap(),
+      StdDuration::from_millis(1500)
+    );
+    let elapsed = shared.now().duration_since(timestamp).to_std();
+    assert!(elapsed > StdDuration::from_millis(1499) && elapsed < StdDuration::from_millis(1501));
+
+    assert_eq!(shared, shared.clone());
+    assert_ne!(shared, SharedClock::new(ManualClock::new()));
+    assert_ne!(shared, SharedClock::default());
+    assert_eq!(SharedClock::default(), SharedClock::default());
+  }
+}
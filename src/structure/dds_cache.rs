@@ -1,47 +1,211 @@
+use log::warn;
 use std::{
-  collections::{BTreeMap, HashMap, btree_map::Range},
+  collections::{BTreeMap, BTreeSet, HashMap, HashSet, btree_map::Range},
+  fmt,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+  },
 };
 use crate::dds::{
   typedesc::TypeDesc,
-  qos::{QosPolicies, QosPolicyBuilder},
+  qos::{QosPolicies, policy::History},
 };
-use crate::structure::time::Timestamp;
+use crate::structure::{clock::SharedClock, duration::Duration, time::Timestamp};
+use crate::common::lock::{read_lock, write_lock, PoisonRecovery};
 
 use super::{
   topic_kind::TopicKind,
   cache_change::{ChangeKind, CacheChange},
+  guid::GUID,
+  sequence_number::SequenceNumber,
 };
 use std::ops::Bound::{Included, Excluded};
 
+/// Key of a CacheChange in the DDSCache, and the handle Readers, Writers and
+/// DataReaders keep of it. Instants are ordered by the Timestamp they were taken at.
+/// Each one is unique, even if the clock did not advance between two of them.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct CacheInstant {
+  timestamp: Timestamp,
+  // Breaks ties between instants of the same Timestamp. Only instants made from a
+  // Timestamp, which serve as range bounds, have zero.
+  serial: u64,
+}
+
+static NEXT_CACHE_INSTANT_SERIAL: AtomicU64 = AtomicU64::new(1);
+
+impl CacheInstant {
+  pub fn now() -> CacheInstant {
+    CacheInstant::at(Timestamp::now())
+  }
+
+  /// A new instant at the given time, e.g. the now of a Clock.
+  pub fn at(timestamp: Timestamp) -> CacheInstant {
+    CacheInstant {
+      timestamp,
+      serial: NEXT_CACHE_INSTANT_SERIAL.fetch_add(1, Ordering::Relaxed),
+    }
+  }
+
+  pub fn timestamp(&self) -> Timestamp {
+    self.timestamp
+  }
+}
+
+// Before every instant taken at the Timestamp or later.
+impl From<Timestamp> for CacheInstant {
+  fn from(timestamp: Timestamp) -> CacheInstant {
+    CacheInstant {
+      timestamp,
+      serial: 0,
+    }
+  }
+}
+
+/// Why a change could not be added to, modified in or removed from the DDSCache.
+/// These are not fatal: a topic may be removed while messages of it are still being
+/// processed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheError {
+  /// The topic is not (or no longer) in the DDSCache.
+  UnknownTopic(String),
+  /// The topic has no CacheChange at the instant.
+  UnknownChange(CacheInstant),
+}
+
+impl fmt::Display for CacheError {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      CacheError::UnknownTopic(topic_name) => {
+        formatter.write_fmt(format_args!("Topic {:?} is not in DDSCache", topic_name))
+      }
+      CacheError::UnknownChange(instant) => {
+        formatter.write_fmt(format_args!("No CacheChange at {:?}", instant))
+      }
+    }
+  }
+}
+
+impl std::error::Error for CacheError {}
+
+/// How many changes of a topic the DDSCache of a
+/// [DomainParticipant](struct.DomainParticipant.html) holds, and how much their
+/// payloads take. Changes stay in the cache also after DataReaders have taken them,
+/// until they are garbage collected.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TopicCacheStatistics {
+  /// CacheChanges, including dispose and unregister changes
+  pub changes: usize,
+  /// Serialized payload bytes of the changes. Changes handed over within the participant
+  /// share their payload, but it is counted for each.
+  pub payload_bytes: usize,
+  /// Instances of a keyed topic that have changes in the cache
+  pub instances: usize,
+  /// When the oldest change was added to the cache
+  pub oldest: Option<Timestamp>,
+  /// When the newest change was added to the cache
+  pub newest: Option<Timestamp>,
+}
+
+impl TopicCacheStatistics {
+  fn add(&mut self, other: &TopicCacheStatistics) {
+    self.changes += other.changes;
+    self.payload_bytes += other.payload_bytes;
+    self.instances += other.instances;
+    self.oldest = match (self.oldest, other.oldest) {
+      (Some(a), Some(b)) => Some(a.min(b)),
+      (a, b) => a.or(b),
+    };
+    self.newest = self.newest.max(other.newest);
+  }
+}
+
+/// Statistics of the DDSCache of a [DomainParticipant](struct.DomainParticipant.html),
+/// by topic and in total. This is a copy, it does not change when the cache does.
+///
+/// # Examples
+/// ```
+/// # use rustdds::dds::DomainParticipant;
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// let statistics = domain_participant.cache_statistics();
+/// println!(
+///   "{} changes, {} payload bytes",
+///   statistics.total.changes, statistics.total.payload_bytes
+/// );
+/// for (topic_name, topic) in statistics.topics.iter() {
+///   println!("{}: {} instances", topic_name, topic.instances);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CacheStatistics {
+  /// Statistics of each topic in the cache, by topic name
+  pub topics: HashMap<String, TopicCacheStatistics>,
+  /// Sums of the topic statistics, with the oldest and newest change of all topics
+  pub total: TopicCacheStatistics,
+}
+
 ///DDSCache contains all cacheCahanges that are produced by participant or recieved by participant.
 ///Each topic that is been published or been subscribed are contained in separate TopicCaches.
 ///One TopicCache cotains only DDSCacheChanges of one serialized IDL datatype.
 ///-> all cachechanges in same TopicCache can be serialized/deserialized same way.
 ///Topic/TopicCache is identified by its name, which must be unique in the whole Domain.
+///
+///Each TopicCache is behind its own lock, so that readers and writers of different topics
+///do not block each other. Changing the DDSCache itself, i.e. the lock around it, is only
+///needed for adding and removing topics.
 #[derive(Debug)]
 pub struct DDSCache {
-  topic_caches: HashMap<String, TopicCache>,
+  topic_caches: HashMap<String, Arc<RwLock<TopicCache>>>,
+  // How many Topic handles of the application there are of each topic. Clones of a
+  // Topic count once.
+  topic_handles: HashMap<String, usize>,
+  // the clock of the participant, also used by its Readers, Writers and Discovery
+  clock: SharedClock,
+  // times a thread panicked holding the lock of this or of a TopicCache
+  poison_recoveries: Arc<AtomicU64>,
 }
 
 impl DDSCache {
   pub fn new() -> DDSCache {
+    DDSCache::with_clock(SharedClock::default())
+  }
+
+  pub fn with_clock(clock: SharedClock) -> DDSCache {
     DDSCache {
       topic_caches: HashMap::new(),
+      topic_handles: HashMap::new(),
+      clock,
+      poison_recoveries: Arc::new(AtomicU64::new(0)),
     }
   }
 
+  pub fn clock(&self) -> SharedClock {
+    self.clock.clone()
+  }
+
+  pub fn poison_recoveries(&self) -> u64 {
+    self.poison_recoveries.load(Ordering::Relaxed)
+  }
+
   pub fn add_new_topic(
     &mut self,
     topic_name: &String,
     topic_kind: TopicKind,
     topic_data_type: &TypeDesc,
+    topic_qos: &QosPolicies,
   ) -> bool {
     if self.topic_caches.contains_key(topic_name) {
       return false;
     } else {
       self.topic_caches.insert(
         topic_name.to_string(),
-        TopicCache::new(topic_kind, topic_data_type.clone()),
+        Arc::new(RwLock::new(TopicCache::new(
+          topic_kind,
+          topic_data_type.clone(),
+          topic_qos.clone(),
+          self.poison_recoveries.clone(),
+        ))),
       );
       return true;
     }
@@ -53,164 +217,608 @@ impl DDSCache {
     }
   }
 
-  pub fn get_topic_qos_mut(&mut self, topic_name: &String) -> Option<&mut QosPolicies> {
-    if self.topic_caches.contains_key(topic_name) {
-      return Some(&mut self.topic_caches.get_mut(topic_name).unwrap().topic_qos);
-    } else {
-      return None;
-    }
+  /// The TopicCache of the topic. Holding it does not keep the DDSCache locked, but it
+  /// is no longer the cache of the topic if the topic is removed.
+  pub fn get_topic_cache(&self, topic_name: &str) -> Option<Arc<RwLock<TopicCache>>> {
+    self.topic_caches.get(topic_name).cloned()
   }
 
-  pub fn get_topic_qos(&self, topic_name: &String) -> Option<&QosPolicies> {
-    if self.topic_caches.contains_key(topic_name) {
-      return Some(&self.topic_caches.get(topic_name).unwrap().topic_qos);
-    } else {
-      return None;
-    }
+  pub fn get_topic_qos(&self, topic_name: &String) -> Option<QosPolicies> {
+    self.read_topic(topic_name).map(|tc| tc.topic_qos.clone())
+  }
+
+  /// Replaces the QoS of the topic, when the QoS of the Topic is changed.
+  pub fn update_topic_qos(
+    &self,
+    topic_name: &String,
+    topic_qos: &QosPolicies,
+  ) -> Result<(), CacheError> {
+    self.write_topic(topic_name)?.topic_qos = topic_qos.clone();
+    Ok(())
   }
 
   pub fn from_topic_get_change(
     &self,
     topic_name: &String,
-    instant: &Timestamp,
-  ) -> Option<&CacheChange> {
-    match self.topic_caches.get(topic_name) {
-      Some(tc) => tc.get_change(instant),
-      None => None,
-    }
+    instant: &CacheInstant,
+  ) -> Option<CacheChange> {
+    self.read_topic(topic_name)?.get_change(instant).cloned()
   }
 
   /// Sets cacheChange to not alive disposed. So its waiting to be permanently removed.
   pub fn from_topic_set_change_to_not_alive_disposed(
-    &mut self,
+    &self,
     topic_name: &String,
-    instant: &Timestamp,
-  ) {
-    if self.topic_caches.contains_key(topic_name) {
-      self
-        .topic_caches
-        .get_mut(topic_name)
-        .unwrap()
-        .set_change_to_not_alive_disposed(instant);
-    } else {
-      panic!("Topic: '{:?}' is not in DDSCache", topic_name);
-    }
+    instant: &CacheInstant,
+  ) -> Result<(), CacheError> {
+    self
+      .write_topic(topic_name)?
+      .set_change_to_not_alive_disposed(instant)
   }
 
   /// Removes cacheChange permanently
   pub fn from_topic_remove_change(
-    &mut self,
+    &self,
     topic_name: &String,
-    instant: &Timestamp,
+    instant: &CacheInstant,
+  ) -> Result<CacheChange, CacheError> {
+    self
+      .write_topic(topic_name)?
+      .remove_change(instant)
+      .ok_or(CacheError::UnknownChange(*instant))
+  }
+
+  pub fn from_topic_get_all_changes(&self, topic_name: &str) -> Vec<(CacheInstant, CacheChange)> {
+    match self.read_topic(topic_name) {
+      Some(tc) => owned(tc.get_all_changes()),
+      None => vec![],
+    }
+  }
+
+  pub fn from_topic_get_changes_in_range(
+    &self,
+    topic_name: &String,
+    start_instant: &CacheInstant,
+    end_instant: &CacheInstant,
+  ) -> Vec<(CacheInstant, CacheChange)> {
+    match self.read_topic(topic_name) {
+      Some(tc) => owned(tc.get_changes_in_range(start_instant, end_instant)),
+      None => vec![],
+    }
+  }
+
+  /// The change our own writer wrote with the sequence number.
+  pub fn from_topic_get_change_by_sn(
+    &self,
+    topic_name: &str,
+    writer_guid: GUID,
+    sequence_number: SequenceNumber,
   ) -> Option<CacheChange> {
-    if self.topic_caches.contains_key(topic_name) {
-      return self
-        .topic_caches
-        .get_mut(topic_name)
-        .unwrap()
-        .remove_change(instant);
-    } else {
-      panic!("Topic: '{:?}' is not in DDSCache", topic_name);
+    self
+      .read_topic(topic_name)?
+      .get_change_by_sn(writer_guid, sequence_number)
+      .cloned()
+  }
+
+  /// Key hashes of the instances of a keyed topic that have changes in the cache.
+  pub fn from_topic_get_instances(&self, topic_name: &str) -> Vec<u128> {
+    match self.read_topic(topic_name) {
+      Some(tc) => tc.get_instances(),
+      None => vec![],
     }
   }
 
-  pub fn from_topic_get_all_changes(&self, topic_name: &str) -> Vec<(&Timestamp, &CacheChange)> {
-    match self.topic_caches.get(topic_name) {
-      Some(r) => r.get_all_changes(),
+  /// Changes of one instance of a keyed topic, in the order of their instants.
+  pub fn from_topic_get_instance_changes(
+    &self,
+    topic_name: &str,
+    key_hash: u128,
+  ) -> Vec<(CacheInstant, CacheChange)> {
+    match self.read_topic(topic_name) {
+      Some(tc) => owned(tc.get_instance_changes(key_hash)),
       None => vec![],
     }
   }
 
-  pub fn from_topic_get_changes_in_range(
+  pub fn from_topic_get_latest_instance_change(
+    &self,
+    topic_name: &str,
+    key_hash: u128,
+  ) -> Option<(CacheInstant, CacheChange)> {
+    self
+      .read_topic(topic_name)?
+      .get_latest_instance_change(key_hash)
+      .map(|(i, cc)| (*i, cc.clone()))
+  }
+
+  /// Removes the oldest changes of the instance, so that at most depth of them remain.
+  /// Returns the removed changes.
+  pub fn from_topic_remove_instance_changes_but_keep_depth(
     &self,
     topic_name: &String,
-    start_instant: &Timestamp,
-    end_instant: &Timestamp,
-  ) -> Vec<(&Timestamp, &CacheChange)> {
-    if self.topic_caches.contains_key(topic_name) {
-      return self
-        .topic_caches
-        .get(topic_name)
-        .unwrap()
-        .get_changes_in_range(start_instant, end_instant);
-    } else {
-      return vec![];
+    key_hash: u128,
+    depth: usize,
+  ) -> Result<Vec<(CacheInstant, CacheChange)>, CacheError> {
+    let removed = self
+      .write_topic(topic_name)?
+      .remove_instance_changes_but_keep_depth(key_hash, depth);
+    for (_, change) in removed.iter() {
+      dds_event!(
+        trace,
+        "cache change evicted",
+        topic = topic_name,
+        writer = change.writer_guid,
+        seq = change.sequence_number,
+      );
+    }
+    Ok(removed)
+  }
+
+  /// Records that the DataReader has taken all changes of the topic up to and including
+  /// the instant. Garbage collection only removes changes that all DataReaders have taken.
+  pub fn set_data_reader_progress(
+    &self,
+    topic_name: &String,
+    data_reader: GUID,
+    instant: CacheInstant,
+  ) -> Result<(), CacheError> {
+    self
+      .write_topic(topic_name)?
+      .data_readers
+      .insert(data_reader, instant);
+    Ok(())
+  }
+
+  /// Forgets the progress of a deleted DataReader.
+  pub fn remove_data_reader(
+    &self,
+    topic_name: &String,
+    data_reader: GUID,
+  ) -> Result<(), CacheError> {
+    self
+      .write_topic(topic_name)?
+      .data_readers
+      .remove(&data_reader);
+    Ok(())
+  }
+
+  /// Records that a DataWriter or DataReader of this participant uses the topic.
+  pub fn add_topic_user(&self, topic_name: &String, user: GUID) -> Result<(), CacheError> {
+    self.write_topic(topic_name)?.users.insert(user);
+    Ok(())
+  }
+
+  /// Forgets a deleted DataWriter or DataReader of the topic, and removes the whole topic
+  /// if it was the last one and there are no Topic handles of it. Returns true if the
+  /// topic was removed.
+  pub fn remove_topic_user(&mut self, topic_name: &String, user: GUID) -> bool {
+    let unused = match self.write_topic(topic_name) {
+      Ok(mut tc) => {
+        tc.users.remove(&user);
+        tc.data_readers.remove(&user);
+        tc.users.is_empty() && !self.topic_handles.contains_key(topic_name)
+      }
+      Err(_) => return false,
+    };
+    if unused {
+      self.remove_topic(topic_name);
+    }
+    unused
+  }
+
+  /// Records a new Topic handle of the topic. The topic need not be in the cache yet.
+  pub fn add_topic_handle(&mut self, topic_name: &str) {
+    *self
+      .topic_handles
+      .entry(topic_name.to_string())
+      .or_insert(0) += 1;
+  }
+
+  /// Forgets a dropped Topic handle, and removes the topic if it was the last handle and
+  /// no DataWriter or DataReader uses the topic. Returns true if the topic is no longer
+  /// used.
+  pub fn remove_topic_handle(&mut self, topic_name: &str) -> bool {
+    match self.topic_handles.get_mut(topic_name) {
+      Some(count) if *count > 1 => {
+        *count -= 1;
+        return false;
+      }
+      Some(_) => {
+        self.topic_handles.remove(topic_name);
+      }
+      None => return false,
+    }
+    if self.topic_has_users(topic_name) {
+      return false;
+    }
+    self.remove_topic(&topic_name.to_string());
+    true
+  }
+
+  /// Whether some DataWriter or DataReader of this participant uses the topic.
+  pub fn topic_has_users(&self, topic_name: &str) -> bool {
+    match self.read_topic(topic_name) {
+      Some(tc) => !tc.users.is_empty(),
+      None => false,
     }
   }
 
+  /// Removes the received changes that are no longer needed from all topics, see
+  /// TopicCache::collect_garbage. Returns how many changes were removed. Topics are
+  /// locked one at a time.
+  pub fn collect_garbage(&self) -> usize {
+    let now = self.clock.now();
+    let mut removed = 0;
+    for topic_name in self.topic_caches.keys() {
+      let count = match self.write_topic(topic_name) {
+        Ok(mut tc) => tc.collect_garbage(now),
+        Err(_) => continue,
+      };
+      if count > 0 {
+        dds_event!(
+          debug,
+          "cache changes evicted",
+          topic = topic_name,
+          count = count
+        );
+      }
+      removed += count;
+    }
+    removed
+  }
+
+  pub fn statistics(&self) -> CacheStatistics {
+    let mut statistics = CacheStatistics::default();
+    for topic_name in self.topic_caches.keys() {
+      let topic = match self.read_topic(topic_name) {
+        Some(tc) => tc.statistics(),
+        None => continue,
+      };
+      statistics.total.add(&topic);
+      statistics.topics.insert(topic_name.clone(), topic);
+    }
+    statistics
+  }
+
   pub fn to_topic_add_change(
-    &mut self,
+    &self,
     topic_name: &String,
-    instant: &Timestamp,
+    instant: &CacheInstant,
     cache_change: CacheChange,
-  ) {
-    if self.topic_caches.contains_key(topic_name) {
-      return self
-        .topic_caches
-        .get_mut(topic_name)
-        .unwrap()
-        .add_change(instant, cache_change);
-    } else {
-      panic!("Topic: '{:?}' is not added to DDSCache", topic_name);
+  ) -> Result<(), CacheError> {
+    self
+      .write_topic(topic_name)?
+      .add_change(instant, cache_change);
+    Ok(())
+  }
+
+  // Adds the changes with one lock of the TopicCache, e.g. the ones of one received
+  // message.
+  pub fn to_topic_add_changes(
+    &self,
+    topic_name: &String,
+    changes: Vec<(CacheInstant, CacheChange)>,
+  ) -> Result<(), CacheError> {
+    let mut topic_cache = self.write_topic(topic_name)?;
+    for (instant, cache_change) in changes {
+      topic_cache.add_change(&instant, cache_change);
+    }
+    Ok(())
+  }
+
+  fn read_topic(&self, topic_name: &str) -> Option<RwLockReadGuard<'_, TopicCache>> {
+    self.topic_caches.get(topic_name).map(|tc| read_lock(tc))
+  }
+
+  fn write_topic(&self, topic_name: &str) -> Result<RwLockWriteGuard<'_, TopicCache>, CacheError> {
+    match self.topic_caches.get(topic_name) {
+      Some(tc) => Ok(write_lock(tc)),
+      None => Err(CacheError::UnknownTopic(topic_name.to_string())),
     }
   }
 }
 
+impl PoisonRecovery for DDSCache {
+  const NAME: &'static str = "DDSCache";
+
+  fn count_poison_recovery(&self) {
+    self.poison_recoveries.fetch_add(1, Ordering::Relaxed);
+  }
+}
+
+// Copies changes out of a TopicCache, so that its lock can be released.
+fn owned(changes: Vec<(&CacheInstant, &CacheChange)>) -> Vec<(CacheInstant, CacheChange)> {
+  changes
+    .into_iter()
+    .map(|(i, cc)| (*i, cc.clone()))
+    .collect()
+}
+
+/// The changes of one topic, ordered by their instants. Changes of keyed topics are also
+/// indexed by instance, so that the changes of one instance are found without going
+/// through all of them.
 #[derive(Debug)]
 pub struct TopicCache {
   topic_data_type: TypeDesc,
   topic_kind: TopicKind,
   topic_qos: QosPolicies,
   history_cache: DDSHistoryCache,
+  // The instants of the changes of each instance of a keyed topic, by key hash.
+  // Changes with an unknown (zero) key hash are only in history_cache.
+  instances: HashMap<u128, BTreeSet<CacheInstant>>,
+  // The changes of our own writers by writer and sequence number, for answering
+  // AckNacks. Received changes are not here, as several Readers may have a copy of the
+  // same change.
+  sequence_numbers: HashMap<(GUID, SequenceNumber), CacheInstant>,
+  // How far each DataReader of the topic has taken changes to its DataSampleCache.
+  // Samples loaned from a DataReader are copies there, so they are not affected by
+  // removing changes that have been taken.
+  data_readers: HashMap<GUID, CacheInstant>,
+  // Our DataWriters and DataReaders of the topic. The topic is removed from DDSCache
+  // when the last of them is deleted, unless there are Topic handles of it.
+  users: HashSet<GUID>,
+  // payload bytes of the changes in history_cache
+  payload_bytes: usize,
+  // shared with the DDSCache
+  poison_recoveries: Arc<AtomicU64>,
 }
 
 impl TopicCache {
-  pub fn new(topic_kind: TopicKind, topic_data_type: TypeDesc) -> TopicCache {
+  pub fn new(
+    topic_kind: TopicKind,
+    topic_data_type: TypeDesc,
+    topic_qos: QosPolicies,
+    poison_recoveries: Arc<AtomicU64>,
+  ) -> TopicCache {
     TopicCache {
       topic_data_type: topic_data_type,
       topic_kind: topic_kind,
-      topic_qos: QosPolicyBuilder::new().build(),
+      topic_qos,
       history_cache: DDSHistoryCache::new(),
+      instances: HashMap::new(),
+      sequence_numbers: HashMap::new(),
+      data_readers: HashMap::new(),
+      users: HashSet::new(),
+      payload_bytes: 0,
+      poison_recoveries,
     }
   }
-  pub fn get_change(&self, instant: &Timestamp) -> Option<&CacheChange> {
+  pub fn get_change(&self, instant: &CacheInstant) -> Option<&CacheChange> {
     self.history_cache.get_change(instant)
   }
 
-  pub fn add_change(&mut self, instant: &Timestamp, cache_change: CacheChange) {
-    self.history_cache.add_change(instant, cache_change)
+  pub fn add_change(&mut self, instant: &CacheInstant, cache_change: CacheChange) {
+    let key_hash = cache_change.key;
+    self.payload_bytes += payload_bytes(&cache_change);
+    let written = match cache_change.received_by {
+      None => Some((cache_change.writer_guid, cache_change.sequence_number)),
+      Some(_) => None,
+    };
+    if let Some(replaced) = self.history_cache.add_change(instant, cache_change) {
+      self.remove_from_indexes(instant, &replaced);
+    }
+    if self.topic_kind == TopicKind::WithKey && key_hash != 0 {
+      self
+        .instances
+        .entry(key_hash)
+        .or_insert_with(BTreeSet::new)
+        .insert(*instant);
+    }
+    if let Some(writer_sn) = written {
+      self.sequence_numbers.insert(writer_sn, *instant);
+    }
   }
 
-  pub fn get_all_changes(&self) -> Vec<(&Timestamp, &CacheChange)> {
+  pub fn get_change_by_sn(
+    &self,
+    writer_guid: GUID,
+    sequence_number: SequenceNumber,
+  ) -> Option<&CacheChange> {
+    let instant = self.sequence_numbers.get(&(writer_guid, sequence_number))?;
+    self.history_cache.get_change(instant)
+  }
+
+  pub fn get_all_changes(&self) -> Vec<(&CacheInstant, &CacheChange)> {
     self.history_cache.get_all_changes()
   }
 
   pub fn get_changes_in_range(
     &self,
-    start_instant: &Timestamp,
-    end_instant: &Timestamp,
-  ) -> Vec<(&Timestamp, &CacheChange)> {
+    start_instant: &CacheInstant,
+    end_instant: &CacheInstant,
+  ) -> Vec<(&CacheInstant, &CacheChange)> {
     self
       .history_cache
       .get_range_of_changes_vec(start_instant, end_instant)
   }
 
   ///Removes and returns value if it was found
-  pub fn remove_change(&mut self, instant: &Timestamp) -> Option<CacheChange> {
-    return self.history_cache.remove_change(instant);
+  pub fn remove_change(&mut self, instant: &CacheInstant) -> Option<CacheChange> {
+    let removed = self.history_cache.remove_change(instant);
+    if let Some(change) = &removed {
+      self.remove_from_indexes(instant, change);
+    }
+    removed
   }
 
-  pub fn set_change_to_not_alive_disposed(&mut self, instant: &Timestamp) {
+  pub fn get_instances(&self) -> Vec<u128> {
+    self.instances.keys().copied().collect()
+  }
+
+  pub fn get_instance_changes(&self, key_hash: u128) -> Vec<(&CacheInstant, &CacheChange)> {
+    match self.instances.get(&key_hash) {
+      Some(instants) => instants
+        .iter()
+        .filter_map(|i| self.history_cache.get_change(i).map(|cc| (i, cc)))
+        .collect(),
+      None => vec![],
+    }
+  }
+
+  pub fn get_latest_instance_change(
+    &self,
+    key_hash: u128,
+  ) -> Option<(&CacheInstant, &CacheChange)> {
+    let instant = self.instances.get(&key_hash)?.iter().next_back()?;
     self
       .history_cache
-      .change_change_kind(instant, ChangeKind::NOT_ALIVE_DISPOSED);
+      .get_change(instant)
+      .map(|cc| (instant, cc))
+  }
+
+  pub fn remove_instance_changes_but_keep_depth(
+    &mut self,
+    key_hash: u128,
+    depth: usize,
+  ) -> Vec<(CacheInstant, CacheChange)> {
+    let oldest: Vec<CacheInstant> = match self.instances.get(&key_hash) {
+      Some(instants) => instants
+        .iter()
+        .take(instants.len().saturating_sub(depth))
+        .copied()
+        .collect(),
+      None => return vec![],
+    };
+    oldest
+      .into_iter()
+      .filter_map(|i| self.remove_change(&i).map(|cc| (i, cc)))
+      .collect()
+  }
+
+  /// Removes the received changes that all DataReaders have taken and that are beyond
+  /// the History depth of their instance. Once the latest change of an instance is a
+  /// dispose or unregister older than the delay of the ReaderDataLifecycle QoS, all taken
+  /// changes of the instance are removed. Changes of our own writers are not touched:
+  /// only the writer knows which of them matched readers have acknowledged.
+  /// Returns how many changes were removed.
+  pub fn collect_garbage(&mut self, now: Timestamp) -> usize {
+    let depth = match self.topic_qos.history {
+      Some(History::KeepLast { depth }) => depth.max(0) as usize,
+      Some(History::KeepAll) => usize::MAX,
+      // DDS default is KeepLast 1
+      None => 1,
+    };
+    // received changes of each instance, in the order of their instants
+    let mut instances: HashMap<u128, Vec<(CacheInstant, ChangeKind, bool)>> = HashMap::new();
+    for (instant, change) in self.history_cache.get_all_changes() {
+      let taken = match change.received_by {
+        Some(reader) => self.is_taken(instant, change.writer_guid, reader),
+        None => continue,
+      };
+      let key_hash = match self.topic_kind {
+        TopicKind::WithKey => change.key,
+        TopicKind::NoKey => 0,
+      };
+      instances
+        .entry(key_hash)
+        .or_insert_with(Vec::new)
+        .push((*instant, change.kind, taken));
+    }
+
+    let mut garbage = Vec::new();
+    for changes in instances.values() {
+      let keep = match changes.last() {
+        Some((instant, kind, _)) if self.autopurge_delay_passed(instant, *kind, now) => 0,
+        _ => depth,
+      };
+      garbage.extend(
+        changes
+          .iter()
+          .rev()
+          .skip(keep)
+          .filter(|(_, _, taken)| *taken)
+          .map(|(instant, _, _)| *instant),
+      );
+    }
+    for instant in garbage.iter() {
+      self.remove_change(instant);
+    }
+    garbage.len()
+  }
+
+  // Changes handed over from our own writers are only for the DataReader of the Reader
+  // that received them. Changes from other participants are read by all DataReaders.
+  fn is_taken(&self, instant: &CacheInstant, writer_guid: GUID, received_by: GUID) -> bool {
+    if writer_guid.guidPrefix == received_by.guidPrefix {
+      match self.data_readers.get(&received_by) {
+        Some(taken) => instant <= taken,
+        // the DataReader has been deleted
+        None => true,
+      }
+    } else {
+      self.data_readers.values().all(|taken| instant <= taken)
+    }
+  }
+
+  fn autopurge_delay_passed(
+    &self,
+    instant: &CacheInstant,
+    kind: ChangeKind,
+    now: Timestamp,
+  ) -> bool {
+    let lifecycle = match self.topic_qos.reader_data_lifecycle {
+      Some(lifecycle) => lifecycle,
+      None => return false,
+    };
+    let delay = match kind {
+      ChangeKind::ALIVE => return false,
+      ChangeKind::NOT_ALIVE_DISPOSED => lifecycle.autopurge_disposed_samples_delay,
+      ChangeKind::NOT_ALIVE_UNREGISTERED => lifecycle.autopurge_nowriter_samples_delay,
+    };
+    delay != Duration::DURATION_INFINITE && now.duration_since(instant.timestamp()) >= delay
+  }
+
+  pub fn statistics(&self) -> TopicCacheStatistics {
+    TopicCacheStatistics {
+      changes: self.history_cache.len(),
+      payload_bytes: self.payload_bytes,
+      instances: self.instances.len(),
+      oldest: self.history_cache.first_instant().map(|i| i.timestamp()),
+      newest: self.history_cache.last_instant().map(|i| i.timestamp()),
+    }
+  }
+
+  // Forgets a change that is no longer in history_cache.
+  fn remove_from_indexes(&mut self, instant: &CacheInstant, change: &CacheChange) {
+    self.payload_bytes -= payload_bytes(change);
+    if let Some(instants) = self.instances.get_mut(&change.key) {
+      instants.remove(instant);
+      if instants.is_empty() {
+        self.instances.remove(&change.key);
+      }
+    }
+    let writer_sn = (change.writer_guid, change.sequence_number);
+    if self.sequence_numbers.get(&writer_sn) == Some(instant) {
+      self.sequence_numbers.remove(&writer_sn);
+    }
+  }
+
+  pub fn set_change_to_not_alive_disposed(
+    &mut self,
+    instant: &CacheInstant,
+  ) -> Result<(), CacheError> {
+    self
+      .history_cache
+      .change_change_kind(instant, ChangeKind::NOT_ALIVE_DISPOSED)
   }
 }
 
+impl PoisonRecovery for TopicCache {
+  const NAME: &'static str = "TopicCache";
+
+  fn count_poison_recovery(&self) {
+    self.poison_recoveries.fetch_add(1, Ordering::Relaxed);
+  }
+}
+
+fn payload_bytes(change: &CacheChange) -> usize {
+  change.data_value.as_ref().map_or(0, |p| p.value.len())
+}
+
 // This is contained in a TopicCache
 #[derive(Debug)]
 pub struct DDSHistoryCache {
-  changes: BTreeMap<Timestamp, CacheChange>,
+  changes: BTreeMap<CacheInstant, CacheChange>,
 }
 
 impl DDSHistoryCache {
@@ -220,29 +828,49 @@ impl DDSHistoryCache {
     }
   }
 
-  pub fn add_change(&mut self, instant: &Timestamp, cache_change: CacheChange) {
-    let result = self.changes.insert(*instant, cache_change);
-    if result.is_none() {
-      // all is good. timestamp was not inserted before.
-    } else {
-      // If this happens cahce changes were created at exactly same instant.
-      panic!("DDSHistoryCache already contained element with key !!!");
+  /// Returns the change that was replaced, if there already was one at the instant.
+  pub fn add_change(
+    &mut self,
+    instant: &CacheInstant,
+    cache_change: CacheChange,
+  ) -> Option<CacheChange> {
+    let replaced = self.changes.insert(*instant, cache_change);
+    // Instants are unique, so this only happens if one is added twice.
+    if replaced.is_some() {
+      warn!("DDSHistoryCache replaced the change at {:?}", instant);
     }
+    replaced
   }
 
-  pub fn get_all_changes(&self) -> Vec<(&Timestamp, &CacheChange)> {
+  pub fn get_all_changes(&self) -> Vec<(&CacheInstant, &CacheChange)> {
     self.changes.iter().collect()
   }
 
-  pub fn get_change(&self, instant: &Timestamp) -> Option<&CacheChange> {
+  pub fn len(&self) -> usize {
+    self.changes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+
+  pub fn first_instant(&self) -> Option<&CacheInstant> {
+    self.changes.keys().next()
+  }
+
+  pub fn last_instant(&self) -> Option<&CacheInstant> {
+    self.changes.keys().next_back()
+  }
+
+  pub fn get_change(&self, instant: &CacheInstant) -> Option<&CacheChange> {
     self.changes.get(instant)
   }
 
   pub fn get_range_of_changes(
     &self,
-    start_instant: &Timestamp,
-    end_instant: &Timestamp,
-  ) -> Range<Timestamp, CacheChange> {
+    start_instant: &CacheInstant,
+    end_instant: &CacheInstant,
+  ) -> Range<CacheInstant, CacheChange> {
     self
       .changes
       .range((Included(start_instant), Included(end_instant)))
@@ -250,10 +878,10 @@ impl DDSHistoryCache {
 
   pub fn get_range_of_changes_vec(
     &self,
-    start_instant: &Timestamp,
-    end_instant: &Timestamp,
-  ) -> Vec<(&Timestamp, &CacheChange)> {
-    let mut changes: Vec<(&Timestamp, &CacheChange)> = vec![];
+    start_instant: &CacheInstant,
+    end_instant: &CacheInstant,
+  ) -> Vec<(&CacheInstant, &CacheChange)> {
+    let mut changes: Vec<(&CacheInstant, &CacheChange)> = vec![];
     for (i, c) in self
       .changes
       .range((Excluded(start_instant), Included(end_instant)))
@@ -263,15 +891,17 @@ impl DDSHistoryCache {
     return changes;
   }
 
-  pub fn change_change_kind(&mut self, instant: &Timestamp, change_kind: ChangeKind) {
-    let change = self.changes.get_mut(instant);
-    if change.is_some() {
-      change.unwrap().kind = change_kind;
-    } else {
-      panic!(
-        "CacheChange with instance: {:?} was not found on DDSHistoryCache!",
-        instant
-      );
+  pub fn change_change_kind(
+    &mut self,
+    instant: &CacheInstant,
+    change_kind: ChangeKind,
+  ) -> Result<(), CacheError> {
+    match self.changes.get_mut(instant) {
+      Some(change) => {
+        change.kind = change_kind;
+        Ok(())
+      }
+      None => Err(CacheError::UnknownChange(*instant)),
     }
   }
 
@@ -289,7 +919,7 @@ impl DDSHistoryCache {
   */
 
   /// Removes and returns value if it was found
-  pub fn remove_change(&mut self, instant: &Timestamp) -> Option<CacheChange> {
+  pub fn remove_change(&mut self, instant: &CacheInstant) -> Option<CacheChange> {
     self.changes.remove(instant)
   }
 }
@@ -300,10 +930,11 @@ mod tests {
   use std::{thread};
   use log::info;
 
-  use super::DDSCache;
+  use super::{CacheError, CacheInstant, DDSCache};
   use crate::{
     dds::{
-      data_types::DDSTimestamp, ddsdata::DDSData, data_types::DDSDuration, typedesc::TypeDesc,
+      data_types::Timestamp, ddsdata::DDSData, data_types::Duration, typedesc::TypeDesc,
+      qos::QosPolicies,
     },
     messages::submessages::submessage_elements::serialized_payload::{SerializedPayload},
     structure::{
@@ -326,11 +957,13 @@ mod tests {
       topic_name,
       TopicKind::WithKey,
       &TypeDesc::new("IDontKnowIfThisIsNecessary".to_string()),
+      &QosPolicies::qos_none(),
     );
     cache
       .write()
       .unwrap()
-      .to_topic_add_change(topic_name, &DDSTimestamp::now(), change1);
+      .to_topic_add_change(topic_name, &CacheInstant::now(), change1)
+      .unwrap();
 
     let pointerToCache1 = cache.clone();
 
@@ -342,22 +975,22 @@ mod tests {
         SequenceNumber::from(1),
         Some(DDSData::new(SerializedPayload::default())),
       );
-      pointerToCache1.write().unwrap().to_topic_add_change(
-        topic_name,
-        &DDSTimestamp::now(),
-        cahange2,
-      );
+      pointerToCache1
+        .write()
+        .unwrap()
+        .to_topic_add_change(topic_name, &CacheInstant::now(), cahange2)
+        .unwrap();
       let cahange3 = CacheChange::new(
         ChangeKind::ALIVE,
         GUID::GUID_UNKNOWN,
         SequenceNumber::from(2),
         Some(DDSData::new(SerializedPayload::default())),
       );
-      pointerToCache1.write().unwrap().to_topic_add_change(
-        topic_name,
-        &DDSTimestamp::now(),
-        cahange3,
-      );
+      pointerToCache1
+        .write()
+        .unwrap()
+        .to_topic_add_change(topic_name, &CacheInstant::now(), cahange3)
+        .unwrap();
     })
     .join()
     .unwrap();
@@ -365,15 +998,15 @@ mod tests {
     cache
       .read()
       .unwrap()
-      .from_topic_get_change(topic_name, &DDSTimestamp::now());
+      .from_topic_get_change(topic_name, &CacheInstant::now());
     assert_eq!(
       cache
         .read()
         .unwrap()
         .from_topic_get_changes_in_range(
           topic_name,
-          &(DDSTimestamp::now() - DDSDuration::from_secs(23)),
-          &DDSTimestamp::now()
+          &CacheInstant::from(Timestamp::now() - Duration::from_secs(23)),
+          &CacheInstant::now()
         )
         .len(),
       3
@@ -382,9 +1015,1002 @@ mod tests {
       "{:?}",
       cache.read().unwrap().from_topic_get_changes_in_range(
         topic_name,
-        &(DDSTimestamp::now() - DDSDuration::from_secs(23)),
-        &DDSTimestamp::now()
+        &CacheInstant::from(Timestamp::now() - Duration::from_secs(23)),
+        &CacheInstant::now()
       )
     );
   }
+
+  #[test]
+  fn dds_cache_concurrent_writes() {
+    let cache = Arc::new(RwLock::new(DDSCache::new()));
+    let topic_name = String::from("Crowded");
+    cache.write().unwrap().add_new_topic(
+      &topic_name,
+      TopicKind::NoKey,
+      &TypeDesc::new("Crowded".to_string()),
+      &QosPolicies::qos_none(),
+    );
+
+    // Many changes get the same Timestamp, as the clock does not advance between them.
+    let writers: Vec<_> = (0..8)
+      .map(|_| {
+        let cache = cache.clone();
+        let topic_name = topic_name.clone();
+        thread::spawn(move || {
+          let writer_guid = GUID::new();
+          let mut instants = Vec::new();
+          for sn in 1..=1000 {
+            let change = CacheChange::new(
+              ChangeKind::ALIVE,
+              writer_guid,
+              SequenceNumber::from(sn),
+              Some(DDSData::new(SerializedPayload::default())),
+            );
+            let instant = CacheInstant::now();
+            cache
+              .write()
+              .unwrap()
+              .to_topic_add_change(&topic_name, &instant, change)
+              .unwrap();
+            instants.push(instant);
+          }
+          instants
+        })
+      })
+      .collect();
+    let instants: Vec<CacheInstant> = writers
+      .into_iter()
+      .flat_map(|w| w.join().unwrap())
+      .collect();
+
+    let cache = cache.read().unwrap();
+    assert_eq!(cache.from_topic_get_all_changes(&topic_name).len(), 8000);
+    for instant in instants.iter() {
+      assert!(cache.from_topic_get_change(&topic_name, instant).is_some());
+    }
+    // the first thread's changes after its first one, and those of others in between
+    assert!(
+      cache
+        .from_topic_get_changes_in_range(&topic_name, &instants[0], &instants[999])
+        .len()
+        >= 999
+    );
+  }
+
+  // Run with: cargo test --release dds_cache_topic_lock_benchmark -- --ignored --nocapture
+  #[test]
+  #[ignore]
+  fn dds_cache_topic_lock_benchmark() {
+    use std::time::{Duration, Instant};
+
+    let topics: Vec<String> = (0..40).map(|i| format!("Topic{}", i)).collect();
+
+    // One writer and one reader thread per topic. With `exclusive` every
+    // operation takes the DDSCache write lock, as all of them did before
+    // each TopicCache got its own lock.
+    let run = |exclusive: bool| -> Duration {
+      let cache = Arc::new(RwLock::new(DDSCache::new()));
+      for topic_name in topics.iter() {
+        cache.write().unwrap().add_new_topic(
+          topic_name,
+          TopicKind::NoKey,
+          &TypeDesc::new(topic_name.clone()),
+          &QosPolicies::qos_none(),
+        );
+      }
+      let start = Instant::now();
+      let threads: Vec<_> = topics
+        .iter()
+        .flat_map(|topic_name| {
+          let writer = {
+            let cache = cache.clone();
+            let topic_name = topic_name.clone();
+            thread::spawn(move || {
+              let writer_guid = GUID::new();
+              for sn in 1..=2000 {
+                let change = CacheChange::new(
+                  ChangeKind::ALIVE,
+                  writer_guid,
+                  SequenceNumber::from(sn),
+                  Some(DDSData::new(SerializedPayload::default())),
+                );
+                let instant = CacheInstant::now();
+                if exclusive {
+                  cache
+                    .write()
+                    .unwrap()
+                    .to_topic_add_change(&topic_name, &instant, change)
+                } else {
+                  cache
+                    .read()
+                    .unwrap()
+                    .to_topic_add_change(&topic_name, &instant, change)
+                }
+                .unwrap();
+              }
+            })
+          };
+          let reader = {
+            let cache = cache.clone();
+            let topic_name = topic_name.clone();
+            thread::spawn(move || {
+              let mut latest = CacheInstant::from(Timestamp::TIME_ZERO);
+              let mut received = 0;
+              while received < 2000 {
+                let now = CacheInstant::now();
+                let changes = if exclusive {
+                  cache
+                    .write()
+                    .unwrap()
+                    .from_topic_get_changes_in_range(&topic_name, &latest, &now)
+                } else {
+                  cache
+                    .read()
+                    .unwrap()
+                    .from_topic_get_changes_in_range(&topic_name, &latest, &now)
+                };
+                if let Some((instant, _)) = changes.last() {
+                  latest = *instant;
+                }
+                received += changes.len();
+              }
+            })
+          };
+          vec![writer, reader]
+        })
+        .collect();
+      for t in threads {
+        t.join().unwrap();
+      }
+      start.elapsed()
+    };
+
+    let global = run(true);
+    let per_topic = run(false);
+    println!(
+      "{} topics, 2000 changes each: global lock {:?}, per-topic locks {:?}",
+      topics.len(),
+      global,
+      per_topic
+    );
+  }
+
+  #[test]
+  fn dds_cache_unknown_topic_and_change() {
+    let mut cache = DDSCache::new();
+    let topic_name = String::from("Gone");
+    let change = || {
+      CacheChange::new(
+        ChangeKind::ALIVE,
+        GUID::GUID_UNKNOWN,
+        SequenceNumber::from(1),
+        Some(DDSData::new(SerializedPayload::default())),
+      )
+    };
+    let instant = CacheInstant::now();
+    let unknown_topic = Err(CacheError::UnknownTopic(topic_name.clone()));
+    assert_eq!(
+      cache.to_topic_add_change(&topic_name, &instant, change()),
+      unknown_topic
+    );
+
+    cache.add_new_topic(
+      &topic_name,
+      TopicKind::NoKey,
+      &TypeDesc::new("Gone".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    assert_eq!(
+      cache.from_topic_set_change_to_not_alive_disposed(&topic_name, &instant),
+      Err(CacheError::UnknownChange(instant))
+    );
+    assert_eq!(
+      cache.from_topic_remove_change(&topic_name, &instant),
+      Err(CacheError::UnknownChange(instant))
+    );
+    cache
+      .to_topic_add_change(&topic_name, &instant, change())
+      .unwrap();
+
+    // A writer disposes its change after the topic is gone.
+    cache.remove_topic(&topic_name);
+    assert_eq!(
+      cache.from_topic_set_change_to_not_alive_disposed(&topic_name, &instant),
+      unknown_topic
+    );
+    assert!(cache
+      .from_topic_remove_change(&topic_name, &instant)
+      .is_err());
+  }
+
+  fn keyed_change(key_hash: u128, sn: i64, kind: ChangeKind) -> CacheChange {
+    let mut change = CacheChange::new(
+      kind,
+      GUID::GUID_UNKNOWN,
+      SequenceNumber::from(sn),
+      Some(DDSData::new(SerializedPayload::default())),
+    );
+    change.key = key_hash;
+    change
+  }
+
+  fn keyed_topic(topic_name: &String) -> DDSCache {
+    let mut cache = DDSCache::new();
+    cache.add_new_topic(
+      topic_name,
+      TopicKind::WithKey,
+      &TypeDesc::new("Keyed".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    cache
+  }
+
+  #[test]
+  fn dds_cache_instances() {
+    let topic_name = String::from("Instances");
+    let mut cache = keyed_topic(&topic_name);
+    let mut instants = Vec::new();
+    for (sn, key_hash) in [(1, 0xA), (2, 0xB), (3, 0xA), (4, 0)].iter() {
+      let instant = CacheInstant::now();
+      cache
+        .to_topic_add_change(
+          &topic_name,
+          &instant,
+          keyed_change(*key_hash, *sn, ChangeKind::ALIVE),
+        )
+        .unwrap();
+      instants.push(instant);
+    }
+
+    // the unknown key hash is not an instance, but the change is in arrival order
+    let mut instances = cache.from_topic_get_instances(&topic_name);
+    instances.sort();
+    assert_eq!(instances, vec![0xA, 0xB]);
+    assert_eq!(cache.from_topic_get_all_changes(&topic_name).len(), 4);
+    let seqnums = |changes: Vec<(CacheInstant, CacheChange)>| -> Vec<i64> {
+      changes
+        .iter()
+        .map(|(_, cc)| i64::from(cc.sequence_number))
+        .collect()
+    };
+    assert_eq!(
+      seqnums(cache.from_topic_get_instance_changes(&topic_name, 0xA)),
+      vec![1, 3]
+    );
+    assert_eq!(
+      seqnums(cache.from_topic_get_instance_changes(&topic_name, 0xB)),
+      vec![2]
+    );
+    assert!(cache
+      .from_topic_get_instance_changes(&topic_name, 0xC)
+      .is_empty());
+
+    // Topics without key have no instances.
+    let no_key = String::from("NoKey");
+    cache.add_new_topic(
+      &no_key,
+      TopicKind::NoKey,
+      &TypeDesc::new("NoKey".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    cache
+      .to_topic_add_change(
+        &no_key,
+        &CacheInstant::now(),
+        keyed_change(0xA, 1, ChangeKind::ALIVE),
+      )
+      .unwrap();
+    assert!(cache.from_topic_get_instances(&no_key).is_empty());
+  }
+
+  #[test]
+  fn dds_cache_instance_dispose() {
+    let topic_name = String::from("Instances");
+    let cache = keyed_topic(&topic_name);
+    let first = CacheInstant::now();
+    cache
+      .to_topic_add_change(&topic_name, &first, keyed_change(0xA, 1, ChangeKind::ALIVE))
+      .unwrap();
+    let (_, latest) = cache
+      .from_topic_get_latest_instance_change(&topic_name, 0xA)
+      .unwrap();
+    assert_eq!(latest.kind, ChangeKind::ALIVE);
+
+    // a received dispose is the latest change of the instance
+    let dispose = CacheInstant::now();
+    cache
+      .to_topic_add_change(
+        &topic_name,
+        &dispose,
+        keyed_change(0xA, 2, ChangeKind::NOT_ALIVE_DISPOSED),
+      )
+      .unwrap();
+    let (instant, latest) = cache
+      .from_topic_get_latest_instance_change(&topic_name, 0xA)
+      .unwrap();
+    assert_eq!(instant, dispose);
+    assert_eq!(latest.kind, ChangeKind::NOT_ALIVE_DISPOSED);
+
+    // and so is a writer's own dispose of a change
+    cache
+      .from_topic_remove_change(&topic_name, &dispose)
+      .unwrap();
+    cache
+      .from_topic_set_change_to_not_alive_disposed(&topic_name, &first)
+      .unwrap();
+    let (instant, latest) = cache
+      .from_topic_get_latest_instance_change(&topic_name, 0xA)
+      .unwrap();
+    assert_eq!(instant, first);
+    assert_eq!(latest.kind, ChangeKind::NOT_ALIVE_DISPOSED);
+  }
+
+  #[test]
+  fn dds_cache_instance_evict() {
+    let topic_name = String::from("Instances");
+    let cache = keyed_topic(&topic_name);
+    for sn in 1..=5 {
+      for key_hash in [0xA, 0xB].iter() {
+        cache
+          .to_topic_add_change(
+            &topic_name,
+            &CacheInstant::now(),
+            keyed_change(*key_hash, sn, ChangeKind::ALIVE),
+          )
+          .unwrap();
+      }
+    }
+
+    // KeepLast 2 of instance A
+    let removed = cache
+      .from_topic_remove_instance_changes_but_keep_depth(&topic_name, 0xA, 2)
+      .unwrap();
+    let removed_seqnums: Vec<i64> = removed
+      .iter()
+      .map(|(_, cc)| i64::from(cc.sequence_number))
+      .collect();
+    assert_eq!(removed_seqnums, vec![1, 2, 3]);
+    assert_eq!(
+      cache
+        .from_topic_get_instance_changes(&topic_name, 0xA)
+        .len(),
+      2
+    );
+    assert_eq!(
+      cache
+        .from_topic_get_instance_changes(&topic_name, 0xB)
+        .len(),
+      5
+    );
+    assert_eq!(cache.from_topic_get_all_changes(&topic_name).len(), 7);
+
+    // removing the last changes of an instance removes the instance
+    for (instant, _) in removed.iter() {
+      assert!(cache.from_topic_get_change(&topic_name, instant).is_none());
+    }
+    cache
+      .from_topic_remove_instance_changes_but_keep_depth(&topic_name, 0xA, 0)
+      .unwrap();
+    assert_eq!(cache.from_topic_get_instances(&topic_name), vec![0xB]);
+    assert_eq!(
+      cache.from_topic_remove_instance_changes_but_keep_depth(&String::from("Gone"), 0xA, 0),
+      Err(CacheError::UnknownTopic("Gone".to_string()))
+    );
+  }
+
+  // Run with: cargo test --release dds_cache_instance_lookup_benchmark -- --ignored --nocapture
+  #[test]
+  #[ignore]
+  fn dds_cache_instance_lookup_benchmark() {
+    use std::time::Instant;
+
+    let topic_name = String::from("Benchmark");
+    for &changes in [1_000, 10_000, 100_000, 1_000_000].iter() {
+      let cache = keyed_topic(&topic_name);
+      // 10 changes of each instance
+      let instances = changes / 10;
+      for sn in 0..changes {
+        cache
+          .to_topic_add_change(
+            &topic_name,
+            &CacheInstant::now(),
+            keyed_change((sn % instances) as u128 + 1, sn, ChangeKind::ALIVE),
+          )
+          .unwrap();
+      }
+
+      let lookups = 1000;
+      let start = Instant::now();
+      for i in 0..lookups {
+        let key_hash = (i % instances) as u128 + 1;
+        assert_eq!(
+          cache
+            .from_topic_get_instance_changes(&topic_name, key_hash)
+            .len(),
+          10
+        );
+      }
+      let indexed = start.elapsed() / lookups as u32;
+
+      // what finding an instance took without the index
+      let start = Instant::now();
+      for i in 0..10 {
+        let key_hash = (i % instances) as u128 + 1;
+        let found = cache
+          .from_topic_get_all_changes(&topic_name)
+          .into_iter()
+          .filter(|(_, cc)| cc.key == key_hash)
+          .count();
+        assert_eq!(found, 10);
+      }
+      let scanned = start.elapsed() / 10;
+
+      println!(
+        "{:>8} changes: instance lookup {:?}, scan {:?}",
+        changes, indexed, scanned
+      );
+    }
+  }
+
+  #[test]
+  fn dds_cache_change_by_sn() {
+    let topic_name = String::from("Instances");
+    let cache = keyed_topic(&topic_name);
+    let writer_guid = GUID::new();
+    let mut instants = Vec::new();
+    for sn in 1..=4 {
+      let mut change = keyed_change(0xA, sn, ChangeKind::ALIVE);
+      change.writer_guid = writer_guid;
+      let instant = CacheInstant::now();
+      cache
+        .to_topic_add_change(&topic_name, &instant, change)
+        .unwrap();
+      instants.push(instant);
+    }
+    // A copy received by a Reader is not found, as it is not ours to resend.
+    let mut received = keyed_change(0xA, 5, ChangeKind::ALIVE);
+    received.writer_guid = writer_guid;
+    received.received_by = Some(GUID::new());
+    cache
+      .to_topic_add_change(&topic_name, &CacheInstant::now(), received)
+      .unwrap();
+
+    let found = cache
+      .from_topic_get_change_by_sn(&topic_name, writer_guid, SequenceNumber::from(3))
+      .unwrap();
+    assert_eq!(found.sequence_number, SequenceNumber::from(3));
+    assert!(cache
+      .from_topic_get_change_by_sn(&topic_name, writer_guid, SequenceNumber::from(5))
+      .is_none());
+    assert!(cache
+      .from_topic_get_change_by_sn(&topic_name, GUID::new(), SequenceNumber::from(3))
+      .is_none());
+
+    // Removed and evicted changes are no longer found.
+    cache
+      .from_topic_remove_change(&topic_name, &instants[0])
+      .unwrap();
+    assert!(cache
+      .from_topic_get_change_by_sn(&topic_name, writer_guid, SequenceNumber::from(1))
+      .is_none());
+    cache
+      .from_topic_remove_instance_changes_but_keep_depth(&topic_name, 0xA, 1)
+      .unwrap();
+    for removed in 2..=4 {
+      assert!(cache
+        .from_topic_get_change_by_sn(&topic_name, writer_guid, SequenceNumber::from(removed))
+        .is_none());
+    }
+    assert_eq!(cache.from_topic_get_all_changes(&topic_name).len(), 1);
+  }
+
+  // Run with: cargo test --release dds_cache_retransmit_benchmark -- --ignored --nocapture
+  #[test]
+  #[ignore]
+  fn dds_cache_retransmit_benchmark() {
+    use std::time::Instant;
+
+    let topic_name = String::from("Benchmark");
+    let cache = keyed_topic(&topic_name);
+    let writer_guid = GUID::new();
+    let history = 100_000;
+    for sn in 1..=history {
+      let mut change = keyed_change(1, sn, ChangeKind::ALIVE);
+      change.writer_guid = writer_guid;
+      cache
+        .to_topic_add_change(&topic_name, &CacheInstant::now(), change)
+        .unwrap();
+    }
+
+    // an AckNack asking for 100 changes spread over the history
+    let nacked: Vec<SequenceNumber> = (1..=100)
+      .map(|i| SequenceNumber::from(i * history / 100))
+      .collect();
+
+    let start = Instant::now();
+    for sn in nacked.iter() {
+      let change = cache.from_topic_get_change_by_sn(&topic_name, writer_guid, *sn);
+      assert_eq!(change.unwrap().sequence_number, *sn);
+    }
+    let indexed = start.elapsed();
+
+    // what finding the changes took without the index
+    let start = Instant::now();
+    for sn in nacked.iter() {
+      let change = cache
+        .from_topic_get_all_changes(&topic_name)
+        .into_iter()
+        .find(|(_, cc)| cc.writer_guid == writer_guid && cc.sequence_number == *sn);
+      assert!(change.is_some());
+    }
+    let scanned = start.elapsed();
+
+    println!(
+      "Resending {} of {} changes: by sequence number {:?}, scan {:?}",
+      nacked.len(),
+      history,
+      indexed,
+      scanned
+    );
+  }
+
+  #[test]
+  fn dds_cache_statistics() {
+    use crate::messages::submessages::submessage_elements::RepresentationIdentifier;
+
+    let topic_name = String::from("Instances");
+    let mut cache = keyed_topic(&topic_name);
+    let change_of_bytes = |key_hash: u128, sn: i64, bytes: usize| {
+      let mut change = CacheChange::new(
+        ChangeKind::ALIVE,
+        GUID::GUID_UNKNOWN,
+        SequenceNumber::from(sn),
+        Some(DDSData::new(SerializedPayload::new(
+          RepresentationIdentifier::CDR_LE,
+          vec![0; bytes],
+        ))),
+      );
+      change.key = key_hash;
+      change
+    };
+    let mut instants = Vec::new();
+    for (sn, key_hash, bytes) in [(1, 0xA, 100), (2, 0xB, 10), (3, 0xA, 1000)].iter() {
+      let instant = CacheInstant::now();
+      cache
+        .to_topic_add_change(
+          &topic_name,
+          &instant,
+          change_of_bytes(*key_hash, *sn, *bytes),
+        )
+        .unwrap();
+      instants.push(instant);
+    }
+    let stats = cache.statistics().topics[&topic_name];
+    assert_eq!(stats.changes, 3);
+    assert_eq!(stats.payload_bytes, 1110);
+    assert_eq!(stats.instances, 2);
+    assert_eq!(stats.oldest, Some(instants[0].timestamp()));
+    assert_eq!(stats.newest, Some(instants[2].timestamp()));
+
+    // dispose: the disposed change keeps its payload, and the dispose has none
+    cache
+      .from_topic_set_change_to_not_alive_disposed(&topic_name, &instants[1])
+      .unwrap();
+    let mut dispose = keyed_change(0xB, 4, ChangeKind::NOT_ALIVE_DISPOSED);
+    dispose.data_value = None;
+    cache
+      .to_topic_add_change(&topic_name, &CacheInstant::now(), dispose)
+      .unwrap();
+    let stats = cache.statistics().topics[&topic_name];
+    assert_eq!(
+      (stats.changes, stats.payload_bytes, stats.instances),
+      (4, 1110, 2)
+    );
+
+    // eviction
+    cache
+      .from_topic_remove_instance_changes_but_keep_depth(&topic_name, 0xA, 1)
+      .unwrap();
+    cache
+      .from_topic_remove_change(&topic_name, &instants[1])
+      .unwrap();
+    let stats = cache.statistics().topics[&topic_name];
+    assert_eq!(
+      (stats.changes, stats.payload_bytes, stats.instances),
+      (2, 1000, 2)
+    );
+    assert_eq!(stats.oldest, Some(instants[2].timestamp()));
+
+    // totals over topics, and topic removal
+    let other = String::from("Other");
+    cache.add_new_topic(
+      &other,
+      TopicKind::NoKey,
+      &TypeDesc::new("Other".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let other_instant = CacheInstant::now();
+    cache
+      .to_topic_add_change(&other, &other_instant, change_of_bytes(0, 1, 5))
+      .unwrap();
+    let statistics = cache.statistics();
+    assert_eq!(statistics.topics.len(), 2);
+    assert_eq!(statistics.total.changes, 3);
+    assert_eq!(statistics.total.payload_bytes, 1005);
+    assert_eq!(statistics.total.instances, 2);
+    assert_eq!(statistics.total.oldest, Some(instants[2].timestamp()));
+    assert_eq!(statistics.total.newest, Some(other_instant.timestamp()));
+
+    cache.remove_topic(&topic_name);
+    let statistics = cache.statistics();
+    assert!(!statistics.topics.contains_key(&topic_name));
+    assert_eq!(statistics.total, statistics.topics[&other]);
+  }
+
+  #[test]
+  fn dds_cache_topic_qos() {
+    use crate::dds::qos::{policy::History, QosPolicyBuilder};
+
+    let mut cache = DDSCache::new();
+    let topic_name = String::from("Qos");
+    let qos = QosPolicyBuilder::new()
+      .history(History::KeepLast { depth: 3 })
+      .build();
+    assert!(cache.add_new_topic(
+      &topic_name,
+      TopicKind::WithKey,
+      &TypeDesc::new("Qos".to_string()),
+      &qos
+    ));
+    assert_eq!(cache.get_topic_qos(&topic_name), Some(qos.clone()));
+
+    // The topic is already there, and keeps its QoS.
+    assert!(!cache.add_new_topic(
+      &topic_name,
+      TopicKind::WithKey,
+      &TypeDesc::new("Qos".to_string()),
+      &QosPolicies::qos_none()
+    ));
+    assert_eq!(cache.get_topic_qos(&topic_name), Some(qos.clone()));
+
+    let new_qos = QosPolicyBuilder::new()
+      .history(History::KeepLast { depth: 10 })
+      .build();
+    cache.update_topic_qos(&topic_name, &new_qos).unwrap();
+    assert_eq!(
+      cache.get_topic_qos(&topic_name).unwrap().history,
+      Some(History::KeepLast { depth: 10 })
+    );
+    assert_eq!(
+      cache.update_topic_qos(&String::from("Gone"), &new_qos),
+      Err(CacheError::UnknownTopic("Gone".to_string()))
+    );
+  }
+
+  #[test]
+  fn dds_cache_topic_cache_handle() {
+    let topic_name = String::from("Handle");
+    let mut cache = keyed_topic(&topic_name);
+    let topic_cache = cache.get_topic_cache(&topic_name).unwrap();
+    assert!(cache.get_topic_cache("Nothing").is_none());
+
+    let instant = CacheInstant::now();
+    cache
+      .to_topic_add_change(&topic_name, &instant, keyed_change(1, 1, ChangeKind::ALIVE))
+      .unwrap();
+    assert_eq!(
+      topic_cache
+        .read()
+        .unwrap()
+        .get_change(&instant)
+        .map(|cc| cc.sequence_number),
+      Some(SequenceNumber::from(1))
+    );
+
+    // The handle outlives the removal of the topic, but is no longer in use.
+    cache.remove_topic(&topic_name);
+    assert!(cache
+      .to_topic_add_change(
+        &topic_name,
+        &CacheInstant::now(),
+        keyed_change(1, 2, ChangeKind::ALIVE)
+      )
+      .is_err());
+    assert_eq!(topic_cache.read().unwrap().get_all_changes().len(), 1);
+  }
+
+  #[test]
+  fn dds_cache_topic_users() {
+    let topic_name = String::from("Users");
+    let mut cache = keyed_topic(&topic_name);
+    let writer = GUID::new();
+    let reader = GUID::new();
+    assert!(!cache.topic_has_users(&topic_name));
+    cache.add_topic_user(&topic_name, writer).unwrap();
+    cache.add_topic_user(&topic_name, reader).unwrap();
+    cache
+      .set_data_reader_progress(&topic_name, reader, CacheInstant::now())
+      .unwrap();
+    assert!(cache.topic_has_users(&topic_name));
+    assert_eq!(
+      cache.add_topic_user(&String::from("Nothing"), writer),
+      Err(CacheError::UnknownTopic("Nothing".to_string()))
+    );
+
+    assert!(!cache.remove_topic_user(&topic_name, reader));
+    assert!(cache.get_topic_cache(&topic_name).is_some());
+    // removing an unknown user changes nothing
+    assert!(!cache.remove_topic_user(&topic_name, reader));
+    assert!(cache.remove_topic_user(&topic_name, writer));
+    assert!(cache.get_topic_cache(&topic_name).is_none());
+    assert!(!cache.topic_has_users(&topic_name));
+    assert!(!cache.remove_topic_user(&topic_name, writer));
+  }
+
+  #[test]
+  fn dds_cache_topic_handles() {
+    let topic_name = String::from("Handles");
+    let mut cache = keyed_topic(&topic_name);
+    let reader = GUID::new();
+    cache.add_topic_handle(&topic_name);
+    cache.add_topic_handle(&topic_name);
+    cache.add_topic_user(&topic_name, reader).unwrap();
+
+    // the Topic handles keep the topic after its last user is gone
+    assert!(!cache.remove_topic_user(&topic_name, reader));
+    assert!(cache.get_topic_cache(&topic_name).is_some());
+    assert!(!cache.remove_topic_handle(&topic_name));
+    assert!(cache.get_topic_cache(&topic_name).is_some());
+    assert!(cache.remove_topic_handle(&topic_name));
+    assert!(cache.get_topic_cache(&topic_name).is_none());
+    assert!(!cache.remove_topic_handle(&topic_name));
+
+    // and users keep it after the last handle is gone
+    let mut cache = keyed_topic(&topic_name);
+    cache.add_topic_handle(&topic_name);
+    cache.add_topic_user(&topic_name, reader).unwrap();
+    assert!(!cache.remove_topic_handle(&topic_name));
+    assert!(cache.get_topic_cache(&topic_name).is_some());
+    assert!(cache.remove_topic_user(&topic_name, reader));
+    assert!(cache.get_topic_cache(&topic_name).is_none());
+  }
+
+  fn received_change(
+    key_hash: u128,
+    sn: i64,
+    kind: ChangeKind,
+    writer_guid: GUID,
+    received_by: GUID,
+  ) -> CacheChange {
+    let mut change = keyed_change(key_hash, sn, kind);
+    change.writer_guid = writer_guid;
+    change.received_by = Some(received_by);
+    change
+  }
+
+  #[test]
+  fn dds_cache_garbage_collection() {
+    use crate::dds::qos::{
+      policy::{History, ReaderDataLifecycle},
+      QosPolicyBuilder,
+    };
+    use crate::structure::{duration::Duration, guid::EntityId};
+
+    let topic_name = String::from("Garbage");
+    let mut cache = DDSCache::new();
+    cache.add_new_topic(
+      &topic_name,
+      TopicKind::WithKey,
+      &TypeDesc::new("Garbage".to_string()),
+      &QosPolicyBuilder::new()
+        .history(History::KeepLast { depth: 1 })
+        .reader_data_lifecycle(ReaderDataLifecycle {
+          autopurge_nowriter_samples_delay: Duration::DURATION_INFINITE,
+          autopurge_disposed_samples_delay: Duration::DURATION_ZERO,
+        })
+        .build(),
+    );
+    let local_writer = GUID::new();
+    let reader = local_writer.from_prefix(EntityId::createCustomEntityID([0, 0, 1], 0x07));
+    let other_reader = local_writer.from_prefix(EntityId::createCustomEntityID([0, 0, 2], 0x07));
+    let remote_writer = GUID::new();
+    let start = CacheInstant::now();
+    for data_reader in [reader, other_reader].iter() {
+      cache
+        .set_data_reader_progress(&topic_name, *data_reader, start)
+        .unwrap();
+    }
+
+    // Our own writer's change is left for the writer.
+    let mut own = keyed_change(0xA, 1, ChangeKind::ALIVE);
+    own.writer_guid = local_writer;
+    cache
+      .to_topic_add_change(&topic_name, &CacheInstant::now(), own)
+      .unwrap();
+    let mut received = Vec::new();
+    for (key_hash, sn) in [(0xA, 1), (0xA, 2), (0xA, 3), (0xB, 1)].iter() {
+      let instant = CacheInstant::now();
+      let change = received_change(*key_hash, *sn, ChangeKind::ALIVE, remote_writer, reader);
+      cache
+        .to_topic_add_change(&topic_name, &instant, change)
+        .unwrap();
+      received.push(instant);
+    }
+
+    // Changes from other participants are removed only when all DataReaders have taken them.
+    cache
+      .set_data_reader_progress(&topic_name, reader, received[3])
+      .unwrap();
+    assert_eq!(cache.collect_garbage(), 0);
+    cache
+      .set_data_reader_progress(&topic_name, other_reader, received[1])
+      .unwrap();
+    assert_eq!(cache.collect_garbage(), 2);
+    assert!(cache
+      .from_topic_get_change(&topic_name, &received[1])
+      .is_none());
+    assert!(cache
+      .from_topic_get_change(&topic_name, &received[2])
+      .is_some());
+    assert_eq!(cache.statistics().total.changes, 3);
+
+    // A change handed over from our own writer is only for the DataReader that received
+    // it. The latest change of an instance is kept.
+    let handed_over = CacheInstant::now();
+    let change = received_change(0xB, 2, ChangeKind::ALIVE, local_writer, other_reader);
+    cache
+      .to_topic_add_change(&topic_name, &handed_over, change)
+      .unwrap();
+    cache
+      .set_data_reader_progress(&topic_name, other_reader, handed_over)
+      .unwrap();
+    assert_eq!(cache.collect_garbage(), 1);
+    assert!(cache
+      .from_topic_get_change(&topic_name, &received[3])
+      .is_none());
+    assert!(cache
+      .from_topic_get_change(&topic_name, &handed_over)
+      .is_some());
+
+    // A disposed instance is purged after the delay, but only what has been taken.
+    let disposed = CacheInstant::now();
+    let change = received_change(
+      0xA,
+      4,
+      ChangeKind::NOT_ALIVE_DISPOSED,
+      remote_writer,
+      reader,
+    );
+    cache
+      .to_topic_add_change(&topic_name, &disposed, change)
+      .unwrap();
+    assert_eq!(cache.collect_garbage(), 1);
+    assert!(cache
+      .from_topic_get_change(&topic_name, &received[2])
+      .is_none());
+    for data_reader in [reader, other_reader].iter() {
+      cache
+        .set_data_reader_progress(&topic_name, *data_reader, disposed)
+        .unwrap();
+    }
+    assert_eq!(cache.collect_garbage(), 1);
+    assert_eq!(
+      cache
+        .from_topic_get_instance_changes(&topic_name, 0xA)
+        .len(),
+      1
+    );
+
+    cache.remove_data_reader(&topic_name, other_reader).unwrap();
+    assert_eq!(cache.collect_garbage(), 0);
+    assert!(cache
+      .from_topic_get_change_by_sn(&topic_name, local_writer, SequenceNumber::from(1))
+      .is_some());
+    assert_eq!(cache.statistics().total.changes, 2);
+    assert_eq!(
+      cache.set_data_reader_progress(&String::from("Gone"), reader, disposed),
+      Err(CacheError::UnknownTopic("Gone".to_string()))
+    );
+  }
+
+  #[test]
+  fn dds_cache_autopurge_delay_follows_clock() {
+    use crate::dds::qos::{
+      policy::{History, ReaderDataLifecycle},
+      QosPolicyBuilder,
+    };
+    use crate::structure::{
+      clock::{Clock, ManualClock, SharedClock},
+      duration::Duration,
+    };
+    use std::time::Duration as StdDuration;
+
+    let clock = Arc::new(ManualClock::new());
+    let topic_name = String::from("Purged");
+    let mut cache = DDSCache::with_clock(SharedClock::from(clock.clone()));
+    cache.add_new_topic(
+      &topic_name,
+      TopicKind::WithKey,
+      &TypeDesc::new("Purged".to_string()),
+      &QosPolicyBuilder::new()
+        .history(History::KeepLast { depth: 1 })
+        .reader_data_lifecycle(ReaderDataLifecycle {
+          autopurge_nowriter_samples_delay: Duration::DURATION_INFINITE,
+          autopurge_disposed_samples_delay: Duration::from_secs(10),
+        })
+        .build(),
+    );
+    let reader = GUID::new();
+    let remote_writer = GUID::new();
+    for (sn, kind) in [(1, ChangeKind::ALIVE), (2, ChangeKind::NOT_ALIVE_DISPOSED)].iter() {
+      let change = received_change(0xA, *sn, *kind, remote_writer, reader);
+      cache
+        .to_topic_add_change(&topic_name, &CacheInstant::at(clock.now()), change)
+        .unwrap();
+    }
+    let taken = CacheInstant::at(clock.now());
+    cache
+      .set_data_reader_progress(&topic_name, reader, taken)
+      .unwrap();
+
+    // the disposal is kept until its delay has passed
+    assert_eq!(cache.collect_garbage(), 1);
+    clock.advance(StdDuration::from_millis(9999));
+    assert_eq!(cache.collect_garbage(), 0);
+    clock.advance(StdDuration::from_millis(1));
+    assert_eq!(cache.collect_garbage(), 1);
+    assert_eq!(cache.statistics().total.changes, 0);
+  }
+
+  // A DataReader keeps up with a stream of samples. With garbage collection the cache
+  // stays the same size, instead of growing with every sample.
+  #[test]
+  fn dds_cache_garbage_collection_soak() {
+    use crate::dds::qos::{policy::History, QosPolicyBuilder};
+    use crate::messages::submessages::submessage_elements::RepresentationIdentifier;
+
+    let topic_name = String::from("Soak");
+    let mut cache = DDSCache::new();
+    cache.add_new_topic(
+      &topic_name,
+      TopicKind::WithKey,
+      &TypeDesc::new("Soak".to_string()),
+      &QosPolicyBuilder::new()
+        .history(History::KeepLast { depth: 2 })
+        .build(),
+    );
+    let remote_writer = GUID::new();
+    let reader = GUID::new();
+    cache
+      .set_data_reader_progress(&topic_name, reader, CacheInstant::now())
+      .unwrap();
+
+    let mut sn = 0;
+    let mut sizes = Vec::new();
+    for _round in 0..50 {
+      let mut latest = CacheInstant::now();
+      for key_hash in 1..=100 {
+        sn += 1;
+        let mut change = received_change(key_hash, sn, ChangeKind::ALIVE, remote_writer, reader);
+        change.data_value = Some(SerializedPayload::new(
+          RepresentationIdentifier::CDR_LE,
+          vec![0; 100],
+        ));
+        latest = CacheInstant::now();
+        cache
+          .to_topic_add_change(&topic_name, &latest, change)
+          .unwrap();
+      }
+      cache
+        .set_data_reader_progress(&topic_name, reader, latest)
+        .unwrap();
+      cache.collect_garbage();
+      let statistics = cache.statistics().total;
+      sizes.push((statistics.changes, statistics.payload_bytes));
+    }
+    info!("DDSCache size by round: {:?}", sizes);
+    assert_eq!(sizes[1], (200, 200 * 100));
+    assert!(sizes[1..].iter().all(|size| *size == sizes[1]));
+  }
 }
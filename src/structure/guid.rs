@@ -170,6 +170,11 @@ impl EntityId {
   pub fn set_kind(&mut self, entityKind: u8) {
     self.entityKind = entityKind;
   }
+
+  // Builtin entity kinds have both of the two highest bits set (RTPS spec 9.3.1.2).
+  pub fn is_builtin(self) -> bool {
+    self.entityKind & 0xC0 == 0xC0
+  }
 }
 
 impl Default for EntityId {
@@ -253,7 +258,11 @@ impl GUID {
   }
 }
 
-impl Key for GUID {}
+impl Key for GUID {
+  fn may_exceed_16_bytes() -> bool {
+    false
+  }
+}
 
 #[derive(Serialize, Deserialize)]
 pub(crate) struct GUIDData {
@@ -309,6 +318,14 @@ mod tests {
     assert_eq!(e6, entity6);
   }
 
+  #[test]
+  fn entity_id_is_builtin() {
+    assert!(EntityId::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_WRITER.is_builtin());
+    assert!(EntityId::ENTITYID_SPDP_BUILTIN_PARTICIPANT_READER.is_builtin());
+    assert!(!EntityId::createCustomEntityID([1, 2, 3], 0x02).is_builtin());
+    assert!(!EntityId::createCustomEntityID([1, 2, 3], 0x07).is_builtin());
+  }
+
   #[test]
   fn minimum_bytes_needed() {
     assert_eq!(
@@ -2,7 +2,10 @@ use std::io::Cursor;
 use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
 use enumflags2::BitFlags;
 
-use super::cache_change::ChangeKind;
+use super::{
+  cache_change::ChangeKind,
+  guid::{EntityId, GuidPrefix, GUID},
+};
 use crate::{
   messages::submessages::submessage_elements::RepresentationIdentifier,
   serialization::{
@@ -72,6 +75,12 @@ pub struct KeyHash {
   key: u128,
 }
 
+impl From<u128> for KeyHash {
+  fn from(key: u128) -> KeyHash {
+    KeyHash { key }
+  }
+}
+
 impl KeyHash {
   pub fn empty() -> KeyHash {
     KeyHash { key: 0 }
@@ -81,6 +90,23 @@ impl KeyHash {
     self.key
   }
 
+  /// KeyHash is an array of 16 octets on the wire, so it does not depend on endianness.
+  /// The value is the octets as little-endian `u128`, like in `Key::into_hash_key`.
+  pub fn from_octets(bytes: &[u8]) -> Result<KeyHash, crate::serialization::error::Error> {
+    if bytes.len() < 16 {
+      return Err(crate::serialization::error::Error::Eof);
+    }
+    let mut octets: [u8; 16] = [0; 16];
+    octets.copy_from_slice(&bytes[..16]);
+    Ok(KeyHash {
+      key: u128::from_le_bytes(octets),
+    })
+  }
+
+  pub fn to_octets(&self) -> [u8; 16] {
+    self.key.to_le_bytes()
+  }
+
   pub fn into_cdr_bytes<BO: ByteOrder>(
     &self,
   ) -> Result<Vec<u8>, crate::serialization::error::Error> {
@@ -105,6 +131,54 @@ impl KeyHash {
   }
 }
 
+/// Identifies a sample by the GUID of its writer and the sequence number the writer gave
+/// it. Sent as the related sample identity inline QoS to tell which sample, e.g. a
+/// request, another sample replies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SampleIdentity {
+  pub writer_guid: GUID,
+  pub sequence_number: i64,
+}
+
+impl SampleIdentity {
+  pub const OCTETS: usize = 24;
+
+  /// GUID octets followed by the sequence number as high (i32) and low (u32) parts.
+  pub fn to_octets<BO: ByteOrder>(&self) -> [u8; SampleIdentity::OCTETS] {
+    let mut octets = [0; SampleIdentity::OCTETS];
+    octets[..12].copy_from_slice(&self.writer_guid.guidPrefix.entityKey);
+    octets[12..15].copy_from_slice(&self.writer_guid.entityId.entityKey);
+    octets[15] = self.writer_guid.entityId.entityKind;
+    BO::write_i32(&mut octets[16..20], (self.sequence_number >> 32) as i32);
+    BO::write_u32(&mut octets[20..24], self.sequence_number as u32);
+    octets
+  }
+
+  pub fn from_octets<BO: ByteOrder>(
+    bytes: &[u8],
+  ) -> Result<SampleIdentity, crate::serialization::error::Error> {
+    if bytes.len() < SampleIdentity::OCTETS {
+      return Err(crate::serialization::error::Error::Eof);
+    }
+    let mut prefix = [0; 12];
+    prefix.copy_from_slice(&bytes[..12]);
+    let mut entity_key = [0; 3];
+    entity_key.copy_from_slice(&bytes[12..15]);
+    let high = i64::from(BO::read_i32(&bytes[16..20]));
+    let low = i64::from(BO::read_u32(&bytes[20..24]));
+    Ok(SampleIdentity {
+      writer_guid: GUID::new_with_prefix_and_id(
+        GuidPrefix { entityKey: prefix },
+        EntityId {
+          entityKey: entity_key,
+          entityKind: bytes[15],
+        },
+      ),
+      sequence_number: (high << 32) | low,
+    })
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -176,4 +250,48 @@ mod tests {
     let key_hash = KeyHash::from_cdr_bytes(&bytes, RepresentationIdentifier::CDR_BE).unwrap();
     assert_eq!(KeyHash { key: 1 }, key_hash);
   }
+
+  #[test]
+  fn inline_qos_key_hash_octets() {
+    let octets: Vec<u8> = vec![
+      0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+      0x02,
+    ];
+    let key_hash = KeyHash::from_octets(&octets).unwrap();
+    assert_eq!(key_hash.to_octets().to_vec(), octets);
+    assert!(KeyHash::from_octets(&octets[..8]).is_err());
+  }
+
+  #[test]
+  fn inline_qos_sample_identity() {
+    let identity = SampleIdentity {
+      writer_guid: GUID::new_with_prefix_and_id(
+        GuidPrefix {
+          entityKey: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+        },
+        EntityId {
+          entityKey: [13, 14, 15],
+          entityKind: 0x03,
+        },
+      ),
+      sequence_number: (2 << 32) + 5,
+    };
+    let le = identity.to_octets::<LittleEndian>();
+    assert_eq!(
+      le[..16],
+      [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 3]
+    );
+    assert_eq!(le[16..], [2, 0, 0, 0, 5, 0, 0, 0]);
+    let be = identity.to_octets::<BigEndian>();
+    assert_eq!(be[16..], [0, 0, 0, 2, 0, 0, 0, 5]);
+    assert_eq!(
+      SampleIdentity::from_octets::<LittleEndian>(&le).unwrap(),
+      identity
+    );
+    assert_eq!(
+      SampleIdentity::from_octets::<BigEndian>(&be).unwrap(),
+      identity
+    );
+    assert!(SampleIdentity::from_octets::<BigEndian>(&be[..20]).is_err());
+  }
 }
@@ -14,6 +14,15 @@ impl LocatorKind {
   pub const LOCATOR_KIND_RESERVED: LocatorKind = LocatorKind { value: 0 };
   pub const LOCATOR_KIND_UDPv4: LocatorKind = LocatorKind { value: 1 };
   pub const LOCATOR_KIND_UDPv6: LocatorKind = LocatorKind { value: 2 };
+  // Vendor specific: a shared memory segment of an endpoint on the same host. Other
+  // implementations skip locators of kinds they do not know.
+  pub const LOCATOR_KIND_SHM: LocatorKind = LocatorKind { value: 0x0100_5253 };
+  // Vendor specific: RTPS messages over a TCP connection, framed with their length.
+  pub const LOCATOR_KIND_TCPv4: LocatorKind = LocatorKind { value: 0x0100_5443 };
+
+  pub fn is_udp(self) -> bool {
+    self == LocatorKind::LOCATOR_KIND_UDPv4 || self == LocatorKind::LOCATOR_KIND_UDPv6
+  }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
@@ -48,6 +57,14 @@ impl Default for Locator {
 
 impl From<SocketAddr> for Locator {
   fn from(socket_address: SocketAddr) -> Self {
+    // IPv4 peers of a dual-stack socket appear as IPv4-mapped IPv6 addresses
+    if let IpAddr::V6(ip6) = socket_address.ip() {
+      let s = ip6.segments();
+      if s[..5] == [0; 5] && s[5] == 0xffff {
+        let ip4 = Ipv4Addr::new((s[6] >> 8) as u8, s[6] as u8, (s[7] >> 8) as u8, s[7] as u8);
+        return Locator::from(SocketAddr::new(IpAddr::V4(ip4), socket_address.port()));
+      }
+    }
     Locator {
       kind: if socket_address.ip().is_unspecified() {
         LocatorKind::LOCATOR_KIND_INVALID
@@ -236,6 +253,18 @@ mod tests {
       )
   });
 
+  #[test]
+  fn locator_from_ipv4_mapped_address() {
+    assert_eq!(
+      Locator::from("[::ffff:127.0.0.1]:7410".parse::<SocketAddr>().unwrap()),
+      Locator::from("127.0.0.1:7410".parse::<SocketAddr>().unwrap())
+    );
+    assert_eq!(
+      Locator::from("[::1]:7410".parse::<SocketAddr>().unwrap()).kind,
+      LocatorKind::LOCATOR_KIND_UDPv6
+    );
+  }
+
   serialization_test!( type = Locator,
       {
           locator_invalid,
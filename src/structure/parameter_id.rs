@@ -61,6 +61,28 @@ impl ParameterId {
   pub const PID_ENTITY_NAME: ParameterId = ParameterId { value: 0x0062 };
   pub const PID_KEY_HASH: ParameterId = ParameterId { value: 0x0070 };
   pub const PID_STATUS_INFO: ParameterId = ParameterId { value: 0x0071 };
+  pub const PID_RELATED_SAMPLE_IDENTITY: ParameterId = ParameterId { value: 0x0083 };
+  // Vendor specific id used by RTI Connext and Cyclone DDS before 0x0083 was standardized
+  pub const PID_CUSTOM_RELATED_SAMPLE_IDENTITY: ParameterId = ParameterId { value: 0x800f };
+  pub const PID_DOMAIN_TAG: ParameterId = ParameterId { value: 0x4014 };
+
+  // RTPS spec 9.6.2.2.1: each vendor defines the ids with this bit set for itself,
+  // so they mean what the vendor of the message defines.
+  pub fn is_vendor_specific(&self) -> bool {
+    self.value & 0x8000 != 0
+  }
+}
+
+impl From<u16> for ParameterId {
+  fn from(value: u16) -> ParameterId {
+    ParameterId { value }
+  }
+}
+
+impl From<ParameterId> for u16 {
+  fn from(parameter_id: ParameterId) -> u16 {
+    parameter_id.value
+  }
 }
 
 #[cfg(test)]
@@ -379,5 +401,11 @@ mod tests {
       ParameterId::PID_STATUS_INFO,
       le = [0x71, 0x00],
       be = [0x00, 0x71]
+  },
+  {
+      pid_domain_tag,
+      ParameterId::PID_DOMAIN_TAG,
+      le = [0x14, 0x40],
+      be = [0x40, 0x14]
   });
 }
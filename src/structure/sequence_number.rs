@@ -22,6 +22,12 @@ impl SequenceNumber {
       SequenceNumber::from(0)
     }
   }
+
+  /// Addition that stops at the largest sequence number instead of overflowing.
+  /// Sequence numbers from the wire can be anything.
+  pub fn saturating_add(self, rhs: Self) -> SequenceNumber {
+    SequenceNumber(self.0.saturating_add(rhs.0))
+  }
 }
 
 impl From<i64> for SequenceNumber {
@@ -73,6 +79,21 @@ impl Default for SequenceNumber {
 
 pub type SequenceNumberSet = RangedBitSet<SequenceNumber>;
 
+impl SequenceNumberSet {
+  /// A set is valid if its base is at least 1 and it covers at most 256 sequence
+  /// numbers from it (RTPS spec 9.4.2.6). The sequence numbers must also be
+  /// representable, which a base close to the maximum may not allow.
+  pub fn is_valid(&self) -> bool {
+    if self.base < SequenceNumber::from(1) {
+      return false;
+    }
+    match self.set.iter().last() {
+      None => true,
+      Some(offset) => offset < 256 && self.base.0.checked_add(offset as i64).is_some(),
+    }
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -82,6 +103,35 @@ mod tests {
     assert_eq!(SequenceNumber::from(1), SequenceNumber::default());
   }
 
+  #[test]
+  fn sequence_number_saturating_add() {
+    let max = SequenceNumber::from(std::i64::MAX);
+    assert_eq!(max.saturating_add(SequenceNumber::from(1)), max);
+    assert_eq!(
+      SequenceNumber::from(1).saturating_add(SequenceNumber::from(2)),
+      SequenceNumber::from(3)
+    );
+  }
+
+  #[test]
+  fn sequence_number_set_validity() {
+    assert!(SequenceNumberSet::new(SequenceNumber::from(1)).is_valid());
+    assert!(!SequenceNumberSet::new(SequenceNumber::from(0)).is_valid());
+    assert!(!SequenceNumberSet::new(SequenceNumber::SEQUENCENUMBER_UNKNOWN).is_valid());
+
+    let mut set = SequenceNumberSet::new(SequenceNumber::from(10));
+    set.set.insert(255);
+    assert!(set.is_valid());
+    // as received with a numBits over 256
+    set.set.insert(256);
+    assert!(!set.is_valid());
+
+    let mut set = SequenceNumberSet::new(SequenceNumber::from(std::i64::MAX));
+    assert!(set.is_valid());
+    set.set.insert(1);
+    assert!(!set.is_valid());
+  }
+
   serialization_test!( type = SequenceNumber,
   {
       sequence_number_default,
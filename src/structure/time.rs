@@ -1,6 +1,7 @@
 use speedy::{Readable, Writable};
 use serde::{Serialize, Deserialize};
 use std::ops::Sub;
+use std::time::{SystemTime, UNIX_EPOCH};
 use chrono;
 
 use super::duration::Duration;
@@ -72,6 +73,14 @@ impl Timestamp {
   }
 }
 
+impl From<SystemTime> for Timestamp {
+  fn from(time: SystemTime) -> Timestamp {
+    // times before the epoch are not representable
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Timestamp::from_nanos(since_epoch.as_nanos() as u64)
+  }
+}
+
 impl Sub for Timestamp {
   type Output = Duration;
 
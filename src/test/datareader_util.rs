@@ -2,7 +2,7 @@ use serde::de::DeserializeOwned;
 use mio_extras::channel as mio_channel;
 
 use crate::{
-  dds::{With_Key_DataReader as DataReader},
+  dds::{WithKeyDataReader as DataReader},
   dds::error::StatusChange,
   dds::traits::Keyed,
   serialization::DeserializerAdapter,
@@ -5,6 +5,7 @@ use serde::Deserialize;
 #[allow(unused_imports)] // since this is testing code only
 use crate::{
   dds::traits::{
+    dds_type::DdsType,
     key::{Key, Keyed},
     //datasample_trait::DataSampleTrait,
   },
@@ -37,3 +38,9 @@ impl Keyed for RandomData {
     self.a
   }
 }
+
+impl DdsType for RandomData {
+  fn type_name() -> &'static str {
+    "test::RandomData"
+  }
+}
@@ -114,26 +114,27 @@ use std::{net::SocketAddr, time::Duration as StdDuration};
 use serde::Serialize;
 use byteorder::LittleEndian;
 use enumflags2::BitFlags;
+use bytes::Bytes;
 use crate::messages::submessages::submessages::*;
 
 pub(crate) fn spdp_participant_msg() -> Message {
   let data = spdp_participant_data_raw();
 
-  let rtpsmsg = Message::read_from_buffer(&data).unwrap();
+  let rtpsmsg = Message::read_from_buffer(&Bytes::copy_from_slice(&data)).unwrap();
   rtpsmsg
 }
 
 pub(crate) fn spdp_subscription_msg() -> Message {
   let data = spdp_subscription_data_raw();
 
-  let rtpsmsg = Message::read_from_buffer(&data).unwrap();
+  let rtpsmsg = Message::read_from_buffer(&Bytes::copy_from_slice(&data)).unwrap();
   rtpsmsg
 }
 
 pub(crate) fn spdp_publication_msg() -> Message {
   let data = spdp_publication_data_raw();
 
-  let rtpsmsg = Message::read_from_buffer(&data).unwrap();
+  let rtpsmsg = Message::read_from_buffer(&Bytes::copy_from_slice(&data)).unwrap();
   rtpsmsg
 }
 
@@ -161,7 +162,7 @@ pub(crate) fn spdp_participant_msg_mod(port: u16) -> Message {
           data =
             to_bytes::<SPDPDiscoveredParticipantData, byteorder::LittleEndian>(&participant_data)
               .unwrap();
-          d.serialized_payload.as_mut().unwrap().value = data.clone();
+          d.serialized_payload.as_mut().unwrap().value = data.clone().into();
           submsglen =
             submsglen + d.serialized_payload.as_ref().unwrap().value.len() as u16 - datalen;
         }
@@ -178,7 +179,7 @@ pub(crate) fn spdp_participant_msg_mod(port: u16) -> Message {
 pub(crate) fn spdp_participant_data() -> Option<SPDPDiscoveredParticipantData> {
   let data = spdp_participant_data_raw();
 
-  let rtpsmsg = Message::read_from_buffer(&data).unwrap();
+  let rtpsmsg = Message::read_from_buffer(&Bytes::copy_from_slice(&data)).unwrap();
   let submsgs = rtpsmsg.submessages();
 
   for submsg in submsgs.iter() {
@@ -276,6 +277,7 @@ pub(crate) fn publication_builtin_topic_data() -> Option<PublicationBuiltinTopic
     participant_key: Some(GUID::new()),
     topic_name: Some("rand topic namm".to_string()),
     type_name: Some("RandomData".to_string()),
+    entity_name: Some("rand writer".to_string()),
     durability: Some(Durability::Volatile),
     deadline: Some(Deadline(Duration::from_secs(30))),
     latency_budget: Some(LatencyBudget {
@@ -365,7 +367,7 @@ pub(crate) fn create_rtps_data_message<D: Serialize>(
   let serialized_payload = SerializedPayload {
     representation_identifier: u16::from(RepresentationIdentifier::PL_CDR_LE),
     representation_options: [0; 2],
-    value: tdata.clone(),
+    value: tdata.clone().into(),
   };
   let data_message = Data {
     reader_id,